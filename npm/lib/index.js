@@ -0,0 +1,53 @@
+/**
+ * VaultX Core Loader
+ *
+ * Exposes the vault API (`Vault`) from the native addon when one was built
+ * for this platform, and from the WASM module otherwise. Both offer the
+ * same methods: create, create_with_preset, open, open_with_progress, save,
+ * init_project, add_secret, get_secret, remove_secret, list_projects,
+ * list_secrets, the SSH identity and server methods, and audit.
+ *
+ * Set VX_CORE_BACKEND to "native" or "wasm" to force one.
+ */
+
+const path = require('path');
+
+const NATIVE_DIR = path.join(__dirname, '..', 'native');
+const WASM_MODULE = path.join(__dirname, '..', 'wasm', 'vx_core.js');
+
+function loadNative() {
+  const addon = path.join(NATIVE_DIR, `vx-node.${process.platform}-${process.arch}.node`);
+  const native = require(addon);
+  return { backend: 'native', Vault: native.Vault };
+}
+
+function loadWasm() {
+  const wasm = require(WASM_MODULE);
+  return { backend: 'wasm', Vault: wasm.WasmVault };
+}
+
+function load() {
+  const forced = process.env.VX_CORE_BACKEND;
+
+  switch (forced) {
+    case 'native':
+      return loadNative();
+    case 'wasm':
+      return loadWasm();
+    case undefined:
+    case '':
+      break;
+    default:
+      throw new Error(`Unknown VX_CORE_BACKEND '${forced}' (expected 'native' or 'wasm')`);
+  }
+
+  // Prefer the native addon; fall back to WASM when there is none for this
+  // platform or it fails to load (e.g. an incompatible libc)
+  try {
+    return loadNative();
+  } catch (error) {
+    return loadWasm();
+  }
+}
+
+module.exports = load();
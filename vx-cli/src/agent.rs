@@ -0,0 +1,268 @@
+//! Hands an SSH identity to `ssh` without writing it to disk.
+//!
+//! `vx ssh` and `vx scp` start a `KeyAgent` for the decrypted identity and
+//! let it add the options pointing `ssh` at it. On Unix it is an SSH agent
+//! serving the key on a socket in a private directory, answered by
+//! `vx_core::ssh_agent`; `ssh` reaches it through `IdentityAgent`, so the
+//! user's own agent is not consulted for the session. Elsewhere the key is
+//! written to a `ScratchFile` and passed with `-i`.
+//!
+//! Security-key identities cannot be served by an agent, since only the
+//! token can sign: `IdentityKey` hands their key handle file to `ssh`
+//! instead, which asks the token for a touch on every login. For
+//! identities on a PKCS#11 token, `ssh` loads the token's module itself
+//! and is told which of its keys to use.
+//!
+//! # Security Notes
+//! - On Unix the private key only exists in this process's memory
+//! - The socket is 0600 and connections from other users are dropped
+//! - The agent stops, and its copy of the key is wiped, when dropped
+
+use crate::error::CliError;
+use crate::scratch::ScratchFile;
+use std::process::Command;
+use vx_core::ssh;
+
+#[cfg(unix)]
+pub use unix::KeyAgent;
+
+#[cfg(not(unix))]
+pub use fallback::KeyAgent;
+
+/// An identity's key made available to `ssh` for the lifetime of a command.
+pub enum IdentityKey {
+    /// An ed25519 seed, served by a `KeyAgent`
+    Agent(KeyAgent),
+    /// The key handle file of a security-key identity
+    SecurityKey(ScratchFile),
+    /// A key on a PKCS#11 token, chosen by its public key
+    Token {
+        module: String,
+        public_key: ScratchFile,
+    },
+}
+
+impl IdentityKey {
+    /// Starts offering the identity with `public_key` and the stored
+    /// `private_key`: its seed, or its key handle file for a security key.
+    pub fn start(public_key: &str, private_key: &[u8]) -> Result<Self, CliError> {
+        if ssh::is_security_key(public_key) {
+            Ok(Self::SecurityKey(ScratchFile::create(
+                "id_vx_sk",
+                private_key,
+            )?))
+        } else {
+            Ok(Self::Agent(KeyAgent::start(private_key)?))
+        }
+    }
+
+    /// Offers the key with `public_key` on the PKCS#11 token `module` loads.
+    pub fn token(module: &str, public_key: &str) -> Result<Self, CliError> {
+        Ok(Self::Token {
+            module: module.to_string(),
+            public_key: ScratchFile::create(
+                "id_vx_token.pub",
+                format!("{}\n", public_key).as_bytes(),
+            )?,
+        })
+    }
+
+    /// Points `cmd`, an `ssh` or `scp` command, at the key. Adds options,
+    /// so call it before the destination is added.
+    pub fn configure(&self, cmd: &mut Command) {
+        match self {
+            Self::Agent(agent) => agent.configure(cmd),
+            Self::SecurityKey(key_handle) => {
+                cmd.arg("-o")
+                    .arg("IdentityAgent=none")
+                    .arg("-o")
+                    .arg("IdentitiesOnly=yes")
+                    .arg("-i")
+                    .arg(key_handle.path());
+            }
+            Self::Token { module, public_key } => {
+                cmd.arg("-I")
+                    .arg(module)
+                    .arg("-o")
+                    .arg("IdentityAgent=none")
+                    .arg("-o")
+                    .arg("IdentitiesOnly=yes")
+                    .arg("-i")
+                    .arg(public_key.path());
+            }
+        }
+    }
+}
+
+fn invalid_key(e: vx_core::SshError) -> CliError {
+    CliError::SshError(format!("Invalid key format: {}", e))
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use crate::{daemon, permissions, scratch};
+    use std::io::{self, Read, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread::{self, JoinHandle};
+    use tempfile::TempDir;
+    use vx_core::ssh_agent::{self, Agent};
+
+    /// Socket file name inside the agent's private directory
+    const SOCKET_FILE: &str = "agent.sock";
+
+    /// An SSH agent holding identities for the lifetime of a command.
+    pub struct KeyAgent {
+        socket: PathBuf,
+        stop: Arc<AtomicBool>,
+        thread: Option<JoinHandle<()>>,
+        // Removed after the listener has stopped
+        _dir: TempDir,
+    }
+
+    impl KeyAgent {
+        /// Starts serving `private_key`, the 32-byte ed25519 seed.
+        pub fn start(private_key: &[u8]) -> Result<Self, CliError> {
+            Self::start_with_keys(&[private_key])
+        }
+
+        /// Starts serving several seeds, e.g. the identities of both
+        /// servers in a copy between them.
+        pub fn start_with_keys(private_keys: &[&[u8]]) -> Result<Self, CliError> {
+            let agent = Arc::new(Agent::with_keys(private_keys).map_err(invalid_key)?);
+            let dir = scratch::private_dir()?;
+            let socket = dir.path().join(SOCKET_FILE);
+            let listener = UnixListener::bind(&socket)?;
+            permissions::restrict_file(&socket)?;
+            tracing::debug!(socket = %socket.display(), "started key agent");
+
+            let stop = Arc::new(AtomicBool::new(false));
+            let thread = {
+                let stop = Arc::clone(&stop);
+                thread::spawn(move || serve(listener, agent, stop))
+            };
+
+            Ok(Self {
+                socket,
+                stop,
+                thread: Some(thread),
+                _dir: dir,
+            })
+        }
+
+        /// Points `cmd`, an `ssh` or `scp` command, at the agent. Adds
+        /// options, so call it before the destination is added.
+        pub fn configure(&self, cmd: &mut Command) {
+            cmd.env("SSH_AUTH_SOCK", &self.socket)
+                .arg("-o")
+                .arg("IdentityAgent=SSH_AUTH_SOCK");
+        }
+    }
+
+    impl Drop for KeyAgent {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::SeqCst);
+            // Wakes the listener so it sees the flag
+            let _ = UnixStream::connect(&self.socket);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    /// Accepts connections from the current user until `stop` is set,
+    /// answering each on its own thread.
+    fn serve(listener: UnixListener, agent: Arc<Agent>, stop: Arc<AtomicBool>) {
+        let uid = daemon::current_uid();
+        for stream in listener.incoming() {
+            if stop.load(Ordering::SeqCst) {
+                break;
+            }
+            let Ok(stream) = stream else { continue };
+            if daemon::peer_uid(&stream).ok() != Some(uid) {
+                continue;
+            }
+
+            let agent = Arc::clone(&agent);
+            thread::spawn(move || {
+                if let Err(e) = answer(stream, &agent) {
+                    tracing::debug!(error = %e, "key agent connection failed");
+                }
+            });
+        }
+    }
+
+    /// Answers length-prefixed requests on `stream` until it is closed.
+    fn answer(mut stream: UnixStream, agent: &Agent) -> io::Result<()> {
+        loop {
+            let mut len = [0u8; 4];
+            match stream.read_exact(&mut len) {
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                result => result?,
+            }
+            let len = u32::from_be_bytes(len) as usize;
+            if len > ssh_agent::MAX_MESSAGE_LEN {
+                return Err(io::ErrorKind::InvalidData.into());
+            }
+
+            let mut message = vec![0u8; len];
+            stream.read_exact(&mut message)?;
+            let reply = agent.handle_message(&message);
+            tracing::trace!(
+                request = message.first(),
+                reply = reply[0],
+                "answered agent request"
+            );
+
+            stream.write_all(&(reply.len() as u32).to_be_bytes())?;
+            stream.write_all(&reply)?;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod fallback {
+    use super::*;
+
+    /// Identities written to scratch files for `ssh -i`.
+    pub struct KeyAgent {
+        key_files: Vec<ScratchFile>,
+    }
+
+    impl KeyAgent {
+        /// Writes `private_key`, the 32-byte ed25519 seed, as an OpenSSH
+        /// private key file.
+        pub fn start(private_key: &[u8]) -> Result<Self, CliError> {
+            Self::start_with_keys(&[private_key])
+        }
+
+        /// Writes several seeds, each to its own key file.
+        pub fn start_with_keys(private_keys: &[&[u8]]) -> Result<Self, CliError> {
+            let key_files = private_keys
+                .iter()
+                .map(|private_key| {
+                    let public_key = ssh::reconstruct_signing_key(private_key)
+                        .map_err(invalid_key)?
+                        .verifying_key();
+                    let pem = ssh::format_private_key(private_key, public_key.as_bytes())
+                        .map_err(|e| {
+                            CliError::SshError(format!("Failed to format private key: {}", e))
+                        })?;
+                    ScratchFile::create("id_vx", pem.as_bytes())
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Self { key_files })
+        }
+
+        /// Passes the key files to `cmd`, an `ssh` or `scp` command. Adds
+        /// options, so call it before the destination is added.
+        pub fn configure(&self, cmd: &mut Command) {
+            for key_file in &self.key_files {
+                cmd.arg("-i").arg(key_file.path());
+            }
+        }
+    }
+}
@@ -1,53 +1,49 @@
 //! Add a secret to a project.
 
+use super::qualified_key;
+use crate::config;
 use crate::error::CliError;
 use crate::input;
-use crate::session;
 use crate::storage;
-use vx_core::{ttl, Vault, KEY_SIZE};
+use vx_core::{ttl, Operation, SecretBytes, SecretRef, Vault, KEY_SIZE};
 
 /// Executes the add command.
+/// Secrets added without `ttl_str` get the config file's `default_ttl`,
+/// except templates, which never expire, and certificates, which expire
+/// with the certificate.
 pub fn execute(
     project: &str,
     key: Option<&str>,
     file: Option<String>,
-    env: Option<String>,
+    from_env: Option<String>,
+    env: Option<&str>,
     ttl_str: Option<String>,
+    kind: ValueKind,
 ) -> Result<(), CliError> {
     // Load vault with encryption key
-    let (mut vault, encryption_key, password_bytes) = if let Some(cached) = session::get_cached_password()? {
-        match storage::load_vault_with_key(&cached) {
-            Ok((v, k)) => (v, k, cached),
-            Err(_) => {
-                let _ = session::clear_cached_password();
-                let p = input::read_password("Enter master password: ")?;
-                let (v, k) = storage::load_vault_with_key(p.as_bytes())?;
-                (v, k, p.into_bytes())
-            }
-        }
-    } else {
-         let p = input::read_password("Enter master password: ")?;
-         let (v, k) = storage::load_vault_with_key(p.as_bytes())?;
-         (v, k, p.into_bytes())
-    };
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    storage::unlock_project(&mut vault, project, &encryption_key)?;
 
     // Parse TTL if provided
     let ttl_seconds = if let Some(ttl) = ttl_str {
         Some(ttl::parse_ttl(&ttl).map_err(|e| CliError::InvalidTtl(e.to_string()))?)
-    } else {
+    } else if matches!(kind, ValueKind::Template | ValueKind::Certificate) {
         None
+    } else {
+        config::get().default_ttl
     };
+    let options = ValueOptions { ttl_seconds, kind };
 
     if let Some(k) = key {
         // Single add mode
         add_secret_interactive(
             &mut vault,
             project,
-            k,
-            file,
             env,
+            k,
+            || input::read_secret(file.as_deref(), from_env.as_deref()),
             &encryption_key,
-            ttl_seconds,
+            options,
         )?;
     } else {
         // Interactive mode
@@ -62,11 +58,11 @@ pub fn execute(
             match add_secret_interactive(
                 &mut vault,
                 project,
+                env,
                 &key_input,
-                None,
-                None,
+                || input::read_secret(None, None),
                 &encryption_key,
-                ttl_seconds,
+                options,
             ) {
                 Ok(_) => {}
                 Err(e) => eprintln!("Error adding secret: {}", e),
@@ -75,46 +71,120 @@ pub fn execute(
     }
 
     // Save vault
-    storage::save_vault(&vault, &password_bytes)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
 
     Ok(())
 }
 
+/// What an added value holds
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    /// Any value, stored as is
+    Plain,
+    /// A template rendered on read
+    Template,
+    /// A JSON document
+    Json,
+    /// An X.509 certificate, expiring at its notAfter
+    Certificate,
+}
+
+/// How added values are stored
+#[derive(Clone, Copy)]
+struct ValueOptions {
+    ttl_seconds: Option<u64>,
+    kind: ValueKind,
+}
+
 fn add_secret_interactive(
     vault: &mut Vault,
     project: &str,
+    env: Option<&str>,
     key: &str,
-    file: Option<String>,
-    env: Option<String>,
+    read_value: impl FnOnce() -> Result<SecretBytes, CliError>,
     encryption_key: &[u8; KEY_SIZE],
-    ttl_seconds: Option<u64>,
+    options: ValueOptions,
 ) -> Result<(), CliError> {
     // Check if secret already exists
     if vault
         .projects
         .get(project)
-        .map(|p| p.secrets.contains_key(key))
+        .and_then(|p| p.secrets_in(env).ok())
+        .map(|secrets| secrets.contains_key(key))
         .unwrap_or(false)
+        && !input::confirm(&format!("Secret '{}' already exists. Overwrite?", key))?
     {
-        if !input::confirm(&format!("Secret '{}' already exists. Overwrite?", key))? {
-            println!("Skipped.");
-            return Ok(());
-        }
+        println!("Skipped.");
+        return Ok(());
     }
 
     // Read secret value
-    let secret_value = input::read_secret(file.as_deref(), env.as_deref())?;
+    let secret_value = read_value()?;
 
     // Add secret
-    vault.add_secret(project, key, &secret_value, encryption_key, ttl_seconds)?;
+    let certificate =
+        vault.track_write(Operation::Add, SecretRef::new(project, env, key), |vault| {
+            match options.kind {
+                ValueKind::Plain => vault
+                    .add_secret_in(
+                        project,
+                        env,
+                        key,
+                        &secret_value,
+                        encryption_key,
+                        options.ttl_seconds,
+                    )
+                    .map(|_| None),
+                ValueKind::Template => vault
+                    .add_template_in(project, env, key, &secret_value, encryption_key)
+                    .map(|_| None),
+                ValueKind::Json => vault
+                    .add_json_in(
+                        project,
+                        env,
+                        key,
+                        &secret_value,
+                        encryption_key,
+                        options.ttl_seconds,
+                    )
+                    .map(|_| None),
+                ValueKind::Certificate => vault
+                    .add_certificate_in(project, env, key, &secret_value, encryption_key)
+                    .map(Some),
+            }
+        })?;
+    vault.record(Operation::Add, Some(project), Some(&qualified_key(env, key)));
 
-    if let Some(ttl) = ttl_seconds {
+    let location = match env {
+        Some(env) => format!("project '{}' ({})", project, env),
+        None => format!("project '{}'", project),
+    };
+    let what = match options.kind {
+        ValueKind::Json => "JSON document",
+        _ => "Secret",
+    };
+    if let Some(certificate) = certificate {
+        let subject = certificate
+            .common_name
+            .map(|name| format!(" for {}", name))
+            .unwrap_or_default();
+        println!(
+            "Certificate '{}'{} added to {} (expires {}, {}).",
+            key,
+            subject,
+            location,
+            ttl::format_timestamp(certificate.not_after),
+            ttl::format_until(certificate.not_after, ttl::current_timestamp())
+        );
+    } else if options.kind == ValueKind::Template {
+        println!("Template '{}' added to {}.", key, location);
+    } else if let Some(ttl) = options.ttl_seconds {
         println!(
-            "Secret '{}' added to project '{}' (expires in {} seconds).",
-            key, project, ttl
+            "{} '{}' added to {} (expires in {} seconds).",
+            what, key, location, ttl
         );
     } else {
-        println!("Secret '{}' added to project '{}'.", key, project);
+        println!("{} '{}' added to {}.", what, key, location);
     }
 
     Ok(())
@@ -0,0 +1,47 @@
+//! Make a secret an alias of another project's secret.
+
+use super::qualified_key;
+use crate::error::CliError;
+use crate::input;
+use crate::storage;
+use vx_core::{Operation, SecretRef};
+
+/// Executes the alias command.
+/// Creates `project/key` (in `env`) referring to the target secret, so
+/// reading it returns the target's current value.
+pub fn execute(
+    project: &str,
+    key: &str,
+    env: Option<&str>,
+    target_project: &str,
+    target_key: &str,
+    target_env: Option<&str>,
+) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+
+    if !vault.projects.contains_key(project) {
+        return Err(CliError::ProjectNotFound(project.to_string()));
+    }
+
+    if vault.projects[project]
+        .secrets_in(env)
+        .map(|secrets| secrets.contains_key(key))
+        .unwrap_or(false)
+        && !input::confirm(&format!("Secret '{}' already exists. Replace it with an alias?", key))?
+    {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let target = SecretRef::new(target_project, target_env, target_key);
+    let location = SecretRef::new(project, env, key);
+    vault.track_write(Operation::Add, location, |vault| {
+        vault.add_alias(project, env, key, target.clone())
+    })?;
+    vault.record(Operation::Add, Some(project), Some(&qualified_key(env, key)));
+
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("✓ {} now refers to {}.", SecretRef::new(project, env, key), target);
+    Ok(())
+}
@@ -0,0 +1,122 @@
+//! `vx archive` and `vx unarchive`: keep a project in a standalone
+//! encrypted file, e.g. for a decommissioned service, and bring it back.
+//!
+//! The archive is encrypted under a passphrase chosen when it is made, not
+//! under the vault's credentials, so it can be restored into any vault; see
+//! `vx_core::archive`.
+
+use crate::error::CliError;
+use crate::input;
+use crate::progress;
+use crate::storage;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use vx_core::{archive, ttl, Operation};
+
+/// Executes the archive command.
+/// Writes the project and its attachments to `out`, then moves the project
+/// to the trash if `remove` is set.
+pub fn archive(project: &str, out: &str, remove: bool) -> Result<(), CliError> {
+    // Exporting a project asks for a credential even while the daemon holds
+    // the key
+    let (mut vault, encryption_key) = storage::open_vault_with_credential()?;
+    vault.unseal_project(project, &encryption_key)?;
+    storage::unlock_project(&mut vault, project, &encryption_key)?;
+
+    let blobs = vault.projects[project]
+        .attachments
+        .values()
+        .map(|attachment| {
+            let blob = storage::read_attachment_blob(&attachment.blob_id)?;
+            Ok((attachment.blob_id.clone(), blob))
+        })
+        .collect::<Result<HashMap<_, _>, CliError>>()?;
+    let contents = archive::collect(&vault, project, &encryption_key, &blobs)?;
+
+    if Path::new(out).exists() && !input::confirm(&format!("{} exists. Overwrite it?", out))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let passphrase = input::read_strong_password_as("archive passphrase")?;
+    let data = progress::with_spinner("Encrypting archive...", || {
+        archive::seal(&contents, passphrase.as_bytes())
+    })?;
+
+    let mut file = fs::File::create(out)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions = fs::Permissions::from_mode(0o600);
+        file.set_permissions(permissions)?;
+    }
+
+    file.write_all(&data)?;
+    file.sync_all()?;
+
+    println!(
+        "✓ Project '{}' archived to {} with {} secret(s) and {} attachment(s).",
+        project,
+        out,
+        contents.project.all_secrets().count(),
+        contents.attachments.len()
+    );
+    if vault.projects[project].lock.is_some() {
+        println!("  The archive does not keep the project passphrase.");
+    }
+
+    vault.record(Operation::Archive, Some(project), None);
+    if remove {
+        let id = vault.trash_project(project, &encryption_key)?;
+        vault.record(Operation::Remove, Some(project), None);
+        println!("  Project '{}' removed; it is kept in the trash as {}.", project, id);
+    }
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("  Restore it with: vx unarchive {}", out);
+    Ok(())
+}
+
+/// Executes the unarchive command.
+/// Adds the project in the archive `file` under its own name, or `rename`.
+pub fn unarchive(file: &str, rename: Option<&str>) -> Result<(), CliError> {
+    let data = fs::read(file).map_err(|_| CliError::FileNotFound(file.to_string()))?;
+    let info = archive::read_info(&data)?;
+    let name = rename.unwrap_or(&info.project);
+
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+    if vault.project_names().contains(&name) {
+        return Err(CliError::Generic(format!(
+            "Project '{}' already exists; restore under another name with --as <name>",
+            name
+        )));
+    }
+
+    println!(
+        "Archive of project '{}' ({} secret(s), {} attachment(s)) made {} with VaultX {}.",
+        info.project,
+        info.secrets,
+        info.attachments,
+        ttl::format_relative(info.created_at, ttl::current_timestamp()),
+        info.created_with
+    );
+    let passphrase = input::read_password("Enter archive passphrase: ")?;
+    let contents = progress::with_spinner("Decrypting archive...", || {
+        archive::open(&data, passphrase.as_bytes())
+    })?;
+
+    let blobs = archive::restore(&mut vault, &contents, name, &encryption_key)?;
+    // Write the blobs before the vault so the vault never references a
+    // missing blob
+    for (blob_id, blob) in &blobs {
+        storage::write_attachment_blob(blob_id, blob)?;
+    }
+    vault.record(Operation::Unarchive, Some(name), None);
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("✓ Project '{}' restored.", name);
+    Ok(())
+}
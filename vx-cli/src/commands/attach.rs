@@ -0,0 +1,37 @@
+//! Attach a file to a project.
+
+use crate::error::CliError;
+use crate::storage;
+use std::path::Path;
+
+/// Executes the attach command.
+pub fn execute(project: &str, file: &str, name: Option<&str>) -> Result<(), CliError> {
+    let path = Path::new(file);
+    let contents = std::fs::read(path).map_err(|_| CliError::FileNotFound(file.to_string()))?;
+
+    // Default the attachment name to the file name
+    let name = match name {
+        Some(n) => n.to_string(),
+        None => path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| CliError::Generic(format!("Invalid file name: {}", file)))?,
+    };
+
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+
+    let (blob_id, blob) = vault.add_attachment(project, &name, &contents, &encryption_key)?;
+
+    // Write the blob before the vault so the vault never references a missing blob
+    storage::write_attachment_blob(&blob_id, &blob)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!(
+        "File '{}' attached to project '{}' ({} bytes).",
+        name,
+        project,
+        contents.len()
+    );
+
+    Ok(())
+}
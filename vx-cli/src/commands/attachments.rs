@@ -0,0 +1,63 @@
+//! List or extract attachments of a project.
+
+use crate::error::CliError;
+use crate::storage;
+use std::io::{self, Write};
+
+/// Executes the attachments command.
+/// If name is provided, writes the decrypted attachment to `out` (or stdout).
+/// If name is not provided, lists all attachments in the project.
+pub fn execute(project: &str, name: Option<&str>, out: Option<&str>) -> Result<(), CliError> {
+    let Some(name) = name else {
+        return list(project);
+    };
+
+    let (vault, encryption_key) = storage::load_vault_with_key_auto()?;
+
+    let attachment = vault.get_attachment(project, name)?;
+    let blob = storage::read_attachment_blob(&attachment.blob_id)?;
+    let contents = vault.open_attachment(project, name, &blob, &encryption_key)?;
+
+    if let Some(out_path) = out {
+        let mut file = std::fs::File::create(out_path)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let permissions = std::fs::Permissions::from_mode(0o600);
+            file.set_permissions(permissions)?;
+        }
+
+        file.write_all(&contents)?;
+        file.sync_all()?;
+        println!("Attachment '{}' written to {}.", name, out_path);
+    } else {
+        io::stdout().write_all(&contents)?;
+        io::stdout().flush()?;
+    }
+
+    Ok(())
+}
+
+/// Lists attachment names and sizes without decrypting any contents.
+fn list(project: &str) -> Result<(), CliError> {
+    let vault = storage::load_vault_auto()?;
+
+    let proj = vault
+        .projects
+        .get(project)
+        .ok_or_else(|| CliError::ProjectNotFound(project.to_string()))?;
+
+    if proj.attachments.is_empty() {
+        println!("No attachments in project '{}'.", project);
+        return Ok(());
+    }
+
+    println!("Attachments in project '{}':", project);
+
+    for (name, attachment) in &proj.attachments {
+        println!("  • {} ({} bytes)", name, attachment.size);
+    }
+
+    Ok(())
+}
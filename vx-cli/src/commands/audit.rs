@@ -1,138 +1,140 @@
 //! Audit the vault for security issues.
 
+use super::qualified_key;
+use crate::config::{self, OutputFormat};
 use crate::error::CliError;
-
+use crate::permissions::{self, FileIssue};
+use crate::session;
 use crate::storage;
-use vx_core::ttl::current_timestamp;
-use vx_core::ttl::is_expired;
-
-/// Number of days after which a secret is considered long-lived
-const LONG_LIVED_DAYS: u64 = 90;
-
-/// Seconds in a day
-const SECONDS_PER_DAY: u64 = 86400;
-
-/// High-risk patterns in secret names
-const HIGH_RISK_PATTERNS: &[&str] = &[
-    "password",
-    "passwd",
-    "secret",
-    "token",
-    "api_key",
-    "apikey",
-    "private_key",
-    "privatekey",
-    "credential",
-];
-
-/// Executes the audit command.
-pub fn execute() -> Result<(), CliError> {
-    // Load vault
-    let (vault, _key) = storage::load_vault_with_key_auto()?;
+use serde::Serialize;
+use vx_core::audit::{self, AuditIssue, AuditReport, IssueKind, SECONDS_PER_DAY};
+use vx_core::generate::PolicyViolation;
+use vx_core::ttl::{self, current_timestamp};
+
+/// The vault report with the storage checks, as printed by `--format json`.
+#[derive(Serialize)]
+struct Report<'a> {
+    #[serde(flatten)]
+    vault: &'a AuditReport,
+    files: &'a [FileIssue],
+}
 
-    let now = current_timestamp();
-    let long_lived_threshold = now.saturating_sub(LONG_LIVED_DAYS * SECONDS_PER_DAY);
+/// Executes the audit command, with the thresholds from the config file.
+/// `min_idle` (e.g. `180d`) overrides the config's threshold for flagging
+/// secrets that have not been read.
+pub fn execute(format: OutputFormat, min_idle: Option<&str>) -> Result<(), CliError> {
+    let mut thresholds = config::get().audit.thresholds();
+    if let Some(min_idle) = min_idle {
+        let seconds =
+            ttl::parse_ttl(min_idle).map_err(|e| CliError::InvalidTtl(e.to_string()))?;
+        thresholds.min_idle_days = Some(seconds / SECONDS_PER_DAY);
+    }
 
-    let mut total_secrets = 0;
-    let mut expired_count = 0;
-    let mut long_lived_count = 0;
-    let mut high_risk_count = 0;
+    // Load vault
+    let (vault, key) = storage::load_vault_with_key_auto()?;
 
-    let mut issues: Vec<String> = Vec::new();
+    let now = current_timestamp();
+    let mut report = audit::audit_vault_with(&vault, now, thresholds);
+    audit::audit_values(&mut report, &vault, &key, now)?;
+    let extra: Vec<_> = session::cache_file().into_iter().collect();
+    let files = permissions::audit(&storage::vault_dir()?, &extra)?;
+
+    if format == OutputFormat::Json {
+        let report = Report {
+            vault: &report,
+            files: &files,
+        };
+        let json = serde_json::to_string_pretty(&report).map_err(std::io::Error::from)?;
+        println!("{}", json);
+        return Ok(());
+    }
 
     println!("\n=== VaultX Security Audit ===\n");
 
-    for (project_name, project) in &vault.projects {
-        let mut project_expired = 0;
-        let mut project_long_lived = 0;
-        let mut project_high_risk = 0;
-
-        for (key, secret) in &project.secrets {
-            total_secrets += 1;
-
-            // Check if expired
-            if is_expired(secret.expires_at, now) {
-                expired_count += 1;
-                project_expired += 1;
-                issues.push(format!(
-                    "  [EXPIRED] {}/{} - Secret has expired",
-                    project_name, key
-                ));
-            }
-
-            // Check if long-lived
-            if secret.created_at < long_lived_threshold {
-                long_lived_count += 1;
-                project_long_lived += 1;
-                let age_days = (now - secret.created_at) / SECONDS_PER_DAY;
-                issues.push(format!(
-                    "  [LONG-LIVED] {}/{} - {} days old (consider rotation)",
-                    project_name, key, age_days
-                ));
-            }
-
-            // Check for high-risk patterns
-            let key_lower = key.to_lowercase();
-            for pattern in HIGH_RISK_PATTERNS {
-                if key_lower.contains(pattern) {
-                    // Only flag if no TTL set
-                    if secret.expires_at.is_none() {
-                        high_risk_count += 1;
-                        project_high_risk += 1;
-                        issues.push(format!(
-                            "  [HIGH-RISK] {}/{} - Sensitive secret without TTL",
-                            project_name, key
-                        ));
-                        break;
-                    }
-                }
-            }
-        }
-
-        // Project summary
-        let project_total = project.secrets.len();
-        let project_flagged = project_expired + project_long_lived + project_high_risk;
-
+    for project in &report.projects {
         println!(
             "Project '{}': {} secrets ({} expired, {} long-lived, {} high-risk)",
-            project_name, project_total, project_expired, project_long_lived, project_high_risk
+            project.name,
+            project.total_secrets,
+            project.count(IssueKind::Expired),
+            project.count(IssueKind::LongLived),
+            project.count(IssueKind::HighRisk)
         );
 
-        if project_flagged > 0 {
-            for issue in issues
-                .iter()
-                .filter(|i| i.contains(&format!("{}/", project_name)))
-            {
-                println!("{}", issue);
+        if !project.issues.is_empty() {
+            for issue in &project.issues {
+                println!("{}", describe(issue));
+                if let Some(provenance) = &issue.provenance {
+                    println!("      Written by {}", provenance);
+                }
             }
             println!();
         }
     }
 
     // SSH identities summary
-    let ssh_count = vault.ssh_identities.len();
-    if ssh_count > 0 {
-        println!("SSH Identities: {}", ssh_count);
-        for (name, identity) in &vault.ssh_identities {
-            let age_days = (now - identity.created_at) / SECONDS_PER_DAY;
-            if age_days > LONG_LIVED_DAYS {
-                println!(
-                    "  [LONG-LIVED] {} - {} days old (consider rotation)",
-                    name, age_days
-                );
-            }
+    if report.ssh_identities > 0 {
+        println!("SSH Identities: {}", report.ssh_identities);
+        for identity in &report.long_lived_identities {
+            println!(
+                "  [LONG-LIVED] {} - {} days old (consider rotation)",
+                identity.name, identity.age_days
+            );
+        }
+        println!();
+    }
+
+    if !report.trash.is_empty() {
+        println!("Trash: {} item(s) that can still be restored", report.trash.len());
+        for item in &report.trash {
+            println!(
+                "  [TRASH] #{} {} - removed {} days ago",
+                item.id, item.item, item.age_days
+            );
+        }
+        println!();
+    }
+
+    if !files.is_empty() {
+        println!("Files: {} stored where others may read them", files.len());
+        for issue in &files {
+            println!("  [PERMISSIONS] {} - {}", issue.path.display(), issue.problem);
+            println!("      Fix: {}", issue.fix);
         }
         println!();
     }
 
     // Overall summary
     println!("=== Summary ===");
-    println!("Total secrets: {}", total_secrets);
-    println!("Expired: {}", expired_count);
-    println!("Long-lived (>90 days): {}", long_lived_count);
-    println!("High-risk without TTL: {}", high_risk_count);
+    println!("Total secrets: {}", report.total_secrets());
+    println!("Expired: {}", report.count(IssueKind::Expired));
+    println!(
+        "Long-lived (>{} days): {}",
+        thresholds.long_lived_days,
+        report.count(IssueKind::LongLived)
+    );
+    println!("High-risk without TTL: {}", report.count(IssueKind::HighRisk));
+    println!(
+        "Certificates expiring within {} days: {}",
+        thresholds.certificate_warning_days,
+        report.count(IssueKind::CertificateExpiring)
+    );
+    if let Some(min_idle_days) = thresholds.min_idle_days {
+        println!(
+            "Unused (not read in {} days): {}",
+            min_idle_days,
+            report.count(IssueKind::Unused)
+        );
+    }
 
-    let total_issues = expired_count + long_lived_count + high_risk_count;
+    println!("Rule violations: {}", report.count(IssueKind::RuleViolation));
+    println!(
+        "Generation policy violations: {}",
+        report.count(IssueKind::PolicyViolation)
+    );
+    println!("File permission problems: {}", files.len());
+
+    let total_issues = report.total_issues() + files.len();
     if total_issues == 0 {
         println!("\n✓ No security issues found.");
     } else {
@@ -141,3 +143,44 @@ pub fn execute() -> Result<(), CliError> {
 
     Ok(())
 }
+
+/// Formats an issue as a report line. Secrets in named environments are
+/// shown as project/env/key.
+fn describe(issue: &AuditIssue) -> String {
+    let name = format!(
+        "{}/{}",
+        issue.project,
+        qualified_key(issue.env.as_deref(), &issue.key)
+    );
+
+    match issue.kind {
+        IssueKind::Expired => format!("  [EXPIRED] {} - Secret has expired", name),
+        IssueKind::LongLived => format!(
+            "  [LONG-LIVED] {} - {} days old (consider rotation)",
+            name, issue.age_days
+        ),
+        IssueKind::HighRisk => format!("  [HIGH-RISK] {} - Sensitive secret without TTL", name),
+        IssueKind::CertificateExpiring => format!(
+            "  [CERT-EXPIRING] {} - certificate expires in {} days (renew it)",
+            name,
+            issue.expires_in_days.unwrap_or(0)
+        ),
+        IssueKind::Unused => format!(
+            "  [UNUSED] {} - not read in {} days (consider removing)",
+            name,
+            issue.idle_days.unwrap_or(issue.age_days)
+        ),
+        IssueKind::RuleViolation => match &issue.rule {
+            Some(rule) => format!("  [RULE] {} - {}", name, rule),
+            None => format!("  [RULE] {} - breaks a project rule", name),
+        },
+        IssueKind::PolicyViolation => match &issue.policy {
+            Some(policy @ PolicyViolation::RotationDue { .. }) => format!(
+                "  [ROTATE] {} - {} days old; {} (run vx generate)",
+                name, issue.age_days, policy
+            ),
+            Some(policy) => format!("  [POLICY] {} - {}", name, policy),
+            None => format!("  [POLICY] {} - breaks the generation policy", name),
+        },
+    }
+}
@@ -0,0 +1,41 @@
+//! Make a paper backup of the vault's data key.
+
+use crate::error::CliError;
+use crate::input;
+use crate::storage;
+use vx_core::mnemonic;
+
+/// Words per row of the printed phrase
+const ROW_LEN: usize = 4;
+
+/// Executes the backup mnemonic command.
+/// Prints the vault's data key as a 24-word BIP39 mnemonic.
+pub fn mnemonic() -> Result<(), CliError> {
+    // Ask for a credential even while the daemon holds the key
+    let (_vault, encryption_key) = storage::open_vault_with_credential()?;
+
+    println!("The recovery phrase unlocks the vault like a master password, and");
+    println!("cannot be revoked: removing key slots does not invalidate it.");
+    if !input::confirm("Show the recovery phrase?")? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let phrase = mnemonic::from_key(&encryption_key);
+    let words: Vec<&str> = phrase.split(' ').collect();
+    let rows = words.len().div_ceil(ROW_LEN);
+
+    println!();
+    for row in 0..rows {
+        let line: Vec<String> = (0..ROW_LEN)
+            .map(|column| column * rows + row)
+            .filter(|&i| i < words.len())
+            .map(|i| format!("{:>2}. {:<10}", i + 1, words[i]))
+            .collect();
+        println!("  {}", line.join(" ").trim_end());
+    }
+    println!();
+    println!("Write the words down in order and keep them offline. With a copy of");
+    println!("the vault file, 'vx restore mnemonic' sets a new master password.");
+    Ok(())
+}
@@ -0,0 +1,105 @@
+//! Run the unlock daemon.
+//!
+//! Unlocks the vault once and serves the data key to later `vx` commands
+//! over a unix socket; see `crate::daemon` for the protocol.
+
+use crate::error::CliError;
+
+/// Executes the daemon command: runs the daemon in the foreground until it
+/// is stopped or `timeout` runs out, or stops a running one. With
+/// `notify_expiring`, checks hourly for anything expiring within that
+/// window.
+#[cfg(unix)]
+pub fn execute(
+    stop: bool,
+    timeout: Option<&str>,
+    notify_expiring: Option<&str>,
+) -> Result<(), CliError> {
+    use crate::daemon;
+    use crate::expiry;
+    use crate::storage;
+    use std::fs;
+    use vx_core::ttl;
+
+    if stop {
+        if daemon::stop() {
+            println!("✓ Daemon stopped; the key has been wiped.");
+        } else {
+            println!("No daemon is running.");
+        }
+        return Ok(());
+    }
+
+    let timeout_seconds = timeout
+        .map(|t| ttl::parse_ttl(t).map_err(|e| CliError::InvalidTtl(e.to_string())))
+        .transpose()?;
+    let notify_window = notify_expiring
+        .map(|w| ttl::parse_ttl(w).map_err(|e| CliError::InvalidTtl(e.to_string())))
+        .transpose()?;
+
+    if daemon::is_running() {
+        return Err(CliError::Generic(
+            "A daemon is already running (stop it with 'vx daemon --stop')".to_string(),
+        ));
+    }
+
+    // Unlock with a credential; the daemon itself is not consulted
+    let (_vault, key) = storage::open_vault_with_credential()?;
+
+    let path = daemon::socket_path()?;
+    if path.exists() {
+        // Left behind by a daemon that did not shut down cleanly
+        fs::remove_file(&path)?;
+    }
+    let listener = daemon::bind_private(&path)?;
+
+    println!("✓ Daemon listening on {}", path.display());
+    if !key.is_locked() {
+        println!("⚠ Could not lock the key into memory; it may be swapped to disk.");
+    }
+    match timeout {
+        Some(timeout) => println!("The key will be wiped after {}.", timeout),
+        None => println!("Stop it with 'vx daemon --stop'."),
+    }
+
+    if let Some(seconds) = timeout_seconds {
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_secs(seconds));
+            daemon::stop();
+        });
+    }
+
+    if let Some(window) = notify_window {
+        let key = key.clone();
+        std::thread::spawn(move || {
+            // Re-read each time to see secrets added since the last check
+            let mut notified = Vec::new();
+            loop {
+                if let Some(mut vault) = storage::open_vault_with_data_key(&key) {
+                    if vault.unseal_all(&key).is_ok() {
+                        expiry::notify_expiring(&vault, window, &mut notified);
+                    }
+                }
+                std::thread::sleep(expiry::CHECK_INTERVAL);
+            }
+        });
+    }
+
+    daemon::serve(&listener, &path, &key, daemon::current_uid());
+
+    let _ = fs::remove_file(&path);
+    println!("Daemon stopped; the key has been wiped.");
+    Ok(())
+}
+
+/// Executes the daemon command. Unix domain sockets are required.
+#[cfg(not(unix))]
+pub fn execute(
+    _stop: bool,
+    _timeout: Option<&str>,
+    _notify_expiring: Option<&str>,
+) -> Result<(), CliError> {
+    Err(CliError::Generic(
+        "vx daemon is only supported on Unix-like systems".to_string(),
+    ))
+}
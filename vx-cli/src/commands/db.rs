@@ -0,0 +1,172 @@
+//! Store database credentials and open a client with them.
+
+use super::qualified_key;
+use crate::config;
+use crate::error::CliError;
+use crate::input;
+use crate::scratch::ScratchFile;
+use crate::storage;
+use std::process::Command;
+use vx_core::database::{DbConnection, DbEngine};
+use vx_core::{ttl, Operation, SecretRef};
+use zeroize::Zeroizing;
+
+/// Where a database lives and who connects, as given to `vx db add`
+pub struct ConnectionOptions {
+    pub engine: DbEngine,
+    pub host: String,
+    pub port: Option<u16>,
+    pub user: String,
+    pub database: Option<String>,
+}
+
+/// Executes the db add command.
+/// Prompts for the password and stores the connection as the secret
+/// `name`. Connections added without `ttl_str` get the config file's
+/// `default_ttl`.
+pub fn add(
+    project: &str,
+    name: &str,
+    env: Option<&str>,
+    options: ConnectionOptions,
+    ttl_str: Option<&str>,
+) -> Result<(), CliError> {
+    let ttl_seconds = match ttl_str {
+        Some(ttl) => Some(ttl::parse_ttl(ttl).map_err(|e| CliError::InvalidTtl(e.to_string()))?),
+        None => config::get().default_ttl,
+    };
+
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    storage::unlock_project(&mut vault, project, &encryption_key)?;
+
+    let exists = vault
+        .projects
+        .get(project)
+        .and_then(|p| p.secrets_in(env).ok())
+        .is_some_and(|secrets| secrets.contains_key(name));
+    if exists && !input::confirm(&format!("Secret '{}' already exists. Overwrite?", name))? {
+        println!("Skipped.");
+        return Ok(());
+    }
+
+    let password = input::read_password("Database password: ")?;
+    let connection = DbConnection {
+        engine: options.engine,
+        host: options.host,
+        port: options.port,
+        user: options.user,
+        password: password.to_string(),
+        database: options.database,
+    };
+
+    vault.track_write(
+        Operation::Add,
+        SecretRef::new(project, env, name),
+        |vault| {
+            vault.add_database_in(
+                project,
+                env,
+                name,
+                &connection.to_bytes(),
+                &encryption_key,
+                ttl_seconds,
+            )
+        },
+    )?;
+    vault.record(
+        Operation::Add,
+        Some(project),
+        Some(&qualified_key(env, name)),
+    );
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!(
+        "✓ {} connection '{}' added to project '{}'.",
+        connection.engine, name, project
+    );
+    println!("  Connect with: vx db connect {} {}", project, name);
+    Ok(())
+}
+
+/// Executes the db connect command.
+/// Runs the engine's client with `args` appended, handing it the password
+/// through a `ScratchFile` so it never appears in the client's arguments or
+/// environment.
+pub fn connect(
+    project: &str,
+    name: &str,
+    env: Option<&str>,
+    args: &[String],
+) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    let value = storage::read_secret(&mut vault, project, env, name, &encryption_key)?;
+    let connection = DbConnection::parse(&value).map_err(|_| {
+        CliError::Generic(format!(
+            "Secret '{}' is not a database connection (add one with 'vx db add')",
+            name
+        ))
+    })?;
+    storage::record_access(
+        &mut vault,
+        Operation::Get,
+        Some(project),
+        Some(&qualified_key(env, name)),
+        &encryption_key,
+    );
+
+    let client = connection.engine.client();
+    let mut cmd = Command::new(client);
+    let credentials = match connection.engine {
+        DbEngine::Postgres => {
+            let pgpass = ScratchFile::create("pgpass", connection.pgpass_line().as_bytes())?;
+            cmd.env("PGHOST", &connection.host)
+                .env("PGPORT", connection.port().to_string())
+                .env("PGUSER", &connection.user)
+                .env("PGPASSFILE", pgpass.path());
+            if let Some(database) = &connection.database {
+                cmd.env("PGDATABASE", database);
+            }
+            cmd.args(args);
+            pgpass
+        }
+        DbEngine::Mysql => {
+            let option_file =
+                ScratchFile::create("my.cnf", connection.mysql_option_file()?.as_bytes())?;
+            // Must be the first option
+            let mut option = std::ffi::OsString::from("--defaults-extra-file=");
+            option.push(option_file.path());
+            cmd.arg(option);
+            cmd.args(connection.database.as_ref());
+            cmd.args(args);
+            option_file
+        }
+        DbEngine::Mongodb => {
+            let uri = Zeroizing::new(
+                serde_json::to_string(connection.mongodb_uri().as_str())
+                    .map_err(std::io::Error::from)?,
+            );
+            let script = Zeroizing::new(format!("db = connect({});\n", uri.as_str()));
+            let script_file = ScratchFile::create("connect.js", script.as_bytes())?;
+            cmd.args(args)
+                .arg("--nodb")
+                .arg("--shell")
+                .arg(script_file.path());
+            script_file
+        }
+    };
+
+    let status = cmd.status();
+    drop(credentials);
+
+    let status =
+        status.map_err(|e| CliError::Generic(format!("Failed to execute {}: {}", client, e)))?;
+    if !status.success() {
+        return Err(CliError::Generic(format!(
+            "{} exited with status: {}",
+            client,
+            status.code().unwrap_or(-1)
+        )));
+    }
+
+    Ok(())
+}
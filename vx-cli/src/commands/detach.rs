@@ -0,0 +1,31 @@
+//! Remove an attachment from a project.
+
+use crate::error::CliError;
+use crate::input;
+use crate::storage;
+
+/// Executes the detach command.
+pub fn execute(project: &str, name: &str) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+
+    // Fail early on unknown attachments before asking for confirmation
+    vault.get_attachment(project, name)?;
+
+    if !input::confirm(&format!(
+        "Are you sure you want to remove attachment '{}' from project '{}'?",
+        name, project
+    ))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let attachment = vault.remove_attachment(project, name)?;
+
+    // Save the vault first; a leftover blob is harmless, a dangling reference is not
+    storage::save_vault(&mut vault, &encryption_key)?;
+    storage::remove_attachment_blob(&attachment.blob_id)?;
+
+    println!("Attachment '{}' removed from project '{}'.", name, project);
+
+    Ok(())
+}
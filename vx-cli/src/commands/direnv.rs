@@ -0,0 +1,57 @@
+//! direnv integration.
+
+use super::env::quote;
+use crate::error::CliError;
+use crate::storage;
+
+/// Executes the direnv command.
+/// Prints a `use_vaultx` function for `~/.config/direnv/direnvrc` and the
+/// `.envrc` line that loads `project` (or one of its environments) with it.
+///
+/// The function runs `vx env --no-prompt`, so entering the directory never
+/// blocks on a password prompt: the vault must be unlocked by `vx daemon`
+/// (the `vx login` cache belongs to the shell it ran in, not to direnv's) or
+/// `VX_KEYFILE`. It also watches the vault file, so direnv reloads the
+/// secrets when they change.
+pub fn execute(project: &str, env: Option<&str>) -> Result<(), CliError> {
+    let vault_path = storage::vault_path()?;
+
+    let mut args = shell_word(project);
+    if let Some(env) = env {
+        args.push_str(" --env ");
+        args.push_str(&shell_word(env));
+    }
+
+    println!("# Add to ~/.config/direnv/direnvrc (once):");
+    println!("use_vaultx() {{");
+    println!(
+        "  watch_file {}",
+        shell_word(&vault_path.display().to_string())
+    );
+    println!("  local exports");
+    println!("  if exports=\"$(vx env --no-prompt \"$@\")\"; then");
+    println!("    eval \"$exports\"");
+    println!("  else");
+    println!(
+        "    log_error \"vaultx: unlock the vault with 'vx daemon', then run 'direnv reload'\""
+    );
+    println!("  fi");
+    println!("}}");
+    println!();
+    println!("# Add to .envrc:");
+    println!("use vaultx {}", args);
+    Ok(())
+}
+
+/// Quotes `word` for the shell unless it only holds safe characters.
+fn shell_word(word: &str) -> String {
+    let safe = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:@".contains(c));
+    if safe {
+        word.to_string()
+    } else {
+        String::from_utf8_lossy(&quote(word.as_bytes())).into_owned()
+    }
+}
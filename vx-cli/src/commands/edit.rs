@@ -1,36 +1,38 @@
+use super::qualified_key;
 use crate::error::CliError;
 use crate::input;
-use crate::session;
 use crate::storage;
+use vx_core::{ttl, Operation, SecretKind, SecretRef};
+
+/// Executes the edit command.
+/// The secret keeps its expiry unless `ttl_str` gives a new TTL.
+pub fn execute(
+    project: &str,
+    key: &str,
+    env: Option<&str>,
+    ttl_str: Option<&str>,
+) -> Result<(), CliError> {
+    let new_ttl = ttl_str
+        .map(|ttl| ttl::parse_ttl(ttl).map_err(|e| CliError::InvalidTtl(e.to_string())))
+        .transpose()?;
 
-pub fn execute(project: &str, key: &str) -> Result<(), CliError> {
     // Load vault with encryption key
-    let (mut vault, encryption_key, password_bytes) = if let Some(cached) = session::get_cached_password()? {
-        match storage::load_vault_with_key(&cached) {
-            Ok((v, k)) => (v, k, cached),
-            Err(_) => {
-                let _ = session::clear_cached_password();
-                let p = input::read_password("Enter master password: ")?;
-                let (v, k) = storage::load_vault_with_key(p.as_bytes())?;
-                (v, k, p.into_bytes())
-            }
-        }
-    } else {
-         let p = input::read_password("Enter master password: ")?;
-         let (v, k) = storage::load_vault_with_key(p.as_bytes())?;
-         (v, k, p.into_bytes())
-    };
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
 
     // Check if secret exists
-    let exists = vault
+    let existing = vault
         .projects
         .get(project)
-        .map(|p| p.secrets.contains_key(key))
-        .unwrap_or(false);
+        .ok_or_else(|| CliError::ProjectNotFound(project.to_string()))?
+        .secrets_in(env)?
+        .get(key)
+        .ok_or_else(|| vx_core::VaultError::SecretNotFound(key.to_string()))?;
+    let old_ttl_expiry = existing.expires_at;
+    let alias_target = existing.alias.clone();
+    let is_template = existing.template;
+    let kind = existing.kind;
 
-    if !exists {
-        return Err(CliError::Vault(vx_core::VaultError::SecretNotFound(key.to_string())));
-    }
+    storage::unlock_project(&mut vault, project, &encryption_key)?;
 
     println!("Editing secret '{}' in project '{}'.", key, project);
     
@@ -38,10 +40,10 @@ pub fn execute(project: &str, key: &str) -> Result<(), CliError> {
     let secret_value = input::read_secret(None, None)?;
 
     // Preserve existing TTL
-    let old_ttl_expiry = vault.projects.get(project).unwrap().secrets.get(key).unwrap().expires_at;
-    
-    let ttl_seconds = if let Some(expiry) = old_ttl_expiry {
-        let now = vx_core::ttl::current_timestamp();
+    let ttl_seconds = if new_ttl.is_some() {
+        new_ttl
+    } else if let Some(expiry) = old_ttl_expiry {
+        let now = ttl::current_timestamp();
         if expiry > now {
             Some(expiry - now)
         } else {
@@ -52,12 +54,39 @@ pub fn execute(project: &str, key: &str) -> Result<(), CliError> {
     };
 
     // Update secret
-    vault.add_secret(project, key, &secret_value, &encryption_key, ttl_seconds)?;
+    vault.track_write(Operation::Edit, SecretRef::new(project, env, key), |vault| {
+        if is_template {
+            vault.add_template_in(project, env, key, &secret_value, &encryption_key)
+        } else {
+            match kind {
+                SecretKind::Json => vault
+                    .add_json_in(project, env, key, &secret_value, &encryption_key, ttl_seconds),
+                SecretKind::Database => vault.add_database_in(
+                    project,
+                    env,
+                    key,
+                    &secret_value,
+                    &encryption_key,
+                    ttl_seconds,
+                ),
+                // A new certificate brings its own expiry
+                SecretKind::Certificate => vault
+                    .add_certificate_in(project, env, key, &secret_value, &encryption_key)
+                    .map(|_| ()),
+                SecretKind::Text => vault
+                    .add_secret_in(project, env, key, &secret_value, &encryption_key, ttl_seconds),
+            }
+        }
+    })?;
+    vault.record(Operation::Edit, Some(project), Some(&qualified_key(env, key)));
 
     // Save vault
-    storage::save_vault(&vault, &password_bytes)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
 
     println!("Secret '{}' updated.", key);
+    if let Some(target) = alias_target {
+        println!("It was an alias of {} and now holds its own value.", target);
+    }
 
     Ok(())
 }
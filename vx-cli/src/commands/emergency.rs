@@ -0,0 +1,255 @@
+//! Emergency access to the vault for a trusted person, after a waiting
+//! period the owner can veto.
+
+use crate::error::CliError;
+use crate::input;
+use crate::progress;
+use crate::storage;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use vx_core::emergency::{self, SECRET_KEY_PREFIX};
+use vx_core::{
+    ttl, vault, EmergencyGrant, EmergencyRequest, Operation, RequestStatus, SlotKind, VaultError,
+};
+use zeroize::Zeroizing;
+
+/// Executes the emergency keygen command.
+/// Run by the recipient: writes a new secret key to `out` and prints the
+/// public key to hand to the vault owner.
+pub fn keygen(out: &str) -> Result<(), CliError> {
+    if Path::new(out).exists() && !input::confirm(&format!("{} exists. Overwrite it?", out))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let (secret, public) = emergency::generate_keypair();
+    let mut file = fs::File::create(out)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions = fs::Permissions::from_mode(0o600);
+        file.set_permissions(permissions)?;
+    }
+
+    file.write_all(secret.as_bytes())?;
+    file.write_all(b"\n")?;
+    file.sync_all()?;
+
+    println!("✓ Emergency access key written to {}.", out);
+    println!("\n  Public key for the vault owner:\n");
+    println!("      {}\n", public);
+    println!("  The owner adds it with 'vx emergency grant <LABEL> <PUBLIC_KEY>'.");
+    println!("  Keep {} safe: it is all you need to request access.", out);
+    Ok(())
+}
+
+/// Executes the emergency grant command.
+/// Wraps the data key for `public_key`, usable once `wait` has passed after
+/// the recipient requests access.
+pub fn grant(label: &str, public_key: &str, wait: &str) -> Result<(), CliError> {
+    let wait = ttl::parse_ttl(wait).map_err(|e| CliError::InvalidTtl(e.to_string()))?;
+
+    // Ask for a credential even while the daemon holds the key
+    let (mut vault, encryption_key) = storage::open_vault_with_credential()?;
+    let id = vault.add_emergency_grant(label, public_key.trim(), wait, &encryption_key)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("✓ Emergency grant {} added for {}.", id, label);
+    println!(
+        "  Once they request access with 'vx emergency request', the vault opens to them after {}.",
+        ttl::format_duration(wait)
+    );
+    println!(
+        "  You are warned whenever you unlock the vault; refuse with 'vx emergency veto {}'.",
+        id
+    );
+    Ok(())
+}
+
+/// Executes the emergency list command.
+/// Reads the grants from the vault header, so no password is needed.
+pub fn list() -> Result<(), CliError> {
+    let grants = vault::read_emergency_grants(&storage::read_vault_file()?)?;
+    if grants.is_empty() {
+        println!("No emergency grants. Add one with 'vx emergency grant <LABEL> <PUBLIC_KEY>'.");
+        return Ok(());
+    }
+
+    let now = ttl::current_timestamp();
+    println!("\n🚨 Emergency grants\n");
+    println!("{:<4} {:<20} {:<6} STATUS", "ID", "LABEL", "WAIT");
+    println!("{}", "─".repeat(70));
+    for grant in &grants {
+        let request = storage::read_emergency_request(grant.id)?;
+        let status = match grant.status(request.as_ref(), now) {
+            RequestStatus::NotRequested => "not requested".to_string(),
+            RequestStatus::Vetoed => "vetoed".to_string(),
+            RequestStatus::Waiting { available_at } => format!(
+                "requested {}, opens {}",
+                ttl::format_relative(
+                    request.map_or(0, |r| r.requested_at.max(grant.requested_at.unwrap_or(0))),
+                    now
+                ),
+                ttl::format_until(available_at, now)
+            ),
+            RequestStatus::Available => "open".to_string(),
+        };
+        println!(
+            "{:<4} {:<20} {:<6} {}",
+            grant.id,
+            grant.label,
+            ttl::format_duration(grant.wait),
+            status
+        );
+    }
+    println!("{}", "─".repeat(70));
+    Ok(())
+}
+
+/// Executes the emergency revoke command.
+pub fn revoke(id: u32) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+    let label = find_label(vault.emergency_grants(), id)?;
+
+    if !input::confirm(&format!("Revoke emergency grant {} ({})?", id, label))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    vault.remove_emergency_grant(id)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+    storage::remove_emergency_request(id)?;
+
+    println!("✓ Emergency grant {} revoked.", id);
+    Ok(())
+}
+
+/// Executes the emergency veto command.
+/// Refuses every request made under grant `id` so far. The grant stays in
+/// place.
+pub fn veto(id: u32) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+    let label = find_label(vault.emergency_grants(), id)?;
+
+    vault.veto_emergency_grant(id, ttl::current_timestamp())?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+    storage::remove_emergency_request(id)?;
+
+    println!("✓ Emergency access requests by {} vetoed.", label);
+    println!(
+        "  To stop future requests as well, run 'vx emergency revoke {}'.",
+        id
+    );
+    Ok(())
+}
+
+/// Executes the emergency request command.
+/// Run by the recipient: starts the waiting period of the grant made for
+/// the key in `key_file`.
+pub fn request(key_file: &str) -> Result<(), CliError> {
+    let secret = read_key_file(key_file)?;
+    let grant = find_grant(&storage::read_vault_file()?, &secret)?;
+    let now = ttl::current_timestamp();
+
+    let existing = storage::read_emergency_request(grant.id)?;
+    if let RequestStatus::Waiting { available_at } = grant.status(existing.as_ref(), now) {
+        println!(
+            "Emergency access was already requested; it opens {}.",
+            ttl::format_until(available_at, now)
+        );
+        return Ok(());
+    }
+
+    let request = EmergencyRequest::new(grant.id);
+    storage::write_emergency_request(&request)?;
+
+    println!("✓ Emergency access requested.");
+    println!(
+        "  Unless the owner vetoes it, the vault opens to you {}; then run",
+        ttl::format_until(request.requested_at.saturating_add(grant.wait), now)
+    );
+    println!("  'vx emergency open {}'.", key_file);
+    Ok(())
+}
+
+/// Executes the emergency open command.
+/// Run by the recipient once the waiting period has passed: unlocks the
+/// vault with the grant and adds a password slot for them.
+pub fn open(key_file: &str) -> Result<(), CliError> {
+    let secret = read_key_file(key_file)?;
+    let data = storage::read_vault_file()?;
+    let grant = find_grant(&data, &secret)?;
+
+    let request = storage::read_emergency_request(grant.id)?;
+    let now = ttl::current_timestamp();
+    let key = grant
+        .unwrap_key(&secret, request.as_ref(), now)
+        .map_err(|e| match e {
+            VaultError::EmergencyNotRequested => CliError::Generic(format!(
+                "Emergency access has not been requested; run 'vx emergency request {}' first",
+                key_file
+            )),
+            VaultError::EmergencyWaiting { available_at } => CliError::Generic(format!(
+                "The waiting period has not passed; emergency access opens {}",
+                ttl::format_until(available_at, now)
+            )),
+            e => CliError::Vault(e),
+        })?;
+    // Opening authenticates the header, so the time recorded on the grant
+    // (see `Vault::note_emergency_request`) cannot have been changed
+    let mut vault = vault::open_vault(&data, &key)?;
+    storage::attach_shards(&mut vault)?;
+
+    println!("✓ Emergency access granted. Choose a master password.");
+    let password = input::read_new_password()?;
+    let label = format!("emergency: {}", grant.label);
+    let id = progress::with_spinner("Wrapping key...", || {
+        vault.add_key_slot(SlotKind::Password, &label, password.as_bytes(), &key)
+    })?;
+    vault.record(Operation::EmergencyAccess, None, Some(&grant.label));
+    storage::save_vault(&mut vault, &key)?;
+    storage::remove_emergency_request(grant.id)?;
+
+    println!(
+        "✓ Password slot {} added; the vault unlocks with the new password.",
+        id
+    );
+    Ok(())
+}
+
+/// Reads a secret key written by `vx emergency keygen`.
+fn read_key_file(path: &str) -> Result<Zeroizing<String>, CliError> {
+    let contents = Zeroizing::new(
+        fs::read_to_string(path).map_err(|_| CliError::FileNotFound(path.to_string()))?,
+    );
+    if !contents.trim().starts_with(SECRET_KEY_PREFIX) {
+        return Err(CliError::Generic(format!(
+            "{} is not an emergency access key made by 'vx emergency keygen'",
+            path
+        )));
+    }
+    Ok(Zeroizing::new(contents.trim().to_string()))
+}
+
+/// Finds the grant made for `secret` in the vault file header.
+fn find_grant(data: &[u8], secret: &str) -> Result<EmergencyGrant, CliError> {
+    vault::read_emergency_grants(data)?
+        .into_iter()
+        .find(|grant| grant.is_for(secret))
+        .ok_or_else(|| {
+            CliError::Generic(
+                "The vault has no emergency grant for this key (or it was revoked)".to_string(),
+            )
+        })
+}
+
+/// Returns the label of grant `id`.
+fn find_label(grants: &[EmergencyGrant], id: u32) -> Result<String, CliError> {
+    grants
+        .iter()
+        .find(|grant| grant.id == id)
+        .map(|grant| grant.label.clone())
+        .ok_or(CliError::Vault(VaultError::EmergencyGrantNotFound(id)))
+}
@@ -0,0 +1,123 @@
+//! Print a project's secrets as shell `export` statements.
+
+use crate::error::CliError;
+use crate::manifest;
+use crate::storage;
+use std::io::{self, Write};
+use vx_core::{Operation, VaultError};
+use zeroize::Zeroizing;
+
+/// Executes the env command.
+/// Prints `export KEY='value'` lines for every secret in the project (or in
+/// one of its environments), for `eval "$(vx env app)"`. `prefix` is
+/// prepended to each variable name. Expired secrets and keys that are not
+/// valid variable names are skipped.
+///
+/// With `no_prompt` (used by `vx direnv`), the vault must already be
+/// unlocked by `vx login`, `vx daemon` or `VX_KEYFILE`, and passphrase-locked
+/// projects are refused rather than prompted for.
+pub fn execute(
+    project: &str,
+    env: Option<&str>,
+    prefix: &str,
+    no_prompt: bool,
+) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = if no_prompt {
+        storage::open_vault_without_prompt()?
+    } else {
+        storage::open_vault_with_key_auto()?
+    };
+    vault
+        .unseal_project(project, &encryption_key)
+        .map_err(|e| match e {
+            VaultError::ProjectNotFound(name) => CliError::ProjectNotFound(name),
+            e => CliError::Vault(e),
+        })?;
+    if no_prompt && vault.is_locked(project) {
+        return Err(VaultError::ProjectLocked(project.to_string()).into());
+    }
+    storage::unlock_project(&mut vault, project, &encryption_key)?;
+
+    // Limited to the keys the repository's .vaultx.toml asks for
+    let mut keys: Vec<String> = vault.projects[project]
+        .secrets_in(env)?
+        .keys()
+        .filter(|key| manifest::includes(project, key))
+        .cloned()
+        .collect();
+    keys.sort();
+
+    // Values are collected first, so that the access (and the read times)
+    // is saved before anything is revealed
+    let mut out = Zeroizing::new(Vec::new());
+
+    for key in &keys {
+        let name = format!("{}{}", prefix, key);
+        if !is_variable_name(&name) {
+            eprintln!("⚠️  Skipping '{}': not a valid variable name", name);
+            continue;
+        }
+
+        // Aliases and templates are exported with their resolved values
+        let value = if no_prompt {
+            storage::read_secret_without_prompt(&mut vault, project, env, key, &encryption_key)
+        } else {
+            storage::read_secret(&mut vault, project, env, key, &encryption_key)
+        };
+        let value = match value {
+            Ok(value) => value,
+            Err(CliError::Vault(VaultError::SecretExpired(_))) => {
+                eprintln!("⚠️  Skipping expired secret '{}'", key);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if value.contains(&0) {
+            eprintln!(
+                "⚠️  Skipping '{}': environment variables cannot hold NUL bytes",
+                key
+            );
+            continue;
+        }
+
+        out.write_all(format!("export {}=", name).as_bytes())?;
+        out.write_all(&quote(&value))?;
+        out.write_all(b"\n")?;
+    }
+
+    storage::record_access(
+        &mut vault,
+        Operation::Get,
+        Some(project),
+        env,
+        &encryption_key,
+    );
+
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(&out)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Returns whether `name` can be used as a shell variable name.
+pub(super) fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Quotes a value for POSIX shells. Nothing is special inside single quotes
+/// except the closing quote, which is written as `'\''`.
+pub(super) fn quote(value: &[u8]) -> Vec<u8> {
+    let mut quoted = Vec::with_capacity(value.len() + 2);
+    quoted.push(b'\'');
+    for &b in value {
+        if b == b'\'' {
+            quoted.extend_from_slice(b"'\\''");
+        } else {
+            quoted.push(b);
+        }
+    }
+    quoted.push(b'\'');
+    quoted
+}
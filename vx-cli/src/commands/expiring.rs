@@ -0,0 +1,41 @@
+//! List secrets, certificates and SSH identities about to expire.
+
+use crate::config::OutputFormat;
+use crate::error::CliError;
+use crate::storage;
+use vx_core::{audit, ttl};
+
+/// Window used when `--within` is not given
+pub const DEFAULT_WINDOW: &str = "30d";
+
+/// Executes the expiring command.
+/// Lists what runs out within `within` (e.g. `30d`), soonest first. Items
+/// that have already expired are reported by `vx audit`.
+pub fn execute(within: &str, format: OutputFormat) -> Result<(), CliError> {
+    let window = ttl::parse_ttl(within).map_err(|e| CliError::InvalidTtl(e.to_string()))?;
+
+    let (vault, _key) = storage::load_vault_with_key_auto()?;
+    let now = ttl::current_timestamp();
+    let upcoming = audit::upcoming_expirations(&vault, now, window);
+
+    if format == OutputFormat::Json {
+        let json = serde_json::to_string_pretty(&upcoming).map_err(std::io::Error::from)?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    if upcoming.is_empty() {
+        println!("Nothing expires within {}.", ttl::format_duration(window));
+        return Ok(());
+    }
+
+    println!("Expiring within {}:", ttl::format_duration(window));
+    for expiration in &upcoming {
+        println!(
+            "  {} ({})",
+            expiration.describe(now),
+            ttl::format_timestamp(expiration.expires_at)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+//! Export a project's secrets in .env format.
+
+use crate::error::CliError;
+use crate::manifest;
+use crate::storage;
+use std::io::{self, Write};
+use vx_core::{dotenv, Operation, VaultError};
+use zeroize::Zeroizing;
+
+/// Executes the export command.
+/// Prints `KEY="value"` lines for every secret in the project (or in one of
+/// its environments) to stdout. Expired secrets, and keys left out by the
+/// repository's `.vaultx.toml`, are skipped.
+pub fn execute(project: &str, env: Option<&str>) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+    vault
+        .unseal_project(project, &encryption_key)
+        .map_err(|e| match e {
+            VaultError::ProjectNotFound(name) => CliError::ProjectNotFound(name),
+            e => CliError::Vault(e),
+        })?;
+    storage::unlock_project(&mut vault, project, &encryption_key)?;
+
+    // Limited to the keys the repository's .vaultx.toml asks for
+    let mut keys: Vec<String> = vault.projects[project]
+        .secrets_in(env)?
+        .keys()
+        .filter(|key| manifest::includes(project, key))
+        .cloned()
+        .collect();
+    keys.sort();
+
+    // Values are collected first, so that the access (and the read times)
+    // is saved before anything is revealed
+    let mut out = Zeroizing::new(Vec::new());
+
+    for key in &keys {
+        // Aliases and templates are exported with their resolved values
+        let value = match storage::read_secret(&mut vault, project, env, key, &encryption_key) {
+            Ok(value) => value,
+            Err(CliError::Vault(VaultError::SecretExpired(_))) => {
+                eprintln!("⚠️  Skipping expired secret '{}'", key);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        writeln!(out, "{}=\"{}\"", key, dotenv::escape(&String::from_utf8_lossy(&value)))?;
+    }
+
+    storage::record_access(&mut vault, Operation::Get, Some(project), env, &encryption_key);
+
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(&out)?;
+    stdout.flush()?;
+    Ok(())
+}
@@ -0,0 +1,47 @@
+//! Export a read-only copy of the vault for consumers (CI agents, staging).
+
+use crate::error::CliError;
+use crate::input;
+use crate::progress;
+use crate::storage;
+use std::fs;
+use std::io::Write;
+use vx_core::vault;
+
+/// Executes the export-readonly command.
+/// Writes a read-only vault protected by a separate export password to `out`.
+pub fn execute(out: &str) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+
+    // Passphrase-protected projects are exported with the same passphrase
+    let names: Vec<String> = vault.projects.keys().cloned().collect();
+    for name in &names {
+        storage::unlock_project(&mut vault, name, &encryption_key)?;
+    }
+
+    println!("Choose a password for the exported vault (share it with the consumer).");
+    let password = input::read_new_password_as("export password")?;
+
+    let data = progress::with_spinner("Re-encrypting vault...", || {
+        vault::export_read_only(&vault, &encryption_key, password.as_bytes())
+    })?;
+
+    let mut file = fs::File::create(out)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions = fs::Permissions::from_mode(0o600);
+        file.set_permissions(permissions)?;
+    }
+
+    file.write_all(&data)?;
+    file.sync_all()?;
+
+    println!("\n✓ Read-only vault written to {}.", out);
+    println!("  Install it as ~/.vaultx/vault.vx on the consumer; `vx get` and");
+    println!("  `vx list` work, but any command that modifies the vault is refused.");
+    println!("  Attachments and the access log are not included.");
+
+    Ok(())
+}
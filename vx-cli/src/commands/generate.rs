@@ -0,0 +1,71 @@
+//! Generate a random secret with the project's generation policy.
+
+use super::qualified_key;
+use crate::config;
+use crate::error::CliError;
+use crate::input;
+use crate::storage;
+use vx_core::generate::Charset;
+use vx_core::{ttl, Operation, SecretRef};
+
+/// Executes the generate command.
+/// `length` and `charset` override the project's policy for this secret;
+/// secrets generated without `ttl_str` get the config file's `default_ttl`.
+/// Generating an existing secret rotates it, after confirmation.
+pub fn execute(
+    project: &str,
+    key: &str,
+    env: Option<&str>,
+    length: Option<usize>,
+    charset: Option<Charset>,
+    ttl_str: Option<&str>,
+) -> Result<(), CliError> {
+    let ttl_seconds = match ttl_str {
+        Some(ttl) => Some(ttl::parse_ttl(ttl).map_err(|e| CliError::InvalidTtl(e.to_string()))?),
+        None => config::get().default_ttl,
+    };
+
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    storage::unlock_project(&mut vault, project, &encryption_key)?;
+
+    let mut policy = vault.generation_policy(project)?;
+    if let Some(length) = length {
+        policy.length = length;
+    }
+    if let Some(charset) = charset {
+        policy.charset = charset;
+    }
+    policy.validate()?;
+
+    let exists = vault.projects[project]
+        .secrets_in(env)
+        .is_ok_and(|secrets| secrets.contains_key(key));
+    if exists && !input::confirm(&format!("Secret '{}' already exists. Rotate it?", key))? {
+        println!("Skipped.");
+        return Ok(());
+    }
+
+    let value = policy.generate();
+    let operation = if exists {
+        Operation::Edit
+    } else {
+        Operation::Add
+    };
+    let location = SecretRef::new(project, env, key);
+    vault.track_write(operation, location.clone(), |vault| {
+        vault.add_secret_in(project, env, key, &value, &encryption_key, ttl_seconds)
+    })?;
+    vault.record(operation, Some(project), Some(&qualified_key(env, key)));
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!(
+        "✓ Generated {} ({} {} characters{}).",
+        location,
+        policy.length,
+        policy.charset,
+        ttl_seconds
+            .map(|ttl| format!(", expires in {}", ttl::format_duration(ttl)))
+            .unwrap_or_default()
+    );
+    Ok(())
+}
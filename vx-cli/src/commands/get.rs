@@ -1,90 +1,464 @@
-//! Get a secret from a project.
+//! Get secrets from a project.
 
+use super::qualified_key;
+use crate::config;
+use crate::desktop;
 use crate::error::CliError;
+use crate::input;
+use crate::manifest;
 
 use crate::storage;
-use vx_core::ttl;
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::io::{self, IsTerminal, Write};
+use vx_core::structured::JsonDocument;
+use vx_core::{dotenv, pattern, share, ttl, Operation, SecretBytes, Vault, VaultError, KEY_SIZE};
+use zeroize::Zeroizing;
+
+/// Printed in place of values that are not revealed; the same for every
+/// value so it gives nothing away about their length
+const MASK: &str = "********";
+
+/// How the values read are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// The values alone, one per line
+    #[default]
+    Text,
+    /// A JSON object mapping each key to its value
+    Json,
+    /// `KEY="value"` lines, as in a .env file
+    Dotenv,
+}
+
+/// Where the values read go
+#[derive(Clone, Copy)]
+pub enum Destination {
+    /// Printed. In a terminal, only with `reveal` or after confirmation;
+    /// otherwise masked
+    Stdout { reveal: bool },
+    /// Copied to the clipboard
+    Clipboard,
+    /// Shown as a QR code, encrypted under a passphrase with `encrypt`
+    Qr { encrypt: bool },
+}
 
 /// Executes the get command.
-/// If key is provided, gets that specific secret.
-/// If key is not provided, shows all secrets in the project.
-pub fn execute(project: &str, key: Option<&str>) -> Result<(), CliError> {
-    // Load vault with encryption key
-    let (vault, encryption_key) = storage::load_vault_with_key_auto()?;
-
-    // Get project
-    let proj = vault
+/// `args` is a project followed by keys. The project may be omitted if the
+/// repository's `.vaultx.toml` or the config file names one; the first
+/// argument is taken as the project only if the vault has a project by that
+/// name.
+/// If keys are provided, gets those secrets, unlocking the vault once. A
+/// key holding a wildcard (`DB_*`, see `vx_core::pattern`), or with `regex`
+/// any key, selects every secret whose name matches it; see `select_keys`.
+/// If no key is provided, shows all secrets in the project (limited by the
+/// `.vaultx.toml` key filters).
+/// `env` selects a named environment instead of the default one.
+/// Values printed to a terminal are masked unless revealed; see
+/// `Destination::Stdout`.
+/// With `Destination::Clipboard`, the single secret requested goes to the
+/// clipboard instead and is cleared after the config file's
+/// `clipboard_timeout`.
+/// With `Destination::Qr`, it is shown as a QR code instead.
+/// With `path`, each secret must hold a JSON document and only the field at
+/// `path` is output.
+pub fn execute(
+    args: &[String],
+    env: Option<&str>,
+    path: Option<&str>,
+    format: Format,
+    regex: bool,
+    destination: Destination,
+) -> Result<(), CliError> {
+    // Open vault with encryption key; only the requested project is unsealed
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+
+    let (project, keys) = match args.split_first() {
+        Some((first, rest)) if vault.project_names().contains(&first.as_str()) => {
+            (first.clone(), rest)
+        }
+        _ => match config::get().project(None) {
+            Ok(project) => (project, args),
+            Err(e) => match args.first() {
+                Some(first) => return Err(CliError::ProjectNotFound(first.clone())),
+                None => return Err(e),
+            },
+        },
+    };
+    let project = project.as_str();
+
+    if path.is_some() && keys.is_empty() {
+        return Err(CliError::Generic("--path needs the keys to read".to_string()));
+    }
+
+    vault
+        .unseal_project(project, &encryption_key)
+        .map_err(|e| match e {
+            VaultError::ProjectNotFound(name) => CliError::ProjectNotFound(name),
+            e => CliError::Vault(e),
+        })?;
+    storage::unlock_project(&mut vault, project, &encryption_key)?;
+
+    if keys.is_empty() {
+        let Destination::Stdout { reveal } = destination else {
+            unreachable!("--copy and --qr take a single key");
+        };
+        return list(&mut vault, project, env, format, reveal, &encryption_key);
+    }
+
+    let keys = select_keys(&vault, project, env, keys, regex)?;
+    match destination {
+        Destination::Clipboard if keys.len() != 1 => {
+            return Err(CliError::Generic("--copy takes a single key".to_string()));
+        }
+        Destination::Qr { .. } if keys.len() != 1 => {
+            return Err(CliError::Generic("--qr takes a single key".to_string()));
+        }
+        _ => {}
+    }
+
+    // Get specific secrets
+    let mut values = Vec::with_capacity(keys.len());
+    for key in &keys {
+        values.push(storage::read_secret(
+            &mut vault,
+            project,
+            env,
+            key,
+            &encryption_key,
+        )?);
+    }
+
+    if let Some(path) = path {
+        for (value, key) in values.iter_mut().zip(&keys) {
+            let document = JsonDocument::parse(value).map_err(|_| {
+                CliError::Generic(format!("Secret '{}' does not hold a JSON document", key))
+            })?;
+            *value = document.query(path)?;
+        }
+    }
+
+    // Record the accesses before any value is revealed
+    if !vault.read_only {
+        for key in &keys {
+            vault.record(
+                Operation::Get,
+                Some(project),
+                Some(&qualified_key(env, key)),
+            );
+        }
+        storage::save_access_log(&mut vault, &encryption_key);
+    }
+
+    match destination {
+        Destination::Clipboard => return copy_to_clipboard(&values[0]),
+        Destination::Qr { encrypt } => return show_qr(&values[0], encrypt),
+        Destination::Stdout { reveal } => {
+            if !confirm_reveal(reveal, values.len())? {
+                values.fill_with(|| SecretBytes::new(MASK.as_bytes().to_vec()));
+            }
+        }
+    }
+    match format {
+        Format::Json => return print_json(&keys, &values),
+        Format::Dotenv => return print_dotenv(&keys, &values),
+        Format::Text => {}
+    }
+
+    // Output to stdout, one value per line
+    for secret_value in &values {
+        io::stdout().write_all(secret_value)?;
+        io::stdout().flush()?;
+
+        // Add newline if output is text
+        if secret_value
+            .iter()
+            .all(|&b| b != 0 && (b.is_ascii() || b > 127))
+        {
+            println!();
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves the key arguments to the keys to read, in order and without
+/// repeats. An argument with a wildcard that is not itself the name of a
+/// secret, or with `regex` any argument, is replaced by the matching keys
+/// in sorted order, limited by the `.vaultx.toml` key filters; it is an
+/// error for it to match none.
+fn select_keys(
+    vault: &Vault,
+    project: &str,
+    env: Option<&str>,
+    args: &[String],
+    regex: bool,
+) -> Result<Vec<String>, CliError> {
+    let secrets = vault
+        .projects
+        .get(project)
+        .ok_or_else(|| CliError::ProjectNotFound(project.to_string()))?
+        .secrets_in(env)?;
+    let mut names: Vec<&String> = secrets
+        .keys()
+        .filter(|name| manifest::includes(project, name))
+        .collect();
+    names.sort();
+
+    let mut keys: Vec<String> = Vec::new();
+    for arg in args {
+        let matched: Vec<String> = if regex {
+            let expression = Regex::new(arg).map_err(|e| {
+                CliError::Generic(format!("Invalid regular expression '{}': {}", arg, e))
+            })?;
+            names
+                .iter()
+                .filter(|name| expression.is_match(name))
+                .map(|name| name.to_string())
+                .collect()
+        } else if pattern::is_glob(arg) && !secrets.contains_key(arg) {
+            names
+                .iter()
+                .filter(|name| pattern::matches(arg, name))
+                .map(|name| name.to_string())
+                .collect()
+        } else {
+            vec![arg.clone()]
+        };
+
+        if matched.is_empty() {
+            return Err(CliError::Generic(format!(
+                "No secrets in project '{}' match '{}'",
+                project, arg
+            )));
+        }
+        for key in matched {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+    Ok(keys)
+}
+
+/// Shows every secret in the project (limited by the `.vaultx.toml` key
+/// filters), as a table, a JSON object or .env lines.
+fn list(
+    vault: &mut Vault,
+    project: &str,
+    env: Option<&str>,
+    format: Format,
+    reveal: bool,
+    encryption_key: &[u8; KEY_SIZE],
+) -> Result<(), CliError> {
+    // Open the projects that aliases and templates in the listing lead to;
+    // failures show up on their own rows
+    let indirect: Vec<String> = vault
         .projects
         .get(project)
-        .ok_or_else(|| CliError::ProjectNotFound(project.to_string()))?;
+        .and_then(|p| p.secrets_in(env).ok())
+        .map(|secrets| {
+            secrets
+                .values()
+                .filter(|s| s.alias.is_some() || s.template)
+                .map(|s| s.key.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+    for name in &indirect {
+        let _ = storage::read_secret(vault, project, env, name, encryption_key);
+    }
+
+    let secrets = vault
+        .projects
+        .get(project)
+        .ok_or_else(|| CliError::ProjectNotFound(project.to_string()))?
+        .secrets_in(env)?;
+    let mut names: Vec<&String> = secrets
+        .keys()
+        .filter(|name| manifest::includes(project, name))
+        .collect();
+    names.sort();
+
+    let mut rows: Vec<(String, Option<u64>, Result<SecretBytes, VaultError>)> = names
+        .into_iter()
+        .map(|name| {
+            let value = vault.get_secret_in(project, env, name, encryption_key);
+            (name.clone(), secrets[name].expires_at, value)
+        })
+        .collect();
+
+    // Recorded, along with the read times, before anything is revealed
+    storage::record_access(vault, Operation::Get, Some(project), env, encryption_key);
+
+    if !rows.is_empty() && !confirm_reveal(reveal, rows.len())? {
+        for (_, _, value) in &mut rows {
+            if value.is_ok() {
+                *value = Ok(SecretBytes::new(MASK.as_bytes().to_vec()));
+            }
+        }
+    }
 
-    // If no specific key, show all secrets
-    if key.is_none() {
-        if proj.secrets.is_empty() {
-            println!("No secrets in project '{}'.", project);
-            return Ok(());
+    if format != Format::Text {
+        let mut names = Vec::with_capacity(rows.len());
+        let mut values = Vec::with_capacity(rows.len());
+        for (name, _, value) in rows {
+            names.push(name);
+            values.push(value?);
         }
+        return match format {
+            Format::Dotenv => print_dotenv(&names, &values),
+            _ => print_json(&names, &values),
+        };
+    }
 
-        println!("\n📋 All Secrets in Project '{}'\n", project);
-        println!("{:<30} {:<40} {:<15}", "KEY", "VALUE", "EXPIRY");
-        println!("{}", "─".repeat(85));
+    if rows.is_empty() {
+        println!("No secrets in project '{}'.", project);
+        return Ok(());
+    }
 
-        let now = ttl::current_timestamp();
+    match env {
+        Some(env) => println!("\n📋 All Secrets in Project '{}' ({})\n", project, env),
+        None => println!("\n📋 All Secrets in Project '{}'\n", project),
+    }
+    println!("{:<30} {:<40} {:<15}", "KEY", "VALUE", "EXPIRY");
+    println!("{}", "─".repeat(85));
 
-        for (secret_key, secret) in &proj.secrets {
-            // Decrypt value
-            match vault.get_secret(project, secret_key, &encryption_key) {
-                Ok(value) => {
-                    let value_str = String::from_utf8_lossy(&value);
-                    let value_display = if value_str.len() > 37 {
-                        format!("{}...", &value_str[..37])
-                    } else {
-                        value_str.to_string()
-                    };
+    let now = ttl::current_timestamp();
+
+    for (secret_key, expires_at, value) in &rows {
+        match value {
+            Ok(value) => {
+                let value_str = String::from_utf8_lossy(value);
+                let value_display = if value_str.len() > 37 {
+                    format!("{}...", &value_str[..37])
+                } else {
+                    value_str.to_string()
+                };
 
-                    let expiry_str = if let Some(expires_at) = secret.expires_at {
-                        if expires_at < now {
-                            "EXPIRED".to_string()
+                let expiry_str = if let Some(expires_at) = *expires_at {
+                    if expires_at < now {
+                        "EXPIRED".to_string()
+                    } else {
+                        let remaining = expires_at - now;
+                        let hours = remaining / 3600;
+                        let minutes = (remaining % 3600) / 60;
+                        if hours > 0 {
+                            format!("{}h {}m", hours, minutes)
                         } else {
-                            let remaining = expires_at - now;
-                            let hours = remaining / 3600;
-                            let minutes = (remaining % 3600) / 60;
-                            if hours > 0 {
-                                format!("{}h {}m", hours, minutes)
-                            } else {
-                                format!("{}m", minutes)
-                            }
+                            format!("{}m", minutes)
                         }
-                    } else {
-                        "∞".to_string()
-                    };
-
-                    println!("{:<30} {:<40} {:<15}", secret_key, value_display, expiry_str);
-                }
-                Err(e) => {
-                    eprintln!("⚠️  Secret '{}' decryption failed: {}", secret_key, e);
-                    eprintln!("   This may indicate the vault was corrupted or password is different.");
-                    println!("{:<30} {:<40} {:<15}", secret_key, "[DECRYPTION FAILED]", "—");
-                }
+                    }
+                } else {
+                    "∞".to_string()
+                };
+
+                println!("{:<30} {:<40} {:<15}", secret_key, value_display, expiry_str);
+            }
+            Err(e) => {
+                eprintln!("⚠️  Secret '{}' decryption failed: {}", secret_key, e);
+                eprintln!("   This may indicate the vault was corrupted or password is different.");
+                println!("{:<30} {:<40} {:<15}", secret_key, "[DECRYPTION FAILED]", "—");
             }
         }
-        println!("{}", "─".repeat(85));
-        return Ok(());
+    }
+    println!("{}", "─".repeat(85));
+    Ok(())
+}
+
+/// Decides whether `count` values may be printed: always when stdout is
+/// piped or redirected, and in a terminal with `reveal` or once confirmed.
+/// Without a terminal to confirm on, they are not.
+fn confirm_reveal(reveal: bool, count: usize) -> Result<bool, CliError> {
+    if reveal || !io::stdout().is_terminal() {
+        return Ok(true);
+    }
+
+    let revealed = io::stdin().is_terminal()
+        && input::confirm(&match count {
+            1 => "Show the secret value on screen?".to_string(),
+            n => format!("Show {} secret values on screen?", n),
+        })?;
+    if !revealed {
+        eprintln!("Values hidden; pass --reveal to show them, or pipe the output.");
+    }
+    Ok(revealed)
+}
+
+/// Prints secrets as a JSON object mapping each name to its value.
+fn print_json(names: &[String], values: &[SecretBytes]) -> Result<(), CliError> {
+    let mut object = Map::new();
+    for (name, value) in names.iter().zip(values) {
+        let value = std::str::from_utf8(value).map_err(|_| {
+            CliError::Generic(format!(
+                "Secret '{}' is not valid UTF-8; omit --format json",
+                name
+            ))
+        })?;
+        object.insert(name.to_string(), Value::String(value.to_string()));
     }
 
-    // Get specific secret
-    let key = key.unwrap();
-    let secret_value = vault.get_secret(project, key, &encryption_key)?;
+    let json = serde_json::to_string_pretty(&object).map_err(std::io::Error::from)?;
+    println!("{}", *Zeroizing::new(json));
+    Ok(())
+}
+
+/// Prints secrets as `KEY="value"` lines, escaped as `vx export` does.
+fn print_dotenv(names: &[String], values: &[SecretBytes]) -> Result<(), CliError> {
+    let mut out = Zeroizing::new(Vec::new());
+    for (name, value) in names.iter().zip(values) {
+        let value = Zeroizing::new(String::from_utf8_lossy(value).into_owned());
+        writeln!(out, "{}=\"{}\"", name, *Zeroizing::new(dotenv::escape(&value)))?;
+    }
 
-    // Output to stdout
-    use std::io::{self, Write};
-    io::stdout().write_all(&secret_value)?;
-    io::stdout().flush()?;
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(&out)?;
+    stdout.flush()?;
+    Ok(())
+}
 
-    // Add newline if output is text
-    if secret_value.iter().all(|&b| b != 0 && (b.is_ascii() || b > 127)) {
-        println!();
+/// Copies a secret to the clipboard, waits out the clipboard timeout and
+/// clears it again.
+fn copy_to_clipboard(value: &[u8]) -> Result<(), CliError> {
+    if !desktop::copy_to_clipboard(value) {
+        return Err(CliError::Generic(
+            "No clipboard tool found (install wl-clipboard, xclip or xsel)".to_string(),
+        ));
     }
 
+    let timeout = config::get().clipboard_timeout;
+    println!("✓ Copied to the clipboard; it will be cleared in {}s.", timeout);
+    std::thread::sleep(std::time::Duration::from_secs(timeout));
+
+    desktop::copy_to_clipboard(b"");
+    println!("Clipboard cleared.");
+    Ok(())
+}
+
+/// Prints a secret as a QR code, or with `encrypt` a `vxs1:` string holding
+/// it encrypted under a passphrase, which the web app can open.
+fn show_qr(value: &[u8], encrypt: bool) -> Result<(), CliError> {
+    let shared;
+    let data = if encrypt {
+        let passphrase = input::read_new_password_as("QR passphrase")?;
+        shared = share::seal(value, passphrase.as_bytes())?;
+        shared.as_bytes()
+    } else {
+        value
+    };
+
+    let code = QrCode::new(data).map_err(|e| {
+        CliError::Generic(format!("Cannot show the secret as a QR code: {}", e))
+    })?;
+    // Light modules drawn dark, for terminals with a dark background
+    let image = Zeroizing::new(
+        code.render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .build(),
+    );
+    println!("{}", *image);
     Ok(())
 }
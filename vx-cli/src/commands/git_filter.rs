@@ -0,0 +1,71 @@
+//! git clean/smudge filter keeping files encrypted in a repository.
+//!
+//! Set up with:
+//! ```text
+//! git config filter.vaultx.clean "vx git-filter clean --project app --key REPO_KEY"
+//! git config filter.vaultx.smudge "vx git-filter smudge --project app --key REPO_KEY"
+//! git config filter.vaultx.required true
+//! echo "config/secrets.yml filter=vaultx" >> .gitattributes
+//! ```
+//!
+//! git runs the filter once per file with the contents on stdin and stdout,
+//! so it never prompts: the vault must be unlocked by `vx daemon` or
+//! `VX_KEYFILE`. Reads are not written to the access log, as a checkout
+//! would add an entry per file.
+
+use crate::error::CliError;
+use crate::storage;
+use clap::ValueEnum;
+use std::io::{self, Read, Write};
+use vx_core::git_filter::{decrypt_file, encrypt_file, is_encrypted};
+use vx_core::VaultError;
+use zeroize::Zeroizing;
+
+/// Which way the filter runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    /// Encrypt the working-tree file for the repository
+    Clean,
+    /// Decrypt the repository file for the working tree
+    Smudge,
+}
+
+/// Executes the git-filter command.
+/// Encrypts (clean) or decrypts (smudge) stdin to stdout with the key stored
+/// in `project/key`. Files already in the target form pass through
+/// unchanged, so files committed before the filter was set up still check
+/// out.
+pub fn execute(mode: Mode, project: &str, key: &str, env: Option<&str>) -> Result<(), CliError> {
+    let mut input = Zeroizing::new(Vec::new());
+    io::stdin().read_to_end(&mut input)?;
+
+    let passthrough = match mode {
+        Mode::Clean => is_encrypted(&input),
+        Mode::Smudge => !is_encrypted(&input),
+    };
+    if passthrough {
+        return write_output(&input);
+    }
+
+    let (mut vault, encryption_key) = storage::open_vault_without_prompt()?;
+    vault
+        .unseal_project(project, &encryption_key)
+        .map_err(|e| match e {
+            VaultError::ProjectNotFound(name) => CliError::ProjectNotFound(name),
+            e => CliError::Vault(e),
+        })?;
+    let secret =
+        storage::read_secret_without_prompt(&mut vault, project, env, key, &encryption_key)?;
+
+    match mode {
+        Mode::Clean => write_output(&encrypt_file(&secret, &input)?),
+        Mode::Smudge => write_output(&decrypt_file(&secret, &input)?),
+    }
+}
+
+fn write_output(output: &[u8]) -> Result<(), CliError> {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(output)?;
+    stdout.flush()?;
+    Ok(())
+}
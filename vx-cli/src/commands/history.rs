@@ -0,0 +1,47 @@
+//! Show recent vault operations from the access log.
+
+use crate::error::CliError;
+use crate::storage;
+use vx_core::ttl;
+
+/// Executes the history command.
+/// Shows the newest `limit` operations, optionally only those touching `project`.
+pub fn execute(limit: usize, project: Option<&str>) -> Result<(), CliError> {
+    let vault = storage::load_vault_auto()?;
+
+    let entries: Vec<_> = vault
+        .access_log()
+        .iter()
+        .rev()
+        .filter(|entry| project.is_none() || entry.project.as_deref() == project)
+        .take(limit)
+        .collect();
+
+    if entries.is_empty() {
+        match project {
+            Some(p) => println!("No recorded operations for project '{}'.", p),
+            None => println!("No recorded operations."),
+        }
+        return Ok(());
+    }
+
+    let now = ttl::current_timestamp();
+
+    println!("\n🕘 Recent Operations\n");
+    println!("{:<12} {:<22} {:<14} TARGET", "WHEN", "TIME (UTC)", "OPERATION");
+    println!("{}", "─".repeat(80));
+
+    for entry in entries {
+        println!(
+            "{:<12} {:<22} {:<14} {}",
+            ttl::format_relative(entry.timestamp, now),
+            ttl::format_timestamp(entry.timestamp),
+            entry.operation.to_string(),
+            entry.target()
+        );
+    }
+
+    println!("{}", "─".repeat(80));
+
+    Ok(())
+}
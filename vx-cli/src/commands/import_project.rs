@@ -0,0 +1,84 @@
+//! `vx import-project`: copy a single project out of another vault file.
+//!
+//! Unlike `vx transfer import`, which installs a whole vault, this adds one
+//! project to the current vault and leaves everything else as it is. The
+//! project goes through the same path as `vx archive` and `vx unarchive`,
+//! so its secrets keep their timestamps and expiry and its attachments come
+//! along from the other vault's attachment directory.
+
+use crate::error::CliError;
+use crate::input;
+use crate::progress;
+use crate::storage;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use vx_core::{archive, vault, Operation, VaultError};
+
+/// Executes the import-project command.
+/// Copies `project` from the vault file `file` into the current vault,
+/// under its own name or `rename`.
+pub fn execute(file: &str, project: &str, rename: Option<&str>) -> Result<(), CliError> {
+    let data = fs::read(file).map_err(|_| CliError::FileNotFound(file.to_string()))?;
+    vault::verify_file(&data)?;
+    let name = rename.unwrap_or(project);
+
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    if vault.project_names().contains(&name) {
+        return Err(CliError::Generic(format!(
+            "Project '{}' already exists; import under another name with --as <name>",
+            name
+        )));
+    }
+
+    let password = input::read_password(&format!("Enter master password for {}: ", file))?;
+    let (mut other, other_key) = progress::with_spinner("Unlocking other vault...", || {
+        vault::unlock_vault(&data, password.as_bytes())
+    })?;
+    storage::attach_shards_of(&mut other, Path::new(file))?;
+    other.unseal_project(project, &other_key)?;
+    storage::unlock_project(&mut other, project, &other_key)?;
+
+    let blob_dir = storage::attachments_dir_of(Path::new(file));
+    let blobs: HashMap<String, Vec<u8>> = other.projects[project]
+        .attachments
+        .values()
+        .filter_map(|attachment| {
+            let blob = fs::read(blob_dir.join(&attachment.blob_id)).ok()?;
+            Some((attachment.blob_id.clone(), blob))
+        })
+        .collect();
+    let contents = match archive::collect(&other, project, &other_key, &blobs) {
+        Err(VaultError::AttachmentNotFound(attachment)) => {
+            return Err(CliError::Generic(format!(
+                "Attachment '{}' of project '{}' has no blob in {}",
+                attachment,
+                project,
+                blob_dir.display()
+            )))
+        }
+        contents => contents?,
+    };
+
+    let blobs = archive::restore(&mut vault, &contents, name, &encryption_key)?;
+    // Write the blobs before the vault so the vault never references a
+    // missing blob
+    for (blob_id, blob) in &blobs {
+        storage::write_attachment_blob(blob_id, blob)?;
+    }
+    vault.record(Operation::Add, Some(name), None);
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!(
+        "✓ Project '{}' imported from {} as '{}' with {} secret(s) and {} attachment(s).",
+        project,
+        file,
+        name,
+        contents.project.all_secrets().count(),
+        blobs.len()
+    );
+    if other.projects[project].lock.is_some() {
+        println!("  Its passphrase was not carried over; set one with 'vx passphrase {}'.", name);
+    }
+    Ok(())
+}
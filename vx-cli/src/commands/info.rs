@@ -0,0 +1,103 @@
+//! Show a secret's metadata.
+
+use super::qualified_key;
+use crate::error::CliError;
+use crate::storage;
+use vx_core::{ttl, Operation, SecretKind, SecretRef, VaultError};
+
+/// Executes the info command.
+/// Prints when the secret was created, last read and expires, its size,
+/// where it was last written from, and how often the access log has seen
+/// it written and read, without decrypting it.
+pub fn execute(project: &str, key: &str, env: Option<&str>) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+    vault
+        .unseal_project(project, &encryption_key)
+        .map_err(|e| match e {
+            VaultError::ProjectNotFound(name) => CliError::ProjectNotFound(name),
+            e => CliError::Vault(e),
+        })?;
+
+    let secret = vault.projects[project]
+        .secrets_in(env)?
+        .get(key)
+        .ok_or_else(|| VaultError::SecretNotFound(qualified_key(env, key)))?;
+    let now = ttl::current_timestamp();
+
+    let kind = match &secret.alias {
+        Some(target) => format!("alias of {}", target),
+        None if secret.template => "template".to_string(),
+        None if secret.kind == SecretKind::Json => "JSON document".to_string(),
+        None if secret.kind == SecretKind::Certificate => "certificate".to_string(),
+        None if secret.kind == SecretKind::Database => "database connection".to_string(),
+        None => "value".to_string(),
+    };
+    let expires = match secret.expires_at {
+        Some(expires_at) if expires_at <= now => {
+            format!("{} (expired)", ttl::format_timestamp(expires_at))
+        }
+        Some(expires_at) => format!(
+            "{} ({})",
+            ttl::format_timestamp(expires_at),
+            ttl::format_until(expires_at, now)
+        ),
+        None => "never".to_string(),
+    };
+    let last_read = match secret.last_accessed.get() {
+        Some(timestamp) => format!(
+            "{} ({})",
+            ttl::format_timestamp(timestamp),
+            ttl::format_relative(timestamp, now)
+        ),
+        None => "never".to_string(),
+    };
+
+    println!("Secret:     {}", SecretRef::new(project, env, key));
+    println!("Kind:       {}", kind);
+    println!(
+        "Created:    {} ({})",
+        ttl::format_timestamp(secret.created_at),
+        ttl::format_relative(secret.created_at, now)
+    );
+    // Adds, edits and reads of this secret in the access log; secrets
+    // older than the log count as written once, when created
+    let name = qualified_key(env, key);
+    let logged: Vec<_> = vault
+        .access_log()
+        .iter()
+        .filter(|entry| {
+            entry.project.as_deref() == Some(project) && entry.name.as_deref() == Some(&name)
+        })
+        .collect();
+    let writes: Vec<u64> = logged
+        .iter()
+        .filter(|entry| matches!(entry.operation, Operation::Add | Operation::Edit))
+        .map(|entry| entry.timestamp)
+        .collect();
+    let reads = logged
+        .iter()
+        .filter(|entry| entry.operation == Operation::Get)
+        .count();
+    let last_written = writes.last().copied().unwrap_or(secret.created_at);
+
+    let size = match secret.value_size() {
+        Some(1) => "1 byte".to_string(),
+        Some(size) => format!("{} bytes", size),
+        None => "— (alias)".to_string(),
+    };
+
+    println!("Expires:    {}", expires);
+    println!("Size:       {}", size);
+    println!(
+        "Changes:    {}, the last {}",
+        writes.len().max(1),
+        ttl::format_relative(last_written, now)
+    );
+    match &secret.provenance {
+        Some(provenance) => println!("Written by: {}", provenance),
+        None => println!("Written by: not recorded"),
+    }
+    println!("Last read:  {}", last_read);
+    println!("Reads:      {} logged", reads);
+    Ok(())
+}
@@ -2,44 +2,61 @@
 
 use crate::error::CliError;
 use crate::input;
-use crate::session;
 use crate::storage;
+use clap::ValueEnum;
+use vx_core::crypto::KdfParams;
+
+/// Key derivation profile of a new vault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Security {
+    /// Argon2id with 64 MB and 3 passes; unlocks in well under a second
+    Standard,
+    /// Argon2id with 1 GB and 4 passes; unlocks in several seconds
+    Paranoid,
+}
+
+impl Security {
+    /// Returns the Argon2id parameters of the profile.
+    fn kdf(self) -> KdfParams {
+        match self {
+            Security::Standard => KdfParams::for_this_platform(),
+            Security::Paranoid => KdfParams::PARANOID,
+        }
+    }
+}
 
 /// Executes the init command.
-pub fn execute(project: &str) -> Result<(), CliError> {
-    let (mut vault, password_bytes) = if storage::vault_exists()? {
-        // Load existing vault
-        // Check cache first
-        if let Some(cached) = session::get_cached_password()? {
-            match storage::load_vault_with_key(&cached) {
-                Ok((v, _)) => (v, cached),
-                Err(_) => {
-                    // Cache invalid/stale
-                    let _ = session::clear_cached_password();
-                    let p = input::read_password("Enter master password: ")?;
-                    let (v, _) = storage::load_vault_with_key(p.as_bytes())?;
-                    (v, p.into_bytes())
-                }
-            }
-        } else {
-             let p = input::read_password("Enter master password: ")?;
-             let (v, _) = storage::load_vault_with_key(p.as_bytes())?;
-             (v, p.into_bytes())
+/// Creates the vault first if there is none, deriving its key with the
+/// `security` profile.
+pub fn execute(project: &str, security: Security) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = if storage::vault_exists()? {
+        if security != Security::Standard {
+            eprintln!("⚠️  The vault already exists; --security only applies when creating it.");
         }
+        // Load existing vault (checks the session cache first)
+        storage::load_vault_with_key_auto()?
     } else {
         // Create new vault
         println!("Creating new vault...");
         let password = input::read_new_password()?;
-        let (vault, _key) = storage::create_vault(password.as_bytes())?;
-        (vault, password.into_bytes())
+        let kdf = security.kdf();
+        let created = storage::create_vault_with_kdf(password.as_bytes(), kdf)?;
+        if security == Security::Paranoid {
+            println!(
+                "Vault key derived with {} MB × {} passes; expect unlocking to take several seconds.",
+                kdf.memory_kib / 1024,
+                kdf.iterations
+            );
+        }
+        created
     };
 
     // Initialize the project
     vault.init_project(project)?;
 
     // Save the vault
-    storage::save_vault(&vault, &password_bytes)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
 
     println!("Project '{}' initialized successfully.", project);
     Ok(())
-}
\ No newline at end of file
+}
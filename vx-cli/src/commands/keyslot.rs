@@ -0,0 +1,128 @@
+//! Manage the credentials (key slots) that can unlock the vault.
+
+use crate::error::CliError;
+use crate::input;
+use crate::progress;
+use crate::storage;
+use std::fs;
+use std::path::Path;
+use vx_core::{keyslot, ttl, SlotKind, VaultError};
+
+/// Executes the keyslot add command.
+/// Adds a password slot, or a keyfile / recovery code slot if requested.
+pub fn add(keyfile: Option<&str>, recovery: bool, label: Option<&str>) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+
+    let id = if let Some(path) = keyfile {
+        let contents =
+            fs::read(path).map_err(|_| CliError::FileNotFound(path.to_string()))?;
+        let default_label = Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string());
+
+        let id = progress::with_spinner("Wrapping key...", || {
+            vault.add_key_slot(
+                SlotKind::Keyfile,
+                label.unwrap_or(&default_label),
+                &contents,
+                &encryption_key,
+            )
+        })?;
+        storage::save_vault(&mut vault, &encryption_key)?;
+
+        println!("✓ Keyfile slot {} added.", id);
+        println!("  Unlock with: VX_KEYFILE={} vx <command>", path);
+        id
+    } else if recovery {
+        let code = keyslot::generate_recovery_code();
+        let id = progress::with_spinner("Wrapping key...", || {
+            vault.add_key_slot(
+                SlotKind::RecoveryCode,
+                label.unwrap_or("recovery"),
+                code.as_bytes(),
+                &encryption_key,
+            )
+        })?;
+        storage::save_vault(&mut vault, &encryption_key)?;
+
+        println!("✓ Recovery code slot {} added.\n", id);
+        println!("  {}\n", code.as_str());
+        println!("  Store this code somewhere safe; it will not be shown again.");
+        println!("  Enter it at the master password prompt to unlock the vault.");
+        id
+    } else {
+        let password = input::read_strong_password_as("password for the new slot")?;
+        let id = progress::with_spinner("Wrapping key...", || {
+            vault.add_key_slot(
+                SlotKind::Password,
+                label.unwrap_or("password"),
+                password.as_bytes(),
+                &encryption_key,
+            )
+        })?;
+        storage::save_vault(&mut vault, &encryption_key)?;
+
+        println!("✓ Password slot {} added.", id);
+        id
+    };
+
+    println!(
+        "  The vault now has {} key slots; `vx keyslot remove {}` revokes this one.",
+        vault.key_slots().len(),
+        id
+    );
+    Ok(())
+}
+
+/// Executes the keyslot remove command.
+pub fn remove(id: u32) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+
+    let slot = vault
+        .key_slots()
+        .iter()
+        .find(|slot| slot.id == id)
+        .ok_or(VaultError::KeySlotNotFound(id))?;
+    let description = format!("{} slot {} ('{}')", slot.kind, slot.id, slot.label);
+
+    if !input::confirm(&format!(
+        "Remove {}? Its credential will no longer unlock the vault.",
+        description
+    ))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    vault.remove_key_slot(id)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("✓ Removed {}.", description);
+    Ok(())
+}
+
+/// Executes the keyslot list command.
+pub fn list() -> Result<(), CliError> {
+    let (vault, _encryption_key) = storage::open_vault_with_key_auto()?;
+
+    println!("\n🔑 Key Slots\n");
+    println!("{:<4} {:<15} {:<25} {:<12} CREATED (UTC)", "ID", "KIND", "LABEL", "KDF");
+    println!("{}", "─".repeat(83));
+
+    for slot in vault.key_slots() {
+        let kdf = format!("{} MB × {}", slot.kdf.memory_kib / 1024, slot.kdf.iterations);
+        println!(
+            "{:<4} {:<15} {:<25} {:<12} {}",
+            slot.id,
+            slot.kind.to_string(),
+            slot.label,
+            kdf,
+            ttl::format_timestamp(slot.created_at)
+        );
+    }
+
+    println!("{}", "─".repeat(83));
+    println!("{} of {} slots in use", vault.key_slots().len(), keyslot::MAX_KEY_SLOTS);
+
+    Ok(())
+}
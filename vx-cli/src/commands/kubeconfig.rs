@@ -0,0 +1,79 @@
+//! Manage the kubeconfig files `vx kubectl` runs with.
+
+use crate::error::CliError;
+use crate::input;
+use crate::storage;
+use vx_core::{ttl, VaultError};
+use zeroize::Zeroizing;
+
+/// Executes the kubeconfig add command.
+/// Stores the kubeconfig at `file` under `name`; the file itself is left in
+/// place for the user to delete.
+pub fn add(name: &str, file: &str) -> Result<(), CliError> {
+    let config =
+        Zeroizing::new(std::fs::read(file).map_err(|_| CliError::FileNotFound(file.to_string()))?);
+    if config.is_empty() {
+        return Err(CliError::Generic(format!("'{}' is empty", file)));
+    }
+
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+
+    if vault.kubeconfigs.contains_key(name) {
+        if !input::confirm(&format!("Kubeconfig '{}' already exists. Overwrite?", name))? {
+            println!("Cancelled.");
+            return Ok(());
+        }
+        vault.remove_kubeconfig(name)?;
+    }
+    vault.add_kubeconfig(name, &config, &encryption_key)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("✓ Kubeconfig '{}' stored ({} bytes).", name, config.len());
+    println!("  Run kubectl with it: vx kubectl {} -- get pods", name);
+    println!("  You can now delete {}.", file);
+    Ok(())
+}
+
+/// Executes the kubeconfig list command.
+/// Lists names only; nothing is decrypted.
+pub fn list() -> Result<(), CliError> {
+    let vault = storage::load_vault_auto()?;
+
+    if vault.kubeconfigs.is_empty() {
+        println!("No kubeconfigs stored. Add one with 'vx kubeconfig add <name> <file>'.");
+        return Ok(());
+    }
+
+    let now = ttl::current_timestamp();
+    let mut kubeconfigs: Vec<_> = vault.kubeconfigs.values().collect();
+    kubeconfigs.sort_by(|a, b| a.name.cmp(&b.name));
+
+    println!("Kubeconfigs:");
+    for kubeconfig in kubeconfigs {
+        println!(
+            "  • {} (added {})",
+            kubeconfig.name,
+            ttl::format_relative(kubeconfig.created_at, now)
+        );
+    }
+    Ok(())
+}
+
+/// Executes the kubeconfig remove command.
+pub fn remove(name: &str) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+
+    if !vault.kubeconfigs.contains_key(name) {
+        return Err(VaultError::KubeconfigNotFound(name.to_string()).into());
+    }
+    if !input::confirm(&format!("Remove kubeconfig '{}'?", name))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    vault.remove_kubeconfig(name)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("✓ Kubeconfig '{}' removed.", name);
+    Ok(())
+}
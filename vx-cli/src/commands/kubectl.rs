@@ -0,0 +1,41 @@
+//! Run kubectl with a kubeconfig stored in the vault.
+
+use crate::error::CliError;
+use crate::scratch::ScratchFile;
+use crate::storage;
+use std::process::Command;
+use vx_core::Operation;
+
+/// Executes the kubectl command.
+///
+/// # Security
+/// - The kubeconfig only exists as a `ScratchFile` while kubectl runs
+pub fn execute(name: &str, args: &[String]) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    let config = vault.get_kubeconfig(name, &encryption_key)?;
+    storage::record_access(
+        &mut vault,
+        Operation::Kubectl,
+        None,
+        Some(name),
+        &encryption_key,
+    );
+
+    let config_file = ScratchFile::create("config", config.expose())?;
+    let status = Command::new("kubectl")
+        .args(args)
+        .env("KUBECONFIG", config_file.path())
+        .status();
+    drop(config_file);
+
+    let status =
+        status.map_err(|e| CliError::Generic(format!("Failed to execute kubectl: {}", e)))?;
+    if !status.success() {
+        return Err(CliError::Generic(format!(
+            "kubectl exited with status: {}",
+            status.code().unwrap_or(-1)
+        )));
+    }
+
+    Ok(())
+}
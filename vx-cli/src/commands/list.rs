@@ -1,11 +1,14 @@
 //! List all projects in the vault.
 
+use crate::config::OutputFormat;
 use crate::error::CliError;
 
 use crate::storage;
+use serde_json::json;
+use vx_core::Vault;
 
 /// Executes the list command.
-pub fn execute() -> Result<(), CliError> {
+pub fn execute(format: OutputFormat) -> Result<(), CliError> {
     // Check if vault exists
     if !storage::vault_exists()? {
         println!("No vault found. Run 'vx init <PROJECT>' to create one.");
@@ -15,6 +18,11 @@ pub fn execute() -> Result<(), CliError> {
     // Load vault
     let (vault, _key) = storage::load_vault_with_key_auto()?;
 
+    if format == OutputFormat::Json {
+        println!("{}", to_json(&vault));
+        return Ok(());
+    }
+
     let has_projects = !vault.projects.is_empty();
     let has_ssh = !vault.ssh_identities.is_empty();
     let has_servers = !vault.ssh_servers.is_empty();
@@ -30,7 +38,8 @@ pub fn execute() -> Result<(), CliError> {
         for (name, project) in &vault.projects {
             let secret_count = project.secrets.len();
             let secret_word = if secret_count == 1 { "secret" } else { "secrets" };
-            println!("  • {} ({} {})", name, secret_count, secret_word);
+            let lock = if project.lock.is_some() { " 🔒" } else { "" };
+            println!("  • {} ({} {}){}", name, secret_count, secret_word, lock);
         }
         println!();
     }
@@ -39,8 +48,9 @@ pub fn execute() -> Result<(), CliError> {
     if has_servers {
         println!("SSH Servers:");
         for (name, server) in &vault.ssh_servers {
-            println!("  • {} → {}@{}",
-                name, server.username, server.ip_address);
+            let shared = if server.multiplex.is_some() { " (shared connection)" } else { "" };
+            println!("  • {} → {}@{}{}",
+                name, server.username, server.ip_address, shared);
         }
         println!();
     }
@@ -61,3 +71,33 @@ pub fn execute() -> Result<(), CliError> {
 
     Ok(())
 }
+
+/// Formats the listing as JSON: projects, servers and identities, each
+/// sorted by name.
+fn to_json(vault: &Vault) -> String {
+    let mut projects: Vec<_> = vault
+        .projects
+        .iter()
+        .map(|(name, project)| {
+            json!({
+                "name": name,
+                "secrets": project.secrets.len(),
+                "locked": project.lock.is_some(),
+            })
+        })
+        .collect();
+    projects.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+    let mut servers: Vec<_> = vault.ssh_servers.values().collect();
+    servers.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut identities: Vec<_> = vault.ssh_identities.keys().collect();
+    identities.sort();
+
+    let listing = json!({
+        "projects": projects,
+        "ssh_servers": servers,
+        "ssh_identities": identities,
+    });
+    serde_json::to_string_pretty(&listing).expect("JSON values always serialize")
+}
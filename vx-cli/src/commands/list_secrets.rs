@@ -3,10 +3,21 @@
 use crate::error::CliError;
 
 use crate::storage;
-use vx_core::ttl;
+use clap::ValueEnum;
+use std::collections::HashMap;
+use vx_core::{ttl, Secret};
+
+/// How secrets are ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    /// By key
+    Name,
+    /// Least recently read first, never-read secrets at the top
+    Accessed,
+}
 
 /// Executes the list-secrets command.
-pub fn execute(project: &str) -> Result<(), CliError> {
+pub fn execute(project: &str, sort: SortOrder) -> Result<(), CliError> {
     // Load vault with encryption key
     let (vault, _encryption_key) = storage::load_vault_with_key_auto()?;
 
@@ -16,18 +27,40 @@ pub fn execute(project: &str) -> Result<(), CliError> {
         .get(project)
         .ok_or_else(|| CliError::ProjectNotFound(project.to_string()))?;
 
-    if proj.secrets.is_empty() {
+    if proj.all_secrets().next().is_none() {
         println!("No secrets in project '{}'.", project);
         return Ok(());
     }
 
     println!("Secrets in project '{}':  ", project);
-    
+
     let now = ttl::current_timestamp();
-    
-    for (key, secret) in &proj.secrets {
+    print_secrets(&proj.secrets, sort, now);
+
+    for env in proj.environment_names() {
+        println!("\nEnvironment '{}':", env);
+        print_secrets(&proj.environments[env], sort, now);
+    }
+
+    Ok(())
+}
+
+/// Prints secret names with their expiry status, or the target of aliases,
+/// and when they were last read.
+fn print_secrets(secrets: &HashMap<String, Secret>, sort: SortOrder, now: u64) {
+    let mut secrets: Vec<(&String, &Secret)> = secrets.iter().collect();
+    match sort {
+        SortOrder::Name => secrets.sort_by_key(|(key, _)| *key),
+        SortOrder::Accessed => secrets.sort_by_key(|(key, s)| (s.last_accessed.get(), *key)),
+    }
+
+    for (key, secret) in secrets {
         // Check if expired
-        let status = if let Some(expires_at) = secret.expires_at {
+        let status = if let Some(target) = &secret.alias {
+            format!("→ {}", target)
+        } else if secret.template {
+            "(template)".to_string()
+        } else if let Some(expires_at) = secret.expires_at {
             if expires_at < now {
                 "(expired)".to_string()
             } else {
@@ -44,8 +77,11 @@ pub fn execute(project: &str) -> Result<(), CliError> {
             "(no expiry)".to_string()
         };
         
-        println!("  • {} {}", key, status);
-    }
+        let accessed = match secret.last_accessed.get() {
+            Some(timestamp) => format!("read {}", ttl::format_relative(timestamp, now)),
+            None => "never read".to_string(),
+        };
 
-    Ok(())
+        println!("  • {} {} · {}", key, status, accessed);
+    }
 }
@@ -0,0 +1,59 @@
+//! Show the vault access log.
+
+use crate::error::CliError;
+use crate::storage;
+use vx_core::{ttl, Vault};
+
+/// Executes the log command.
+/// With `verify`, checks the log's hash chain instead of printing it.
+pub fn execute(verify: bool) -> Result<(), CliError> {
+    let vault = storage::load_vault_auto()?;
+
+    if verify {
+        return verify_log(&vault);
+    }
+
+    let entries = vault.access_log();
+
+    if entries.is_empty() {
+        println!("Access log is empty.");
+        return Ok(());
+    }
+
+    println!("\n📜 Access Log\n");
+    println!("{:<22} {:<14} TARGET", "TIME (UTC)", "OPERATION");
+    println!("{}", "─".repeat(70));
+
+    for entry in entries {
+        println!(
+            "{:<22} {:<14} {}",
+            ttl::format_timestamp(entry.timestamp),
+            entry.operation.to_string(),
+            entry.target()
+        );
+    }
+
+    println!("{}", "─".repeat(70));
+    println!("{} entries", entries.len());
+
+    Ok(())
+}
+
+/// Verifies the hash chain and prints the head hash.
+fn verify_log(vault: &Vault) -> Result<(), CliError> {
+    let status = vault.verify_access_log()?;
+
+    println!("✓ Access log intact: {} chained entries.", status.chained);
+    if status.unchained > 0 {
+        println!(
+            "  {} older entries predate hash chaining and cannot be verified.",
+            status.unchained
+        );
+    }
+
+    let head: String = status.head.iter().map(|b| format!("{:02x}", b)).collect();
+    println!("  Head: {}", head);
+    println!("  Compare the head with a previously recorded value to detect truncation.");
+
+    Ok(())
+}
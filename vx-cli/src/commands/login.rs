@@ -1,31 +1,66 @@
 //! Session password caching for VaultX.
 //!
-//! Caches the vault password in an encrypted temporary file to avoid
-//! repeated password prompts within the same session.
+//! Caches the vault password, encrypted, in the kernel keyring or a temporary
+//! file to avoid repeated password prompts within the same session.
 
+use crate::config;
 use crate::error::CliError;
+use crate::expiry;
 use crate::input;
-use crate::session;
+use crate::session::{self, SessionLimits};
 use crate::storage;
+use vx_core::ttl;
 
 /// Executes the login command - caches password for session.
-pub fn execute() -> Result<(), CliError> {
+/// The cache is refused after `timeout` (or `session_timeout` from the
+/// config file), and after `session_idle_timeout` without use. With
+/// `notify_expiring`, also notifies about anything expiring within that
+/// window.
+pub fn execute(timeout: Option<&str>, notify_expiring: Option<&str>) -> Result<(), CliError> {
+    let timeout = match timeout {
+        Some(timeout) => {
+            Some(ttl::parse_ttl(timeout).map_err(|e| CliError::InvalidTtl(e.to_string()))?)
+        }
+        None => config::get().session_timeout,
+    };
+    let window = notify_expiring
+        .map(|w| ttl::parse_ttl(w).map_err(|e| CliError::InvalidTtl(e.to_string())))
+        .transpose()?;
+
     // Verify vault exists
     if !storage::vault_exists()? {
         return Err(CliError::VaultNotFound);
     }
 
     // Get password
-    let password = input::read_password("Enter master password: ")?;
+    let password = input::read_master_password("Enter master password: ")?;
 
     // Verify password is correct by trying to load vault
-    let _ = storage::load_vault(password.as_bytes())?;
+    let vault = storage::load_vault(password.as_bytes())?;
 
     // Cache the password
-    session::cache_password(password.as_bytes())?;
+    let now = ttl::current_timestamp();
+    let limits = SessionLimits {
+        expires_at: timeout.map(|timeout| now.saturating_add(timeout)),
+        idle_timeout: config::get().session_idle_timeout,
+    };
+    session::cache_password(password.as_bytes(), limits)?;
 
     println!("✓ Password cached for current session.");
     println!("Subsequent commands will use cached password.");
+    if let Some(expires_at) = limits.expires_at {
+        println!("The cache expires {}.", ttl::format_until(expires_at, now));
+    }
+    if let Some(idle) = limits.idle_timeout {
+        println!(
+            "It is dropped after {} without use.",
+            ttl::format_duration(idle)
+        );
+    }
+
+    if let Some(window) = window {
+        expiry::notify_expiring(&vault, window, &mut Vec::new());
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,14 +1,71 @@
 //! CLI command implementations.
 
 pub mod add;
+pub mod alias;
+pub mod archive;
+pub mod attach;
+pub mod attachments;
 pub mod audit;
+pub mod backup;
+pub mod daemon;
+pub mod db;
+pub mod detach;
+pub mod direnv;
 pub mod edit;
+pub mod emergency;
+pub mod env;
+pub mod expiring;
+pub mod export;
+pub mod export_readonly;
+pub mod generate;
 pub mod get;
+pub mod git_filter;
+pub mod history;
+pub mod import_project;
+pub mod info;
 pub mod init;
+pub mod keyslot;
+pub mod kubeconfig;
+pub mod kubectl;
 pub mod list;
 pub mod list_secrets;
+pub mod log;
 pub mod login;
+pub mod native_host;
+pub mod origin;
+pub mod passphrase;
+pub mod plugin;
+pub mod policy;
 pub mod remove;
+pub mod restore;
+pub mod rules;
+pub mod scan;
 pub mod scp;
+pub mod snapshot;
 pub mod ssh;
+pub mod ssh_backup;
+pub mod ssh_exec;
+pub mod ssh_group;
+pub mod ssh_hostkey;
+pub mod ssh_pkcs11;
+pub mod ssh_pubkeys;
+pub mod ssh_test;
+pub mod stats;
+pub mod sync;
+pub mod touchid;
+pub mod transfer;
+pub mod trash;
+pub mod ttl;
+pub mod tune;
+pub mod undo;
 pub mod update;
+pub mod wg;
+
+/// Returns `env/key` for a secret in a named environment and `key` for one
+/// in the default environment, as shown in the access log and reports.
+pub(crate) fn qualified_key(env: Option<&str>, key: &str) -> String {
+    match env {
+        Some(env) => format!("{}/{}", env, key),
+        None => key.to_string(),
+    }
+}
@@ -0,0 +1,192 @@
+//! Native messaging host for the VaultX browser extension.
+//!
+//! Chrome and Firefox start `vx native-host` and exchange messages with it
+//! over stdin/stdout: each message is a 32-bit native-endian length followed
+//! by that many bytes of UTF-8 JSON.
+//!
+//! # Requests
+//! - `{"id": 1, "action": "list", "origin": "https://github.com"}` returns
+//!   the secrets the origin may request, as `project/[env/]key` strings
+//! - `{"id": 2, "action": "get", "origin": "https://github.com",
+//!   "project": "web", "key": "TOKEN", "env": null}` returns the value
+//!
+//! Responses echo `id` with `"ok": true` and a `value`, or `"ok": false` and
+//! an `error`.
+//!
+//! # Security Notes
+//! - Only secrets granted to the origin with `vx origin allow` are served
+//! - Every `get` is confirmed by the user in a desktop dialog, and denied if
+//!   no dialog can be shown
+//! - The host never prompts for the master password; the vault must be
+//!   unlocked with `vx login`, `vx daemon` or `VX_KEYFILE`
+
+use crate::desktop;
+use crate::error::CliError;
+use crate::storage;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, Read, Write};
+use vx_core::vault::normalize_origin;
+use vx_core::{Operation, SecretRef, VaultError};
+use zeroize::Zeroizing;
+
+/// Name the host is registered under in the browser's manifest
+pub const HOST_NAME: &str = "com.vaultx.native_host";
+
+/// Largest message accepted from the browser. Chrome caps messages to the
+/// host at 4 GiB, far beyond anything the extension sends.
+const MAX_MESSAGE: u32 = 1024 * 1024;
+
+/// A request from the extension.
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    action: String,
+    origin: String,
+    #[serde(default)]
+    project: Option<String>,
+    #[serde(default)]
+    env: Option<String>,
+    #[serde(default)]
+    key: Option<String>,
+}
+
+/// Executes the native-host command: serves requests until the browser
+/// closes stdin.
+pub fn execute() -> Result<(), CliError> {
+    let mut stdin = io::stdin().lock();
+    let mut stdout = io::stdout().lock();
+
+    while let Some(message) = read_message(&mut stdin)? {
+        let (id, result) = match serde_json::from_slice::<Request>(&message) {
+            Ok(request) => (request.id.clone(), handle(&request)),
+            Err(e) => (
+                Value::Null,
+                Err(CliError::Generic(format!("Invalid request: {}", e))),
+            ),
+        };
+
+        let response = match result {
+            Ok(value) => {
+                Zeroizing::new(json!({ "id": id, "ok": true, "value": value }).to_string())
+            }
+            Err(e) => {
+                Zeroizing::new(json!({ "id": id, "ok": false, "error": e.to_string() }).to_string())
+            }
+        };
+        write_message(&mut stdout, response.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Prints the native messaging manifest registering this binary for the
+/// extension with the given origin (`chrome-extension://<id>/`) or, for
+/// Firefox, add-on id.
+pub fn print_manifest(extension: &str) -> Result<(), CliError> {
+    let path = std::env::current_exe()?;
+
+    let mut manifest = json!({
+        "name": HOST_NAME,
+        "description": "VaultX secrets for the browser extension",
+        "path": path,
+        "type": "stdio",
+    });
+    if extension.contains("://") {
+        manifest["allowed_origins"] = json!([extension]);
+    } else {
+        manifest["allowed_extensions"] = json!([extension]);
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&manifest).map_err(io::Error::from)?
+    );
+    Ok(())
+}
+
+/// Handles one request, returning the response value.
+fn handle(request: &Request) -> Result<Value, CliError> {
+    let origin = normalize_origin(&request.origin)?;
+
+    match request.action.as_str() {
+        "list" => {
+            let (vault, _encryption_key) = storage::open_vault_without_prompt()?;
+            let secrets: Vec<String> = vault
+                .origin_allowed_secrets(&origin)
+                .iter()
+                .map(SecretRef::to_string)
+                .collect();
+            Ok(json!(secrets))
+        }
+        "get" => {
+            let (Some(project), Some(key)) = (&request.project, &request.key) else {
+                return Err(CliError::Generic(
+                    "'get' needs a project and a key".to_string(),
+                ));
+            };
+            let env = request.env.as_deref();
+            let secret = SecretRef::new(project, env, key);
+
+            let (mut vault, encryption_key) = storage::open_vault_without_prompt()?;
+            if !vault.origin_allows(&origin, &secret) {
+                return Err(VaultError::OriginNotAllowed(origin).into());
+            }
+
+            if !desktop::confirm(&format!("Allow {} to read {}?", origin, secret)) {
+                return Err(CliError::Generic("Request denied".to_string()));
+            }
+
+            // Passphrase-locked projects cannot be prompted for here
+            let value =
+                storage::read_secret_without_prompt(&mut vault, project, env, key, &encryption_key)?;
+            let value = std::str::from_utf8(&value)
+                .map_err(|_| CliError::Generic("Secret is not valid UTF-8".to_string()))?
+                .to_string();
+
+            storage::record_access(
+                &mut vault,
+                Operation::Get,
+                Some(project),
+                Some(&super::qualified_key(env, key)),
+                &encryption_key,
+            );
+            Ok(Value::String(value))
+        }
+        other => Err(CliError::Generic(format!("Unknown action '{}'", other))),
+    }
+}
+
+/// Reads one length-prefixed message. Returns `None` when the browser has
+/// closed the pipe.
+fn read_message(reader: &mut impl Read) -> Result<Option<Vec<u8>>, CliError> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let len = u32::from_ne_bytes(len);
+    if len > MAX_MESSAGE {
+        return Err(CliError::Generic(format!(
+            "Message of {} bytes is too large",
+            len
+        )));
+    }
+
+    let mut message = vec![0u8; len as usize];
+    reader.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+/// Writes one length-prefixed message.
+fn write_message(writer: &mut impl Write, message: &[u8]) -> Result<(), CliError> {
+    let len = u32::try_from(message.len())
+        .map_err(|_| CliError::Generic("Response is too large".to_string()))?;
+    writer.write_all(&len.to_ne_bytes())?;
+    writer.write_all(message)?;
+    writer.flush()?;
+    Ok(())
+}
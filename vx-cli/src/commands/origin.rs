@@ -0,0 +1,77 @@
+//! Manage which browser origins may request secrets through `vx native-host`.
+
+use crate::error::CliError;
+use crate::storage;
+use vx_core::vault::normalize_origin;
+use vx_core::SecretRef;
+
+/// Executes the origin allow command.
+/// Lets `origin` request `project/key` (in `env`) from the browser extension.
+pub fn allow(origin: &str, project: &str, key: &str, env: Option<&str>) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+    vault.unseal_project(project, &encryption_key)?;
+
+    let secret = SecretRef::new(project, env, key);
+    vault.allow_origin(origin, secret.clone())?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!(
+        "✓ {} may now request {}.",
+        normalize_origin(origin)?,
+        secret
+    );
+    println!("  Each request is still confirmed in a desktop dialog.");
+    Ok(())
+}
+
+/// Executes the origin revoke command.
+/// Revokes one secret if `project` and `key` are given, otherwise every
+/// secret granted to `origin`.
+pub fn revoke(
+    origin: &str,
+    project: Option<&str>,
+    key: Option<&str>,
+    env: Option<&str>,
+) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+
+    let secret = match (project, key) {
+        (Some(project), Some(key)) => Some(SecretRef::new(project, env, key)),
+        _ => None,
+    };
+    vault.revoke_origin(origin, secret.as_ref())?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    let origin = normalize_origin(origin)?;
+    match secret {
+        Some(secret) => println!("✓ {} may no longer request {}.", origin, secret),
+        None => println!("✓ {} may no longer request any secrets.", origin),
+    }
+    Ok(())
+}
+
+/// Executes the origin list command.
+pub fn list() -> Result<(), CliError> {
+    let (vault, _encryption_key) = storage::open_vault_with_key_auto()?;
+
+    if vault.origin_grants.is_empty() {
+        println!("No origins are allowed. Add one with 'vx origin allow'.");
+        return Ok(());
+    }
+
+    let mut origins: Vec<&String> = vault.origin_grants.keys().collect();
+    origins.sort();
+
+    println!("\n🌐 Allowed Origins\n");
+    println!("{:<40} SECRET", "ORIGIN");
+    println!("{}", "─".repeat(70));
+
+    for origin in origins {
+        for secret in &vault.origin_grants[origin] {
+            println!("{:<40} {}", origin, secret);
+        }
+    }
+
+    println!("{}", "─".repeat(70));
+    Ok(())
+}
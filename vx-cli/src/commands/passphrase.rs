@@ -0,0 +1,48 @@
+//! Set or remove a project's own passphrase.
+
+use crate::error::CliError;
+use crate::input;
+use crate::progress;
+use crate::storage;
+use vx_core::{Operation, VaultError};
+
+/// Executes the passphrase command.
+/// Sets (or changes) the passphrase of a project, or removes it with `remove`.
+pub fn execute(project: &str, remove: bool) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+    vault
+        .unseal_project(project, &encryption_key)
+        .map_err(|e| match e {
+            VaultError::ProjectNotFound(name) => CliError::ProjectNotFound(name),
+            e => CliError::Vault(e),
+        })?;
+
+    let had_passphrase = vault.projects[project].lock.is_some();
+    storage::unlock_project(&mut vault, project, &encryption_key)?;
+
+    if remove {
+        if !had_passphrase {
+            println!("Project '{}' has no passphrase.", project);
+            return Ok(());
+        }
+        vault.remove_project_passphrase(project, &encryption_key)?;
+    } else {
+        let passphrase = input::read_new_password_as("project passphrase")?;
+        progress::with_spinner("Deriving project key...", || {
+            vault.set_project_passphrase(project, passphrase.as_bytes(), &encryption_key)
+        })?;
+    }
+
+    vault.record(Operation::Edit, Some(project), None);
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    if remove {
+        println!("✓ Passphrase removed from project '{}'.", project);
+    } else if had_passphrase {
+        println!("✓ Passphrase changed for project '{}'.", project);
+    } else {
+        println!("✓ Project '{}' now requires its passphrase in addition to the master password.", project);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,217 @@
+//! External subcommands: `vx <name> ...` runs a `vx-<name>` executable
+//! found on `PATH`, as cargo and git do, so integrations can be shipped
+//! without changing `vx`.
+//!
+//! A plugin runs only once the config file enables it with a
+//! `[plugins.<name>]` table, so a stray executable on `PATH` is never run
+//! by a mistyped command. It is told where the vault is (`VX_VAULT_PATH`)
+//! and which `vx` started it (`VX_BIN`). If its table names projects, it
+//! also gets a session token in `VX_SESSION_TOKEN`: the `vx get`, `vx env`
+//! and `vx add` commands it runs are served those projects' secrets by the
+//! `vx` that started it, which never hands over the data key. See
+//! `plugin_session`.
+
+use crate::config::{self, PluginConfig};
+use crate::error::CliError;
+use crate::storage;
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Prefix of plugin executables
+const PREFIX: &str = "vx-";
+
+/// Seconds a plugin's session lasts unless its config says otherwise
+#[cfg(unix)]
+const DEFAULT_SESSION_LIFETIME: u64 = 3600;
+
+/// Executes the plugin named by `args[0]` with the remaining arguments.
+pub fn execute(args: &[String]) -> Result<(), CliError> {
+    let (name, args) = args
+        .split_first()
+        .ok_or_else(|| CliError::Generic("No command given; see 'vx --help'".to_string()))?;
+    let program = find(name).ok_or_else(|| {
+        CliError::Generic(format!(
+            "Unknown command '{}': no {}{} plugin found on PATH; see 'vx --help'",
+            name, PREFIX, name
+        ))
+    })?;
+    let plugin = config::get().plugins.get(name.as_str()).ok_or_else(|| {
+        CliError::Generic(format!(
+            "{} is not enabled; to run it as 'vx {}', add [plugins.{}] to {}",
+            program.display(),
+            name,
+            name,
+            config::path()
+                .map_or_else(|_| "the config file".to_string(), |p| p.display().to_string())
+        ))
+    })?;
+    tracing::debug!(path = %program.display(), "running plugin");
+
+    let mut cmd = Command::new(&program);
+    cmd.args(args).env("VX_VAULT_PATH", storage::vault_dir()?);
+    if let Ok(exe) = env::current_exe() {
+        cmd.env("VX_BIN", exe);
+    }
+
+    #[cfg(unix)]
+    let session = start_session(plugin)?;
+    #[cfg(unix)]
+    match &session {
+        Some(session) => {
+            cmd.env(crate::plugin_session::TOKEN_ENV, session.token());
+        }
+        None => {
+            cmd.env_remove(crate::plugin_session::TOKEN_ENV);
+        }
+    }
+    #[cfg(not(unix))]
+    if !plugin.projects.is_empty() {
+        eprintln!("⚠️  Plugin sessions need unix sockets; {}{} runs without one.", PREFIX, name);
+    }
+
+    let status = cmd.status();
+    #[cfg(unix)]
+    drop(session);
+
+    let status = status.map_err(|e| {
+        CliError::Generic(format!("Failed to execute {}: {}", program.display(), e))
+    })?;
+    if !status.success() {
+        return Err(CliError::Generic(format!(
+            "{}{} exited with status: {}",
+            PREFIX,
+            name,
+            status.code().unwrap_or(-1)
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns the path of the `vx-<name>` executable first on `PATH`.
+fn find(name: &str) -> Option<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return None;
+    }
+    let file_name = format!("{}{}{}", PREFIX, name, env::consts::EXE_SUFFIX);
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+/// Serves the projects named in the plugin's config to it, unlocking the
+/// vault as other commands do. A plugin without projects gets no session.
+#[cfg(unix)]
+fn start_session(
+    plugin: &PluginConfig,
+) -> Result<Option<crate::plugin_session::PluginSession>, CliError> {
+    use crate::plugin_session::{PluginSession, Scope};
+
+    if plugin.projects.is_empty() {
+        return Ok(None);
+    }
+
+    let (_vault, key) = storage::open_vault_with_key_auto()?;
+    let lifetime = plugin.session.unwrap_or(DEFAULT_SESSION_LIFETIME);
+    let scope = Scope {
+        projects: plugin.projects.clone(),
+        write: plugin.write,
+        expires_at: vx_core::ttl::current_timestamp().saturating_add(lifetime),
+    };
+    PluginSession::start(key, scope).map(Some)
+}
+
+/// Executes the get command for a plugin: prints the values of `args`, a
+/// project followed by keys, one per line. The project may be left out as
+/// with `vx get` when it is one the session serves.
+#[cfg(unix)]
+pub fn get(
+    session: &crate::plugin_session::Client,
+    args: &[String],
+    env: Option<&str>,
+) -> Result<(), CliError> {
+    use std::io::Write;
+
+    let (project, keys) = match args.split_first() {
+        Some((first, rest)) if session.list(first, env).is_ok() => (first.clone(), rest),
+        // Let the session say why the project is refused
+        Some((first, rest)) => match config::get().project(None) {
+            Ok(project) => (project, args),
+            Err(_) => (first.clone(), rest),
+        },
+        None => (config::get().project(None)?, args),
+    };
+    if keys.is_empty() {
+        return Err(CliError::Generic(
+            "Plugins must name the keys to read".to_string(),
+        ));
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    for key in keys {
+        let value = session.get(&project, env, key)?;
+        stdout.write_all(&value)?;
+        stdout.write_all(b"\n")?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Executes the env command for a plugin; see `env::execute`.
+#[cfg(unix)]
+pub fn env(
+    session: &crate::plugin_session::Client,
+    project: &str,
+    env: Option<&str>,
+    prefix: &str,
+) -> Result<(), CliError> {
+    use std::io::Write;
+    use zeroize::Zeroizing;
+
+    let mut out = Zeroizing::new(Vec::new());
+    for key in session.list(project, env)? {
+        let name = format!("{}{}", prefix, key);
+        if !super::env::is_variable_name(&name) {
+            eprintln!("⚠️  Skipping '{}': not a valid variable name", name);
+            continue;
+        }
+        let value = match session.get(project, env, &key) {
+            Ok(value) if !value.contains(&0) => value,
+            Ok(_) | Err(_) => {
+                eprintln!("⚠️  Skipping '{}'", key);
+                continue;
+            }
+        };
+        out.write_all(format!("export {}=", name).as_bytes())?;
+        out.write_all(&super::env::quote(&value))?;
+        out.write_all(b"\n")?;
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(&out)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Executes the add command for a plugin whose session may write: stores
+/// the value read from `file` or `from_env` as `key`.
+#[cfg(unix)]
+pub fn add(
+    session: &crate::plugin_session::Client,
+    project: &str,
+    key: &str,
+    env: Option<&str>,
+    file: Option<&str>,
+    from_env: Option<&str>,
+) -> Result<(), CliError> {
+    if file.is_none() && from_env.is_none() {
+        return Err(CliError::Generic(
+            "Plugins must pass the value with --file or --from-env".to_string(),
+        ));
+    }
+    let value = crate::input::read_secret(file, from_env)?;
+    session.set(project, env, key, &value)?;
+    println!("Secret '{}' added to project '{}'.", key, project);
+    Ok(())
+}
@@ -0,0 +1,101 @@
+//! Show or set how a project generates its secrets.
+
+use crate::error::CliError;
+use crate::storage;
+use vx_core::generate::Charset;
+use vx_core::{ttl, Operation};
+
+/// Policy settings to change, as given to `vx policy`
+pub struct Changes {
+    pub length: Option<usize>,
+    pub charset: Option<Charset>,
+    /// Rotation interval, e.g. `90d`
+    pub rotate: Option<String>,
+    /// Glob selecting the keys the policy applies to
+    pub keys: Option<String>,
+    /// Remove the policy before applying the other settings
+    pub clear: bool,
+}
+
+impl Changes {
+    fn is_empty(&self) -> bool {
+        self.length.is_none()
+            && self.charset.is_none()
+            && self.rotate.is_none()
+            && self.keys.is_none()
+            && !self.clear
+    }
+}
+
+/// Executes the policy command.
+/// Prints the project's generation policy, after applying `changes` if
+/// there are any. Settings not given keep their current (or default)
+/// value.
+pub fn execute(project: &str, changes: Changes) -> Result<(), CliError> {
+    let rotate_after = changes
+        .rotate
+        .as_deref()
+        .map(|rotate| ttl::parse_ttl(rotate).map_err(|e| CliError::InvalidTtl(e.to_string())))
+        .transpose()?;
+
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    let mut policy = vault
+        .projects
+        .get(project)
+        .ok_or_else(|| CliError::ProjectNotFound(project.to_string()))?
+        .generation
+        .clone();
+
+    if !changes.is_empty() {
+        if changes.clear {
+            policy = None;
+        }
+        let settings = [
+            changes.length.is_some(),
+            changes.charset.is_some(),
+            rotate_after.is_some(),
+            changes.keys.is_some(),
+        ];
+        if settings.contains(&true) {
+            let policy = policy.get_or_insert_with(Default::default);
+            if let Some(length) = changes.length {
+                policy.length = length;
+            }
+            if let Some(charset) = changes.charset {
+                policy.charset = charset;
+            }
+            if rotate_after.is_some() {
+                policy.rotate_after = rotate_after;
+            }
+            if let Some(keys) = changes.keys {
+                policy.keys = Some(keys);
+            }
+        }
+
+        vault.set_generation_policy(project, policy.clone())?;
+        vault.record(Operation::Edit, Some(project), None);
+        storage::save_vault(&mut vault, &encryption_key)?;
+        println!("✓ Generation policy of project '{}' updated.", project);
+    }
+
+    let Some(policy) = policy else {
+        println!(
+            "Project '{}' has no generation policy; vx generate makes {} {} characters.",
+            project,
+            vx_core::generate::DEFAULT_LENGTH,
+            Charset::default()
+        );
+        return Ok(());
+    };
+    println!("Generation policy of project '{}':", project);
+    println!("  Length:     {}", policy.length);
+    println!("  Charset:    {}", policy.charset);
+    if let Some(rotate_after) = policy.rotate_after {
+        println!("  Rotate:     every {}", ttl::format_duration(rotate_after));
+    }
+    println!(
+        "  Applies to: {}",
+        policy.keys.as_deref().unwrap_or("every secret")
+    );
+    Ok(())
+}
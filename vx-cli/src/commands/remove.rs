@@ -1,25 +1,15 @@
+use super::qualified_key;
 use crate::error::CliError;
 use crate::input;
-use crate::session;
 use crate::storage;
+use vx_core::{Operation, SecretRef, Vault, VaultError};
 
-pub fn execute(project: &str, key: Option<&str>) -> Result<(), CliError> {
+pub fn execute(project: &str, key: Option<&str>, env: Option<&str>) -> Result<(), CliError> {
     // Load vault with encryption key
-    let (mut vault, password_bytes) = if let Some(cached) = session::get_cached_password()? {
-        match storage::load_vault_with_key(&cached) {
-            Ok((v, _)) => (v, cached),
-            Err(_) => {
-                let _ = session::clear_cached_password();
-                let p = input::read_password("Enter master password: ")?;
-                let (v, _) = storage::load_vault_with_key(p.as_bytes())?;
-                (v, p.into_bytes())
-            }
-        }
-    } else {
-         let p = input::read_password("Enter master password: ")?;
-         let (v, _) = storage::load_vault_with_key(p.as_bytes())?;
-         (v, p.into_bytes())
-    };
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+
+    // Aliases that currently resolve, to detect the ones this removal breaks
+    let resolvable = resolvable_aliases(&vault);
 
     if let Some(k) = key {
         // Remove secret
@@ -27,20 +17,124 @@ pub fn execute(project: &str, key: Option<&str>) -> Result<(), CliError> {
             println!("Cancelled.");
             return Ok(());
         }
-        vault.remove_secret(project, k)?;
+        let id = vault.trash_secret(project, env, k)?;
         println!("Secret '{}' removed from project '{}'.", k, project);
+        print_restore_hint(id);
+    } else if let Some(env) = env {
+        // Remove environment
+        if !input::confirm(&format!("Are you sure you want to remove environment '{}' and ALL its secrets from project '{}'?", env, project))? {
+            println!("Cancelled.");
+            return Ok(());
+        }
+        let id = vault.trash_environment(project, env)?;
+        println!("Environment '{}' removed from project '{}'.", env, project);
+        print_restore_hint(id);
     } else {
         // Remove project
         if !input::confirm(&format!("Are you sure you want to remove project '{}' and ALL its secrets?", project))? {
             println!("Cancelled.");
             return Ok(());
         }
-        vault.remove_project(project)?;
+        let id = vault.trash_project(project, &encryption_key)?;
         println!("Project '{}' removed.", project);
+        print_restore_hint(id);
     }
 
+    warn_dangling_aliases(&vault, resolvable);
+
+    let logged = match (key, env) {
+        (Some(k), env) => Some(qualified_key(env, k)),
+        (None, env) => env.map(str::to_string),
+    };
+    vault.record(Operation::Remove, Some(project), logged.as_deref());
+
     // Save vault
-    storage::save_vault(&vault, &password_bytes)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    Ok(())
+}
+
+/// Removes every secret in the project (or in one of its environments)
+/// whose key matches the glob `pattern`, after listing them and asking
+/// once. With `dry_run`, only lists them.
+pub fn remove_matching(
+    project: &str,
+    pattern: &str,
+    env: Option<&str>,
+    dry_run: bool,
+) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+
+    let keys = vault.matching_keys(project, env, pattern)?;
+    if keys.is_empty() {
+        println!("No secrets in project '{}' match '{}'.", project, pattern);
+        return Ok(());
+    }
 
+    println!(
+        "{} secret(s) in project '{}' match '{}':",
+        keys.len(),
+        project,
+        pattern
+    );
+    for key in &keys {
+        println!("  {}", qualified_key(env, key));
+    }
+    if dry_run {
+        println!("Dry run; nothing was removed.");
+        return Ok(());
+    }
+    if !input::confirm(&format!("Remove these {} secret(s)?", keys.len()))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let resolvable = resolvable_aliases(&vault);
+    let removed = vault.remove_matching(project, env, pattern)?;
+    println!("{} secret(s) removed from project '{}'.", removed.len(), project);
+    println!("They are kept in the trash; restore them with 'vx trash restore <id>':");
+    for (key, id) in &removed {
+        println!("  {:>4}  {}", id, qualified_key(env, key));
+    }
+
+    warn_dangling_aliases(&vault, resolvable);
+
+    for (key, _) in &removed {
+        vault.record(
+            Operation::Remove,
+            Some(project),
+            Some(&qualified_key(env, key)),
+        );
+    }
+    storage::save_vault(&mut vault, &encryption_key)?;
     Ok(())
 }
+
+/// Warns about each of the `resolvable` aliases that no longer resolves.
+fn warn_dangling_aliases(vault: &Vault, resolvable: Vec<SecretRef>) {
+    for alias in resolvable {
+        if let Err(VaultError::DanglingAlias(_)) =
+            vault.resolve_alias(&alias.project, alias.env.as_deref(), &alias.key)
+        {
+            eprintln!("⚠️  Alias '{}' now refers to a secret that no longer exists", alias);
+        }
+    }
+}
+
+fn print_restore_hint(id: u32) {
+    println!("It is kept in the trash; restore it with 'vx trash restore {}'.", id);
+}
+
+/// Returns the location of every alias whose chain currently resolves.
+fn resolvable_aliases(vault: &Vault) -> Vec<SecretRef> {
+    vault
+        .aliases()
+        .into_iter()
+        .map(|(location, _)| location)
+        .filter(|alias| {
+            vault
+                .resolve_alias(&alias.project, alias.env.as_deref(), &alias.key)
+                .is_ok()
+        })
+        .collect()
+}
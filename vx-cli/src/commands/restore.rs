@@ -0,0 +1,35 @@
+//! Regain access to the vault from a paper backup.
+
+use crate::error::CliError;
+use crate::input;
+use crate::progress;
+use crate::storage;
+use vx_core::{mnemonic, SlotKind};
+
+/// Executes the restore mnemonic command.
+/// Unlocks the vault with the recovery phrase from `vx backup mnemonic` and
+/// adds a password slot for a new master password.
+pub fn mnemonic() -> Result<(), CliError> {
+    println!(
+        "Enter the {} words of the recovery phrase, separated by spaces.",
+        mnemonic::WORD_COUNT
+    );
+    let phrase = input::read_password("Recovery phrase: ")?;
+    let key = mnemonic::to_key(&phrase)?;
+    let mut vault = storage::open_vault_with_recovered_key(&key)?;
+
+    println!("✓ Recovery phrase accepted. Choose a new master password.");
+    let password = input::read_new_password()?;
+    let id = progress::with_spinner("Wrapping key...", || {
+        vault.add_key_slot(SlotKind::Password, "restored", password.as_bytes(), &key)
+    })?;
+    storage::save_vault(&mut vault, &key)?;
+
+    println!(
+        "✓ Password slot {} added; the vault unlocks with the new password.",
+        id
+    );
+    println!("  Remove the slots of lost credentials with 'vx keyslot list' and");
+    println!("  'vx keyslot remove <ID>'.");
+    Ok(())
+}
@@ -0,0 +1,96 @@
+//! Show or set the validation rules of a project.
+
+use crate::error::CliError;
+use crate::storage;
+use vx_core::rules::SecretRules;
+use vx_core::{ttl, Operation};
+
+/// Rules to change, as given to `vx rules`
+pub struct Changes {
+    pub key_pattern: Option<String>,
+    pub require_ttl: bool,
+    /// Longest TTL allowed, e.g. `30d`
+    pub max_ttl: Option<String>,
+    pub max_size: Option<u64>,
+    /// Patterns added to the forbidden ones
+    pub forbid: Vec<String>,
+    /// Remove every rule before applying the others
+    pub clear: bool,
+}
+
+impl Changes {
+    fn is_empty(&self) -> bool {
+        self.key_pattern.is_none()
+            && !self.require_ttl
+            && self.max_ttl.is_none()
+            && self.max_size.is_none()
+            && self.forbid.is_empty()
+            && !self.clear
+    }
+}
+
+/// Executes the rules command.
+/// Prints the project's rules, after applying `changes` if there are any.
+pub fn execute(project: &str, changes: Changes) -> Result<(), CliError> {
+    let max_ttl = changes
+        .max_ttl
+        .as_deref()
+        .map(|max| ttl::parse_ttl(max).map_err(|e| CliError::InvalidTtl(e.to_string())))
+        .transpose()?;
+
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    let mut rules = vault
+        .projects
+        .get(project)
+        .ok_or_else(|| CliError::ProjectNotFound(project.to_string()))?
+        .rules
+        .clone();
+
+    if !changes.is_empty() {
+        if changes.clear {
+            rules = SecretRules::default();
+        }
+        if let Some(pattern) = changes.key_pattern {
+            rules.key_pattern = Some(pattern);
+        }
+        rules.require_ttl |= changes.require_ttl;
+        if max_ttl.is_some() {
+            rules.max_ttl = max_ttl;
+        }
+        if let Some(max) = changes.max_size {
+            rules.max_value_size = Some(max);
+        }
+        for pattern in changes.forbid {
+            if !rules.forbidden_patterns.contains(&pattern) {
+                rules.forbidden_patterns.push(pattern);
+            }
+        }
+
+        vault.set_rules(project, rules.clone())?;
+        vault.record(Operation::Edit, Some(project), None);
+        storage::save_vault(&mut vault, &encryption_key)?;
+        println!("✓ Rules of project '{}' updated.", project);
+    }
+
+    if rules.is_empty() {
+        println!("Project '{}' has no rules.", project);
+        return Ok(());
+    }
+    println!("Rules of project '{}':", project);
+    if let Some(pattern) = &rules.key_pattern {
+        println!("  Keys must match:   {}", pattern);
+    }
+    if rules.require_ttl {
+        println!("  Every secret must have a TTL");
+    }
+    if let Some(max_ttl) = rules.max_ttl {
+        println!("  Longest TTL:       {}", ttl::format_duration(max_ttl));
+    }
+    if let Some(max) = rules.max_value_size {
+        println!("  Largest value:     {} bytes", max);
+    }
+    for pattern in &rules.forbidden_patterns {
+        println!("  Values must not contain: {}", pattern);
+    }
+    Ok(())
+}
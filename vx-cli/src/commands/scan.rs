@@ -0,0 +1,283 @@
+//! Scan a directory, and optionally its git history, for stored secret
+//! values.
+
+use crate::error::CliError;
+use crate::progress::Spinner;
+use crate::storage;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use vx_core::scan::{LeakMatch, LeakScanner, MIN_VALUE_LEN};
+use vx_core::{SecretRef, VaultError};
+
+/// A place where a stored value was found.
+struct Finding {
+    /// File path, with the commit that added it for history findings
+    location: String,
+    line: u64,
+    secret: usize,
+}
+
+/// Executes the scan command.
+/// Looks for the values stored in `project` (by default every project) in
+/// the files under `path`, and with `history` in every blob reachable in
+/// the git repository at `path`. Values are compared as salted hashes and
+/// never printed; only where they were found is.
+pub fn execute(path: &str, project: Option<&str>, history: bool) -> Result<(), CliError> {
+    let root = Path::new(path);
+    if !root.exists() {
+        return Err(CliError::FileNotFound(path.to_string()));
+    }
+
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+    let projects: Vec<String> = match project {
+        Some(project) => {
+            vault
+                .unseal_project(project, &encryption_key)
+                .map_err(|e| match e {
+                    VaultError::ProjectNotFound(name) => CliError::ProjectNotFound(name),
+                    e => CliError::Vault(e),
+                })?;
+            storage::unlock_project(&mut vault, project, &encryption_key)?;
+            vec![project.to_string()]
+        }
+        None => {
+            vault.unseal_all(&encryption_key)?;
+            vault
+                .project_names()
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        }
+    };
+
+    let mut scanner = LeakScanner::new();
+    let mut secrets: Vec<SecretRef> = Vec::new();
+    let mut too_short = 0;
+    for name in &projects {
+        if vault.is_locked(name) {
+            eprintln!(
+                "⚠️  Skipping passphrase-locked project '{}'; scan it with --project {}",
+                name, name
+            );
+            continue;
+        }
+        for (secret, value) in vault.stored_values(name, &encryption_key)? {
+            match scanner.add(&value) {
+                Some(_) => secrets.push(secret),
+                None => too_short += 1,
+            }
+        }
+    }
+
+    if too_short > 0 {
+        eprintln!(
+            "⚠️  {} value(s) shorter than {} bytes are not scanned for",
+            too_short, MIN_VALUE_LEN
+        );
+    }
+    if scanner.is_empty() {
+        println!("No secret values to scan for.");
+        return Ok(());
+    }
+
+    let spinner = Spinner::start("Scanning files...");
+    let mut findings = Vec::new();
+    let mut scanned = 0;
+    scan_dir(&scanner, root, &mut findings, &mut scanned)?;
+
+    if history {
+        spinner.set_message("Scanning git history...");
+        scan_history(&scanner, root, &mut findings, &mut scanned)?;
+    }
+    drop(spinner);
+
+    if findings.is_empty() {
+        println!(
+            "✓ None of {} secret value(s) found in {} file(s).",
+            scanner.len(),
+            scanned
+        );
+        return Ok(());
+    }
+
+    println!("{:<60} SECRET", "LOCATION");
+    println!("{}", "─".repeat(80));
+    for finding in &findings {
+        println!(
+            "{:<60} {}",
+            format!("{}:{}", finding.location, finding.line),
+            secrets[finding.secret]
+        );
+    }
+    println!("{}", "─".repeat(80));
+
+    let leaked: HashSet<usize> = findings.iter().map(|f| f.secret).collect();
+    Err(CliError::Generic(format!(
+        "{} secret(s) found in {} place(s); rotate them before removing them from history",
+        leaked.len(),
+        findings.len()
+    )))
+}
+
+/// Scans the files under `dir`, skipping `.git` and symbolic links.
+fn scan_dir(
+    scanner: &LeakScanner,
+    dir: &Path,
+    findings: &mut Vec<Finding>,
+    scanned: &mut usize,
+) -> Result<(), CliError> {
+    if fs::symlink_metadata(dir)?.is_file() {
+        return scan_file(scanner, dir, findings, scanned);
+    }
+
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+
+    for path in entries {
+        let file_type = fs::symlink_metadata(&path)?.file_type();
+        if file_type.is_dir() {
+            if path.file_name().is_some_and(|name| name == ".git") {
+                continue;
+            }
+            scan_dir(scanner, &path, findings, scanned)?;
+        } else if file_type.is_file() {
+            scan_file(scanner, &path, findings, scanned)?;
+        }
+    }
+    Ok(())
+}
+
+/// Scans one file. Unreadable files are reported and skipped.
+fn scan_file(
+    scanner: &LeakScanner,
+    path: &Path,
+    findings: &mut Vec<Finding>,
+    scanned: &mut usize,
+) -> Result<(), CliError> {
+    let matches = match File::open(path).and_then(|file| scanner.scan(file)) {
+        Ok(matches) => matches,
+        Err(e) => {
+            eprintln!("⚠️  Skipping {}: {}", path.display(), e);
+            return Ok(());
+        }
+    };
+
+    *scanned += 1;
+    record(findings, &path.display().to_string(), matches);
+    Ok(())
+}
+
+/// Scans every blob reachable from any ref of the repository at `repo`,
+/// reading them with one `git cat-file --batch`.
+fn scan_history(
+    scanner: &LeakScanner,
+    repo: &Path,
+    findings: &mut Vec<Finding>,
+    scanned: &mut usize,
+) -> Result<(), CliError> {
+    let output = git(repo)
+        .args(["rev-list", "--all", "--objects"])
+        .output()
+        .map_err(|e| CliError::Generic(format!("Failed to run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(CliError::Generic(format!(
+            "git rev-list failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    // Trees and blobs are listed with their path; commits without one
+    let mut objects = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some((id, path)) = line.split_once(' ') {
+            objects.push((id.to_string(), path.to_string()));
+        }
+    }
+
+    let mut child = git(repo)
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| CliError::Generic(format!("Failed to run git: {}", e)))?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let ids: Vec<String> = objects.iter().map(|(id, _)| id.clone()).collect();
+    let writer = thread::spawn(move || -> io::Result<()> {
+        for id in ids {
+            writeln!(stdin, "{}", id)?;
+        }
+        Ok(())
+    });
+
+    let mut stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    for (id, path) in &objects {
+        // Each object is "<id> <type> <size>\n", its contents and "\n"
+        let mut header = String::new();
+        stdout.read_line(&mut header)?;
+        let mut fields = header.split_whitespace().skip(1);
+        let (Some(kind), Some(size)) = (fields.next(), fields.next()) else {
+            return Err(CliError::Generic(format!(
+                "Unexpected git cat-file output for {}",
+                id
+            )));
+        };
+        let size: u64 = size
+            .parse()
+            .map_err(|_| CliError::Generic(format!("Unexpected git cat-file output for {}", id)))?;
+
+        let mut contents = (&mut stdout).take(size);
+        if kind == "blob" {
+            let matches = scanner.scan(&mut contents)?;
+            *scanned += 1;
+            if !matches.is_empty() {
+                let location = format!("{} ({})", path, first_commit(repo, id));
+                record(findings, &location, matches);
+            }
+        }
+        io::copy(&mut contents, &mut io::sink())?;
+        stdout.read_line(&mut String::new())?;
+    }
+
+    writer
+        .join()
+        .map_err(|_| CliError::Generic("git cat-file writer panicked".to_string()))??;
+    child.wait()?;
+    Ok(())
+}
+
+/// Returns the abbreviated id of the earliest commit that adds the blob `id`.
+fn first_commit(repo: &Path, id: &str) -> String {
+    git(repo)
+        .args(["log", "--all", "--reverse", "--format=%h"])
+        .arg(format!("--find-object={}", id))
+        .output()
+        .ok()
+        .and_then(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .next()
+                .map(|commit| format!("commit {}", commit))
+        })
+        .unwrap_or_else(|| format!("blob {}", &id[..id.len().min(12)]))
+}
+
+fn git(repo: &Path) -> Command {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(repo);
+    command
+}
+
+fn record(findings: &mut Vec<Finding>, location: &str, matches: Vec<LeakMatch>) {
+    findings.extend(matches.into_iter().map(|m| Finding {
+        location: location.to_string(),
+        line: m.line,
+        secret: m.value,
+    }));
+}
@@ -1,63 +1,91 @@
 //! Secure copy (SCP) command implementation.
+//!
+//! Remote paths are written `:path` after a server name
+//! (`vx scp web1 :/etc/hosts .`), or `server:path` with the server in each
+//! operand (`vx scp web1:/etc/hosts .`). The latter also copies between
+//! two configured servers, relayed through this machine (`scp -3`) so
+//! that each server is reached with its own identity and neither needs
+//! access to the other.
 
+use super::ssh_hostkey::TrustedHostKeys;
+use crate::agent::{IdentityKey, KeyAgent};
 use crate::error::CliError;
 use crate::storage;
-use std::fs;
-use std::io::Write;
 use std::process::Command;
-use vx_core::ssh;
+use vx_core::{ssh, Vault, KEY_SIZE};
+
+/// scp options that take the next argument as their value
+const OPTIONS_WITH_VALUE: [&str; 10] = ["-c", "-D", "-F", "-i", "-J", "-l", "-o", "-P", "-S", "-X"];
 
 /// Executes the scp command.
-pub fn execute(server_name: &str, args: &[String]) -> Result<(), CliError> {
+/// `name` is a server, a `server:path` operand, or a server group whose
+/// members are copied with one after another; a failed copy does not stop
+/// the others.
+pub fn execute(name: &str, args: &[String]) -> Result<(), CliError> {
     // Load vault with encryption key (auto-cached)
-    let (vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    if vault.has_ssh_server(name) {
+        return copy(&mut vault, name, args, &encryption_key);
+    }
+    if remote_operand(&vault, name).is_some() {
+        let operands: Vec<String> = std::iter::once(name.to_string())
+            .chain(args.iter().cloned())
+            .collect();
+        return copy_operands(&mut vault, &operands, &encryption_key);
+    }
+    if !vault.ssh_server_groups.contains_key(name) {
+        return Err(CliError::SshError(format!("Server '{}' not found", name)));
+    }
+
+    let servers = vault.expand_ssh_servers(&[name])?;
+    let mut failed = Vec::new();
+    for server in &servers {
+        println!("── {} ──", server);
+        if let Err(e) = copy(&mut vault, server, args, &encryption_key) {
+            eprintln!("Error: {}", e);
+            failed.push(server.as_str());
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(CliError::SshError(format!(
+            "{} of {} servers failed: {}",
+            failed.len(),
+            servers.len(),
+            failed.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Runs scp with the server `server_name`.
+fn copy(
+    vault: &mut Vault,
+    server_name: &str,
+    args: &[String],
+    encryption_key: &[u8; KEY_SIZE],
+) -> Result<(), CliError> {
+    let host_keys = super::ssh_hostkey::verify(vault, server_name, encryption_key)?;
 
     // Get server config
     let server = vault
         .get_ssh_server(server_name)
         .map_err(|_| CliError::SshError(format!("Server '{}' not found", server_name)))?;
 
-    // Get SSH identity
-    let (_public_key, private_key_bytes) =
-        vault.get_ssh_identity(&server.identity_name, &encryption_key)?;
-
-    // Reconstruct signing key and format private key
-    let signing_key = ssh::reconstruct_signing_key(&private_key_bytes)
-        .map_err(|e| CliError::SshError(format!("Invalid key format: {}", e)))?;
-
-    let public_key_bytes = signing_key.verifying_key();
-    let private_key_pem = ssh::format_private_key(&private_key_bytes, public_key_bytes.as_bytes())
-        .map_err(|e| CliError::SshError(format!("Failed to format private key: {}", e)))?;
-
-    // Create temp file for private key
-    let temp_dir = tempfile::tempdir()?;
-    let key_path = temp_dir.path().join("id_temp");
-
-    // Write private key with restricted permissions
-    {
-        let mut file = fs::File::create(&key_path)?;
-
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let permissions = fs::Permissions::from_mode(0o600);
-            file.set_permissions(permissions)?;
-        }
-
-        file.write_all(private_key_pem.as_bytes())?;
-        file.sync_all()?;
-    }
+    // Offer the key to scp's ssh without writing it to disk
+    let key = super::ssh::start_identity(vault, &server.identity_name, encryption_key)?;
 
     // Build SCP command
     let mut cmd = Command::new("scp");
-    cmd.arg("-i").arg(&key_path);
-    
+    key.configure(&mut cmd);
+    host_keys.configure(&mut cmd);
+    super::ssh::add_multiplex_options(&mut cmd, server.multiplex)?;
+
     // Process arguments to replace ':' prefix with 'user@host:'
     for arg in args {
-        if arg.starts_with(':') {
+        if let Some(path_part) = arg.strip_prefix(':') {
             // It's a remote path: :path/to/file -> user@host:path/to/file
             // or just : -> user@host:
-            let path_part = &arg[1..];
             let remote_arg = if path_part.is_empty() {
                 format!("{}@{}:", server.username, server.ip_address)
             } else {
@@ -70,9 +98,142 @@ pub fn execute(server_name: &str, args: &[String]) -> Result<(), CliError> {
         }
     }
 
-    println!("Executing secure copy with identity '{}'...", server.identity_name);
+    println!(
+        "Executing secure copy with identity '{}'...",
+        server.identity_name
+    );
+    run(cmd)
+}
+
+/// Copies with `server:path` operands. With one server this is the same
+/// as `vx scp <server>` with `:path` operands; with two, the copy is
+/// relayed between them.
+fn copy_operands(
+    vault: &mut Vault,
+    operands: &[String],
+    encryption_key: &[u8; KEY_SIZE],
+) -> Result<(), CliError> {
+    let mut servers: Vec<String> = Vec::new();
+    for (server, _) in operands.iter().filter_map(|arg| remote_operand(vault, arg)) {
+        if !servers.iter().any(|s| s == server) {
+            servers.push(server.to_string());
+        }
+    }
+
+    match servers.as_slice() {
+        [server] => {
+            let args: Vec<String> = operands
+                .iter()
+                .map(|arg| match remote_operand(vault, arg) {
+                    Some((_, path)) => format!(":{}", path),
+                    None => arg.clone(),
+                })
+                .collect();
+            copy(vault, &server.clone(), &args, encryption_key)
+        }
+        [from, to] => copy_between(vault, from, to, operands, encryption_key),
+        _ => Err(CliError::Generic(
+            "vx scp copies between at most two servers".to_string(),
+        )),
+    }
+}
+
+/// Copies between the servers `from` and `to` through this machine.
+fn copy_between(
+    vault: &mut Vault,
+    from: &str,
+    to: &str,
+    operands: &[String],
+    encryption_key: &[u8; KEY_SIZE],
+) -> Result<(), CliError> {
+    for server in [from, to] {
+        super::ssh_hostkey::verify(vault, server, encryption_key)?;
+    }
+    let host_keys = TrustedHostKeys::for_servers(vault, &[from, to])?;
+
+    let identities = [
+        vault.get_ssh_server(from)?.identity_name.clone(),
+        vault.get_ssh_server(to)?.identity_name.clone(),
+    ];
+    let key = if identities[0] == identities[1] {
+        super::ssh::start_identity(vault, &identities[0], encryption_key)?
+    } else {
+        start_both(vault, &identities, encryption_key)?
+    };
+
+    let mut cmd = Command::new("scp");
+    cmd.arg("-3");
+    key.configure(&mut cmd);
+    host_keys.configure(&mut cmd);
+
+    // Options go first, as scp stops reading them at the first path
+    let mut options = Vec::new();
+    let mut paths = Vec::new();
+    let mut rest = operands.iter();
+    while let Some(arg) = rest.next() {
+        if !arg.starts_with('-') {
+            paths.push(arg);
+            continue;
+        }
+        options.push(arg);
+        if OPTIONS_WITH_VALUE.contains(&arg.as_str()) {
+            options.extend(rest.next());
+        }
+    }
+    cmd.args(options);
+    for arg in paths {
+        match remote_operand(vault, arg) {
+            Some((server, path)) => {
+                let server = vault.get_ssh_server(server)?;
+                cmd.arg(format!(
+                    "{}@{}:{}",
+                    server.username, server.ip_address, path
+                ));
+            }
+            None => {
+                cmd.arg(arg);
+            }
+        }
+    }
+
+    println!(
+        "Copying from '{}' to '{}' through this machine...",
+        from, to
+    );
+    run(cmd)
+}
+
+/// Serves two identities from one agent, so scp's two connections can
+/// each find theirs. Only keys stored in the vault can be combined.
+fn start_both(
+    vault: &Vault,
+    identities: &[String; 2],
+    encryption_key: &[u8; KEY_SIZE],
+) -> Result<IdentityKey, CliError> {
+    let mut private_keys = Vec::new();
+    for identity in identities {
+        let entry = &vault.ssh_identities[identity];
+        if entry.pkcs11.is_some() || ssh::is_security_key(&entry.public_key) {
+            return Err(CliError::SshError(format!(
+                "Identity '{}' is on a security key or token; copying between servers \
+                 with different identities needs both keys stored in the vault",
+                identity
+            )));
+        }
+        private_keys.push(vault.get_ssh_identity(identity, encryption_key)?.1);
+    }
+    let seeds: Vec<&[u8]> = private_keys.iter().map(|key| key.expose()).collect();
+    Ok(IdentityKey::Agent(KeyAgent::start_with_keys(&seeds)?))
+}
+
+/// Splits a `server:path` operand naming a configured server.
+fn remote_operand<'a>(vault: &Vault, arg: &'a str) -> Option<(&'a str, &'a str)> {
+    arg.split_once(':')
+        .filter(|(server, _)| vault.has_ssh_server(server))
+}
 
-    // Execute SCP
+/// Runs a prepared scp command with the terminal attached.
+fn run(mut cmd: Command) -> Result<(), CliError> {
     let status = cmd
         .stdin(std::process::Stdio::inherit())
         .stdout(std::process::Stdio::inherit())
@@ -0,0 +1,115 @@
+//! Take named snapshots of the vault and roll back to them.
+
+use crate::error::CliError;
+use crate::input;
+use crate::storage;
+use std::fs;
+use vx_core::{ttl, Operation};
+
+/// Executes the snapshot create command.
+/// Copies the vault, as currently saved, to the snapshot `name`.
+pub fn create(name: &str) -> Result<(), CliError> {
+    validate_name(name)?;
+    if storage::snapshot_path(name)?.exists() {
+        return Err(CliError::Generic(format!(
+            "Snapshot '{}' already exists; delete it first with 'vx snapshot delete {}'",
+            name, name
+        )));
+    }
+
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+    storage::write_snapshot(name, &vault, &encryption_key)?;
+
+    vault.record(Operation::Snapshot, None, Some(name));
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("✓ Snapshot '{}' taken.", name);
+    println!("Roll back to it with 'vx snapshot rollback {}'.", name);
+    Ok(())
+}
+
+/// Executes the snapshot list command.
+pub fn list() -> Result<(), CliError> {
+    let snapshots = storage::list_snapshots()?;
+    if snapshots.is_empty() {
+        println!("No snapshots. Take one with 'vx snapshot create <NAME>'.");
+        return Ok(());
+    }
+
+    let now = ttl::current_timestamp();
+    println!("\n📸 Snapshots\n");
+    println!("{:<30} TAKEN", "NAME");
+    println!("{}", "─".repeat(60));
+    for (name, taken) in &snapshots {
+        println!("{:<30} {}", name, ttl::format_relative(*taken, now));
+    }
+    println!("{}", "─".repeat(60));
+    Ok(())
+}
+
+/// Executes the snapshot rollback command.
+/// Replaces the vault's secrets, projects and settings with those of the
+/// snapshot `name`. The access log and key slots are kept.
+pub fn rollback(name: &str) -> Result<(), CliError> {
+    validate_name(name)?;
+    if !storage::snapshot_path(name)?.exists() {
+        return Err(CliError::SnapshotNotFound(name.to_string()));
+    }
+
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+    let snapshot = storage::open_snapshot(name, &encryption_key)?;
+
+    let taken = storage::list_snapshots()?
+        .into_iter()
+        .find(|(snapshot, _)| snapshot == name)
+        .map_or(0, |(_, taken)| taken);
+    if !input::confirm(&format!(
+        "Roll the vault back to snapshot '{}' (taken {})? Changes made since will be lost.",
+        name,
+        ttl::format_relative(taken, ttl::current_timestamp())
+    ))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    vault.restore_snapshot(snapshot);
+    vault.record(Operation::Rollback, None, Some(name));
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("✓ Rolled back to snapshot '{}'.", name);
+    Ok(())
+}
+
+/// Executes the snapshot delete command.
+pub fn delete(name: &str) -> Result<(), CliError> {
+    validate_name(name)?;
+    let path = storage::snapshot_path(name)?;
+    if !path.exists() {
+        return Err(CliError::SnapshotNotFound(name.to_string()));
+    }
+
+    if !input::confirm(&format!("Delete snapshot '{}'?", name))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    fs::remove_file(&path)?;
+    println!("✓ Snapshot '{}' deleted.", name);
+    Ok(())
+}
+
+/// Snapshot names become file names, so only allow characters that are
+/// safe in one.
+fn validate_name(name: &str) -> Result<(), CliError> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(CliError::InvalidSnapshotName(name.to_string()))
+    }
+}
@@ -1,24 +1,72 @@
 //! SSH identity and server management commands.
 
+use super::ssh_hostkey::TrustedHostKeys;
+use crate::agent::IdentityKey;
 use crate::error::CliError;
 use crate::input;
-use crate::session;
 use crate::storage;
-use std::fs;
 use std::io::Write;
-use std::process::Command;
-use vx_core::ssh;
+use std::process::{Command, Stdio};
+use std::path::PathBuf;
+use vx_core::vault::SshServerConfig;
+use vx_core::{ssh, ttl, Operation, SecretBytes, KEY_SIZE};
+
+/// Directory in the vault directory holding shared connection sockets
+const CONTROL_DIR: &str = "ssh-control";
+
+/// How long a shared connection stays open once idle, unless given
+const DEFAULT_MULTIPLEX_IDLE: u64 = 10 * 60;
 
 /// Entry point for SSH command dispatch.
 /// Handles `vx ssh init`, `vx ssh connect`, and `vx ssh <server>`.
 pub fn execute(target: Option<String>, args: Vec<String>) -> Result<(), CliError> {
     match target.as_deref() {
-        Some("init") => {
+        Some("init") => match args.as_slice() {
+            [name] => init(name, false),
+            [name, flag] if flag == "--security-key" => init(name, true),
+            _ => Err(CliError::Generic(
+                "Usage: vx ssh init <name> [--security-key]".to_string(),
+            )),
+        },
+        Some("multiplex") => {
             if args.is_empty() {
-                return Err(CliError::Generic("Usage: vx ssh init <name>".to_string()));
+                return Err(CliError::Generic(
+                    "Usage: vx ssh multiplex <server> [on [idle]|off]".to_string(),
+                ));
             }
-            init(&args[0])
+            multiplex(
+                &args[0],
+                args.get(1).map(String::as_str),
+                args.get(2).map(String::as_str),
+            )
         }
+        Some("push-key") => {
+            if args.is_empty() {
+                return Err(CliError::Generic(
+                    "Usage: vx ssh push-key <identity_or_server> [user@host] [ssh options...]"
+                        .to_string(),
+                ));
+            }
+            push_key(&args[0], args.get(1).map(String::as_str), args.get(2..).unwrap_or(&[]))
+        }
+        Some("backup") => super::ssh_backup::backup(&args),
+        Some("restore") => super::ssh_backup::restore(&args),
+        Some("exec") => super::ssh_exec::execute(&args),
+        Some("group") => super::ssh_group::execute(&args),
+        Some("pkcs11") => super::ssh_pkcs11::execute(&args),
+        Some("pubkeys") => super::ssh_pubkeys::execute(&args),
+        Some("hostkey") => match args.first() {
+            Some(server) => {
+                super::ssh_hostkey::execute(server, args.get(1).map(String::as_str))
+            }
+            None => Err(CliError::Generic(
+                "Usage: vx ssh hostkey <server> [forget]".to_string(),
+            )),
+        },
+        Some("test") => match args.first() {
+            Some(server) => super::ssh_test::execute(server),
+            None => Err(CliError::Generic("Usage: vx ssh test <server>".to_string())),
+        },
         Some("connect") => {
             if args.is_empty() {
                  return Err(CliError::Generic("Usage: vx ssh connect <identity_or_server> [target] [args...]".to_string()));
@@ -90,53 +138,91 @@ pub fn execute(target: Option<String>, args: Vec<String>) -> Result<(), CliError
 
 
 /// Executes the ssh init command.
-pub fn init(name: &str) -> Result<(), CliError> {
+/// With `security_key`, the key is generated on a FIDO2 security key; see
+/// `generate_security_key`.
+pub fn init(name: &str, security_key: bool) -> Result<(), CliError> {
     // Load or create vault
-    let (mut vault, encryption_key, password_bytes) = if storage::vault_exists()? {
+    let (mut vault, encryption_key) = if storage::vault_exists()? {
         // Load existing vault with cache check
-        if let Some(cached) = session::get_cached_password()? {
-            match storage::load_vault_with_key(&cached) {
-                Ok((v, k)) => (v, k, cached),
-                Err(_) => {
-                    let _ = session::clear_cached_password();
-                    let p = input::read_password("Enter master password: ")?;
-                    let (v, k) = storage::load_vault_with_key(p.as_bytes())?;
-                    (v, k, p.into_bytes())
-                }
-            }
-        } else {
-             let p = input::read_password("Enter master password: ")?;
-             let (v, k) = storage::load_vault_with_key(p.as_bytes())?;
-             (v, k, p.into_bytes())
-        }
+        storage::load_vault_with_key_auto()?
     } else {
         println!("Creating new vault...");
         let password = input::read_new_password()?;
-        let (vault, key) = storage::create_vault(password.as_bytes())?;
-        (vault, key, password.into_bytes())
+        storage::create_vault(password.as_bytes())?
     };
 
     // Generate keypair
-    let (public_key, private_key) = ssh::generate_keypair().map_err(|e| {
-        CliError::SshError(format!("Failed to generate keypair: {}", e))
-    })?;
+    let (public_key, private_key) = if security_key {
+        generate_security_key()?
+    } else {
+        ssh::generate_keypair().map_err(|e| {
+            CliError::SshError(format!("Failed to generate keypair: {}", e))
+        })?
+    };
 
     // Store identity
     vault.add_ssh_identity(name, public_key.clone(), &private_key, &encryption_key)?;
 
     // Save vault
-    storage::save_vault(&vault, &password_bytes)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
 
     // Display public key and setup commands
     println!("\n✓ SSH identity '{}' created successfully.\n", name);
+    if security_key {
+        println!("Logging in with it needs the security key plugged in and a touch.\n");
+    }
     println!("Public key:");
     println!("{}\n", public_key);
-    println!("Setup commands for remote server:");
+    println!("Install it on a server with (asks for your password there once):");
+    println!("  vx ssh push-key {} user@host\n", name);
+    println!("Or run these setup commands on the remote server:");
     println!("{}", ssh::generate_setup_commands(&public_key));
 
     Ok(())
 }
 
+/// Generates an `ed25519-sk` key on a FIDO2 security key with `ssh-keygen`,
+/// which asks for a touch (and the token's PIN if it has one). Returns the
+/// public key and the key handle file: the private key never leaves the
+/// token, so the handle is only usable with it.
+fn generate_security_key() -> Result<(String, SecretBytes), CliError> {
+    let dir = crate::scratch::private_dir()?;
+    let key_file = dir.path().join("id_vx_sk");
+
+    println!("Generating the key on your security key; touch it when it blinks.");
+    let status = Command::new("ssh-keygen")
+        .arg("-q")
+        .arg("-t")
+        .arg("ed25519-sk")
+        .arg("-N")
+        .arg("")
+        .arg("-C")
+        .arg("vaultx-generated")
+        .arg("-f")
+        .arg(&key_file)
+        .status()
+        .map_err(|e| CliError::SshError(format!("Failed to execute ssh-keygen: {}", e)))?;
+    if !status.success() {
+        return Err(CliError::SshError(
+            "ssh-keygen could not create a key on the security key; it needs \
+             OpenSSH 8.2 or later built with FIDO2 support"
+                .to_string(),
+        ));
+    }
+
+    let public_key = std::fs::read_to_string(key_file.with_extension("pub"))?
+        .trim()
+        .to_string();
+    if !ssh::is_security_key(&public_key) {
+        return Err(CliError::SshError(format!(
+            "ssh-keygen wrote an unexpected key type: {}",
+            public_key
+        )));
+    }
+    let key_handle = SecretBytes::new(std::fs::read(&key_file)?);
+    Ok((public_key, key_handle))
+}
+
 /// Dispatches SSH connect based on whether argument is identity or server.
 pub fn connect_dispatch(
     identity_or_server: &str,
@@ -144,14 +230,16 @@ pub fn connect_dispatch(
     extra_args: &[String],
 ) -> Result<(), CliError> {
     // Load vault to check what we're dealing with
-    let (vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
 
     // Check if it's a configured server
     if vault.has_ssh_server(identity_or_server) {
         // It's a server name - use server shorthand
-        connect_server(&vault, &encryption_key, identity_or_server, extra_args)
+        record_connect(&mut vault, identity_or_server, &encryption_key);
+        connect_server(&mut vault, &encryption_key, identity_or_server, extra_args)
     } else if let Some(tgt) = target {
         // It's identity + target - use original connect logic
+        record_connect(&mut vault, identity_or_server, &encryption_key);
         connect_with_identity(&vault, &encryption_key, identity_or_server, tgt, extra_args)
     } else {
         // Check if it's "connect <servername>" for setup
@@ -160,36 +248,37 @@ pub fn connect_dispatch(
     }
 }
 
+/// Records an SSH connection in the access log before it is made.
+fn record_connect(
+    vault: &mut vx_core::Vault,
+    server_or_identity: &str,
+    key: &[u8; KEY_SIZE],
+) {
+    storage::record_access(
+        vault,
+        Operation::SshConnect,
+        None,
+        Some(server_or_identity),
+        key,
+    );
+}
+
 /// Interactive setup for SSH server configuration.
 fn setup_server(servername: &str) -> Result<(), CliError> {
     println!("Setting up SSH server configuration: {}", servername);
 
     // Load vault
-    let (mut vault, _encryption_key, password_bytes) = if let Some(cached) = session::get_cached_password()? {
-        match storage::load_vault_with_key(&cached) {
-            Ok((v, k)) => (v, k, cached),
-            Err(_) => {
-                let _ = session::clear_cached_password();
-                let p = input::read_password("Enter master password: ")?;
-                let (v, k) = storage::load_vault_with_key(p.as_bytes())?;
-                (v, k, p.into_bytes())
-            }
-        }
-    } else {
-         let p = input::read_password("Enter master password: ")?;
-         let (v, k) = storage::load_vault_with_key(p.as_bytes())?;
-         (v, k, p.into_bytes())
-    };
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
 
     // Check if server already exists
-    if vault.has_ssh_server(servername) {
-        if !input::confirm(&format!(
+    if vault.has_ssh_server(servername)
+        && !input::confirm(&format!(
             "Server '{}' already configured. Overwrite?",
             servername
-        ))? {
-            println!("Cancelled.");
-            return Ok(());
-        }
+        ))?
+    {
+        println!("Cancelled.");
+        return Ok(());
     }
 
     // Check if identity with same name exists
@@ -222,7 +311,7 @@ fn setup_server(servername: &str) -> Result<(), CliError> {
         servername.to_string(), // Identity has same name as server
     )?;
 
-    storage::save_vault(&vault, &password_bytes)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
 
     println!(
         "\n✓ Server '{}' configured successfully!",
@@ -238,25 +327,33 @@ fn setup_server(servername: &str) -> Result<(), CliError> {
 
 /// Connects using a configured server shorthand.
 fn connect_server(
-    vault: &vx_core::Vault,
+    vault: &mut vx_core::Vault,
     encryption_key: &[u8; 32],
     servername: &str,
     command_args: &[String],
 ) -> Result<(), CliError> {
+    let host_keys = super::ssh_hostkey::verify(vault, servername, encryption_key)?;
+
     // Get server config
     let server = vault
         .get_ssh_server(servername)
         .map_err(|_| CliError::SshError(format!("Server '{}' not found", servername)))?;
 
     // Get SSH identity
-    let (_public_key, private_key_bytes) = 
-        vault.get_ssh_identity(&server.identity_name, encryption_key)?;
+    let key = start_identity(vault, &server.identity_name, encryption_key)?;
 
     // Build target string
     let target = format!("{}@{}", server.username, server.ip_address);
 
     // Use existing connection logic
-    execute_ssh_connection(&private_key_bytes, &target, &server.identity_name, command_args)
+    execute_ssh_connection(
+        &key,
+        &target,
+        &server.identity_name,
+        Some(&host_keys),
+        server.multiplex,
+        command_args,
+    )
 }
 
 /// Connects using identity and target (original behavior).
@@ -268,53 +365,55 @@ fn connect_with_identity(
     extra_args: &[String],
 ) -> Result<(), CliError> {
     // Get SSH identity
-    let (_public_key, private_key_bytes) = vault.get_ssh_identity(identity, encryption_key)?;
+    let key = start_identity(vault, identity, encryption_key)?;
 
-    execute_ssh_connection(&private_key_bytes, target, identity, extra_args)
+    execute_ssh_connection(&key, target, identity, None, None, extra_args)
+}
+
+/// Decrypts an SSH identity and starts offering it to ssh; see
+/// `IdentityKey`. Identities on a PKCS#11 token have nothing to decrypt.
+///
+/// The decrypted plaintext is zeroized when this returns, so only the
+/// agent's copy survives for the duration of the connection.
+pub fn start_identity(
+    vault: &vx_core::Vault,
+    identity: &str,
+    encryption_key: &[u8; 32],
+) -> Result<IdentityKey, CliError> {
+    if let Some(entry) = vault.ssh_identities.get(identity) {
+        if let Some(token) = &entry.pkcs11 {
+            return IdentityKey::token(&token.module, &entry.public_key);
+        }
+    }
+    let (public_key, private_key) = vault.get_ssh_identity(identity, encryption_key)?;
+    IdentityKey::start(&public_key, &private_key)
 }
 
 /// Common SSH connection execution logic.
 ///
 /// # Security
-/// - Decrypts private key in memory
-/// - Writes to temp file with 0600 permissions
-/// - Deletes temp file after SSH session
+/// - The key is offered to ssh by a `KeyAgent` for the session, never
+///   written to a key file on Unix; a security-key identity's key handle
+///   is useless without the token
+///
+/// With `host_keys`, only those keys are accepted for the server. With
+/// `multiplex`, the session shares a connection kept open for that many
+/// idle seconds; see `add_multiplex_options`.
 fn execute_ssh_connection(
-    private_key_bytes: &[u8],
+    key: &IdentityKey,
     target: &str,
     identity_name: &str,
+    host_keys: Option<&TrustedHostKeys>,
+    multiplex: Option<u64>,
     extra_args: &[String],
 ) -> Result<(), CliError> {
-    // Reconstruct signing key and format private key
-    let signing_key = ssh::reconstruct_signing_key(private_key_bytes)
-        .map_err(|e| CliError::SshError(format!("Invalid key format: {}", e)))?;
-
-    let public_key_bytes = signing_key.verifying_key();
-    let private_key_pem = ssh::format_private_key(private_key_bytes, public_key_bytes.as_bytes())
-        .map_err(|e| CliError::SshError(format!("Failed to format private key: {}", e)))?;
-
-    // Create temp file for private key
-    let temp_dir = tempfile::tempdir()?;
-    let key_path = temp_dir.path().join("id_temp");
-
-    // Write private key with restricted permissions
-    {
-        let mut file = fs::File::create(&key_path)?;
-
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let permissions = fs::Permissions::from_mode(0o600);
-            file.set_permissions(permissions)?;
-        }
-
-        file.write_all(private_key_pem.as_bytes())?;
-        file.sync_all()?;
-    }
-
     // Build SSH command
     let mut cmd = Command::new("ssh");
-    cmd.arg("-i").arg(&key_path);
+    key.configure(&mut cmd);
+    if let Some(host_keys) = host_keys {
+        host_keys.configure(&mut cmd);
+    }
+    add_multiplex_options(&mut cmd, multiplex)?;
     cmd.arg(target);
 
     // Add extra arguments
@@ -334,6 +433,12 @@ fn execute_ssh_connection(
         )
     };
     println!("{}", message);
+    tracing::debug!(
+        target = %target,
+        identity = identity_name,
+        args = extra_args.len(),
+        "running ssh"
+    );
 
     // Execute SSH with inherited stdio for interactive shell and command output
     let status = cmd
@@ -342,6 +447,7 @@ fn execute_ssh_connection(
         .stderr(std::process::Stdio::inherit())
         .status()
         .map_err(|e| CliError::SshError(format!("Failed to execute ssh: {}", e)))?;
+    tracing::debug!(status = %status, "ssh exited");
 
     if !status.success() {
         return Err(CliError::SshError(format!(
@@ -353,6 +459,206 @@ fn execute_ssh_connection(
     Ok(())
 }
 
+/// Executes the ssh push-key command.
+/// Appends the public key of `identity` to `~/.ssh/authorized_keys` on
+/// `target`, like `ssh-copy-id`. The connection logs in however ssh
+/// normally would, usually with the account's password. A configured
+/// server can be given instead, whose identity and address are used when
+/// `target` is omitted. `ssh_args` go before the destination, e.g. `-p 2222`.
+pub fn push_key(
+    identity: &str,
+    target: Option<&str>,
+    ssh_args: &[String],
+) -> Result<(), CliError> {
+    let (vault, _) = storage::load_vault_with_key_auto()?;
+
+    let (identity, target) = match (vault.ssh_servers.get(identity), target) {
+        (Some(server), None) => (
+            server.identity_name.as_str(),
+            format!("{}@{}", server.username, server.ip_address),
+        ),
+        (_, Some(target)) => (identity, target.to_string()),
+        (None, None) => {
+            return Err(CliError::Generic(format!(
+                "'{}' is not a configured server; give the host: vx ssh push-key {} user@host",
+                identity, identity
+            )))
+        }
+    };
+    let public_key = &vault
+        .ssh_identities
+        .get(identity)
+        .ok_or_else(|| {
+            CliError::SshError(format!(
+                "SSH identity '{}' not found. Create it first with: vx ssh init {}",
+                identity, identity
+            ))
+        })?
+        .public_key;
+
+    println!("Installing the public key of '{}' on {}...", identity, target);
+    let mut child = Command::new("ssh")
+        .args(ssh_args)
+        .arg(&target)
+        .arg(ssh::INSTALL_KEY_COMMAND)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| CliError::SshError(format!("Failed to execute ssh: {}", e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "{}", public_key)?;
+    }
+    let output = child.wait_with_output()?;
+
+    if !output.status.success() {
+        return Err(CliError::SshError(format!(
+            "Could not install the key: ssh exited with status {}",
+            output.status.code().unwrap_or(-1)
+        )));
+    }
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "present" => println!("✓ The key of '{}' was already installed on {}.", identity, target),
+        _ => println!("✓ Key of '{}' installed on {}.", identity, target),
+    }
+    if !vault.has_ssh_server(identity) {
+        println!("  Save the server with: vx ssh connect {}", identity);
+    }
+    Ok(())
+}
+
+/// Executes the ssh multiplex command.
+/// Shows whether connections to `servername` are shared, or with `setting`
+/// `on` or `off` changes it. `idle` is how long the shared connection stays
+/// open after the last session ends, e.g. `30m`; ten minutes by default.
+pub fn multiplex(
+    servername: &str,
+    setting: Option<&str>,
+    idle: Option<&str>,
+) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    let server = vault
+        .get_ssh_server(servername)
+        .map_err(|_| CliError::SshError(format!("Server '{}' not found", servername)))?
+        .clone();
+
+    let idle_seconds = match setting {
+        None => {
+            match server.multiplex {
+                Some(seconds) => println!(
+                    "Connections to '{}' are shared, kept open for {} when idle.",
+                    servername,
+                    ttl::format_duration(seconds)
+                ),
+                None => println!("Connections to '{}' are not shared.", servername),
+            }
+            return Ok(());
+        }
+        Some("on") => Some(match idle {
+            Some(idle) => {
+                ttl::parse_ttl(idle).map_err(|e| CliError::InvalidTtl(e.to_string()))?
+            }
+            None => DEFAULT_MULTIPLEX_IDLE,
+        }),
+        Some("off") => None,
+        Some(other) => {
+            return Err(CliError::Generic(format!(
+                "Unknown setting '{}': use 'on' or 'off'",
+                other
+            )))
+        }
+    };
+
+    if cfg!(windows) && idle_seconds.is_some() {
+        return Err(CliError::SshError(
+            "Connection sharing is not supported by OpenSSH on Windows".to_string(),
+        ));
+    }
+
+    vault.set_ssh_server_multiplex(servername, idle_seconds)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    match idle_seconds {
+        Some(seconds) => println!(
+            "✓ Connections to '{}' now share one login, kept open for {} when idle.",
+            servername,
+            ttl::format_duration(seconds)
+        ),
+        None => {
+            if server.multiplex.is_some() {
+                close_shared_connection(&server)?;
+            }
+            println!("✓ Connections to '{}' are no longer shared.", servername);
+        }
+    }
+    Ok(())
+}
+
+/// Adds the options making `cmd`, an `ssh` or `scp` command, use or start
+/// a shared connection kept open for `idle_seconds`. Does nothing for
+/// `None`, or on Windows, whose OpenSSH has no connection sharing.
+pub fn add_multiplex_options(cmd: &mut Command, idle_seconds: Option<u64>) -> Result<(), CliError> {
+    let Some(idle_seconds) = idle_seconds.filter(|_| cfg!(unix)) else {
+        return Ok(());
+    };
+
+    let control_path = control_path()?;
+    tracing::debug!(control_path = %control_path.display(), idle_seconds, "sharing ssh connection");
+    // Quoted in case the path holds spaces
+    cmd.arg("-o")
+        .arg("ControlMaster=auto")
+        .arg("-o")
+        .arg(format!("ControlPath=\"{}\"", control_path.display()))
+        .arg("-o")
+        .arg(format!("ControlPersist={}", idle_seconds));
+    Ok(())
+}
+
+/// Returns the `ControlPath` for shared connections, creating the private
+/// directory holding the sockets. `%C` names each socket after a hash of
+/// the destination, keeping paths short enough for a unix socket.
+fn control_path() -> Result<PathBuf, CliError> {
+    let dir = storage::vault_dir()?.join(CONTROL_DIR);
+    crate::permissions::create_private_dir(&dir)?;
+    Ok(dir.join("%C"))
+}
+
+/// Asks the shared connection to `server`, if one is open, to close.
+fn close_shared_connection(server: &SshServerConfig) -> Result<(), CliError> {
+    let output = Command::new("ssh")
+        .arg("-o")
+        .arg(format!("ControlPath=\"{}\"", control_path()?.display()))
+        .arg("-O")
+        .arg("exit")
+        .arg(format!("{}@{}", server.username, server.ip_address))
+        .output()
+        .map_err(|e| CliError::SshError(format!("Failed to execute ssh: {}", e)))?;
+    if output.status.success() {
+        println!("Closed the open shared connection.");
+    }
+    Ok(())
+}
+
+/// Returns the host name and port ssh will connect to, after the user's
+/// ssh config (`ssh -G`) is applied; the configured address and port 22
+/// if ssh cannot tell.
+pub fn endpoint(destination: &str, address: &str) -> (String, u16) {
+    let mut host = address.to_string();
+    let mut port = 22;
+    let Ok(output) = Command::new("ssh").arg("-G").arg(destination).output() else {
+        return (host, port);
+    };
+
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        match line.split_once(' ') {
+            Some(("hostname", value)) => host = value.to_string(),
+            Some(("port", value)) => port = value.parse().unwrap_or(port),
+            _ => {}
+        }
+    }
+    (host, port)
+}
+
 /// Basic IP address or hostname validation.
 fn validate_ip_or_hostname(addr: &str) -> Result<(), CliError> {
     if addr.is_empty() {
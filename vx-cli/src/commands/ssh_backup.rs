@@ -0,0 +1,117 @@
+//! `vx ssh backup` and `vx ssh restore`: move a single SSH identity, and
+//! the servers using it, between vaults.
+//!
+//! The backup file is encrypted under a passphrase chosen when it is made,
+//! not under the vault's credentials, so it can be restored into any
+//! vault; see `vx_core::ssh_backup`.
+
+use crate::error::CliError;
+use crate::input;
+use crate::progress;
+use crate::storage;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use vx_core::{ssh_backup, ttl};
+
+/// Executes the ssh backup command.
+/// Writes the identity `args[0]` and its servers to the file given with
+/// `--out`.
+pub fn backup(args: &[String]) -> Result<(), CliError> {
+    let usage = || CliError::Generic("Usage: vx ssh backup <identity> --out <file>".to_string());
+    let (name, out) = match args {
+        [name, flag, out] if flag == "--out" || flag == "-o" => (name, out),
+        _ => return Err(usage()),
+    };
+
+    // Exporting a private key asks for a credential even while the daemon
+    // holds the key
+    let (vault, encryption_key) = storage::open_vault_with_credential()?;
+    let identity = ssh_backup::collect(&vault, name, &encryption_key)?;
+
+    if Path::new(out).exists() && !input::confirm(&format!("{} exists. Overwrite it?", out))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    println!("The backup holds the private key, protected only by its passphrase.");
+    let passphrase = input::read_strong_password_as("backup passphrase")?;
+    let data = progress::with_spinner("Encrypting backup...", || {
+        ssh_backup::seal(&identity, passphrase.as_bytes())
+    })?;
+
+    let mut file = fs::File::create(out)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions = fs::Permissions::from_mode(0o600);
+        file.set_permissions(permissions)?;
+    }
+
+    file.write_all(&data)?;
+    file.sync_all()?;
+
+    println!(
+        "✓ SSH identity '{}' backed up to {} with {} server(s).",
+        name,
+        out,
+        identity.servers.len()
+    );
+    println!("  Restore it with: vx ssh restore {}", out);
+    Ok(())
+}
+
+/// Executes the ssh restore command.
+/// Adds the identity in the backup `args[0]`, under its own name or the
+/// one given with `--as`, and the servers it was used with.
+pub fn restore(args: &[String]) -> Result<(), CliError> {
+    let usage = || CliError::Generic("Usage: vx ssh restore <file> [--as <name>]".to_string());
+    let (file, rename) = match args {
+        [file] => (file, None),
+        [file, flag, name] if flag == "--as" => (file, Some(name.as_str())),
+        _ => return Err(usage()),
+    };
+
+    let data = fs::read(file).map_err(|_| CliError::FileNotFound(file.to_string()))?;
+    let info = ssh_backup::read_info(&data)?;
+    let name = rename.unwrap_or(&info.identity);
+
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    if vault.ssh_identities.contains_key(name) {
+        return Err(CliError::Generic(format!(
+            "SSH identity '{}' already exists; restore under another name with --as <name>",
+            name
+        )));
+    }
+
+    println!(
+        "Backup of SSH identity '{}' made {} with VaultX {}.",
+        info.identity,
+        ttl::format_relative(info.created_at, ttl::current_timestamp()),
+        info.created_with
+    );
+    let passphrase = input::read_password("Enter backup passphrase: ")?;
+    let identity = progress::with_spinner("Decrypting backup...", || {
+        ssh_backup::open(&data, passphrase.as_bytes())
+    })?;
+
+    let skipped = ssh_backup::restore(&mut vault, &identity, name, &encryption_key)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("✓ SSH identity '{}' restored.", name);
+    for server in &identity.servers {
+        if skipped.contains(&server.name) {
+            println!(
+                "  • {} skipped: a server with that name is already configured",
+                server.name
+            );
+        } else {
+            println!(
+                "  • {} → {}@{}",
+                server.name, server.username, server.ip_address
+            );
+        }
+    }
+    Ok(())
+}
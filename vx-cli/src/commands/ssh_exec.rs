@@ -0,0 +1,294 @@
+//! `vx ssh exec`: runs one command on several configured servers.
+//!
+//! Each server is reached like `vx ssh <server>`, with its own identity
+//! and recorded host keys, but without prompting (`BatchMode`) and with
+//! no input. Servers run one at a time, or up to `--parallel` at once;
+//! every line they print is prefixed with the server's name, and a
+//! summary of exit codes follows. `--servers` also takes group names
+//! (`vx ssh group`), which stand for their members.
+
+use super::ssh_hostkey::{self, TrustedHostKeys};
+use crate::agent::IdentityKey;
+use crate::error::CliError;
+use crate::storage;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use vx_core::{Operation, Vault, KEY_SIZE};
+
+const USAGE: &str =
+    "Usage: vx ssh exec --servers <server_or_group,...> [--parallel N] -- <command...>";
+
+/// What to run where, from the command line
+struct ExecOptions {
+    servers: Vec<String>,
+    parallel: usize,
+    command: Vec<String>,
+}
+
+/// One server's ssh command, ready to run
+struct Job {
+    index: usize,
+    server: String,
+    cmd: Command,
+    // Kept alive until ssh exits
+    _key: IdentityKey,
+    _host_keys: TrustedHostKeys,
+}
+
+/// How a server's run ended
+enum Outcome {
+    Exited(i32),
+    Failed(String),
+}
+
+/// Executes the ssh exec command.
+pub fn execute(args: &[String]) -> Result<(), CliError> {
+    let options = parse_args(args)?;
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    let servers = vault.expand_ssh_servers(&options.servers)?;
+
+    let mut outcomes = Vec::new();
+    let mut jobs = Vec::new();
+    for (index, server) in servers.iter().enumerate() {
+        match prepare(&mut vault, index, server, &options.command, &encryption_key) {
+            Ok(job) => jobs.push(job),
+            Err(e) => outcomes.push((index, Outcome::Failed(e.to_string()))),
+        }
+    }
+    if !vault.read_only {
+        for job in &jobs {
+            vault.record(Operation::SshConnect, None, Some(&job.server));
+        }
+        storage::save_access_log(&mut vault, &encryption_key);
+    }
+
+    let width = servers.iter().map(String::len).max().unwrap_or(0);
+    outcomes.extend(run(jobs, options.parallel, width));
+    outcomes.sort_by_key(|(index, _)| *index);
+
+    println!("\nSummary:");
+    let mut failed = 0;
+    for (index, outcome) in &outcomes {
+        let server = &servers[*index];
+        match outcome {
+            Outcome::Exited(0) => println!("  ✓ {:<width$}  exit 0", server),
+            Outcome::Exited(code) => {
+                failed += 1;
+                println!("  ✗ {:<width$}  exit {}", server, code);
+            }
+            Outcome::Failed(reason) => {
+                failed += 1;
+                println!("  ✗ {:<width$}  {}", server, reason);
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(CliError::SshError(format!(
+            "{} of {} servers failed",
+            failed,
+            outcomes.len()
+        )));
+    }
+    Ok(())
+}
+
+fn parse_args(args: &[String]) -> Result<ExecOptions, CliError> {
+    let usage = || CliError::Generic(USAGE.to_string());
+    let mut servers = Vec::new();
+    let mut parallel = 1;
+    let mut rest = args.iter();
+
+    while let Some(arg) = rest.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| rest.next().cloned())
+                .ok_or_else(usage)
+        };
+        match flag {
+            "--servers" | "-s" => {
+                for name in value()?.split(',').map(str::trim) {
+                    if !name.is_empty() && !servers.iter().any(|s| s == name) {
+                        servers.push(name.to_string());
+                    }
+                }
+            }
+            "--parallel" | "-P" => {
+                parallel = value()?.parse().ok().filter(|n| *n > 0).ok_or_else(|| {
+                    CliError::Generic("--parallel needs a number above 0".to_string())
+                })?;
+            }
+            "--" => break,
+            _ => {
+                return Err(CliError::Generic(format!(
+                    "Unknown option '{}'\n{}",
+                    arg, USAGE
+                )))
+            }
+        }
+    }
+
+    let command: Vec<String> = rest.cloned().collect();
+    if servers.is_empty() || command.is_empty() {
+        return Err(usage());
+    }
+    Ok(ExecOptions {
+        servers,
+        parallel,
+        command,
+    })
+}
+
+/// Checks the server's host key and builds its ssh command.
+fn prepare(
+    vault: &mut Vault,
+    index: usize,
+    server: &str,
+    command: &[String],
+    encryption_key: &[u8; KEY_SIZE],
+) -> Result<Job, CliError> {
+    let host_keys = ssh_hostkey::verify(vault, server, encryption_key)?;
+    let config = vault.get_ssh_server(server)?;
+    let key = super::ssh::start_identity(vault, &config.identity_name, encryption_key)?;
+
+    let mut cmd = Command::new("ssh");
+    key.configure(&mut cmd);
+    host_keys.configure(&mut cmd);
+    super::ssh::add_multiplex_options(&mut cmd, config.multiplex)?;
+    cmd.arg("-o")
+        .arg("BatchMode=yes")
+        .arg("--")
+        .arg(format!("{}@{}", config.username, config.ip_address))
+        .args(command);
+
+    Ok(Job {
+        index,
+        server: server.to_string(),
+        cmd,
+        _key: key,
+        _host_keys: host_keys,
+    })
+}
+
+/// Runs the jobs on up to `parallel` threads, returning each one's outcome.
+fn run(jobs: Vec<Job>, parallel: usize, width: usize) -> Vec<(usize, Outcome)> {
+    let workers = parallel.min(jobs.len());
+    let queue = Mutex::new(jobs.into_iter());
+    let outcomes = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let next = queue.lock().unwrap().next();
+                let Some(job) = next else { break };
+                let index = job.index;
+                let outcome = run_job(job, width);
+                outcomes.lock().unwrap().push((index, outcome));
+            });
+        }
+    });
+    outcomes.into_inner().unwrap()
+}
+
+/// Runs one server's command, copying its output line by line with the
+/// server's name in front.
+fn run_job(mut job: Job, width: usize) -> Outcome {
+    tracing::debug!(server = %job.server, "running remote command");
+    let mut child = match job
+        .cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => return Outcome::Failed(format!("failed to execute ssh: {}", e)),
+    };
+
+    let prefix = format!("[{:<width$}] ", job.server);
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    thread::scope(|scope| {
+        if let Some(stdout) = stdout {
+            scope.spawn(|| copy_lines(stdout, &prefix, io::stdout));
+        }
+        if let Some(stderr) = stderr {
+            scope.spawn(|| copy_lines(stderr, &prefix, io::stderr));
+        }
+    });
+
+    match child.wait() {
+        Ok(status) => match status.code() {
+            Some(code) => Outcome::Exited(code),
+            None => Outcome::Failed("ssh was killed by a signal".to_string()),
+        },
+        Err(e) => Outcome::Failed(e.to_string()),
+    }
+}
+
+fn copy_lines<R: Read, W: Write>(from: R, prefix: &str, to: fn() -> W) {
+    for line in BufReader::new(from).split(b'\n') {
+        let Ok(line) = line else { break };
+        let line = String::from_utf8_lossy(&line);
+        let _ = writeln!(to(), "{}{}", prefix, line.trim_end_matches('\r'));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<ExecOptions, CliError> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_args(&args)
+    }
+
+    fn error(args: &[&str]) -> String {
+        match parse(args) {
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("{:?} was accepted", args),
+        }
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options =
+            parse(&["--servers", "web, db,web", "-P", "2", "--", "uptime", "-p"]).unwrap();
+        assert_eq!(options.servers, ["web", "db"]);
+        assert_eq!(options.parallel, 2);
+        assert_eq!(options.command, ["uptime", "-p"]);
+
+        let options = parse(&["-s", "web", "--servers=db", "--", "ls", "--", "-l"]).unwrap();
+        assert_eq!(options.servers, ["web", "db"]);
+        assert_eq!(options.parallel, 1);
+        assert_eq!(options.command, ["ls", "--", "-l"]);
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        for args in [
+            &["--servers", "web", "--"][..],
+            &["--", "uptime"],
+            &["--servers", ",", "--", "uptime"],
+            &["--servers"],
+            &["--servers", "web", "uptime"],
+        ] {
+            assert!(error(args).contains(USAGE), "{:?}", args);
+        }
+        for parallel in ["0", "-1", "two"] {
+            assert_eq!(
+                error(&["--servers", "web", "--parallel", parallel, "--", "uptime"]),
+                CliError::Generic("--parallel needs a number above 0".to_string()).to_string()
+            );
+        }
+        assert!(error(&["--servers", "web", "--user", "root", "--", "id"])
+            .starts_with("Unknown option '--user'"));
+    }
+}
@@ -0,0 +1,81 @@
+//! `vx ssh group`: named groups of configured servers.
+//!
+//! A group can be given wherever several servers are taken, e.g.
+//! `vx ssh exec --servers web` or `vx scp web file :/tmp/`, and stands for
+//! its members in order. Removing a server drops it from its groups.
+
+use crate::error::CliError;
+use crate::input;
+use crate::storage;
+use vx_core::VaultError;
+
+const USAGE: &str = "Usage: vx ssh group [list | create <name> <server...> | remove <name>]";
+
+/// Executes the ssh group command.
+pub fn execute(args: &[String]) -> Result<(), CliError> {
+    match args.split_first() {
+        None => list(),
+        Some((action, rest)) => match (action.as_str(), rest) {
+            ("list", []) => list(),
+            ("create", [name, members @ ..]) if !members.is_empty() => create(name, members),
+            ("remove", [name]) => remove(name),
+            _ => Err(CliError::Generic(USAGE.to_string())),
+        },
+    }
+}
+
+/// Creates the group `name` of the servers `members`.
+fn create(name: &str, members: &[String]) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    vault.create_ssh_server_group(name, members)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!(
+        "✓ Group '{}' created: {}",
+        name,
+        vault.ssh_server_groups[name].join(", ")
+    );
+    println!(
+        "  Run a command on it: vx ssh exec --servers {} -- uptime",
+        name
+    );
+    Ok(())
+}
+
+/// Lists the groups and their members.
+fn list() -> Result<(), CliError> {
+    let vault = storage::load_vault_auto()?;
+
+    if vault.ssh_server_groups.is_empty() {
+        println!("No server groups. Create one with 'vx ssh group create <name> <server...>'.");
+        return Ok(());
+    }
+
+    let mut groups: Vec<_> = vault.ssh_server_groups.iter().collect();
+    groups.sort_by(|a, b| a.0.cmp(b.0));
+
+    println!("Server groups:");
+    for (name, members) in groups {
+        println!("  • {} → {}", name, members.join(", "));
+    }
+    Ok(())
+}
+
+/// Removes the group `name`; its servers are kept.
+fn remove(name: &str) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+
+    if !vault.ssh_server_groups.contains_key(name) {
+        return Err(VaultError::ServerGroupNotFound(name.to_string()).into());
+    }
+    if !input::confirm(&format!("Remove group '{}'? Its servers are kept.", name))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    vault.remove_ssh_server_group(name)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("✓ Group '{}' removed.", name);
+    Ok(())
+}
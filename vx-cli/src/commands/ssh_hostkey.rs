@@ -0,0 +1,224 @@
+//! Host keys of configured servers, recorded in the vault.
+//!
+//! The first `vx ssh` or `vx scp` to a server fetches its host keys with
+//! `ssh-keyscan`, shows their fingerprints and, once the user accepts
+//! them, records them in the vault (trust on first use). Every later
+//! connection scans the server again and refuses to go on, before ssh is
+//! started, if none of the keys it presents was recorded. ssh itself is
+//! handed only the recorded keys as its `known_hosts`, so a key swapped
+//! after the scan is refused too.
+//!
+//! `vx ssh hostkey <server>` shows the recorded fingerprints, and
+//! `vx ssh hostkey <server> forget` drops them once a server has been
+//! reinstalled.
+
+use crate::error::CliError;
+use crate::input;
+use crate::scratch::ScratchFile;
+use crate::storage;
+use std::process::Command;
+use vx_core::{ssh, Vault, KEY_SIZE};
+
+/// Seconds `ssh-keyscan` waits for a server
+const SCAN_TIMEOUT: &str = "5";
+
+/// Recorded host keys written as a `known_hosts` file for ssh.
+pub struct TrustedHostKeys {
+    file: ScratchFile,
+}
+
+impl TrustedHostKeys {
+    /// Writes `host_keys` as entries matching any host name, since the file
+    /// is only used for connections to one server.
+    pub fn new(host_keys: &[String]) -> Result<Self, CliError> {
+        let entries: String = host_keys.iter().map(|key| format!("* {}\n", key)).collect();
+        Ok(Self {
+            file: ScratchFile::create("known_hosts", entries.as_bytes())?,
+        })
+    }
+
+    /// Writes the keys recorded for each of `servernames`, every entry
+    /// limited to its server's host name and port, for a command that
+    /// connects to several servers.
+    pub fn for_servers(vault: &Vault, servernames: &[&str]) -> Result<Self, CliError> {
+        let mut entries = String::new();
+        for servername in servernames {
+            let server = vault.get_ssh_server(servername)?;
+            let destination = format!("{}@{}", server.username, server.ip_address);
+            let (host, port) = super::ssh::endpoint(&destination, &server.ip_address);
+            let pattern = if port == 22 {
+                host
+            } else {
+                format!("[{}]:{}", host, port)
+            };
+            for key in &server.host_keys {
+                entries.push_str(&format!("{} {}\n", pattern, key));
+            }
+        }
+        Ok(Self {
+            file: ScratchFile::create("known_hosts", entries.as_bytes())?,
+        })
+    }
+
+    /// Makes `cmd`, an `ssh` or `scp` command, accept only these keys. Adds
+    /// options, so call it before the destination is added.
+    pub fn configure(&self, cmd: &mut Command) {
+        cmd.arg("-o")
+            .arg(format!(
+                "UserKnownHostsFile=\"{}\"",
+                self.file.path().display()
+            ))
+            .arg("-o")
+            .arg("GlobalKnownHostsFile=none")
+            .arg("-o")
+            .arg("StrictHostKeyChecking=yes");
+    }
+}
+
+/// Executes the ssh hostkey command.
+/// Shows the host keys recorded for `servername`, or with `forget` drops
+/// them so the next connection asks again.
+pub fn execute(servername: &str, action: Option<&str>) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    let server = vault
+        .get_ssh_server(servername)
+        .map_err(|_| CliError::SshError(format!("Server '{}' not found", servername)))?;
+
+    match action {
+        None if server.host_keys.is_empty() => {
+            println!(
+                "No host key recorded for '{}'; the next connection asks to accept one.",
+                servername
+            );
+        }
+        None => {
+            println!("Host keys of '{}':", servername);
+            print_fingerprints(&server.host_keys);
+        }
+        Some("forget") => {
+            vault.set_ssh_server_host_keys(servername, Vec::new())?;
+            storage::save_vault(&mut vault, &encryption_key)?;
+            println!(
+                "✓ Host keys of '{}' forgotten; the next connection asks to accept new ones.",
+                servername
+            );
+        }
+        Some(other) => {
+            return Err(CliError::Generic(format!(
+                "Unknown action '{}': use 'forget'",
+                other
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// Checks the host key `servername` presents against the recorded ones
+/// and returns them for ssh. On the first connection the scanned keys are
+/// shown and, if the user accepts them, recorded and saved.
+///
+/// # Errors
+/// `CliError::SshError` if the server presents a key that was not
+/// recorded, or its keys cannot be fetched on the first connection.
+pub fn verify(
+    vault: &mut Vault,
+    servername: &str,
+    encryption_key: &[u8; KEY_SIZE],
+) -> Result<TrustedHostKeys, CliError> {
+    let server = vault.get_ssh_server(servername)?;
+    let destination = format!("{}@{}", server.username, server.ip_address);
+    let (host, port) = super::ssh::endpoint(&destination, &server.ip_address);
+    let scanned = scan(&host, port)?;
+    tracing::debug!(host = %host, port, keys = scanned.len(), "scanned host keys");
+
+    if !server.host_keys.is_empty() {
+        // An unreachable server is left for ssh to report
+        if scanned.is_empty() || scanned.iter().any(|key| server.host_keys.contains(key)) {
+            return TrustedHostKeys::new(&server.host_keys);
+        }
+
+        eprintln!(
+            "The host key of '{}' ({}, port {}) does not match the one recorded in the vault.",
+            servername, host, port
+        );
+        eprintln!("  Recorded:");
+        for key in &server.host_keys {
+            eprintln!("    {}", describe(key));
+        }
+        eprintln!("  Presented now:");
+        for key in &scanned {
+            eprintln!("    {}", describe(key));
+        }
+        eprintln!(
+            "Someone may be intercepting the connection, or the server was reinstalled \
+             and has new keys."
+        );
+        eprintln!(
+            "If you are sure it was reinstalled, run 'vx ssh hostkey {} forget' and \
+             connect again to check the new keys.",
+            servername
+        );
+        return Err(CliError::SshError(format!(
+            "Host key of '{}' changed; not connecting",
+            servername
+        )));
+    }
+
+    if scanned.is_empty() {
+        return Err(CliError::SshError(format!(
+            "Could not fetch the host key of '{}' from {} port {}",
+            servername, host, port
+        )));
+    }
+
+    println!(
+        "First connection to '{}' ({}, port {}). It presents these host keys:",
+        servername, host, port
+    );
+    print_fingerprints(&scanned);
+    println!(
+        "Compare them with the server's own, e.g. the output of \
+         'ssh-keygen -lf /etc/ssh/ssh_host_ed25519_key.pub' on it."
+    );
+    if !input::confirm("Trust these keys and connect?")? {
+        return Err(CliError::Generic(
+            "Host key not accepted; not connecting".to_string(),
+        ));
+    }
+
+    vault.set_ssh_server_host_keys(servername, scanned.clone())?;
+    storage::save_vault(vault, encryption_key)?;
+    println!("✓ Host keys of '{}' recorded in the vault.\n", servername);
+    TrustedHostKeys::new(&scanned)
+}
+
+/// Fetches the host keys `host` presents on `port`; none if it cannot be
+/// reached.
+fn scan(host: &str, port: u16) -> Result<Vec<String>, CliError> {
+    let output = Command::new("ssh-keyscan")
+        .arg("-T")
+        .arg(SCAN_TIMEOUT)
+        .arg("-p")
+        .arg(port.to_string())
+        .arg(host)
+        .output()
+        .map_err(|e| CliError::SshError(format!("Failed to execute ssh-keyscan: {}", e)))?;
+    Ok(ssh::parse_host_keys(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+fn print_fingerprints(host_keys: &[String]) {
+    for key in host_keys {
+        println!("  {}", describe(key));
+    }
+}
+
+/// Formats a host key as its type and fingerprint.
+fn describe(host_key: &str) -> String {
+    let key_type = host_key.split_whitespace().next().unwrap_or_default();
+    match ssh::fingerprint(host_key) {
+        Ok(fingerprint) => format!("{:<20} {}", key_type, fingerprint),
+        Err(_) => format!("{:<20} (unreadable key)", key_type),
+    }
+}
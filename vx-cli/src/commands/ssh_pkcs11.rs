@@ -0,0 +1,112 @@
+//! `vx ssh pkcs11`: registers a key on a smartcard or HSM as an identity.
+//!
+//! The key never leaves the token and nothing secret is stored: the vault
+//! keeps the module path, the key's label and its public key. `vx ssh`
+//! then has `ssh` load the module (`-I`) and use only that key, so the
+//! token asks for its PIN, if it has one, on every login.
+
+use crate::error::CliError;
+use crate::storage;
+use std::process::Command;
+use vx_core::{ssh, Pkcs11Key};
+
+const USAGE: &str = "Usage: vx ssh pkcs11 <name> --module <path> [--label <label>]";
+
+/// Executes the ssh pkcs11 command.
+pub fn execute(args: &[String]) -> Result<(), CliError> {
+    let usage = || CliError::Generic(USAGE.to_string());
+    let (name, options) = args.split_first().ok_or_else(usage)?;
+    let mut module = None;
+    let mut label = None;
+    let mut rest = options.iter();
+    while let Some(flag) = rest.next() {
+        match flag.as_str() {
+            "--module" => module = Some(rest.next().ok_or_else(usage)?),
+            "--label" => label = Some(rest.next().ok_or_else(usage)?),
+            _ => return Err(usage()),
+        }
+    }
+    let module = module.ok_or_else(usage)?;
+
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    if vault.ssh_identities.contains_key(name) {
+        return Err(vx_core::VaultError::IdentityAlreadyExists(name.clone()).into());
+    }
+
+    let keys = list_token_keys(module)?;
+    let (public_key, label) = select_key(&keys, label.map(String::as_str), module)?;
+
+    vault.add_pkcs11_identity(
+        name,
+        format!("{} {}", public_key, name),
+        Pkcs11Key {
+            module: module.clone(),
+            label: label.clone(),
+        },
+    )?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    let public_key = &vault.ssh_identities[name].public_key;
+    println!(
+        "✓ SSH identity '{}' registered for the token key '{}'.\n",
+        name, label
+    );
+    println!("Public key:");
+    println!("{}\n", public_key);
+    println!("Install it on a server with (asks for your password there once):");
+    println!("  vx ssh push-key {} user@host", name);
+    Ok(())
+}
+
+/// Lists the keys on the token `module` gives access to.
+fn list_token_keys(module: &str) -> Result<Vec<(String, String)>, CliError> {
+    let output = Command::new("ssh-keygen")
+        .arg("-D")
+        .arg(module)
+        .output()
+        .map_err(|e| CliError::SshError(format!("Failed to execute ssh-keygen: {}", e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(CliError::SshError(format!(
+            "Cannot read keys through {}: {}",
+            module,
+            stderr.trim()
+        )));
+    }
+    Ok(ssh::parse_token_keys(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Picks the key labelled `label`, or the only key when no label is given.
+fn select_key<'a>(
+    keys: &'a [(String, String)],
+    label: Option<&str>,
+    module: &str,
+) -> Result<&'a (String, String), CliError> {
+    let matching: Vec<_> = keys
+        .iter()
+        .filter(|(_, key_label)| label.is_none_or(|label| key_label == label))
+        .collect();
+
+    match matching.as_slice() {
+        [key] => Ok(key),
+        [] if keys.is_empty() => Err(CliError::SshError(format!(
+            "No keys found on the token through {}",
+            module
+        ))),
+        _ => {
+            let labels: Vec<&str> = keys.iter().map(|(_, label)| label.as_str()).collect();
+            let reason = match label {
+                Some(label) if matching.is_empty() => format!("No key labelled '{}'", label),
+                Some(label) => format!("Several keys are labelled '{}'", label),
+                None => "The token holds several keys".to_string(),
+            };
+            Err(CliError::SshError(format!(
+                "{}; choose one with --label: {}",
+                reason,
+                labels.join(", ")
+            )))
+        }
+    }
+}
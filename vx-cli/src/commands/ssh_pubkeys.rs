@@ -0,0 +1,110 @@
+//! `vx ssh pubkeys`: exports the public keys of all SSH identities.
+//!
+//! Each key is printed in OpenSSH format with the identity's name as its
+//! comment, sorted by name, so the output can be fed straight to
+//! provisioning tools. `--authorized-keys` wraps the keys in a marked
+//! `authorized_keys` block, and `--dir` writes files instead of printing:
+//! one `<identity>.pub` per identity, or a single `authorized_keys`.
+//! Only public data is read; nothing is decrypted.
+
+use crate::error::CliError;
+use crate::storage;
+use std::fs;
+use std::path::Path;
+use vx_core::ssh;
+
+const USAGE: &str = "Usage: vx ssh pubkeys [--authorized-keys] [--dir <directory>]";
+
+/// Executes the ssh pubkeys command.
+pub fn execute(args: &[String]) -> Result<(), CliError> {
+    let mut authorized_keys = false;
+    let mut dir = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--authorized-keys" | "-a" => authorized_keys = true,
+            "--dir" | "-d" => {
+                dir = Some(
+                    rest.next()
+                        .ok_or_else(|| CliError::Generic(USAGE.to_string()))?,
+                )
+            }
+            _ => {
+                return Err(CliError::Generic(format!(
+                    "Unknown option '{}'\n{}",
+                    arg, USAGE
+                )))
+            }
+        }
+    }
+
+    let vault = storage::load_vault_auto()?;
+    let mut identities: Vec<(&str, &str)> = vault
+        .ssh_identities
+        .values()
+        .map(|identity| (identity.name.as_str(), identity.public_key.as_str()))
+        .collect();
+    identities.sort();
+
+    if identities.is_empty() {
+        println!("No SSH identities. Create one with 'vx ssh init <name>'.");
+        return Ok(());
+    }
+
+    match (dir, authorized_keys) {
+        (None, true) => print!(
+            "{}",
+            ssh::authorized_keys_block(&identities).map_err(invalid)?
+        ),
+        (None, false) => {
+            for (name, public_key) in &identities {
+                println!("{}", ssh::with_comment(public_key, name).map_err(invalid)?);
+            }
+        }
+        (Some(dir), true) => {
+            let block = ssh::authorized_keys_block(&identities).map_err(invalid)?;
+            let path = Path::new(dir).join("authorized_keys");
+            write(&path, &block)?;
+            println!(
+                "✓ Wrote {} public key(s) to {}",
+                identities.len(),
+                path.display()
+            );
+        }
+        (Some(dir), false) => {
+            if let Some((name, _)) = identities.iter().find(|(name, _)| !is_file_name(name)) {
+                return Err(CliError::Generic(format!(
+                    "Identity '{}' cannot be used as a file name; use --authorized-keys",
+                    name
+                )));
+            }
+            for (name, public_key) in &identities {
+                let line = ssh::with_comment(public_key, name).map_err(invalid)?;
+                write(
+                    &Path::new(dir).join(format!("{}.pub", name)),
+                    &format!("{}\n", line),
+                )?;
+            }
+            println!("✓ Wrote {} public key(s) to {}", identities.len(), dir);
+        }
+    }
+    Ok(())
+}
+
+/// Whether `name` can be written as a file inside the target directory
+/// without leaving it.
+fn is_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), CliError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+fn invalid(e: vx_core::SshError) -> CliError {
+    CliError::SshError(format!("Invalid public key: {}", e))
+}
@@ -0,0 +1,420 @@
+//! `vx ssh test`: checks each step of connecting to a configured server.
+//!
+//! The checks run in order and stop at the first that fails, so the output
+//! says where a connection breaks: resolving the host name, reaching the
+//! SSH port, verifying the server's host key against `known_hosts`, and
+//! logging in with the server's identity without any prompt
+//! (`BatchMode`). Nothing is run on the server but `true`, and
+//! `known_hosts` is never changed.
+
+use super::ssh_hostkey::TrustedHostKeys;
+use crate::agent::IdentityKey;
+use crate::error::CliError;
+use crate::scratch::ScratchFile;
+use crate::storage;
+use std::io::{BufRead, BufReader, Read};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::process::{Command, Output};
+use std::time::Duration;
+
+/// How long each network step may take
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest identification line read from the server
+const MAX_BANNER: u64 = 255;
+
+/// A failed check, with what to do about it
+struct Failure {
+    reason: String,
+    hint: String,
+}
+
+impl Failure {
+    fn new(reason: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+            hint: hint.into(),
+        }
+    }
+}
+
+/// Executes the ssh test command.
+pub fn execute(servername: &str) -> Result<(), CliError> {
+    let (vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    let server = vault
+        .get_ssh_server(servername)
+        .map_err(|_| CliError::SshError(format!("Server '{}' not found", servername)))?;
+    let destination = format!("{}@{}", server.username, server.ip_address);
+    let (host, port) = super::ssh::endpoint(&destination, &server.ip_address);
+
+    println!(
+        "Testing '{}' ({}, port {}):\n",
+        servername, destination, port
+    );
+
+    let recorded = if server.host_keys.is_empty() {
+        None
+    } else {
+        Some(TrustedHostKeys::new(&server.host_keys)?)
+    };
+
+    let addresses = check(servername, "DNS", resolve(&host, port))?;
+    check(servername, "TCP", connect(&addresses, port))?;
+    check(
+        servername,
+        "Host key",
+        verify_host_key(servername, &destination, &host, recorded.as_ref()),
+    )?;
+
+    let key = super::ssh::start_identity(&vault, &server.identity_name, &encryption_key)?;
+    let public_key = &vault.ssh_identities[&server.identity_name].public_key;
+    let public_key_file = ScratchFile::create("id.pub", format!("{}\n", public_key).as_bytes())?;
+    check(
+        servername,
+        "Login",
+        log_in(
+            servername,
+            &destination,
+            public_key,
+            &key,
+            &public_key_file,
+            recorded.as_ref(),
+        ),
+    )?;
+
+    println!("\n✓ '{}' is ready: vx ssh {}", servername, servername);
+    Ok(())
+}
+
+/// Prints the outcome of one check, turning a failure into the command's
+/// error.
+fn check<T>(
+    servername: &str,
+    name: &str,
+    result: Result<(T, String), Failure>,
+) -> Result<T, CliError> {
+    match result {
+        Ok((value, detail)) => {
+            println!("  ✓ {:<9} {}", name, detail);
+            Ok(value)
+        }
+        Err(failure) => {
+            println!("  ✗ {:<9} {}", name, failure.reason);
+            println!("    {}", failure.hint);
+            Err(CliError::SshError(format!(
+                "'{}' failed the {} check",
+                servername, name
+            )))
+        }
+    }
+}
+
+fn resolve(host: &str, port: u16) -> Result<(Vec<SocketAddr>, String), Failure> {
+    let addresses: Vec<SocketAddr> = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| {
+            Failure::new(
+                format!("cannot resolve '{}': {}", host, e),
+                "Check the address with 'vx ssh connect', or your DNS settings",
+            )
+        })?
+        .collect();
+
+    let detail = addresses
+        .iter()
+        .map(|address| address.ip().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Ok((addresses, format!("{} resolves to {}", host, detail)))
+}
+
+/// Connects to the first address that accepts and reads the server's
+/// identification line, to tell an SSH server from anything else.
+fn connect(addresses: &[SocketAddr], port: u16) -> Result<((), String), Failure> {
+    let mut last_error = None;
+    for address in addresses {
+        let stream = match TcpStream::connect_timeout(address, TIMEOUT) {
+            Ok(stream) => stream,
+            Err(e) => {
+                last_error = Some(e);
+                continue;
+            }
+        };
+
+        let _ = stream.set_read_timeout(Some(TIMEOUT));
+        let mut banner = String::new();
+        let _ = BufReader::new((&stream).take(MAX_BANNER)).read_line(&mut banner);
+        let banner = banner.trim();
+        if banner.is_empty() {
+            return Err(Failure::new(
+                format!("{} accepts connections but sends nothing", address),
+                "A firewall or proxy may be intercepting the connection",
+            ));
+        }
+        if !banner.starts_with("SSH-") {
+            return Err(Failure::new(
+                format!("{} answers, but not as an SSH server", address),
+                format!("Check that port {} is the server's SSH port", port),
+            ));
+        }
+        return Ok(((), format!("{} is reachable ({})", address, banner)));
+    }
+
+    let error = last_error.expect("at least one address is resolved");
+    let hint = match error.kind() {
+        std::io::ErrorKind::ConnectionRefused => format!(
+            "Nothing listens on port {}; check that sshd is running",
+            port
+        ),
+        std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => {
+            "A firewall may be dropping the connection, or the host is down".to_string()
+        }
+        _ => "Check your network connection and the server's address".to_string(),
+    };
+    Err(Failure::new(
+        format!("cannot connect to port {}: {}", port, error),
+        hint,
+    ))
+}
+
+/// Checks the host key by attempting the `none` authentication method,
+/// which the server refuses only after ssh has verified its key. With
+/// `recorded`, the keys recorded in the vault are checked rather than
+/// `known_hosts`.
+fn verify_host_key(
+    servername: &str,
+    destination: &str,
+    host: &str,
+    recorded: Option<&TrustedHostKeys>,
+) -> Result<((), String), Failure> {
+    let mut cmd = ssh_batch();
+    if let Some(recorded) = recorded {
+        recorded.configure(&mut cmd);
+    }
+    let output = cmd
+        .arg("-o")
+        .arg("PreferredAuthentications=none")
+        .arg("-o")
+        .arg("IdentityAgent=none")
+        .arg(destination)
+        .arg("true")
+        .output()
+        .map_err(run_failure)?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    if recorded.is_some() && stderr.contains("Host key verification failed") {
+        return Err(Failure::new(
+            "the server's host key differs from the one recorded in the vault",
+            format!(
+                "If the server was reinstalled, forget the old key with: vx ssh hostkey {} forget",
+                servername
+            ),
+        ));
+    }
+    if stderr.contains("REMOTE HOST IDENTIFICATION HAS CHANGED") {
+        return Err(Failure::new(
+            "the server's host key has changed since it was last accepted",
+            format!(
+                "If the server was reinstalled, forget the old key with: ssh-keygen -R {}",
+                host
+            ),
+        ));
+    }
+    if stderr.contains("Host key verification failed") {
+        return Err(Failure::new(
+            "the server's host key is not in known_hosts",
+            format!(
+                "Connect once with 'vx ssh {}' to check and accept it",
+                servername
+            ),
+        ));
+    }
+    if output.status.success() || stderr.contains("Permission denied") {
+        let source = if recorded.is_some() {
+            "the vault"
+        } else {
+            "known_hosts"
+        };
+        return Ok(((), format!("matches {}", source)));
+    }
+    Err(unexpected(servername, &output))
+}
+
+/// Logs in with only the vault identity, offered by `key`; the file with
+/// its public key is handed to ssh so no other key is tried.
+fn log_in(
+    servername: &str,
+    destination: &str,
+    public_key: &str,
+    key: &IdentityKey,
+    public_key_file: &ScratchFile,
+    recorded: Option<&TrustedHostKeys>,
+) -> Result<((), String), Failure> {
+    let mut cmd = ssh_batch();
+    key.configure(&mut cmd);
+    if let Some(recorded) = recorded {
+        recorded.configure(&mut cmd);
+    }
+    let output = cmd
+        .arg("-o")
+        .arg("PreferredAuthentications=publickey")
+        .arg("-o")
+        .arg("IdentitiesOnly=yes")
+        .arg("-i")
+        .arg(public_key_file.path())
+        .arg(destination)
+        .arg("true")
+        .output()
+        .map_err(run_failure)?;
+
+    if output.status.success() {
+        return Ok(((), "logged in with the server's identity".to_string()));
+    }
+    if String::from_utf8_lossy(&output.stderr).contains("Permission denied") {
+        return Err(Failure::new(
+            "the server refused the identity's key",
+            format!(
+                "Add this line to ~/.ssh/authorized_keys on the server: {}",
+                public_key
+            ),
+        ));
+    }
+    Err(unexpected(servername, &output))
+}
+
+/// An ssh command that never prompts, reuses no shared connection and
+/// leaves `known_hosts` alone.
+fn ssh_batch() -> Command {
+    let mut cmd = Command::new("ssh");
+    cmd.arg("-o")
+        .arg("BatchMode=yes")
+        .arg("-o")
+        .arg(format!("ConnectTimeout={}", TIMEOUT.as_secs()))
+        .arg("-o")
+        .arg("ControlPath=none")
+        .arg("-o")
+        .arg("StrictHostKeyChecking=yes");
+    cmd
+}
+
+fn run_failure(e: std::io::Error) -> Failure {
+    Failure::new(
+        format!("cannot run ssh: {}", e),
+        "Install the OpenSSH client",
+    )
+}
+
+/// Reports the last line ssh printed for an error not recognized above.
+fn unexpected(servername: &str, output: &Output) -> Failure {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reason = stderr
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("ssh failed without a message")
+        .trim()
+        .to_string();
+    Failure::new(
+        reason,
+        format!(
+            "Connect with 'vx ssh {}' to see ssh's full output",
+            servername
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serves one connection, answering it with `banner`.
+    fn serve_banner(banner: &'static [u8]) -> (SocketAddr, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.write_all(banner);
+        });
+        (address, server)
+    }
+
+    #[test]
+    fn test_connect_recognizes_ssh_servers() {
+        let (address, server) = serve_banner(b"SSH-2.0-OpenSSH_9.6\r\n");
+        let ((), detail) =
+            connect(&[address], address.port()).unwrap_or_else(|f| panic!("{}", f.reason));
+        assert_eq!(detail, format!("{} is reachable (SSH-2.0-OpenSSH_9.6)", address));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_connect_reports_other_servers() {
+        let (address, server) = serve_banner(b"HTTP/1.1 400 Bad Request\r\n");
+        let failure = connect(&[address], address.port()).unwrap_err();
+        assert_eq!(
+            failure.reason,
+            format!("{} answers, but not as an SSH server", address)
+        );
+        server.join().unwrap();
+
+        let (address, server) = serve_banner(b"");
+        let failure = connect(&[address], address.port()).unwrap_err();
+        assert_eq!(
+            failure.reason,
+            format!("{} accepts connections but sends nothing", address)
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_connect_reports_closed_port() {
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let failure = connect(&[address], address.port()).unwrap_err();
+        let expected = format!("cannot connect to port {}", address.port());
+        assert!(failure.reason.starts_with(&expected));
+        assert!(failure.hint.starts_with("Nothing listens on port"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let (addresses, detail) =
+            resolve("127.0.0.1", 22).unwrap_or_else(|f| panic!("{}", f.reason));
+        assert_eq!(addresses, ["127.0.0.1:22".parse::<SocketAddr>().unwrap()]);
+        assert_eq!(detail, "127.0.0.1 resolves to 127.0.0.1");
+
+        let failure = resolve("not a host name", 22).unwrap_err();
+        assert!(failure.reason.starts_with("cannot resolve 'not a host name'"));
+    }
+
+    #[test]
+    fn test_failed_check_is_an_error() {
+        let failure = Err::<((), String), _>(Failure::new("refused", "try again"));
+        match check("web", "TCP", failure) {
+            Err(CliError::SshError(message)) => assert_eq!(message, "'web' failed the TCP check"),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+        assert_eq!(check("web", "DNS", Ok((7, "ok".to_string()))).unwrap(), 7);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unexpected_reports_last_line() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let output = Output {
+            status: std::process::ExitStatus::from_raw(255 << 8),
+            stdout: Vec::new(),
+            stderr: b"debug1: connecting\nssh: connect to host web: No route to host\n\n"
+                .to_vec(),
+        };
+        let failure = unexpected("web", &output);
+        assert_eq!(failure.reason, "ssh: connect to host web: No route to host");
+        assert_eq!(failure.hint, "Connect with 'vx ssh web' to see ssh's full output");
+
+        let output = Output { stderr: Vec::new(), ..output };
+        assert_eq!(unexpected("web", &output).reason, "ssh failed without a message");
+    }
+}
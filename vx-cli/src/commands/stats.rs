@@ -0,0 +1,84 @@
+//! Show how much the vault holds: counts, sizes, the largest and oldest
+//! secrets and when secrets expire.
+
+use crate::config::OutputFormat;
+use crate::error::CliError;
+use crate::storage;
+use serde::Serialize;
+use std::fs;
+use vx_core::stats::{self, format_size, VaultStats};
+use vx_core::ttl;
+
+/// The vault statistics with the file size, as printed by `--format json`.
+#[derive(Serialize)]
+struct Report<'a> {
+    #[serde(flatten)]
+    vault: &'a VaultStats,
+    file_bytes: u64,
+}
+
+/// Executes the stats command, listing the `top` largest and oldest
+/// secrets.
+pub fn execute(top: usize, format: OutputFormat) -> Result<(), CliError> {
+    let (vault, _key) = storage::load_vault_with_key_auto()?;
+    let now = ttl::current_timestamp();
+    let stats = stats::vault_stats(&vault, now, top);
+    let file_bytes = fs::metadata(storage::vault_path()?)?.len();
+
+    if format == OutputFormat::Json {
+        let report = Report {
+            vault: &stats,
+            file_bytes,
+        };
+        let json = serde_json::to_string_pretty(&report).map_err(std::io::Error::from)?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    println!("Vault file:       {}", format_size(file_bytes));
+    println!("Projects:         {}", stats.projects);
+    println!("Environments:     {} named", stats.environments);
+    println!(
+        "Secrets:          {} ({} encrypted)",
+        stats.secrets,
+        format_size(stats.secret_bytes)
+    );
+    println!("Aliases:          {}", stats.aliases);
+    println!(
+        "Attachments:      {} ({})",
+        stats.attachments,
+        format_size(stats.attachment_bytes)
+    );
+    println!("SSH identities:   {}", stats.ssh_identities);
+    println!("SSH servers:      {}", stats.ssh_servers);
+    println!("Kubeconfigs:      {}", stats.kubeconfigs);
+    println!("WireGuard:        {}", stats.wireguard_configs);
+    println!("In the trash:     {}", stats.trash_items);
+    println!("Log entries:      {}", stats.log_entries);
+
+    if !stats.largest.is_empty() {
+        println!("\nLargest secrets:");
+        for secret in &stats.largest {
+            println!("  {:>10}  {}", format_size(secret.size), secret.location);
+        }
+        println!("\nOldest secrets:");
+        for secret in &stats.oldest {
+            println!(
+                "  {}  {} ({})",
+                ttl::format_timestamp(secret.created_at),
+                secret.location,
+                ttl::format_relative(secret.created_at, now)
+            );
+        }
+    }
+
+    let expiry = &stats.expiry;
+    println!("\nExpiry:");
+    println!("  expired         {}", expiry.expired);
+    println!("  within a day    {}", expiry.within_day);
+    println!("  within a week   {}", expiry.within_week);
+    println!("  within 30 days  {}", expiry.within_month);
+    println!("  later           {}", expiry.later);
+    println!("  never           {}", expiry.never);
+    Ok(())
+}
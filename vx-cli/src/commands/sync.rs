@@ -0,0 +1,105 @@
+//! `vx sync pull`: merge another copy of the vault into this one.
+//!
+//! Meant for a vault directory kept in sync by a tool such as Syncthing or
+//! Dropbox, which leaves a conflicted copy next to the vault file when two
+//! machines save it apart. The copies are merged secret by secret (see
+//! `vx_core::merge`), and only a secret changed on both sides is asked
+//! about.
+
+use crate::error::CliError;
+use crate::input;
+use crate::progress;
+use crate::storage;
+use std::fs;
+use std::path::Path;
+use vx_core::merge::{self, Conflict, Side};
+use vx_core::{ttl, vault, Operation, VaultError};
+
+/// Executes the sync pull command.
+/// Merges the copy of the vault at `file` into the vault.
+pub fn pull(file: &str) -> Result<(), CliError> {
+    let data = fs::read(file).map_err(|_| CliError::FileNotFound(file.to_string()))?;
+    vault::verify_file(&data)?;
+
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    let mut remote = vault::open_vault(&data, &encryption_key).map_err(|e| match e {
+        VaultError::AuthenticationFailed => CliError::Generic(format!(
+            "{} is not a copy of this vault; copy single projects with 'vx import-project'",
+            file
+        )),
+        e => CliError::Vault(e),
+    })?;
+    storage::attach_shards_of(&mut remote, Path::new(file))?;
+    progress::with_spinner("Decrypting copy...", || remote.unseal_all(&encryption_key))?;
+
+    let plan = merge::plan(&vault, &remote)?;
+    let now = ttl::current_timestamp();
+    for name in &plan.skipped {
+        eprintln!(
+            "⚠️  Project '{}' has a different passphrase in {}; it was left as it is.",
+            name, file
+        );
+    }
+    if plan.is_empty() {
+        println!("Already up to date with {}.", file);
+        return Ok(());
+    }
+
+    match plan.diverged_at {
+        Some(at) => println!("The copies diverged {}.", ttl::format_relative(at, now)),
+        None => println!("The copies share no history; secrets that differ are conflicts."),
+    }
+    for update in &plan.updates {
+        println!("  {}", update);
+    }
+    if !plan.conflicts.is_empty() {
+        println!(
+            "\n{} secret(s) changed on both sides:",
+            plan.conflicts.len()
+        );
+    }
+
+    let mut choices = Vec::new();
+    for conflict in &plan.conflicts {
+        choices.push(ask(conflict, now)?);
+    }
+    let mut choices = choices.into_iter();
+    merge::apply(&mut vault, &remote, &plan, &encryption_key, |_| {
+        choices.next().unwrap_or(Side::Local)
+    })?;
+
+    vault.record(Operation::Sync, None, None);
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!(
+        "✓ Merged {} change(s) and {} conflict(s) from {}.",
+        plan.updates.len(),
+        plan.conflicts.len(),
+        file
+    );
+    println!("  Once every machine has pulled it, the copy can be deleted.");
+    Ok(())
+}
+
+/// Asks which side's version of a conflicting secret to keep.
+fn ask(conflict: &Conflict, now: u64) -> Result<Side, CliError> {
+    let describe = |changed: Option<u64>| match changed {
+        Some(at) => format!("changed {}", ttl::format_relative(at, now)),
+        None => "removed".to_string(),
+    };
+    println!(
+        "  {}: {} here, {} in the copy",
+        conflict.location,
+        describe(conflict.local),
+        describe(conflict.remote)
+    );
+
+    loop {
+        let answer = input::read_answer("  Keep [l]ocal or take [r]emote? ")?;
+        match answer.to_ascii_lowercase().as_str() {
+            "l" | "local" => return Ok(Side::Local),
+            "r" | "remote" => return Ok(Side::Remote),
+            _ => continue,
+        }
+    }
+}
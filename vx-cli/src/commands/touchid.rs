@@ -0,0 +1,129 @@
+//! Unlock the vault with Touch ID instead of the master password (macOS).
+
+use crate::error::CliError;
+
+/// Label of the key slot holding the keychain credential
+#[cfg(target_os = "macos")]
+const SLOT_LABEL: &str = "touch-id";
+
+/// Executes the touchid enable command.
+/// Adds a key slot for a random credential kept in the keychain behind
+/// Touch ID, offered instead of the master password for `window` after the
+/// password was last entered.
+#[cfg(target_os = "macos")]
+pub fn enable(window: &str) -> Result<(), CliError> {
+    use crate::progress;
+    use crate::storage;
+    use crate::touchid::{self, TouchIdState};
+    use vx_core::{crypto, ttl, SlotKind};
+
+    let window = ttl::parse_ttl(window).map_err(|e| CliError::InvalidTtl(e.to_string()))?;
+
+    // Ask for a credential even while the daemon holds the key
+    let (mut vault, encryption_key) = storage::open_vault_with_credential()?;
+
+    // Enabling again replaces the slot and the keychain item
+    if let Some(state) = touchid::read_state()? {
+        if vault.key_slots().iter().any(|slot| slot.id == state.slot) {
+            vault.remove_key_slot(state.slot)?;
+        }
+    }
+
+    let credential = crypto::generate_key();
+    touchid::store_credential(&credential)?;
+    let id = progress::with_spinner("Wrapping key...", || {
+        vault.add_key_slot(
+            SlotKind::Biometric,
+            SLOT_LABEL,
+            credential.as_slice(),
+            &encryption_key,
+        )
+    })?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+    touchid::write_state(&TouchIdState {
+        slot: id,
+        window,
+        expires_at: ttl::current_timestamp().saturating_add(window),
+    })?;
+
+    println!("✓ Touch ID unlock enabled (key slot {}).", id);
+    println!(
+        "  vx asks for Touch ID instead of the master password for {} after",
+        ttl::format_duration(window)
+    );
+    println!("  the password was last entered, and for the password if Touch ID fails.");
+    Ok(())
+}
+
+/// Executes the touchid disable command.
+/// Removes the key slot, the keychain item and the unlock window.
+#[cfg(target_os = "macos")]
+pub fn disable() -> Result<(), CliError> {
+    use crate::storage;
+    use crate::touchid;
+
+    let Some(state) = touchid::read_state()? else {
+        println!("Touch ID unlock is not enabled.");
+        return Ok(());
+    };
+
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+    if vault.key_slots().iter().any(|slot| slot.id == state.slot) {
+        vault.remove_key_slot(state.slot)?;
+        storage::save_vault(&mut vault, &encryption_key)?;
+    }
+    touchid::delete_credential()?;
+    touchid::remove_state()?;
+
+    println!("✓ Touch ID unlock disabled.");
+    Ok(())
+}
+
+/// Executes the touchid status command.
+/// Reads only the state file, so neither Touch ID nor a password is needed.
+#[cfg(target_os = "macos")]
+pub fn status() -> Result<(), CliError> {
+    use crate::touchid;
+    use vx_core::ttl;
+
+    let Some(state) = touchid::read_state()? else {
+        println!("Touch ID unlock is not enabled. Enable it with 'vx touchid enable'.");
+        return Ok(());
+    };
+
+    let now = ttl::current_timestamp();
+    println!("Touch ID unlock is enabled (key slot {}).", state.slot);
+    if state.is_active(now) {
+        println!(
+            "  Touch ID is offered for another {}; entering the password renews it to {}.",
+            ttl::format_duration(state.expires_at - now),
+            ttl::format_duration(state.window)
+        );
+    } else {
+        println!(
+            "  The window has passed; the next password unlock reopens it for {}.",
+            ttl::format_duration(state.window)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn enable(_window: &str) -> Result<(), CliError> {
+    Err(unsupported())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn disable() -> Result<(), CliError> {
+    Err(unsupported())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn status() -> Result<(), CliError> {
+    Err(unsupported())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn unsupported() -> CliError {
+    CliError::Generic("Touch ID unlock is only supported on macOS".to_string())
+}
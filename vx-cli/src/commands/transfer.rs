@@ -0,0 +1,106 @@
+//! Move the vault to another machine in a single encrypted bundle.
+
+use crate::error::CliError;
+use crate::input;
+use crate::progress;
+use crate::storage;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use vx_core::transfer::{self, BundleContents, BundledBlob};
+use vx_core::ttl;
+
+/// Executes the transfer export command.
+/// Writes the vault file and its attachments to `out`, encrypted under a
+/// one-time passphrase that is printed once.
+pub fn export(out: &str) -> Result<(), CliError> {
+    let (vault, encryption_key) = storage::open_vault_with_key_auto()?;
+
+    if Path::new(out).exists() && !input::confirm(&format!("{} exists. Overwrite it?", out))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let attachments = storage::list_attachment_blobs()?
+        .into_iter()
+        .map(|id| {
+            Ok(BundledBlob {
+                data: storage::read_attachment_blob(&id)?,
+                id,
+            })
+        })
+        .collect::<Result<Vec<_>, CliError>>()?;
+    let contents = BundleContents {
+        vault: storage::read_saved_vault(&vault, &encryption_key)?,
+        attachments,
+    };
+
+    let passphrase = transfer::generate_passphrase();
+    let bundle = progress::with_spinner("Encrypting bundle...", || {
+        transfer::seal(&contents, passphrase.as_bytes())
+    })?;
+
+    let mut file = fs::File::create(out)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions = fs::Permissions::from_mode(0o600);
+        file.set_permissions(permissions)?;
+    }
+
+    file.write_all(&bundle)?;
+    file.sync_all()?;
+
+    println!(
+        "\n✓ Transfer bundle written to {} ({} attachment(s)).",
+        out,
+        contents.attachments.len()
+    );
+    println!("\n  Transfer passphrase (shown only this once):\n");
+    println!("      {}\n", passphrase.as_str());
+    println!("  On the new machine, run 'vx transfer import <FILE>' and enter it.");
+    println!("  The vault still unlocks with its current master password there.");
+    println!("  Send the passphrase separately from the bundle, and delete the");
+    println!("  bundle once it has been imported.");
+
+    Ok(())
+}
+
+/// Executes the transfer import command.
+/// Installs the vault and attachments from a bundle made by
+/// `vx transfer export`. Refuses to replace an existing vault.
+pub fn import(file: &str) -> Result<(), CliError> {
+    let data = fs::read(file).map_err(|_| CliError::FileNotFound(file.to_string()))?;
+    let info = transfer::read_info(&data)?;
+
+    if storage::vault_exists()? {
+        return Err(CliError::Generic(format!(
+            "A vault already exists at {}; move it away before importing",
+            storage::vault_path()?.display()
+        )));
+    }
+
+    println!(
+        "Transfer bundle made {} with VaultX {} ({} attachment(s)).",
+        ttl::format_relative(info.created_at, ttl::current_timestamp()),
+        info.created_with,
+        info.attachments
+    );
+    let passphrase = input::read_password("Enter transfer passphrase: ")?;
+    let contents = progress::with_spinner("Decrypting bundle...", || {
+        transfer::open(&data, passphrase.trim().as_bytes())
+    })?;
+
+    // The vault goes in last, so an interrupted import leaves no vault
+    // missing its attachments
+    for blob in &contents.attachments {
+        storage::write_attachment_blob(&blob.id, &blob.data)?;
+    }
+    storage::install_vault_file(&contents.vault)?;
+
+    println!("✓ Vault imported to {}.", storage::vault_path()?.display());
+    println!("  Unlock it with the master password you used on the old machine,");
+    println!("  then delete {}.", file);
+    Ok(())
+}
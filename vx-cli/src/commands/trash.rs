@@ -0,0 +1,113 @@
+//! List, restore and empty the trash of removed secrets and projects.
+
+use super::qualified_key;
+use crate::config;
+use crate::error::CliError;
+use crate::input;
+use crate::storage;
+use vx_core::trash::{self, TrashedItem};
+use vx_core::{ttl, Operation};
+
+/// Executes the trash list command.
+pub fn list() -> Result<(), CliError> {
+    let (vault, _encryption_key) = storage::open_vault_with_key_auto()?;
+
+    if vault.trash().is_empty() {
+        println!("The trash is empty.");
+        return Ok(());
+    }
+
+    let now = ttl::current_timestamp();
+    let retention = config::get()
+        .trash_retention
+        .unwrap_or(trash::DEFAULT_RETENTION);
+
+    println!("\n🗑  Trash\n");
+    println!("{:<5} {:<12} {:<12} ITEM", "ID", "REMOVED", "PURGED");
+    println!("{}", "─".repeat(80));
+
+    for entry in vault.trash() {
+        println!(
+            "{:<5} {:<12} {:<12} {}",
+            entry.id,
+            ttl::format_relative(entry.deleted_at, now),
+            ttl::format_until(entry.deleted_at.saturating_add(retention), now),
+            entry.item
+        );
+    }
+
+    println!("{}", "─".repeat(80));
+    println!("Restore an item with 'vx trash restore <ID>'.");
+    Ok(())
+}
+
+/// Executes the trash restore command.
+/// Puts item `id` back where it was removed from.
+pub fn restore(id: u32) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+
+    // A secret or environment goes back into its project, which must be
+    // unsealed; a restored project stays sealed
+    let project = vault
+        .trash()
+        .iter()
+        .find(|entry| entry.id == id)
+        .and_then(|entry| match &entry.item {
+            TrashedItem::Project { .. } => None,
+            item => Some(item.project().to_string()),
+        });
+    if let Some(project) = project {
+        vault.unseal_project(&project, &encryption_key)?;
+    }
+
+    let entry = vault.restore_from_trash(id)?;
+    let (project, name) = log_target(&entry.item);
+    vault.record(Operation::Restore, Some(project), name.as_deref());
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("✓ Restored {}.", entry.item);
+    Ok(())
+}
+
+/// Returns the project and name a trashed item is recorded under in the
+/// access log, as `vx remove` records it.
+pub(super) fn log_target(item: &TrashedItem) -> (&str, Option<String>) {
+    match item {
+        TrashedItem::Secret { location, .. } => (
+            &location.project,
+            Some(qualified_key(location.env.as_deref(), &location.key)),
+        ),
+        TrashedItem::Environment { project, env, .. } => (project, Some(env.clone())),
+        TrashedItem::Project { name, .. } => (name, None),
+    }
+}
+
+/// Executes the trash empty command.
+/// Permanently deletes everything in the trash.
+pub fn empty() -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+
+    let count = vault.trash().len();
+    if count == 0 {
+        println!("The trash is empty.");
+        return Ok(());
+    }
+
+    if !input::confirm(&format!(
+        "Permanently delete {} item(s) in the trash? They cannot be restored.",
+        count
+    ))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let purged = vault.empty_trash();
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    for blob_id in purged.iter().flat_map(|entry| entry.item.blob_ids()) {
+        storage::remove_attachment_blob(blob_id)?;
+    }
+
+    println!("✓ Deleted {} item(s).", purged.len());
+    Ok(())
+}
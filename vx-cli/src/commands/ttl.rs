@@ -0,0 +1,75 @@
+//! Show or change when a secret expires, without re-entering its value.
+
+use super::qualified_key;
+use crate::error::CliError;
+use crate::storage;
+use vx_core::{ttl, Operation, SecretRef, VaultError};
+
+/// What to do with the secret's TTL
+pub enum Change<'a> {
+    /// Print when it expires
+    Show,
+    /// Make it expire this long from now (e.g. `30d`)
+    Set(&'a str),
+    /// Make it never expire
+    Clear,
+}
+
+/// Executes the ttl command.
+/// Changes are tracked like edits, so `vx undo` reverts them.
+pub fn execute(
+    project: &str,
+    key: &str,
+    env: Option<&str>,
+    change: Change,
+) -> Result<(), CliError> {
+    let ttl_seconds = match change {
+        Change::Set(ttl) => {
+            Some(ttl::parse_ttl(ttl).map_err(|e| CliError::InvalidTtl(e.to_string()))?)
+        }
+        Change::Show | Change::Clear => None,
+    };
+
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    let location = SecretRef::new(project, env, key);
+    let name = qualified_key(env, key);
+
+    let expires_at = match change {
+        Change::Show => {
+            vault
+                .projects
+                .get(project)
+                .ok_or_else(|| CliError::ProjectNotFound(project.to_string()))?
+                .secrets_in(env)?
+                .get(key)
+                .ok_or_else(|| VaultError::SecretNotFound(name.clone()))?
+                .expires_at
+        }
+        Change::Set(_) | Change::Clear => {
+            let expires_at = vault.track_write(Operation::Edit, location.clone(), |vault| {
+                vault.set_ttl_in(project, env, key, ttl_seconds)
+            })?;
+            vault.record(Operation::Edit, Some(project), Some(&name));
+            storage::save_vault(&mut vault, &encryption_key)?;
+            expires_at
+        }
+    };
+
+    let now = ttl::current_timestamp();
+    let status = match expires_at {
+        None => "never expires".to_string(),
+        Some(expires_at) if expires_at <= now => {
+            format!("expired on {}", ttl::format_timestamp(expires_at))
+        }
+        Some(expires_at) => format!(
+            "expires on {} ({})",
+            ttl::format_timestamp(expires_at),
+            ttl::format_until(expires_at, now)
+        ),
+    };
+    match change {
+        Change::Show => println!("{} {}.", location, status),
+        Change::Set(_) | Change::Clear => println!("✓ {} now {}.", location, status),
+    }
+    Ok(())
+}
@@ -0,0 +1,121 @@
+//! Calibrate Argon2id for this machine.
+
+use crate::error::CliError;
+use crate::input;
+use crate::progress::{self, Spinner};
+use crate::storage;
+use std::fs;
+use std::time::{Duration, Instant};
+use vx_core::crypto::{self, KdfParams};
+use vx_core::{SlotKind, VaultError};
+use zeroize::Zeroizing;
+
+/// Executes the tune command.
+/// Benchmarks Argon2id with `memory_mb` of memory and recommends the number
+/// of passes that takes about `target_ms` to unlock. With `apply`, re-wraps
+/// key slot `slot` (by default the only password slot) with them.
+pub fn execute(
+    target_ms: u64,
+    memory_mb: u32,
+    apply: bool,
+    slot: Option<u32>,
+) -> Result<(), CliError> {
+    let spinner = Spinner::start(&format!("Benchmarking Argon2id with {} MB...", memory_mb));
+    let kdf = crypto::calibrate_kdf(Duration::from_millis(target_ms), memory_mb * 1024)?;
+
+    // Time the recommendation itself rather than extrapolating
+    spinner.set_message("Checking the recommendation...");
+    let start = Instant::now();
+    crypto::derive_key_with_params(b"calibration", &crypto::generate_salt(), &kdf)?;
+    let took = start.elapsed();
+    drop(spinner);
+
+    println!(
+        "Recommended: {} MB × {} passes × {} lanes ({} ms on this machine)",
+        kdf.memory_kib / 1024,
+        kdf.iterations,
+        kdf.parallelism,
+        took.as_millis()
+    );
+    if is_weaker_than_standard(&kdf) {
+        let standard = KdfParams::STANDARD;
+        println!(
+            "⚠ This is weaker than the default ({} MB × {} passes); consider a longer target.",
+            standard.memory_kib / 1024,
+            standard.iterations
+        );
+    }
+
+    if !apply {
+        println!(
+            "\nApply it with: vx tune --target {} --memory {} --apply",
+            target_ms, memory_mb
+        );
+        return Ok(());
+    }
+
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+
+    let id = match slot {
+        Some(id) => id,
+        None => {
+            let mut passwords = vault
+                .key_slots()
+                .iter()
+                .filter(|slot| slot.kind == SlotKind::Password);
+            match (passwords.next(), passwords.next()) {
+                (Some(only), None) => only.id,
+                _ => {
+                    return Err(CliError::Generic(
+                        "Choose the key slot to re-wrap with --slot (see 'vx keyslot list')"
+                            .to_string(),
+                    ))
+                }
+            }
+        }
+    };
+    let kind = vault
+        .key_slots()
+        .iter()
+        .find(|slot| slot.id == id)
+        .ok_or(VaultError::KeySlotNotFound(id))?
+        .kind;
+
+    let credential = match kind {
+        SlotKind::Keyfile => {
+            let path = input::read_input(&format!("Keyfile for slot {}: ", id))?;
+            Zeroizing::new(fs::read(&path).map_err(|_| CliError::FileNotFound(path))?)
+        }
+        SlotKind::Biometric => {
+            return Err(CliError::Generic(
+                "The Touch ID slot has no credential to enter; re-create it with 'vx touchid enable'"
+                    .to_string(),
+            ))
+        }
+        kind => {
+            let prompt = format!("Enter the {} for slot {}: ", kind, id);
+            Zeroizing::new(input::read_password(&prompt)?.as_bytes().to_vec())
+        }
+    };
+
+    progress::with_spinner("Re-wrapping key slot...", || {
+        vault.rewrap_key_slot(id, &credential, kdf)
+    })?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!(
+        "✓ Slot {} now uses {} MB × {} passes × {} lanes.",
+        id,
+        kdf.memory_kib / 1024,
+        kdf.iterations,
+        kdf.parallelism
+    );
+    Ok(())
+}
+
+/// Returns whether `kdf` uses less memory than the standard preset, or as
+/// much memory but fewer passes.
+fn is_weaker_than_standard(kdf: &KdfParams) -> bool {
+    let standard = KdfParams::STANDARD;
+    (kdf.memory_kib, kdf.iterations) < (standard.memory_kib, standard.iterations)
+}
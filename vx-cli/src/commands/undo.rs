@@ -0,0 +1,95 @@
+//! Revert the last add, edit or removal.
+
+use super::{qualified_key, trash};
+use crate::error::CliError;
+use crate::input;
+use crate::storage;
+use vx_core::trash::TrashedItem;
+use vx_core::undo::Change;
+use vx_core::{ttl, Operation};
+
+/// Executes the undo command.
+/// Describes the last tracked change and, once confirmed, reverts it.
+pub fn execute() -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::open_vault_with_key_auto()?;
+
+    let Some(record) = vault.last_change().cloned() else {
+        println!("Nothing to undo.");
+        return Ok(());
+    };
+
+    // The project the change touched must be unsealed to revert it; a
+    // project restored from the trash stays sealed
+    let trashed = match &record.change {
+        Change::Trash { id } => vault.trash().iter().find(|entry| entry.id == *id).cloned(),
+        Change::Write { .. } => None,
+    };
+    let project = match (&record.change, &trashed) {
+        (Change::Write { location, .. }, _) => Some(location.project.clone()),
+        (_, Some(entry)) if !matches!(entry.item, TrashedItem::Project { .. }) => {
+            Some(entry.item.project().to_string())
+        }
+        _ => None,
+    };
+    if let Some(project) = project.filter(|project| vault.is_sealed(project)) {
+        vault.unseal_project(&project, &encryption_key)?;
+    }
+
+    let (change, revert) = match (&record.change, &trashed) {
+        (
+            Change::Write {
+                location,
+                before: None,
+                ..
+            },
+            _,
+        ) => (
+            format!("added {}", location),
+            format!("{} will be removed", location),
+        ),
+        (Change::Write { location, .. }, _) if record.operation == Operation::Edit => (
+            format!("edited {}", location),
+            format!("{} will get its previous value back", location),
+        ),
+        (Change::Write { location, .. }, _) => (
+            format!("overwrote {}", location),
+            format!("{} will get its previous value back", location),
+        ),
+        (Change::Trash { .. }, Some(entry)) => (
+            format!("removed {}", entry.item),
+            format!("{} will be restored from the trash", entry.item),
+        ),
+        (Change::Trash { id }, None) => (
+            format!("removed trash entry {}", id),
+            "it is no longer in the trash".to_string(),
+        ),
+    };
+
+    println!(
+        "Last change ({}): {}",
+        ttl::format_relative(record.timestamp, ttl::current_timestamp()),
+        change
+    );
+    println!("Undoing it: {}.", revert);
+    if !input::confirm("Undo this change?")? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    vault.undo()?;
+    match (&record.change, &trashed) {
+        (Change::Write { location, .. }, _) => {
+            let name = qualified_key(location.env.as_deref(), &location.key);
+            vault.record(Operation::Undo, Some(&location.project), Some(&name));
+        }
+        (_, Some(entry)) => {
+            let (project, name) = trash::log_target(&entry.item);
+            vault.record(Operation::Undo, Some(project), name.as_deref());
+        }
+        (_, None) => {}
+    }
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("✓ Undone: {}.", change);
+    Ok(())
+}
@@ -0,0 +1,130 @@
+//! Bring WireGuard interfaces up and down with configs stored in the vault.
+
+use crate::error::CliError;
+use crate::input;
+use crate::scratch::ScratchFile;
+use crate::storage;
+use std::process::Command;
+use vx_core::{ttl, wireguard, Operation, VaultError};
+use zeroize::Zeroizing;
+
+/// Executes the wg add command.
+/// Stores the config at `file` for the interface `name`; the file itself is
+/// left in place for the user to delete.
+pub fn add(name: &str, file: &str) -> Result<(), CliError> {
+    let config =
+        Zeroizing::new(std::fs::read(file).map_err(|_| CliError::FileNotFound(file.to_string()))?);
+    // Check before asking to overwrite anything
+    wireguard::validate_interface_name(name)?;
+    wireguard::validate_config(&config)?;
+
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+
+    if vault.wireguard_configs.contains_key(name) {
+        if !input::confirm(&format!(
+            "WireGuard config '{}' already exists. Overwrite?",
+            name
+        ))? {
+            println!("Cancelled.");
+            return Ok(());
+        }
+        vault.remove_wireguard_config(name)?;
+    }
+    vault.add_wireguard_config(name, &config, &encryption_key)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("✓ WireGuard config '{}' stored.", name);
+    println!("  Bring the interface up with: vx wg up {}", name);
+    println!("  You can now delete {}.", file);
+    Ok(())
+}
+
+/// Executes the wg list command.
+/// Lists interface names only; nothing is decrypted.
+pub fn list() -> Result<(), CliError> {
+    let vault = storage::load_vault_auto()?;
+
+    if vault.wireguard_configs.is_empty() {
+        println!("No WireGuard configs stored. Add one with 'vx wg add <name> <file>'.");
+        return Ok(());
+    }
+
+    let now = ttl::current_timestamp();
+    let mut configs: Vec<_> = vault.wireguard_configs.values().collect();
+    configs.sort_by(|a, b| a.name.cmp(&b.name));
+
+    println!("WireGuard configs:");
+    for config in configs {
+        println!(
+            "  • {} (added {})",
+            config.name,
+            ttl::format_relative(config.created_at, now)
+        );
+    }
+    Ok(())
+}
+
+/// Executes the wg remove command.
+pub fn remove(name: &str) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+
+    if !vault.wireguard_configs.contains_key(name) {
+        return Err(VaultError::WireguardConfigNotFound(name.to_string()).into());
+    }
+    if !input::confirm(&format!("Remove WireGuard config '{}'?", name))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    vault.remove_wireguard_config(name)?;
+    storage::save_vault(&mut vault, &encryption_key)?;
+
+    println!("✓ WireGuard config '{}' removed.", name);
+    Ok(())
+}
+
+/// Executes the wg up command.
+pub fn up(name: &str) -> Result<(), CliError> {
+    wg_quick("up", name)
+}
+
+/// Executes the wg down command. `wg-quick` needs the config again to undo
+/// its routes and hooks.
+pub fn down(name: &str) -> Result<(), CliError> {
+    wg_quick("down", name)
+}
+
+/// Runs `wg-quick <action>` on the stored config for `name`.
+///
+/// # Security
+/// - The config only exists as a `ScratchFile` named after the interface
+///   while wg-quick runs; wg-quick asks for sudo itself when needed
+fn wg_quick(action: &str, name: &str) -> Result<(), CliError> {
+    let (mut vault, encryption_key) = storage::load_vault_with_key_auto()?;
+    let config = vault.get_wireguard_config(name, &encryption_key)?;
+    storage::record_access(
+        &mut vault,
+        Operation::Wireguard,
+        None,
+        Some(name),
+        &encryption_key,
+    );
+
+    let config_file = ScratchFile::create(&format!("{}.conf", name), config.expose())?;
+    let status = Command::new("wg-quick")
+        .arg(action)
+        .arg(config_file.path())
+        .status();
+    drop(config_file);
+
+    let status =
+        status.map_err(|e| CliError::Generic(format!("Failed to execute wg-quick: {}", e)))?;
+    if !status.success() {
+        return Err(CliError::Generic(format!(
+            "wg-quick exited with status: {}",
+            status.code().unwrap_or(-1)
+        )));
+    }
+
+    Ok(())
+}
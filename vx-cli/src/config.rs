@@ -0,0 +1,254 @@
+//! User configuration (`~/.vaultx/config.toml`).
+//!
+//! Read once at startup by `main`. Every key is optional and falls back to
+//! the built-in default; unknown keys are rejected so typos do not go
+//! unnoticed.
+//!
+//! ```toml
+//! vault_path = "~/Sync/vaultx" # instead of ~/.vaultx
+//! default_ttl = "90d"          # vx add without --ttl
+//! default_project = "my-app"   # when no project is given or set by .vaultx.toml
+//! output = "json"              # vx list / vx audit without --format
+//! session_timeout = "8h"       # how long vx login's cached password lasts
+//! session_idle_timeout = "30m" # drop the cached password after this long unused
+//! clipboard_timeout = 30       # seconds before vx get --copy clears it
+//! remove_expired_after = "30d" # drop secrets this long past their TTL on save
+//! trash_retention = "14d"      # how long removed items can be restored (default 30d)
+//! min_password_entropy = 60    # bits required of new vault passwords (default 50, 0 = off)
+//! compress = true              # zstd-compress the vault before encrypting it on save
+//! sharded = true               # keep each project in a file of its own
+//!
+//! [audit]
+//! long_lived_days = 180
+//! min_idle = "180d"            # vx audit without --min-idle
+//! certificate_warning_days = 14 # flag certificates this close to expiry (default 30)
+//!
+//! [hooks]                      # shell commands run on vault events; see `hooks`
+//! post-add = "notify-send \"VaultX: $VX_HOOK_NAME added\""
+//! post-remove = "./sync.sh"
+//! pre-save = "./check.sh"      # a failing pre-save hook stops the save
+//! expired-detected = "./page-oncall.sh"
+//!
+//! [plugins.deploy]             # let 'vx deploy' run vx-deploy; see `commands::plugin`
+//! projects = ["my-app"]        # projects its session may read (none by default)
+//! write = true                 # and add secrets to (default false)
+//! session = "15m"              # how long its session lasts (default 1h)
+//! ```
+
+use crate::error::CliError;
+use crate::manifest;
+use crate::storage;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use vx_core::audit::{
+    AuditThresholds, CERTIFICATE_WARNING_DAYS, LONG_LIVED_DAYS, SECONDS_PER_DAY,
+};
+use vx_core::strength::DEFAULT_MIN_ENTROPY;
+use vx_core::ttl;
+
+/// Directory holding the config file, relative to the home directory
+const CONFIG_DIR: &str = ".vaultx";
+
+/// Config file name
+const CONFIG_FILE: &str = "config.toml";
+
+/// Seconds a copied secret stays in the clipboard by default
+const DEFAULT_CLIPBOARD_TIMEOUT: u64 = 45;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// How commands that support several formats print their results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Human-readable tables
+    #[default]
+    Text,
+    /// JSON for scripts
+    Json,
+}
+
+/// Audit settings (`[audit]`).
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    /// Days after which a secret or SSH identity is long-lived
+    pub long_lived_days: u64,
+    /// Time without a read after which a secret is unused; not checked if
+    /// unset
+    #[serde(deserialize_with = "duration")]
+    pub min_idle: Option<u64>,
+    /// Days before expiry from which a certificate is flagged
+    pub certificate_warning_days: u64,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            long_lived_days: LONG_LIVED_DAYS,
+            min_idle: None,
+            certificate_warning_days: CERTIFICATE_WARNING_DAYS,
+        }
+    }
+}
+
+impl AuditConfig {
+    /// Returns the thresholds to audit with.
+    pub fn thresholds(&self) -> AuditThresholds {
+        AuditThresholds {
+            long_lived_days: self.long_lived_days,
+            min_idle_days: self.min_idle.map(|seconds| seconds / SECONDS_PER_DAY),
+            certificate_warning_days: self.certificate_warning_days,
+        }
+    }
+}
+
+/// Commands run on vault events (`[hooks]`); see `hooks`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct HooksConfig {
+    /// Run for each secret added, after the vault is saved
+    pub post_add: Option<String>,
+    /// Run for each secret removed, after the vault is saved
+    pub post_remove: Option<String>,
+    /// Run before the vault is saved; the save is cancelled if it fails
+    pub pre_save: Option<String>,
+    /// Run for each secret found to have expired since the vault was last
+    /// saved
+    pub expired_detected: Option<String>,
+}
+
+/// A plugin allowed to run (`[plugins.<name>]`); see `commands::plugin`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PluginConfig {
+    /// Projects whose secrets the plugin's session may read; without any,
+    /// the plugin runs without a session
+    pub projects: Vec<String>,
+    /// Whether the session may also add secrets to those projects
+    pub write: bool,
+    /// How long the session lasts; an hour if unset
+    #[serde(deserialize_with = "duration")]
+    pub session: Option<u64>,
+}
+
+/// The contents of the config file. Durations are parsed to seconds.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Directory holding the vault; `VX_VAULT_PATH` overrides it
+    pub vault_path: Option<PathBuf>,
+    /// TTL given to secrets added without `--ttl`
+    #[serde(deserialize_with = "duration")]
+    pub default_ttl: Option<u64>,
+    /// Project used by commands whose project argument is omitted
+    pub default_project: Option<String>,
+    /// Format used by commands run without `--format`
+    pub output: OutputFormat,
+    /// How long a password cached by `vx login` stays valid
+    #[serde(deserialize_with = "duration")]
+    pub session_timeout: Option<u64>,
+    /// How long a password cached by `vx login` may go unused before it is
+    /// dropped
+    #[serde(deserialize_with = "duration")]
+    pub session_idle_timeout: Option<u64>,
+    /// Seconds before `vx get --copy` clears the clipboard
+    pub clipboard_timeout: u64,
+    /// How long after expiring a secret is removed the next time the vault
+    /// is saved; expired secrets are kept if unset
+    #[serde(deserialize_with = "duration")]
+    pub remove_expired_after: Option<u64>,
+    /// How long removed items stay in the trash; 30 days if unset
+    #[serde(deserialize_with = "duration")]
+    pub trash_retention: Option<u64>,
+    /// Estimated entropy, in bits, required of new master passwords and
+    /// password slots; 0 accepts any password
+    pub min_password_entropy: u32,
+    /// Whether to compress the vault payload when saving; the vault is
+    /// left as it is if unset
+    pub compress: Option<bool>,
+    /// Whether to keep each project in a shard file of its own when saving;
+    /// the vault is left as it is if unset
+    pub sharded: Option<bool>,
+    pub audit: AuditConfig,
+    pub hooks: HooksConfig,
+    /// Plugins allowed to run, by command name
+    pub plugins: HashMap<String, PluginConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            vault_path: None,
+            default_ttl: None,
+            default_project: None,
+            output: OutputFormat::default(),
+            session_timeout: None,
+            session_idle_timeout: None,
+            clipboard_timeout: DEFAULT_CLIPBOARD_TIMEOUT,
+            remove_expired_after: None,
+            trash_retention: None,
+            min_password_entropy: DEFAULT_MIN_ENTROPY,
+            compress: None,
+            sharded: None,
+            audit: AuditConfig::default(),
+            hooks: HooksConfig::default(),
+            plugins: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns `project`, or if it is omitted the project named by the
+    /// repository's `.vaultx.toml`, or the default project.
+    pub fn project(&self, project: Option<String>) -> Result<String, CliError> {
+        project
+            .or_else(|| manifest::get().map(|manifest| manifest.project.clone()))
+            .or_else(|| self.default_project.clone())
+            .ok_or_else(|| {
+                CliError::Generic(format!(
+                    "No project given, no {} in this repository and no default_project in the config file",
+                    manifest::FILE_NAME
+                ))
+            })
+    }
+}
+
+/// Parses an optional duration such as `"90d"` into seconds.
+fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| ttl::parse_ttl(&value).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// Returns the path to the config file. It stays in the home directory
+/// even when the vault is moved with `vault_path`.
+pub fn path() -> Result<PathBuf, CliError> {
+    Ok(storage::home_dir()?.join(CONFIG_DIR).join(CONFIG_FILE))
+}
+
+/// Reads the config file, if there is one, and makes it available through
+/// `get`.
+pub fn load() -> Result<&'static Config, CliError> {
+    let path = path()?;
+
+    let config = if path.exists() {
+        tracing::debug!(path = %path.display(), "reading config file");
+        let contents = fs::read_to_string(&path)?;
+        toml::from_str(&contents)
+            .map_err(|e| CliError::InvalidConfig(path.display().to_string(), e.to_string()))?
+    } else {
+        Config::default()
+    };
+
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// Returns the configuration read by `load`, or the defaults if it has not
+/// been loaded.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
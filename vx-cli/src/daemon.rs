@@ -0,0 +1,313 @@
+//! Unlock daemon protocol and client.
+//!
+//! `vx daemon` keeps the vault's data key in locked memory and hands it to
+//! `vx` processes of the same user over a unix socket in the vault
+//! directory, so commands skip both the password prompt and Argon2.
+//!
+//! # Protocol
+//! One request per connection, as a single line: `KEY`, `PING` or `STOP`.
+//! The daemon answers `OK` (followed by the hex-encoded key for `KEY`) or
+//! `ERR <reason>`.
+//!
+//! # Security Notes
+//! - The socket is created with mode 0600, and both ends check the other's
+//!   uid with SO_PEERCRED / getpeereid before exchanging anything
+//! - Each connection is answered on its own thread, so a client that never
+//!   sends its request cannot hold up the others
+//! - The key never touches disk; stopping the daemon wipes it
+
+use crate::error::CliError;
+use crate::storage;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use vx_core::crypto::KEY_SIZE;
+use vx_core::SecureKey;
+use zeroize::Zeroizing;
+
+/// Socket file name inside the vault directory
+const SOCKET_FILE: &str = "daemon.sock";
+
+/// How long either side waits for the other before giving up
+pub const IO_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Longest request or response line accepted
+pub const MAX_LINE: u64 = 128;
+
+/// Returns the path to the daemon socket.
+pub fn socket_path() -> Result<PathBuf, CliError> {
+    Ok(storage::vault_dir()?.join(SOCKET_FILE))
+}
+
+/// Binds a unix socket at `path` that only the current user can connect
+/// to. The socket is bound inside a fresh directory only the user can
+/// enter, restricted to mode 0600 there and then moved to `path`, so it is
+/// never open to others, not even briefly. The process umask is left alone,
+/// as other threads may be creating files.
+pub fn bind_private(path: &Path) -> io::Result<UnixListener> {
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    // Short names, as socket paths are limited to about 100 bytes
+    let dir = tempfile::Builder::new()
+        .prefix(".vx")
+        .permissions(fs::Permissions::from_mode(0o700))
+        .tempdir_in(parent)?;
+    let staged = dir.path().join("s");
+    let listener = UnixListener::bind(&staged)?;
+    fs::set_permissions(&staged, fs::Permissions::from_mode(0o600))?;
+    fs::rename(&staged, path)?;
+    Ok(listener)
+}
+
+/// Serves `key` on `listener`, bound at `path`, to processes running as
+/// `uid` until one asks to stop.
+pub fn serve(listener: &UnixListener, path: &Path, key: &SecureKey, uid: u32) {
+    let stopping = AtomicBool::new(false);
+    thread::scope(|scope| {
+        for stream in listener.incoming() {
+            if stopping.load(Ordering::SeqCst) {
+                break;
+            }
+            let Ok(stream) = stream else { continue };
+            let _ = stream.set_read_timeout(Some(IO_TIMEOUT));
+            let _ = stream.set_write_timeout(Some(IO_TIMEOUT));
+
+            // Only processes running as the same user may talk to the daemon
+            if peer_uid(&stream).ok() != Some(uid) {
+                let _ = (&stream).write_all(b"ERR permission denied\n");
+                continue;
+            }
+
+            let stopping = &stopping;
+            scope.spawn(move || {
+                if answer(&stream, key) {
+                    stopping.store(true, Ordering::SeqCst);
+                    // Wake the accept loop so it sees the flag
+                    let _ = UnixStream::connect(path);
+                }
+            });
+        }
+    });
+}
+
+/// Answers one request. Returns whether it asked the daemon to stop.
+fn answer(stream: &UnixStream, key: &SecureKey) -> bool {
+    let Ok(request) = read_line(stream) else { return false };
+    let response = match request.as_str() {
+        "KEY" => Zeroizing::new(format!("OK {}\n", *encode_hex(&key[..]))),
+        "PING" | "STOP" => Zeroizing::new("OK\n".to_string()),
+        _ => Zeroizing::new("ERR unknown request\n".to_string()),
+    };
+    let _ = (&*stream).write_all(response.as_bytes());
+    request.as_str() == "STOP"
+}
+
+/// Returns the uid of the process at the other end of `stream`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+    // SAFETY: cred and len are valid for writes of the sizes passed.
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
+
+/// Returns the uid of the process at the other end of `stream`.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let mut uid = 0;
+    let mut gid = 0;
+
+    // SAFETY: uid and gid are valid for writes.
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+/// Returns the effective uid of this process.
+pub fn current_uid() -> u32 {
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    unsafe { libc::geteuid() }
+}
+
+/// Reads one line of at most `MAX_LINE` bytes, without the newline.
+pub fn read_line(stream: &UnixStream) -> io::Result<Zeroizing<String>> {
+    read_line_limited(stream, MAX_LINE)
+}
+
+/// Reads one line of at most `limit` bytes, without the newline.
+pub fn read_line_limited(stream: &UnixStream, limit: u64) -> io::Result<Zeroizing<String>> {
+    let mut line = Zeroizing::new(String::new());
+    BufReader::new(stream.take(limit)).read_line(&mut line)?;
+    let trimmed = line.trim_end().len();
+    line.truncate(trimmed);
+    Ok(line)
+}
+
+/// Sends `request` to a running daemon and returns its answer after `OK`.
+///
+/// Returns `None` if no daemon is listening, it belongs to another user, or
+/// it refused the request.
+fn send(request: &str) -> Option<Zeroizing<String>> {
+    send_to(&socket_path().ok()?, request)
+}
+
+/// Sends `request` to the daemon listening at `path`; see `send`.
+fn send_to(path: &Path, request: &str) -> Option<Zeroizing<String>> {
+    let stream = UnixStream::connect(path).ok()?;
+    if peer_uid(&stream).ok()? != current_uid() {
+        return None;
+    }
+    stream.set_read_timeout(Some(IO_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(IO_TIMEOUT)).ok()?;
+
+    (&stream).write_all(format!("{}\n", request).as_bytes()).ok()?;
+    let response = read_line(&stream).ok()?;
+
+    let rest = response.strip_prefix("OK")?;
+    Some(Zeroizing::new(rest.trim_start().to_string()))
+}
+
+/// Fetches the data key from a running daemon.
+pub fn request_key() -> Option<SecureKey> {
+    let bytes = decode_hex(&send("KEY")?)?;
+    let key: Zeroizing<[u8; KEY_SIZE]> = Zeroizing::new(bytes.as_slice().try_into().ok()?);
+    Some(SecureKey::new(&key))
+}
+
+/// Returns whether a daemon is running and answering.
+pub fn is_running() -> bool {
+    send("PING").is_some()
+}
+
+/// Asks a running daemon to wipe its key and exit. Returns whether one was
+/// running.
+pub fn stop() -> bool {
+    send("STOP").is_some()
+}
+
+/// Encodes a key or secret value as lowercase hex.
+pub fn encode_hex(bytes: &[u8]) -> Zeroizing<String> {
+    let mut hex = Zeroizing::new(String::with_capacity(bytes.len() * 2));
+    for byte in bytes {
+        hex.push(char::from_digit(u32::from(byte >> 4), 16).expect("nibble is a hex digit"));
+        hex.push(char::from_digit(u32::from(byte & 0x0f), 16).expect("nibble is a hex digit"));
+    }
+    hex
+}
+
+/// Decodes hex made by `encode_hex`. Returns `None` if `hex` is malformed.
+pub fn decode_hex(hex: &str) -> Option<Zeroizing<Vec<u8>>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    let mut out = Zeroizing::new(Vec::with_capacity(hex.len() / 2));
+    for pair in hex.as_bytes().chunks(2) {
+        let pair = std::str::from_utf8(pair).ok()?;
+        out.push(u8::from_str_radix(pair, 16).ok()?);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn test_key() -> SecureKey {
+        SecureKey::new(&[7u8; KEY_SIZE])
+    }
+
+    #[test]
+    fn test_socket_is_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SOCKET_FILE);
+        let _listener = bind_private(&path).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // Nothing is left behind next to it
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_key_round_trip_and_stop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SOCKET_FILE);
+        let listener = bind_private(&path).unwrap();
+        let server = {
+            let path = path.clone();
+            thread::spawn(move || serve(&listener, &path, &test_key(), current_uid()))
+        };
+
+        let hex = send_to(&path, "KEY").unwrap();
+        assert_eq!(decode_hex(&hex).unwrap().as_slice(), &[7u8; KEY_SIZE]);
+        assert!(send_to(&path, "NONSENSE").is_none());
+
+        assert!(send_to(&path, "STOP").is_some());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_other_users_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SOCKET_FILE);
+        let listener = bind_private(&path).unwrap();
+        // Serves a uid that is not ours; it cannot be asked to stop, so the
+        // thread ends with the test process
+        let other = current_uid().wrapping_add(1);
+        let server_path = path.clone();
+        thread::spawn(move || serve(&listener, &server_path, &test_key(), other));
+
+        let stream = UnixStream::connect(&path).unwrap();
+        stream.set_read_timeout(Some(IO_TIMEOUT)).unwrap();
+        (&stream).write_all(b"KEY\n").unwrap();
+        assert_eq!(read_line(&stream).unwrap().as_str(), "ERR permission denied");
+    }
+
+    #[test]
+    fn test_silent_client_does_not_block_others() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SOCKET_FILE);
+        let listener = bind_private(&path).unwrap();
+        let server = {
+            let path = path.clone();
+            thread::spawn(move || serve(&listener, &path, &test_key(), current_uid()))
+        };
+
+        // Connects and never sends its request
+        let _silent = UnixStream::connect(&path).unwrap();
+
+        let started = Instant::now();
+        assert!(send_to(&path, "PING").is_some());
+        assert!(started.elapsed() < IO_TIMEOUT / 2);
+
+        assert!(send_to(&path, "STOP").is_some());
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+//! Desktop dialogs, notifications and the clipboard.
+//!
+//! Shells out to the platform's own tools (zenity, kdialog, notify-send and
+//! wl-copy/xclip/xsel on Linux, osascript and pbcopy on macOS, PowerShell
+//! and clip on Windows) rather than linking a GUI toolkit into `vx`. Text is passed as an argument or environment
+//! variable rather than spliced into a script, so secret and origin names
+//! cannot inject code.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Asks the user to confirm a request in a desktop dialog. Returns `false`
+/// if they decline or no dialog can be shown.
+pub fn confirm(message: &str) -> bool {
+    let dialogs: Vec<Command> = if cfg!(target_os = "macos") {
+        let mut osascript = Command::new("osascript");
+        osascript.args([
+            "-e",
+            "on run argv",
+            "-e",
+            "display dialog (item 1 of argv) with title \"VaultX\" \
+             buttons {\"Deny\", \"Allow\"} default button \"Deny\" cancel button \"Deny\"",
+            "-e",
+            "end run",
+            message,
+        ]);
+        vec![osascript]
+    } else if cfg!(windows) {
+        let mut powershell = Command::new("powershell");
+        powershell
+            .args([
+                "-NoProfile",
+                "-Command",
+                "Add-Type -AssemblyName PresentationFramework; \
+                 $r = [System.Windows.MessageBox]::Show($env:VX_PROMPT, 'VaultX', 'YesNo', 'Question'); \
+                 if ($r -eq 'Yes') { exit 0 } else { exit 1 }",
+            ])
+            .env("VX_PROMPT", message);
+        vec![powershell]
+    } else {
+        let mut zenity = Command::new("zenity");
+        zenity.args([
+            "--question",
+            "--no-markup",
+            "--title=VaultX",
+            "--text",
+            message,
+        ]);
+        let mut kdialog = Command::new("kdialog");
+        kdialog.args(["--title", "VaultX", "--yesno", message]);
+        vec![zenity, kdialog]
+    };
+
+    for dialog in dialogs {
+        if let Some(confirmed) = run(dialog) {
+            return confirmed;
+        }
+    }
+    false
+}
+
+/// Shows a desktop notification. Returns `false` if none could be shown.
+pub fn notify(title: &str, body: &str) -> bool {
+    let notifiers: Vec<Command> = if cfg!(target_os = "macos") {
+        let mut osascript = Command::new("osascript");
+        osascript.args([
+            "-e",
+            "on run argv",
+            "-e",
+            "display notification (item 2 of argv) with title (item 1 of argv)",
+            "-e",
+            "end run",
+            title,
+            body,
+        ]);
+        vec![osascript]
+    } else if cfg!(windows) {
+        let mut powershell = Command::new("powershell");
+        powershell
+            .args([
+                "-NoProfile",
+                "-Command",
+                "Add-Type -AssemblyName System.Windows.Forms; \
+                 $n = New-Object System.Windows.Forms.NotifyIcon; \
+                 $n.Icon = [System.Drawing.SystemIcons]::Warning; $n.Visible = $true; \
+                 $n.ShowBalloonTip(10000, $env:VX_TITLE, $env:VX_BODY, 'Warning'); \
+                 Start-Sleep -Seconds 10; $n.Dispose()",
+            ])
+            .env("VX_TITLE", title)
+            .env("VX_BODY", body);
+        vec![powershell]
+    } else {
+        let mut notify_send = Command::new("notify-send");
+        notify_send.args(["--app-name=VaultX", "--", title, body]);
+        vec![notify_send]
+    };
+
+    notifiers.into_iter().any(|mut notifier| {
+        if cfg!(windows) {
+            // The balloon stays up only while its PowerShell process runs,
+            // so it is not waited for
+            return notifier
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .spawn()
+                .is_ok();
+        }
+        run(notifier) == Some(true)
+    })
+}
+
+/// Replaces the clipboard contents with `data`. Returns `false` if no
+/// clipboard tool could be run.
+pub fn copy_to_clipboard(data: &[u8]) -> bool {
+    let tools: &[&[&str]] = if cfg!(target_os = "macos") {
+        &[&["pbcopy"]]
+    } else if cfg!(windows) {
+        &[&["clip"]]
+    } else {
+        &[
+            &["wl-copy"],
+            &["xclip", "-selection", "clipboard"],
+            &["xsel", "--clipboard", "--input"],
+        ]
+    };
+
+    tools.iter().any(|tool| {
+        let Ok(mut child) = Command::new(tool[0])
+            .args(&tool[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            return false;
+        };
+
+        let written = child
+            .stdin
+            .take()
+            .is_some_and(|mut stdin| stdin.write_all(data).is_ok());
+        child.wait().is_ok_and(|status| status.success()) && written
+    })
+}
+
+/// Runs a dialog or notifier with stdin and stdout detached. Returns
+/// whether it succeeded, or `None` if it could not be started.
+///
+/// stdout is never inherited, since `vx native-host` uses it for the
+/// messaging protocol.
+fn run(mut command: Command) -> Option<bool> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status()
+        .ok()
+        .map(|status| status.success())
+}
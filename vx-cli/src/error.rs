@@ -1,5 +1,10 @@
 //! CLI error types.
+//!
+//! Errors are printed on stderr as `Error: <message>`, or with `--output
+//! json` as an object with the exit code, its class, the message and the
+//! items involved (see `CliError::to_json`).
 
+use serde_json::{json, Map, Value};
 use thiserror::Error;
 use vx_core::{CryptoError, VaultError};
 
@@ -20,8 +25,15 @@ pub enum CliError {
     #[error("Passwords do not match")]
     PasswordMismatch,
 
-    #[error("Secret input cancelled")]
-    InputCancelled,
+    #[error("Give the master password only one way: VX_PASSWORD, --password-file or --password-stdin")]
+    ConflictingPasswordSources,
+
+    #[error("Password is too weak: about {bits} bits of estimated entropy, {required} required{hints}")]
+    WeakPassword {
+        bits: u32,
+        required: u32,
+        hints: String,
+    },
 
     #[error("Environment variable '{0}' not found")]
     EnvVarNotFound(String),
@@ -38,15 +50,397 @@ pub enum CliError {
     #[error("Project not found: {0}")]
     ProjectNotFound(String),
 
+    #[error("Snapshot not found: {0}")]
+    SnapshotNotFound(String),
+
+    #[error("Invalid snapshot name '{0}': use letters, digits, '-', '_' and '.'")]
+    InvalidSnapshotName(String),
+
     #[error("SSH connection failed: {0}")]
     SshError(String),
 
-    #[error("Security violation: {0}")]
-    SecurityViolation(String),
-
     #[error("Update failed: {0}")]
     UpdateError(String),
 
+    #[error("Invalid config file {0}: {1}")]
+    InvalidConfig(String, String),
+
     #[error("{0}")]
     Generic(String),
 }
+
+/// Exit codes by class of error, so scripts can branch on the kind of
+/// failure instead of matching the message. Listed in the README; a code,
+/// once given a meaning, keeps it.
+pub mod exit_code {
+    /// Any failure not covered by a more specific code
+    pub const FAILURE: i32 = 1;
+    /// The vault, a project, secret, file or other item does not exist
+    pub const NOT_FOUND: i32 = 2;
+    /// Wrong password or passphrase, or none could be read
+    pub const AUTH_FAILED: i32 = 3;
+    /// The secret exists but has expired
+    pub const EXPIRED: i32 = 4;
+    /// Reading or writing a file failed
+    pub const IO: i32 = 5;
+    /// The item already exists, or the vault changed since it was loaded
+    pub const CONFLICT: i32 = 6;
+    /// An argument, value or config setting is invalid
+    pub const INVALID_INPUT: i32 = 7;
+    /// The vault file is damaged, tampered with or from a newer release
+    pub const CORRUPTED: i32 = 8;
+    /// The operation is not allowed (read-only vault, locked project,
+    /// pending emergency access, ...)
+    pub const DENIED: i32 = 9;
+    /// The command line could not be parsed (as `EX_USAGE` in sysexits.h)
+    pub const USAGE: i32 = 64;
+
+    /// Returns the name of the class of an exit code, as given in the
+    /// `kind` of a JSON error.
+    pub fn kind(code: i32) -> &'static str {
+        match code {
+            NOT_FOUND => "not-found",
+            AUTH_FAILED => "auth-failed",
+            EXPIRED => "expired",
+            IO => "io",
+            CONFLICT => "conflict",
+            INVALID_INPUT => "invalid-input",
+            CORRUPTED => "corrupted",
+            DENIED => "denied",
+            USAGE => "usage",
+            _ => "failure",
+        }
+    }
+}
+
+/// Formats an error as printed with `--output json`:
+/// `{"code", "kind", "message", "context"}`.
+pub fn json_error(code: i32, message: &str, context: Map<String, Value>) -> String {
+    json!({
+        "code": code,
+        "kind": exit_code::kind(code),
+        "message": message,
+        "context": context,
+    })
+    .to_string()
+}
+
+/// Returns whether a command line that could not be parsed asked for
+/// `--output json`, so its usage error is printed as JSON too.
+pub fn asks_for_json(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == "--output=json")
+        || args
+            .windows(2)
+            .any(|pair| pair[0] == "--output" && pair[1] == "json")
+}
+
+impl CliError {
+    /// Returns the exit code of the error's class; see `exit_code`.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Vault(e) => vault_exit_code(e),
+            CliError::Crypto(e) => crypto_exit_code(e),
+            CliError::Io(_) => exit_code::IO,
+            CliError::PasswordReadError => exit_code::AUTH_FAILED,
+            CliError::PasswordMismatch
+            | CliError::ConflictingPasswordSources
+            | CliError::WeakPassword { .. }
+            | CliError::InvalidTtl(_)
+            | CliError::InvalidSnapshotName(_)
+            | CliError::InvalidConfig(..) => exit_code::INVALID_INPUT,
+            CliError::EnvVarNotFound(_)
+            | CliError::FileNotFound(_)
+            | CliError::VaultNotFound
+            | CliError::ProjectNotFound(_)
+            | CliError::SnapshotNotFound(_) => exit_code::NOT_FOUND,
+            CliError::SshError(_) | CliError::UpdateError(_) | CliError::Generic(_) => {
+                exit_code::FAILURE
+            }
+        }
+    }
+
+    /// Formats the error as printed with `--output json`; see `json_error`.
+    pub fn to_json(&self) -> String {
+        json_error(self.exit_code(), &self.to_string(), self.context())
+    }
+
+    /// Returns the items the error is about (project, secret, file, ...),
+    /// for scripts to act on without parsing the message.
+    fn context(&self) -> Map<String, Value> {
+        let mut context = Map::new();
+        match self {
+            CliError::Vault(e) => vault_context(e, &mut context),
+            CliError::Io(e) => {
+                context.insert("io_error".into(), format!("{:?}", e.kind()).into());
+            }
+            CliError::WeakPassword { bits, required, .. } => {
+                context.insert("bits".into(), (*bits).into());
+                context.insert("required_bits".into(), (*required).into());
+            }
+            CliError::EnvVarNotFound(name) => {
+                context.insert("variable".into(), name.as_str().into());
+            }
+            CliError::FileNotFound(path) | CliError::InvalidConfig(path, _) => {
+                context.insert("path".into(), path.as_str().into());
+            }
+            CliError::ProjectNotFound(project) => {
+                context.insert("project".into(), project.as_str().into());
+            }
+            CliError::SnapshotNotFound(name) | CliError::InvalidSnapshotName(name) => {
+                context.insert("snapshot".into(), name.as_str().into());
+            }
+            _ => {}
+        }
+        context
+    }
+}
+
+/// Adds the items a vault error is about to `context`.
+fn vault_context(e: &VaultError, context: &mut Map<String, Value>) {
+    let (key, value): (&str, Value) = match e {
+        VaultError::ProjectNotFound(project)
+        | VaultError::ProjectAlreadyExists(project)
+        | VaultError::ProjectSealed(project)
+        | VaultError::ProjectLocked(project)
+        | VaultError::MissingShard(project)
+        | VaultError::InvalidProjectPassphrase(project) => ("project", project.as_str().into()),
+        VaultError::SecretNotFound(secret)
+        | VaultError::SecretAlreadyExists(secret)
+        | VaultError::SecretExpired(secret)
+        | VaultError::DanglingAlias(secret)
+        | VaultError::AliasCycle(secret)
+        | VaultError::TemplateCycle(secret) => ("secret", secret.as_str().into()),
+        VaultError::RuleViolation(secret, rule) => {
+            context.insert("rule".into(), serde_json::to_value(rule).unwrap_or_default());
+            ("secret", secret.as_str().into())
+        }
+        VaultError::InvalidRule(pattern, _) => ("pattern", pattern.as_str().into()),
+        VaultError::EnvironmentNotFound(env) | VaultError::EnvironmentAlreadyExists(env) => {
+            ("environment", env.as_str().into())
+        }
+        VaultError::AttachmentNotFound(name) | VaultError::AttachmentAlreadyExists(name) => {
+            ("attachment", name.as_str().into())
+        }
+        VaultError::IdentityNotFound(name)
+        | VaultError::IdentityAlreadyExists(name)
+        | VaultError::IdentityOnToken(name) => ("identity", name.as_str().into()),
+        VaultError::TrashEntryNotFound(id) => ("trash_id", (*id).into()),
+        VaultError::KeySlotNotFound(id) => ("slot", (*id).into()),
+        VaultError::EmergencyGrantNotFound(id) => ("grant", (*id).into()),
+        VaultError::EmergencyWaiting { available_at } => ("available_at", (*available_at).into()),
+        VaultError::UnsupportedVersion(version) => ("version", (*version).into()),
+        VaultError::GenerationConflict { expected, found } => {
+            context.insert("expected_generation".into(), (*expected).into());
+            ("found_generation", (*found).into())
+        }
+        VaultError::TruncatedVault { expected, found } => {
+            context.insert("expected_bytes".into(), (*expected).into());
+            ("found_bytes", (*found).into())
+        }
+        _ => return,
+    };
+    context.insert(key.into(), value);
+}
+
+/// Classifies a vault error. Every variant is listed, so a new one has to
+/// be given a class.
+fn vault_exit_code(e: &VaultError) -> i32 {
+    match e {
+        VaultError::ProjectNotFound(_)
+        | VaultError::SecretNotFound(_)
+        | VaultError::EnvironmentNotFound(_)
+        | VaultError::TrashEntryNotFound(_)
+        | VaultError::DanglingAlias(_)
+        | VaultError::JsonPathNotFound(_)
+        | VaultError::AttachmentNotFound(_)
+        | VaultError::IdentityNotFound(_)
+        | VaultError::ServerNotFound(_)
+        | VaultError::ServerGroupNotFound(_)
+        | VaultError::KubeconfigNotFound(_)
+        | VaultError::WireguardConfigNotFound(_)
+        | VaultError::KeySlotNotFound(_)
+        | VaultError::EmergencyGrantNotFound(_) => exit_code::NOT_FOUND,
+
+        VaultError::AuthenticationFailed | VaultError::InvalidProjectPassphrase(_) => {
+            exit_code::AUTH_FAILED
+        }
+
+        VaultError::SecretExpired(_) => exit_code::EXPIRED,
+
+        VaultError::ProjectAlreadyExists(_)
+        | VaultError::SecretAlreadyExists(_)
+        | VaultError::EnvironmentAlreadyExists(_)
+        | VaultError::AttachmentAlreadyExists(_)
+        | VaultError::IdentityAlreadyExists(_)
+        | VaultError::ServerAlreadyExists(_)
+        | VaultError::ServerGroupAlreadyExists(_)
+        | VaultError::KubeconfigAlreadyExists(_)
+        | VaultError::WireguardConfigAlreadyExists(_)
+        | VaultError::UndoStale(_)
+        | VaultError::GenerationConflict { .. } => exit_code::CONFLICT,
+
+        VaultError::AliasCycle(_)
+        | VaultError::InvalidTemplate(_)
+        | VaultError::TemplateCycle(_)
+        | VaultError::InvalidJson(_)
+        | VaultError::InvalidCertificate(_)
+        | VaultError::FixedExpiry(..)
+        | VaultError::TtlTooLong(_)
+        | VaultError::RuleViolation(..)
+        | VaultError::InvalidRule(..)
+        | VaultError::InvalidPolicy(_)
+        | VaultError::UnknownCharset(_)
+        | VaultError::EmptyServerGroup(_)
+        | VaultError::InvalidInterfaceName(_)
+        | VaultError::InvalidWireguardConfig(_)
+        | VaultError::UnknownDbEngine(_)
+        | VaultError::InvalidDbConnection(_)
+        | VaultError::InvalidOrigin(_)
+        | VaultError::InvalidIpAddress(_)
+        | VaultError::InvalidMnemonic(_)
+        | VaultError::InvalidEmergencyKey(_) => exit_code::INVALID_INPUT,
+
+        VaultError::CorruptedVault
+        | VaultError::TruncatedVault { .. }
+        | VaultError::MissingShard(_)
+        | VaultError::AccessLogTampered(_)
+        | VaultError::InvalidFormat(_)
+        | VaultError::UnsupportedVersion(_) => exit_code::CORRUPTED,
+
+        VaultError::ProjectSealed(_)
+        | VaultError::ProjectLocked(_)
+        | VaultError::IdentityOnToken(_)
+        | VaultError::OriginNotAllowed(_)
+        | VaultError::ReadOnly
+        | VaultError::KeySlotsFull(_)
+        | VaultError::LastKeySlot
+        | VaultError::EmergencyNotRequested
+        | VaultError::EmergencyVetoed
+        | VaultError::EmergencyWaiting { .. } => exit_code::DENIED,
+
+        VaultError::NothingToUndo | VaultError::SerializationError(_) => exit_code::FAILURE,
+
+        VaultError::CryptoError(e) => crypto_exit_code(e),
+    }
+}
+
+/// Classifies a cryptographic error: a failed decryption means a wrong key.
+fn crypto_exit_code(e: &CryptoError) -> i32 {
+    match e {
+        CryptoError::DecryptionFailed => exit_code::AUTH_FAILED,
+        CryptoError::InvalidStreamHeader => exit_code::CORRUPTED,
+        CryptoError::KeyDerivationFailed
+        | CryptoError::EncryptionFailed
+        | CryptoError::InvalidNonce
+        | CryptoError::InvalidKeyLength => exit_code::FAILURE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_by_class() {
+        let cases = [
+            (CliError::Generic("failed".into()), exit_code::FAILURE),
+            (CliError::SshError("refused".into()), exit_code::FAILURE),
+            (CliError::VaultNotFound, exit_code::NOT_FOUND),
+            (CliError::ProjectNotFound("app".into()), exit_code::NOT_FOUND),
+            (VaultError::SecretNotFound("KEY".into()).into(), exit_code::NOT_FOUND),
+            (CliError::PasswordReadError, exit_code::AUTH_FAILED),
+            (VaultError::AuthenticationFailed.into(), exit_code::AUTH_FAILED),
+            (CryptoError::DecryptionFailed.into(), exit_code::AUTH_FAILED),
+            (
+                VaultError::CryptoError(CryptoError::DecryptionFailed).into(),
+                exit_code::AUTH_FAILED,
+            ),
+            (VaultError::SecretExpired("KEY".into()).into(), exit_code::EXPIRED),
+            (std::io::Error::other("disk full").into(), exit_code::IO),
+            (
+                VaultError::GenerationConflict {
+                    expected: 1,
+                    found: 2,
+                }
+                .into(),
+                exit_code::CONFLICT,
+            ),
+            (CliError::InvalidTtl("6x".into()), exit_code::INVALID_INPUT),
+            (CliError::ConflictingPasswordSources, exit_code::INVALID_INPUT),
+            (VaultError::CorruptedVault.into(), exit_code::CORRUPTED),
+            (CryptoError::InvalidStreamHeader.into(), exit_code::CORRUPTED),
+            (VaultError::ReadOnly.into(), exit_code::DENIED),
+            (VaultError::ProjectLocked("app".into()).into(), exit_code::DENIED),
+        ];
+        for (error, code) in cases {
+            assert_eq!(error.exit_code(), code, "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn test_exit_codes_are_stable() {
+        // Scripts depend on these values; they must never change
+        assert_eq!(
+            [
+                exit_code::FAILURE,
+                exit_code::NOT_FOUND,
+                exit_code::AUTH_FAILED,
+                exit_code::EXPIRED,
+                exit_code::IO,
+                exit_code::CONFLICT,
+                exit_code::INVALID_INPUT,
+                exit_code::CORRUPTED,
+                exit_code::DENIED,
+                exit_code::USAGE,
+            ],
+            [1, 2, 3, 4, 5, 6, 7, 8, 9, 64]
+        );
+        assert_eq!(exit_code::kind(exit_code::NOT_FOUND), "not-found");
+        assert_eq!(exit_code::kind(exit_code::USAGE), "usage");
+        assert_eq!(exit_code::kind(exit_code::FAILURE), "failure");
+        assert_eq!(exit_code::kind(42), "failure");
+    }
+
+    fn json(error: CliError) -> Value {
+        serde_json::from_str(&error.to_json()).unwrap()
+    }
+
+    #[test]
+    fn test_errors_as_json() {
+        assert_eq!(
+            json(CliError::ProjectNotFound("app".into())),
+            json!({
+                "code": 2,
+                "kind": "not-found",
+                "message": "Project not found: app",
+                "context": {"project": "app"},
+            })
+        );
+        assert_eq!(
+            json(VaultError::GenerationConflict { expected: 3, found: 4 }.into())["context"],
+            json!({"expected_generation": 3, "found_generation": 4})
+        );
+        assert_eq!(
+            json(std::io::Error::from(std::io::ErrorKind::PermissionDenied).into())["context"],
+            json!({"io_error": "PermissionDenied"})
+        );
+
+        let generic = json(CliError::Generic("Something \"quoted\"\nbroke".into()));
+        assert_eq!(generic["kind"], "failure");
+        assert_eq!(generic["message"], "Something \"quoted\"\nbroke");
+        assert_eq!(generic["context"], json!({}));
+    }
+
+    #[test]
+    fn test_usage_errors_as_json() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert!(asks_for_json(&args(&["vx", "get", "--output", "json", "--bogus"])));
+        assert!(asks_for_json(&args(&["vx", "--output=json", "bogus"])));
+        assert!(!asks_for_json(&args(&["vx", "--output", "text", "bogus"])));
+        assert!(!asks_for_json(&args(&["vx", "--output"])));
+
+        let usage: Value =
+            serde_json::from_str(&json_error(exit_code::USAGE, "bad", Map::new())).unwrap();
+        assert_eq!(usage, json!({"code": 64, "kind": "usage", "message": "bad", "context": {}}));
+    }
+}
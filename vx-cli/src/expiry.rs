@@ -0,0 +1,54 @@
+//! Desktop notifications for secrets and SSH identities about to expire.
+//!
+//! Opt-in: `vx login --notify-expiring` checks once after unlocking, and
+//! `vx daemon --notify-expiring` checks every hour while it runs, so TTLs
+//! get renewed before something fails for lack of them.
+
+use crate::desktop;
+use std::time::Duration;
+use vx_core::{audit, ttl, Expiration, Vault};
+
+/// Window used when `--notify-expiring` is given without a duration
+pub const DEFAULT_WINDOW: &str = "7d";
+
+/// How often the daemon checks for upcoming expirations
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Most items named in one notification
+const MAX_LISTED: usize = 5;
+
+/// Notifies about secrets and SSH identities in the unsealed projects of
+/// `vault` that expire within `window` seconds, skipping those already in
+/// `notified` and adding the rest to it. The same lines are printed to
+/// stdout, in case no notification can be shown.
+pub fn notify_expiring(vault: &Vault, window: u64, notified: &mut Vec<Expiration>) {
+    let now = ttl::current_timestamp();
+    let upcoming: Vec<Expiration> = audit::upcoming_expirations(vault, now, window)
+        .into_iter()
+        .filter(|expiration| !notified.contains(expiration))
+        .collect();
+    if upcoming.is_empty() {
+        return;
+    }
+
+    let title = match upcoming.len() {
+        1 => "VaultX: 1 item expires soon".to_string(),
+        n => format!("VaultX: {} items expire soon", n),
+    };
+    let mut lines: Vec<String> = upcoming
+        .iter()
+        .take(MAX_LISTED)
+        .map(|expiration| expiration.describe(now))
+        .collect();
+    if upcoming.len() > MAX_LISTED {
+        lines.push(format!("and {} more", upcoming.len() - MAX_LISTED));
+    }
+
+    println!("⚠ {}", title.trim_start_matches("VaultX: "));
+    for expiration in &upcoming {
+        println!("  {}", expiration.describe(now));
+    }
+    desktop::notify(&title, &lines.join("\n"));
+
+    notified.extend(upcoming);
+}
@@ -0,0 +1,123 @@
+//! Hooks: commands run on vault events, set under `[hooks]` in the config
+//! file.
+//!
+//! A hook is run through the shell (`sh -c`, or `cmd /C` on Windows) with
+//! stdin closed and its output sent to stderr, so it cannot read a prompt
+//! or mix into output that is being piped. It learns what happened from
+//! environment variables that carry names only, never secret values:
+//!
+//! - `VX_HOOK_EVENT`: `post-add`, `post-remove`, `pre-save` or
+//!   `expired-detected`
+//! - `VX_HOOK_PROJECT`: the project, if the event concerns one
+//! - `VX_HOOK_NAME`: the secret, as `env/KEY` for a named environment;
+//!   unset when a whole project is removed
+//! - `VX_HOOK_EXPIRES_AT`: Unix time the secret expired at, for
+//!   `expired-detected`
+//!
+//! Hooks are not run from within a hook, so a hook can call `vx` without
+//! triggering itself.
+
+use crate::config::{self, HooksConfig};
+use crate::error::CliError;
+use std::process::{Command, Stdio};
+
+/// Set in the environment of every hook
+const EVENT_VAR: &str = "VX_HOOK_EVENT";
+
+/// A vault event hooks can be set for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    PostAdd,
+    PostRemove,
+    PreSave,
+    ExpiredDetected,
+}
+
+impl Event {
+    /// Returns the event's name, as written in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::PostAdd => "post-add",
+            Event::PostRemove => "post-remove",
+            Event::PreSave => "pre-save",
+            Event::ExpiredDetected => "expired-detected",
+        }
+    }
+
+    fn command(self, hooks: &HooksConfig) -> Option<&str> {
+        match self {
+            Event::PostAdd => hooks.post_add.as_deref(),
+            Event::PostRemove => hooks.post_remove.as_deref(),
+            Event::PreSave => hooks.pre_save.as_deref(),
+            Event::ExpiredDetected => hooks.expired_detected.as_deref(),
+        }
+    }
+}
+
+/// What an event is about; every field is optional.
+#[derive(Debug, Default)]
+pub struct Subject<'a> {
+    pub project: Option<&'a str>,
+    pub name: Option<&'a str>,
+    pub expires_at: Option<u64>,
+}
+
+/// Returns whether a hook is set for `event` and would be run.
+pub fn is_set(event: Event) -> bool {
+    event.command(&config::get().hooks).is_some() && std::env::var_os(EVENT_VAR).is_none()
+}
+
+/// Runs the hook set for `event`, if any, and waits for it. Fails if the
+/// hook cannot be started or exits unsuccessfully.
+pub fn run(event: Event, subject: &Subject) -> Result<(), CliError> {
+    if !is_set(event) {
+        return Ok(());
+    }
+    let Some(command) = event.command(&config::get().hooks) else {
+        return Ok(());
+    };
+
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    cmd.env(EVENT_VAR, event.name())
+        .stdin(Stdio::null())
+        .stdout(std::io::stderr())
+        .stderr(Stdio::inherit());
+    if let Some(project) = subject.project {
+        cmd.env("VX_HOOK_PROJECT", project);
+    }
+    if let Some(name) = subject.name {
+        cmd.env("VX_HOOK_NAME", name);
+    }
+    if let Some(expires_at) = subject.expires_at {
+        cmd.env("VX_HOOK_EXPIRES_AT", expires_at.to_string());
+    }
+
+    tracing::debug!(event = event.name(), "running hook");
+    let status = cmd.status().map_err(|e| {
+        CliError::Generic(format!("Failed to run the {} hook: {}", event.name(), e))
+    })?;
+    if !status.success() {
+        return Err(CliError::Generic(format!(
+            "The {} hook exited with status {}",
+            event.name(),
+            status.code().unwrap_or(-1)
+        )));
+    }
+    Ok(())
+}
+
+/// Runs the hook for an event that has already happened, reporting a
+/// failure as a warning instead of an error.
+pub fn notify(event: Event, subject: &Subject) {
+    if let Err(e) = run(event, subject) {
+        eprintln!("⚠️  {}", e);
+    }
+}
@@ -4,22 +4,202 @@
 //! - Passwords are read with terminal echo disabled
 //! - Secrets can be provided via prompt, file, or environment variable
 //! - Secrets are NEVER accepted as command-line arguments
+//! - Passwords and secret values are zeroized when dropped
+//!
+//! # Automation
+//! Without a terminal, the master password can be given in `VX_PASSWORD`,
+//! in a file named by `--password-file`, or on the first line of stdin with
+//! `--password-stdin`; see `init_password_source`. It is then used wherever
+//! `vx` would prompt for the master password.
+//!
+//! Secret values are only prompted for on a terminal; scripts pass them
+//! with `--file` or `--from-env` instead (see `read_secret`).
 
+use crate::config;
 use crate::error::CliError;
-use std::io::{self, Write};
+use crate::permissions;
+use std::ffi::OsString;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use vx_core::{strength, SecretBytes};
+use zeroize::Zeroizing;
+
+/// Environment variable holding the master password for scripts and CI
+pub const PASSWORD_ENV: &str = "VX_PASSWORD";
+
+/// Where the master password comes from when `vx` runs without a terminal.
+enum PasswordSource {
+    /// `VX_PASSWORD`, read at startup
+    Env(Zeroizing<String>),
+    /// `--password-file`
+    File(PathBuf),
+    /// `--password-stdin`
+    Stdin,
+}
+
+/// The source chosen by `init_password_source`; prompts if unset
+static PASSWORD_SOURCE: OnceLock<PasswordSource> = OnceLock::new();
+
+/// The password read from `PASSWORD_SOURCE`, read once and kept for the
+/// rest of the command
+static SUPPLIED_PASSWORD: Mutex<Option<Zeroizing<String>>> = Mutex::new(None);
+
+/// Chooses where the master password comes from: the file of
+/// `--password-file`, stdin with `--password-stdin`, or `VX_PASSWORD`.
+/// Without any of them, `vx` prompts for it.
+///
+/// Giving more than one is an error. `VX_PASSWORD` is read right away and
+/// removed from the environment, so the commands, hooks and plugins `vx`
+/// starts do not inherit it.
+pub fn init_password_source(file: Option<PathBuf>, stdin: bool) -> Result<(), CliError> {
+    let env = std::env::var_os(PASSWORD_ENV);
+    if env.is_some() {
+        std::env::remove_var(PASSWORD_ENV);
+    }
+
+    let Some(source) = choose_password_source(env, file, stdin)? else {
+        return Ok(());
+    };
+    if let PasswordSource::Env(_) = source {
+        eprintln!(
+            "⚠️  Using the master password from {}; other processes of this user may be able to read it.",
+            PASSWORD_ENV
+        );
+    }
+    let _ = PASSWORD_SOURCE.set(source);
+    Ok(())
+}
+
+/// Picks the one password source given, if any: the value of `VX_PASSWORD`
+/// in `env`, `file` or `stdin`.
+fn choose_password_source(
+    env: Option<OsString>,
+    file: Option<PathBuf>,
+    stdin: bool,
+) -> Result<Option<PasswordSource>, CliError> {
+    let given = [env.is_some(), file.is_some(), stdin];
+    if given.iter().filter(|given| **given).count() > 1 {
+        return Err(CliError::ConflictingPasswordSources);
+    }
+
+    Ok(if let Some(password) = env {
+        let password = password
+            .into_string()
+            .map_err(|_| CliError::PasswordReadError)?;
+        Some(PasswordSource::Env(Zeroizing::new(password)))
+    } else if let Some(file) = file {
+        Some(PasswordSource::File(file))
+    } else if stdin {
+        Some(PasswordSource::Stdin)
+    } else {
+        None
+    })
+}
+
+/// Returns the master password given with `VX_PASSWORD`, `--password-file`
+/// or `--password-stdin`, or `None` if it is to be prompted for.
+fn supplied_password() -> Result<Option<Zeroizing<String>>, CliError> {
+    let Some(source) = PASSWORD_SOURCE.get() else {
+        return Ok(None);
+    };
+    let mut supplied = SUPPLIED_PASSWORD
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if supplied.is_none() {
+        *supplied = Some(read_supplied_password(source, io::stdin().lock())?);
+    }
+    Ok(supplied.clone())
+}
+
+/// Reads the password from `source`: for a file or `stdin`, its first
+/// line, without the line ending.
+fn read_supplied_password(
+    source: &PasswordSource,
+    mut stdin: impl BufRead,
+) -> Result<Zeroizing<String>, CliError> {
+    let mut password = match source {
+        PasswordSource::Env(password) => password.clone(),
+        PasswordSource::File(path) => {
+            if let Some(issue) = permissions::check_file(path) {
+                eprintln!(
+                    "⚠️  {}: {}; run: {}",
+                    path.display(),
+                    issue.problem,
+                    issue.fix
+                );
+            }
+            let contents = std::fs::read_to_string(path)
+                .map_err(|_| CliError::FileNotFound(path.display().to_string()))?;
+            Zeroizing::new(contents)
+        }
+        PasswordSource::Stdin => {
+            let mut line = Zeroizing::new(String::new());
+            stdin.read_line(&mut line)?;
+            line
+        }
+    };
+
+    let len = password.lines().next().unwrap_or_default().len();
+    password.truncate(len);
+    if password.is_empty() {
+        return Err(CliError::PasswordReadError);
+    }
+    Ok(password)
+}
+
+/// Returns the master password, prompting with `prompt` unless it was
+/// given with `VX_PASSWORD`, `--password-file` or `--password-stdin`.
+pub fn read_master_password(prompt: &str) -> Result<Zeroizing<String>, CliError> {
+    match supplied_password()? {
+        Some(password) => Ok(password),
+        None => read_password(prompt),
+    }
+}
 
 /// Prompts for a password with no echo.
-pub fn read_password(prompt: &str) -> Result<String, CliError> {
+pub fn read_password(prompt: &str) -> Result<Zeroizing<String>, CliError> {
     print!("{}", prompt);
     io::stdout().flush()?;
 
-    rpassword::read_password().map_err(|_| CliError::PasswordReadError)
+    rpassword::read_password()
+        .map(Zeroizing::new)
+        .map_err(|_| CliError::PasswordReadError)
 }
 
-/// Prompts for a new password with confirmation.
-pub fn read_new_password() -> Result<String, CliError> {
-    let password = read_password("Enter master password: ")?;
-    let confirm = read_password("Confirm master password: ")?;
+/// Prompts for a new master password with confirmation, rejecting weak
+/// ones. A password given with `VX_PASSWORD`, `--password-file` or
+/// `--password-stdin` is used instead, without confirmation.
+pub fn read_new_password() -> Result<Zeroizing<String>, CliError> {
+    if let Some(password) = supplied_password()? {
+        check_strength(&password)?;
+        return Ok(password);
+    }
+    read_strong_password_as("master password")
+}
+
+/// Prompts for a new password that unlocks the vault, like
+/// `read_new_password_as`, and rejects it before asking for confirmation if
+/// its estimated entropy is below `min_password_entropy` from the config
+/// file.
+pub fn read_strong_password_as(what: &str) -> Result<Zeroizing<String>, CliError> {
+    read_and_confirm(what, check_strength)
+}
+
+/// Prompts for a new password with confirmation, naming it `what` in the
+/// prompts (e.g. "export password").
+pub fn read_new_password_as(what: &str) -> Result<Zeroizing<String>, CliError> {
+    read_and_confirm(what, |_| Ok(()))
+}
+
+/// Prompts for a password, runs `check` on it, then asks for it again.
+fn read_and_confirm(
+    what: &str,
+    check: fn(&str) -> Result<(), CliError>,
+) -> Result<Zeroizing<String>, CliError> {
+    let password = read_password(&format!("Enter {}: ", what))?;
+    check(&password)?;
+    let confirm = read_password(&format!("Confirm {}: ", what))?;
 
     if password != confirm {
         return Err(CliError::PasswordMismatch);
@@ -28,6 +208,27 @@ pub fn read_new_password() -> Result<String, CliError> {
     Ok(password)
 }
 
+/// Rejects a password whose estimated entropy is below the configured
+/// minimum, listing what would make it stronger.
+fn check_strength(password: &str) -> Result<(), CliError> {
+    let required = config::get().min_password_entropy;
+    let estimate = strength::estimate(password);
+    if estimate.entropy_bits >= required {
+        return Ok(());
+    }
+
+    let hints = estimate
+        .feedback
+        .iter()
+        .map(|hint| format!("\n  • {}", hint))
+        .collect();
+    Err(CliError::WeakPassword {
+        bits: estimate.entropy_bits,
+        required,
+        hints,
+    })
+}
+
 /// Reads a secret value from various sources.
 ///
 /// # Arguments
@@ -38,20 +239,29 @@ pub fn read_new_password() -> Result<String, CliError> {
 /// The secret value as bytes
 ///
 /// # Security
-/// If neither file nor env is provided, prompts for input with no echo.
-pub fn read_secret(file: Option<&str>, env: Option<&str>) -> Result<Vec<u8>, CliError> {
+/// If neither file nor env is provided, prompts for input with no echo. The
+/// prompt needs a terminal; the value is never read from piped stdin.
+pub fn read_secret(file: Option<&str>, env: Option<&str>) -> Result<SecretBytes, CliError> {
     if let Some(file_path) = file {
         // Read from file
-        std::fs::read(file_path).map_err(|_| CliError::FileNotFound(file_path.to_string()))
+        std::fs::read(file_path)
+            .map(SecretBytes::new)
+            .map_err(|_| CliError::FileNotFound(file_path.to_string()))
     } else if let Some(var_name) = env {
         // Read from environment variable
         std::env::var(var_name)
-            .map(|v| v.into_bytes())
+            .map(|v| SecretBytes::new(v.into_bytes()))
             .map_err(|_| CliError::EnvVarNotFound(var_name.to_string()))
     } else {
         // Prompt for input
-        let secret = read_password("Enter secret value: ")?;
-        Ok(secret.into_bytes())
+        let secret = read_password("Enter secret value: ").map_err(|_| {
+            CliError::Generic(
+                "Could not read the secret value from a terminal; without one, use \
+                 'vx add --file' or 'vx add --from-env'"
+                    .to_string(),
+            )
+        })?;
+        Ok(SecretBytes::new(secret.as_bytes().to_vec()))
     }
 }
 
@@ -76,3 +286,96 @@ pub fn read_input(prompt: &str) -> Result<String, CliError> {
 
     Ok(input.trim().to_string())
 }
+
+/// Reads the answer to a question that cannot be skipped: unlike
+/// `read_input`, fails once standard input is closed rather than returning
+/// an empty answer, so callers that ask again do not loop forever.
+pub fn read_answer(prompt: &str) -> Result<String, CliError> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    read_answer_from(io::stdin().lock())
+}
+
+fn read_answer_from(mut input: impl BufRead) -> Result<String, CliError> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Err(CliError::Generic(
+            "Standard input closed before an answer was given".to_string(),
+        ));
+    }
+    Ok(line.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn password_file(contents: &str) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("password");
+        std::fs::write(&path, contents).unwrap();
+        permissions::restrict_file(&path).unwrap();
+        (dir, path)
+    }
+
+    fn read(source: &PasswordSource, stdin: &str) -> Result<Zeroizing<String>, CliError> {
+        read_supplied_password(source, Cursor::new(stdin.as_bytes()))
+    }
+
+    #[test]
+    fn test_only_one_password_source_may_be_given() {
+        let file = Some(PathBuf::from("password"));
+        for (env, file, stdin) in [
+            (Some(OsString::from("pw")), file.clone(), false),
+            (Some(OsString::from("pw")), None, true),
+            (None, file, true),
+        ] {
+            assert!(matches!(
+                choose_password_source(env, file, stdin),
+                Err(CliError::ConflictingPasswordSources)
+            ));
+        }
+        assert!(choose_password_source(None, None, false).unwrap().is_none());
+
+        let env = choose_password_source(Some(OsString::from("pw")), None, false).unwrap();
+        assert_eq!(read(&env.unwrap(), "ignored").unwrap().as_str(), "pw");
+    }
+
+    #[test]
+    fn test_first_line_is_the_password() {
+        for contents in ["pw", "pw\n", "pw\r\n", "pw\nsecond line\n"] {
+            let (_dir, path) = password_file(contents);
+            assert_eq!(read(&PasswordSource::File(path), "").unwrap().as_str(), "pw");
+            assert_eq!(read(&PasswordSource::Stdin, contents).unwrap().as_str(), "pw");
+        }
+        // Only the line ending goes; other whitespace is part of the password
+        assert_eq!(read(&PasswordSource::Stdin, " pw \n").unwrap().as_str(), " pw ");
+    }
+
+    #[test]
+    fn test_empty_or_missing_password_is_rejected() {
+        for contents in ["", "\n", "\nsecond line"] {
+            let (_dir, path) = password_file(contents);
+            assert!(matches!(
+                read(&PasswordSource::File(path), ""),
+                Err(CliError::PasswordReadError)
+            ));
+            assert!(matches!(
+                read(&PasswordSource::Stdin, contents),
+                Err(CliError::PasswordReadError)
+            ));
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let missing = PasswordSource::File(dir.path().join("missing"));
+        assert!(matches!(read(&missing, ""), Err(CliError::FileNotFound(_))));
+    }
+
+    #[test]
+    fn test_answer_fails_once_input_is_closed() {
+        assert_eq!(read_answer_from(Cursor::new(" r \n")).unwrap(), "r");
+        assert_eq!(read_answer_from(Cursor::new("\n")).unwrap(), "");
+        assert!(matches!(read_answer_from(Cursor::new("")), Err(CliError::Generic(_))));
+    }
+}
@@ -0,0 +1,144 @@
+//! Minimal client for the Linux kernel key retention service.
+//!
+//! Keys are `user` keys in the caller's user keyring: they live in kernel
+//! memory, are shared by the user's processes, and can be given a timeout
+//! after which the kernel discards them.
+//!
+//! # Security Notes
+//! - Keys are created with the default permissions, so only processes
+//!   possessing the user keyring (the same uid) can read them
+//! - Payloads never touch disk, not even in swap
+
+use std::ffi::CString;
+use std::io;
+use zeroize::Zeroizing;
+
+/// `KEY_SPEC_USER_KEYRING`: the caller's user keyring
+const USER_KEYRING: libc::c_long = -4;
+
+const KEYCTL_GET_KEYRING_ID: libc::c_long = 0;
+const KEYCTL_UNLINK: libc::c_long = 9;
+const KEYCTL_SEARCH: libc::c_long = 10;
+const KEYCTL_READ: libc::c_long = 11;
+const KEYCTL_SET_TIMEOUT: libc::c_long = 15;
+
+/// Key type of keys holding arbitrary payloads
+const USER_TYPE: &[u8] = b"user\0";
+
+/// Returns whether the kernel supports keyrings and the user keyring can
+/// be reached.
+pub fn is_available() -> bool {
+    // SAFETY: KEYCTL_GET_KEYRING_ID takes only integer arguments.
+    unsafe { libc::syscall(libc::SYS_keyctl, KEYCTL_GET_KEYRING_ID, USER_KEYRING, 0 as libc::c_long) >= 0 }
+}
+
+/// Adds a key to the user keyring, replacing the payload of an existing key
+/// with the same description. With `timeout`, the kernel discards the key
+/// that many seconds from now; without, any earlier timeout is cleared.
+pub fn add(description: &str, payload: &[u8], timeout: Option<u64>) -> io::Result<()> {
+    let description = c_string(description)?;
+
+    // SAFETY: the type and description are NUL-terminated and the payload
+    // is valid for reads of its length.
+    let id = unsafe {
+        libc::syscall(
+            libc::SYS_add_key,
+            USER_TYPE.as_ptr(),
+            description.as_ptr(),
+            payload.as_ptr(),
+            payload.len(),
+            USER_KEYRING,
+        )
+    };
+    if id < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // The timeout is a u32 number of seconds; 0 means none
+    let timeout = timeout.map_or(0, |t| t.clamp(1, u64::from(u32::MAX))) as libc::c_ulong;
+    // SAFETY: KEYCTL_SET_TIMEOUT takes only integer arguments.
+    if unsafe { libc::syscall(libc::SYS_keyctl, KEYCTL_SET_TIMEOUT, id, timeout) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Reads the payload of the key with `description`, or `None` if there is
+/// no such key or it has expired.
+pub fn read(description: &str) -> io::Result<Option<Zeroizing<Vec<u8>>>> {
+    let Some(id) = search(description)? else {
+        return Ok(None);
+    };
+
+    let mut buffer = Zeroizing::new(vec![0u8; 256]);
+    loop {
+        // SAFETY: the buffer is valid for writes of its length.
+        let len = unsafe {
+            libc::syscall(
+                libc::SYS_keyctl,
+                KEYCTL_READ,
+                id,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+            )
+        };
+        if len < 0 {
+            let error = io::Error::last_os_error();
+            return match error.raw_os_error() {
+                Some(libc::ENOKEY | libc::EKEYEXPIRED | libc::EKEYREVOKED) => Ok(None),
+                _ => Err(error),
+            };
+        }
+
+        // The kernel reports the full length when the buffer is too small
+        let len = len as usize;
+        if len <= buffer.len() {
+            buffer.truncate(len);
+            return Ok(Some(buffer));
+        }
+        buffer = Zeroizing::new(vec![0u8; len]);
+    }
+}
+
+/// Removes the key with `description` from the user keyring. A missing key
+/// is ignored.
+pub fn remove(description: &str) -> io::Result<()> {
+    let Some(id) = search(description)? else {
+        return Ok(());
+    };
+
+    // SAFETY: KEYCTL_UNLINK takes only integer arguments.
+    if unsafe { libc::syscall(libc::SYS_keyctl, KEYCTL_UNLINK, id, USER_KEYRING) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Finds the key with `description` in the user keyring.
+fn search(description: &str) -> io::Result<Option<libc::c_long>> {
+    let description = c_string(description)?;
+
+    // SAFETY: the type and description are NUL-terminated.
+    let id = unsafe {
+        libc::syscall(
+            libc::SYS_keyctl,
+            KEYCTL_SEARCH,
+            USER_KEYRING,
+            USER_TYPE.as_ptr(),
+            description.as_ptr(),
+            0 as libc::c_long,
+        )
+    };
+    if id < 0 {
+        let error = io::Error::last_os_error();
+        return match error.raw_os_error() {
+            Some(libc::ENOKEY | libc::EKEYEXPIRED | libc::EKEYREVOKED) => Ok(None),
+            _ => Err(error),
+        };
+    }
+    Ok(Some(id))
+}
+
+fn c_string(value: &str) -> io::Result<CString> {
+    CString::new(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
@@ -0,0 +1,74 @@
+//! Diagnostic logging for `--verbose`.
+//!
+//! Events from the CLI and `vx-core` are written to stderr, so they never
+//! mix with secrets printed on stdout. `-v` shows debug events (files read
+//! and written, how the vault was unlocked, commands run) and `-vv` adds
+//! trace events; without either only warnings are shown.
+//!
+//! # Security Notes
+//! - Fields named like `password` or `value` are formatted as `[REDACTED]`
+//!   whatever they hold; see `vx_core::redact`
+//! - Types holding plaintext redact themselves in `Debug` output
+
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::FormatFields;
+use vx_core::redact::{self, REDACTED};
+
+/// Starts writing log events to stderr at the level picked by the number
+/// of `-v` flags.
+pub fn init(verbosity: u8) {
+    let level = match verbosity {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .fmt_fields(RedactingFields)
+        .init();
+}
+
+/// Formats event and span fields as `name=value`, redacting sensitive ones.
+struct RedactingFields;
+
+impl<'writer> FormatFields<'writer> for RedactingFields {
+    fn format_fields<R: RecordFields>(&self, writer: Writer<'writer>, fields: R) -> fmt::Result {
+        let mut visitor = RedactingVisitor {
+            writer,
+            result: Ok(()),
+            empty: true,
+        };
+        fields.record(&mut visitor);
+        visitor.result
+    }
+}
+
+struct RedactingVisitor<'writer> {
+    writer: Writer<'writer>,
+    result: fmt::Result,
+    empty: bool,
+}
+
+impl Visit for RedactingVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if self.result.is_err() {
+            return;
+        }
+
+        let separator = if self.empty { "" } else { " " };
+        self.empty = false;
+        self.result = match field.name() {
+            "message" => write!(self.writer, "{}{:?}", separator, value),
+            name if redact::is_sensitive_field(name) => {
+                write!(self.writer, "{}{}={}", separator, name, REDACTED)
+            }
+            name => write!(self.writer, "{}{}={:?}", separator, name, value),
+        };
+    }
+}
@@ -2,14 +2,39 @@
 //!
 //! This is the main entry point for the `vx` command-line tool.
 
+mod agent;
 mod commands;
+mod config;
+#[cfg(unix)]
+mod daemon;
+mod desktop;
 mod error;
+mod expiry;
+mod hooks;
 mod input;
+#[cfg(target_os = "linux")]
+mod keyring;
+mod logging;
+mod manifest;
+mod permissions;
+#[cfg(unix)]
+mod plugin_session;
+mod progress;
+mod scratch;
 mod session;
 mod storage;
+#[cfg(target_os = "macos")]
+mod touchid;
 
 use clap::{Parser, Subcommand};
-use error::CliError;
+use commands::get::Format as ValueFormat;
+use commands::git_filter::Mode as GitFilterMode;
+use commands::init::Security;
+use commands::list_secrets::SortOrder as SecretsSort;
+use config::OutputFormat;
+use error::{exit_code, CliError};
+use vx_core::database::DbEngine;
+use vx_core::generate::Charset;
 
 const BANNER: &str = r#"
 __      __          _ _  __   __
@@ -29,6 +54,26 @@ __      __          _ _  __   __
 #[command(propagate_version = true)]
 #[command(before_help = BANNER)]
 struct Cli {
+    /// Log what vx is doing to stderr (-v for debug, -vv for trace);
+    /// secret values are always redacted
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Output format of errors, and of results for commands with --format
+    /// (overrides `output` in the config file)
+    #[arg(long, global = true, value_enum)]
+    output: Option<OutputFormat>,
+
+    /// Read the master password from the first line of this file instead
+    /// of prompting for it
+    #[arg(long, global = true, value_name = "PATH")]
+    password_file: Option<std::path::PathBuf>,
+
+    /// Read the master password from the first line of stdin instead of
+    /// prompting for it. VX_PASSWORD may hold it instead
+    #[arg(long, global = true)]
+    password_stdin: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -39,6 +84,11 @@ enum Commands {
     Init {
         /// Name of the project to create
         project: String,
+
+        /// Key derivation profile when creating the vault; paranoid is much
+        /// slower to unlock and to brute-force
+        #[arg(long, value_enum, default_value_t = Security::Standard)]
+        security: Security,
     },
 
     /// Add a secret to a project
@@ -55,8 +105,56 @@ enum Commands {
 
         /// Read secret from environment variable
         #[arg(long, value_name = "VAR")]
+        from_env: Option<String>,
+
+        /// Environment to store the secret in (e.g. staging, prod)
+        #[arg(long)]
+        env: Option<String>,
+
+        /// Time-to-live (e.g., 6h, 7d, 2w)
+        #[arg(long)]
+        ttl: Option<String>,
+
+        /// Store the value as a template, e.g. postgres://{{DB_USER}}@{{DB_HOST}}/app,
+        /// rendered from the project's other secrets when read
+        #[arg(long, conflicts_with = "ttl")]
+        template: bool,
+
+        /// Store the value as a JSON document, whose fields `vx get --path`
+        /// reads one at a time
+        #[arg(long, conflicts_with = "template")]
+        json: bool,
+
+        /// Store the value as an X.509 certificate (PEM or DER), which
+        /// expires when the certificate does
+        #[arg(long, conflicts_with_all = ["ttl", "template", "json"])]
+        certificate: bool,
+    },
+
+    /// Generate a random secret with the project's generation policy
+    ///
+    /// Without a policy (see `vx policy`), values are 32 alphanumeric
+    /// characters. Generating an existing secret rotates it.
+    Generate {
+        /// Project name
+        project: String,
+
+        /// Secret key name
+        key: String,
+
+        /// Environment to store the secret in (e.g. staging, prod)
+        #[arg(long)]
         env: Option<String>,
 
+        /// Length of the value, instead of the policy's
+        #[arg(long)]
+        length: Option<usize>,
+
+        /// Characters to draw from, instead of the policy's (alphanumeric,
+        /// hex, digits, base64url or printable)
+        #[arg(long)]
+        charset: Option<Charset>,
+
         /// Time-to-live (e.g., 6h, 7d, 2w)
         #[arg(long)]
         ttl: Option<String>,
@@ -64,33 +162,258 @@ enum Commands {
 
     /// Get a secret from a project (or all secrets if no key specified)
     Get {
-        /// Project name
+        /// Project name, then secret key names (omit the keys to see all
+        /// secrets). A key may hold wildcards, 'DB_*' or 'KEY_?', to get
+        /// every matching secret. The project may be left out inside a
+        /// repository with a .vaultx.toml, or with default_project in the
+        /// config file
+        #[arg(value_name = "[PROJECT] KEY")]
+        args: Vec<String>,
+
+        /// Environment to read from (e.g. staging, prod)
+        #[arg(long)]
+        env: Option<String>,
+
+        /// Output only this field of a JSON secret, as a dotted path
+        /// (.private_key, .scopes.0) or a JSON pointer (/private_key)
+        #[arg(long, requires = "args")]
+        path: Option<String>,
+
+        /// Output format; json prints an object mapping each key to its
+        /// value, dotenv KEY="value" lines
+        #[arg(long, value_enum, default_value_t = ValueFormat::Text)]
+        format: ValueFormat,
+
+        /// Treat the keys as regular expressions, e.g. '^DB_(HOST|PORT)$'
+        #[arg(long, requires = "args")]
+        regex: bool,
+
+        /// Copy the secret to the clipboard instead of printing it, clearing
+        /// it again after the clipboard timeout
+        #[arg(long, requires = "args", conflicts_with = "format")]
+        copy: bool,
+
+        /// Show the secret as a QR code in the terminal, e.g. to scan it
+        /// with a phone
+        #[arg(long, requires = "args", conflicts_with_all = ["format", "copy"])]
+        qr: bool,
+
+        /// Encrypt the value in the QR code under a passphrase (asked for)
+        #[arg(long, requires = "qr")]
+        encrypt: bool,
+
+        /// Print values to the terminal without asking first (piped output
+        /// is never masked)
+        #[arg(long, conflicts_with_all = ["copy", "qr"])]
+        reveal: bool,
+    },
+
+    /// Make a secret refer to another project's secret instead of holding a copy
+    Alias {
+        /// Project to create the alias in
         project: String,
 
-        /// Secret key name (optional - omit to see all secrets)
-        key: Option<String>,
+        /// Name of the alias
+        key: String,
+
+        /// Project holding the secret to refer to
+        target_project: String,
+
+        /// Name of the secret to refer to
+        target_key: String,
+
+        /// Environment to create the alias in
+        #[arg(long)]
+        env: Option<String>,
+
+        /// Environment of the secret to refer to
+        #[arg(long)]
+        target_env: Option<String>,
+    },
+
+    /// Print a project's secrets as KEY="value" lines for a .env file
+    Export {
+        /// Project name (defaults to the one in .vaultx.toml, then default_project)
+        project: Option<String>,
+
+        /// Environment to export (e.g. dev, prod)
+        #[arg(long)]
+        env: Option<String>,
+    },
+
+    /// Print a project's secrets as shell export statements, for
+    /// eval "$(vx env my-project)"
+    Env {
+        /// Project name (defaults to the one in .vaultx.toml, then default_project)
+        project: Option<String>,
+
+        /// Environment to export (e.g. dev, prod)
+        #[arg(long)]
+        env: Option<String>,
+
+        /// Prefix added to every variable name (e.g. APP_)
+        #[arg(long, default_value = "")]
+        prefix: String,
+
+        /// Fail instead of prompting when the vault is not unlocked by
+        /// vx login, vx daemon or VX_KEYFILE
+        #[arg(long)]
+        no_prompt: bool,
+    },
+
+    /// Print a direnv function that loads a project's secrets, for
+    /// ~/.config/direnv/direnvrc, and the line that uses it in .envrc
+    Direnv {
+        /// Project name (defaults to the one in .vaultx.toml, then default_project)
+        project: Option<String>,
+
+        /// Environment to load (e.g. dev, prod)
+        #[arg(long)]
+        env: Option<String>,
     },
 
     /// List all projects in the vault
-    List,
+    List {
+        /// Output format (defaults to output from the config file)
+        #[arg(long, value_enum)]
+        format: Option<OutputFormat>,
+    },
 
     /// List all secrets in a project
     Secrets {
+        /// Project name (defaults to the one in .vaultx.toml, then default_project)
+        project: Option<String>,
+
+        /// Order of the listing
+        #[arg(long, value_enum, default_value_t = SecretsSort::Name)]
+        sort: SecretsSort,
+    },
+
+    /// Show a secret's metadata: when it was created, changed, last read and
+    /// expires, and its size; never its value
+    Info {
         /// Project name
         project: String,
+
+        /// Secret key name
+        key: String,
+
+        /// Environment of the secret
+        #[arg(long)]
+        env: Option<String>,
+    },
+
+    /// Show or change when a secret expires, keeping its value
+    ///
+    /// Usage:
+    ///   vx ttl <project> <key>          - Show when it expires
+    ///   vx ttl <project> <key> 30d      - Make it expire 30 days from now
+    ///   vx ttl <project> <key> --clear  - Make it never expire
+    Ttl {
+        /// Project name
+        project: String,
+
+        /// Secret key name
+        key: String,
+
+        /// New time-to-live from now (e.g., 6h, 7d, 2w)
+        ttl: Option<String>,
+
+        /// Remove the expiry
+        #[arg(long, conflicts_with = "ttl")]
+        clear: bool,
+
+        /// Environment of the secret
+        #[arg(long)]
+        env: Option<String>,
     },
 
     /// Audit the vault for security issues
-    Audit,
+    Audit {
+        /// Output format (defaults to output from the config file)
+        #[arg(long, value_enum)]
+        format: Option<OutputFormat>,
+
+        /// Flag secrets not read for this long as unused (e.g., 180d)
+        #[arg(long, value_name = "DURATION")]
+        min_idle: Option<String>,
+    },
+
+    /// List secrets, certificates and SSH identities that expire soon
+    Expiring {
+        /// How far ahead to look (e.g., 7d, 2w)
+        #[arg(long, value_name = "DURATION", default_value = commands::expiring::DEFAULT_WINDOW)]
+        within: String,
+
+        /// Output format (defaults to output from the config file)
+        #[arg(long, value_enum)]
+        format: Option<OutputFormat>,
+    },
+
+    /// Show how many secrets and other items the vault holds, how large
+    /// they are, the largest and oldest secrets, and when secrets expire
+    Stats {
+        /// Number of largest and oldest secrets to list
+        #[arg(long, value_name = "N", default_value_t = vx_core::stats::DEFAULT_TOP)]
+        top: usize,
+
+        /// Output format (defaults to output from the config file)
+        #[arg(long, value_enum)]
+        format: Option<OutputFormat>,
+    },
+
+    /// Look for stored secret values in a directory and its git history
+    Scan {
+        /// Directory or file to scan
+        #[arg(default_value = ".")]
+        path: String,
+
+        /// Only look for the secrets of this project
+        #[arg(long)]
+        project: Option<String>,
+
+        /// Also scan every blob reachable in the git repository at PATH
+        #[arg(long)]
+        history: bool,
+    },
+
+    /// Show recent vault operations, newest first
+    History {
+        /// Number of operations to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+
+        /// Only show operations on this project
+        #[arg(long)]
+        project: Option<String>,
+    },
+
+    /// Show the access log of vault operations (names only, never values)
+    Log {
+        /// Verify the log's hash chain instead of printing it
+        #[arg(long)]
+        verify: bool,
+    },
 
     /// SSH identity management
     ///
     /// Usage:
-    ///   vx ssh init <name>           - Initialize new SSH identity
+    ///   vx ssh init <name> [--security-key] - Initialize new SSH identity
     ///   vx ssh <server>              - Connect to configured server
     ///   vx ssh <identity> <user@host> - Connect using identity
+    ///   vx ssh multiplex <server> [on [idle]|off] - Share one connection
+    ///   vx ssh test <server>         - Check connectivity and login
+    ///   vx ssh push-key <identity> <user@host> - Install the public key
+    ///   vx ssh hostkey <server> [forget] - Show or forget recorded host keys
+    ///   vx ssh exec --servers <a,b> [--parallel N] -- <command> - Run on many
+    ///   vx ssh group [list | create <name> <server...> | remove <name>] - Server groups
+    ///   vx ssh pkcs11 <name> --module <path> [--label <label>] - Use a smartcard/HSM key
+    ///   vx ssh pubkeys [--authorized-keys] [--dir <dir>] - Export all public keys
+    ///   vx ssh backup <identity> --out <file> - Passphrase-encrypted identity backup
+    ///   vx ssh restore <file> [--as <name>] - Import an identity backup
     Ssh {
-        /// Subcommand (init, connect) or server/identity name
+        /// Subcommand (init, backup, connect, exec, group, hostkey, multiplex,
+        /// pkcs11, pubkeys, push-key, restore, test) or server/identity name
         #[arg(allow_hyphen_values = true)]
         target: Option<String>,
 
@@ -103,9 +426,12 @@ enum Commands {
     ///
     /// Usage:
     ///   vx scp <server> <source> <dest>
-    ///   Use ':' prefix to indicate remote path (e.g., :file.txt or :/tmp/file)
+    ///   vx scp <group> <source> <dest> - Copy with each server of a group
+    ///   vx scp <server>:<path> <server2>:<path> - Copy between two servers
+    ///   Use ':' prefix to indicate remote path (e.g., :file.txt or :/tmp/file),
+    ///   or name the server in it (e.g., web1:/tmp/file)
     Scp {
-        /// Server name
+        /// Server, server group, or server:path operand
         server: String,
 
         /// SCP arguments (use ':' prefix for remote paths)
@@ -113,13 +439,165 @@ enum Commands {
         args: Vec<String>,
     },
 
-    /// Remove a secret or project from the vault
+    /// Manage the kubeconfig files vx kubectl runs with
+    Kubeconfig {
+        #[command(subcommand)]
+        action: KubeconfigAction,
+    },
+
+    /// Run kubectl with a kubeconfig from the vault
+    ///
+    /// Usage:
+    ///   vx kubectl <name> -- get pods
+    ///   The kubeconfig only exists in a private temporary file while kubectl runs
+    Kubectl {
+        /// Kubeconfig name
+        name: String,
+
+        /// kubectl arguments
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+
+    /// Bring WireGuard interfaces up and down with configs from the vault
+    Wg {
+        #[command(subcommand)]
+        action: WgAction,
+    },
+
+    /// Store database credentials and open psql, mysql or mongosh with them
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+
+    /// Remove a secret or project from the vault (kept in the trash for a while)
     Remove {
         /// Project name
         project: String,
 
-        /// The name of the secret to remove (optional - if omitted, removes the entire project)
+        /// The name of the secret to remove (optional - if omitted, removes the entire project,
+        /// or the whole environment with --env)
         key: Option<String>,
+
+        /// Environment to remove from
+        #[arg(long)]
+        env: Option<String>,
+
+        /// Remove every secret whose key matches this glob, e.g. 'TEMP_*',
+        /// after listing them and asking once
+        #[arg(long = "match", value_name = "PATTERN", conflicts_with = "key")]
+        pattern: Option<String>,
+
+        /// With --match, only list the secrets that would be removed
+        #[arg(long, requires = "pattern")]
+        dry_run: bool,
+    },
+
+    /// List, restore or permanently delete removed secrets and projects
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+
+    /// Revert the last add, edit or removal
+    Undo,
+
+    /// Take named snapshots of the vault and roll back to them
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+
+    /// Move the vault to another machine in an encrypted bundle
+    Transfer {
+        #[command(subcommand)]
+        action: TransferAction,
+    },
+
+    /// Merge copies of the vault that were changed on different machines
+    Sync {
+        #[command(subcommand)]
+        action: SyncAction,
+    },
+
+    /// Write a project to a standalone encrypted archive, e.g. for a
+    /// decommissioned service
+    ///
+    /// The archive is protected by a passphrase of its own and can be
+    /// restored into any vault with `vx unarchive`.
+    Archive {
+        /// Project name
+        project: String,
+
+        /// Archive file to write
+        #[arg(short, long, value_name = "FILE")]
+        out: String,
+
+        /// Remove the project from the vault (to the trash) once archived
+        #[arg(long)]
+        remove: bool,
+    },
+
+    /// Copy one project from another vault file into this vault
+    ///
+    /// Asks for the other vault's master password. Secrets keep their
+    /// timestamps and TTLs; nothing else in either vault changes.
+    ImportProject {
+        /// The other vault file
+        file: String,
+
+        /// Project to copy
+        project: String,
+
+        /// Import under another project name
+        #[arg(long = "as", value_name = "NAME")]
+        rename: Option<String>,
+    },
+
+    /// Add a project back from an archive made by `vx archive`
+    Unarchive {
+        /// Archive file
+        file: String,
+
+        /// Restore under another project name
+        #[arg(long = "as", value_name = "NAME")]
+        rename: Option<String>,
+    },
+
+    /// Attach a file to a project (stored as a separate encrypted blob)
+    Attach {
+        /// Project name
+        project: String,
+
+        /// File to attach
+        file: String,
+
+        /// Attachment name (defaults to the file name)
+        #[arg(long)]
+        name: Option<String>,
+    },
+
+    /// List attachments in a project, or extract one
+    Attachments {
+        /// Project name
+        project: String,
+
+        /// Attachment name (optional - omit to list all attachments)
+        name: Option<String>,
+
+        /// Write the attachment to a file instead of stdout
+        #[arg(long, value_name = "FILE")]
+        out: Option<String>,
+    },
+
+    /// Remove an attachment from a project
+    Detach {
+        /// Project name
+        project: String,
+
+        /// Attachment name
+        name: String,
     },
 
     /// Edit a secret in the vault
@@ -129,6 +607,146 @@ enum Commands {
 
         /// The name of the secret to edit
         key: String,
+
+        /// Environment the secret belongs to
+        #[arg(long)]
+        env: Option<String>,
+
+        /// New time-to-live from now (e.g., 6h, 7d, 2w), instead of keeping
+        /// the current expiry
+        #[arg(long)]
+        ttl: Option<String>,
+    },
+
+    /// Export a read-only copy of the vault for CI agents and other consumers
+    ExportReadonly {
+        /// Output file
+        #[arg(long, value_name = "FILE", default_value = "vault-readonly.vx")]
+        out: String,
+    },
+
+    /// Require an extra passphrase (on top of the master password) for a project
+    Passphrase {
+        /// Project name
+        project: String,
+
+        /// Remove the project's passphrase instead of setting it
+        #[arg(long)]
+        remove: bool,
+    },
+
+    /// Show or set how `vx generate` creates a project's secrets
+    ///
+    /// Options change only the settings they name; with none, the policy is
+    /// shown. `vx audit` flags secrets the policy applies to that are too
+    /// short, use other characters or are due for rotation.
+    Policy {
+        /// Project name
+        project: String,
+
+        /// Length of generated values
+        #[arg(long)]
+        length: Option<usize>,
+
+        /// Characters generated values are drawn from (alphanumeric, hex,
+        /// digits, base64url or printable)
+        #[arg(long)]
+        charset: Option<Charset>,
+
+        /// How often secrets should be rotated (e.g., 30d, 12w)
+        #[arg(long, value_name = "INTERVAL")]
+        rotate: Option<String>,
+
+        /// Glob selecting the secrets the policy applies to (e.g. '*_TOKEN');
+        /// all by default
+        #[arg(long, value_name = "GLOB")]
+        keys: Option<String>,
+
+        /// Remove the policy first
+        #[arg(long)]
+        clear: bool,
+    },
+
+    /// Show or set the rules a project's secrets must follow
+    ///
+    /// Options change only the rules they name; with none, the rules are
+    /// shown. Secrets already stored are not checked until `vx audit`.
+    Rules {
+        /// Project name
+        project: String,
+
+        /// Regular expression every key must match (e.g. '[A-Z][A-Z0-9_]*')
+        #[arg(long)]
+        key_pattern: Option<String>,
+
+        /// Require every secret to have a TTL
+        #[arg(long)]
+        require_ttl: bool,
+
+        /// Longest TTL a secret may be given (e.g., 30d)
+        #[arg(long, value_name = "TTL")]
+        max_ttl: Option<String>,
+
+        /// Largest value allowed, in bytes
+        #[arg(long, value_name = "BYTES")]
+        max_size: Option<u64>,
+
+        /// Regular expression no value may contain (repeatable)
+        #[arg(long, value_name = "PATTERN")]
+        forbid: Vec<String>,
+
+        /// Remove every rule first
+        #[arg(long)]
+        clear: bool,
+    },
+
+    /// Manage the passwords, keyfiles and recovery codes that unlock the vault
+    Keyslot {
+        #[command(subcommand)]
+        action: KeyslotAction,
+    },
+
+    /// Back up the vault's data key on paper
+    Backup {
+        #[command(subcommand)]
+        action: BackupAction,
+    },
+
+    /// Regain access to the vault from a paper backup
+    Restore {
+        #[command(subcommand)]
+        action: RestoreAction,
+    },
+
+    /// Let a trusted person open the vault after a waiting period you can veto
+    Emergency {
+        #[command(subcommand)]
+        action: EmergencyAction,
+    },
+
+    /// Unlock with Touch ID instead of the master password (macOS)
+    Touchid {
+        #[command(subcommand)]
+        action: TouchidAction,
+    },
+
+    /// Benchmark Argon2 and recommend (or apply) parameters for a target unlock time
+    Tune {
+        /// Target unlock time in milliseconds
+        #[arg(long, value_name = "MS", default_value_t = 500)]
+        target: u64,
+
+        /// Memory to use, in MB
+        #[arg(long, value_name = "MB", default_value_t = 64)]
+        memory: u32,
+
+        /// Re-wrap a key slot with the recommended parameters
+        #[arg(long)]
+        apply: bool,
+
+        /// Key slot to re-wrap (defaults to the only password slot)
+        #[arg(long, value_name = "ID", requires = "apply")]
+        slot: Option<u32>,
     },
 
     /// Update the VX CLI to the latest version
@@ -139,37 +757,879 @@ enum Commands {
     },
 
     /// Cache vault password for current session
-    Login,
+    Login {
+        /// Refuse the cached password after this long (e.g. 30m, 2h; defaults
+        /// to session_timeout from the config file)
+        #[arg(long, value_name = "DURATION")]
+        timeout: Option<String>,
+
+        /// Show a desktop notification for secrets and SSH identities expiring
+        /// within this window (default 7d)
+        #[arg(
+            long,
+            value_name = "WINDOW",
+            num_args = 0..=1,
+            default_missing_value = expiry::DEFAULT_WINDOW
+        )]
+        notify_expiring: Option<String>,
+    },
+
+    /// Hold the unlocked vault key in memory so commands skip the password
+    /// and key derivation (Unix only)
+    Daemon {
+        /// Stop the running daemon, wiping its key
+        #[arg(long, conflicts_with = "timeout")]
+        stop: bool,
+
+        /// Wipe the key and exit after this long (e.g. 30m, 8h)
+        #[arg(long, value_name = "DURATION")]
+        timeout: Option<String>,
+
+        /// Check hourly and show a desktop notification for secrets and SSH
+        /// identities expiring within this window (default 7d)
+        #[arg(
+            long,
+            value_name = "WINDOW",
+            num_args = 0..=1,
+            default_missing_value = expiry::DEFAULT_WINDOW,
+            conflicts_with = "stop"
+        )]
+        notify_expiring: Option<String>,
+    },
+
+    /// Encrypt (clean) or decrypt (smudge) a file on stdin, as a git filter
+    /// for .gitattributes
+    GitFilter {
+        /// Filter direction
+        #[arg(value_enum)]
+        mode: GitFilterMode,
+
+        /// Project holding the encryption key
+        #[arg(long)]
+        project: String,
+
+        /// Secret holding the encryption key
+        #[arg(long)]
+        key: String,
+
+        /// Environment of the secret
+        #[arg(long)]
+        env: Option<String>,
+    },
+
+    /// Manage which browser origins may request secrets from the extension
+    Origin {
+        #[command(subcommand)]
+        action: OriginAction,
+    },
+
+    /// Serve the browser extension over native messaging (started by the browser)
+    NativeHost {
+        /// Print the host manifest for this extension origin
+        /// (chrome-extension://<id>/) or Firefox add-on id, then exit
+        #[arg(long, value_name = "EXTENSION")]
+        manifest: Option<String>,
+
+        /// Arguments the browser passes to the host; ignored
+        #[arg(hide = true, allow_hyphen_values = true, trailing_var_arg = true)]
+        browser_args: Vec<String>,
+    },
+
+    /// Any other command runs the `vx-<command>` plugin found on PATH
+    #[command(external_subcommand)]
+    Plugin(Vec<String>),
+}
+
+#[derive(Subcommand)]
+enum KeyslotAction {
+    /// Add a key slot (a new password unless --keyfile or --recovery is given)
+    Add {
+        /// Unlock with the contents of this file (set VX_KEYFILE to use it)
+        #[arg(long, value_name = "FILE", conflicts_with = "recovery")]
+        keyfile: Option<String>,
+
+        /// Generate a one-time-displayed recovery code
+        #[arg(long)]
+        recovery: bool,
+
+        /// Description of the slot, e.g. who holds the credential
+        #[arg(long)]
+        label: Option<String>,
+    },
+
+    /// Remove a key slot; its credential stops unlocking the vault
+    Remove {
+        /// Slot id (see `vx keyslot list`)
+        id: u32,
+    },
+
+    /// List key slots
+    List,
+}
+
+#[derive(Subcommand)]
+enum KubeconfigAction {
+    /// Store a kubeconfig file in the vault
+    Add {
+        /// Name to run it by, e.g. the cluster
+        name: String,
+
+        /// Kubeconfig file to store
+        file: String,
+    },
+
+    /// List stored kubeconfigs
+    List,
+
+    /// Remove a stored kubeconfig
+    Remove {
+        /// Kubeconfig name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum WgAction {
+    /// Store a WireGuard config in the vault
+    Add {
+        /// Interface name, e.g. wg0 (at most 15 characters)
+        name: String,
+
+        /// wg-quick config file to store
+        file: String,
+    },
+
+    /// List stored WireGuard configs
+    List,
+
+    /// Remove a stored WireGuard config
+    Remove {
+        /// Interface name
+        name: String,
+    },
+
+    /// Bring the interface up with wg-quick
+    Up {
+        /// Interface name
+        name: String,
+    },
+
+    /// Take the interface down with wg-quick
+    Down {
+        /// Interface name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbAction {
+    /// Store a database connection as a secret (prompts for the password)
+    Add {
+        /// Project name
+        project: String,
+
+        /// Secret key name for the connection
+        name: String,
+
+        /// Database engine: postgres, mysql or mongodb
+        #[arg(long)]
+        engine: DbEngine,
+
+        /// Database host
+        #[arg(long)]
+        host: String,
+
+        /// Port, if not the engine's default
+        #[arg(long)]
+        port: Option<u16>,
+
+        /// User to log in as
+        #[arg(long)]
+        user: String,
+
+        /// Database to open
+        #[arg(long)]
+        database: Option<String>,
+
+        /// Environment to store the connection in (e.g. staging, prod)
+        #[arg(long)]
+        env: Option<String>,
+
+        /// Time-to-live (e.g., 6h, 7d, 2w)
+        #[arg(long)]
+        ttl: Option<String>,
+    },
+
+    /// Open the engine's client, passing the password through a private file
+    ///
+    /// Usage:
+    ///   vx db connect <project> <name> -- -c 'select 1'
+    Connect {
+        /// Project name
+        project: String,
+
+        /// Secret key name of the connection
+        name: String,
+
+        /// Environment to read from (e.g. staging, prod)
+        #[arg(long)]
+        env: Option<String>,
+
+        /// Arguments for the client
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupAction {
+    /// Show the data key as a 24-word BIP39 recovery phrase
+    Mnemonic,
+}
+
+#[derive(Subcommand)]
+enum RestoreAction {
+    /// Set a new master password using the 24-word recovery phrase
+    Mnemonic,
+}
+
+#[derive(Subcommand)]
+enum EmergencyAction {
+    /// Generate a key for receiving emergency access (run by the recipient)
+    Keygen {
+        /// File to write the secret key to
+        out: String,
+    },
+
+    /// Let the holder of a public key open the vault after a waiting period
+    Grant {
+        /// Who the grant is for, e.g. a name
+        label: String,
+
+        /// Public key printed by `vx emergency keygen`
+        public_key: String,
+
+        /// Time between a request and access (e.g. 72h, 7d, 2w)
+        #[arg(long, value_name = "DURATION", default_value = "7d")]
+        wait: String,
+    },
+
+    /// List emergency grants and pending requests
+    List,
+
+    /// Remove an emergency grant
+    Revoke {
+        /// Grant id (see `vx emergency list`)
+        id: u32,
+    },
+
+    /// Refuse the pending request on a grant
+    Veto {
+        /// Grant id (see `vx emergency list`)
+        id: u32,
+    },
+
+    /// Start the waiting period of your grant (run by the recipient)
+    Request {
+        /// Key file written by `vx emergency keygen`
+        key: String,
+    },
+
+    /// Open the vault once the waiting period has passed and set a password
+    Open {
+        /// Key file written by `vx emergency keygen`
+        key: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TouchidAction {
+    /// Keep an unlock key in the Keychain behind Touch ID
+    Enable {
+        /// How long after the master password was entered Touch ID is offered (e.g. 8h, 7d)
+        #[arg(long, value_name = "DURATION", default_value = "7d")]
+        window: String,
+    },
+
+    /// Remove the Keychain unlock key and its key slot
+    Disable,
+
+    /// Show whether Touch ID unlock is enabled and until when
+    Status,
+}
+
+#[derive(Subcommand)]
+enum TrashAction {
+    /// List removed items and when they will be purged
+    List,
+
+    /// Put a removed item back
+    Restore {
+        /// Trash id (see `vx trash list`)
+        id: u32,
+    },
+
+    /// Permanently delete everything in the trash
+    Empty,
+}
+
+#[derive(Subcommand)]
+enum SnapshotAction {
+    /// Save a copy of the vault as it is now
+    Create {
+        /// Snapshot name, e.g. pre-migration
+        name: String,
+    },
+
+    /// List snapshots and when they were taken
+    List,
+
+    /// Replace the vault's contents with a snapshot
+    Rollback {
+        /// Snapshot name
+        name: String,
+    },
+
+    /// Delete a snapshot
+    Delete {
+        /// Snapshot name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TransferAction {
+    /// Write the vault and its attachments to a bundle protected by a
+    /// one-time passphrase
+    Export {
+        /// Bundle file to write
+        out: String,
+    },
+
+    /// Install the vault from a bundle (on a machine without one)
+    Import {
+        /// Bundle file made by `vx transfer export`
+        file: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SyncAction {
+    /// Merge another copy of the vault, e.g. a sync tool's conflicted
+    /// copy, into this one secret by secret
+    ///
+    /// Changes made on one side only are taken as they are; a secret
+    /// changed on both sides is asked about.
+    Pull {
+        /// The other copy of the vault file
+        file: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum OriginAction {
+    /// Let an origin (e.g. https://github.com) request a secret
+    Allow {
+        /// Web origin, scheme and host only
+        origin: String,
+
+        /// Project name
+        project: String,
+
+        /// Secret key name
+        key: String,
+
+        /// Environment of the secret
+        #[arg(long)]
+        env: Option<String>,
+    },
+
+    /// Revoke one secret from an origin, or all of them if none is given
+    Revoke {
+        /// Web origin, scheme and host only
+        origin: String,
+
+        /// Project name
+        #[arg(requires = "key")]
+        project: Option<String>,
+
+        /// Secret key name
+        key: Option<String>,
+
+        /// Environment of the secret
+        #[arg(long, requires = "key")]
+        env: Option<String>,
+    },
+
+    /// List allowed origins and their secrets
+    List,
 }
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+    let cli = parse_args();
+    let json_errors = cli.output == Some(OutputFormat::Json);
+    if let Err(e) = run(cli) {
+        if json_errors {
+            eprintln!("{}", e.to_json());
+        } else {
+            eprintln!("Error: {}", e);
+        }
+        std::process::exit(e.exit_code());
     }
 }
 
-fn run() -> Result<(), CliError> {
-    let cli = Cli::parse();
+/// Parses the command line. clap exits with 2 on a usage error, which is
+/// NOT_FOUND here, so usage errors are reported here with
+/// `exit_code::USAGE`, as JSON if `--output json` was given.
+fn parse_args() -> Cli {
+    Cli::try_parse().unwrap_or_else(|e| {
+        if !e.use_stderr() {
+            // --help and --version
+            let _ = e.print();
+            std::process::exit(0);
+        }
+
+        let args: Vec<String> = std::env::args().collect();
+        if error::asks_for_json(&args) {
+            let message = e.to_string();
+            eprintln!(
+                "{}",
+                error::json_error(exit_code::USAGE, message.trim(), Default::default())
+            );
+        } else {
+            let _ = e.print();
+        }
+        std::process::exit(exit_code::USAGE);
+    })
+}
+
+/// Runs a command for a plugin through the session of the `vx` that started
+/// it; see `commands::plugin`. Only reads and writes of single values are
+/// served.
+#[cfg(unix)]
+fn run_in_plugin_session(
+    session: &plugin_session::Client,
+    command: Commands,
+    config: &config::Config,
+) -> Result<(), CliError> {
+    match command {
+        Commands::Get {
+            args,
+            env,
+            path: None,
+            format: ValueFormat::Text,
+            regex: false,
+            copy: false,
+            qr: false,
+            ..
+        } => commands::plugin::get(session, &args, env.as_deref()),
+        Commands::Env {
+            project,
+            env,
+            prefix,
+            ..
+        } => commands::plugin::env(session, &config.project(project)?, env.as_deref(), &prefix),
+        Commands::Add {
+            project,
+            key: Some(key),
+            file,
+            from_env,
+            env,
+            ttl: None,
+            template: false,
+            json: false,
+            certificate: false,
+        } => commands::plugin::add(
+            session,
+            &project,
+            &key,
+            env.as_deref(),
+            file.as_deref(),
+            from_env.as_deref(),
+        ),
+        _ => Err(CliError::Generic(
+            "Plugins can only run 'vx get', 'vx env' and 'vx add' with the options their \
+             session supports"
+                .to_string(),
+        )),
+    }
+}
+
+fn run(cli: Cli) -> Result<(), CliError> {
+    logging::init(cli.verbose);
+    input::init_password_source(cli.password_file, cli.password_stdin)?;
+    let config = config::load()?;
+    let output = cli.output.unwrap_or(config.output);
+    manifest::load()?;
+    storage::recover_interrupted_save()?;
+
+    #[cfg(unix)]
+    if let Some(session) = plugin_session::Client::from_env() {
+        return run_in_plugin_session(&session, cli.command, config);
+    }
 
     match cli.command {
-        Commands::Init { project } => commands::init::execute(&project),
+        Commands::Init { project, security } => commands::init::execute(&project, security),
         Commands::Add {
             project,
             key,
             file,
+            from_env,
+            env,
+            ttl,
+            template,
+            json,
+            certificate,
+        } => {
+            let kind = if template {
+                commands::add::ValueKind::Template
+            } else if json {
+                commands::add::ValueKind::Json
+            } else if certificate {
+                commands::add::ValueKind::Certificate
+            } else {
+                commands::add::ValueKind::Plain
+            };
+            commands::add::execute(
+                &project,
+                key.as_deref(),
+                file,
+                from_env,
+                env.as_deref(),
+                ttl,
+                kind,
+            )
+        }
+        Commands::Generate {
+            project,
+            key,
             env,
+            length,
+            charset,
             ttl,
-        } => commands::add::execute(&project, key.as_deref(), file, env, ttl),
-        Commands::Get { project, key } => commands::get::execute(&project, key.as_deref()),
-        Commands::List => commands::list::execute(),
-        Commands::Secrets { project } => commands::list_secrets::execute(&project),
-        Commands::Audit => commands::audit::execute(),
+        } => commands::generate::execute(
+            &project,
+            &key,
+            env.as_deref(),
+            length,
+            charset,
+            ttl.as_deref(),
+        ),
+        Commands::Get {
+            args,
+            env,
+            path,
+            format,
+            regex,
+            copy,
+            qr,
+            encrypt,
+            reveal,
+        } => {
+            let destination = if copy {
+                commands::get::Destination::Clipboard
+            } else if qr {
+                commands::get::Destination::Qr { encrypt }
+            } else {
+                commands::get::Destination::Stdout { reveal }
+            };
+            commands::get::execute(
+                &args,
+                env.as_deref(),
+                path.as_deref(),
+                format,
+                regex,
+                destination,
+            )
+        }
+        Commands::Alias {
+            project,
+            key,
+            target_project,
+            target_key,
+            env,
+            target_env,
+        } => commands::alias::execute(
+            &project,
+            &key,
+            env.as_deref(),
+            &target_project,
+            &target_key,
+            target_env.as_deref(),
+        ),
+        Commands::Export { project, env } => {
+            commands::export::execute(&config.project(project)?, env.as_deref())
+        }
+        Commands::Env {
+            project,
+            env,
+            prefix,
+            no_prompt,
+        } => commands::env::execute(
+            &config.project(project)?,
+            env.as_deref(),
+            &prefix,
+            no_prompt,
+        ),
+        Commands::Direnv { project, env } => {
+            commands::direnv::execute(&config.project(project)?, env.as_deref())
+        }
+        Commands::List { format } => commands::list::execute(format.unwrap_or(output)),
+        Commands::Secrets { project, sort } => {
+            commands::list_secrets::execute(&config.project(project)?, sort)
+        }
+        Commands::Info { project, key, env } => {
+            commands::info::execute(&project, &key, env.as_deref())
+        }
+        Commands::Ttl {
+            project,
+            key,
+            ttl,
+            clear,
+            env,
+        } => {
+            let change = match ttl.as_deref() {
+                Some(ttl) => commands::ttl::Change::Set(ttl),
+                None if clear => commands::ttl::Change::Clear,
+                None => commands::ttl::Change::Show,
+            };
+            commands::ttl::execute(&project, &key, env.as_deref(), change)
+        }
+        Commands::Audit { format, min_idle } => {
+            commands::audit::execute(format.unwrap_or(output), min_idle.as_deref())
+        }
+        Commands::Expiring { within, format } => {
+            commands::expiring::execute(&within, format.unwrap_or(output))
+        }
+        Commands::Stats { top, format } => commands::stats::execute(top, format.unwrap_or(output)),
+        Commands::Scan {
+            path,
+            project,
+            history,
+        } => commands::scan::execute(&path, project.as_deref(), history),
+        Commands::History { limit, project } => {
+            commands::history::execute(limit, project.as_deref())
+        }
+        Commands::Log { verify } => commands::log::execute(verify),
         Commands::Ssh { target, args } => commands::ssh::execute(target, args),
         Commands::Scp { server, args } => commands::scp::execute(&server, &args),
-        Commands::Remove { project, key } => commands::remove::execute(&project, key.as_deref()),
-        Commands::Edit { project, key } => commands::edit::execute(&project, &key),
+        Commands::Kubeconfig { action } => match action {
+            KubeconfigAction::Add { name, file } => commands::kubeconfig::add(&name, &file),
+            KubeconfigAction::List => commands::kubeconfig::list(),
+            KubeconfigAction::Remove { name } => commands::kubeconfig::remove(&name),
+        },
+        Commands::Kubectl { name, args } => commands::kubectl::execute(&name, &args),
+        Commands::Wg { action } => match action {
+            WgAction::Add { name, file } => commands::wg::add(&name, &file),
+            WgAction::List => commands::wg::list(),
+            WgAction::Remove { name } => commands::wg::remove(&name),
+            WgAction::Up { name } => commands::wg::up(&name),
+            WgAction::Down { name } => commands::wg::down(&name),
+        },
+        Commands::Db { action } => match action {
+            DbAction::Add {
+                project,
+                name,
+                engine,
+                host,
+                port,
+                user,
+                database,
+                env,
+                ttl,
+            } => commands::db::add(
+                &project,
+                &name,
+                env.as_deref(),
+                commands::db::ConnectionOptions {
+                    engine,
+                    host,
+                    port,
+                    user,
+                    database,
+                },
+                ttl.as_deref(),
+            ),
+            DbAction::Connect {
+                project,
+                name,
+                env,
+                args,
+            } => commands::db::connect(&project, &name, env.as_deref(), &args),
+        },
+        Commands::Remove {
+            project,
+            key,
+            env,
+            pattern,
+            dry_run,
+        } => match pattern {
+            Some(pattern) => {
+                commands::remove::remove_matching(&project, &pattern, env.as_deref(), dry_run)
+            }
+            None => commands::remove::execute(&project, key.as_deref(), env.as_deref()),
+        },
+        Commands::Trash { action } => match action {
+            TrashAction::List => commands::trash::list(),
+            TrashAction::Restore { id } => commands::trash::restore(id),
+            TrashAction::Empty => commands::trash::empty(),
+        },
+        Commands::Undo => commands::undo::execute(),
+        Commands::Snapshot { action } => match action {
+            SnapshotAction::Create { name } => commands::snapshot::create(&name),
+            SnapshotAction::List => commands::snapshot::list(),
+            SnapshotAction::Rollback { name } => commands::snapshot::rollback(&name),
+            SnapshotAction::Delete { name } => commands::snapshot::delete(&name),
+        },
+        Commands::Transfer { action } => match action {
+            TransferAction::Export { out } => commands::transfer::export(&out),
+            TransferAction::Import { file } => commands::transfer::import(&file),
+        },
+        Commands::Sync { action } => match action {
+            SyncAction::Pull { file } => commands::sync::pull(&file),
+        },
+        Commands::Archive {
+            project,
+            out,
+            remove,
+        } => commands::archive::archive(&project, &out, remove),
+        Commands::ImportProject {
+            file,
+            project,
+            rename,
+        } => commands::import_project::execute(&file, &project, rename.as_deref()),
+        Commands::Unarchive { file, rename } => {
+            commands::archive::unarchive(&file, rename.as_deref())
+        }
+        Commands::Attach {
+            project,
+            file,
+            name,
+        } => commands::attach::execute(&project, &file, name.as_deref()),
+        Commands::Attachments { project, name, out } => {
+            commands::attachments::execute(&project, name.as_deref(), out.as_deref())
+        }
+        Commands::Detach { project, name } => commands::detach::execute(&project, &name),
+        Commands::Edit {
+            project,
+            key,
+            env,
+            ttl,
+        } => commands::edit::execute(&project, &key, env.as_deref(), ttl.as_deref()),
+        Commands::ExportReadonly { out } => commands::export_readonly::execute(&out),
+        Commands::Passphrase { project, remove } => {
+            commands::passphrase::execute(&project, remove)
+        }
+        Commands::Policy {
+            project,
+            length,
+            charset,
+            rotate,
+            keys,
+            clear,
+        } => commands::policy::execute(
+            &project,
+            commands::policy::Changes {
+                length,
+                charset,
+                rotate,
+                keys,
+                clear,
+            },
+        ),
+        Commands::Rules {
+            project,
+            key_pattern,
+            require_ttl,
+            max_ttl,
+            max_size,
+            forbid,
+            clear,
+        } => commands::rules::execute(
+            &project,
+            commands::rules::Changes {
+                key_pattern,
+                require_ttl,
+                max_ttl,
+                max_size,
+                forbid,
+                clear,
+            },
+        ),
+        Commands::Keyslot { action } => match action {
+            KeyslotAction::Add {
+                keyfile,
+                recovery,
+                label,
+            } => commands::keyslot::add(keyfile.as_deref(), recovery, label.as_deref()),
+            KeyslotAction::Remove { id } => commands::keyslot::remove(id),
+            KeyslotAction::List => commands::keyslot::list(),
+        },
+        Commands::Backup { action } => match action {
+            BackupAction::Mnemonic => commands::backup::mnemonic(),
+        },
+        Commands::Restore { action } => match action {
+            RestoreAction::Mnemonic => commands::restore::mnemonic(),
+        },
+        Commands::Emergency { action } => match action {
+            EmergencyAction::Keygen { out } => commands::emergency::keygen(&out),
+            EmergencyAction::Grant {
+                label,
+                public_key,
+                wait,
+            } => commands::emergency::grant(&label, &public_key, &wait),
+            EmergencyAction::List => commands::emergency::list(),
+            EmergencyAction::Revoke { id } => commands::emergency::revoke(id),
+            EmergencyAction::Veto { id } => commands::emergency::veto(id),
+            EmergencyAction::Request { key } => commands::emergency::request(&key),
+            EmergencyAction::Open { key } => commands::emergency::open(&key),
+        },
+        Commands::Touchid { action } => match action {
+            TouchidAction::Enable { window } => commands::touchid::enable(&window),
+            TouchidAction::Disable => commands::touchid::disable(),
+            TouchidAction::Status => commands::touchid::status(),
+        },
+        Commands::Tune {
+            target,
+            memory,
+            apply,
+            slot,
+        } => commands::tune::execute(target, memory, apply, slot),
         Commands::Update { yes } => commands::update::execute(yes),
-        Commands::Login => commands::login::execute(),
+        Commands::Login {
+            timeout,
+            notify_expiring,
+        } => commands::login::execute(timeout.as_deref(), notify_expiring.as_deref()),
+        Commands::Daemon {
+            stop,
+            timeout,
+            notify_expiring,
+        } => commands::daemon::execute(stop, timeout.as_deref(), notify_expiring.as_deref()),
+        Commands::GitFilter {
+            mode,
+            project,
+            key,
+            env,
+        } => commands::git_filter::execute(mode, &project, &key, env.as_deref()),
+        Commands::Origin { action } => match action {
+            OriginAction::Allow {
+                origin,
+                project,
+                key,
+                env,
+            } => commands::origin::allow(&origin, &project, &key, env.as_deref()),
+            OriginAction::Revoke {
+                origin,
+                project,
+                key,
+                env,
+            } => commands::origin::revoke(&origin, project.as_deref(), key.as_deref(), env.as_deref()),
+            OriginAction::List => commands::origin::list(),
+        },
+        Commands::NativeHost { manifest, .. } => match manifest {
+            Some(extension) => commands::native_host::print_manifest(&extension),
+            None => commands::native_host::execute(),
+        },
+        Commands::Plugin(args) => commands::plugin::execute(&args),
     }
 }
\ No newline at end of file
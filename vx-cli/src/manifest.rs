@@ -0,0 +1,85 @@
+//! Per-repository project mapping (`.vaultx.toml`).
+//!
+//! A repository can name its vault project in a `.vaultx.toml` at its root
+//! (or in any directory above the current one, up to the repository root),
+//! so that `vx get`, `vx env`, `vx export` and `vx secrets` work without a
+//! project argument. `keys` optionally limits the secrets `vx env`,
+//! `vx export` and a listing `vx get` show; `*` matches any run of
+//! characters and `?` any one.
+//!
+//! ```toml
+//! project = "my-app"
+//! keys = ["DATABASE_URL", "STRIPE_*"]
+//! ```
+
+use crate::error::CliError;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use vx_core::pattern;
+
+/// Manifest file name
+pub const FILE_NAME: &str = ".vaultx.toml";
+
+static MANIFEST: OnceLock<Option<Manifest>> = OnceLock::new();
+
+/// The contents of a `.vaultx.toml`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Vault project used when a command's project argument is omitted
+    pub project: String,
+    /// Patterns limiting the keys that are exported or listed; all keys if
+    /// empty
+    #[serde(default)]
+    pub keys: Vec<String>,
+}
+
+impl Manifest {
+    /// Returns whether `key` passes the manifest's key filters.
+    pub fn includes(&self, key: &str) -> bool {
+        self.keys.is_empty() || self.keys.iter().any(|pattern| pattern::matches(pattern, key))
+    }
+}
+
+/// Looks for a manifest in the current directory and its parents, stopping
+/// at the root of the repository, and makes it available through `get`.
+pub fn load() -> Result<Option<&'static Manifest>, CliError> {
+    let mut manifest = None;
+
+    let cwd = std::env::current_dir()?;
+    for dir in cwd.ancestors() {
+        let path = dir.join(FILE_NAME);
+        if path.is_file() {
+            let contents = fs::read_to_string(&path)?;
+            manifest = Some(toml::from_str(&contents).map_err(|e| {
+                CliError::InvalidConfig(path.display().to_string(), e.to_string())
+            })?);
+            break;
+        }
+        if is_repository_root(dir) {
+            break;
+        }
+    }
+
+    Ok(MANIFEST.get_or_init(|| manifest).as_ref())
+}
+
+/// Returns the manifest found by `load`, if any.
+pub fn get() -> Option<&'static Manifest> {
+    MANIFEST.get().and_then(Option::as_ref)
+}
+
+/// Returns whether `key` of `project` passes the key filters of the
+/// manifest. Keys of other projects are not filtered.
+pub fn includes(project: &str, key: &str) -> bool {
+    match get() {
+        Some(manifest) if manifest.project == project => manifest.includes(key),
+        _ => true,
+    }
+}
+
+fn is_repository_root(dir: &Path) -> bool {
+    dir.join(".git").exists()
+}
@@ -0,0 +1,431 @@
+//! Owner-only permissions for the vault directory and the files in it.
+//!
+//! On Unix directories get mode 0700 and files 0600. On Windows both get a
+//! protected DACL with a single entry granting the current user full
+//! control, so nothing is inherited from the parent folder; a directory's
+//! entry is inherited by the files created in it.
+//!
+//! `audit` checks that files are still stored that way, for `vx audit`.
+
+use crate::error::CliError;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Folder names used by cloud sync clients; names starting with one of
+/// them count, e.g. "OneDrive - Company"
+const SYNCED_FOLDERS: &[&str] = &[
+    "Dropbox",
+    "OneDrive",
+    "Google Drive",
+    "My Drive",
+    "iCloud Drive",
+    "Mobile Documents",
+    "Box Sync",
+    "pCloud Drive",
+    "Nextcloud",
+    "ownCloud",
+];
+
+/// A vault file or directory stored where others may be able to read it.
+#[derive(Debug, Serialize)]
+pub struct FileIssue {
+    pub path: PathBuf,
+    pub problem: String,
+    /// What to do about it
+    pub fix: String,
+}
+
+/// Creates `dir` and its parents if needed and restricts `dir` to the
+/// current user.
+pub fn create_private_dir(dir: &Path) -> Result<(), CliError> {
+    fs::create_dir_all(dir)?;
+    restrict_dir(dir)
+}
+
+/// Restricts an existing directory to the current user.
+pub fn restrict_dir(dir: &Path) -> Result<(), CliError> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    }
+
+    #[cfg(windows)]
+    windows::set_owner_only(dir, true)?;
+
+    Ok(())
+}
+
+/// Restricts an existing file to the current user.
+pub fn restrict_file(file: &Path) -> Result<(), CliError> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(file, fs::Permissions::from_mode(0o600))?;
+    }
+
+    #[cfg(windows)]
+    windows::set_owner_only(file, false)?;
+
+    Ok(())
+}
+
+/// Checks the vault directory, everything in it and the `extra` files
+/// (e.g. the session cache) for permissions letting other users in, and the
+/// vault directory for being inside a cloud-synced folder others can read.
+///
+/// Permissions are only checked on Unix.
+pub fn audit(vault_dir: &Path, extra: &[PathBuf]) -> Result<Vec<FileIssue>, CliError> {
+    let mut issues = Vec::new();
+    if !vault_dir.exists() {
+        return Ok(issues);
+    }
+
+    let mut pending = vec![vault_dir.to_path_buf()];
+    pending.extend(extra.iter().filter(|path| path.exists()).cloned());
+    while let Some(path) = pending.pop() {
+        let metadata = fs::symlink_metadata(&path)?;
+        if metadata.is_dir() {
+            for entry in fs::read_dir(&path)? {
+                pending.push(entry?.path());
+            }
+        }
+        if let Some(issue) = check_private(&path, &metadata) {
+            issues.push(issue);
+        }
+    }
+
+    if let Some(issue) = check_synced(vault_dir) {
+        issues.push(issue);
+    }
+
+    issues.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(issues)
+}
+
+/// Reports a file outside the vault directory (e.g. a password file) that
+/// is not private to the current user, as `audit` does.
+pub fn check_file(path: &Path) -> Option<FileIssue> {
+    let metadata = fs::metadata(path).ok()?;
+    check_private(path, &metadata)
+}
+
+/// Reports a file or directory that is not owned by and private to the
+/// current user.
+#[cfg(unix)]
+fn check_private(path: &Path, metadata: &fs::Metadata) -> Option<FileIssue> {
+    use std::os::unix::fs::MetadataExt;
+
+    if metadata.file_type().is_symlink() {
+        return None;
+    }
+
+    if metadata.uid() != crate::daemon::current_uid() {
+        return Some(FileIssue {
+            path: path.to_path_buf(),
+            problem: format!("owned by another user (uid {})", metadata.uid()),
+            fix: format!("sudo chown \"$USER\" {}", quote(path)),
+        });
+    }
+
+    let mode = metadata.mode() & 0o777;
+    if mode & 0o077 == 0 {
+        return None;
+    }
+    let (kind, private_mode) = if metadata.is_dir() {
+        ("directory", 0o700)
+    } else {
+        ("file", 0o600)
+    };
+    let who = if mode & 0o007 != 0 {
+        "all users"
+    } else {
+        "its group"
+    };
+    Some(FileIssue {
+        path: path.to_path_buf(),
+        problem: format!("{} has mode {:o}, open to {}", kind, mode, who),
+        fix: format!("chmod {:o} {}", private_mode, quote(path)),
+    })
+}
+
+#[cfg(not(unix))]
+fn check_private(_path: &Path, _metadata: &fs::Metadata) -> Option<FileIssue> {
+    None
+}
+
+/// Reports a vault directory inside a cloud-synced folder that is public
+/// or that other local users can read.
+fn check_synced(vault_dir: &Path) -> Option<FileIssue> {
+    let vault_dir = vault_dir.canonicalize().ok()?;
+    let (root, service) = vault_dir.ancestors().find_map(|dir| {
+        let name = dir.file_name()?.to_str()?;
+        let service = SYNCED_FOLDERS
+            .iter()
+            .find(|synced| name.starts_with(*synced))?;
+        Some((dir, *service))
+    })?;
+
+    let public = vault_dir
+        .strip_prefix(root)
+        .ok()?
+        .components()
+        .any(|part| part.as_os_str().eq_ignore_ascii_case("Public"));
+    if public {
+        return Some(FileIssue {
+            path: vault_dir.clone(),
+            problem: format!("in the public folder of {}, shared with anyone", service),
+            fix: "move the vault out of it and point vault_path in config.toml at the new place"
+                .to_string(),
+        });
+    }
+
+    if readable_by_others(root) {
+        return Some(FileIssue {
+            path: root.to_path_buf(),
+            problem: format!(
+                "the vault is synced by {}, whose folder other users can read",
+                service
+            ),
+            fix: format!(
+                "chmod 700 {}, and check the folder is not shared in {}",
+                quote(root),
+                service
+            ),
+        });
+    }
+    None
+}
+
+#[cfg(unix)]
+fn readable_by_others(dir: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(dir).is_ok_and(|metadata| metadata.mode() & 0o004 != 0)
+}
+
+#[cfg(not(unix))]
+fn readable_by_others(_dir: &Path) -> bool {
+    false
+}
+
+/// Quotes a path for POSIX shells, if it needs it.
+fn quote(path: &Path) -> String {
+    let path = path.display().to_string();
+    if path
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/._-~".contains(c))
+    {
+        path
+    } else {
+        format!("'{}'", path.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use crate::error::CliError;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use std::ptr;
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, LocalFree, ERROR_SUCCESS, GENERIC_ALL, HANDLE,
+    };
+    use windows_sys::Win32::Security::Authorization::{
+        SetEntriesInAclW, SetNamedSecurityInfoW, EXPLICIT_ACCESS_W, NO_MULTIPLE_TRUSTEE,
+        SET_ACCESS, SE_FILE_OBJECT, TRUSTEE_IS_SID, TRUSTEE_IS_USER, TRUSTEE_W,
+    };
+    use windows_sys::Win32::Security::{
+        GetTokenInformation, TokenUser, ACL, DACL_SECURITY_INFORMATION, NO_INHERITANCE,
+        PROTECTED_DACL_SECURITY_INFORMATION, SUB_CONTAINERS_AND_OBJECTS_INHERIT, TOKEN_QUERY,
+        TOKEN_USER,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    /// Replaces the DACL of `path` with one granting only the current user
+    /// full control. With `inherit`, files and directories created inside
+    /// get the same entry.
+    pub fn set_owner_only(path: &Path, inherit: bool) -> Result<(), CliError> {
+        let user = current_user()?;
+        let sid = user.as_ptr() as *const TOKEN_USER;
+
+        let access = EXPLICIT_ACCESS_W {
+            grfAccessPermissions: GENERIC_ALL,
+            grfAccessMode: SET_ACCESS,
+            grfInheritance: if inherit {
+                SUB_CONTAINERS_AND_OBJECTS_INHERIT
+            } else {
+                NO_INHERITANCE
+            },
+            Trustee: TRUSTEE_W {
+                pMultipleTrustee: ptr::null_mut(),
+                MultipleTrusteeOperation: NO_MULTIPLE_TRUSTEE,
+                TrusteeForm: TRUSTEE_IS_SID,
+                TrusteeType: TRUSTEE_IS_USER,
+                // SAFETY: `user` holds a TOKEN_USER filled in by the system
+                ptstrName: unsafe { (*sid).User.Sid } as *mut u16,
+            },
+        };
+
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut acl: *mut ACL = ptr::null_mut();
+
+        // SAFETY: every pointer is valid for the duration of the calls, and
+        // the ACL allocated by SetEntriesInAclW is freed with LocalFree
+        unsafe {
+            let status = SetEntriesInAclW(1, &access, ptr::null(), &mut acl);
+            if status != ERROR_SUCCESS {
+                return Err(os_error(status));
+            }
+
+            let status = SetNamedSecurityInfoW(
+                wide.as_ptr(),
+                SE_FILE_OBJECT,
+                DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                acl,
+                ptr::null(),
+            );
+            LocalFree(acl as _);
+            if status != ERROR_SUCCESS {
+                return Err(os_error(status));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the TOKEN_USER of the current process, which holds the
+    /// user's SID.
+    fn current_user() -> Result<Vec<u64>, CliError> {
+        // SAFETY: the token handle is closed before returning and the buffer
+        // is sized by the first GetTokenInformation call
+        unsafe {
+            let mut token: HANDLE = ptr::null_mut();
+            if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+
+            let mut len = 0u32;
+            GetTokenInformation(token, TokenUser, ptr::null_mut(), 0, &mut len);
+            // u64 elements keep the buffer aligned for TOKEN_USER
+            let mut buffer = vec![0u64; (len as usize).div_ceil(8)];
+            let ok =
+                GetTokenInformation(token, TokenUser, buffer.as_mut_ptr().cast(), len, &mut len);
+            let error = std::io::Error::last_os_error();
+            CloseHandle(token);
+
+            if ok == 0 {
+                return Err(error.into());
+            }
+            Ok(buffer)
+        }
+    }
+
+    fn os_error(code: u32) -> CliError {
+        std::io::Error::from_raw_os_error(code as i32).into()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn test_vault_dir_and_files_are_private() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("a").join("vault");
+        create_private_dir(&dir).unwrap();
+        assert_eq!(mode(&dir), 0o700);
+
+        let file = dir.join("vault.vx");
+        fs::write(&file, b"data").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o644)).unwrap();
+        restrict_file(&file).unwrap();
+        assert_eq!(mode(&file), 0o600);
+
+        // An existing directory is tightened too
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        create_private_dir(&dir).unwrap();
+        assert_eq!(mode(&dir), 0o700);
+    }
+
+    #[test]
+    fn test_restricting_missing_paths_fails() {
+        let root = tempfile::tempdir().unwrap();
+        let missing = root.path().join("missing");
+        assert!(restrict_file(&missing).is_err());
+        assert!(restrict_dir(&missing).is_err());
+    }
+
+    #[test]
+    fn test_audit_reports_files_open_to_others() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("vault");
+        create_private_dir(&dir).unwrap();
+        let private = dir.join("vault.vx");
+        fs::write(&private, b"data").unwrap();
+        restrict_file(&private).unwrap();
+        assert!(audit(&dir, &[]).unwrap().is_empty());
+
+        let shard = dir.join("shard file");
+        fs::write(&shard, b"data").unwrap();
+        fs::set_permissions(&shard, fs::Permissions::from_mode(0o640)).unwrap();
+        let session = root.path().join("session");
+        fs::write(&session, b"data").unwrap();
+        fs::set_permissions(&session, fs::Permissions::from_mode(0o604)).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o750)).unwrap();
+
+        let issues = audit(&dir, &[session.clone(), root.path().join("missing")]).unwrap();
+        let found: Vec<(&Path, &str, &str)> = issues
+            .iter()
+            .map(|issue| (issue.path.as_path(), issue.problem.as_str(), issue.fix.as_str()))
+            .collect();
+        let shard_fix = format!("chmod 600 '{}'", shard.display());
+        let dir_fix = format!("chmod 700 {}", dir.display());
+        let session_fix = format!("chmod 600 {}", session.display());
+        assert_eq!(
+            found,
+            [
+                (session.as_path(), "file has mode 604, open to all users", session_fix.as_str()),
+                (dir.as_path(), "directory has mode 750, open to its group", dir_fix.as_str()),
+                (shard.as_path(), "file has mode 640, open to its group", shard_fix.as_str()),
+            ]
+        );
+        assert!(check_file(&session).is_some());
+        assert!(check_file(&root.path().join("missing")).is_none());
+    }
+
+    #[test]
+    fn test_audit_of_missing_vault_dir_is_empty() {
+        let root = tempfile::tempdir().unwrap();
+        assert!(audit(&root.path().join("missing"), &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_synced_folders_are_reported() {
+        let root = tempfile::tempdir().unwrap();
+        let dropbox = root.path().join("Dropbox (Personal)");
+        let public = dropbox.join("Public").join("vault");
+        create_private_dir(&public).unwrap();
+        let issue = check_synced(&public).unwrap();
+        assert_eq!(issue.problem, "in the public folder of Dropbox, shared with anyone");
+
+        let vault = dropbox.join("vault");
+        create_private_dir(&vault).unwrap();
+        fs::set_permissions(&dropbox, fs::Permissions::from_mode(0o755)).unwrap();
+        let issue = check_synced(&vault).unwrap();
+        assert_eq!(issue.path, dropbox.canonicalize().unwrap());
+        assert!(issue.problem.contains("Dropbox"));
+
+        fs::set_permissions(&dropbox, fs::Permissions::from_mode(0o700)).unwrap();
+        assert!(check_synced(&vault).is_none());
+        assert!(check_synced(root.path()).is_none());
+    }
+}
@@ -0,0 +1,467 @@
+//! Plugin sessions: the `vx` running a plugin serves it the secrets it may
+//! use, without handing over the data key.
+//!
+//! While the plugin runs, the `vx` that started it listens on a unix socket
+//! in the vault directory and answers requests made with the session token.
+//! The `vx get`, `vx env` and `vx add` commands the plugin runs find the
+//! token in `VX_SESSION_TOKEN` and go through the session instead of
+//! unlocking the vault; see `Client`.
+//!
+//! # Protocol
+//! One request per connection, as a single line starting with the token:
+//! `<token> GET <project> <name>`, `<token> LIST <project> [<env>]` or
+//! `<token> SET <project> <name> <hex value>`, where `<name>` is a key, or
+//! `<env>/<key>` for a key in an environment. The session answers `OK`
+//! followed by the hex-encoded value for `GET` and the keys for `LIST`, or
+//! `ERR <reason>`.
+//!
+//! # Security Notes
+//! - A session reaches only the projects its `Scope` names, is read-only
+//!   unless the plugin's config allows writes, and ends at its expiry or
+//!   when the plugin exits, whichever comes first
+//! - Templates are rendered only from projects in scope
+//! - The socket is created with mode 0600, and the session checks the
+//!   client's uid like `vx daemon` does
+
+use crate::commands::qualified_key;
+use crate::config;
+use crate::daemon;
+use crate::error::CliError;
+use crate::storage;
+use std::fs;
+use std::io::Write;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use vx_core::crypto;
+use vx_core::ttl::current_timestamp;
+use vx_core::{Operation, SecretBytes, SecureKey, Vault, VaultError};
+use zeroize::Zeroizing;
+
+/// Environment variable holding the session token of the plugin a `vx`
+/// command runs under, as `<pid>:<token>`
+pub const TOKEN_ENV: &str = "VX_SESSION_TOKEN";
+
+/// Longest request or response line accepted, enough for a large value in
+/// hex
+const MAX_LINE: u64 = 1024 * 1024;
+
+/// What a plugin's session may do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scope {
+    /// Projects whose secrets may be read
+    pub projects: Vec<String>,
+    /// Whether secrets may be added to them
+    pub write: bool,
+    /// Unix time at which the session ends
+    pub expires_at: u64,
+}
+
+/// A request to a plugin session.
+#[derive(Debug, PartialEq, Eq)]
+enum Request {
+    /// Read the secret `name` of `project`
+    Get {
+        project: String,
+        env: Option<String>,
+        name: String,
+    },
+    /// List the keys of `project`, or of one of its environments
+    List {
+        project: String,
+        env: Option<String>,
+    },
+    /// Add or replace the secret `name` of `project`
+    Set {
+        project: String,
+        env: Option<String>,
+        name: String,
+        value: Zeroizing<Vec<u8>>,
+    },
+}
+
+impl Request {
+    /// Parses a request line with the token removed.
+    fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split(' ');
+        let request = match (words.next()?, words.next()?) {
+            ("GET", project) => {
+                let (env, name) = split_name(words.next()?);
+                Request::Get {
+                    project: project.to_string(),
+                    env,
+                    name,
+                }
+            }
+            ("LIST", project) => Request::List {
+                project: project.to_string(),
+                env: words.next().map(str::to_string),
+            },
+            ("SET", project) => {
+                let (env, name) = split_name(words.next()?);
+                Request::Set {
+                    project: project.to_string(),
+                    env,
+                    name,
+                    value: daemon::decode_hex(words.next()?)?,
+                }
+            }
+            _ => return None,
+        };
+        words.next().is_none().then_some(request)
+    }
+
+    fn project(&self) -> &str {
+        match self {
+            Request::Get { project, .. }
+            | Request::List { project, .. }
+            | Request::Set { project, .. } => project,
+        }
+    }
+}
+
+/// Splits `<env>/<key>` into its environment and key.
+fn split_name(name: &str) -> (Option<String>, String) {
+    match name.split_once('/') {
+        Some((env, key)) => (Some(env.to_string()), key.to_string()),
+        None => (None, name.to_string()),
+    }
+}
+
+impl Scope {
+    /// Checks that `request` may be served at `now`.
+    fn check(&self, request: &Request, now: u64) -> Result<(), String> {
+        if now >= self.expires_at {
+            return Err("the plugin's session has expired".to_string());
+        }
+        if !self.projects.iter().any(|project| project == request.project()) {
+            return Err(format!(
+                "project '{}' is not in the plugin's session",
+                request.project()
+            ));
+        }
+        if matches!(request, Request::Set { .. }) && !self.write {
+            return Err("the plugin's session is read-only".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Checks a request line against the session's token and scope, and
+/// returns the request.
+fn authorize(line: &str, token: &str, scope: &Scope, now: u64) -> Result<Request, String> {
+    let (given, request) = line.split_once(' ').unwrap_or((line, ""));
+    if !same_token(given, token) {
+        return Err("invalid session token".to_string());
+    }
+    let request = Request::parse(request).ok_or_else(|| "unknown request".to_string())?;
+    scope.check(&request, now)?;
+    Ok(request)
+}
+
+/// Compares tokens in time independent of where they differ.
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Returns the socket path of the session run by process `pid`.
+fn socket_path(pid: u32) -> Result<PathBuf, CliError> {
+    Ok(storage::vault_dir()?.join(format!("plugin-{}.sock", pid)))
+}
+
+/// A session served to a plugin while it runs. Dropping it stops serving
+/// and removes the socket, which ends the token.
+pub struct PluginSession {
+    path: PathBuf,
+    token: Zeroizing<String>,
+    stop: Arc<AtomicBool>,
+    server: Option<JoinHandle<()>>,
+}
+
+impl PluginSession {
+    /// Starts serving the secrets `scope` allows, read with `key`.
+    pub fn start(key: SecureKey, scope: Scope) -> Result<Self, CliError> {
+        let pid = std::process::id();
+        let path = socket_path(pid)?;
+        if path.exists() {
+            // Left behind by a session that did not end cleanly
+            fs::remove_file(&path)?;
+        }
+        let listener = daemon::bind_private(&path)?;
+
+        let secret = daemon::encode_hex(&crypto::generate_key()[..]);
+        let stop = Arc::new(AtomicBool::new(false));
+        let server = {
+            let (secret, stop) = (secret.clone(), Arc::clone(&stop));
+            std::thread::spawn(move || serve(listener, &key, &secret, &scope, &stop))
+        };
+        tracing::debug!(path = %path.display(), "serving plugin session");
+
+        Ok(Self {
+            path,
+            token: Zeroizing::new(format!("{}:{}", pid, *secret)),
+            stop,
+            server: Some(server),
+        })
+    }
+
+    /// Returns the token to pass in `VX_SESSION_TOKEN`.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+}
+
+impl Drop for PluginSession {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Wake the server from accept so it sees the flag
+        let _ = UnixStream::connect(&self.path);
+        if let Some(server) = self.server.take() {
+            let _ = server.join();
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Answers requests on `listener` until `stop` is set.
+fn serve(listener: UnixListener, key: &SecureKey, token: &str, scope: &Scope, stop: &AtomicBool) {
+    let uid = daemon::current_uid();
+    for stream in listener.incoming() {
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        let Ok(stream) = stream else { continue };
+        let _ = stream.set_read_timeout(Some(daemon::IO_TIMEOUT));
+        let _ = stream.set_write_timeout(Some(daemon::IO_TIMEOUT));
+
+        if daemon::peer_uid(&stream).ok() != Some(uid) {
+            let _ = (&stream).write_all(b"ERR permission denied\n");
+            continue;
+        }
+        let Ok(line) = daemon::read_line_limited(&stream, MAX_LINE) else {
+            continue;
+        };
+
+        let response = match authorize(&line, token, scope, current_timestamp()) {
+            Ok(request) => match handle(&request, key, scope) {
+                Ok(answer) if answer.is_empty() => Zeroizing::new("OK\n".to_string()),
+                Ok(answer) => Zeroizing::new(format!("OK {}\n", *answer)),
+                Err(e) => Zeroizing::new(format!("ERR {}\n", e)),
+            },
+            Err(reason) => {
+                tracing::debug!(reason = %reason, "refused plugin request");
+                Zeroizing::new(format!("ERR {}\n", reason))
+            }
+        };
+        let _ = (&stream).write_all(response.as_bytes());
+    }
+}
+
+/// Serves an authorized request from the vault as it is on disk.
+fn handle(request: &Request, key: &SecureKey, scope: &Scope) -> Result<Zeroizing<String>, CliError> {
+    let mut vault = storage::open_vault_with_data_key(key)
+        .ok_or_else(|| CliError::Generic("the session's key no longer opens the vault".to_string()))?;
+    let project = request.project();
+    vault.unseal_project(project, key).map_err(|e| match e {
+        VaultError::ProjectNotFound(name) => CliError::ProjectNotFound(name),
+        e => CliError::Vault(e),
+    })?;
+    if vault.is_locked(project) {
+        return Err(VaultError::ProjectLocked(project.to_string()).into());
+    }
+
+    match request {
+        Request::Get { env, name, .. } => {
+            let value = read_in_scope(&mut vault, project, env.as_deref(), name, key, scope)?;
+            let name = qualified_key(env.as_deref(), name);
+            storage::record_access(&mut vault, Operation::Get, Some(project), Some(&name), key);
+            Ok(daemon::encode_hex(&value))
+        }
+        Request::List { env, .. } => {
+            let mut keys: Vec<&str> = vault.projects[project]
+                .secrets_in(env.as_deref())?
+                .keys()
+                .map(String::as_str)
+                .collect();
+            keys.sort();
+            Ok(Zeroizing::new(keys.join(" ")))
+        }
+        Request::Set {
+            env, name, value, ..
+        } => {
+            let ttl = config::get().default_ttl;
+            vault.add_secret_in(project, env.as_deref(), name, value, key, ttl)?;
+            let name = qualified_key(env.as_deref(), name);
+            vault.record(Operation::Add, Some(project), Some(&name));
+            storage::save_vault(&mut vault, key)?;
+            Ok(Zeroizing::new(String::new()))
+        }
+    }
+}
+
+/// Reads a secret like `storage::read_secret_without_prompt`, refusing a
+/// template that refers to a project out of scope.
+fn read_in_scope(
+    vault: &mut Vault,
+    project: &str,
+    env: Option<&str>,
+    name: &str,
+    key: &SecureKey,
+    scope: &Scope,
+) -> Result<SecretBytes, CliError> {
+    loop {
+        match vault.get_secret_in(project, env, name, key) {
+            Err(VaultError::ProjectSealed(other)) if scope.projects.contains(&other) => {
+                vault.unseal_project(&other, key)?
+            }
+            Err(VaultError::ProjectSealed(other)) => {
+                return Err(CliError::Generic(format!(
+                    "'{}' refers to project '{}', which is not in the plugin's session",
+                    name, other
+                )))
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+/// The end of a plugin session used by the `vx` commands a plugin runs.
+pub struct Client {
+    path: PathBuf,
+    token: Zeroizing<String>,
+}
+
+impl Client {
+    /// Returns the client of the session this command runs under, if any.
+    pub fn from_env() -> Option<Self> {
+        let token = Zeroizing::new(std::env::var(TOKEN_ENV).ok()?);
+        let (pid, secret) = token.split_once(':')?;
+        Some(Self {
+            path: socket_path(pid.parse().ok()?).ok()?,
+            token: Zeroizing::new(secret.to_string()),
+        })
+    }
+
+    /// Reads the secret `key` of `project`.
+    pub fn get(&self, project: &str, env: Option<&str>, key: &str) -> Result<SecretBytes, CliError> {
+        let answer = self.send(&format!("GET {} {}", project, qualified_key(env, key)))?;
+        let value = daemon::decode_hex(&answer)
+            .ok_or_else(|| CliError::Generic("Malformed answer from the plugin session".to_string()))?;
+        Ok(SecretBytes::new(value.to_vec()))
+    }
+
+    /// Lists the keys of `project`, or of one of its environments.
+    pub fn list(&self, project: &str, env: Option<&str>) -> Result<Vec<String>, CliError> {
+        let request = match env {
+            Some(env) => format!("LIST {} {}", project, env),
+            None => format!("LIST {}", project),
+        };
+        Ok(self.send(&request)?.split_whitespace().map(str::to_string).collect())
+    }
+
+    /// Adds or replaces the secret `key` of `project`.
+    pub fn set(&self, project: &str, env: Option<&str>, key: &str, value: &[u8]) -> Result<(), CliError> {
+        let request = Zeroizing::new(format!(
+            "SET {} {} {}",
+            project,
+            qualified_key(env, key),
+            *daemon::encode_hex(value)
+        ));
+        self.send(&request).map(drop)
+    }
+
+    /// Sends `request` and returns the answer after `OK`.
+    fn send(&self, request: &str) -> Result<Zeroizing<String>, CliError> {
+        let ended = || CliError::Generic("The plugin's session has ended".to_string());
+        let stream = UnixStream::connect(&self.path).map_err(|_| ended())?;
+        if daemon::peer_uid(&stream).ok() != Some(daemon::current_uid()) {
+            return Err(ended());
+        }
+        stream.set_read_timeout(Some(daemon::IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(daemon::IO_TIMEOUT))?;
+
+        let line = Zeroizing::new(format!("{} {}\n", *self.token, request));
+        (&stream).write_all(line.as_bytes())?;
+        let response = daemon::read_line_limited(&stream, MAX_LINE)?;
+
+        match response.strip_prefix("OK") {
+            Some(rest) => Ok(Zeroizing::new(rest.trim_start().to_string())),
+            None => Err(CliError::Generic(format!(
+                "Refused by the plugin session: {}",
+                response.strip_prefix("ERR ").unwrap_or(&response)
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    fn scope(write: bool) -> Scope {
+        Scope {
+            projects: vec!["app".to_string()],
+            write,
+            expires_at: 1_000,
+        }
+    }
+
+    #[test]
+    fn test_requests_are_limited_to_scope() {
+        let read_only = scope(false);
+        assert_eq!(
+            authorize(&format!("{} GET app prod/DB_URL", TOKEN), TOKEN, &read_only, 10),
+            Ok(Request::Get {
+                project: "app".to_string(),
+                env: Some("prod".to_string()),
+                name: "DB_URL".to_string(),
+            })
+        );
+        assert!(authorize(&format!("{} LIST app", TOKEN), TOKEN, &read_only, 10).is_ok());
+
+        // Other projects, writes and an expired session are refused
+        let other = authorize(&format!("{} GET other KEY", TOKEN), TOKEN, &read_only, 10);
+        assert!(other.unwrap_err().contains("not in the plugin's session"));
+        let set = format!("{} SET app KEY 6869", TOKEN);
+        assert!(authorize(&set, TOKEN, &read_only, 10)
+            .unwrap_err()
+            .contains("read-only"));
+        assert!(authorize(&set, TOKEN, &scope(true), 10).is_ok());
+        assert!(authorize(&set, TOKEN, &scope(true), 1_000)
+            .unwrap_err()
+            .contains("expired"));
+    }
+
+    #[test]
+    fn test_wrong_token_is_rejected() {
+        let scope = scope(true);
+        for line in [
+            "fedcba9876543210 GET app KEY".to_string(),
+            format!("{}0 GET app KEY", TOKEN),
+            "GET app KEY".to_string(),
+            String::new(),
+        ] {
+            assert_eq!(
+                authorize(&line, TOKEN, &scope, 10),
+                Err("invalid session token".to_string())
+            );
+        }
+        assert_eq!(
+            authorize(&format!("{} KEY", TOKEN), TOKEN, &scope, 10),
+            Err("unknown request".to_string())
+        );
+        assert_eq!(
+            authorize(&format!("{} SET app KEY not-hex", TOKEN), TOKEN, &scope, 10),
+            Err("unknown request".to_string())
+        );
+    }
+}
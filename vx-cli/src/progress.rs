@@ -0,0 +1,95 @@
+//! Progress indication for slow operations.
+//!
+//! Key derivation takes seconds by design, and decrypting a large vault can
+//! take as long again; a spinner on stderr shows that `vx` is working rather
+//! than hung. Nothing is drawn when stderr is not a terminal, or when the
+//! operation finishes before `DELAY`, so fast paths (the daemon, scripts)
+//! print nothing extra.
+
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How long an operation runs before the spinner appears
+const DELAY: Duration = Duration::from_millis(150);
+
+/// Time between spinner frames
+const FRAME_INTERVAL: Duration = Duration::from_millis(80);
+
+const FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// A spinner with a message, drawn on stderr until dropped.
+pub struct Spinner {
+    message: Arc<Mutex<String>>,
+    done: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    /// Starts a spinner showing `message`.
+    pub fn start(message: &str) -> Self {
+        let message = Arc::new(Mutex::new(message.to_string()));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let thread = io::stderr().is_terminal().then(|| {
+            let message = Arc::clone(&message);
+            let done = Arc::clone(&done);
+            thread::spawn(move || spin(&message, &done))
+        });
+
+        Self {
+            message,
+            done,
+            thread,
+        }
+    }
+
+    /// Replaces the message shown next to the spinner.
+    pub fn set_message(&self, message: &str) {
+        if let Ok(mut current) = self.message.lock() {
+            *current = message.to_string();
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Draws frames until `done` is set, then erases the line if anything was
+/// drawn.
+fn spin(message: &Mutex<String>, done: &AtomicBool) {
+    thread::park_timeout(DELAY);
+
+    let mut drawn = false;
+    for frame in FRAMES.iter().cycle() {
+        if done.load(Ordering::Relaxed) {
+            break;
+        }
+        if let Ok(message) = message.lock() {
+            eprint!("\r\x1b[2K{} {}", frame, message);
+            let _ = io::stderr().flush();
+            drawn = true;
+        }
+        thread::park_timeout(FRAME_INTERVAL);
+    }
+
+    if drawn {
+        eprint!("\r\x1b[2K");
+        let _ = io::stderr().flush();
+    }
+}
+
+/// Runs `f` with a spinner showing `message`.
+pub fn with_spinner<T>(message: &str, f: impl FnOnce() -> T) -> T {
+    let _spinner = Spinner::start(message);
+    f()
+}
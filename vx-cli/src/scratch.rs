@@ -0,0 +1,90 @@
+//! Short-lived plaintext files for tools that only read configs from disk.
+//!
+//! `vx kubectl` and `vx wg` hand decrypted configs to other programs by
+//! path. A `ScratchFile` lives in a directory only the user can enter,
+//! on tmpfs where available, and is wiped and deleted when dropped.
+//!
+//! # Security Notes
+//! - The file is created with 0600 permissions before anything is written
+//! - Its contents are overwritten with zeros before it is removed
+
+use crate::error::CliError;
+use crate::permissions;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Prefix of the scratch directories
+const DIR_PREFIX: &str = "vx-";
+
+/// A decrypted config written to a private temporary file.
+pub struct ScratchFile {
+    path: PathBuf,
+    len: usize,
+    // Removed after the file is wiped
+    _dir: TempDir,
+}
+
+impl ScratchFile {
+    /// Writes `contents` to a new file called `file_name`.
+    pub fn create(file_name: &str, contents: &[u8]) -> Result<Self, CliError> {
+        let dir = private_dir()?;
+        let path = dir.path().join(file_name);
+
+        let mut file = fs::File::create(&path)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let permissions = fs::Permissions::from_mode(0o600);
+            file.set_permissions(permissions)?;
+        }
+
+        file.write_all(contents)?;
+        file.sync_all()?;
+        tracing::debug!(path = %path.display(), "wrote scratch file");
+
+        Ok(Self {
+            path,
+            len: contents.len(),
+            _dir: dir,
+        })
+    }
+
+    /// Returns the path to hand to the other program.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ScratchFile {
+    fn drop(&mut self) {
+        let _ = fs::write(&self.path, vec![0u8; self.len]);
+    }
+}
+
+/// Creates a directory only the user can enter, preferring memory-backed
+/// locations so the contents never reach disk.
+pub fn private_dir() -> Result<TempDir, CliError> {
+    let tmpfs = [
+        std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from),
+        Some(PathBuf::from("/dev/shm")),
+    ];
+    let in_memory = tmpfs
+        .into_iter()
+        .flatten()
+        .filter(|dir| dir.is_dir())
+        .find_map(|dir| {
+            tempfile::Builder::new()
+                .prefix(DIR_PREFIX)
+                .tempdir_in(dir)
+                .ok()
+        });
+    let dir = match in_memory {
+        Some(dir) => dir,
+        None => tempfile::Builder::new().prefix(DIR_PREFIX).tempdir()?,
+    };
+    permissions::restrict_dir(dir.path())?;
+    Ok(dir)
+}
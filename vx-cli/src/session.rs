@@ -1,9 +1,23 @@
 //! Session management and password caching.
+//!
+//! The encrypted cache is kept by a `SessionStore`: on Linux the kernel
+//! keyring, which expires it on its own, and elsewhere (or where keyrings
+//! are unavailable) a file in the temporary directory.
 
 use crate::error::CliError;
+use crate::permissions;
 use std::fs;
 use std::io::Write;
-use vx_core::crypto::{self, KEY_SIZE};
+use std::path::PathBuf;
+use vx_core::crypto::{self, SecretBytes, KEY_SIZE};
+use vx_core::ttl;
+use zeroize::Zeroizing;
+
+/// Marks the cache format whose plaintext starts with the session limits
+const CACHE_MAGIC: &[u8; 4] = b"VXS2";
+
+/// Size of the magic and limit fields preceding the password
+const METADATA_SIZE: usize = 4 + 3 * 8;
 
 /// Gets the session identifier for password caching.
 ///
@@ -49,15 +63,106 @@ fn get_session_id() -> u32 {
     }
 }
 
-/// Returns the path to the password cache file.
-fn password_cache_path() -> Result<std::path::PathBuf, CliError> {
-    let temp_dir = std::env::temp_dir();
-    let session_id = get_session_id();
-    Ok(temp_dir.join(format!("vaultx_session_{}.cache", session_id)))
+/// Where the encrypted password cache is kept.
+trait SessionStore {
+    /// Reads the cache, if there is one.
+    fn load(&self) -> Result<Option<Zeroizing<Vec<u8>>>, CliError>;
+
+    /// Replaces the cache. Stores that can expire entries on their own drop
+    /// it after `lifetime` seconds.
+    fn save(&self, data: &[u8], lifetime: Option<u64>) -> Result<(), CliError>;
+
+    /// Removes the cache, if there is one.
+    fn remove(&self) -> Result<(), CliError>;
+}
+
+/// Cache file in the temporary directory, readable only by the user.
+struct FileStore {
+    path: PathBuf,
+}
+
+impl SessionStore for FileStore {
+    fn load(&self) -> Result<Option<Zeroizing<Vec<u8>>>, CliError> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(Zeroizing::new(fs::read(&self.path)?)))
+    }
+
+    fn save(&self, data: &[u8], _lifetime: Option<u64>) -> Result<(), CliError> {
+        // Write with restricted permissions
+        let mut file = fs::File::create(&self.path)?;
+        permissions::restrict_file(&self.path)?;
+
+        file.write_all(data)?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    fn remove(&self) -> Result<(), CliError> {
+        if self.path.exists() {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Key in the user's kernel keyring, held in kernel memory and discarded
+/// by the kernel once its lifetime is over.
+#[cfg(target_os = "linux")]
+struct KeyringStore {
+    description: String,
+}
+
+#[cfg(target_os = "linux")]
+impl SessionStore for KeyringStore {
+    fn load(&self) -> Result<Option<Zeroizing<Vec<u8>>>, CliError> {
+        Ok(crate::keyring::read(&self.description)?)
+    }
+
+    fn save(&self, data: &[u8], lifetime: Option<u64>) -> Result<(), CliError> {
+        Ok(crate::keyring::add(&self.description, data, lifetime)?)
+    }
+
+    fn remove(&self) -> Result<(), CliError> {
+        Ok(crate::keyring::remove(&self.description)?)
+    }
+}
+
+/// Returns the store for the current session's cache.
+fn session_store() -> Box<dyn SessionStore> {
+    let name = format!("vaultx_session_{}", get_session_id());
+
+    #[cfg(target_os = "linux")]
+    if crate::keyring::is_available() {
+        return Box::new(KeyringStore { description: name });
+    }
+
+    Box::new(FileStore {
+        path: cache_file_path(&name),
+    })
+}
+
+/// Returns the path of the file the cache `name` is kept in when the
+/// keyring is not used.
+fn cache_file_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{}.cache", name))
+}
+
+/// Returns the current session's cache file, if the cache is kept in a
+/// file and there is one.
+pub fn cache_file() -> Option<PathBuf> {
+    #[cfg(target_os = "linux")]
+    if crate::keyring::is_available() {
+        return None;
+    }
+
+    let path = cache_file_path(&format!("vaultx_session_{}", get_session_id()));
+    path.exists().then_some(path)
 }
 
 /// Derives a session-specific encryption key.
-fn derive_session_key() -> Result<[u8; KEY_SIZE], CliError> {
+fn derive_session_key() -> Result<Zeroizing<[u8; KEY_SIZE]>, CliError> {
     let session_id = get_session_id();
     let salt_input = format!("vaultx_session_{}_key", session_id);
 
@@ -68,57 +173,86 @@ fn derive_session_key() -> Result<[u8; KEY_SIZE], CliError> {
         salt[i] = sid_bytes[i % sid_bytes.len()].wrapping_add(i as u8);
     }
 
-    crypto::derive_key(salt_input.as_bytes(), &salt)
-        .map_err(CliError::Crypto)
+    crate::progress::with_spinner("Opening session...", || {
+        crypto::derive_key(salt_input.as_bytes(), &salt)
+    })
+    .map_err(CliError::Crypto)
 }
 
-/// Caches the password encrypted with a session key.
-pub fn cache_password(password: &[u8]) -> Result<(), CliError> {
-    let cache_path = password_cache_path()?;
+/// Limits on how long a cached password may be used, stored inside the
+/// encrypted cache file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionLimits {
+    /// Unix time after which the cache is refused
+    pub expires_at: Option<u64>,
+    /// Seconds without use after which the cache is refused
+    pub idle_timeout: Option<u64>,
+}
+
+/// Caches the password encrypted with a session key, to be used until
+/// `limits` are exceeded.
+pub fn cache_password(password: &[u8], limits: SessionLimits) -> Result<(), CliError> {
+    let store = session_store();
 
     // Derive session-specific encryption key
     let session_key = derive_session_key()?;
 
+    write_cache(&*store, &session_key, password, limits, ttl::current_timestamp())
+}
+
+/// Encrypts the password with its limits and the time it was last used,
+/// and saves it to `store`.
+fn write_cache(
+    store: &dyn SessionStore,
+    session_key: &[u8; KEY_SIZE],
+    password: &[u8],
+    limits: SessionLimits,
+    last_used: u64,
+) -> Result<(), CliError> {
+    // Plaintext: magic + expiry + idle timeout + last use (0 = unset) + password
+    let mut plaintext = Zeroizing::new(Vec::with_capacity(METADATA_SIZE + password.len()));
+    plaintext.extend_from_slice(CACHE_MAGIC);
+    plaintext.extend_from_slice(&limits.expires_at.unwrap_or(0).to_le_bytes());
+    plaintext.extend_from_slice(&limits.idle_timeout.unwrap_or(0).to_le_bytes());
+    plaintext.extend_from_slice(&last_used.to_le_bytes());
+    plaintext.extend_from_slice(password);
+
     // Encrypt password
-    let encrypted = crypto::encrypt(password, &session_key)
+    let encrypted = crypto::encrypt(&plaintext, session_key)
         .map_err(CliError::Crypto)?;
 
-    // Build cache file: session_key + nonce + ciphertext
-    let mut cache_data = Vec::new();
-    cache_data.extend_from_slice(&session_key);
+    // Build cache: session_key + nonce + ciphertext
+    let mut cache_data = Zeroizing::new(Vec::new());
+    cache_data.extend_from_slice(session_key);
     cache_data.extend_from_slice(&encrypted.nonce);
     cache_data.extend_from_slice(&encrypted.ciphertext);
 
-    // Write with restricted permissions
-    let mut file = fs::File::create(&cache_path)?;
+    // Let the store drop the cache when the first limit is reached
+    let lifetime = [
+        limits.expires_at.map(|at| at.saturating_sub(last_used)),
+        limits.idle_timeout,
+    ]
+    .into_iter()
+    .flatten()
+    .min();
 
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let permissions = fs::Permissions::from_mode(0o600);
-        file.set_permissions(permissions)?;
-    }
-
-    file.write_all(&cache_data)?;
-    file.sync_all()?;
-
-    Ok(())
+    store.save(&cache_data, lifetime)
 }
 
 /// Gets the cached password if available and valid.
-pub fn get_cached_password() -> Result<Option<Vec<u8>>, CliError> {
-    let cache_path = password_cache_path()?;
+///
+/// The cache is removed once its expiry has passed or it has gone unused
+/// for longer than its idle timeout; otherwise its last use is updated.
+pub fn get_cached_password() -> Result<Option<SecretBytes>, CliError> {
+    let store = session_store();
 
-    if !cache_path.exists() {
+    let Some(data) = store.load()? else {
         return Ok(None);
-    }
-
-    // Read cache file
-    let data = fs::read(&cache_path)?;
+    };
 
     if data.len() < KEY_SIZE + 12 {
-        // Invalid cache file, remove it
-        let _ = fs::remove_file(&cache_path);
+        // Invalid cache, remove it
+        let _ = store.remove();
         return Ok(None);
     }
 
@@ -126,28 +260,57 @@ pub fn get_cached_password() -> Result<Option<Vec<u8>>, CliError> {
 
     let nonce: [u8; 12] = data[KEY_SIZE..KEY_SIZE + 12]
         .try_into()
-        .map_err(|_| CliError::Generic("Invalid cache file".to_string()))?;
+        .map_err(|_| CliError::Generic("Invalid session cache".to_string()))?;
 
     let ciphertext = data[KEY_SIZE + 12..].to_vec();
 
     let encrypted = vx_core::crypto::EncryptedData { ciphertext, nonce };
 
-    // Try to decrypt
-    match crypto::decrypt(&encrypted, &session_key) {
-        Ok(password) => Ok(Some(password)),
-        Err(_) => {
-            // Cache is corrupted or from different session (key mismatch)
-            let _ = fs::remove_file(&cache_path);
-            Ok(None)
+    // Try to decrypt; caches from different sessions (key mismatch) or
+    // older versions without limits are removed
+    let plaintext = match crypto::decrypt(&encrypted, &session_key) {
+        Ok(plaintext) if plaintext.expose().starts_with(CACHE_MAGIC) => plaintext,
+        _ => {
+            let _ = store.remove();
+            return Ok(None);
         }
+    };
+    let plaintext = plaintext.expose();
+    if plaintext.len() < METADATA_SIZE {
+        let _ = store.remove();
+        return Ok(None);
+    }
+
+    let field = |index: usize| {
+        let start = CACHE_MAGIC.len() + index * 8;
+        u64::from_le_bytes(plaintext[start..start + 8].try_into().unwrap())
+    };
+    let limits = SessionLimits {
+        expires_at: Some(field(0)).filter(|&at| at != 0),
+        idle_timeout: Some(field(1)).filter(|&timeout| timeout != 0),
+    };
+    let last_used = field(2);
+    let password = &plaintext[METADATA_SIZE..];
+
+    let now = ttl::current_timestamp();
+    let expired = limits.expires_at.is_some_and(|at| now >= at);
+    let idle = limits
+        .idle_timeout
+        .is_some_and(|timeout| now.saturating_sub(last_used) >= timeout);
+    if expired || idle {
+        let _ = store.remove();
+        return Ok(None);
     }
+
+    // Only the idle timeout needs the last use
+    if limits.idle_timeout.is_some() {
+        write_cache(&*store, &session_key, password, limits, now)?;
+    }
+
+    Ok(Some(SecretBytes::new(password.to_vec())))
 }
 
 /// Clears the cached password.
 pub fn clear_cached_password() -> Result<(), CliError> {
-    let cache_path = password_cache_path()?;
-    if cache_path.exists() {
-        fs::remove_file(&cache_path)?;
-    }
-    Ok(())
+    session_store().remove()
 }
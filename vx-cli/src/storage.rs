@@ -2,12 +2,22 @@
 //!
 //! Handles reading and writing the vault file with atomic operations.
 
+use crate::config;
 use crate::error::CliError;
+use crate::hooks::{self, Event, Subject};
+use crate::permissions;
+use crate::progress::{self, Spinner};
 use std::fs;
 use std::io::Write;
-use std::path::PathBuf;
-use vx_core::crypto::{derive_key, KEY_SIZE, SALT_SIZE};
-use vx_core::{vault, Vault};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use vx_core::backend::{Blocking, VaultBackend};
+use vx_core::crypto::{KdfParams, KEY_SIZE};
+use vx_core::trash;
+use vx_core::ttl::{self, current_timestamp};
+use vx_core::{
+    audit, vault, EmergencyRequest, ExpiringItem, Operation, Provenance, RequestStatus, SecretBytes, SecureKey, Vault, VaultError,
+};
 
 /// Default vault directory name
 const VAULT_DIR: &str = ".vaultx";
@@ -15,19 +25,66 @@ const VAULT_DIR: &str = ".vaultx";
 /// Default vault file name
 const VAULT_FILE: &str = "vault.vx";
 
-/// Header size (magic + version + reserved)
-const HEADER_SIZE: usize = 16;
+/// Directory (inside the vault directory) holding encrypted attachment blobs
+const ATTACHMENTS_DIR: &str = "attachments";
 
-/// Returns the path to the vault directory.
-pub fn vault_dir() -> Result<PathBuf, CliError> {
-    let home = dirs::home_dir().ok_or_else(|| {
+/// Directory (inside the vault directory) holding named vault snapshots
+const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// Directory (inside the vault directory) holding the project shards of a
+/// sharded vault
+const SHARDS_DIR: &str = "projects";
+
+/// File extension of snapshots
+const SNAPSHOT_EXTENSION: &str = "vx";
+
+/// Directory (inside the vault directory) holding emergency access requests
+const EMERGENCY_DIR: &str = "emergency";
+
+/// File extension of emergency access requests
+const REQUEST_EXTENSION: &str = "request";
+
+/// Suffix of the journals a new vault file is written to before it replaces
+/// the old one, followed by the writer's pid and a random tag
+const JOURNAL_SUFFIX: &str = ".journal";
+
+/// Suffix of the lock file held while the vault file is replaced
+const LOCK_SUFFIX: &str = ".lock";
+
+/// Environment variable naming a keyfile to unlock the vault with instead of
+/// a password
+const KEYFILE_ENV: &str = "VX_KEYFILE";
+
+/// Environment variable naming the vault directory, overriding the config
+const VAULT_PATH_ENV: &str = "VX_VAULT_PATH";
+
+/// Returns the user's home directory.
+pub fn home_dir() -> Result<PathBuf, CliError> {
+    dirs::home_dir().ok_or_else(|| {
         CliError::Io(std::io::Error::new(
             std::io::ErrorKind::NotFound,
             "Could not determine home directory",
         ))
-    })?;
+    })
+}
+
+/// Returns the path to the vault directory.
+///
+/// `VX_VAULT_PATH` takes precedence over the config file's `vault_path`,
+/// so the vault can live on an encrypted volume or in a synced folder;
+/// both default to `~/.vaultx`. A leading `~` in the config is expanded.
+pub fn vault_dir() -> Result<PathBuf, CliError> {
+    if let Some(path) = std::env::var_os(VAULT_PATH_ENV).filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
 
-    Ok(home.join(VAULT_DIR))
+    match &crate::config::get().vault_path {
+        Some(path) => match path.strip_prefix("~") {
+            Ok(rest) => Ok(home_dir()?.join(rest)),
+            Err(_) => Ok(path.clone()),
+        },
+        None => Ok(home_dir()?.join(VAULT_DIR)),
+    }
 }
 
 /// Returns the path to the vault file.
@@ -35,47 +92,342 @@ pub fn vault_path() -> Result<PathBuf, CliError> {
     Ok(vault_dir()?.join(VAULT_FILE))
 }
 
+/// Returns the path to the attachment blob directory.
+pub fn attachments_dir() -> Result<PathBuf, CliError> {
+    Ok(vault_dir()?.join(ATTACHMENTS_DIR))
+}
+
+/// Returns the attachment blob directory of the vault file at `vault_file`,
+/// which need not be this machine's vault.
+pub fn attachments_dir_of(vault_file: &Path) -> PathBuf {
+    vault_file
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(ATTACHMENTS_DIR)
+}
+
+/// Returns the project shard directory of the vault file at `vault_file`,
+/// which need not be this machine's vault.
+pub fn shards_dir_of(vault_file: &Path) -> PathBuf {
+    vault_file
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(SHARDS_DIR)
+}
+
+/// Returns the path of the project shard `id`.
+fn shard_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.{}", id, vault::SHARD_EXTENSION))
+}
+
+/// Reads the shards of a sharded vault's projects from the shard
+/// directory; see `Vault::attach_shards`. Does nothing for a vault kept in
+/// a single file.
+pub fn attach_shards(vault: &mut Vault) -> Result<(), CliError> {
+    attach_shards_of(vault, &vault_path()?)
+}
+
+/// Reads the shards of a vault opened from the file at `vault_file` from
+/// the shard directory next to it.
+pub fn attach_shards_of(vault: &mut Vault, vault_file: &Path) -> Result<(), CliError> {
+    if vault.pending_shards().is_empty() {
+        return Ok(());
+    }
+
+    let dir = shards_dir_of(vault_file);
+    vault.attach_shards(|id| fs::read(shard_path(&dir, id)).ok())?;
+    tracing::debug!(path = %dir.display(), "read project shards");
+    Ok(())
+}
+
+/// Writes the shards of a sharded save that are not on disk yet, then,
+/// once the vault file refers to them, deletes the ones it no longer does.
+/// With `shards` empty, every shard is deleted.
+fn write_shards(dir: &Path, shards: &[vault::Shard]) -> Result<(), CliError> {
+    if shards.is_empty() && !dir.exists() {
+        return Ok(());
+    }
+
+    permissions::create_private_dir(dir)?;
+    for (id, shard) in shards {
+        let path = shard_path(dir, id);
+        // Shards are named after their contents, so an existing one is the
+        // same shard
+        if !path.exists() {
+            write_atomically(&path, shard)?;
+        }
+    }
+    sync_dir(dir)?;
+    Ok(())
+}
+
+/// Deletes the shards the vault file no longer refers to.
+fn remove_unused_shards(dir: &Path, shards: &[vault::Shard]) -> Result<(), CliError> {
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != vault::SHARD_EXTENSION) {
+            continue;
+        }
+        let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        if !shards.iter().any(|(used, _)| used == id) {
+            fs::remove_file(&path)?;
+            tracing::trace!(shard = id, "removed unused shard");
+        }
+    }
+    Ok(())
+}
+
+/// Returns the path to the snapshot directory.
+pub fn snapshots_dir() -> Result<PathBuf, CliError> {
+    Ok(vault_dir()?.join(SNAPSHOTS_DIR))
+}
+
+/// Returns the path of the snapshot `name`.
+pub fn snapshot_path(name: &str) -> Result<PathBuf, CliError> {
+    Ok(snapshots_dir()?.join(format!("{}.{}", name, SNAPSHOT_EXTENSION)))
+}
+
+/// Returns the path of the emergency access request for grant `id`.
+pub fn emergency_request_path(id: u32) -> Result<PathBuf, CliError> {
+    Ok(vault_dir()?
+        .join(EMERGENCY_DIR)
+        .join(format!("{}.{}", id, REQUEST_EXTENSION)))
+}
+
 /// Checks if the vault file exists.
 pub fn vault_exists() -> Result<bool, CliError> {
     Ok(vault_path()?.exists())
 }
 
-/// Extracts the salt from a vault file without decrypting.
-pub fn extract_salt() -> Result<[u8; SALT_SIZE], CliError> {
-    let path = vault_path()?;
-    let data = fs::read(&path)?;
+/// The vault file as a `VaultBackend`: read directly, and replaced through
+/// its journal (see `write_journaled`).
+struct VaultFile {
+    path: PathBuf,
+}
 
-    if data.len() < HEADER_SIZE + SALT_SIZE {
-        return Err(CliError::Vault(vx_core::VaultError::CorruptedVault));
-    }
+impl VaultBackend for VaultFile {
+    type Error = CliError;
 
-    let salt: [u8; SALT_SIZE] = data[HEADER_SIZE..HEADER_SIZE + SALT_SIZE]
-        .try_into()
-        .map_err(|_| CliError::Vault(vx_core::VaultError::CorruptedVault))?;
+    async fn load(&self) -> Result<Vec<u8>, CliError> {
+        let data = fs::read(&self.path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => CliError::VaultNotFound,
+            _ => CliError::Io(e),
+        })?;
+        tracing::debug!(path = %self.path.display(), bytes = data.len(), "read vault file");
+        Ok(data)
+    }
 
-    Ok(salt)
+    async fn save(&self, data: &[u8]) -> Result<(), CliError> {
+        write_journaled(&self.path, data)
+    }
 }
 
-/// Derives the encryption key from password using the vault's salt.
-pub fn derive_vault_key(password: &[u8]) -> Result<[u8; KEY_SIZE], CliError> {
-    let salt = extract_salt()?;
-    derive_key(password, &salt).map_err(CliError::Crypto)
+/// Returns the vault file, driven synchronously like the rest of the CLI.
+fn vault_file() -> Result<Blocking<VaultFile>, CliError> {
+    Ok(Blocking::new(VaultFile {
+        path: vault_path()?,
+    }))
 }
 
 /// Loads the vault from disk.
 pub fn load_vault(password: &[u8]) -> Result<Vault, CliError> {
-    let path = vault_path()?;
+    let data = vault_file()?.load()?;
+    let (vault, key) =
+        progress::with_spinner("Unlocking vault...", || vault::unlock_vault(&data, password))?;
+    let mut vault = with_provenance(vault);
+    attach_shards(&mut vault)?;
+    vault.unseal_all(&key)?;
+    Ok(vault)
+}
 
-    if !path.exists() {
-        return Err(CliError::VaultNotFound);
+/// Records this machine, user and `vx` version on the secrets the vault's
+/// caller adds or edits; see `Vault::set_provenance`.
+fn with_provenance(mut vault: Vault) -> Vault {
+    let user = ["USER", "USERNAME"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok())
+        .filter(|user| !user.is_empty());
+    vault.set_provenance(Provenance {
+        hostname: sysinfo::System::host_name(),
+        user,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    });
+    vault
+}
+
+/// Unlocks the vault with any credential matching one of its key slots and
+/// returns it with the data key, leaving every project sealed. Callers
+/// unseal only the projects they need.
+///
+/// A vault file in an older format is rewritten in the current one (see
+/// `upgrade_vault_file`).
+///
+/// The key is returned in locked memory.
+pub fn open_vault_with_key(credential: &[u8]) -> Result<(Vault, SecureKey), CliError> {
+    let file = vault_file()?;
+    let data = file.load()?;
+
+    // Unwrap the data key from a matching slot and decrypt the vault; with
+    // several slots, show how far through them the unlock is
+    let spinner = Spinner::start("Unlocking vault...");
+    let (vault, key) = vault::unlock_vault_with_progress(&data, credential, |fraction| {
+        if fraction > 0.0 && fraction < 1.0 {
+            spinner.set_message(&format!("Unlocking vault ({:.0}%)...", fraction * 100.0));
+        }
+    })
+    .map_err(CliError::Vault)?;
+    drop(spinner);
+
+    let mut vault = with_provenance(vault);
+    attach_shards(&mut vault)?;
+    if vault.needs_upgrade() && !vault.read_only {
+        upgrade_vault_file(&mut vault, &key, &file, &data)?;
     }
 
-    let data = fs::read(&path)?;
-    vault::load_vault(&data, password).map_err(CliError::Vault)
+    Ok((vault, SecureKey::new(&key)))
+}
+
+/// Rewrites a vault read from an older format file in the current format,
+/// keeping the original next to it as `vault.vx.v<N>.bak`.
+fn upgrade_vault_file(
+    vault: &mut Vault,
+    key: &[u8; KEY_SIZE],
+    file: &Blocking<VaultFile>,
+    original: &[u8],
+) -> Result<(), CliError> {
+    let mut backup = file.backend().path.as_os_str().to_owned();
+    backup.push(format!(".v{}.bak", vault.version));
+    let backup = PathBuf::from(backup);
+    write_atomically(&backup, original)?;
+    replace_vault_file(file, vault, key)?;
+
+    eprintln!(
+        "Upgraded the vault file from format {} to {}; the original is kept at {}.",
+        vault.version,
+        vault::VAULT_VERSION,
+        backup.display()
+    );
+    vault.version = vault::VAULT_VERSION;
+    Ok(())
+}
+
+/// Loads vault using cached password if available, otherwise prompts.
+pub fn load_vault_auto() -> Result<Vault, CliError> {
+    let (vault, _key) = load_vault_with_key_auto()?;
+    Ok(vault)
+}
+
+/// Loads vault with key using cached password if available.
+pub fn load_vault_with_key_auto() -> Result<(Vault, SecureKey), CliError> {
+    let (mut vault, key) = open_vault_with_key_auto()?;
+    progress::with_spinner("Decrypting vault...", || vault.unseal_all(&key))?;
+    Ok((vault, key))
+}
+
+/// Opens the vault with projects left sealed.
+///
+/// Uses the key held by a running `vx daemon` if there is one, otherwise
+/// unlocks as `open_vault_with_credential` does.
+///
+/// Warns about pending emergency access requests, so the owner can veto
+/// them.
+pub fn open_vault_with_key_auto() -> Result<(Vault, SecureKey), CliError> {
+    let mut opened = match open_vault_from_daemon() {
+        Some(opened) => opened,
+        None => open_vault_with_credential()?,
+    };
+
+    warn_emergency_requests(&mut opened.0, &opened.1);
+    Ok(opened)
 }
 
-/// Loads the vault and returns both the vault and the derived encryption key.
-pub fn load_vault_with_key(password: &[u8]) -> Result<(Vault, [u8; KEY_SIZE]), CliError> {
+/// Prints a warning for each emergency access request the owner has not
+/// vetoed.
+///
+/// A request seen for the first time is recorded on its grant and the vault
+/// saved, so the recipient cannot move its date back afterwards (see
+/// `Vault::note_emergency_request`).
+fn warn_emergency_requests(vault: &mut Vault, key: &[u8; KEY_SIZE]) {
+    let now = current_timestamp();
+    let mut noted = false;
+    for grant in vault.emergency_grants().to_vec() {
+        let Ok(Some(request)) = read_emergency_request(grant.id) else {
+            continue;
+        };
+        let opens = match grant.status(Some(&request), now) {
+            RequestStatus::Waiting { available_at } => ttl::format_until(available_at, now),
+            RequestStatus::Available => "now".to_string(),
+            RequestStatus::NotRequested | RequestStatus::Vetoed => continue,
+        };
+        eprintln!(
+            "⚠️  {} requested emergency access to this vault {}; it opens {}.",
+            grant.label,
+            ttl::format_relative(request.requested_at.max(grant.requested_at.unwrap_or(0)), now),
+            opens
+        );
+        eprintln!("    Refuse it with 'vx emergency veto {}'.", grant.id);
+        noted |= vault.note_emergency_request(grant.id, request.requested_at.min(now));
+    }
+
+    if noted && !vault.read_only {
+        if let Err(e) = save(vault, key, false) {
+            eprintln!("⚠️  The request could not be recorded in the vault: {}", e);
+        }
+    }
+}
+
+/// Opens the vault with projects left sealed, without ever prompting.
+///
+/// Uses a running `vx daemon`, `VX_KEYFILE` or the cached password, and
+/// fails if none of them is available. For commands whose stdin is not a
+/// terminal, such as `vx native-host`.
+pub fn open_vault_without_prompt() -> Result<(Vault, SecureKey), CliError> {
+    if let Some(opened) = open_vault_from_daemon() {
+        return Ok(opened);
+    }
+
+    open_vault_with_stored_credential()?.ok_or_else(|| {
+        CliError::Generic(
+            "The vault is locked; unlock it with 'vx login' or 'vx daemon'".to_string(),
+        )
+    })
+}
+
+/// Opens the vault with the key held by a running `vx daemon`, if any.
+fn open_vault_from_daemon() -> Option<(Vault, SecureKey)> {
+    #[cfg(unix)]
+    if let Some(key) = crate::daemon::request_key() {
+        if let Some(vault) = open_vault_with_data_key(&key) {
+            tracing::debug!("opened vault with the daemon's key");
+            return Some((vault, key));
+        }
+        tracing::debug!("daemon's key does not open the vault");
+    }
+
+    None
+}
+
+/// Decrypts the vault with its data key, skipping key derivation.
+///
+/// Returns `None` if the key does not open the vault or the vault predates
+/// key slots (it must be unlocked with a password once to get one).
+#[cfg(unix)]
+pub fn open_vault_with_data_key(key: &[u8; KEY_SIZE]) -> Option<Vault> {
+    let data = fs::read(vault_path().ok()?).ok()?;
+    let mut vault = vault::open_vault(&data, key).ok()?;
+    attach_shards(&mut vault).ok()?;
+    (!vault.key_slots().is_empty()).then(|| with_provenance(vault))
+}
+
+/// Decrypts the vault with a data key recovered from a paper backup,
+/// leaving projects sealed.
+pub fn open_vault_with_recovered_key(key: &[u8; KEY_SIZE]) -> Result<Vault, CliError> {
     let path = vault_path()?;
 
     if !path.exists() {
@@ -83,63 +435,172 @@ pub fn load_vault_with_key(password: &[u8]) -> Result<(Vault, [u8; KEY_SIZE]), C
     }
 
     let data = fs::read(&path)?;
+    let mut vault = vault::open_vault(&data, key).map_err(|e| match e {
+        VaultError::AuthenticationFailed => CliError::Generic(format!(
+            "The recovery phrase does not unlock the vault at {}",
+            path.display()
+        )),
+        e => CliError::Vault(e),
+    })?;
+    attach_shards(&mut vault)?;
+    Ok(with_provenance(vault))
+}
 
-    // Extract salt from file
-    if data.len() < HEADER_SIZE + SALT_SIZE {
-        return Err(CliError::Vault(vx_core::VaultError::CorruptedVault));
+/// Opens the vault with projects left sealed, without consulting the
+/// daemon.
+///
+/// Unlocks with the keyfile named by `VX_KEYFILE` if set, otherwise with
+/// the cached password if available, otherwise with Touch ID on macOS if
+/// enabled, otherwise prompts.
+pub fn open_vault_with_credential() -> Result<(Vault, SecureKey), CliError> {
+    if let Some(opened) = open_vault_with_stored_credential()? {
+        return Ok(opened);
     }
 
-    let salt: [u8; SALT_SIZE] = data[HEADER_SIZE..HEADER_SIZE + SALT_SIZE]
-        .try_into()
-        .map_err(|_| CliError::Vault(vx_core::VaultError::CorruptedVault))?;
+    #[cfg(target_os = "macos")]
+    if let Some(opened) = open_vault_with_touch_id() {
+        return Ok(opened);
+    }
 
-    // Derive key
-    let key = derive_key(password, &salt).map_err(CliError::Crypto)?;
+    // Fall back to prompting, unless the file could not be opened anyway
+    if let Ok(data) = fs::read(vault_path()?) {
+        vault::verify_file(&data)?;
+    }
+    let password = crate::input::read_master_password("Enter master password: ")?;
+    let opened = open_vault_with_key(password.as_bytes())?;
 
-    // Load vault
-    let vault = vault::load_vault(&data, password).map_err(CliError::Vault)?;
+    #[cfg(target_os = "macos")]
+    let _ = crate::touchid::renew_window();
 
-    Ok((vault, key))
+    Ok(opened)
 }
 
-/// Loads vault using cached password if available, otherwise prompts.
-pub fn load_vault_auto() -> Result<Vault, CliError> {
+/// Opens the vault with the credential kept in the keychain, prompting for
+/// Touch ID. Returns `None` outside the unlock window or if the keychain
+/// does not hand the credential over.
+#[cfg(target_os = "macos")]
+fn open_vault_with_touch_id() -> Option<(Vault, SecureKey)> {
+    use crate::touchid;
+
+    let state = touchid::read_state().ok()??;
+    if !state.is_active(current_timestamp()) {
+        return None;
+    }
+
+    let credential = touchid::load_credential()?;
+    open_vault_with_key(&credential).ok()
+}
+
+/// Opens the vault with the keyfile named by `VX_KEYFILE` or the cached
+/// password. Returns `None` if neither is available.
+fn open_vault_with_stored_credential() -> Result<Option<(Vault, SecureKey)>, CliError> {
     use crate::session;
 
+    if let Some(keyfile) = std::env::var_os(KEYFILE_ENV) {
+        tracing::debug!(path = %keyfile.to_string_lossy(), "unlocking with {}", KEYFILE_ENV);
+        let contents = fs::read(&keyfile)
+            .map_err(|_| CliError::FileNotFound(keyfile.to_string_lossy().into_owned()))?;
+        return open_vault_with_key(&contents).map(Some);
+    }
+
     // Try cached password first
     if let Some(cached_password) = session::get_cached_password()? {
-        match load_vault(&cached_password) {
-            Ok(vault) => return Ok(vault),
-            Err(_) => {
+        match open_vault_with_key(&cached_password) {
+            Ok(result) => {
+                tracing::debug!("opened vault with the cached password");
+                return Ok(Some(result));
+            }
+            Err(e) => {
+                tracing::debug!(error = %e, "cached password failed; clearing it");
                 // Cache is stale, clear it
                 let _ = session::clear_cached_password();
             }
         }
     }
 
-    // Fall back to prompting
-    let password = crate::input::read_password("Enter master password: ")?;
-    load_vault(password.as_bytes())
+    Ok(None)
 }
 
-/// Loads vault with key using cached password if available.
-pub fn load_vault_with_key_auto() -> Result<(Vault, [u8; KEY_SIZE]), CliError> {
-    use crate::session;
+/// Prompts for a project's passphrase and unlocks it, if the project has one
+/// that has not been unlocked yet.
+pub fn unlock_project(vault: &mut Vault, name: &str, key: &[u8; KEY_SIZE]) -> Result<(), CliError> {
+    if !vault.is_locked(name) {
+        return Ok(());
+    }
 
-    // Try cached password first
-    if let Some(cached_password) = session::get_cached_password()? {
-        match load_vault_with_key(&cached_password) {
-            Ok(result) => return Ok(result),
-            Err(_) => {
-                // Cache is stale, clear it
-                let _ = session::clear_cached_password();
-            }
+    let passphrase =
+        crate::input::read_password(&format!("Enter passphrase for project '{}': ", name))?;
+    progress::with_spinner("Unlocking project...", || {
+        vault.unlock_project(name, passphrase.as_bytes(), key)
+    })?;
+    Ok(())
+}
+
+/// Reads a secret, unsealing and unlocking (with a passphrase prompt) the
+/// other projects its aliases and template placeholders lead to.
+pub fn read_secret(
+    vault: &mut Vault,
+    project: &str,
+    env: Option<&str>,
+    name: &str,
+    key: &[u8; KEY_SIZE],
+) -> Result<SecretBytes, CliError> {
+    loop {
+        match vault.get_secret_in(project, env, name, key) {
+            Err(VaultError::ProjectSealed(other)) => vault.unseal_project(&other, key)?,
+            Err(VaultError::ProjectLocked(other)) => unlock_project(vault, &other, key)?,
+            result => return Ok(result?),
+        }
+    }
+}
+
+/// Reads a secret like `read_secret`, but fails on a passphrase-locked
+/// project instead of prompting for its passphrase.
+pub fn read_secret_without_prompt(
+    vault: &mut Vault,
+    project: &str,
+    env: Option<&str>,
+    name: &str,
+    key: &[u8; KEY_SIZE],
+) -> Result<SecretBytes, CliError> {
+    loop {
+        match vault.get_secret_in(project, env, name, key) {
+            Err(VaultError::ProjectSealed(other)) => vault.unseal_project(&other, key)?,
+            result => return Ok(result?),
         }
     }
+}
+
+/// Records a read operation (get, ssh connect) in the access log and saves
+/// it with `save_access_log`.
+///
+/// Read-only exports cannot be written, so nothing is recorded for them.
+pub fn record_access(
+    vault: &mut Vault,
+    operation: Operation,
+    project: Option<&str>,
+    name: Option<&str>,
+    key: &[u8; KEY_SIZE],
+) {
+    if vault.read_only {
+        return;
+    }
+
+    vault.record(operation, project, name);
+    save_access_log(vault, key);
+}
 
-    // Fall back to prompting
-    let password = crate::input::read_password("Enter master password: ")?;
-    load_vault_with_key(password.as_bytes())
+/// Saves a vault whose only changes are reads recorded in its access log.
+///
+/// Recording is best effort and never keeps the value from the caller: if
+/// the save fails, e.g. because another process saved in between, a
+/// warning is printed and the read goes unrecorded. The `pre-save` hook is
+/// not run, as nothing the user changed is being saved.
+pub fn save_access_log(vault: &mut Vault, key: &[u8; KEY_SIZE]) {
+    if let Err(e) = save(vault, key, false) {
+        tracing::debug!(error = %e, "could not record access");
+        eprintln!("⚠️  This access could not be recorded in the access log: {}", e);
+    }
 }
 
 /// Saves the vault to disk using atomic write.
@@ -147,54 +608,677 @@ pub fn load_vault_with_key_auto() -> Result<(Vault, [u8; KEY_SIZE]), CliError> {
 /// # Security
 /// Uses write-to-temp-then-rename pattern to prevent corruption
 /// from interrupted writes.
-/// 
-/// For existing vaults, preserves the original salt to ensure
-/// consistent encryption key derivation.
-pub fn save_vault(vault: &Vault, password: &[u8]) -> Result<(), CliError> {
-    let path = vault_path()?;
+///
+/// The vault is encrypted under `key`, its data key; key slots travel with
+/// the vault, so no password is needed to save.
+///
+/// # Concurrency
+/// Refuses to overwrite the file if its generation no longer matches the
+/// one the vault was loaded with, i.e. another process saved in between.
+/// On success `vault.generation` is advanced to the value written.
+///
+/// # Cleanup
+/// With `remove_expired_after` set in the config file, secrets of unsealed
+/// projects that expired longer ago than that are removed (and the removal
+/// logged) before saving. Items trashed longer ago than `trash_retention`
+/// are purged, and the attachment blobs of purged projects deleted once the
+/// vault is written.
+///
+/// # Hooks
+/// The `pre-save` hook runs before writing and cancels the save if it
+/// fails; the other hooks run once the vault is written (see `hooks`).
+pub fn save_vault(vault: &mut Vault, key: &[u8; KEY_SIZE]) -> Result<(), CliError> {
+    save(vault, key, true)
+}
+
+/// Saves the vault like `save_vault`, running the `pre-save` hook only if
+/// `pre_save_hook` is set.
+fn save(vault: &mut Vault, key: &[u8; KEY_SIZE], pre_save_hook: bool) -> Result<(), CliError> {
+    let file = vault_file()?;
+    let path = &file.backend().path;
     let dir = vault_dir()?;
 
-    // Ensure directory exists
-    if !dir.exists() {
-        fs::create_dir_all(&dir)?;
+    let config = config::get();
+    let now = current_timestamp();
+    if let Some(grace) = config.remove_expired_after {
+        for secret in vault.remove_expired(now, grace) {
+            let name = crate::commands::qualified_key(secret.env.as_deref(), &secret.key);
+            vault.record(Operation::Remove, Some(&secret.project), Some(&name));
+        }
+    }
+    let retention = config.trash_retention.unwrap_or(trash::DEFAULT_RETENTION);
+    let purged = vault.purge_trash(now.saturating_sub(retention));
+    if let Some(compress) = config.compress {
+        vault.compressed = compress;
     }
+    if let Some(sharded) = config.sharded {
+        vault.sharded = sharded;
+    }
+
+    permissions::create_private_dir(&dir)?;
+
+    // Run before taking the lock, so a hook that itself runs vx cannot wait
+    // on it
+    if pre_save_hook {
+        hooks::run(Event::PreSave, &Subject::default())?;
+    }
+
+    let last_written = replace_vault_file(&file, vault, key)?;
+    tracing::debug!(
+        path = %path.display(),
+        generation = vault.generation,
+        "saved vault"
+    );
+    run_post_save_hooks(vault, last_written, now);
+    vault.mark_saved();
 
-    // Extract existing salt if vault exists, otherwise None for new vault
-    let existing_salt = if path.exists() {
-        Some(extract_salt()?)
+    for entry in &purged {
+        for blob_id in entry.item.blob_ids() {
+            remove_attachment_blob(blob_id)?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the vault file with `vault` at the next generation, unless
+/// another process has saved since `vault` was loaded. Returns when the
+/// file was last written before, if it existed.
+///
+/// The generation check and the write happen under the lock (see
+/// `lock_vault_file`), so of two processes saving at once, one fails with
+/// `GenerationConflict` instead of overwriting the other's changes.
+fn replace_vault_file(
+    file: &Blocking<VaultFile>,
+    vault: &mut Vault,
+    key: &[u8; KEY_SIZE],
+) -> Result<Option<u64>, CliError> {
+    let path = &file.backend().path;
+    let _lock = lock_vault_file(path)?;
+
+    let mut last_written = None;
+    if path.exists() {
+        let existing = file.load()?;
+
+        let found = vault::read_generation(&existing)?;
+        if found != vault.generation {
+            tracing::debug!(
+                expected = vault.generation,
+                found,
+                "vault file changed since it was loaded"
+            );
+            return Err(CliError::Vault(VaultError::GenerationConflict {
+                expected: vault.generation,
+                found,
+            }));
+        }
+        last_written = fs::metadata(path)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|since| since.as_secs());
+    }
+
+    let previous_generation = vault.generation;
+    vault.generation = previous_generation.wrapping_add(1);
+
+    if let Err(e) = write_vault_file(file, vault, key) {
+        vault.generation = previous_generation;
+        return Err(e);
+    }
+    Ok(last_written)
+}
+
+/// Writes the vault file, and for a sharded vault the shards of its changed
+/// projects first, so the file never refers to a shard not yet written.
+fn write_vault_file(
+    file: &Blocking<VaultFile>,
+    vault: &Vault,
+    key: &[u8; KEY_SIZE],
+) -> Result<(), CliError> {
+    let dir = shards_dir_of(&file.backend().path);
+    let shards = if vault.sharded {
+        let (data, shards) = vault::save_vault_sharded(vault, key)?;
+        write_shards(&dir, &shards)?;
+        file.save(&data)?;
+        shards
     } else {
-        None
+        file.save_vault(vault, key)?;
+        Vec::new()
     };
 
-    // Serialize and encrypt, preserving salt if it exists
-    let data = if let Some(salt) = existing_salt {
-        vault::save_vault_with_salt(vault, password, Some(&salt))?
-    } else {
-        vault::save_vault(vault, password)?
+    remove_unused_shards(&dir, &shards)
+}
+
+/// Runs the `post-add` and `post-remove` hooks for the secrets added and
+/// removed since the vault was read, and the `expired-detected` hook for
+/// the secrets that expired between `last_written` and `now`.
+fn run_post_save_hooks(vault: &Vault, last_written: Option<u64>, now: u64) {
+    for entry in vault.unsaved_log() {
+        let event = match entry.operation {
+            Operation::Add => Event::PostAdd,
+            Operation::Remove => Event::PostRemove,
+            _ => continue,
+        };
+        hooks::notify(
+            event,
+            &Subject {
+                project: entry.project.as_deref(),
+                name: entry.name.as_deref(),
+                expires_at: None,
+            },
+        );
+    }
+
+    let Some(since) = last_written.filter(|_| hooks::is_set(Event::ExpiredDetected)) else {
+        return;
+    };
+    for expiration in audit::upcoming_expirations(vault, since, now.saturating_sub(since)) {
+        let (ExpiringItem::Secret(secret) | ExpiringItem::Certificate(secret)) = &expiration.item
+        else {
+            continue;
+        };
+        let name = crate::commands::qualified_key(secret.env.as_deref(), &secret.key);
+        hooks::notify(
+            Event::ExpiredDetected,
+            &Subject {
+                project: Some(&secret.project),
+                name: Some(&name),
+                expires_at: Some(expiration.expires_at),
+            },
+        );
+    }
+}
+
+/// Returns `path` with `suffix` appended to its file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Returns a journal path for the vault file at `path` that no other
+/// writer uses: `vault.vx.journal.<pid>.<random>`.
+fn journal_path(path: &Path) -> PathBuf {
+    let tag: String = vx_core::crypto::generate_salt()[..4]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    with_suffix(
+        path,
+        &format!("{}.{}.{}", JOURNAL_SUFFIX, std::process::id(), tag),
+    )
+}
+
+/// Returns the journals left next to the vault file at `path`, including
+/// one named by an older `vx` without the pid and tag.
+fn find_journals(path: &Path) -> Result<Vec<PathBuf>, CliError> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Ok(Vec::new());
     };
+    let prefix = format!("{}{}", name.to_string_lossy(), JOURNAL_SUFFIX);
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+
+    let mut journals = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(journals),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with(&prefix) {
+            journals.push(entry.path());
+        }
+    }
+    Ok(journals)
+}
+
+/// Takes the lock on replacing the vault file at `path`, waiting for
+/// another `vx` holding it. The lock is advisory and released when the
+/// returned file is dropped.
+fn lock_vault_file(path: &Path) -> Result<fs::File, CliError> {
+    let lock_path = with_suffix(path, LOCK_SUFFIX);
+    let lock = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)?;
+    permissions::restrict_file(&lock_path)?;
 
-    // Atomic write: write to temp file, then rename
+    match lock.try_lock() {
+        Ok(()) => {}
+        Err(fs::TryLockError::WouldBlock) => {
+            eprintln!("Waiting for another vx process to finish saving the vault...");
+            lock.lock()?;
+        }
+        Err(fs::TryLockError::Error(e)) => return Err(e.into()),
+    }
+    tracing::trace!(path = %lock_path.display(), "locked vault file");
+    Ok(lock)
+}
+
+/// Replaces the vault file at `path` with `data` through a journal of its
+/// own, created fresh so no other writer can be sharing it.
+///
+/// The journal is written and synced, then renamed over the vault file,
+/// syncing the directory after each step. A crash at any point leaves the
+/// old vault file or the new one, plus at most a journal that
+/// `recover_interrupted_save` finishes or discards.
+fn write_journaled(path: &Path, data: &[u8]) -> Result<(), CliError> {
+    let journal = journal_path(path);
+    let dir = path.parent().unwrap_or(Path::new("."));
+
+    {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&journal)?;
+        permissions::restrict_file(&journal)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+    }
+    sync_dir(dir)?;
+
+    fs::rename(&journal, path)?;
+    sync_dir(dir)?;
+    tracing::trace!(path = %path.display(), bytes = data.len(), "replaced vault file");
+    Ok(())
+}
+
+/// Flushes a directory's entries to disk, so a rename in it survives a
+/// power loss.
+fn sync_dir(dir: &Path) -> Result<(), CliError> {
+    #[cfg(unix)]
+    fs::File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// Finishes or discards a vault save that a crash or power loss
+/// interrupted, so the vault file is always the last consistent state.
+pub fn recover_interrupted_save() -> Result<(), CliError> {
+    recover_journals(&vault_path()?)
+}
+
+/// Recovers the vault file at `path` from its leftover journals.
+///
+/// The journals are looked at under the lock (see `lock_vault_file`), so
+/// one another process is still writing is never taken for a leftover. Of
+/// those that verify and are newer than the vault file, the newest is
+/// installed; the rest, partly written or outdated, are deleted.
+fn recover_journals(path: &Path) -> Result<(), CliError> {
+    if find_journals(path)?.is_empty() {
+        return Ok(());
+    }
+    let _lock = lock_vault_file(path)?;
+
+    let mut current_generation = fs::read(path)
+        .ok()
+        .filter(|data| vault::verify_file(data).is_ok())
+        .and_then(|data| vault::read_generation(&data).ok());
+    let mut newest = None;
+    let mut discarded = Vec::new();
+    for journal in find_journals(path)? {
+        let pending = fs::read(&journal)?;
+        let complete = vault::verify_file(&pending).is_ok();
+        let generation = vault::read_generation(&pending).ok().filter(|_| complete);
+        let newer = match (generation, current_generation) {
+            (Some(pending), Some(current)) => pending > current,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        tracing::debug!(
+            path = %journal.display(),
+            complete,
+            newer,
+            "found journal of an interrupted save"
+        );
+
+        if newer {
+            current_generation = generation;
+            discarded.extend(newest.replace(journal));
+        } else {
+            discarded.push(journal);
+        }
+    }
+
+    for journal in &discarded {
+        fs::remove_file(journal)?;
+    }
+    if let Some(journal) = newest {
+        fs::rename(&journal, path)?;
+        sync_dir(path.parent().unwrap_or(Path::new(".")))?;
+        eprintln!("Recovered the vault from a save that was interrupted before it finished.");
+    } else if !discarded.is_empty() {
+        eprintln!("Discarded an unfinished vault save; the vault is as it was before it.");
+    }
+    Ok(())
+}
+
+/// Writes `data` to a temporary file next to `path`, then renames it over
+/// `path`. The file is readable by the current user only.
+pub fn write_atomically(path: &Path, data: &[u8]) -> Result<(), CliError> {
     let temp_path = path.with_extension("tmp");
 
     {
         let mut file = fs::File::create(&temp_path)?;
-        file.write_all(&data)?;
+        permissions::restrict_file(&temp_path)?;
+        file.write_all(data)?;
         file.sync_all()?;
     }
 
     // Rename temp to final (atomic on most filesystems)
-    fs::rename(&temp_path, &path)?;
+    fs::rename(&temp_path, path)?;
+    tracing::trace!(path = %path.display(), bytes = data.len(), "replaced file");
+
+    Ok(())
+}
+
+/// Writes an encrypted attachment blob with `write_atomically`.
+pub fn write_attachment_blob(blob_id: &str, blob: &[u8]) -> Result<(), CliError> {
+    let dir = attachments_dir()?;
+
+    permissions::create_private_dir(&dir)?;
+
+    write_atomically(&dir.join(blob_id), blob)
+}
+
+/// Returns the attachment blob ids found in the blob directory.
+pub fn list_attachment_blobs() -> Result<Vec<String>, CliError> {
+    let dir = attachments_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut ids = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        // Skip leftovers of interrupted writes
+        if path.extension().is_some() {
+            continue;
+        }
+        if let Some(id) = path.file_name().and_then(|name| name.to_str()) {
+            ids.push(id.to_string());
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
+
+/// Reads the vault file as last saved, still encrypted. A sharded vault is
+/// returned as a single file with its projects inside, so the copy stands
+/// on its own.
+///
+/// `vault` must be the vault as opened from that file; if another process
+/// has saved since, the file is not returned.
+pub fn read_saved_vault(vault: &Vault, key: &[u8; KEY_SIZE]) -> Result<Vec<u8>, CliError> {
+    let data = vault_file()?.load()?;
+
+    let found = vault::read_generation(&data)?;
+    if found != vault.generation {
+        return Err(CliError::Vault(VaultError::GenerationConflict {
+            expected: vault.generation,
+            found,
+        }));
+    }
+    if vault::read_flags(&data)? & vault::FLAG_SHARDED == 0 {
+        return Ok(data);
+    }
+
+    let mut saved = vault::open_vault(&data, key)?;
+    attach_shards(&mut saved)?;
+    Ok(vault::save_vault_with_key(&saved, key)?)
+}
+
+/// Installs `data` as the vault file, creating the vault directory if
+/// needed. Used to move a vault from another machine; any existing vault
+/// file is replaced.
+pub fn install_vault_file(data: &[u8]) -> Result<(), CliError> {
+    let dir = vault_dir()?;
+    permissions::create_private_dir(&dir)?;
+
+    let file = vault_file()?;
+    let _lock = lock_vault_file(&file.backend().path)?;
+    file.save(data)
+}
+
+/// Copies the vault file, as last saved, to the snapshot `name`. The copy
+/// stays encrypted under the vault's data key.
+///
+/// `vault` must be the vault as opened from that file; if another process
+/// has saved since, the snapshot is not taken.
+pub fn write_snapshot(name: &str, vault: &Vault, key: &[u8; KEY_SIZE]) -> Result<(), CliError> {
+    let data = read_saved_vault(vault, key)?;
+
+    let dir = snapshots_dir()?;
+    permissions::create_private_dir(&dir)?;
+
+    write_atomically(&snapshot_path(name)?, &data)
+}
+
+/// Returns the names of the snapshots with the time each was taken, oldest
+/// first.
+pub fn list_snapshots() -> Result<Vec<(String, u64)>, CliError> {
+    let dir = snapshots_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != SNAPSHOT_EXTENSION) {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let taken = fs::metadata(&path)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        snapshots.push((name.to_string(), taken));
+    }
+
+    snapshots.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(snapshots)
+}
+
+/// Reads the snapshot `name` and decrypts it with the vault's data key,
+/// leaving its projects sealed.
+pub fn open_snapshot(name: &str, key: &[u8; KEY_SIZE]) -> Result<Vault, CliError> {
+    let path = snapshot_path(name)?;
+    if !path.exists() {
+        return Err(CliError::SnapshotNotFound(name.to_string()));
+    }
+
+    let data = fs::read(&path)?;
+    vault::open_vault(&data, key).map_err(|e| {
+        CliError::Generic(format!("Snapshot '{}' cannot be opened: {}", name, e))
+    })
+}
+
+/// Reads the vault file without decrypting it.
+pub fn read_vault_file() -> Result<Vec<u8>, CliError> {
+    vault_file()?.load()
+}
+
+/// Reads the emergency access request for grant `id`, if one was filed.
+pub fn read_emergency_request(id: u32) -> Result<Option<EmergencyRequest>, CliError> {
+    let path = emergency_request_path(id)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let request = serde_json::from_slice(&fs::read(&path)?).map_err(|e| {
+        CliError::Generic(format!("Cannot read {}: {}", path.display(), e))
+    })?;
+    Ok(Some(request))
+}
 
+/// Files an emergency access request next to the vault.
+pub fn write_emergency_request(request: &EmergencyRequest) -> Result<(), CliError> {
+    let path = emergency_request_path(request.grant)?;
+    if let Some(dir) = path.parent() {
+        permissions::create_private_dir(dir)?;
+    }
+
+    let data = serde_json::to_vec_pretty(request)
+        .map_err(|e| CliError::Generic(format!("Cannot write emergency request: {}", e)))?;
+    write_atomically(&path, &data)
+}
+
+/// Deletes the emergency access request for grant `id`. A missing request
+/// is ignored.
+pub fn remove_emergency_request(id: u32) -> Result<(), CliError> {
+    let path = emergency_request_path(id)?;
+    if path.exists() {
+        fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+/// Reads an encrypted attachment blob.
+pub fn read_attachment_blob(blob_id: &str) -> Result<Vec<u8>, CliError> {
+    let path = attachments_dir()?.join(blob_id);
+
+    if !path.exists() {
+        return Err(CliError::FileNotFound(path.display().to_string()));
+    }
+
+    Ok(fs::read(&path)?)
+}
+
+/// Deletes an attachment blob. Missing blobs are ignored.
+pub fn remove_attachment_blob(blob_id: &str) -> Result<(), CliError> {
+    let path = attachments_dir()?.join(blob_id);
+    if path.exists() {
+        fs::remove_file(&path)?;
+    }
     Ok(())
 }
 
-/// Creates a new vault file and returns the vault with its encryption key.
-pub fn create_vault(password: &[u8]) -> Result<(Vault, [u8; KEY_SIZE]), CliError> {
-    let vault = Vault::new();
-    save_vault(&vault, password)?;
+/// Creates a new vault file with a single password slot and returns the
+/// vault with its data key.
+pub fn create_vault(password: &[u8]) -> Result<(Vault, SecureKey), CliError> {
+    create_vault_with_kdf(password, KdfParams::for_this_platform())
+}
 
-    // Now load to get the key (salt was just generated)
-    let key = derive_vault_key(password)?;
+/// Creates a new vault file like `create_vault`, deriving the password
+/// slot's key with `kdf`.
+pub fn create_vault_with_kdf(
+    password: &[u8],
+    kdf: KdfParams,
+) -> Result<(Vault, SecureKey), CliError> {
+    let (mut vault, key) = progress::with_spinner("Creating vault...", || {
+        vault::create_vault_with_kdf(password, kdf)
+    })?;
+    let key = SecureKey::new(&key);
+    save_vault(&mut vault, &key)?;
 
     Ok((vault, key))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Key derivation cheap enough for tests
+    const TEST_KDF: KdfParams = KdfParams {
+        memory_kib: 8,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    /// Returns a vault file at `generation`, as a save would write it.
+    fn vault_at(generation: u32) -> Vec<u8> {
+        let (mut vault, key) = vault::create_vault_with_kdf(b"password", TEST_KDF).unwrap();
+        vault.generation = generation;
+        vault::save_vault_with_key(&vault, &key).unwrap()
+    }
+
+    #[test]
+    fn test_recovers_vault_from_leftover_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(VAULT_FILE);
+        let newest = vault_at(3);
+        fs::write(&path, vault_at(1)).unwrap();
+        fs::write(journal_path(&path), vault_at(2)).unwrap();
+        fs::write(journal_path(&path), &newest).unwrap();
+
+        recover_journals(&path).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), newest);
+        assert!(find_journals(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_discards_outdated_and_partial_journals() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(VAULT_FILE);
+        let current = vault_at(2);
+        fs::write(&path, &current).unwrap();
+
+        // A stale journal from before the last save, under the name older
+        // versions used, and one cut short by a crash
+        fs::write(with_suffix(&path, JOURNAL_SUFFIX), vault_at(1)).unwrap();
+        let partial = vault_at(3);
+        fs::write(journal_path(&path), &partial[..partial.len() - 1]).unwrap();
+
+        recover_journals(&path).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), current);
+        assert!(find_journals(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_writers_use_their_own_journals() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(VAULT_FILE);
+        assert_ne!(journal_path(&path), journal_path(&path));
+
+        let data = vault_at(1);
+        write_journaled(&path, &data).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), data);
+        assert!(find_journals(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_racing_saves_conflict_instead_of_overwriting() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(VAULT_FILE);
+        let (mut vault, key) = vault::create_vault_with_kdf(b"password", TEST_KDF).unwrap();
+        let file = Blocking::new(VaultFile { path: path.clone() });
+        replace_vault_file(&file, &mut vault, &key).unwrap();
+        let data = fs::read(&path).unwrap();
+
+        let start = std::sync::Barrier::new(2);
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let savers: Vec<_> = ["first", "second"]
+                .into_iter()
+                .map(|project| {
+                    let (file, start, data, key) = (&file, &start, &data, &key);
+                    scope.spawn(move || {
+                        let mut vault = vault::open_vault(data, key).unwrap();
+                        vault.init_project(project).unwrap();
+                        start.wait();
+                        replace_vault_file(file, &mut vault, key).map(|_| project)
+                    })
+                })
+                .collect();
+            savers.into_iter().map(|saver| saver.join().unwrap()).collect()
+        });
+
+        let saved: Vec<_> = results.iter().filter_map(|result| result.as_ref().ok()).collect();
+        assert_eq!(saved.len(), 1);
+        assert!(results.iter().any(|result| matches!(
+            result,
+            Err(CliError::Vault(VaultError::GenerationConflict { .. }))
+        )));
+
+        let on_disk = vault::open_vault(&fs::read(&path).unwrap(), &key).unwrap();
+        assert_eq!(on_disk.generation, vault.generation + 1);
+        assert_eq!(on_disk.project_names(), vec![*saved[0]]);
+    }
+}
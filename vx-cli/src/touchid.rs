@@ -0,0 +1,145 @@
+//! Touch ID unlock through the macOS Keychain.
+//!
+//! `vx touchid enable` adds a key slot for a random credential and stores
+//! that credential in the data protection keychain behind biometric access
+//! control. While the unlock window lasts, commands that would prompt for
+//! the master password read the credential instead, which makes macOS ask
+//! for Touch ID. Entering the master password starts a new window.
+//!
+//! # Security Notes
+//! - The keychain item requires the currently enrolled fingerprints, so
+//!   enrolling a new finger invalidates it
+//! - Only the slot id and the end of the window are written next to the
+//!   vault; the credential never touches disk outside the keychain
+//! - Any keychain failure falls back to the password prompt
+
+use crate::error::CliError;
+use crate::storage;
+use security_framework::passwords::{
+    delete_generic_password_options, generic_password, set_generic_password_options,
+    AccessControlOptions, PasswordOptions,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use vx_core::crypto::KEY_SIZE;
+use vx_core::ttl::current_timestamp;
+use zeroize::Zeroizing;
+
+/// Keychain service the credentials are stored under
+const SERVICE: &str = "com.vaultx.touchid";
+
+/// State file name inside the vault directory
+const STATE_FILE: &str = "touchid.json";
+
+/// Status code of a keychain lookup that found nothing
+const ERR_ITEM_NOT_FOUND: i32 = -25300;
+
+/// Non-secret record of an enabled Touch ID unlock.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TouchIdState {
+    /// Key slot the keychain credential unlocks
+    pub slot: u32,
+    /// Length of the unlock window in seconds
+    pub window: u64,
+    /// When Touch ID stops being offered until the password is entered
+    pub expires_at: u64,
+}
+
+impl TouchIdState {
+    /// Returns whether Touch ID may be offered at `now`.
+    pub fn is_active(&self, now: u64) -> bool {
+        now < self.expires_at
+    }
+}
+
+/// Returns the path to the state file.
+fn state_path() -> Result<PathBuf, CliError> {
+    Ok(storage::vault_dir()?.join(STATE_FILE))
+}
+
+/// Reads the state file, if Touch ID is enabled.
+pub fn read_state() -> Result<Option<TouchIdState>, CliError> {
+    let path = state_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let state = serde_json::from_slice(&fs::read(&path)?)
+        .map_err(|e| CliError::Generic(format!("Cannot read {}: {}", path.display(), e)))?;
+    Ok(Some(state))
+}
+
+/// Writes the state file.
+pub fn write_state(state: &TouchIdState) -> Result<(), CliError> {
+    let data = serde_json::to_vec_pretty(state)
+        .map_err(|e| CliError::Generic(format!("Cannot write Touch ID state: {}", e)))?;
+    storage::write_atomically(&state_path()?, &data)
+}
+
+/// Removes the state file, if present.
+pub fn remove_state() -> Result<(), CliError> {
+    let path = state_path()?;
+    if path.exists() {
+        fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+/// Keychain account of the vault: its file path, so every vault gets its
+/// own item.
+fn account() -> Result<String, CliError> {
+    Ok(storage::vault_path()?.display().to_string())
+}
+
+/// Keychain query for this vault's item.
+fn options() -> Result<PasswordOptions, CliError> {
+    let mut options = PasswordOptions::new_generic_password(SERVICE, &account()?);
+    options.use_protected_keychain();
+    Ok(options)
+}
+
+/// Stores `credential` in the keychain, readable only after Touch ID.
+pub fn store_credential(credential: &[u8; KEY_SIZE]) -> Result<(), CliError> {
+    delete_credential()?;
+
+    let mut options = options()?;
+    options.set_access_control_options(AccessControlOptions::BIOMETRY_CURRENT_SET);
+    options.set_label("VaultX Touch ID unlock");
+    set_generic_password_options(credential, options).map_err(|e| {
+        CliError::Generic(format!(
+            "Could not store the unlock key in the keychain: {} (vx needs to be signed \
+             with a keychain access group to use the data protection keychain)",
+            e
+        ))
+    })
+}
+
+/// Reads the credential from the keychain, which prompts for Touch ID.
+///
+/// Returns `None` if there is no item, biometrics are unavailable, or the
+/// prompt was cancelled.
+pub fn load_credential() -> Option<Zeroizing<Vec<u8>>> {
+    generic_password(options().ok()?).ok().map(Zeroizing::new)
+}
+
+/// Deletes the keychain item, if present.
+pub fn delete_credential() -> Result<(), CliError> {
+    match delete_generic_password_options(options()?) {
+        Ok(()) => Ok(()),
+        Err(e) if e.code() == ERR_ITEM_NOT_FOUND => Ok(()),
+        Err(e) => Err(CliError::Generic(format!(
+            "Could not remove the unlock key from the keychain: {}",
+            e
+        ))),
+    }
+}
+
+/// Starts a new unlock window after the master password was entered.
+pub fn renew_window() -> Result<(), CliError> {
+    if let Some(mut state) = read_state()? {
+        state.expires_at = current_timestamp().saturating_add(state.window);
+        write_state(&state)?;
+    }
+    Ok(())
+}
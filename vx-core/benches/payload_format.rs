@@ -0,0 +1,48 @@
+//! Compares JSON and CBOR vault payload encoding.
+//!
+//! Run with `cargo bench -p vx-core`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+use vx_core::vault::{decode_payload, encode_payload, PayloadFormat};
+use vx_core::Vault;
+
+/// Builds a vault with `projects` projects of 50 secrets each.
+fn sample_vault(projects: usize) -> Vault {
+    let key = [0u8; 32];
+    let mut vault = Vault::new();
+
+    for p in 0..projects {
+        let project = format!("project-{}", p);
+        vault.init_project(&project).unwrap();
+        for s in 0..50 {
+            vault
+                .add_secret(&project, &format!("SECRET_{}", s), &[0x42; 64], &key, None)
+                .unwrap();
+        }
+    }
+
+    vault
+}
+
+fn bench_payload(c: &mut Criterion) {
+    let vault = sample_vault(20);
+
+    let mut group = c.benchmark_group("payload");
+    for format in [PayloadFormat::Json, PayloadFormat::Cbor] {
+        let name = format!("{:?}", format);
+        let payload = encode_payload(&vault, format).unwrap();
+        println!("{} payload: {} bytes", name, payload.len());
+
+        group.bench_function(BenchmarkId::new("encode", &name), |b| {
+            b.iter(|| encode_payload(black_box(&vault), format).unwrap())
+        });
+        group.bench_function(BenchmarkId::new("decode", &name), |b| {
+            b.iter(|| decode_payload(black_box(&payload), format).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_payload);
+criterion_main!(benches);
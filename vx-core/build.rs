@@ -0,0 +1,26 @@
+//! Build script for vx-core.
+//!
+//! With the `capi` feature, generates `include/vx_core.h` from the
+//! `extern "C"` functions in `src/capi.rs`.
+
+fn main() {
+    #[cfg(feature = "capi")]
+    generate_header();
+}
+
+#[cfg(feature = "capi")]
+fn generate_header() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is set by cargo");
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+        .expect("cbindgen.toml is valid");
+
+    println!("cargo:rerun-if-changed=src/capi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    cbindgen::Builder::new()
+        .with_src(format!("{}/src/capi.rs", crate_dir))
+        .with_config(config)
+        .generate()
+        .expect("C header generation failed")
+        .write_to_file(format!("{}/include/vx_core.h", crate_dir));
+}
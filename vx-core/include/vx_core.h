@@ -0,0 +1,131 @@
+#ifndef VX_CORE_H
+#define VX_CORE_H
+
+/* Generated by cbindgen from vx-core/src/capi.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Size of an encryption key in bytes
+#define VX_KEY_SIZE 32
+
+// Size of a key derivation salt in bytes
+#define VX_SALT_SIZE 32
+
+// Result of every C API call.
+typedef enum VxStatus {
+  VX_STATUS_OK = 0,
+  // A null pointer, wrong length or invalid UTF-8 was passed
+  VX_STATUS_INVALID_ARGUMENT = 1,
+  // Wrong password or key, or the data has been tampered with
+  VX_STATUS_AUTHENTICATION_FAILED = 2,
+  // The project or secret does not exist
+  VX_STATUS_NOT_FOUND = 3,
+  // The secret's TTL has run out
+  VX_STATUS_EXPIRED = 4,
+  // The project is protected by a passphrase
+  VX_STATUS_LOCKED = 5,
+  // The vault file could not be read
+  VX_STATUS_IO = 6,
+  // Any other failure
+  VX_STATUS_ERROR = 255,
+} VxStatus;
+
+// An opened vault holding its data key. Opaque to C callers.
+typedef struct VxVault VxVault;
+
+// A byte buffer allocated by the library.
+//
+// Release it with `vx_buffer_free`.
+typedef struct VxBuffer {
+  uint8_t *data;
+  size_t len;
+} VxBuffer;
+
+// Returns the library version as a static NUL-terminated string.
+const char *vx_version(void);
+
+// Returns a description of the last failure on the calling thread, or null
+// if the last call succeeded.
+//
+// The string stays valid until the next call into the library on the same
+// thread.
+const char *vx_last_error(void);
+
+// Derives a 32-byte key from a password with Argon2id.
+//
+// # Safety
+// `password` must point to `password_len` readable bytes, `salt` to
+// `salt_len` (which must be `VX_SALT_SIZE`) and `out_key` to
+// `VX_KEY_SIZE` writable bytes.
+enum VxStatus vx_derive_key(const uint8_t *password,
+                            size_t password_len,
+                            const uint8_t *salt,
+                            size_t salt_len,
+                            uint8_t *out_key);
+
+// Encrypts data with AES-256-GCM.
+//
+// On success `out` receives nonce (12 bytes) || ciphertext, the same
+// layout as `wasm_encrypt`.
+//
+// # Safety
+// `plaintext` must point to `plaintext_len` readable bytes, `key` to
+// `VX_KEY_SIZE` readable bytes and `out` to a writable `VxBuffer`.
+enum VxStatus vx_encrypt(const uint8_t *plaintext,
+                         size_t plaintext_len,
+                         const uint8_t *key,
+                         struct VxBuffer *out);
+
+// Decrypts data produced by `vx_encrypt`.
+//
+// # Safety
+// `data` must point to `data_len` readable bytes, `key` to `VX_KEY_SIZE`
+// readable bytes and `out` to a writable `VxBuffer`.
+enum VxStatus vx_decrypt(const uint8_t *data,
+                         size_t data_len,
+                         const uint8_t *key,
+                         struct VxBuffer *out);
+
+// Opens a vault file with any credential matching one of its key slots.
+// Every project is unsealed.
+//
+// On success `out` receives a vault to release with `vx_vault_free`.
+//
+// # Safety
+// `path` must be a NUL-terminated string, `credential` must point to
+// `credential_len` readable bytes and `out` to a writable pointer.
+enum VxStatus vx_vault_open(const char *path,
+                            const uint8_t *credential,
+                            size_t credential_len,
+                            struct VxVault **out);
+
+// Retrieves and decrypts a secret from the default environment of a
+// project. Aliases and templates are resolved.
+//
+// # Safety
+// `vault` must come from `vx_vault_open` and not have been freed;
+// `project` and `key` must be NUL-terminated strings and `out` must point
+// to a writable `VxBuffer`.
+enum VxStatus vx_vault_get_secret(const struct VxVault *vault,
+                                  const char *project,
+                                  const char *key,
+                                  struct VxBuffer *out);
+
+// Closes a vault, wiping its data key. Null is ignored.
+//
+// # Safety
+// `vault` must come from `vx_vault_open` and not already have been freed.
+void vx_vault_free(struct VxVault *vault);
+
+// Wipes and releases a buffer returned by the library. A buffer with a null
+// `data` pointer is ignored.
+//
+// # Safety
+// `buffer` must have been returned by this library and not already freed.
+void vx_buffer_free(struct VxBuffer buffer);
+
+#endif  /* VX_CORE_H */
@@ -0,0 +1,269 @@
+//! Append-only record of vault operations.
+//!
+//! The log lives inside the encrypted vault payload, so it is protected by
+//! the same master key as everything else.
+//!
+//! Entries are hash-chained: each entry stores
+//! `SHA-256(previous hash || entry fields)`, so modifying, reordering or
+//! removing any entry breaks every hash after it.
+//!
+//! # Security Notes
+//! - Entries hold names only (project, secret key, SSH server/identity),
+//!   never secret values
+//! - The vault exposes no API to edit or remove entries
+//! - Dropping entries from the end of the log leaves a valid, shorter chain;
+//!   detect that by comparing the head hash with one recorded earlier
+
+use crate::error::VaultError;
+use crate::ttl;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// Size of an entry hash in bytes (SHA-256)
+pub const HASH_SIZE: usize = 32;
+
+/// A vault operation that is recorded in the access log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Add,
+    Get,
+    Edit,
+    Remove,
+    SshConnect,
+    /// kubectl run with a stored kubeconfig
+    Kubectl,
+    /// A WireGuard interface brought up or down with a stored config
+    Wireguard,
+    /// An item put back from the trash
+    Restore,
+    /// The last add, edit or removal reverted
+    Undo,
+    /// A named snapshot of the vault taken
+    Snapshot,
+    /// The vault rolled back to a named snapshot
+    Rollback,
+    /// The vault opened through an emergency access grant
+    EmergencyAccess,
+    /// A project written to an archive file
+    Archive,
+    /// A project added back from an archive file
+    Unarchive,
+    /// Changes merged in from another copy of the vault
+    Sync,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Operation::Add => "add",
+            Operation::Get => "get",
+            Operation::Edit => "edit",
+            Operation::Remove => "remove",
+            Operation::SshConnect => "ssh-connect",
+            Operation::Kubectl => "kubectl",
+            Operation::Wireguard => "wireguard",
+            Operation::Restore => "restore",
+            Operation::Undo => "undo",
+            Operation::Snapshot => "snapshot",
+            Operation::Rollback => "rollback",
+            Operation::EmergencyAccess => "emergency-access",
+            Operation::Archive => "archive",
+            Operation::Unarchive => "unarchive",
+            Operation::Sync => "sync",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A single access log entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Unix timestamp of the operation
+    pub timestamp: u64,
+    pub operation: Operation,
+    /// Project the operation touched, if any
+    pub project: Option<String>,
+    /// Secret key, SSH server/identity name for `SshConnect`, kubeconfig
+    /// name for `Kubectl`, or interface name for `Wireguard`
+    pub name: Option<String>,
+    /// Chain hash; empty for entries recorded before chaining was added
+    #[serde(default, with = "crate::vault::base64_serde")]
+    pub hash: Vec<u8>,
+}
+
+/// Result of a successful chain verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainStatus {
+    /// Number of entries covered by the chain
+    pub chained: usize,
+    /// Number of leading entries recorded before chaining was added
+    pub unchained: usize,
+    /// Hash of the newest entry (all zeros for an empty chain)
+    pub head: [u8; HASH_SIZE],
+}
+
+impl LogEntry {
+    /// Creates an entry stamped with the current time and chained to `prev`.
+    pub fn new(
+        operation: Operation,
+        project: Option<&str>,
+        name: Option<&str>,
+        prev: &[u8; HASH_SIZE],
+    ) -> Self {
+        let mut entry = Self {
+            timestamp: ttl::current_timestamp(),
+            operation,
+            project: project.map(str::to_string),
+            name: name.map(str::to_string),
+            hash: Vec::new(),
+        };
+        entry.hash = entry.chain_hash(prev).to_vec();
+        entry
+    }
+
+    /// Computes this entry's hash given the previous entry's hash.
+    pub fn chain_hash(&self, prev: &[u8; HASH_SIZE]) -> [u8; HASH_SIZE] {
+        let mut hasher = Sha256::new();
+        hasher.update(prev);
+        hasher.update(self.timestamp.to_le_bytes());
+        hash_field(&mut hasher, Some(&self.operation.to_string()));
+        hash_field(&mut hasher, self.project.as_deref());
+        hash_field(&mut hasher, self.name.as_deref());
+        hasher.finalize().into()
+    }
+
+    /// Returns the `project/name` the entry refers to, for display.
+    pub fn target(&self) -> String {
+        match (&self.project, &self.name) {
+            (Some(project), Some(name)) => format!("{}/{}", project, name),
+            (Some(project), None) => project.clone(),
+            (None, Some(name)) => name.clone(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// Returns the hash the next entry should be chained to.
+pub fn head(entries: &[LogEntry]) -> [u8; HASH_SIZE] {
+    entries
+        .last()
+        .and_then(|entry| entry.hash.as_slice().try_into().ok())
+        .unwrap_or([0u8; HASH_SIZE])
+}
+
+/// Verifies the hash chain over a log.
+///
+/// Leading entries without a hash (recorded before chaining was added) are
+/// skipped; once the chain starts, every entry must link to the previous one.
+///
+/// # Errors
+/// `VaultError::AccessLogTampered` with the index of the first entry whose
+/// hash does not match.
+pub fn verify_chain(entries: &[LogEntry]) -> Result<ChainStatus, VaultError> {
+    let unchained = entries.iter().take_while(|e| e.hash.is_empty()).count();
+
+    let mut prev = [0u8; HASH_SIZE];
+    for (index, entry) in entries.iter().enumerate().skip(unchained) {
+        let expected = entry.chain_hash(&prev);
+        if entry.hash != expected {
+            return Err(VaultError::AccessLogTampered(index));
+        }
+        prev = expected;
+    }
+
+    Ok(ChainStatus {
+        chained: entries.len() - unchained,
+        unchained,
+        head: prev,
+    })
+}
+
+/// Hashes an optional string unambiguously (presence flag + length prefix).
+fn hash_field(hasher: &mut Sha256, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            hasher.update([1u8]);
+            hasher.update((value.len() as u64).to_le_bytes());
+            hasher.update(value.as_bytes());
+        }
+        None => hasher.update([0u8]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(len: usize) -> Vec<LogEntry> {
+        let mut entries = Vec::new();
+        for i in 0..len {
+            let name = format!("KEY_{}", i);
+            let prev = head(&entries);
+            entries.push(LogEntry::new(Operation::Get, Some("app"), Some(&name), &prev));
+        }
+        entries
+    }
+
+    #[test]
+    fn test_entry_target() {
+        let zero = [0u8; HASH_SIZE];
+        let entry = LogEntry::new(Operation::Get, Some("app"), Some("DB_URL"), &zero);
+        assert_eq!(entry.target(), "app/DB_URL");
+
+        let entry = LogEntry::new(Operation::SshConnect, None, Some("prod"), &zero);
+        assert_eq!(entry.target(), "prod");
+    }
+
+    #[test]
+    fn test_chain_verifies() {
+        let entries = chain(5);
+        let status = verify_chain(&entries).unwrap();
+
+        assert_eq!(status.chained, 5);
+        assert_eq!(status.head, head(&entries));
+    }
+
+    #[test]
+    fn test_modified_entry_detected() {
+        let mut entries = chain(5);
+        entries[2].name = Some("OTHER".to_string());
+
+        assert!(matches!(
+            verify_chain(&entries),
+            Err(VaultError::AccessLogTampered(2))
+        ));
+    }
+
+    #[test]
+    fn test_removed_entry_detected() {
+        let mut entries = chain(5);
+        entries.remove(1);
+
+        assert!(matches!(
+            verify_chain(&entries),
+            Err(VaultError::AccessLogTampered(1))
+        ));
+    }
+
+    #[test]
+    fn test_legacy_prefix_is_skipped() {
+        let mut entries = chain(3);
+        for entry in &mut entries {
+            entry.hash.clear();
+        }
+        let prev = head(&entries);
+        entries.push(LogEntry::new(Operation::Add, Some("app"), None, &prev));
+
+        let status = verify_chain(&entries).unwrap();
+        assert_eq!(status.unchained, 3);
+        assert_eq!(status.chained, 1);
+    }
+
+    #[test]
+    fn test_operation_display() {
+        assert_eq!(Operation::SshConnect.to_string(), "ssh-connect");
+        assert_eq!(Operation::Kubectl.to_string(), "kubectl");
+    }
+}
@@ -0,0 +1,370 @@
+//! Archives of single projects.
+//!
+//! An archive holds one project, with its secrets, environments, rules and
+//! attachments, encrypted under a passphrase chosen by the user. It is meant
+//! for projects of decommissioned services that may be needed again: the
+//! project can be removed from the vault and brought back later, into this
+//! vault or another one.
+//!
+//! Secret values stay encrypted inside the archive, under a random key it
+//! carries; restoring re-encrypts them under the data key of the vault they
+//! are restored into. Attachments are carried decrypted.
+//!
+//! # Format
+//! ```text
+//! MAGIC (8) || info length (u32 LE) || info (JSON) || nonce (12) || ciphertext
+//! ```
+//! As in `transfer`: the ciphertext is the CBOR-encoded archive under
+//! AES-256-GCM, everything before the nonce is authenticated as associated
+//! data, and the key is derived from the passphrase with Argon2id using the
+//! salt and parameters stored in the info.
+
+use crate::crypto::{self, EncryptedData, KdfParams, KEY_SIZE, NONCE_SIZE};
+use crate::error::VaultError;
+use crate::ttl;
+use crate::vault::{Project, Vault};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Magic bytes identifying a project archive
+pub const MAGIC: &[u8; 8] = b"\0VXARCH\0";
+
+/// Version of the archive format written by `seal`
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// Size of the info length field
+const INFO_LEN_SIZE: usize = 4;
+
+/// The readable description at the start of an archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveInfo {
+    /// Archive format version
+    pub version: u32,
+    /// Name of the project in the vault it came from
+    pub project: String,
+    /// Number of secrets, across all environments
+    pub secrets: usize,
+    /// Number of attachments
+    pub attachments: usize,
+    /// Version of VaultX that made the archive
+    pub created_with: String,
+    /// When the archive was made
+    pub created_at: u64,
+    /// Argon2id parameters deriving the archive key from the passphrase
+    pub kdf: KdfParams,
+    #[serde(with = "crate::vault::base64_serde")]
+    pub salt: Vec<u8>,
+}
+
+/// A project as carried by an archive. The key and attachment contents are
+/// wiped when dropped.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProjectArchive {
+    /// The project, with its secret values encrypted under `value_key`
+    pub project: Project,
+    #[serde(with = "crate::vault::base64_serde")]
+    value_key: Vec<u8>,
+    pub attachments: Vec<ArchivedAttachment>,
+}
+
+impl Drop for ProjectArchive {
+    fn drop(&mut self) {
+        self.value_key.zeroize();
+    }
+}
+
+/// An attachment in an archive, decrypted.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ArchivedAttachment {
+    pub name: String,
+    pub created_at: u64,
+    #[serde(with = "crate::vault::base64_serde")]
+    pub contents: Vec<u8>,
+}
+
+impl Drop for ArchivedAttachment {
+    fn drop(&mut self) {
+        self.contents.zeroize();
+    }
+}
+
+/// Collects the project `name` from `vault`, with the contents of its
+/// attachments decrypted from `blobs`, keyed by blob id.
+///
+/// The project must be unsealed and, if it has a passphrase, unlocked.
+///
+/// # Errors
+/// `VaultError::AttachmentNotFound` if the blob of an attachment is missing
+/// from `blobs`.
+pub fn collect(
+    vault: &Vault,
+    name: &str,
+    encryption_key: &[u8; KEY_SIZE],
+    blobs: &HashMap<String, Vec<u8>>,
+) -> Result<ProjectArchive, VaultError> {
+    let value_key = crypto::generate_key();
+    let project = vault.export_project(name, encryption_key, &value_key)?;
+
+    let mut attachments = Vec::new();
+    for attachment in vault.projects[name].attachments.values() {
+        let blob = blobs
+            .get(&attachment.blob_id)
+            .ok_or_else(|| VaultError::AttachmentNotFound(attachment.name.clone()))?;
+        let contents = vault.open_attachment(name, &attachment.name, blob, encryption_key)?;
+        attachments.push(ArchivedAttachment {
+            name: attachment.name.clone(),
+            created_at: attachment.created_at,
+            contents: contents.expose().to_vec(),
+        });
+    }
+    attachments.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(ProjectArchive {
+        project,
+        value_key: value_key.to_vec(),
+        attachments,
+    })
+}
+
+/// Adds the project in `archive` to `vault` as `name`, and returns the
+/// attachment blobs for the caller to persist, as `(blob id, blob)` pairs.
+///
+/// # Errors
+/// `VaultError::ProjectAlreadyExists` if `name` is taken.
+pub fn restore(
+    vault: &mut Vault,
+    archive: &ProjectArchive,
+    name: &str,
+    encryption_key: &[u8; KEY_SIZE],
+) -> Result<Vec<(String, Vec<u8>)>, VaultError> {
+    let value_key: &[u8; KEY_SIZE] = archive
+        .value_key
+        .as_slice()
+        .try_into()
+        .map_err(|_| VaultError::CorruptedVault)?;
+    vault.import_project(name, archive.project.clone(), value_key, encryption_key)?;
+
+    let mut blobs = Vec::new();
+    for attachment in &archive.attachments {
+        let (blob_id, blob) =
+            vault.add_attachment(name, &attachment.name, &attachment.contents, encryption_key)?;
+        if let Some(added) = vault
+            .projects
+            .get_mut(name)
+            .and_then(|project| project.attachments.get_mut(&attachment.name))
+        {
+            added.created_at = attachment.created_at;
+        }
+        blobs.push((blob_id, blob));
+    }
+    Ok(blobs)
+}
+
+/// Encrypts `archive` under `passphrase`.
+pub fn seal(archive: &ProjectArchive, passphrase: &[u8]) -> Result<Vec<u8>, VaultError> {
+    let info = ArchiveInfo {
+        version: ARCHIVE_VERSION,
+        project: archive.project.name.clone(),
+        secrets: archive.project.all_secrets().count(),
+        attachments: archive.attachments.len(),
+        created_with: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: ttl::current_timestamp(),
+        kdf: KdfParams::STANDARD,
+        salt: crypto::generate_salt().to_vec(),
+    };
+    let info_json =
+        serde_json::to_vec(&info).map_err(|e| VaultError::SerializationError(e.to_string()))?;
+    let info_len = u32::try_from(info_json.len())
+        .map_err(|_| VaultError::SerializationError("archive info too large".to_string()))?;
+
+    let mut data = Vec::new();
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&info_len.to_le_bytes());
+    data.extend_from_slice(&info_json);
+
+    let mut plaintext = Zeroizing::new(Vec::new());
+    ciborium::ser::into_writer(archive, &mut *plaintext)
+        .map_err(|e| VaultError::SerializationError(e.to_string()))?;
+
+    let key = crypto::derive_key_with_params(passphrase, &info.salt, &info.kdf)?;
+    let encrypted = crypto::encrypt_with_aad(&plaintext, &key, &data)?;
+
+    data.extend_from_slice(&encrypted.nonce);
+    data.extend_from_slice(&encrypted.ciphertext);
+    Ok(data)
+}
+
+/// Reads the info of an archive without decrypting it.
+///
+/// # Errors
+/// `VaultError::InvalidFormat` if `data` is not a project archive.
+pub fn read_info(data: &[u8]) -> Result<ArchiveInfo, VaultError> {
+    let (info, _) = split_archive(data)?;
+    Ok(info)
+}
+
+/// Decrypts an archive made by `seal`.
+///
+/// # Errors
+/// - `VaultError::InvalidFormat` if `data` is not a project archive, or
+///   one in a format this version cannot read
+/// - `VaultError::AuthenticationFailed` if the passphrase is wrong or the
+///   archive was modified
+pub fn open(data: &[u8], passphrase: &[u8]) -> Result<ProjectArchive, VaultError> {
+    let (info, body_start) = split_archive(data)?;
+    if info.version > ARCHIVE_VERSION {
+        return Err(VaultError::InvalidFormat(format!(
+            "the archive was made by VaultX {}; update vx to restore it",
+            info.created_with
+        )));
+    }
+
+    let (aad, body) = data.split_at(body_start);
+    let nonce = body
+        .get(..NONCE_SIZE)
+        .and_then(|nonce| nonce.try_into().ok())
+        .ok_or(VaultError::CorruptedVault)?;
+    let encrypted = EncryptedData {
+        ciphertext: body[NONCE_SIZE..].to_vec(),
+        nonce,
+    };
+
+    let key = crypto::derive_key_with_params(passphrase, &info.salt, &info.kdf)?;
+    let plaintext = crypto::decrypt_with_aad(&encrypted, &key, aad)
+        .map_err(|_| VaultError::AuthenticationFailed)?;
+
+    ciborium::de::from_reader(plaintext.expose()).map_err(|_| VaultError::CorruptedVault)
+}
+
+/// Splits an archive into its info and the offset of the nonce.
+fn split_archive(data: &[u8]) -> Result<(ArchiveInfo, usize), VaultError> {
+    if !data.starts_with(MAGIC) {
+        return Err(VaultError::InvalidFormat(
+            "not a VaultX project archive".to_string(),
+        ));
+    }
+
+    let info_start = MAGIC.len() + INFO_LEN_SIZE;
+    let info_len = data
+        .get(MAGIC.len()..info_start)
+        .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+        .ok_or(VaultError::CorruptedVault)?;
+    let info_end = info_start
+        .checked_add(info_len)
+        .ok_or(VaultError::CorruptedVault)?;
+    let info = data
+        .get(info_start..info_end)
+        .ok_or(VaultError::CorruptedVault)?;
+
+    let info = serde_json::from_slice(info).map_err(|_| VaultError::CorruptedVault)?;
+    Ok((info, info_end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::SecretRef;
+
+    const KEY: [u8; KEY_SIZE] = [3; KEY_SIZE];
+
+    fn vault_with_project() -> (Vault, HashMap<String, Vec<u8>>) {
+        let mut vault = Vault::new();
+        vault.init_project("billing").unwrap();
+        vault
+            .add_secret("billing", "STRIPE_KEY", b"sk_live_1", &KEY, Some(3600))
+            .unwrap();
+        vault
+            .add_secret_in("billing", Some("staging"), "STRIPE_KEY", b"sk_test_1", &KEY, None)
+            .unwrap();
+        vault
+            .add_alias(
+                "billing",
+                None,
+                "PAYMENTS_KEY",
+                SecretRef::new("billing", None, "STRIPE_KEY"),
+            )
+            .unwrap();
+        let (blob_id, blob) = vault
+            .add_attachment("billing", "contract.pdf", b"%PDF", &KEY)
+            .unwrap();
+        vault.set_project_passphrase("billing", b"extra", &KEY).unwrap();
+        (vault, HashMap::from([(blob_id, blob)]))
+    }
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let (vault, blobs) = vault_with_project();
+        let archive = collect(&vault, "billing", &KEY, &blobs).unwrap();
+
+        let data = seal(&archive, b"passphrase").unwrap();
+        let info = read_info(&data).unwrap();
+        assert_eq!(info.project, "billing");
+        assert_eq!((info.secrets, info.attachments), (3, 1));
+        assert!(!data.windows(9).any(|w| w == b"sk_live_1"));
+
+        let opened = open(&data, b"passphrase").unwrap();
+        let other_key = [4; KEY_SIZE];
+        let mut other = Vault::new();
+        let blobs = restore(&mut other, &opened, "billing-old", &other_key).unwrap();
+        assert_eq!(blobs.len(), 1);
+
+        // The passphrase is not carried over
+        assert!(!other.is_locked("billing-old"));
+        let value = other
+            .get_secret("billing-old", "PAYMENTS_KEY", &other_key)
+            .unwrap();
+        assert_eq!(value.expose(), b"sk_live_1");
+        let staging = other
+            .get_secret_in("billing-old", Some("staging"), "STRIPE_KEY", &other_key)
+            .unwrap();
+        assert_eq!(staging.expose(), b"sk_test_1");
+
+        let original = &vault.projects["billing"].secrets["STRIPE_KEY"];
+        let restored = &other.projects["billing-old"].secrets["STRIPE_KEY"];
+        assert_eq!(restored.created_at, original.created_at);
+        assert_eq!(restored.expires_at, original.expires_at);
+
+        let contents = other
+            .open_attachment("billing-old", "contract.pdf", &blobs[0].1, &other_key)
+            .unwrap();
+        assert_eq!(contents.expose(), b"%PDF");
+
+        assert!(matches!(
+            restore(&mut other, &opened, "billing-old", &other_key),
+            Err(VaultError::ProjectAlreadyExists(_))
+        ));
+    }
+
+    #[test]
+    fn test_archive_rejects_wrong_passphrase_and_tampering() {
+        let (vault, blobs) = vault_with_project();
+        let archive = collect(&vault, "billing", &KEY, &blobs).unwrap();
+        let mut data = seal(&archive, b"passphrase").unwrap();
+        assert!(matches!(
+            open(&data, b"wrong"),
+            Err(VaultError::AuthenticationFailed)
+        ));
+
+        // The info is authenticated with the contents
+        let at = data
+            .windows(b"billing".len())
+            .position(|w| w == b"billing")
+            .unwrap();
+        data[at] = b'B';
+        assert_eq!(read_info(&data).unwrap().project, "Billing");
+        assert!(matches!(
+            open(&data, b"passphrase"),
+            Err(VaultError::AuthenticationFailed)
+        ));
+
+        assert!(matches!(
+            read_info(b"\0VXSSHK\0"),
+            Err(VaultError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            collect(&vault, "billing", &KEY, &HashMap::new()),
+            Err(VaultError::AttachmentNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,780 @@
+//! Security audit of a vault.
+//!
+//! Flags secrets that have expired, have not been rotated for a long time,
+//! look sensitive but never expire, break their project's rules or
+//! generation policy or (if asked) have not been read for a long time, and
+//! certificates about to lapse, and lists what runs out soon. The audit
+//! only reads metadata; no secret value is decrypted, except by
+//! `audit_values`.
+
+use crate::access_log::Operation;
+use crate::crypto::KEY_SIZE;
+use crate::error::VaultError;
+use crate::generate::PolicyViolation;
+use crate::rules::RuleViolation;
+use crate::ttl;
+use crate::vault::{Provenance, SecretKind, SecretRef, Vault};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Number of days after which a secret or SSH identity is considered
+/// long-lived
+pub const LONG_LIVED_DAYS: u64 = 90;
+
+/// Seconds in a day
+pub const SECONDS_PER_DAY: u64 = 86400;
+
+/// Number of days before its `notAfter` from which a certificate secret is
+/// flagged
+pub const CERTIFICATE_WARNING_DAYS: u64 = 30;
+
+/// Secret names containing one of these (case-insensitively) are high-risk
+/// when they have no TTL
+pub const HIGH_RISK_PATTERNS: &[&str] = &[
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "api_key",
+    "apikey",
+    "private_key",
+    "privatekey",
+    "credential",
+];
+
+/// Limits applied by `audit_vault_with`; `Default` gives the built-in ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditThresholds {
+    /// Days after which a secret or SSH identity is long-lived
+    pub long_lived_days: u64,
+    /// Days without a read after which a secret is unused; not checked if
+    /// `None`
+    pub min_idle_days: Option<u64>,
+    /// Days before expiry from which a certificate is flagged
+    pub certificate_warning_days: u64,
+}
+
+impl Default for AuditThresholds {
+    fn default() -> Self {
+        Self {
+            long_lived_days: LONG_LIVED_DAYS,
+            min_idle_days: None,
+            certificate_warning_days: CERTIFICATE_WARNING_DAYS,
+        }
+    }
+}
+
+/// The kind of problem an audit found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssueKind {
+    Expired,
+    LongLived,
+    HighRisk,
+    /// Not read for `AuditThresholds::min_idle_days`; a candidate for
+    /// removal
+    Unused,
+    /// A certificate expiring within
+    /// `AuditThresholds::certificate_warning_days`
+    CertificateExpiring,
+    /// Breaks one of its project's rules, most likely written before the
+    /// rule was set
+    RuleViolation,
+    /// Does not follow its project's generation policy, or is due for
+    /// rotation under it
+    PolicyViolation,
+}
+
+/// A problem with one secret.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditIssue {
+    pub kind: IssueKind,
+    pub project: String,
+    /// Environment of the secret, `None` for the default environment
+    pub env: Option<String>,
+    pub key: String,
+    /// Days since the secret was created
+    pub age_days: u64,
+    /// Days since the secret was last read (or created, if it never was);
+    /// set for `Unused` issues
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_days: Option<u64>,
+    /// Days until the secret expires; set for `CertificateExpiring` issues
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_in_days: Option<u64>,
+    /// The rule broken; set for `RuleViolation` issues
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<RuleViolation>,
+    /// How the secret departs from the policy; set for `PolicyViolation`
+    /// issues
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<PolicyViolation>,
+    /// Where the secret was last added or edited, if recorded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+/// Audit results for one project.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectAudit {
+    pub name: String,
+    /// Secrets in all environments, aliases excluded
+    pub total_secrets: usize,
+    pub issues: Vec<AuditIssue>,
+}
+
+impl ProjectAudit {
+    /// Returns the number of issues of one kind.
+    pub fn count(&self, kind: IssueKind) -> usize {
+        self.issues.iter().filter(|issue| issue.kind == kind).count()
+    }
+}
+
+/// An SSH identity that has not been rotated for a long time.
+#[derive(Debug, Clone, Serialize)]
+pub struct IdentityIssue {
+    pub name: String,
+    pub age_days: u64,
+}
+
+/// An item waiting in the trash.
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashedItemAudit {
+    /// Trash id, for `vx trash restore`
+    pub id: u32,
+    /// What the item is, e.g. `secret app/API_KEY`
+    pub item: String,
+    /// Days since the item was removed
+    pub age_days: u64,
+}
+
+/// The result of auditing a vault.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditReport {
+    /// Unsealed projects, sorted by name
+    pub projects: Vec<ProjectAudit>,
+    pub ssh_identities: usize,
+    /// Long-lived SSH identities, sorted by name
+    pub long_lived_identities: Vec<IdentityIssue>,
+    /// Removed items that can still be restored, oldest first
+    pub trash: Vec<TrashedItemAudit>,
+}
+
+impl AuditReport {
+    /// Returns the number of secrets audited.
+    pub fn total_secrets(&self) -> usize {
+        self.projects.iter().map(|p| p.total_secrets).sum()
+    }
+
+    /// Returns the number of secret issues of one kind.
+    pub fn count(&self, kind: IssueKind) -> usize {
+        self.projects.iter().map(|p| p.count(kind)).sum()
+    }
+
+    /// Returns the number of secret issues of every kind.
+    pub fn total_issues(&self) -> usize {
+        self.projects.iter().map(|p| p.issues.len()).sum()
+    }
+}
+
+/// Something that runs out soon.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "name", rename_all = "kebab-case")]
+pub enum ExpiringItem {
+    /// A secret reaching the end of its TTL
+    Secret(SecretRef),
+    /// A certificate secret reaching its `notAfter`
+    Certificate(SecretRef),
+    /// An SSH identity becoming long-lived (older than `LONG_LIVED_DAYS`)
+    SshIdentity(String),
+}
+
+/// An item from `upcoming_expirations` and when it runs out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Expiration {
+    pub item: ExpiringItem,
+    pub expires_at: u64,
+}
+
+impl Expiration {
+    /// Describes the expiration relative to `now`, e.g. `app/API_KEY
+    /// expires in 2d`.
+    pub fn describe(&self, now: u64) -> String {
+        let when = ttl::format_until(self.expires_at, now);
+        match &self.item {
+            ExpiringItem::Secret(secret) => format!("{} expires {}", secret, when),
+            ExpiringItem::Certificate(secret) => {
+                format!("Certificate {} expires {}", secret, when)
+            }
+            ExpiringItem::SshIdentity(name) => {
+                format!("SSH identity '{}' is due for rotation {}", name, when)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ExpiringItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpiringItem::Secret(secret) => write!(f, "{}", secret),
+            ExpiringItem::Certificate(secret) => write!(f, "certificate {}", secret),
+            ExpiringItem::SshIdentity(name) => write!(f, "SSH identity '{}'", name),
+        }
+    }
+}
+
+/// Lists the secrets in unsealed projects whose TTL runs out, and the SSH
+/// identities that become long-lived, after `now` but within `window`
+/// seconds, soonest first.
+///
+/// Items that have already expired are left to `audit_vault`.
+pub fn upcoming_expirations(vault: &Vault, now: u64, window: u64) -> Vec<Expiration> {
+    let deadline = now.saturating_add(window);
+    let upcoming = |expires_at: u64| expires_at > now && expires_at <= deadline;
+
+    let mut expirations = Vec::new();
+    for (name, project) in &vault.projects {
+        for (env, secrets) in project.secrets_by_environment() {
+            for secret in secrets.values() {
+                match secret.expires_at {
+                    Some(expires_at) if secret.alias.is_none() && upcoming(expires_at) => {
+                        let location = SecretRef::new(name, env, &secret.key);
+                        let item = match secret.kind {
+                            SecretKind::Certificate => ExpiringItem::Certificate(location),
+                            _ => ExpiringItem::Secret(location),
+                        };
+                        expirations.push(Expiration { item, expires_at });
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    for identity in vault.ssh_identities.values() {
+        let expires_at = identity.created_at + LONG_LIVED_DAYS * SECONDS_PER_DAY;
+        if upcoming(expires_at) {
+            expirations.push(Expiration {
+                item: ExpiringItem::SshIdentity(identity.name.clone()),
+                expires_at,
+            });
+        }
+    }
+
+    expirations.sort_by(|a, b| {
+        a.expires_at
+            .cmp(&b.expires_at)
+            .then_with(|| a.item.to_string().cmp(&b.item.to_string()))
+    });
+    expirations
+}
+
+/// Audits every unsealed project of `vault` as of `now`.
+///
+/// Aliases are skipped; the secret they refer to is audited in its own
+/// project.
+pub fn audit_vault(vault: &Vault, now: u64) -> AuditReport {
+    audit_vault_with(vault, now, AuditThresholds::default())
+}
+
+/// Audits a vault like `audit_vault`, with custom thresholds.
+///
+/// A secret was last read at its `last_accessed` time or at the last `get`
+/// of it in the access log, whichever is later; the log covers reads made
+/// before read times were tracked.
+pub fn audit_vault_with(vault: &Vault, now: u64, thresholds: AuditThresholds) -> AuditReport {
+    let long_lived_days = thresholds.long_lived_days;
+    let long_lived_threshold = now.saturating_sub(long_lived_days * SECONDS_PER_DAY);
+    let certificate_warning = thresholds.certificate_warning_days * SECONDS_PER_DAY;
+    let last_logged_reads = last_logged_reads(vault);
+
+    let mut projects: Vec<ProjectAudit> = vault
+        .projects
+        .iter()
+        .map(|(name, project)| {
+            let mut audit = ProjectAudit {
+                name: name.clone(),
+                total_secrets: 0,
+                issues: Vec::new(),
+            };
+
+            for (env, secrets) in project.secrets_by_environment() {
+                let mut keys: Vec<&String> = secrets.keys().collect();
+                keys.sort();
+
+                for key in keys {
+                    let secret = &secrets[key];
+                    if secret.alias.is_some() {
+                        continue;
+                    }
+                    audit.total_secrets += 1;
+
+                    let issue = |kind| AuditIssue {
+                        kind,
+                        project: name.clone(),
+                        env: env.map(str::to_string),
+                        key: key.clone(),
+                        age_days: now.saturating_sub(secret.created_at) / SECONDS_PER_DAY,
+                        idle_days: None,
+                        expires_in_days: None,
+                        rule: None,
+                        policy: None,
+                        provenance: secret.provenance.clone(),
+                    };
+
+                    if ttl::is_expired(secret.expires_at, now) {
+                        audit.issues.push(issue(IssueKind::Expired));
+                    }
+                    if let Some(expires_at) = secret.expires_at {
+                        let remaining = expires_at.saturating_sub(now);
+                        if secret.kind == SecretKind::Certificate
+                            && remaining > 0
+                            && remaining <= certificate_warning
+                        {
+                            audit.issues.push(AuditIssue {
+                                expires_in_days: Some(remaining / SECONDS_PER_DAY),
+                                ..issue(IssueKind::CertificateExpiring)
+                            });
+                        }
+                    }
+                    if secret.created_at < long_lived_threshold {
+                        audit.issues.push(issue(IssueKind::LongLived));
+                    }
+                    if secret.expires_at.is_none() && is_high_risk(key) {
+                        audit.issues.push(issue(IssueKind::HighRisk));
+                    }
+                    if let Some(min_idle_days) = thresholds.min_idle_days {
+                        let logged = last_logged_reads
+                            .get(&(name.as_str(), qualified_name(env, key)))
+                            .copied();
+                        let last_read = secret
+                            .last_accessed
+                            .get()
+                            .max(logged)
+                            .unwrap_or(secret.created_at);
+                        let idle_days = now.saturating_sub(last_read) / SECONDS_PER_DAY;
+                        if idle_days >= min_idle_days {
+                            audit.issues.push(AuditIssue {
+                                idle_days: Some(idle_days),
+                                ..issue(IssueKind::Unused)
+                            });
+                        }
+                    }
+                    for rule in project.rules.violations(key, secret, now) {
+                        audit.issues.push(AuditIssue {
+                            rule: Some(rule),
+                            ..issue(IssueKind::RuleViolation)
+                        });
+                    }
+                    if let Some(due) = project
+                        .generation
+                        .as_ref()
+                        .filter(|policy| !secret.template && policy.applies_to(key))
+                        .and_then(|policy| policy.rotation_due(secret.created_at, now))
+                    {
+                        audit.issues.push(AuditIssue {
+                            policy: Some(due),
+                            ..issue(IssueKind::PolicyViolation)
+                        });
+                    }
+                }
+            }
+
+            audit
+        })
+        .collect();
+    projects.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut long_lived_identities: Vec<IdentityIssue> = vault
+        .ssh_identities
+        .values()
+        .map(|identity| IdentityIssue {
+            name: identity.name.clone(),
+            age_days: now.saturating_sub(identity.created_at) / SECONDS_PER_DAY,
+        })
+        .filter(|issue| issue.age_days > long_lived_days)
+        .collect();
+    long_lived_identities.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let trash = vault
+        .trash()
+        .iter()
+        .map(|entry| TrashedItemAudit {
+            id: entry.id,
+            item: entry.item.to_string(),
+            age_days: now.saturating_sub(entry.deleted_at) / SECONDS_PER_DAY,
+        })
+        .collect();
+
+    AuditReport {
+        projects,
+        ssh_identities: vault.ssh_identities.len(),
+        long_lived_identities,
+        trash,
+    }
+}
+
+/// Adds to `report` the secrets whose values contain a pattern their
+/// project forbids or do not follow its generation policy, decrypting the
+/// values of every unsealed project that has either. Projects locked by a
+/// passphrase are skipped.
+pub fn audit_values(
+    report: &mut AuditReport,
+    vault: &Vault,
+    encryption_key: &[u8; KEY_SIZE],
+    now: u64,
+) -> Result<(), VaultError> {
+    for audit in &mut report.projects {
+        let project = &vault.projects[&audit.name];
+        let policy = project.generation.as_ref();
+        if (project.rules.forbidden_patterns.is_empty() && policy.is_none())
+            || vault.is_locked(&audit.name)
+        {
+            continue;
+        }
+
+        let mut values = vault.stored_values(&audit.name, encryption_key)?;
+        values.sort_by(|a, b| (&a.0.env, &a.0.key).cmp(&(&b.0.env, &b.0.key)));
+        for (location, value) in values {
+            let rule = project
+                .rules
+                .forbidden_match(&value)?
+                .map(|pattern| RuleViolation::ForbiddenValue { pattern });
+            let policy = policy
+                .filter(|policy| policy.applies_to(&location.key))
+                .and_then(|policy| policy.check_value(&value));
+            if rule.is_none() && policy.is_none() {
+                continue;
+            }
+
+            let secret = &project.secrets_in(location.env.as_deref())?[&location.key];
+            let issue = AuditIssue {
+                kind: IssueKind::RuleViolation,
+                project: location.project,
+                env: location.env,
+                key: location.key,
+                age_days: now.saturating_sub(secret.created_at) / SECONDS_PER_DAY,
+                idle_days: None,
+                expires_in_days: None,
+                rule: None,
+                policy: None,
+                provenance: secret.provenance.clone(),
+            };
+            if let Some(rule) = rule {
+                audit.issues.push(AuditIssue {
+                    rule: Some(rule),
+                    ..issue.clone()
+                });
+            }
+            if let Some(policy) = policy {
+                audit.issues.push(AuditIssue {
+                    kind: IssueKind::PolicyViolation,
+                    policy: Some(policy),
+                    ..issue
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the time of the last `get` of each secret in the access log, by
+/// project and `env/key` (or `key` in the default environment).
+fn last_logged_reads(vault: &Vault) -> HashMap<(&str, String), u64> {
+    let mut reads = HashMap::new();
+    for entry in vault.access_log() {
+        if let (Operation::Get, Some(project), Some(name)) =
+            (entry.operation, &entry.project, &entry.name)
+        {
+            let last = reads.entry((project.as_str(), name.clone())).or_insert(0);
+            *last = entry.timestamp.max(*last);
+        }
+    }
+    reads
+}
+
+/// Names a secret as the access log does.
+fn qualified_name(env: Option<&str>, key: &str) -> String {
+    match env {
+        Some(env) => format!("{}/{}", env, key),
+        None => key.to_string(),
+    }
+}
+
+/// Returns whether a secret name matches one of `HIGH_RISK_PATTERNS`.
+pub fn is_high_risk(key: &str) -> bool {
+    let key_lower = key.to_lowercase();
+    HIGH_RISK_PATTERNS
+        .iter()
+        .any(|pattern| key_lower.contains(pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_flags_issues() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("app", "DB_PASSWORD", b"x", &key, None).unwrap();
+        vault.add_secret("app", "HOST", b"x", &key, Some(60)).unwrap();
+        vault
+            .add_secret_in("app", Some("prod"), "API_TOKEN", b"x", &key, Some(60))
+            .unwrap();
+        vault
+            .add_alias("app", None, "OTHER_PASSWORD", SecretRef::new("app", None, "DB_PASSWORD"))
+            .unwrap();
+
+        // Two minutes later, once the TTLs have run out
+        let later = ttl::current_timestamp() + 120;
+        let report = audit_vault(&vault, later);
+
+        assert_eq!(report.total_secrets(), 3);
+        assert_eq!(report.count(IssueKind::Expired), 2);
+        assert_eq!(report.count(IssueKind::HighRisk), 1);
+        assert_eq!(report.count(IssueKind::LongLived), 0);
+
+        let year_later = later + 365 * SECONDS_PER_DAY;
+        let report = audit_vault(&vault, year_later);
+        assert_eq!(report.count(IssueKind::LongLived), 3);
+        assert_eq!(report.total_issues(), 6);
+
+        let thresholds = AuditThresholds {
+            long_lived_days: 400,
+            ..AuditThresholds::default()
+        };
+        let report = audit_vault_with(&vault, year_later, thresholds);
+        assert_eq!(report.count(IssueKind::LongLived), 0);
+    }
+
+    #[test]
+    fn test_audit_flags_unused_secrets() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("app", "READ", b"x", &key, None).unwrap();
+        vault.add_secret("app", "LOGGED", b"x", &key, None).unwrap();
+        vault.add_secret_in("app", Some("prod"), "IDLE", b"x", &key, None).unwrap();
+
+        let now = ttl::current_timestamp();
+        let later = now + 200 * SECONDS_PER_DAY;
+        vault.projects["app"].secrets["READ"]
+            .last_accessed
+            .set(now + 150 * SECONDS_PER_DAY);
+        vault.record(Operation::Get, Some("app"), Some("LOGGED"));
+
+        // Off unless a threshold is given
+        assert_eq!(audit_vault(&vault, later).count(IssueKind::Unused), 0);
+
+        let thresholds = AuditThresholds {
+            min_idle_days: Some(180),
+            ..AuditThresholds::default()
+        };
+        let report = audit_vault_with(&vault, later, thresholds);
+        let unused: Vec<(String, Option<u64>)> = report.projects[0]
+            .issues
+            .iter()
+            .filter(|issue| issue.kind == IssueKind::Unused)
+            .map(|issue| (issue.key.clone(), issue.idle_days))
+            .collect();
+        assert_eq!(
+            unused,
+            vec![("LOGGED".to_string(), Some(200)), ("IDLE".to_string(), Some(200))]
+        );
+
+        let thresholds = AuditThresholds {
+            min_idle_days: Some(30),
+            ..AuditThresholds::default()
+        };
+        let report = audit_vault_with(&vault, later, thresholds);
+        assert_eq!(report.count(IssueKind::Unused), 3);
+    }
+
+    #[test]
+    fn test_audit_flags_expiring_certificates() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        let now = ttl::current_timestamp();
+        for (name, days) in [("TLS_SOON", 10), ("TLS_LATER", 90), ("TOKEN", 20)] {
+            vault.add_secret("app", name, b"x", &key, Some(days * SECONDS_PER_DAY)).unwrap();
+        }
+        for name in ["TLS_SOON", "TLS_LATER"] {
+            vault.projects.get_mut("app").unwrap().secrets.get_mut(name).unwrap().kind =
+                SecretKind::Certificate;
+        }
+
+        let report = audit_vault(&vault, now);
+        let flagged: Vec<(&str, Option<u64>)> = report.projects[0]
+            .issues
+            .iter()
+            .filter(|issue| issue.kind == IssueKind::CertificateExpiring)
+            .map(|issue| (issue.key.as_str(), issue.expires_in_days))
+            .collect();
+        assert_eq!(flagged, vec![("TLS_SOON", Some(10))]);
+
+        let upcoming = upcoming_expirations(&vault, now, 30 * SECONDS_PER_DAY);
+        assert_eq!(upcoming[0].describe(now), "Certificate app/TLS_SOON expires in 1w");
+        assert_eq!(upcoming[1].item, ExpiringItem::Secret(SecretRef::new("app", None, "TOKEN")));
+    }
+
+    #[test]
+    fn test_audit_lists_trash() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("app", "OLD_KEY", b"x", &key, None).unwrap();
+        let id = vault.trash_secret("app", None, "OLD_KEY").unwrap();
+
+        let later = ttl::current_timestamp() + 3 * SECONDS_PER_DAY;
+        let report = audit_vault(&vault, later);
+        assert_eq!(report.trash.len(), 1);
+        assert_eq!(report.trash[0].id, id);
+        assert_eq!(report.trash[0].item, "secret app/OLD_KEY");
+        assert_eq!(report.trash[0].age_days, 3);
+        // Trashed secrets are not audited as live ones
+        assert_eq!(report.total_secrets(), 0);
+    }
+
+    #[test]
+    fn test_audit_flags_rule_violations() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("app", "api_key", b"sk-1", &key, Some(3600)).unwrap();
+        vault.add_secret("app", "TOKEN", b"changeme", &key, None).unwrap();
+        vault
+            .add_secret("app", "LONG", b"x", &key, Some(30 * SECONDS_PER_DAY))
+            .unwrap();
+        vault
+            .set_rules(
+                "app",
+                crate::rules::SecretRules {
+                    key_pattern: Some("[A-Z_]+".to_string()),
+                    require_ttl: true,
+                    max_ttl: Some(SECONDS_PER_DAY),
+                    max_value_size: None,
+                    forbidden_patterns: vec!["changeme".to_string()],
+                },
+            )
+            .unwrap();
+
+        let now = ttl::current_timestamp();
+        let mut report = audit_vault(&vault, now);
+        let rules: Vec<_> = report.projects[0]
+            .issues
+            .iter()
+            .filter_map(|issue| issue.rule.clone().map(|rule| (issue.key.as_str(), rule)))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                (
+                    "LONG",
+                    RuleViolation::TtlTooLong {
+                        max_ttl: SECONDS_PER_DAY
+                    }
+                ),
+                ("TOKEN", RuleViolation::MissingTtl),
+                (
+                    "api_key",
+                    RuleViolation::KeyPattern {
+                        pattern: "[A-Z_]+".to_string()
+                    }
+                ),
+            ]
+        );
+
+        // Forbidden values are only found by decrypting
+        audit_values(&mut report, &vault, &key, now).unwrap();
+        assert_eq!(report.count(IssueKind::RuleViolation), 4);
+        let last = report.projects[0].issues.last().unwrap();
+        assert_eq!(last.key, "TOKEN");
+        assert_eq!(
+            last.rule,
+            Some(RuleViolation::ForbiddenValue {
+                pattern: "changeme".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_audit_flags_policy_violations() {
+        use crate::generate::{Charset, GenerationPolicy};
+
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+        let key = [0u8; KEY_SIZE];
+        let policy = GenerationPolicy {
+            length: 16,
+            charset: Charset::Hex,
+            rotate_after: Some(30 * SECONDS_PER_DAY),
+            keys: Some("*_TOKEN".to_string()),
+        };
+        vault
+            .set_generation_policy("app", Some(policy.clone()))
+            .unwrap();
+        let generated = policy.generate();
+        vault.add_secret("app", "API_TOKEN", &generated, &key, None).unwrap();
+        vault.add_secret("app", "OLD_TOKEN", b"short", &key, None).unwrap();
+        vault.add_secret("app", "DB_HOST", b"localhost", &key, None).unwrap();
+
+        let later = ttl::current_timestamp() + 31 * SECONDS_PER_DAY;
+        let mut report = audit_vault(&vault, later);
+        // Both tokens are due for rotation; DB_HOST is not covered
+        assert_eq!(report.count(IssueKind::PolicyViolation), 2);
+
+        audit_values(&mut report, &vault, &key, later).unwrap();
+        let violations: Vec<_> = report.projects[0]
+            .issues
+            .iter()
+            .filter_map(|issue| issue.policy.clone().map(|policy| (issue.key.as_str(), policy)))
+            .collect();
+        assert_eq!(violations.len(), 3);
+        assert_eq!(
+            violations[2],
+            ("OLD_TOKEN", PolicyViolation::TooShort { length: 5, min: 16 })
+        );
+    }
+
+    #[test]
+    fn test_upcoming_expirations() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        let now = ttl::current_timestamp();
+        vault.add_secret("app", "NO_TTL", b"x", &key, None).unwrap();
+        vault.add_secret("app", "SOON", b"x", &key, Some(3600)).unwrap();
+        vault
+            .add_secret_in("app", Some("prod"), "SOONER", b"x", &key, Some(60))
+            .unwrap();
+        vault.add_secret("app", "LATER", b"x", &key, Some(30 * SECONDS_PER_DAY)).unwrap();
+        vault
+            .add_alias("app", None, "ALIAS", SecretRef::new("app", None, "SOON"))
+            .unwrap();
+
+        let upcoming = upcoming_expirations(&vault, now, SECONDS_PER_DAY);
+        let items: Vec<String> = upcoming.iter().map(|e| e.item.to_string()).collect();
+        assert_eq!(items, vec!["app/prod/SOONER", "app/SOON"]);
+        assert_eq!(upcoming[1].describe(now), "app/SOON expires in 1h");
+
+        // Expired secrets are no longer upcoming
+        let upcoming = upcoming_expirations(&vault, now + 120, SECONDS_PER_DAY);
+        assert_eq!(upcoming.len(), 1);
+    }
+
+    #[test]
+    fn test_high_risk_patterns() {
+        assert!(is_high_risk("STRIPE_API_KEY"));
+        assert!(is_high_risk("github_token"));
+        assert!(!is_high_risk("DB_HOST"));
+    }
+}
@@ -0,0 +1,192 @@
+//! Where vault files are kept.
+//!
+//! The core never reads or writes storage itself; it works on the bytes of
+//! a vault file. A `VaultBackend` loads and saves those bytes. Its methods
+//! are async, so that integrations running on an executor (the daemon, a
+//! server, browser storage through wasm-bindgen) do not block it, and they
+//! all unlock and save through `open` and `save`.
+//!
+//! Synchronous callers such as the CLI drive a backend with `Blocking`,
+//! which runs each operation to completion on the calling thread.
+
+use crate::crypto::KEY_SIZE;
+use crate::error::VaultError;
+use crate::vault::{self, Vault};
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use zeroize::Zeroizing;
+
+/// Storage holding one encrypted vault file.
+pub trait VaultBackend {
+    /// Errors of the storage; vault errors are converted into them.
+    type Error: From<VaultError>;
+
+    /// Reads the vault file.
+    fn load(&self) -> impl Future<Output = Result<Vec<u8>, Self::Error>>;
+
+    /// Replaces the vault file with `data`. A reader must see either the
+    /// old file or the new one, never part of `data`.
+    fn save(&self, data: &[u8]) -> impl Future<Output = Result<(), Self::Error>>;
+}
+
+/// Unlocks the vault kept by `backend` with a credential, leaving its
+/// projects sealed. Returns the vault with its data key.
+pub async fn open<B: VaultBackend>(
+    backend: &B,
+    credential: &[u8],
+) -> Result<(Vault, Zeroizing<[u8; KEY_SIZE]>), B::Error> {
+    let data = backend.load().await?;
+    Ok(vault::unlock_vault(&data, credential)?)
+}
+
+/// Encrypts the vault under its data key and saves it to `backend`.
+pub async fn save<B: VaultBackend>(
+    backend: &B,
+    vault: &Vault,
+    key: &[u8; KEY_SIZE],
+) -> Result<(), B::Error> {
+    let data = vault::save_vault_with_key(vault, key)?;
+    backend.save(&data).await
+}
+
+/// A backend driven from synchronous code.
+///
+/// Each call parks the calling thread until the backend's future
+/// completes, so it must not be used on an executor's thread, nor in a
+/// browser, where nothing could wake it.
+pub struct Blocking<B> {
+    backend: B,
+}
+
+impl<B: VaultBackend> Blocking<B> {
+    pub fn new(backend: B) -> Self {
+        Self { backend }
+    }
+
+    /// Returns the backend being driven.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Reads the vault file; see `VaultBackend::load`.
+    pub fn load(&self) -> Result<Vec<u8>, B::Error> {
+        block_on(self.backend.load())
+    }
+
+    /// Replaces the vault file; see `VaultBackend::save`.
+    pub fn save(&self, data: &[u8]) -> Result<(), B::Error> {
+        block_on(self.backend.save(data))
+    }
+
+    /// Unlocks the vault; see `open`.
+    pub fn open(&self, credential: &[u8]) -> Result<(Vault, Zeroizing<[u8; KEY_SIZE]>), B::Error> {
+        block_on(open(&self.backend, credential))
+    }
+
+    /// Encrypts and saves the vault; see `save`.
+    pub fn save_vault(&self, vault: &Vault, key: &[u8; KEY_SIZE]) -> Result<(), B::Error> {
+        block_on(save(&self.backend, vault, key))
+    }
+}
+
+/// Wakes a thread parked in `block_on`.
+struct Unparker(Thread);
+
+impl Wake for Unparker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls `future` to completion on the current thread, parking it while
+/// the future is pending.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(Unparker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        thread::park();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// A backend keeping the vault file in memory, whose operations are
+    /// pending once before completing, as on a real executor.
+    #[derive(Default)]
+    struct Memory {
+        file: Mutex<Option<Vec<u8>>>,
+    }
+
+    /// Returns `Pending` the first time it is polled, waking itself.
+    async fn yield_once() {
+        let mut yielded = false;
+        std::future::poll_fn(|cx| {
+            if yielded {
+                Poll::Ready(())
+            } else {
+                yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    impl VaultBackend for Memory {
+        type Error = VaultError;
+
+        async fn load(&self) -> Result<Vec<u8>, VaultError> {
+            yield_once().await;
+            self.file
+                .lock()
+                .unwrap()
+                .clone()
+                .ok_or(VaultError::CorruptedVault)
+        }
+
+        async fn save(&self, data: &[u8]) -> Result<(), VaultError> {
+            yield_once().await;
+            *self.file.lock().unwrap() = Some(data.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_blocking_roundtrip() {
+        let (mut vault, key) =
+            vault::create_vault_with_kdf(b"password", crate::crypto::KdfParams::BROWSER).unwrap();
+        vault.init_project("app").unwrap();
+        vault
+            .add_secret("app", "API_KEY", b"sk-123", &key, None)
+            .unwrap();
+
+        let storage = Blocking::new(Memory::default());
+        assert!(storage.load().is_err());
+        storage.save_vault(&vault, &key).unwrap();
+
+        let (mut opened, opened_key) = storage.open(b"password").unwrap();
+        assert_eq!(*opened_key, *key);
+        opened.unseal_all(&opened_key).unwrap();
+        assert_eq!(
+            opened
+                .get_secret("app", "API_KEY", &opened_key)
+                .unwrap()
+                .expose(),
+            b"sk-123"
+        );
+        assert!(matches!(
+            storage.open(b"wrong"),
+            Err(VaultError::AuthenticationFailed)
+        ));
+    }
+}
@@ -0,0 +1,474 @@
+//! C bindings for vx-core.
+//!
+//! Enabled with the `capi` feature. The build script then writes
+//! `include/vx_core.h` with cbindgen, and the `cdylib` output can be linked
+//! from C, C++, Swift, Go and anything else with a C FFI.
+//!
+//! # Conventions
+//! - Every function returns a `VxStatus`; on failure a description is
+//!   available from `vx_last_error` on the same thread
+//! - Results are written through out-pointers and left untouched on failure
+//! - Byte buffers returned by the library are owned by the caller and must be
+//!   released with `vx_buffer_free`, which wipes them first
+//! - Strings are NUL-terminated UTF-8
+//! - Panics never cross the boundary; they are reported as `VX_STATUS_ERROR`
+//!
+//! The status values and struct layouts are part of the ABI: new statuses
+//! and functions may be added, existing ones are never renumbered or changed.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use zeroize::Zeroize;
+
+use crate::crypto::{self, EncryptedData, KEY_SIZE, NONCE_SIZE, SALT_SIZE};
+use crate::error::{CryptoError, VaultError};
+use crate::memory::SecureKey;
+use crate::vault::{self, Vault};
+
+/// Size of an encryption key in bytes
+pub const VX_KEY_SIZE: usize = 32;
+
+/// Size of a key derivation salt in bytes
+pub const VX_SALT_SIZE: usize = 32;
+
+const _: () = assert!(VX_KEY_SIZE == KEY_SIZE && VX_SALT_SIZE == SALT_SIZE);
+
+/// Result of every C API call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VxStatus {
+    Ok = 0,
+    /// A null pointer, wrong length or invalid UTF-8 was passed
+    InvalidArgument = 1,
+    /// Wrong password or key, or the data has been tampered with
+    AuthenticationFailed = 2,
+    /// The project or secret does not exist
+    NotFound = 3,
+    /// The secret's TTL has run out
+    Expired = 4,
+    /// The project is protected by a passphrase
+    Locked = 5,
+    /// The vault file could not be read
+    Io = 6,
+    /// Any other failure
+    Error = 255,
+}
+
+/// A byte buffer allocated by the library.
+///
+/// Release it with `vx_buffer_free`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct VxBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// An opened vault holding its data key. Opaque to C callers.
+pub struct VxVault {
+    vault: Vault,
+    key: SecureKey,
+}
+
+/// A failure inside the library, reported as a status plus message.
+struct Failure {
+    status: VxStatus,
+    message: String,
+}
+
+impl Failure {
+    fn invalid(message: &str) -> Self {
+        Self {
+            status: VxStatus::InvalidArgument,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<VaultError> for Failure {
+    fn from(e: VaultError) -> Self {
+        let status = match &e {
+            VaultError::AuthenticationFailed
+            | VaultError::CorruptedVault
+            | VaultError::InvalidProjectPassphrase(_)
+            | VaultError::CryptoError(CryptoError::DecryptionFailed) => {
+                VxStatus::AuthenticationFailed
+            }
+            VaultError::ProjectNotFound(_)
+            | VaultError::SecretNotFound(_)
+            | VaultError::EnvironmentNotFound(_)
+            | VaultError::DanglingAlias(_) => VxStatus::NotFound,
+            VaultError::SecretExpired(_) => VxStatus::Expired,
+            VaultError::ProjectLocked(_) => VxStatus::Locked,
+            _ => VxStatus::Error,
+        };
+
+        Self {
+            status,
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<CryptoError> for Failure {
+    fn from(e: CryptoError) -> Self {
+        VaultError::CryptoError(e).into()
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, converting its result and any panic into a status.
+fn run<F>(f: F) -> VxStatus
+where
+    F: FnOnce() -> Result<(), Failure>,
+{
+    let failure = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => {
+            LAST_ERROR.with(|last| last.borrow_mut().take());
+            return VxStatus::Ok;
+        }
+        Ok(Err(failure)) => failure,
+        Err(_) => Failure {
+            status: VxStatus::Error,
+            message: "internal error (panic)".to_string(),
+        },
+    };
+
+    // Messages never contain NUL; strip any just in case
+    let message = CString::new(failure.message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    failure.status
+}
+
+/// Borrows `len` bytes at `data`. A null pointer is accepted for `len == 0`.
+unsafe fn bytes<'a>(data: *const u8, len: usize, name: &str) -> Result<&'a [u8], Failure> {
+    if data.is_null() {
+        return if len == 0 {
+            Ok(&[])
+        } else {
+            Err(Failure::invalid(&format!("{} is null", name)))
+        };
+    }
+    Ok(std::slice::from_raw_parts(data, len))
+}
+
+/// Borrows a 32-byte key.
+unsafe fn key_array<'a>(key: *const u8) -> Result<&'a [u8; KEY_SIZE], Failure> {
+    if key.is_null() {
+        return Err(Failure::invalid("key is null"));
+    }
+    Ok(&*(key as *const [u8; KEY_SIZE]))
+}
+
+/// Borrows a NUL-terminated UTF-8 string.
+unsafe fn string<'a>(s: *const c_char, name: &str) -> Result<&'a str, Failure> {
+    if s.is_null() {
+        return Err(Failure::invalid(&format!("{} is null", name)));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| Failure::invalid(&format!("{} is not valid UTF-8", name)))
+}
+
+/// Hands a byte vector over to the caller.
+fn into_buffer(bytes: Vec<u8>) -> VxBuffer {
+    let mut bytes = bytes.into_boxed_slice();
+    let buffer = VxBuffer {
+        data: bytes.as_mut_ptr(),
+        len: bytes.len(),
+    };
+    std::mem::forget(bytes);
+    buffer
+}
+
+/// Writes `value` through an out-pointer.
+unsafe fn write_out<T>(out: *mut T, value: T) -> Result<(), Failure> {
+    if out.is_null() {
+        return Err(Failure::invalid("output pointer is null"));
+    }
+    out.write(value);
+    Ok(())
+}
+
+/// Returns the library version as a static NUL-terminated string.
+#[no_mangle]
+pub extern "C" fn vx_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// Returns a description of the last failure on the calling thread, or null
+/// if the last call succeeded.
+///
+/// The string stays valid until the next call into the library on the same
+/// thread.
+#[no_mangle]
+pub extern "C" fn vx_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Derives a 32-byte key from a password with Argon2id.
+///
+/// # Safety
+/// `password` must point to `password_len` readable bytes, `salt` to
+/// `salt_len` (which must be `VX_SALT_SIZE`) and `out_key` to
+/// `VX_KEY_SIZE` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn vx_derive_key(
+    password: *const u8,
+    password_len: usize,
+    salt: *const u8,
+    salt_len: usize,
+    out_key: *mut u8,
+) -> VxStatus {
+    run(|| {
+        let password = bytes(password, password_len, "password")?;
+        let salt = bytes(salt, salt_len, "salt")?;
+        if salt.len() != SALT_SIZE {
+            return Err(Failure::invalid("Invalid salt length"));
+        }
+        if out_key.is_null() {
+            return Err(Failure::invalid("output pointer is null"));
+        }
+
+        let key = crypto::derive_key(password, salt)?;
+        ptr::copy_nonoverlapping(key.as_ptr(), out_key, KEY_SIZE);
+        Ok(())
+    })
+}
+
+/// Encrypts data with AES-256-GCM.
+///
+/// On success `out` receives nonce (12 bytes) || ciphertext, the same
+/// layout as `wasm_encrypt`.
+///
+/// # Safety
+/// `plaintext` must point to `plaintext_len` readable bytes, `key` to
+/// `VX_KEY_SIZE` readable bytes and `out` to a writable `VxBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn vx_encrypt(
+    plaintext: *const u8,
+    plaintext_len: usize,
+    key: *const u8,
+    out: *mut VxBuffer,
+) -> VxStatus {
+    run(|| {
+        let plaintext = bytes(plaintext, plaintext_len, "plaintext")?;
+        let key = key_array(key)?;
+
+        let encrypted = crypto::encrypt(plaintext, key)?;
+        let mut result = Vec::with_capacity(NONCE_SIZE + encrypted.ciphertext.len());
+        result.extend_from_slice(&encrypted.nonce);
+        result.extend_from_slice(&encrypted.ciphertext);
+
+        write_out(out, into_buffer(result))
+    })
+}
+
+/// Decrypts data produced by `vx_encrypt`.
+///
+/// # Safety
+/// `data` must point to `data_len` readable bytes, `key` to `VX_KEY_SIZE`
+/// readable bytes and `out` to a writable `VxBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn vx_decrypt(
+    data: *const u8,
+    data_len: usize,
+    key: *const u8,
+    out: *mut VxBuffer,
+) -> VxStatus {
+    run(|| {
+        let data = bytes(data, data_len, "data")?;
+        let key = key_array(key)?;
+        if data.len() < NONCE_SIZE {
+            return Err(Failure::invalid("Invalid encrypted data"));
+        }
+
+        let encrypted = EncryptedData {
+            nonce: data[..NONCE_SIZE].try_into().expect("slice has NONCE_SIZE bytes"),
+            ciphertext: data[NONCE_SIZE..].to_vec(),
+        };
+        let plaintext = crypto::decrypt(&encrypted, key)?;
+
+        write_out(out, into_buffer(plaintext.to_vec()))
+    })
+}
+
+/// Opens a vault file with any credential matching one of its key slots.
+/// Every project is unsealed.
+///
+/// On success `out` receives a vault to release with `vx_vault_free`.
+///
+/// # Safety
+/// `path` must be a NUL-terminated string, `credential` must point to
+/// `credential_len` readable bytes and `out` to a writable pointer.
+#[no_mangle]
+pub unsafe extern "C" fn vx_vault_open(
+    path: *const c_char,
+    credential: *const u8,
+    credential_len: usize,
+    out: *mut *mut VxVault,
+) -> VxStatus {
+    run(|| {
+        let path = string(path, "path")?;
+        let credential = bytes(credential, credential_len, "credential")?;
+
+        let data = std::fs::read(path).map_err(|e| Failure {
+            status: VxStatus::Io,
+            message: format!("Cannot read '{}': {}", path, e),
+        })?;
+        let (mut vault, key) = vault::unlock_vault(&data, credential)?;
+        vault.unseal_all(&key)?;
+
+        let opened = Box::new(VxVault {
+            vault,
+            key: SecureKey::new(&key),
+        });
+        write_out(out, Box::into_raw(opened))
+    })
+}
+
+/// Retrieves and decrypts a secret from the default environment of a
+/// project. Aliases and templates are resolved.
+///
+/// # Safety
+/// `vault` must come from `vx_vault_open` and not have been freed;
+/// `project` and `key` must be NUL-terminated strings and `out` must point
+/// to a writable `VxBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn vx_vault_get_secret(
+    vault: *const VxVault,
+    project: *const c_char,
+    key: *const c_char,
+    out: *mut VxBuffer,
+) -> VxStatus {
+    run(|| {
+        let opened = vault
+            .as_ref()
+            .ok_or_else(|| Failure::invalid("vault is null"))?;
+        let project = string(project, "project")?;
+        let key = string(key, "key")?;
+
+        let value = opened.vault.get_secret(project, key, &opened.key)?;
+        write_out(out, into_buffer(value.to_vec()))
+    })
+}
+
+/// Closes a vault, wiping its data key. Null is ignored.
+///
+/// # Safety
+/// `vault` must come from `vx_vault_open` and not already have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn vx_vault_free(vault: *mut VxVault) {
+    if !vault.is_null() {
+        drop(Box::from_raw(vault));
+    }
+}
+
+/// Wipes and releases a buffer returned by the library. A buffer with a null
+/// `data` pointer is ignored.
+///
+/// # Safety
+/// `buffer` must have been returned by this library and not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn vx_buffer_free(buffer: VxBuffer) {
+    if buffer.data.is_null() {
+        return;
+    }
+    let mut bytes = Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len));
+    bytes.zeroize();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_buffer() -> VxBuffer {
+        VxBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_roundtrip() {
+        let key = [3u8; KEY_SIZE];
+        let plaintext = b"hello from C";
+
+        unsafe {
+            let mut encrypted = empty_buffer();
+            let status = vx_encrypt(plaintext.as_ptr(), plaintext.len(), key.as_ptr(), &mut encrypted);
+            assert_eq!(status, VxStatus::Ok);
+            assert_eq!(encrypted.len, NONCE_SIZE + plaintext.len() + 16);
+
+            let mut decrypted = empty_buffer();
+            let status = vx_decrypt(encrypted.data, encrypted.len, key.as_ptr(), &mut decrypted);
+            assert_eq!(status, VxStatus::Ok);
+            assert_eq!(std::slice::from_raw_parts(decrypted.data, decrypted.len), plaintext);
+
+            let wrong = [4u8; KEY_SIZE];
+            let status = vx_decrypt(encrypted.data, encrypted.len, wrong.as_ptr(), &mut decrypted);
+            assert_eq!(status, VxStatus::AuthenticationFailed);
+            assert!(!vx_last_error().is_null());
+
+            vx_buffer_free(encrypted);
+            vx_buffer_free(decrypted);
+        }
+    }
+
+    #[test]
+    fn test_null_arguments_are_rejected() {
+        let mut out = empty_buffer();
+        let status = unsafe { vx_encrypt(ptr::null(), 5, ptr::null(), &mut out) };
+
+        assert_eq!(status, VxStatus::InvalidArgument);
+        let message = unsafe { CStr::from_ptr(vx_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "plaintext is null");
+        assert!(out.data.is_null());
+    }
+
+    #[test]
+    fn test_open_vault_and_get_secret() {
+        let (mut created, key) = vault::create_vault(b"pw").unwrap();
+        created.init_project("app").unwrap();
+        created.add_secret("app", "TOKEN", b"abc", &key, None).unwrap();
+        let data = vault::save_vault_with_key(&created, &key).unwrap();
+
+        let path = std::env::temp_dir().join(format!("vx-capi-{}.vx", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+
+        unsafe {
+            let mut opened: *mut VxVault = ptr::null_mut();
+            let status = vx_vault_open(c_path.as_ptr(), b"nope".as_ptr(), 4, &mut opened);
+            assert_eq!(status, VxStatus::AuthenticationFailed);
+            assert!(opened.is_null());
+
+            let status = vx_vault_open(c_path.as_ptr(), b"pw".as_ptr(), 2, &mut opened);
+            assert_eq!(status, VxStatus::Ok);
+
+            let mut value = empty_buffer();
+            let status = vx_vault_get_secret(opened, c"app".as_ptr(), c"TOKEN".as_ptr(), &mut value);
+            assert_eq!(status, VxStatus::Ok);
+            assert_eq!(std::slice::from_raw_parts(value.data, value.len), b"abc");
+            vx_buffer_free(value);
+
+            let mut missing = empty_buffer();
+            let status = vx_vault_get_secret(opened, c"app".as_ptr(), c"MISSING".as_ptr(), &mut missing);
+            assert_eq!(status, VxStatus::NotFound);
+            assert!(missing.data.is_null());
+
+            vx_vault_free(opened);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
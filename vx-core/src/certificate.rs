@@ -0,0 +1,259 @@
+//! X.509 certificate secrets.
+//!
+//! A certificate stored as a secret takes its expiry from the certificate's
+//! `notAfter`, so audits and expiry notifications cover it like any secret
+//! with a TTL. Only the fields needed for that are read, with a minimal DER
+//! reader; the certificate is not validated, and its signature not checked.
+//!
+//! # Security Notes
+//! - Parsing works on the decrypted value in memory and keeps nothing but
+//!   the validity period and the subject's common name
+
+use crate::error::VaultError;
+use crate::ttl;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use zeroize::Zeroizing;
+
+const PEM_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OID: u8 = 0x06;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+/// `[0] EXPLICIT Version`
+const TAG_VERSION: u8 = 0xa0;
+
+/// Object identifier of the common name attribute (2.5.4.3)
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+/// What a certificate secret records about the certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateInfo {
+    /// Start of the validity period (Unix seconds)
+    pub not_before: u64,
+    /// End of the validity period (Unix seconds)
+    pub not_after: u64,
+    /// Common name of the subject, if it has one
+    pub common_name: Option<String>,
+}
+
+/// Parses a certificate in PEM or DER form. Of a PEM chain, the first
+/// certificate (the leaf) is read.
+///
+/// # Errors
+/// `VaultError::InvalidCertificate` if the value is not an X.509
+/// certificate.
+pub fn parse(value: &[u8]) -> Result<CertificateInfo, VaultError> {
+    match std::str::from_utf8(value) {
+        Ok(text) if text.contains(PEM_BEGIN) => parse_der(&pem_to_der(text)?),
+        _ => parse_der(value),
+    }
+}
+
+/// Decodes the first certificate of a PEM file.
+fn pem_to_der(text: &str) -> Result<Zeroizing<Vec<u8>>, VaultError> {
+    let start = text.find(PEM_BEGIN).unwrap_or(0) + PEM_BEGIN.len();
+    let end = text[start..]
+        .find(PEM_END)
+        .ok_or_else(|| invalid("unterminated PEM block"))?;
+    let body: String = text[start..start + end]
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+
+    STANDARD
+        .decode(body)
+        .map(Zeroizing::new)
+        .map_err(|_| invalid("PEM block is not valid base64"))
+}
+
+/// Reads the validity period and subject from a DER certificate.
+fn parse_der(der: &[u8]) -> Result<CertificateInfo, VaultError> {
+    let (certificate, _) = expect(der, TAG_SEQUENCE)?;
+    let (tbs, _) = expect(certificate, TAG_SEQUENCE)?;
+
+    let mut rest = tbs;
+    if rest.first() == Some(&TAG_VERSION) {
+        rest = read(rest)?.2;
+    }
+    let (_, rest) = expect(rest, TAG_INTEGER)?; // serialNumber
+    let (_, rest) = expect(rest, TAG_SEQUENCE)?; // signature
+    let (_, rest) = expect(rest, TAG_SEQUENCE)?; // issuer
+    let (validity, rest) = expect(rest, TAG_SEQUENCE)?;
+    let (subject, _) = expect(rest, TAG_SEQUENCE)?;
+
+    let (not_before, validity) = read_time(validity)?;
+    let (not_after, _) = read_time(validity)?;
+
+    Ok(CertificateInfo {
+        not_before,
+        not_after,
+        common_name: common_name(subject),
+    })
+}
+
+/// Reads a UTCTime or GeneralizedTime in UTC.
+fn read_time(input: &[u8]) -> Result<(u64, &[u8]), VaultError> {
+    let (tag, value, rest) = read(input)?;
+    let text = std::str::from_utf8(value).map_err(|_| invalid("malformed time"))?;
+    let digits = text
+        .strip_suffix('Z')
+        .filter(|d| d.bytes().all(|b| b.is_ascii_digit()))
+        .ok_or_else(|| invalid("time is not in UTC"))?;
+
+    let (year, fields) = match (tag, digits.len()) {
+        // Two-digit years 50-99 are 1950-1999 (RFC 5280)
+        (TAG_UTC_TIME, 12) => {
+            let yy: i64 = digits[..2].parse().unwrap();
+            (if yy < 50 { 2000 + yy } else { 1900 + yy }, &digits[2..])
+        }
+        (TAG_GENERALIZED_TIME, 14) => (digits[..4].parse().unwrap(), &digits[4..]),
+        _ => return Err(invalid("malformed time")),
+    };
+    let field = |i: usize| fields[2 * i..2 * i + 2].parse::<u32>().unwrap();
+
+    let timestamp = ttl::timestamp_from_utc(year, field(0), field(1), field(2), field(3), field(4))
+        .ok_or_else(|| invalid("time out of range"))?;
+    Ok((timestamp, rest))
+}
+
+/// Finds the common name in a Name (a sequence of sets of attributes).
+fn common_name(mut name: &[u8]) -> Option<String> {
+    while !name.is_empty() {
+        let (set, rest) = expect(name, TAG_SET).ok()?;
+        name = rest;
+
+        let mut attributes = set;
+        while !attributes.is_empty() {
+            let (attribute, rest) = expect(attributes, TAG_SEQUENCE).ok()?;
+            attributes = rest;
+
+            let (oid, value) = expect(attribute, TAG_OID).ok()?;
+            if oid == OID_COMMON_NAME {
+                let (_, value, _) = read(value).ok()?;
+                return String::from_utf8(value.to_vec()).ok();
+            }
+        }
+    }
+    None
+}
+
+/// Reads one element with tag `tag`, returning its contents and the input
+/// after it.
+fn expect(input: &[u8], tag: u8) -> Result<(&[u8], &[u8]), VaultError> {
+    match read(input)? {
+        (found, value, rest) if found == tag => Ok((value, rest)),
+        _ => Err(invalid("unexpected DER structure")),
+    }
+}
+
+/// Reads one DER element: its tag, contents and the input after it.
+fn read(input: &[u8]) -> Result<(u8, &[u8], &[u8]), VaultError> {
+    let truncated = || invalid("truncated DER");
+    let (&tag, rest) = input.split_first().ok_or_else(truncated)?;
+    let (&first, rest) = rest.split_first().ok_or_else(truncated)?;
+
+    let (len, rest) = if first < 0x80 {
+        (usize::from(first), rest)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count == 0 || count > 4 || rest.len() < count {
+            return Err(invalid("unsupported DER length"));
+        }
+        let len = rest[..count]
+            .iter()
+            .fold(0usize, |len, &b| (len << 8) | usize::from(b));
+        (len, &rest[count..])
+    };
+
+    if rest.len() < len {
+        return Err(truncated());
+    }
+    Ok((tag, &rest[..len], &rest[len..]))
+}
+
+fn invalid(reason: &str) -> VaultError {
+    VaultError::InvalidCertificate(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Self-signed, CN=api.example.com, valid 2026-10-16 15:22:05 to
+    /// 2036-10-13 15:22:05 (UTCTime)
+    const LEAF: &str = "-----BEGIN CERTIFICATE-----
+MIIBrjCCAVOgAwIBAgIUUJgZYCQlr8ob4b7oEBvN8iBqouAwCgYIKoZIzj0EAwIw
+LDEYMBYGA1UEAwwPYXBpLmV4YW1wbGUuY29tMRAwDgYDVQQKDAdFeGFtcGxlMB4X
+DTI2MTAxNjE1MjIwNVoXDTM2MTAxMzE1MjIwNVowLDEYMBYGA1UEAwwPYXBpLmV4
+YW1wbGUuY29tMRAwDgYDVQQKDAdFeGFtcGxlMFkwEwYHKoZIzj0CAQYIKoZIzj0D
+AQcDQgAE3MBZ+e1tUJCYp+jq71XiHaTuDbk6eP8jFmh304dDxQ//hcWsMCMBFBum
+1nHzUuzMJV4N5cPaOMiFoOBTyeXVo6NTMFEwHQYDVR0OBBYEFN2rTaEOyJPdrFGX
+mrKKEFP6iopJMB8GA1UdIwQYMBaAFN2rTaEOyJPdrFGXmrKKEFP6iopJMA8GA1Ud
+EwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDSQAwRgIhAIbrIXwQ/r8MAksmxgm12bms
+9YHgntUIB8RrZPIbOKm8AiEAkEs9Xu+XVuRRDTfYoWA6+uFic22CtpLpOA/Ym0U5
+XT4=
+-----END CERTIFICATE-----
+";
+
+    /// Self-signed, O=NoCommonName, valid until 2061-01-05 15:22:08
+    /// (GeneralizedTime)
+    const NO_COMMON_NAME: &str = "-----BEGIN CERTIFICATE-----
+MIIBhTCCASugAwIBAgIUXic3bz2hKqfEtcQu2ID3EN/5laowCgYIKoZIzj0EAwIw
+FzEVMBMGA1UECgwMTm9Db21tb25OYW1lMCAXDTI2MTAxNjE1MjIwOFoYDzIwNjEw
+MTA1MTUyMjA4WjAXMRUwEwYDVQQKDAxOb0NvbW1vbk5hbWUwWTATBgcqhkjOPQIB
+BggqhkjOPQMBBwNCAASZMYozvMdfumy/MssiUfjG/GOt0fuKh7uB/A8QJguRfA8Q
+/r5ZwzUyS9HY1kCSfecOKAjBWuHMv1FUEV7RyNLio1MwUTAdBgNVHQ4EFgQUEOhB
+xccnivEHCciATjlm5jNjhpEwHwYDVR0jBBgwFoAUEOhBxccnivEHCciATjlm5jNj
+hpEwDwYDVR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNIADBFAiBtq3TdVK3Yq+Pi
+gMv7i9wltPbmisUlx2wFEt1z/LVuJwIhAKuBr7Z2iAttgMu36KvZUOYi8JN8NOA4
+TOpYhOACBKyH
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn test_parse_pem() {
+        let info = parse(LEAF.as_bytes()).unwrap();
+
+        assert_eq!(info.not_before, 1_792_164_125);
+        assert_eq!(info.not_after, 2_107_524_125);
+        assert_eq!(info.common_name.as_deref(), Some("api.example.com"));
+    }
+
+    #[test]
+    fn test_parse_der_and_generalized_time() {
+        let der = pem_to_der(NO_COMMON_NAME).unwrap();
+        let info = parse(&der).unwrap();
+
+        assert_eq!(info.not_before, 1_792_164_128);
+        assert_eq!(info.not_after, 2_872_164_128);
+        assert_eq!(info.common_name, None);
+    }
+
+    #[test]
+    fn test_parse_chain_reads_leaf() {
+        let chain = format!("{}{}", LEAF, NO_COMMON_NAME);
+        assert_eq!(
+            parse(chain.as_bytes()).unwrap(),
+            parse(LEAF.as_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_other_values() {
+        for value in [
+            &b"not a certificate"[..],
+            b"",
+            b"\x30\x82\xff\xff",
+            b"-----BEGIN CERTIFICATE-----\nAAAA",
+        ] {
+            assert!(matches!(
+                parse(value),
+                Err(VaultError::InvalidCertificate(_))
+            ));
+        }
+    }
+}
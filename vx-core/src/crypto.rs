@@ -2,20 +2,32 @@
 //!
 //! This module provides:
 //! - Key derivation using Argon2id
+//! - Subkey derivation using HKDF-SHA256
 //! - Encryption/decryption using AES-256-GCM
 //!
 //! # Security Notes
 //! - Argon2id is used with 64MB memory cost and 3 iterations
+//! - New keys use one Argon2 lane per available core, computed in parallel;
+//!   on wasm32, where threads may be unavailable, they use a single lane
 //! - Each encryption uses a unique random 96-bit nonce
 //! - Nonces are stored alongside ciphertext
+//! - Derived keys and decrypted plaintext are zeroized when dropped
 
 use crate::error::CryptoError;
 use aes_gcm::{
-    aead::{Aead, KeyInit},
+    aead::{Aead, KeyInit, Payload},
     Aes256Gcm, Nonce,
 };
+#[cfg(target_arch = "wasm32")]
 use argon2::{Algorithm, Argon2, Params, Version};
+use hkdf::Hkdf;
 use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+use std::ops::Deref;
+use std::time::{Duration, Instant};
+use zeroize::{Zeroize, Zeroizing};
 
 /// Size of the encryption key in bytes (256 bits)
 pub const KEY_SIZE: usize = 32;
@@ -23,6 +35,9 @@ pub const KEY_SIZE: usize = 32;
 /// Size of the nonce in bytes (96 bits)
 pub const NONCE_SIZE: usize = 12;
 
+/// Size of the authentication tag AES-GCM appends to a ciphertext
+pub const TAG_SIZE: usize = 16;
+
 /// Size of the salt in bytes
 pub const SALT_SIZE: usize = 32;
 
@@ -35,6 +50,92 @@ const ARGON2_ITERATIONS: u32 = 3;
 /// Argon2 parallelism
 const ARGON2_PARALLELISM: u32 = 4;
 
+/// Most lanes a new key is derived with, however many cores there are
+const MAX_LANES: u32 = 8;
+
+/// Argon2id cost parameters.
+///
+/// Stored with each key slot, so a vault created with a lighter preset (in
+/// a browser, say) is still unlocked with the parameters it was made with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory cost in KiB
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    /// The default: 64 MB, 3 iterations, 4 lanes.
+    pub const STANDARD: KdfParams = KdfParams {
+        memory_kib: ARGON2_MEMORY_COST,
+        iterations: ARGON2_ITERATIONS,
+        parallelism: ARGON2_PARALLELISM,
+    };
+
+    /// A lighter preset for browsers, where WASM runs single-threaded:
+    /// 19 MB, 2 iterations, 1 lane (the OWASP minimum for Argon2id).
+    pub const BROWSER: KdfParams = KdfParams {
+        memory_kib: 19456,
+        iterations: 2,
+        parallelism: 1,
+    };
+
+    /// A much heavier preset for vaults holding credentials that justify
+    /// multi-second unlocks: 1 GB, 4 iterations, 4 lanes.
+    pub const PARANOID: KdfParams = KdfParams {
+        memory_kib: 1024 * 1024,
+        iterations: 4,
+        parallelism: 4,
+    };
+
+    /// Looks up a preset by name: `"standard"`, `"browser"` or
+    /// `"paranoid"`.
+    pub fn preset(name: &str) -> Option<KdfParams> {
+        match name {
+            "standard" => Some(Self::STANDARD),
+            "browser" => Some(Self::BROWSER),
+            "paranoid" => Some(Self::PARANOID),
+            _ => None,
+        }
+    }
+
+    /// The standard preset with one lane per core this platform can use,
+    /// see `available_lanes`. Used for new vaults and key slots; the lane
+    /// count is recorded with the slot, so the key is derived the same way
+    /// wherever the vault is opened.
+    pub fn for_this_platform() -> KdfParams {
+        KdfParams {
+            parallelism: available_lanes(),
+            ..Self::STANDARD
+        }
+    }
+
+    /// Returns the work an attacker spends per guess, in KiB × iterations,
+    /// for comparing parameter sets.
+    pub fn cost(&self) -> u64 {
+        u64::from(self.memory_kib) * u64::from(self.iterations)
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+/// Returns how many Argon2 lanes this platform computes at once: the
+/// available cores, at most `MAX_LANES`, on native targets, and one on
+/// wasm32.
+pub fn available_lanes() -> u32 {
+    if cfg!(target_arch = "wasm32") {
+        return 1;
+    }
+    std::thread::available_parallelism()
+        .map_or(1, |cores| cores.get() as u32)
+        .clamp(1, MAX_LANES)
+}
+
 /// Encrypted data containing ciphertext and nonce.
 #[derive(Debug, Clone)]
 pub struct EncryptedData {
@@ -42,6 +143,50 @@ pub struct EncryptedData {
     pub nonce: [u8; NONCE_SIZE],
 }
 
+/// Sensitive plaintext (decrypted secrets, passwords, private keys).
+///
+/// The buffer is wiped when dropped and never printed by `Debug`.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretBytes(Vec<u8>);
+
+impl SecretBytes {
+    /// Wraps an existing buffer, taking ownership of it.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns the plaintext bytes.
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes([REDACTED; {} bytes])", self.0.len())
+    }
+}
+
 /// Generates a random salt for key derivation.
 pub fn generate_salt() -> [u8; SALT_SIZE] {
     let mut salt = [0u8; SALT_SIZE];
@@ -49,6 +194,15 @@ pub fn generate_salt() -> [u8; SALT_SIZE] {
     salt
 }
 
+/// Generates a random 256-bit key (e.g. a vault data key).
+///
+/// The returned key is zeroized when dropped.
+pub fn generate_key() -> Zeroizing<[u8; KEY_SIZE]> {
+    let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+    rand::thread_rng().fill_bytes(key.as_mut());
+    key
+}
+
 /// Generates a random nonce for encryption.
 fn generate_nonce() -> [u8; NONCE_SIZE] {
     let mut nonce = [0u8; NONCE_SIZE];
@@ -67,25 +221,135 @@ fn generate_nonce() -> [u8; NONCE_SIZE] {
 /// - 64 MB memory cost
 /// - 3 iterations
 /// - 4 parallelism lanes
-pub fn derive_key(password: &[u8], salt: &[u8]) -> Result<[u8; KEY_SIZE], CryptoError> {
-    let params = Params::new(
-        ARGON2_MEMORY_COST,
-        ARGON2_ITERATIONS,
-        ARGON2_PARALLELISM,
-        Some(KEY_SIZE),
-    )
-    .map_err(|_| CryptoError::KeyDerivationFailed)?;
+///
+/// The returned key is zeroized when dropped.
+pub fn derive_key(password: &[u8], salt: &[u8]) -> Result<Zeroizing<[u8; KEY_SIZE]>, CryptoError> {
+    derive_key_with_params(password, salt, &KdfParams::STANDARD)
+}
+
+/// Derives an encryption key like `derive_key`, with explicit Argon2id
+/// cost parameters.
+///
+/// On native targets each lane is computed on its own thread; on wasm32
+/// the lanes are computed one after the other. Both give the same key.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn derive_key_with_params(
+    password: &[u8],
+    salt: &[u8],
+    kdf: &KdfParams,
+) -> Result<Zeroizing<[u8; KEY_SIZE]>, CryptoError> {
+    use rust_argon2::{Config, ThreadMode, Variant, Version};
+
+    let config = Config {
+        variant: Variant::Argon2id,
+        version: Version::Version13,
+        mem_cost: kdf.memory_kib,
+        time_cost: kdf.iterations,
+        lanes: kdf.parallelism,
+        thread_mode: ThreadMode::from_threads(kdf.parallelism),
+        secret: &[],
+        ad: &[],
+        hash_length: KEY_SIZE as u32,
+    };
+    let mut hash = rust_argon2::hash_raw(password, salt, &config)
+        .map_err(|_| CryptoError::KeyDerivationFailed)?;
+
+    let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+    key.copy_from_slice(&hash);
+    hash.zeroize();
+
+    Ok(key)
+}
+
+/// Derives an encryption key like `derive_key`, with explicit Argon2id
+/// cost parameters.
+///
+/// On native targets each lane is computed on its own thread; on wasm32
+/// the lanes are computed one after the other. Both give the same key.
+#[cfg(target_arch = "wasm32")]
+pub fn derive_key_with_params(
+    password: &[u8],
+    salt: &[u8],
+    kdf: &KdfParams,
+) -> Result<Zeroizing<[u8; KEY_SIZE]>, CryptoError> {
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(KEY_SIZE))
+        .map_err(|_| CryptoError::KeyDerivationFailed)?;
 
     let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
 
-    let mut key = [0u8; KEY_SIZE];
+    let mut key = Zeroizing::new([0u8; KEY_SIZE]);
     argon2
-        .hash_password_into(password, salt, &mut key)
+        .hash_password_into(password, salt, key.as_mut())
         .map_err(|_| CryptoError::KeyDerivationFailed)?;
 
     Ok(key)
 }
 
+/// Picks Argon2id parameters that take about `target` to derive a key on
+/// this machine, using `memory_kib` of memory and one lane per available
+/// core (see `available_lanes`).
+///
+/// Times single-pass derivations (best of three) and scales the number of
+/// passes to the target, with at least one. Relies on the system clock, so
+/// it is not meant for WASM.
+pub fn calibrate_kdf(target: Duration, memory_kib: u32) -> Result<KdfParams, CryptoError> {
+    let single_pass = KdfParams {
+        memory_kib,
+        iterations: 1,
+        parallelism: available_lanes(),
+    };
+    let salt = generate_salt();
+
+    let mut fastest = Duration::MAX;
+    for _ in 0..3 {
+        let start = Instant::now();
+        derive_key_with_params(b"calibration", &salt, &single_pass)?;
+        fastest = fastest.min(start.elapsed());
+    }
+
+    let passes = target.as_secs_f64() / fastest.as_secs_f64().max(f64::EPSILON);
+    Ok(KdfParams {
+        iterations: (passes.round() as u32).max(1),
+        ..single_pass
+    })
+}
+
+/// Derives an independent subkey from a master key using HKDF-SHA256.
+///
+/// # Arguments
+/// * `key` - The master key (output of `derive_key`)
+/// * `context` - Domain separation label; distinct labels yield unrelated keys
+///
+/// The returned key is zeroized when dropped.
+pub fn derive_subkey(key: &[u8; KEY_SIZE], context: &[u8]) -> Zeroizing<[u8; KEY_SIZE]> {
+    let hkdf = Hkdf::<Sha256>::new(None, key);
+
+    let mut subkey = Zeroizing::new([0u8; KEY_SIZE]);
+    hkdf.expand(context, subkey.as_mut())
+        .expect("KEY_SIZE is a valid HKDF-SHA256 output length");
+
+    subkey
+}
+
+/// Derives a key from two independent keys with HKDF-SHA256, so that
+/// neither key alone is enough to compute it.
+///
+/// Used to layer a second secret (e.g. a project passphrase) on top of the
+/// master key. `context` domain-separates the result as in `derive_subkey`.
+pub fn combine_keys(
+    key: &[u8; KEY_SIZE],
+    layer: &[u8; KEY_SIZE],
+    context: &[u8],
+) -> Zeroizing<[u8; KEY_SIZE]> {
+    let hkdf = Hkdf::<Sha256>::new(Some(layer), key);
+
+    let mut combined = Zeroizing::new([0u8; KEY_SIZE]);
+    hkdf.expand(context, combined.as_mut())
+        .expect("KEY_SIZE is a valid HKDF-SHA256 output length");
+
+    combined
+}
+
 /// Encrypts plaintext using AES-256-GCM.
 ///
 /// # Arguments
@@ -99,13 +363,25 @@ pub fn derive_key(password: &[u8], salt: &[u8]) -> Result<[u8; KEY_SIZE], Crypto
 /// - Uses a unique random nonce for each encryption
 /// - Nonce must be stored with ciphertext for decryption
 pub fn encrypt(plaintext: &[u8], key: &[u8; KEY_SIZE]) -> Result<EncryptedData, CryptoError> {
+    encrypt_with_aad(plaintext, key, &[])
+}
+
+/// Encrypts plaintext using AES-256-GCM, authenticating `aad` alongside it.
+///
+/// The associated data is not encrypted or stored; the same bytes must be
+/// passed to `decrypt_with_aad`.
+pub fn encrypt_with_aad(
+    plaintext: &[u8],
+    key: &[u8; KEY_SIZE],
+    aad: &[u8],
+) -> Result<EncryptedData, CryptoError> {
     let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| CryptoError::InvalidKeyLength)?;
 
     let nonce_bytes = generate_nonce();
     let nonce = Nonce::from_slice(&nonce_bytes);
 
     let ciphertext = cipher
-        .encrypt(nonce, plaintext)
+        .encrypt(nonce, Payload { msg: plaintext, aad })
         .map_err(|_| CryptoError::EncryptionFailed)?;
 
     Ok(EncryptedData {
@@ -121,18 +397,34 @@ pub fn encrypt(plaintext: &[u8], key: &[u8; KEY_SIZE]) -> Result<EncryptedData,
 /// * `key` - 256-bit encryption key
 ///
 /// # Returns
-/// Decrypted plaintext
+/// Decrypted plaintext, zeroized when dropped
 ///
 /// # Security
 /// - Verifies authentication tag before returning plaintext
 /// - Returns generic error on failure (prevents oracle attacks)
-pub fn decrypt(encrypted: &EncryptedData, key: &[u8; KEY_SIZE]) -> Result<Vec<u8>, CryptoError> {
+pub fn decrypt(encrypted: &EncryptedData, key: &[u8; KEY_SIZE]) -> Result<SecretBytes, CryptoError> {
+    decrypt_with_aad(encrypted, key, &[])
+}
+
+/// Decrypts ciphertext produced by `encrypt_with_aad`.
+///
+/// Fails if either the ciphertext or `aad` has been modified.
+pub fn decrypt_with_aad(
+    encrypted: &EncryptedData,
+    key: &[u8; KEY_SIZE],
+    aad: &[u8],
+) -> Result<SecretBytes, CryptoError> {
     let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| CryptoError::InvalidKeyLength)?;
 
     let nonce = Nonce::from_slice(&encrypted.nonce);
+    let payload = Payload {
+        msg: encrypted.ciphertext.as_ref(),
+        aad,
+    };
 
     cipher
-        .decrypt(nonce, encrypted.ciphertext.as_ref())
+        .decrypt(nonce, payload)
+        .map(SecretBytes::new)
         .map_err(|_| CryptoError::DecryptionFailed)
 }
 
@@ -165,6 +457,62 @@ mod tests {
         assert_ne!(key1, key2);
     }
 
+    #[test]
+    fn test_kdf_params_change_the_key() {
+        let salt = generate_salt();
+
+        let standard = derive_key(b"pw", &salt).unwrap();
+        let browser = derive_key_with_params(b"pw", &salt, &KdfParams::BROWSER).unwrap();
+
+        assert_eq!(standard, derive_key_with_params(b"pw", &salt, &KdfParams::STANDARD).unwrap());
+        assert_ne!(standard, browser);
+        assert_eq!(KdfParams::preset("browser"), Some(KdfParams::BROWSER));
+        assert_eq!(KdfParams::preset("paranoid"), Some(KdfParams::PARANOID));
+        assert_eq!(KdfParams::preset("fast"), None);
+        assert!(KdfParams::PARANOID.cost() > KdfParams::STANDARD.cost());
+    }
+
+    #[test]
+    fn test_calibrate_kdf() {
+        let params = calibrate_kdf(Duration::from_millis(20), 1024).unwrap();
+        assert_eq!(params.memory_kib, 1024);
+        assert_eq!(params.parallelism, available_lanes());
+        assert!(params.iterations >= 1);
+
+        // Too little memory for even a single lane
+        assert!(calibrate_kdf(Duration::from_millis(20), 4).is_err());
+    }
+
+    #[test]
+    fn test_parallel_lanes_match_sequential_derivation() {
+        // Keys derived on threads must open vaults in a browser, where the
+        // lanes are computed by the `argon2` crate one after the other
+        let kdf = KdfParams {
+            memory_kib: 1024,
+            iterations: 2,
+            parallelism: 4,
+        };
+        let salt = generate_salt();
+        let key = derive_key_with_params(b"password", &salt, &kdf).unwrap();
+
+        let params = argon2::Params::new(1024, 2, 4, Some(KEY_SIZE)).unwrap();
+        let mut expected = [0u8; KEY_SIZE];
+        argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+            .hash_password_into(b"password", &salt, &mut expected)
+            .unwrap();
+        assert_eq!(*key, expected);
+    }
+
+    #[test]
+    fn test_platform_kdf_uses_available_lanes() {
+        let lanes = available_lanes();
+        assert!((1..=MAX_LANES).contains(&lanes));
+
+        let kdf = KdfParams::for_this_platform();
+        assert_eq!(kdf.parallelism, lanes);
+        assert_eq!(kdf.cost(), KdfParams::STANDARD.cost());
+    }
+
     #[test]
     fn test_encrypt_decrypt_roundtrip() {
         let password = b"test_password";
@@ -175,7 +523,7 @@ mod tests {
         let encrypted = encrypt(plaintext, &key).unwrap();
         let decrypted = decrypt(&encrypted, &key).unwrap();
 
-        assert_eq!(decrypted, plaintext);
+        assert_eq!(decrypted.expose(), plaintext);
     }
 
     #[test]
@@ -191,6 +539,51 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_aad_is_authenticated() {
+        let key = generate_key();
+
+        let encrypted = encrypt_with_aad(b"payload", &key, b"header").unwrap();
+        assert_eq!(
+            decrypt_with_aad(&encrypted, &key, b"header").unwrap().expose(),
+            b"payload"
+        );
+        assert!(decrypt_with_aad(&encrypted, &key, b"HEADER").is_err());
+        assert!(decrypt(&encrypted, &key).is_err());
+    }
+
+    #[test]
+    fn test_combined_key_needs_both_keys() {
+        let master = [1u8; KEY_SIZE];
+        let layer = [2u8; KEY_SIZE];
+
+        let combined = combine_keys(&master, &layer, b"ctx");
+        assert_ne!(*combined, *combine_keys(&master, &[3u8; KEY_SIZE], b"ctx"));
+        assert_ne!(*combined, *combine_keys(&[3u8; KEY_SIZE], &layer, b"ctx"));
+        assert_ne!(*combined, *derive_subkey(&master, b"ctx"));
+    }
+
+    #[test]
+    fn test_subkeys_are_domain_separated() {
+        let key = [7u8; KEY_SIZE];
+
+        let a = derive_subkey(&key, b"project:a");
+        let b = derive_subkey(&key, b"project:b");
+
+        assert_eq!(a, derive_subkey(&key, b"project:a"));
+        assert_ne!(a, b);
+        assert_ne!(*a, key);
+    }
+
+    #[test]
+    fn test_secret_bytes_debug_is_redacted() {
+        let secret = SecretBytes::from(b"hunter2".to_vec());
+        let debug = format!("{:?}", secret);
+
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains("7 bytes"));
+    }
+
     #[test]
     fn test_unique_nonces() {
         let key = [0u8; KEY_SIZE];
@@ -0,0 +1,33 @@
+//! Values in `.env` files.
+
+/// Escapes a value for a double-quoted `.env` entry, `KEY="value"`.
+///
+/// `$` is escaped too, so shells and loaders that expand variables in
+/// double quotes take the value literally.
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '$' => escaped.push_str("\\$"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("plain"), "plain");
+        assert_eq!(escape("a\"b\\c"), "a\\\"b\\\\c");
+        assert_eq!(escape("line1\nline2\r"), "line1\\nline2\\r");
+        assert_eq!(escape("pa$$"), "pa\\$\\$");
+    }
+}
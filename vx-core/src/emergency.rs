@@ -0,0 +1,432 @@
+//! Emergency access: a trusted person can open the vault after a waiting
+//! period in which the owner did not object.
+//!
+//! The recipient generates an X25519 key pair and gives the owner the
+//! public key. The owner adds a grant for it, which wraps the data key for
+//! that public key (ephemeral X25519, HKDF-SHA256, AES-256-GCM) and is
+//! stored beside the key slots in the file header, so the recipient can read
+//! it without a password. To use it, the recipient files an
+//! `EmergencyRequest` and waits: `EmergencyGrant::unwrap_key` refuses until
+//! the grant's waiting period has passed since the request, and a veto by
+//! the owner voids every request made before it.
+//!
+//! # Security Notes
+//! - The waiting period is advisory. It is enforced by VaultX, not by the
+//!   cryptography: the recipient's secret key unwraps the grant at any time
+//!   with modified software. Only grant access to someone you would trust
+//!   with the vault.
+//! - The request lives in a file the recipient writes, so its time is only
+//!   a claim. Once the owner's vault has seen a request, its time is kept on
+//!   the grant (`Vault::note_emergency_request`) and the file can no longer
+//!   backdate it; before then, the owner is shown the claimed time.
+//! - Grants and vetoes live in the authenticated header, so they cannot be
+//!   changed without the data key
+//! - A grant wraps the data key itself; it keeps working after the owner's
+//!   password changes, until the grant is revoked
+
+use crate::crypto::{self, EncryptedData, KEY_SIZE, NONCE_SIZE};
+use crate::error::VaultError;
+use crate::ttl;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+/// Prefix of a recipient's public key
+pub const PUBLIC_KEY_PREFIX: &str = "vxep1:";
+
+/// Prefix of a recipient's secret key
+pub const SECRET_KEY_PREFIX: &str = "vxek1:";
+
+/// Domain separation label of the wrapping key
+const WRAP_CONTEXT: &[u8] = b"vaultx emergency grant";
+
+/// Size of an X25519 key in bytes
+const X25519_KEY_SIZE: usize = 32;
+
+/// The data key wrapped for a recipient, usable after a waiting period.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmergencyGrant {
+    /// Stable identifier, unique within the vault
+    pub id: u32,
+    /// Who the grant is for
+    pub label: String,
+    /// Seconds that must pass between a request and access
+    pub wait: u64,
+    pub created_at: u64,
+    /// When the owner last vetoed; requests made until then are void
+    #[serde(default)]
+    pub vetoed_at: Option<u64>,
+    /// Time of the latest request the owner's vault has seen; a request
+    /// file dated earlier was backdated and waits from this time instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requested_at: Option<u64>,
+    #[serde(with = "crate::vault::base64_serde")]
+    recipient: Vec<u8>,
+    #[serde(with = "crate::vault::base64_serde")]
+    ephemeral: Vec<u8>,
+    #[serde(with = "crate::vault::base64_serde")]
+    wrapped_key: Vec<u8>,
+    #[serde(with = "crate::vault::base64_serde")]
+    nonce: Vec<u8>,
+}
+
+/// A recipient's notice that they intend to use a grant, kept next to the
+/// vault file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmergencyRequest {
+    /// Id of the grant
+    pub grant: u32,
+    pub requested_at: u64,
+}
+
+/// Where a grant stands with respect to a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestStatus {
+    /// No request, or one made before the grant
+    NotRequested,
+    /// The owner vetoed after the request was made
+    Vetoed,
+    /// Access opens at `available_at` unless the owner vetoes first
+    Waiting { available_at: u64 },
+    /// The waiting period has passed
+    Available,
+}
+
+impl EmergencyRequest {
+    /// Creates a request for grant `grant`, made now.
+    pub fn new(grant: u32) -> Self {
+        Self {
+            grant,
+            requested_at: ttl::current_timestamp(),
+        }
+    }
+}
+
+impl EmergencyGrant {
+    /// Wraps `data_key` for `recipient`, a public key made by
+    /// `generate_keypair`.
+    pub fn new(
+        id: u32,
+        label: &str,
+        recipient: &str,
+        wait: u64,
+        data_key: &[u8; KEY_SIZE],
+    ) -> Result<Self, VaultError> {
+        let recipient = PublicKey::from(parse_public_key(recipient)?);
+        let ephemeral = StaticSecret::random_from_rng(OsRng);
+        let ephemeral_public = PublicKey::from(&ephemeral);
+
+        let shared = ephemeral.diffie_hellman(&recipient);
+        if !shared.was_contributory() {
+            return Err(VaultError::InvalidEmergencyKey(
+                "not a usable public key".to_string(),
+            ));
+        }
+        let kek = wrapping_key(
+            shared.as_bytes(),
+            ephemeral_public.as_bytes(),
+            recipient.as_bytes(),
+        );
+        let wrapped = crypto::encrypt(data_key, &kek)?;
+
+        Ok(Self {
+            id,
+            label: label.to_string(),
+            wait,
+            created_at: ttl::current_timestamp(),
+            vetoed_at: None,
+            requested_at: None,
+            recipient: recipient.as_bytes().to_vec(),
+            ephemeral: ephemeral_public.as_bytes().to_vec(),
+            wrapped_key: wrapped.ciphertext,
+            nonce: wrapped.nonce.to_vec(),
+        })
+    }
+
+    /// Returns the recipient's public key in the form `generate_keypair`
+    /// prints it.
+    pub fn recipient(&self) -> String {
+        format!(
+            "{}{}",
+            PUBLIC_KEY_PREFIX,
+            URL_SAFE_NO_PAD.encode(&self.recipient)
+        )
+    }
+
+    /// Returns whether the grant was made for the public key belonging to
+    /// `secret_key`.
+    pub fn is_for(&self, secret_key: &str) -> bool {
+        parse_secret_key(secret_key)
+            .map(|secret| PublicKey::from(&secret).as_bytes()[..] == self.recipient[..])
+            .unwrap_or(false)
+    }
+
+    /// Returns where the grant stands given the recipient's `request`.
+    ///
+    /// The waiting period runs from the request's time, or from the time
+    /// recorded on the grant if the request claims an earlier one.
+    pub fn status(&self, request: Option<&EmergencyRequest>, now: u64) -> RequestStatus {
+        let Some(request) = request
+            .filter(|request| request.grant == self.id && request.requested_at >= self.created_at)
+        else {
+            return RequestStatus::NotRequested;
+        };
+        let requested_at = request.requested_at.max(self.requested_at.unwrap_or(0));
+
+        if self.vetoed_at.is_some_and(|vetoed| vetoed >= requested_at) {
+            return RequestStatus::Vetoed;
+        }
+
+        let available_at = requested_at.saturating_add(self.wait);
+        if now < available_at {
+            RequestStatus::Waiting { available_at }
+        } else {
+            RequestStatus::Available
+        }
+    }
+
+    /// Recovers the data key with the recipient's secret key, once the
+    /// waiting period since `request` has passed.
+    ///
+    /// # Errors
+    /// - `VaultError::EmergencyNotRequested`, `VaultError::EmergencyVetoed` or
+    ///   `VaultError::EmergencyWaiting` unless the grant is available
+    /// - `VaultError::AuthenticationFailed` if the grant is not for
+    ///   `secret_key`
+    pub fn unwrap_key(
+        &self,
+        secret_key: &str,
+        request: Option<&EmergencyRequest>,
+        now: u64,
+    ) -> Result<Zeroizing<[u8; KEY_SIZE]>, VaultError> {
+        match self.status(request, now) {
+            RequestStatus::NotRequested => return Err(VaultError::EmergencyNotRequested),
+            RequestStatus::Vetoed => return Err(VaultError::EmergencyVetoed),
+            RequestStatus::Waiting { available_at } => {
+                return Err(VaultError::EmergencyWaiting { available_at })
+            }
+            RequestStatus::Available => {}
+        }
+
+        let secret = parse_secret_key(secret_key)?;
+        let ephemeral: [u8; X25519_KEY_SIZE] = self
+            .ephemeral
+            .as_slice()
+            .try_into()
+            .map_err(|_| VaultError::CorruptedVault)?;
+        let nonce: [u8; NONCE_SIZE] = self
+            .nonce
+            .as_slice()
+            .try_into()
+            .map_err(|_| VaultError::CorruptedVault)?;
+
+        let shared = secret.diffie_hellman(&PublicKey::from(ephemeral));
+        let recipient = PublicKey::from(&secret);
+        let kek = wrapping_key(shared.as_bytes(), &ephemeral, recipient.as_bytes());
+        let encrypted = EncryptedData {
+            ciphertext: self.wrapped_key.clone(),
+            nonce,
+        };
+        let plaintext =
+            crypto::decrypt(&encrypted, &kek).map_err(|_| VaultError::AuthenticationFailed)?;
+
+        let key: [u8; KEY_SIZE] = plaintext
+            .expose()
+            .try_into()
+            .map_err(|_| VaultError::CorruptedVault)?;
+        Ok(Zeroizing::new(key))
+    }
+}
+
+/// Generates a recipient key pair and returns the secret and public key as
+/// text.
+pub fn generate_keypair() -> (Zeroizing<String>, String) {
+    let secret = StaticSecret::random_from_rng(OsRng);
+    let public = PublicKey::from(&secret);
+
+    let secret_bytes = Zeroizing::new(secret.to_bytes());
+    let secret_text = Zeroizing::new(format!(
+        "{}{}",
+        SECRET_KEY_PREFIX,
+        URL_SAFE_NO_PAD.encode(secret_bytes.as_ref())
+    ));
+    let public_text = format!(
+        "{}{}",
+        PUBLIC_KEY_PREFIX,
+        URL_SAFE_NO_PAD.encode(public.as_bytes())
+    );
+    (secret_text, public_text)
+}
+
+/// Parses a recipient public key made by `generate_keypair`.
+pub fn parse_public_key(text: &str) -> Result<[u8; X25519_KEY_SIZE], VaultError> {
+    decode_key(text, PUBLIC_KEY_PREFIX)
+        .map(|bytes| *bytes)
+        .ok_or_else(|| {
+            VaultError::InvalidEmergencyKey("not a VaultX emergency public key".to_string())
+        })
+}
+
+/// Parses a recipient secret key made by `generate_keypair`.
+fn parse_secret_key(text: &str) -> Result<StaticSecret, VaultError> {
+    decode_key(text, SECRET_KEY_PREFIX)
+        .map(|bytes| StaticSecret::from(*bytes))
+        .ok_or_else(|| {
+            VaultError::InvalidEmergencyKey("not a VaultX emergency secret key".to_string())
+        })
+}
+
+/// Decodes the base64 key following `prefix`.
+fn decode_key(text: &str, prefix: &str) -> Option<Zeroizing<[u8; X25519_KEY_SIZE]>> {
+    let decoded = Zeroizing::new(
+        URL_SAFE_NO_PAD
+            .decode(text.trim().strip_prefix(prefix)?)
+            .ok()?,
+    );
+    let key: [u8; X25519_KEY_SIZE] = decoded.as_slice().try_into().ok()?;
+    Some(Zeroizing::new(key))
+}
+
+/// Derives the key wrapping the data key from the X25519 shared secret,
+/// bound to both public keys.
+fn wrapping_key(
+    shared: &[u8; X25519_KEY_SIZE],
+    ephemeral: &[u8; X25519_KEY_SIZE],
+    recipient: &[u8; X25519_KEY_SIZE],
+) -> Zeroizing<[u8; KEY_SIZE]> {
+    let mut context = Vec::with_capacity(WRAP_CONTEXT.len() + 2 * X25519_KEY_SIZE);
+    context.extend_from_slice(WRAP_CONTEXT);
+    context.extend_from_slice(ephemeral);
+    context.extend_from_slice(recipient);
+    crypto::derive_subkey(shared, &context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 86_400;
+
+    fn requested(grant: &EmergencyGrant, at: u64) -> EmergencyRequest {
+        EmergencyRequest {
+            grant: grant.id,
+            requested_at: at,
+        }
+    }
+
+    #[test]
+    fn test_grant_opens_after_wait() {
+        let data_key = crypto::generate_key();
+        let (secret, public) = generate_keypair();
+        let grant = EmergencyGrant::new(1, "alice", &public, 7 * DAY, &data_key).unwrap();
+        assert!(grant.is_for(&secret));
+        assert_eq!(grant.recipient(), public);
+
+        let start = grant.created_at;
+        let request = requested(&grant, start);
+        assert!(matches!(
+            grant.unwrap_key(&secret, None, start),
+            Err(VaultError::EmergencyNotRequested)
+        ));
+        assert!(matches!(
+            grant.unwrap_key(&secret, Some(&request), start + DAY),
+            Err(VaultError::EmergencyWaiting { available_at }) if available_at == start + 7 * DAY
+        ));
+
+        let key = grant
+            .unwrap_key(&secret, Some(&request), start + 7 * DAY)
+            .unwrap();
+        assert_eq!(*key, *data_key);
+    }
+
+    #[test]
+    fn test_grant_rejects_other_keys() {
+        let data_key = crypto::generate_key();
+        let (_, public) = generate_keypair();
+        let (other, _) = generate_keypair();
+        let grant = EmergencyGrant::new(1, "alice", &public, 0, &data_key).unwrap();
+        assert!(!grant.is_for(&other));
+
+        let request = requested(&grant, grant.created_at);
+        assert!(matches!(
+            grant.unwrap_key(&other, Some(&request), grant.created_at),
+            Err(VaultError::AuthenticationFailed)
+        ));
+        assert!(matches!(
+            EmergencyGrant::new(2, "bob", "vxep1:AAAA", 0, &data_key),
+            Err(VaultError::InvalidEmergencyKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_veto_voids_earlier_requests() {
+        let data_key = crypto::generate_key();
+        let (secret, public) = generate_keypair();
+        let mut grant = EmergencyGrant::new(1, "alice", &public, DAY, &data_key).unwrap();
+        let start = grant.created_at;
+
+        grant.vetoed_at = Some(start + 10);
+        let early = requested(&grant, start + 5);
+        assert_eq!(
+            grant.status(Some(&early), start + 2 * DAY),
+            RequestStatus::Vetoed
+        );
+        assert!(matches!(
+            grant.unwrap_key(&secret, Some(&early), start + 2 * DAY),
+            Err(VaultError::EmergencyVetoed)
+        ));
+
+        let late = requested(&grant, start + 20);
+        assert_eq!(
+            grant.status(Some(&late), start + 2 * DAY),
+            RequestStatus::Available
+        );
+
+        // Requests for another grant, or older than the grant, do not count
+        let other = EmergencyRequest {
+            grant: 2,
+            requested_at: start + 20,
+        };
+        assert_eq!(
+            grant.status(Some(&other), start + 2 * DAY),
+            RequestStatus::NotRequested
+        );
+        let stale = requested(&grant, start - 1);
+        assert_eq!(
+            grant.status(Some(&stale), start + 2 * DAY),
+            RequestStatus::NotRequested
+        );
+    }
+
+    #[test]
+    fn test_backdated_request_waits_from_recorded_time() {
+        let data_key = crypto::generate_key();
+        let (secret, public) = generate_keypair();
+        let mut grant = EmergencyGrant::new(1, "alice", &public, 7 * DAY, &data_key).unwrap();
+        let start = grant.created_at;
+
+        // The owner's vault saw the request a day after the grant was made;
+        // the recipient then rewrites the request file to an earlier date
+        grant.requested_at = Some(start + DAY);
+        let backdated = requested(&grant, start);
+        assert_eq!(
+            grant.status(Some(&backdated), start + 7 * DAY),
+            RequestStatus::Waiting {
+                available_at: start + 8 * DAY
+            }
+        );
+        assert!(matches!(
+            grant.unwrap_key(&secret, Some(&backdated), start + 7 * DAY),
+            Err(VaultError::EmergencyWaiting { available_at }) if available_at == start + 8 * DAY
+        ));
+
+        // A veto after the recorded request voids it, however it is dated
+        grant.vetoed_at = Some(start + 2 * DAY);
+        assert_eq!(
+            grant.status(Some(&backdated), start + 9 * DAY),
+            RequestStatus::Vetoed
+        );
+    }
+}
@@ -4,6 +4,7 @@
 //! Error messages must NEVER contain secret values.
 //! Authentication errors should be generic to prevent oracle attacks.
 
+use crate::rules::RuleViolation;
 use thiserror::Error;
 
 /// Errors that can occur during cryptographic operations.
@@ -23,6 +24,9 @@ pub enum CryptoError {
 
     #[error("Invalid key length")]
     InvalidKeyLength,
+
+    #[error("Invalid stream header")]
+    InvalidStreamHeader,
 }
 
 /// Errors that can occur during vault operations.
@@ -37,33 +41,192 @@ pub enum VaultError {
     #[error("Project '{0}' already exists")]
     ProjectAlreadyExists(String),
 
+    #[error("Project '{0}' is sealed; unseal it before use")]
+    ProjectSealed(String),
+
+    #[error("Project '{0}' is protected by a passphrase; unlock it first")]
+    ProjectLocked(String),
+
+    #[error("Invalid passphrase for project '{0}'")]
+    InvalidProjectPassphrase(String),
+
+    #[error("Secret '{0}' already exists")]
+    SecretAlreadyExists(String),
+
+    #[error("Environment '{0}' not found")]
+    EnvironmentNotFound(String),
+
+    #[error("Environment '{0}' already exists")]
+    EnvironmentAlreadyExists(String),
+
+    #[error("Trash entry {0} not found")]
+    TrashEntryNotFound(u32),
+
+    #[error("Nothing to undo")]
+    NothingToUndo,
+
+    #[error("{0} has changed since; the change can no longer be undone")]
+    UndoStale(String),
+
+    #[error("Alias '{0}' refers to a secret that no longer exists")]
+    DanglingAlias(String),
+
+    #[error("Alias '{0}' refers back to itself or is nested too deeply")]
+    AliasCycle(String),
+
+    #[error("Invalid template: {0}")]
+    InvalidTemplate(String),
+
+    #[error("Template '{0}' refers back to itself or is nested too deeply")]
+    TemplateCycle(String),
+
+    #[error("Invalid JSON document: {0}")]
+    InvalidJson(String),
+
+    #[error("No value at path '{0}' in the JSON document")]
+    JsonPathNotFound(String),
+
+    #[error("Invalid certificate: {0}")]
+    InvalidCertificate(String),
+
     #[error("Secret '{0}' has expired")]
     SecretExpired(String),
 
+    #[error("The expiry of '{0}' cannot be changed: {1}")]
+    FixedExpiry(String, &'static str),
+
+    #[error("The TTL of '{0}' is too long")]
+    TtlTooLong(String),
+
+    #[error("Secret '{0}' breaks a rule of its project: {1}")]
+    RuleViolation(String, RuleViolation),
+
+    #[error("Invalid rule pattern '{0}': {1}")]
+    InvalidRule(String, String),
+
+    #[error("Invalid generation policy: {0}")]
+    InvalidPolicy(String),
+
+    #[error("Unknown charset '{0}'; expected alphanumeric, hex, digits, base64url or printable")]
+    UnknownCharset(String),
+
+    #[error("Attachment '{0}' not found")]
+    AttachmentNotFound(String),
+
+    #[error("Attachment '{0}' already exists")]
+    AttachmentAlreadyExists(String),
+
     #[error("SSH identity '{0}' not found")]
     IdentityNotFound(String),
 
     #[error("SSH identity '{0}' already exists")]
     IdentityAlreadyExists(String),
 
+    #[error("SSH identity '{0}' is kept on a PKCS#11 token; its private key cannot be read")]
+    IdentityOnToken(String),
+
     #[error("SSH server '{0}' not found")]
     ServerNotFound(String),
 
     #[error("SSH server '{0}' already exists")]
     ServerAlreadyExists(String),
 
+    #[error("SSH server group '{0}' not found")]
+    ServerGroupNotFound(String),
+
+    #[error("SSH server group '{0}' already exists")]
+    ServerGroupAlreadyExists(String),
+
+    #[error("SSH server group '{0}' needs at least one server")]
+    EmptyServerGroup(String),
+
+    #[error("Kubeconfig '{0}' not found")]
+    KubeconfigNotFound(String),
+
+    #[error("Kubeconfig '{0}' already exists")]
+    KubeconfigAlreadyExists(String),
+
+    #[error("WireGuard config '{0}' not found")]
+    WireguardConfigNotFound(String),
+
+    #[error("WireGuard config '{0}' already exists")]
+    WireguardConfigAlreadyExists(String),
+
+    #[error("Invalid interface name '{0}'; use up to 15 letters, digits or _=+.-")]
+    InvalidInterfaceName(String),
+
+    #[error("Invalid WireGuard config: {0}")]
+    InvalidWireguardConfig(String),
+
+    #[error("Unknown database engine '{0}'; expected postgres, mysql or mongodb")]
+    UnknownDbEngine(String),
+
+    #[error("Invalid database connection: {0}")]
+    InvalidDbConnection(String),
+
+    #[error("Invalid origin '{0}'; expected e.g. https://example.com")]
+    InvalidOrigin(String),
+
+    #[error("Origin '{0}' is not allowed to read that secret")]
+    OriginNotAllowed(String),
+
     #[error("Invalid IP address format: '{0}'")]
     InvalidIpAddress(String),
 
     #[error("Vault file is corrupted or has been tampered with")]
     CorruptedVault,
 
+    #[error("Project '{0}' is stored in a shard file that is missing; it may still be syncing")]
+    MissingShard(String),
+
+    #[error("Vault file is truncated ({found} of {expected} payload bytes); it may still be syncing or was cut off while copying")]
+    TruncatedVault { expected: u64, found: u64 },
+
     #[error("Invalid password or corrupted vault")]
     AuthenticationFailed,
 
+    #[error("Vault is a read-only export and cannot be modified")]
+    ReadOnly,
+
+    #[error("Access log entry {0} has been modified or removed")]
+    AccessLogTampered(usize),
+
+    #[error("Key slot {0} not found")]
+    KeySlotNotFound(u32),
+
+    #[error("All {0} key slots are in use")]
+    KeySlotsFull(usize),
+
+    #[error("Cannot remove the last key slot")]
+    LastKeySlot,
+
+    #[error("Invalid recovery phrase: {0}")]
+    InvalidMnemonic(String),
+
+    #[error("Invalid emergency access key: {0}")]
+    InvalidEmergencyKey(String),
+
+    #[error("Emergency grant {0} not found")]
+    EmergencyGrantNotFound(u32),
+
+    #[error("Emergency access has not been requested")]
+    EmergencyNotRequested,
+
+    #[error("The owner vetoed the emergency access request")]
+    EmergencyVetoed,
+
+    #[error("The emergency access waiting period has not passed yet")]
+    EmergencyWaiting { available_at: u64 },
+
+    #[error("Vault was modified by another process (generation {found}, expected {expected}); reload and try again")]
+    GenerationConflict { expected: u32, found: u32 },
+
     #[error("Invalid vault format: {0}")]
     InvalidFormat(String),
 
+    #[error("Vault format version {0} is newer than this version of VaultX supports; upgrade vx to open it")]
+    UnsupportedVersion(u32),
+
     #[error("Serialization error: {0}")]
     SerializationError(String),
 
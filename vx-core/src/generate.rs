@@ -0,0 +1,287 @@
+//! Random secret values and the policies a project generates them with.
+//!
+//! A `GenerationPolicy` sets how long a generated value is, which
+//! characters it is drawn from and how often it should be rotated. Each
+//! character is drawn uniformly from the charset with `rand::thread_rng`,
+//! which is seeded from the operating system.
+
+use crate::crypto::SecretBytes;
+use crate::error::VaultError;
+use crate::pattern;
+use crate::ttl;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Length of generated values when no policy says otherwise
+pub const DEFAULT_LENGTH: usize = 32;
+
+/// Shortest value a policy may generate
+pub const MIN_LENGTH: usize = 8;
+
+/// Longest value a policy may generate
+pub const MAX_LENGTH: usize = 4096;
+
+/// The characters a value is drawn from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Charset {
+    /// Letters and digits
+    #[default]
+    Alphanumeric,
+    /// Lower-case hexadecimal digits
+    Hex,
+    /// Decimal digits
+    Digits,
+    /// Letters, digits, `-` and `_`, safe in URLs and file names
+    Base64Url,
+    /// Every printable ASCII character but space
+    Printable,
+}
+
+impl Charset {
+    /// Returns the characters of the charset.
+    pub fn chars(self) -> &'static [u8] {
+        const ALPHANUMERIC: &[u8] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+        match self {
+            Charset::Alphanumeric => ALPHANUMERIC,
+            Charset::Hex => b"0123456789abcdef",
+            Charset::Digits => b"0123456789",
+            Charset::Base64Url => {
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_"
+            }
+            Charset::Printable => {
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789\
+                  !\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~"
+            }
+        }
+    }
+
+    /// Returns whether every byte of `value` is in the charset.
+    pub fn contains_all(self, value: &[u8]) -> bool {
+        let chars = self.chars();
+        value.iter().all(|c| chars.contains(c))
+    }
+}
+
+impl FromStr for Charset {
+    type Err = VaultError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "alphanumeric" | "alnum" => Ok(Charset::Alphanumeric),
+            "hex" => Ok(Charset::Hex),
+            "digits" | "numeric" => Ok(Charset::Digits),
+            "base64url" | "urlsafe" => Ok(Charset::Base64Url),
+            "printable" | "ascii" => Ok(Charset::Printable),
+            _ => Err(VaultError::UnknownCharset(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Charset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Charset::Alphanumeric => "alphanumeric",
+            Charset::Hex => "hex",
+            Charset::Digits => "digits",
+            Charset::Base64Url => "base64url",
+            Charset::Printable => "printable",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How a project generates its secrets.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenerationPolicy {
+    pub length: usize,
+    pub charset: Charset,
+    /// Seconds after which a secret is due for rotation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate_after: Option<u64>,
+    /// Glob selecting the keys the policy applies to; all if `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keys: Option<String>,
+}
+
+impl Default for GenerationPolicy {
+    fn default() -> Self {
+        Self {
+            length: DEFAULT_LENGTH,
+            charset: Charset::default(),
+            rotate_after: None,
+            keys: None,
+        }
+    }
+}
+
+/// How a secret departs from its project's generation policy. Never holds
+/// any part of the value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum PolicyViolation {
+    TooShort { length: usize, min: usize },
+    Charset { charset: Charset },
+    RotationDue { rotate_after: u64 },
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyViolation::TooShort { length, min } => write!(
+                f,
+                "its value is {} characters, shorter than the {} of the policy",
+                length, min
+            ),
+            PolicyViolation::Charset { charset } => {
+                write!(
+                    f,
+                    "its value has characters outside the {} charset",
+                    charset
+                )
+            }
+            PolicyViolation::RotationDue { rotate_after } => write!(
+                f,
+                "the policy rotates secrets every {}",
+                ttl::format_duration(*rotate_after)
+            ),
+        }
+    }
+}
+
+impl GenerationPolicy {
+    /// Checks that values of the policy's length can be generated.
+    ///
+    /// # Errors
+    /// `VaultError::InvalidPolicy` if the length is out of
+    /// `MIN_LENGTH..=MAX_LENGTH` or the rotation interval is zero.
+    pub fn validate(&self) -> Result<(), VaultError> {
+        if !(MIN_LENGTH..=MAX_LENGTH).contains(&self.length) {
+            return Err(VaultError::InvalidPolicy(format!(
+                "length must be between {} and {}",
+                MIN_LENGTH, MAX_LENGTH
+            )));
+        }
+        if self.rotate_after == Some(0) {
+            return Err(VaultError::InvalidPolicy(
+                "rotation interval must be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Generates a random value following the policy.
+    pub fn generate(&self) -> SecretBytes {
+        let chars = self.charset.chars();
+        let mut rng = rand::thread_rng();
+        SecretBytes::new(
+            (0..self.length)
+                .map(|_| chars[rng.gen_range(0..chars.len())])
+                .collect(),
+        )
+    }
+
+    /// Returns whether the policy applies to the secret `key`.
+    pub fn applies_to(&self, key: &str) -> bool {
+        self.keys
+            .as_deref()
+            .is_none_or(|keys| pattern::matches(keys, key))
+    }
+
+    /// Checks a stored value against the policy: it must be at least as
+    /// long as the policy's values and drawn from its charset.
+    pub fn check_value(&self, value: &[u8]) -> Option<PolicyViolation> {
+        if value.len() < self.length {
+            return Some(PolicyViolation::TooShort {
+                length: value.len(),
+                min: self.length,
+            });
+        }
+        if !self.charset.contains_all(value) {
+            return Some(PolicyViolation::Charset {
+                charset: self.charset,
+            });
+        }
+        None
+    }
+
+    /// Returns `PolicyViolation::RotationDue` if a secret written at
+    /// `written_at` is due for rotation at `now`.
+    pub fn rotation_due(&self, written_at: u64, now: u64) -> Option<PolicyViolation> {
+        let rotate_after = self.rotate_after?;
+        (now.saturating_sub(written_at) >= rotate_after)
+            .then_some(PolicyViolation::RotationDue { rotate_after })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_values_follow_the_policy() {
+        for charset in [
+            Charset::Alphanumeric,
+            Charset::Hex,
+            Charset::Digits,
+            Charset::Base64Url,
+            Charset::Printable,
+        ] {
+            let policy = GenerationPolicy {
+                length: 40,
+                charset,
+                ..GenerationPolicy::default()
+            };
+            let value = policy.generate();
+            assert_eq!(value.len(), 40);
+            assert!(charset.contains_all(&value), "{}", charset);
+            assert_eq!(policy.check_value(&value), None);
+            assert_eq!(charset.to_string().parse::<Charset>().unwrap(), charset);
+        }
+
+        let a = GenerationPolicy::default().generate();
+        assert_ne!(a, GenerationPolicy::default().generate());
+    }
+
+    #[test]
+    fn test_policy_checks() {
+        let policy = GenerationPolicy {
+            length: 8,
+            charset: Charset::Hex,
+            rotate_after: Some(100),
+            keys: Some("*_TOKEN".to_string()),
+        };
+        assert!(policy.validate().is_ok());
+        assert!(policy.applies_to("API_TOKEN"));
+        assert!(!policy.applies_to("DB_HOST"));
+
+        assert_eq!(
+            policy.check_value(b"abc"),
+            Some(PolicyViolation::TooShort { length: 3, min: 8 })
+        );
+        assert_eq!(
+            policy.check_value(b"0123456789ABCDEF"),
+            Some(PolicyViolation::Charset {
+                charset: Charset::Hex
+            })
+        );
+        assert_eq!(policy.rotation_due(1000, 1099), None);
+        assert!(policy.rotation_due(1000, 1100).is_some());
+
+        let short = GenerationPolicy {
+            length: 4,
+            ..GenerationPolicy::default()
+        };
+        assert!(matches!(
+            short.validate(),
+            Err(VaultError::InvalidPolicy(_))
+        ));
+        assert!(matches!(
+            "emoji".parse::<Charset>(),
+            Err(VaultError::UnknownCharset(_))
+        ));
+    }
+}
@@ -0,0 +1,155 @@
+//! Deterministic file encryption for git clean/smudge filters.
+//!
+//! Files kept encrypted in a repository must encrypt to the same bytes every
+//! time, or git would see them as modified after every checkout. The nonce
+//! is therefore synthetic: the first 96 bits of HMAC-SHA256 of the plaintext
+//! under a key of its own (as in AES-SIV). Identical files still produce
+//! identical ciphertexts, which reveals only that they are identical.
+//!
+//! Both keys are derived with HKDF-SHA256 from a secret stored in the vault,
+//! which may be of any length.
+//!
+//! # Format
+//! ```text
+//! MAGIC (8) || nonce (12) || AES-256-GCM ciphertext and tag
+//! ```
+
+use crate::crypto::{SecretBytes, KEY_SIZE, NONCE_SIZE};
+use crate::error::CryptoError;
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+/// Magic bytes identifying an encrypted file. The leading NUL makes git
+/// treat the file as binary.
+pub const MAGIC: &[u8; 8] = b"\0VXGIT1\0";
+
+/// Size of the header preceding the ciphertext
+pub const HEADER_SIZE: usize = MAGIC.len() + NONCE_SIZE;
+
+/// HKDF labels for the two keys
+const ENCRYPTION_CONTEXT: &[u8] = b"vaultx git-filter encryption";
+const NONCE_CONTEXT: &[u8] = b"vaultx git-filter nonce";
+
+/// Returns whether `data` is a file encrypted by `encrypt_file`.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Encrypts a file under keys derived from `secret`. The same file and
+/// secret always give the same output.
+pub fn encrypt_file(secret: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let (encryption_key, nonce_key) = derive_keys(secret);
+
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(nonce_key.as_ref())
+        .map_err(|_| CryptoError::InvalidKeyLength)?;
+    mac.update(plaintext);
+    let tag = mac.finalize().into_bytes();
+    let nonce = &tag[..NONCE_SIZE];
+
+    let cipher = Aes256Gcm::new_from_slice(encryption_key.as_ref())
+        .map_err(|_| CryptoError::InvalidKeyLength)?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(nonce), plaintext)
+        .map_err(|_| CryptoError::EncryptionFailed)?;
+
+    let mut file = Vec::with_capacity(HEADER_SIZE + ciphertext.len());
+    file.extend_from_slice(MAGIC);
+    file.extend_from_slice(nonce);
+    file.extend_from_slice(&ciphertext);
+    Ok(file)
+}
+
+/// Decrypts a file produced by `encrypt_file` with the same secret.
+///
+/// # Errors
+/// - `CryptoError::InvalidStreamHeader` if `data` is not an encrypted file
+/// - `CryptoError::DecryptionFailed` if the secret is wrong or the file was
+///   modified
+pub fn decrypt_file(secret: &[u8], data: &[u8]) -> Result<SecretBytes, CryptoError> {
+    if data.len() < HEADER_SIZE || !is_encrypted(data) {
+        return Err(CryptoError::InvalidStreamHeader);
+    }
+    let (nonce, ciphertext) = data[MAGIC.len()..].split_at(NONCE_SIZE);
+
+    let (encryption_key, _) = derive_keys(secret);
+    let cipher = Aes256Gcm::new_from_slice(encryption_key.as_ref())
+        .map_err(|_| CryptoError::InvalidKeyLength)?;
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| CryptoError::DecryptionFailed)?;
+
+    Ok(SecretBytes::new(plaintext))
+}
+
+/// Derives the encryption and nonce keys from the vault secret.
+fn derive_keys(secret: &[u8]) -> (Zeroizing<[u8; KEY_SIZE]>, Zeroizing<[u8; KEY_SIZE]>) {
+    let hkdf = Hkdf::<Sha256>::new(None, secret);
+
+    let mut encryption_key = Zeroizing::new([0u8; KEY_SIZE]);
+    let mut nonce_key = Zeroizing::new([0u8; KEY_SIZE]);
+    hkdf.expand(ENCRYPTION_CONTEXT, encryption_key.as_mut())
+        .expect("KEY_SIZE is a valid HKDF-SHA256 output length");
+    hkdf.expand(NONCE_CONTEXT, nonce_key.as_mut())
+        .expect("KEY_SIZE is a valid HKDF-SHA256 output length");
+
+    (encryption_key, nonce_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let file = encrypt_file(b"repo key", b"password: hunter2\n").unwrap();
+        assert!(is_encrypted(&file));
+        assert_eq!(
+            decrypt_file(b"repo key", &file).unwrap().expose(),
+            b"password: hunter2\n"
+        );
+    }
+
+    #[test]
+    fn test_encryption_is_deterministic() {
+        let a = encrypt_file(b"repo key", b"same contents").unwrap();
+        let b = encrypt_file(b"repo key", b"same contents").unwrap();
+        let c = encrypt_file(b"repo key", b"other contents").unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a[MAGIC.len()..HEADER_SIZE], c[MAGIC.len()..HEADER_SIZE]);
+    }
+
+    #[test]
+    fn test_wrong_secret_or_tampering_fails() {
+        let mut file = encrypt_file(b"repo key", b"contents").unwrap();
+        assert!(matches!(
+            decrypt_file(b"other key", &file),
+            Err(CryptoError::DecryptionFailed)
+        ));
+
+        let last = file.len() - 1;
+        file[last] ^= 1;
+        assert!(matches!(
+            decrypt_file(b"repo key", &file),
+            Err(CryptoError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn test_plain_files_are_rejected() {
+        assert!(!is_encrypted(b"plain text"));
+        assert!(matches!(
+            decrypt_file(b"repo key", b"plain text"),
+            Err(CryptoError::InvalidStreamHeader)
+        ));
+        assert!(matches!(
+            decrypt_file(b"repo key", MAGIC),
+            Err(CryptoError::InvalidStreamHeader)
+        ));
+    }
+}
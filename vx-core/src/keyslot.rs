@@ -0,0 +1,218 @@
+//! Key slots: independent credentials that unlock the same vault.
+//!
+//! The vault payload is encrypted under a random data key. Each key slot
+//! wraps that data key under a key-encryption key derived from one
+//! credential (a password, the contents of a keyfile, a recovery code, or a
+//! random key kept in the OS keychain), in the style of LUKS. Adding or
+//! removing a credential only rewrites its slot; the vault contents are
+//! never re-encrypted.
+//!
+//! # Security Notes
+//! - Every slot has its own Argon2id salt and cost parameters
+//! - Wrapped keys are AES-256-GCM encrypted, so a wrong credential is
+//!   detected without trying to decrypt the vault
+//! - Slots are stored in the authenticated file header; see `vault`
+
+use crate::crypto::{self, EncryptedData, KdfParams, KEY_SIZE, NONCE_SIZE, SALT_SIZE};
+use crate::error::VaultError;
+use crate::ttl;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroizing;
+
+/// Maximum number of key slots a vault can hold
+pub const MAX_KEY_SLOTS: usize = 8;
+
+/// Random bytes in a recovery code (128 bits)
+const RECOVERY_CODE_BYTES: usize = 16;
+
+/// The kind of credential a key slot is unlocked with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlotKind {
+    Password,
+    Keyfile,
+    RecoveryCode,
+    /// A random key held by the OS keychain behind biometric access control
+    Biometric,
+}
+
+impl fmt::Display for SlotKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SlotKind::Password => "password",
+            SlotKind::Keyfile => "keyfile",
+            SlotKind::RecoveryCode => "recovery-code",
+            SlotKind::Biometric => "biometric",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A credential wrapping the vault's data key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeySlot {
+    /// Stable identifier, unique within the vault
+    pub id: u32,
+    pub kind: SlotKind,
+    /// Free-form description, e.g. the name of the person holding it
+    pub label: String,
+    #[serde(with = "crate::vault::base64_serde")]
+    salt: Vec<u8>,
+    #[serde(with = "crate::vault::base64_serde")]
+    wrapped_key: Vec<u8>,
+    #[serde(with = "crate::vault::base64_serde")]
+    nonce: Vec<u8>,
+    /// Argon2id parameters the slot key is derived with; slots written
+    /// before they were recorded use the standard ones
+    #[serde(default)]
+    pub kdf: KdfParams,
+    pub created_at: u64,
+}
+
+impl KeySlot {
+    /// Wraps `data_key` under a key derived from `credential`.
+    pub fn new(
+        id: u32,
+        kind: SlotKind,
+        label: &str,
+        credential: &[u8],
+        data_key: &[u8; KEY_SIZE],
+    ) -> Result<Self, VaultError> {
+        Self::with_kdf(
+            id,
+            kind,
+            label,
+            credential,
+            data_key,
+            KdfParams::for_this_platform(),
+        )
+    }
+
+    /// Wraps `data_key` like `new`, deriving the slot key with `kdf`.
+    pub fn with_kdf(
+        id: u32,
+        kind: SlotKind,
+        label: &str,
+        credential: &[u8],
+        data_key: &[u8; KEY_SIZE],
+        kdf: KdfParams,
+    ) -> Result<Self, VaultError> {
+        let salt = crypto::generate_salt();
+        let kek = crypto::derive_key_with_params(credential, &salt, &kdf)?;
+        let wrapped = crypto::encrypt(data_key, &kek)?;
+
+        Ok(Self {
+            id,
+            kind,
+            label: label.to_string(),
+            salt: salt.to_vec(),
+            wrapped_key: wrapped.ciphertext,
+            nonce: wrapped.nonce.to_vec(),
+            kdf,
+            created_at: ttl::current_timestamp(),
+        })
+    }
+
+    /// Recovers the data key if `credential` matches this slot.
+    pub fn unwrap_key(&self, credential: &[u8]) -> Option<Zeroizing<[u8; KEY_SIZE]>> {
+        let salt: [u8; SALT_SIZE] = self.salt.as_slice().try_into().ok()?;
+        let nonce: [u8; NONCE_SIZE] = self.nonce.as_slice().try_into().ok()?;
+
+        let kek = crypto::derive_key_with_params(credential, &salt, &self.kdf).ok()?;
+        let encrypted = EncryptedData {
+            ciphertext: self.wrapped_key.clone(),
+            nonce,
+        };
+        let plaintext = crypto::decrypt(&encrypted, &kek).ok()?;
+
+        let key: [u8; KEY_SIZE] = plaintext.expose().try_into().ok()?;
+        Some(Zeroizing::new(key))
+    }
+}
+
+/// Tries `credential` against every slot and returns the data key from the
+/// first one it unlocks.
+///
+/// # Errors
+/// `VaultError::AuthenticationFailed` if no slot matches.
+pub fn unlock(slots: &[KeySlot], credential: &[u8]) -> Result<Zeroizing<[u8; KEY_SIZE]>, VaultError> {
+    slots
+        .iter()
+        .find_map(|slot| slot.unwrap_key(credential))
+        .ok_or(VaultError::AuthenticationFailed)
+}
+
+/// Generates a random recovery code, formatted as dash-separated groups of
+/// four hex digits.
+///
+/// The code is used as a slot credential like any password.
+pub fn generate_recovery_code() -> Zeroizing<String> {
+    let mut bytes = Zeroizing::new([0u8; RECOVERY_CODE_BYTES]);
+    rand::thread_rng().fill_bytes(bytes.as_mut());
+
+    let mut code = Zeroizing::new(String::with_capacity(RECOVERY_CODE_BYTES * 5 / 2));
+    for (i, pair) in bytes.chunks(2).enumerate() {
+        if i > 0 {
+            code.push('-');
+        }
+        code.push_str(&format!("{:02x}{:02x}", pair[0], pair[1]));
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slot_unwraps_with_its_credential() {
+        let data_key = crypto::generate_key();
+        let slot = KeySlot::new(0, SlotKind::Password, "alice", b"secret", &data_key).unwrap();
+
+        assert_eq!(*slot.unwrap_key(b"secret").unwrap(), *data_key);
+        assert!(slot.unwrap_key(b"wrong").is_none());
+    }
+
+    #[test]
+    fn test_unlock_tries_every_slot() {
+        let data_key = crypto::generate_key();
+        let slots = vec![
+            KeySlot::new(0, SlotKind::Password, "", b"first", &data_key).unwrap(),
+            KeySlot::new(1, SlotKind::RecoveryCode, "", b"second", &data_key).unwrap(),
+        ];
+
+        assert_eq!(*unlock(&slots, b"second").unwrap(), *data_key);
+        assert!(matches!(
+            unlock(&slots, b"third"),
+            Err(VaultError::AuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_slot_records_kdf_params() {
+        let data_key = crypto::generate_key();
+        let slot =
+            KeySlot::with_kdf(0, SlotKind::Password, "", b"pw", &data_key, KdfParams::BROWSER)
+                .unwrap();
+
+        assert_eq!(slot.kdf, KdfParams::BROWSER);
+        assert_eq!(*slot.unwrap_key(b"pw").unwrap(), *data_key);
+    }
+
+    #[test]
+    fn test_recovery_code_format() {
+        let code = generate_recovery_code();
+
+        assert_eq!(code.len(), 39);
+        assert_eq!(code.split('-').count(), 8);
+        assert_ne!(*code, *generate_recovery_code());
+    }
+
+    #[test]
+    fn test_slot_kind_display() {
+        assert_eq!(SlotKind::RecoveryCode.to_string(), "recovery-code");
+        assert_eq!(SlotKind::Biometric.to_string(), "biometric");
+    }
+}
@@ -7,16 +7,58 @@
 //! All cryptographic operations are isolated in this crate.
 //! The CLI layer should never perform crypto operations directly.
 
+pub mod access_log;
+pub mod archive;
+pub mod audit;
+pub mod backend;
+pub mod certificate;
 pub mod crypto;
+pub mod database;
+pub mod dotenv;
+pub mod emergency;
 pub mod error;
+pub mod generate;
+pub mod git_filter;
+pub mod keyslot;
+pub mod memory;
+pub mod merge;
+pub mod mnemonic;
+pub mod pattern;
+pub mod redact;
+pub mod rules;
+pub mod scan;
+pub mod share;
 pub mod ssh;
+pub mod ssh_agent;
+pub mod ssh_backup;
+pub mod stats;
+pub mod stream;
+pub mod strength;
+pub mod structured;
+pub mod template;
+pub mod transfer;
+pub mod trash;
 pub mod ttl;
+pub mod undo;
 pub mod vault;
+pub mod wireguard;
 
 // Re-export main types for convenience
-pub use crypto::KEY_SIZE;
+pub use access_log::{ChainStatus, LogEntry, Operation};
+pub use audit::{AuditReport, Expiration, ExpiringItem, IssueKind};
+pub use crypto::{SecretBytes, KEY_SIZE};
+pub use emergency::{EmergencyGrant, EmergencyRequest, RequestStatus};
 pub use error::{CryptoError, SshError, TtlError, VaultError};
-pub use vault::{Project, Secret, SshIdentity, Vault};
+pub use keyslot::{KeySlot, SlotKind};
+pub use memory::{SecureBuffer, SecureKey};
+pub use trash::{TrashEntry, TrashedItem};
+pub use vault::{
+    AccessTime, Attachment, Kubeconfig, PayloadFormat, Pkcs11Key, Project, Provenance, Secret,
+    SecretKind, SecretRef, SshIdentity, Vault, WireguardConfig,
+};
 
 #[cfg(feature = "wasm")]
 pub mod wasm;
+
+#[cfg(feature = "capi")]
+pub mod capi;
@@ -0,0 +1,238 @@
+//! Locked memory for long-lived key material.
+//!
+//! `SecureBuffer` allocates whole pages, locks them into RAM (mlock /
+//! VirtualLock) so they are never swapped to disk, and excludes them from
+//! core dumps on Linux. Contents are zeroized before the pages are released.
+//!
+//! # Security Notes
+//! - Locking is best effort: if the OS refuses (e.g. RLIMIT_MEMLOCK is
+//!   exhausted) the buffer still works and `is_locked()` reports `false`
+//! - On targets without memory locking (wasm32) the buffer is a plain
+//!   zeroizing allocation
+
+use crate::crypto::KEY_SIZE;
+use std::alloc::{self, Layout};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use zeroize::Zeroize;
+
+/// A fixed-size heap buffer held in locked, non-dumpable memory.
+pub struct SecureBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    layout: Layout,
+    locked: bool,
+}
+
+// The buffer exclusively owns its allocation.
+unsafe impl Send for SecureBuffer {}
+unsafe impl Sync for SecureBuffer {}
+
+impl SecureBuffer {
+    /// Allocates a zero-filled buffer of `len` bytes in locked memory.
+    pub fn new(len: usize) -> Self {
+        let page = page_size();
+        let size = len.max(1).div_ceil(page) * page;
+        let layout = Layout::from_size_align(size, page).expect("invalid secure buffer layout");
+
+        // SAFETY: layout has a non-zero size.
+        let raw = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(raw).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+
+        let locked = sys::lock(ptr.as_ptr(), size);
+
+        Self {
+            ptr,
+            len,
+            layout,
+            locked,
+        }
+    }
+
+    /// Copies `data` into a new locked buffer.
+    pub fn from_slice(data: &[u8]) -> Self {
+        let mut buffer = Self::new(data.len());
+        buffer.copy_from_slice(data);
+        buffer
+    }
+
+    /// Returns whether the pages were successfully locked into RAM.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+}
+
+impl Deref for SecureBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: ptr is valid for layout.size() >= len bytes for the lifetime of self.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for SecureBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: ptr is valid for layout.size() >= len bytes and uniquely borrowed.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for SecureBuffer {
+    fn drop(&mut self) {
+        let size = self.layout.size();
+
+        // SAFETY: the whole allocation is owned by this buffer.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), size) }.zeroize();
+
+        if self.locked {
+            sys::unlock(self.ptr.as_ptr(), size);
+        }
+
+        // SAFETY: allocated in `new` with the same layout.
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+impl fmt::Debug for SecureBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecureBuffer([REDACTED; {} bytes])", self.len)
+    }
+}
+
+/// An encryption key held in a `SecureBuffer`.
+///
+/// Dereferences to `[u8; KEY_SIZE]` so it can be passed anywhere a key is expected.
+pub struct SecureKey(SecureBuffer);
+
+impl SecureKey {
+    /// Copies `key` into locked memory.
+    pub fn new(key: &[u8; KEY_SIZE]) -> Self {
+        Self(SecureBuffer::from_slice(key))
+    }
+
+    /// Returns whether the key's pages were successfully locked into RAM.
+    pub fn is_locked(&self) -> bool {
+        self.0.is_locked()
+    }
+}
+
+impl Clone for SecureKey {
+    fn clone(&self) -> Self {
+        Self::new(self)
+    }
+}
+
+impl Deref for SecureKey {
+    type Target = [u8; KEY_SIZE];
+
+    fn deref(&self) -> &[u8; KEY_SIZE] {
+        (*self.0).try_into().expect("secure key has fixed length")
+    }
+}
+
+impl fmt::Debug for SecureKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecureKey([REDACTED])")
+    }
+}
+
+/// Returns the system page size.
+fn page_size() -> usize {
+    sys::page_size().unwrap_or(4096)
+}
+
+#[cfg(unix)]
+mod sys {
+    pub fn page_size() -> Option<usize> {
+        // SAFETY: sysconf has no preconditions.
+        let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        usize::try_from(size).ok().filter(|s| *s > 0)
+    }
+
+    pub fn lock(ptr: *mut u8, len: usize) -> bool {
+        // SAFETY: ptr/len describe a page-aligned allocation owned by the caller.
+        let locked = unsafe { libc::mlock(ptr as *const libc::c_void, len) } == 0;
+
+        #[cfg(target_os = "linux")]
+        // SAFETY: as above; MADV_DONTDUMP only changes core dump behaviour.
+        unsafe {
+            libc::madvise(ptr as *mut libc::c_void, len, libc::MADV_DONTDUMP);
+        }
+
+        locked
+    }
+
+    pub fn unlock(ptr: *mut u8, len: usize) {
+        // SAFETY: ptr/len were previously passed to `lock`.
+        unsafe {
+            libc::munlock(ptr as *const libc::c_void, len);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use windows_sys::Win32::System::Memory::{VirtualLock, VirtualUnlock};
+
+    pub fn page_size() -> Option<usize> {
+        None
+    }
+
+    pub fn lock(ptr: *mut u8, len: usize) -> bool {
+        // SAFETY: ptr/len describe a page-aligned allocation owned by the caller.
+        unsafe { VirtualLock(ptr as *const core::ffi::c_void, len) != 0 }
+    }
+
+    pub fn unlock(ptr: *mut u8, len: usize) {
+        // SAFETY: ptr/len were previously passed to `lock`.
+        unsafe {
+            VirtualUnlock(ptr as *const core::ffi::c_void, len);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    pub fn page_size() -> Option<usize> {
+        None
+    }
+
+    pub fn lock(_ptr: *mut u8, _len: usize) -> bool {
+        false
+    }
+
+    pub fn unlock(_ptr: *mut u8, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secure_buffer_roundtrip() {
+        let buffer = SecureBuffer::from_slice(b"private key material");
+        assert_eq!(&*buffer, b"private key material");
+        assert_eq!(buffer.len(), 20);
+    }
+
+    #[test]
+    fn test_secure_buffer_is_page_aligned() {
+        let buffer = SecureBuffer::new(10);
+        assert_eq!(buffer.as_ptr() as usize % page_size(), 0);
+    }
+
+    #[test]
+    fn test_secure_key_deref() {
+        let key = SecureKey::new(&[7u8; KEY_SIZE]);
+        let as_array: &[u8; KEY_SIZE] = &key;
+        assert_eq!(as_array, &[7u8; KEY_SIZE]);
+    }
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let buffer = SecureBuffer::from_slice(b"hunter2");
+        assert!(!format!("{:?}", buffer).contains("hunter2"));
+    }
+}
@@ -0,0 +1,456 @@
+//! Entry-level merge of two copies of a vault that were changed apart, e.g.
+//! on two machines syncing the vault directory; see `vx sync pull`.
+//!
+//! Both copies carry the same access log up to the point where they
+//! diverged, and past it each log lists what its side did. A secret counts
+//! as changed on a side if its `Secret::last_modified` time is after the
+//! last shared entry, or that side's log names it since. A secret changed
+//! on one side only is taken from that side, and one removed on one side
+//! and left alone on the other is removed. Only a secret changed on both
+//! sides, or changed on one and removed on the other, is a `Conflict` for
+//! the caller to settle.
+//!
+//! The two copies must be the same vault, opened with the same data key
+//! and with every project unsealed: values are compared and copied as
+//! ciphertext, never decrypted.
+
+use crate::access_log::{LogEntry, Operation};
+use crate::crypto::KEY_SIZE;
+use crate::error::VaultError;
+use crate::undo::same_secret;
+use crate::vault::{Project, Secret, SecretRef, Vault};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Which copy of the vault a merged entry is taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// The copy being merged into
+    Local,
+    /// The copy being merged from
+    Remote,
+}
+
+/// A change the merge takes from the remote copy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Update {
+    /// A secret added or changed on the remote side only
+    Secret(SecretRef),
+    /// A secret removed on the remote side and left alone locally
+    RemoveSecret(SecretRef),
+    /// A project added on the remote side
+    Project(String),
+    /// A project removed on the remote side and left alone locally
+    RemoveProject(String),
+}
+
+impl fmt::Display for Update {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Update::Secret(location) => write!(f, "update {}", location),
+            Update::RemoveSecret(location) => write!(f, "remove {}", location),
+            Update::Project(name) => write!(f, "add project {}", name),
+            Update::RemoveProject(name) => write!(f, "remove project {}", name),
+        }
+    }
+}
+
+/// A secret changed on both sides since the copies diverged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub location: SecretRef,
+    /// When the local copy last changed the secret; `None` if it removed it
+    pub local: Option<u64>,
+    /// When the remote copy last changed the secret; `None` if it removed it
+    pub remote: Option<u64>,
+}
+
+/// What merging a remote copy into the local one would do; see `plan`.
+#[derive(Debug, Clone, Default)]
+pub struct MergePlan {
+    /// Time of the last access log entry both copies share, if any
+    pub diverged_at: Option<u64>,
+    /// Changes taken from the remote copy without asking
+    pub updates: Vec<Update>,
+    /// Secrets changed on both sides
+    pub conflicts: Vec<Conflict>,
+    /// Projects whose passphrase differs between the copies; their values
+    /// are under different keys and are left as they are locally
+    pub skipped: Vec<String>,
+}
+
+impl MergePlan {
+    /// Returns whether the merge would leave the local copy as it is.
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty() && self.conflicts.is_empty()
+    }
+}
+
+/// The access log entries each side recorded since the copies diverged.
+struct Since<'a> {
+    at: Option<u64>,
+    local: &'a [LogEntry],
+    remote: &'a [LogEntry],
+}
+
+impl Since<'_> {
+    fn log(&self, side: Side) -> &[LogEntry] {
+        match side {
+            Side::Local => self.local,
+            Side::Remote => self.remote,
+        }
+    }
+
+    /// Returns whether `side` changed the secret at `location` since the
+    /// copies diverged, going by its own copy of it and its log.
+    fn changed(&self, side: Side, location: &SecretRef, secret: &Secret) -> bool {
+        self.at.is_none_or(|at| secret.last_modified() > at)
+            || self.log(side).iter().any(|entry| {
+                matches!(
+                    entry.operation,
+                    Operation::Add | Operation::Edit | Operation::Restore | Operation::Undo
+                ) && entry.target() == location.to_string()
+            })
+    }
+
+    /// Returns whether `side`'s log records removing `target`, or the
+    /// environment or project it is in.
+    fn removed(&self, side: Side, target: &str) -> bool {
+        self.log(side).iter().any(|entry| {
+            let removed = entry.target();
+            entry.operation == Operation::Remove
+                && (target == removed || target.starts_with(&format!("{}/", removed)))
+        })
+    }
+}
+
+/// Works out how to merge `remote` into `local`.
+///
+/// # Errors
+/// `VaultError::ProjectSealed` if a project of either copy is still sealed.
+pub fn plan(local: &Vault, remote: &Vault) -> Result<MergePlan, VaultError> {
+    for vault in [local, remote] {
+        if let Some(name) = vault
+            .project_names()
+            .into_iter()
+            .find(|name| vault.is_sealed(name))
+        {
+            return Err(VaultError::ProjectSealed(name.to_string()));
+        }
+    }
+
+    let (local_log, remote_log) = (local.access_log(), remote.access_log());
+    let shared = local_log
+        .iter()
+        .zip(remote_log)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let since = Since {
+        at: shared.checked_sub(1).map(|last| local_log[last].timestamp),
+        local: &local_log[shared..],
+        remote: &remote_log[shared..],
+    };
+
+    let mut plan = MergePlan {
+        diverged_at: since.at,
+        ..MergePlan::default()
+    };
+    let names: BTreeSet<&String> = local
+        .projects
+        .keys()
+        .chain(remote.projects.keys())
+        .collect();
+    for name in names {
+        match (local.projects.get(name), remote.projects.get(name)) {
+            (Some(ours), Some(theirs)) => {
+                let same_key = match (&ours.lock, &theirs.lock) {
+                    (None, None) => true,
+                    (Some(a), Some(b)) => a.salt == b.salt,
+                    _ => false,
+                };
+                if !same_key {
+                    plan.skipped.push(name.clone());
+                    continue;
+                }
+                merge_secrets(&mut plan, &since, name, ours, theirs);
+            }
+            // Whole projects are only added or removed when the other side
+            // has not touched them
+            (Some(ours), None) => {
+                let touched = locations(name, ours)
+                    .any(|(location, secret)| since.changed(Side::Local, &location, secret));
+                if since.removed(Side::Remote, name) && !touched {
+                    plan.updates.push(Update::RemoveProject(name.clone()));
+                }
+            }
+            (None, Some(_)) => {
+                if !since.removed(Side::Local, name) {
+                    plan.updates.push(Update::Project(name.clone()));
+                }
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    Ok(plan)
+}
+
+/// Iterates over the secrets of the project `name`, with where each is.
+fn locations<'a>(
+    name: &'a str,
+    project: &'a Project,
+) -> impl Iterator<Item = (SecretRef, &'a Secret)> + 'a {
+    project
+        .secrets_by_environment()
+        .flat_map(move |(env, secrets)| {
+            secrets
+                .iter()
+                .map(move |(key, secret)| (SecretRef::new(name, env, key), secret))
+        })
+}
+
+/// Compares the secrets of a project both copies have.
+fn merge_secrets(
+    plan: &mut MergePlan,
+    since: &Since,
+    name: &str,
+    ours: &Project,
+    theirs: &Project,
+) {
+    let mut all: Vec<SecretRef> = locations(name, ours)
+        .chain(locations(name, theirs))
+        .map(|(location, _)| location)
+        .collect();
+    all.sort_by_key(SecretRef::to_string);
+    all.dedup();
+
+    for location in all {
+        let find = |project: &Project| {
+            project
+                .secrets_in(location.env.as_deref())
+                .ok()
+                .and_then(|secrets| secrets.get(&location.key))
+                .cloned()
+        };
+        let target = location.to_string();
+        match (find(ours), find(theirs)) {
+            (Some(a), Some(b)) => {
+                if same_secret(&a, &b) {
+                    continue;
+                }
+                let local = since.changed(Side::Local, &location, &a);
+                let remote = since.changed(Side::Remote, &location, &b);
+                if local && remote {
+                    plan.conflicts.push(Conflict {
+                        location,
+                        local: Some(a.last_modified()),
+                        remote: Some(b.last_modified()),
+                    });
+                } else if remote || (!local && b.last_modified() > a.last_modified()) {
+                    plan.updates.push(Update::Secret(location));
+                }
+            }
+            (Some(a), None) => {
+                if !since.removed(Side::Remote, &target) {
+                    continue;
+                }
+                if since.changed(Side::Local, &location, &a) {
+                    plan.conflicts.push(Conflict {
+                        location,
+                        local: Some(a.last_modified()),
+                        remote: None,
+                    });
+                } else {
+                    plan.updates.push(Update::RemoveSecret(location));
+                }
+            }
+            (None, Some(b)) => {
+                if !since.removed(Side::Local, &target) {
+                    plan.updates.push(Update::Secret(location));
+                } else if since.changed(Side::Remote, &location, &b) {
+                    plan.conflicts.push(Conflict {
+                        location,
+                        local: None,
+                        remote: Some(b.last_modified()),
+                    });
+                }
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+/// Applies a merge plan to `local`, settling each conflict with the side
+/// `resolve` picks. Secrets and projects removed go to the trash, so they
+/// can be restored.
+pub fn apply<F>(
+    local: &mut Vault,
+    remote: &Vault,
+    plan: &MergePlan,
+    key: &[u8; KEY_SIZE],
+    mut resolve: F,
+) -> Result<(), VaultError>
+where
+    F: FnMut(&Conflict) -> Side,
+{
+    let resolved: Vec<&SecretRef> = plan
+        .conflicts
+        .iter()
+        .filter(|conflict| resolve(conflict) == Side::Remote)
+        .map(|conflict| &conflict.location)
+        .collect();
+
+    for update in &plan.updates {
+        match update {
+            Update::Secret(location) => take_secret(local, remote, location)?,
+            Update::RemoveSecret(location) => {
+                local.trash_secret(&location.project, location.env.as_deref(), &location.key)?;
+            }
+            Update::Project(name) => {
+                let project = remote
+                    .projects
+                    .get(name)
+                    .ok_or_else(|| VaultError::ProjectNotFound(name.clone()))?;
+                local.projects.insert(name.clone(), project.clone());
+            }
+            Update::RemoveProject(name) => {
+                local.trash_project(name, key)?;
+            }
+        }
+    }
+    for location in resolved {
+        take_secret(local, remote, location)?;
+    }
+    Ok(())
+}
+
+/// Makes the local copy of a secret what it is on the remote side, removing
+/// it if the remote side has.
+fn take_secret(local: &mut Vault, remote: &Vault, location: &SecretRef) -> Result<(), VaultError> {
+    let theirs = remote
+        .projects
+        .get(&location.project)
+        .and_then(|project| project.secrets_in(location.env.as_deref()).ok())
+        .and_then(|secrets| secrets.get(&location.key));
+
+    let Some(theirs) = theirs else {
+        return local
+            .trash_secret(&location.project, location.env.as_deref(), &location.key)
+            .map(|_| ());
+    };
+    let project = local
+        .projects
+        .get_mut(&location.project)
+        .ok_or_else(|| VaultError::ProjectNotFound(location.project.clone()))?;
+    let secrets = match &location.env {
+        None => &mut project.secrets,
+        Some(env) => project.environments.entry(env.clone()).or_default(),
+    };
+    secrets.insert(location.key.clone(), theirs.clone());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::{create_vault, load_vault_with_key, save_vault_with_key};
+    use zeroize::Zeroizing;
+
+    /// Returns two copies of a vault with secrets A, B and C in project
+    /// "app", as if synced to two machines.
+    fn diverged() -> (Vault, Vault, Zeroizing<[u8; KEY_SIZE]>) {
+        let (mut vault, key) = create_vault(b"test-password").unwrap();
+        vault.init_project("app").unwrap();
+        for name in ["A", "B", "C"] {
+            vault.add_secret("app", name, b"old", &key, None).unwrap();
+            vault.record(Operation::Add, Some("app"), Some(name));
+        }
+
+        let saved = save_vault_with_key(&vault, &key).unwrap();
+        let local = load_vault_with_key(&saved, &key).unwrap();
+        let remote = load_vault_with_key(&saved, &key).unwrap();
+        (local, remote, key)
+    }
+
+    fn edit(vault: &mut Vault, name: &str, value: &[u8], key: &[u8; KEY_SIZE]) {
+        vault.add_secret("app", name, value, key, None).unwrap();
+        vault.record(Operation::Edit, Some("app"), Some(name));
+    }
+
+    #[test]
+    fn test_changes_on_different_secrets_merge() {
+        let (mut local, mut remote, key) = diverged();
+        edit(&mut local, "A", b"local", &key);
+        edit(&mut remote, "B", b"remote", &key);
+        remote.trash_secret("app", None, "C").unwrap();
+        remote.record(Operation::Remove, Some("app"), Some("C"));
+
+        let plan = plan(&local, &remote).unwrap();
+        assert!(plan.conflicts.is_empty());
+        assert_eq!(
+            plan.updates,
+            vec![
+                Update::Secret(SecretRef::new("app", None, "B")),
+                Update::RemoveSecret(SecretRef::new("app", None, "C")),
+            ]
+        );
+
+        apply(&mut local, &remote, &plan, &key, |_| Side::Local).unwrap();
+        assert_eq!(
+            local.get_secret("app", "A", &key).unwrap().expose(),
+            b"local"
+        );
+        assert_eq!(
+            local.get_secret("app", "B", &key).unwrap().expose(),
+            b"remote"
+        );
+        assert!(local.get_secret("app", "C", &key).is_err());
+        assert_eq!(local.trash().len(), 1);
+    }
+
+    #[test]
+    fn test_same_secret_changed_on_both_sides_conflicts() {
+        let (mut local, mut remote, key) = diverged();
+        edit(&mut local, "A", b"local", &key);
+        edit(&mut local, "B", b"local", &key);
+        remote.trash_secret("app", None, "B").unwrap();
+        remote.record(Operation::Remove, Some("app"), Some("B"));
+        edit(&mut remote, "A", b"remote", &key);
+
+        let plan = plan(&local, &remote).unwrap();
+        assert!(plan.updates.is_empty());
+        let conflicts: Vec<_> = plan
+            .conflicts
+            .iter()
+            .map(|c| c.location.key.as_str())
+            .collect();
+        assert_eq!(conflicts, vec!["A", "B"]);
+        assert_eq!(plan.conflicts[1].remote, None);
+
+        apply(&mut local, &remote, &plan, &key, |_| Side::Remote).unwrap();
+        assert_eq!(
+            local.get_secret("app", "A", &key).unwrap().expose(),
+            b"remote"
+        );
+        assert!(local.get_secret("app", "B", &key).is_err());
+    }
+
+    #[test]
+    fn test_projects_added_on_one_side_are_kept() {
+        let (mut local, mut remote, key) = diverged();
+        remote.init_project("new").unwrap();
+        remote.add_secret("new", "KEY", b"new", &key, None).unwrap();
+        remote.record(Operation::Add, Some("new"), Some("KEY"));
+
+        let plan = plan(&local, &remote).unwrap();
+        assert_eq!(plan.updates, vec![Update::Project("new".to_string())]);
+        apply(&mut local, &remote, &plan, &key, |_| Side::Local).unwrap();
+        assert_eq!(
+            local.get_secret("new", "KEY", &key).unwrap().expose(),
+            b"new"
+        );
+
+        // Nothing is left to merge the other way
+        let plan = super::plan(&remote, &local).unwrap();
+        assert!(plan.is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+//! Paper backups of the vault's data key as a BIP39 mnemonic.
+//!
+//! The 256-bit data key encodes to 24 words from the English BIP39 list,
+//! the last of which carries a checksum, so a mistyped word is detected.
+//! The phrase alone does not hold any secrets: it opens a copy of the vault
+//! file in place of a password, recovery code or keyfile.
+
+use crate::crypto::KEY_SIZE;
+use crate::error::VaultError;
+use bip39::{Language, Mnemonic};
+use zeroize::Zeroizing;
+
+/// Number of words in a data key mnemonic
+pub const WORD_COUNT: usize = 24;
+
+/// Encodes a data key as its 24-word mnemonic, words separated by spaces.
+pub fn from_key(key: &[u8; KEY_SIZE]) -> Zeroizing<String> {
+    let mnemonic = Mnemonic::from_entropy_in(Language::English, key)
+        .expect("a 256-bit key is valid BIP39 entropy");
+    Zeroizing::new(mnemonic.to_string())
+}
+
+/// Decodes a data key from its mnemonic. Words are separated by any
+/// whitespace, and case is ignored.
+///
+/// # Errors
+/// `VaultError::InvalidMnemonic` if a word is unknown, the checksum does not
+/// match or the phrase is not 24 words long.
+pub fn to_key(phrase: &str) -> Result<Zeroizing<[u8; KEY_SIZE]>, VaultError> {
+    let phrase = Zeroizing::new(phrase.to_lowercase());
+    let words = phrase.split_whitespace().count();
+    if words != WORD_COUNT {
+        return Err(VaultError::InvalidMnemonic(format!(
+            "expected {} words, got {}",
+            WORD_COUNT, words
+        )));
+    }
+
+    let mnemonic = Mnemonic::parse_in(Language::English, phrase.as_str())
+        .map_err(|e| VaultError::InvalidMnemonic(e.to_string()))?;
+    let (entropy, len) = mnemonic.to_entropy_array();
+    let entropy = Zeroizing::new(entropy);
+
+    let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+    key.copy_from_slice(&entropy[..len]);
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto;
+
+    #[test]
+    fn test_mnemonic_roundtrip() {
+        let key = crypto::generate_key();
+        let phrase = from_key(&key);
+        assert_eq!(phrase.split(' ').count(), WORD_COUNT);
+
+        let phrase = phrase.to_uppercase().replace(' ', "\n  ");
+        assert_eq!(*to_key(&phrase).unwrap(), *key);
+    }
+
+    #[test]
+    fn test_mnemonic_rejects_mistakes() {
+        let key = [0u8; KEY_SIZE];
+        let phrase = from_key(&key);
+        let mut words: Vec<&str> = phrase.split(' ').collect();
+
+        // All-zero entropy gives "abandon" × 23 and a checksum word
+        words[0] = "ability";
+        assert!(matches!(
+            to_key(&words.join(" ")),
+            Err(VaultError::InvalidMnemonic(_))
+        ));
+
+        assert!(matches!(
+            to_key(&words[..12].join(" ")),
+            Err(VaultError::InvalidMnemonic(_))
+        ));
+        assert!(to_key("not a mnemonic").is_err());
+    }
+}
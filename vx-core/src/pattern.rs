@@ -0,0 +1,70 @@
+//! Glob patterns for selecting secrets by name.
+//!
+//! `*` matches any run of characters, including none, and `?` matches
+//! exactly one. Every other character matches itself; there is no escape,
+//! so a key containing `*` or `?` is selected by its exact name instead.
+
+/// Returns whether `pattern` holds a wildcard.
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Matches `text` against the glob `pattern`.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Backtracks to the last `*`, letting it take one more character
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("DB_*", "DB_HOST"));
+        assert!(matches("DB_*", "DB_"));
+        assert!(!matches("DB_*", "XDB_HOST"));
+        assert!(matches("*_KEY", "STRIPE_KEY"));
+        assert!(matches("*STRIPE*", "OLD_STRIPE_KEY"));
+        assert!(matches("A*B*C", "AxxBxxBxxC"));
+        assert!(!matches("A*B*C", "AxxC"));
+        assert!(matches("DB_?", "DB_1"));
+        assert!(!matches("DB_?", "DB_12"));
+        assert!(matches("DATABASE_URL", "DATABASE_URL"));
+        assert!(!matches("DATABASE_URL", "DATABASE_URL2"));
+        assert!(matches("*", ""));
+        assert!(matches("KÉ?", "KÉY"));
+    }
+
+    #[test]
+    fn test_is_glob() {
+        assert!(is_glob("DB_*"));
+        assert!(is_glob("DB_?"));
+        assert!(!is_glob("DB_HOST"));
+    }
+}
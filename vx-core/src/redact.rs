@@ -0,0 +1,83 @@
+//! Redaction of sensitive values in log output.
+//!
+//! Log events are emitted with `tracing` throughout the core and the CLI.
+//! Two rules keep secrets out of them:
+//!
+//! - Types holding plaintext (`SecretBytes`, `SecureBuffer`, `SecureKey`,
+//!   `DbConnection`) print `[REDACTED]` in place of it from `Debug`, so
+//!   they are safe to record with `?value`
+//! - Fields whose name marks them as sensitive are replaced with
+//!   `[REDACTED]` by the log formatter whatever their type, checked with
+//!   `is_sensitive_field`
+//!
+//! Event messages are not inspected, so plaintext must never be
+//! interpolated into them.
+
+/// Written in place of a redacted value
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Field names, and `_`-separated name suffixes, that hold sensitive values
+const SENSITIVE_NAMES: &[&str] = &[
+    "credential",
+    "key",
+    "passphrase",
+    "password",
+    "plaintext",
+    "secret",
+    "token",
+    "value",
+];
+
+/// Returns whether a log field named `name` must be redacted.
+///
+/// A name is sensitive if it, or its last `_`-separated word, is one of
+/// `password`, `passphrase`, `secret`, `value`, `key`, `token`,
+/// `credential` or `plaintext`, ignoring case; `private_key` is sensitive,
+/// `key_count` is not.
+pub fn is_sensitive_field(name: &str) -> bool {
+    let last_word = name.rsplit('_').next().unwrap_or(name);
+    SENSITIVE_NAMES
+        .iter()
+        .any(|sensitive| last_word.eq_ignore_ascii_case(sensitive))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::SecretBytes;
+    use crate::database::{DbConnection, DbEngine};
+    use crate::memory::{SecureBuffer, SecureKey};
+
+    #[test]
+    fn test_sensitive_field_names() {
+        for name in ["password", "PASSWORD", "private_key", "db_password", "value"] {
+            assert!(is_sensitive_field(name), "{}", name);
+        }
+        for name in ["project", "key_count", "generation", "path", "message"] {
+            assert!(!is_sensitive_field(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_plaintext_types_redact_debug() {
+        let connection = DbConnection {
+            engine: DbEngine::Postgres,
+            host: "db.internal".to_string(),
+            port: None,
+            user: "app".to_string(),
+            password: "hunter2-plaintext".to_string(),
+            database: None,
+        };
+
+        for formatted in [
+            format!("{:?}", SecretBytes::new(b"hunter2-plaintext".to_vec())),
+            format!("{:?}", SecureBuffer::from_slice(b"hunter2-plaintext")),
+            format!("{:?}", SecureKey::new(&[0x41; 32])),
+            format!("{:?}", connection),
+        ] {
+            assert!(!formatted.contains("hunter2"), "{}", formatted);
+            assert!(!formatted.contains("65, 65"), "{}", formatted);
+        }
+        assert!(format!("{:?}", connection).contains(REDACTED));
+    }
+}
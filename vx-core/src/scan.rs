@@ -0,0 +1,309 @@
+//! Leak scanning: finding stored secret values in files.
+//!
+//! A `LeakScanner` is loaded with the values to look for and then fed any
+//! number of streams. It does not keep the values themselves: each is
+//! reduced to a SHA-256 digest salted with a random salt drawn for the
+//! scanner, plus a rolling fingerprint under a random base that picks out
+//! candidate windows cheaply. A window is reported only when its salted
+//! digest matches, and nothing is ever written out.
+
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{self, Read};
+
+/// Values shorter than this are not looked for; they would match too much
+/// by chance.
+pub const MIN_VALUE_LEN: usize = 8;
+
+/// Bytes read from a stream at a time
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A place in a stream where a value was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeakMatch {
+    /// Index of the value, as returned by `LeakScanner::add`
+    pub value: usize,
+    /// Byte offset of the match in the stream
+    pub offset: u64,
+    /// Line of the match, starting at 1
+    pub line: u64,
+}
+
+/// A value being looked for.
+struct Target {
+    value: usize,
+    digest: [u8; 32],
+}
+
+/// The values of one length, by fingerprint.
+struct LengthGroup {
+    len: usize,
+    /// `base^(len - 1)`, to roll the leading byte out of a window
+    high: u64,
+    targets: HashMap<u64, Vec<Target>>,
+}
+
+/// Looks for stored values in streams of bytes.
+pub struct LeakScanner {
+    salt: [u8; 32],
+    base: u64,
+    groups: Vec<LengthGroup>,
+    count: usize,
+}
+
+impl Default for LeakScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LeakScanner {
+    /// Creates a scanner with a fresh salt and no values.
+    pub fn new() -> Self {
+        let mut rng = rand::thread_rng();
+        let mut salt = [0u8; 32];
+        rng.fill_bytes(&mut salt);
+
+        Self {
+            salt,
+            base: rng.next_u64() | 1,
+            groups: Vec::new(),
+            count: 0,
+        }
+    }
+
+    /// Adds a value to look for and returns its index, or `None` if it is
+    /// shorter than `MIN_VALUE_LEN` and will not be looked for.
+    pub fn add(&mut self, value: &[u8]) -> Option<usize> {
+        if value.len() < MIN_VALUE_LEN {
+            return None;
+        }
+
+        let index = self.count;
+        self.count += 1;
+
+        let target = Target {
+            value: index,
+            digest: self.digest(value),
+        };
+        let fingerprint = self.fingerprint(value);
+
+        let group = match self.groups.iter().position(|g| g.len == value.len()) {
+            Some(i) => &mut self.groups[i],
+            None => {
+                let high = (1..value.len()).fold(1u64, |high, _| high.wrapping_mul(self.base));
+                self.groups.push(LengthGroup {
+                    len: value.len(),
+                    high,
+                    targets: HashMap::new(),
+                });
+                self.groups.last_mut().expect("group was just added")
+            }
+        };
+        group.targets.entry(fingerprint).or_default().push(target);
+        Some(index)
+    }
+
+    /// Returns the number of values being looked for.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Returns whether no values are being looked for.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Reads `reader` to the end and returns every match, ordered by offset.
+    pub fn scan(&self, mut reader: impl Read) -> io::Result<Vec<LeakMatch>> {
+        let mut matches = Vec::new();
+        let Some(max_len) = self.groups.iter().map(|g| g.len).max() else {
+            return Ok(matches);
+        };
+
+        // The last `max_len - 1` bytes of each chunk are carried over so
+        // that values spanning two chunks are found
+        let mut buffer = Vec::with_capacity(CHUNK_SIZE + max_len);
+        let mut chunk = vec![0u8; CHUNK_SIZE];
+        let mut carried = 0;
+        let mut buffer_offset = 0u64;
+        let mut buffer_line = 1u64;
+
+        loop {
+            let n = match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            buffer.extend_from_slice(&chunk[..n]);
+
+            for (start, value) in self.scan_buffer(&buffer, carried) {
+                matches.push(LeakMatch {
+                    value,
+                    offset: buffer_offset + start as u64,
+                    line: buffer_line + count_lines(&buffer[..start]),
+                });
+            }
+
+            let consumed = buffer.len() - buffer.len().min(max_len - 1);
+            buffer_offset += consumed as u64;
+            buffer_line += count_lines(&buffer[..consumed]);
+            buffer.drain(..consumed);
+            carried = buffer.len();
+        }
+
+        matches.sort_by_key(|m| (m.offset, m.value));
+        Ok(matches)
+    }
+
+    /// Returns `(start, value)` for every match in `buffer` that ends past
+    /// its first `carried` bytes, which were scanned with the previous chunk.
+    fn scan_buffer(&self, buffer: &[u8], carried: usize) -> Vec<(usize, usize)> {
+        let mut found = Vec::new();
+
+        for group in &self.groups {
+            if buffer.len() < group.len {
+                continue;
+            }
+
+            let mut fingerprint = self.fingerprint(&buffer[..group.len]);
+            for start in 0..=buffer.len() - group.len {
+                if start > 0 {
+                    let out = u64::from(buffer[start - 1]).wrapping_mul(group.high);
+                    fingerprint = fingerprint
+                        .wrapping_sub(out)
+                        .wrapping_mul(self.base)
+                        .wrapping_add(u64::from(buffer[start + group.len - 1]));
+                }
+                if start + group.len <= carried {
+                    continue;
+                }
+
+                if let Some(targets) = group.targets.get(&fingerprint) {
+                    let digest = self.digest(&buffer[start..start + group.len]);
+                    for target in targets.iter().filter(|t| t.digest == digest) {
+                        found.push((start, target.value));
+                    }
+                }
+            }
+        }
+
+        found
+    }
+
+    /// Polynomial fingerprint of `bytes` under the scanner's base.
+    fn fingerprint(&self, bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0u64, |fingerprint, &b| {
+            fingerprint
+                .wrapping_mul(self.base)
+                .wrapping_add(u64::from(b))
+        })
+    }
+
+    /// SHA-256 of `bytes` under the scanner's salt.
+    fn digest(&self, bytes: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt);
+        hasher.update(bytes);
+        hasher.finalize().into()
+    }
+}
+
+fn count_lines(bytes: &[u8]) -> u64 {
+    bytes.iter().filter(|&&b| b == b'\n').count() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out at most `step` bytes per read, to exercise chunking.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.data.len().min(self.step).min(buf.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_finds_values_with_lines() {
+        let mut scanner = LeakScanner::new();
+        let token = scanner.add(b"ghp_abcdef123456").unwrap();
+        let password = scanner.add(b"hunter2hunter2").unwrap();
+
+        let data = b"# config\nTOKEN=ghp_abcdef123456\n\npassword: hunter2hunter2 # again ghp_abcdef123456\n";
+        let matches = scanner.scan(&data[..]).unwrap();
+
+        assert_eq!(
+            matches,
+            vec![
+                LeakMatch {
+                    value: token,
+                    offset: 15,
+                    line: 2
+                },
+                LeakMatch {
+                    value: password,
+                    offset: 43,
+                    line: 4
+                },
+                LeakMatch {
+                    value: token,
+                    offset: 66,
+                    line: 4
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_short_values_are_ignored() {
+        let mut scanner = LeakScanner::new();
+        assert_eq!(scanner.add(b"short"), None);
+        assert!(scanner.is_empty());
+        assert!(scanner.scan(&b"short short"[..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_no_false_positives() {
+        let mut scanner = LeakScanner::new();
+        scanner.add(b"s3cr3t-value").unwrap();
+        let matches = scanner
+            .scan(&b"s3cr3t-valu s3cr3t-valuE 3cr3t-value"[..])
+            .unwrap();
+        assert!(matches.is_empty());
+    }
+
+    #[test]
+    fn test_match_across_chunks() {
+        let secret = b"sk_live_0123456789";
+        let mut data = vec![b'x'; CHUNK_SIZE - 5];
+        data.push(b'\n');
+        data.extend_from_slice(secret);
+        data.extend(vec![b'y'; CHUNK_SIZE]);
+
+        let mut scanner = LeakScanner::new();
+        scanner.add(secret).unwrap();
+
+        for step in [CHUNK_SIZE, 7, 1000] {
+            let matches = scanner.scan(Trickle { data: &data, step }).unwrap();
+            assert_eq!(
+                matches,
+                vec![LeakMatch {
+                    value: 0,
+                    offset: (CHUNK_SIZE - 4) as u64,
+                    line: 2
+                }]
+            );
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Passphrase-encrypted values for sharing a single secret outside the
+//! vault, e.g. through a QR code scanned by a phone.
+//!
+//! The key is derived from the passphrase with the browser Argon2id preset,
+//! so the value can be opened quickly by the WASM build on a phone.
+//!
+//! # Format
+//! ```text
+//! "vxs1:" || base64url(salt (32) || nonce (12) || AES-256-GCM ciphertext)
+//! ```
+//! The prefix is authenticated as associated data.
+
+use crate::crypto::{self, EncryptedData, KdfParams, SecretBytes, NONCE_SIZE, SALT_SIZE};
+use crate::error::VaultError;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+/// Prefix identifying (and versioning) a shared value
+pub const PREFIX: &str = "vxs1:";
+
+/// Encrypts `value` under `passphrase` into a printable string.
+pub fn seal(value: &[u8], passphrase: &[u8]) -> Result<String, VaultError> {
+    let salt = crypto::generate_salt();
+    let key = crypto::derive_key_with_params(passphrase, &salt, &KdfParams::BROWSER)?;
+    let encrypted = crypto::encrypt_with_aad(value, &key, PREFIX.as_bytes())?;
+
+    let mut data = Vec::with_capacity(SALT_SIZE + NONCE_SIZE + encrypted.ciphertext.len());
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&encrypted.nonce);
+    data.extend_from_slice(&encrypted.ciphertext);
+    Ok(format!("{}{}", PREFIX, URL_SAFE_NO_PAD.encode(data)))
+}
+
+/// Decrypts a string made by `seal`.
+///
+/// # Errors
+/// - `VaultError::InvalidFormat` if `text` is not a shared value
+/// - `VaultError::AuthenticationFailed` if the passphrase is wrong or the
+///   value was modified
+pub fn open(text: &str, passphrase: &[u8]) -> Result<SecretBytes, VaultError> {
+    let invalid = || VaultError::InvalidFormat("not a VaultX shared value".to_string());
+    let data = text
+        .trim()
+        .strip_prefix(PREFIX)
+        .and_then(|encoded| URL_SAFE_NO_PAD.decode(encoded).ok())
+        .filter(|data| data.len() >= SALT_SIZE + NONCE_SIZE)
+        .ok_or_else(invalid)?;
+
+    let (salt, rest) = data.split_at(SALT_SIZE);
+    let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+    let encrypted = EncryptedData {
+        ciphertext: ciphertext.to_vec(),
+        nonce: nonce.try_into().unwrap(),
+    };
+
+    let key = crypto::derive_key_with_params(passphrase, salt, &KdfParams::BROWSER)?;
+    crypto::decrypt_with_aad(&encrypted, &key, PREFIX.as_bytes())
+        .map_err(|_| VaultError::AuthenticationFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_value_roundtrip() {
+        let text = seal(b"sk_live_123", b"correct horse").unwrap();
+        assert!(text.starts_with(PREFIX));
+
+        assert_eq!(open(&text, b"correct horse").unwrap().expose(), b"sk_live_123");
+        assert!(matches!(
+            open(&text, b"wrong"),
+            Err(VaultError::AuthenticationFailed)
+        ));
+        assert!(matches!(
+            open("sk_live_123", b"correct horse"),
+            Err(VaultError::InvalidFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,190 @@
+//! A minimal SSH agent holding ed25519 keys.
+//!
+//! `vx ssh` and `vx scp` offer the decrypted identity to `ssh` as an agent
+//! instead of writing it to a key file, so the private key never leaves
+//! this process. It usually holds one key; a copy between two servers
+//! with different identities needs both. Only the two requests `ssh` needs
+//! to authenticate are answered: listing identities and signing; everything
+//! else, including adding or removing keys, gets `SSH_AGENT_FAILURE`.
+//!
+//! This module handles message bodies; reading and writing the 4-byte
+//! length prefixes on the socket is left to the caller.
+//!
+//! # Security Notes
+//! - Sign requests are only answered for the held keys
+//! - Every reply is built from public data and signatures
+
+use crate::error::SshError;
+use crate::ssh::{public_key_blob, reconstruct_signing_key};
+use ed25519_dalek::{Signer, SigningKey};
+
+/// Largest message body accepted, as in OpenSSH's agent
+pub const MAX_MESSAGE_LEN: usize = 256 * 1024;
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+/// Comment shown for the key by `ssh-add -l`
+const KEY_COMMENT: &[u8] = b"vaultx";
+
+/// An agent holding identities. The keys are zeroized when dropped.
+pub struct Agent {
+    keys: Vec<SigningKey>,
+}
+
+impl Agent {
+    /// Creates an agent for `private_key`, a stored 32-byte ed25519 seed.
+    ///
+    /// # Errors
+    /// `SshError::InvalidKeyFormat` if the key is not 32 bytes.
+    pub fn new(private_key: &[u8]) -> Result<Self, SshError> {
+        Self::with_keys(&[private_key])
+    }
+
+    /// Creates an agent for several stored seeds, offered in that order.
+    ///
+    /// # Errors
+    /// `SshError::InvalidKeyFormat` if a key is not 32 bytes.
+    pub fn with_keys(private_keys: &[&[u8]]) -> Result<Self, SshError> {
+        let keys = private_keys
+            .iter()
+            .map(|key| reconstruct_signing_key(key))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { keys })
+    }
+
+    /// Returns the reply to the request `message`, both without their
+    /// length prefix.
+    pub fn handle_message(&self, message: &[u8]) -> Vec<u8> {
+        let reply = match message.split_first() {
+            Some((&SSH_AGENTC_REQUEST_IDENTITIES, _)) => Some(identities_answer(&self.keys)),
+            Some((&SSH_AGENTC_SIGN_REQUEST, body)) => sign_response(&self.keys, body),
+            _ => None,
+        };
+        reply.unwrap_or_else(|| vec![SSH_AGENT_FAILURE])
+    }
+}
+
+fn identities_answer(keys: &[SigningKey]) -> Vec<u8> {
+    let mut reply = vec![SSH_AGENT_IDENTITIES_ANSWER];
+    reply.extend_from_slice(&(keys.len() as u32).to_be_bytes());
+    for key in keys {
+        put_string(&mut reply, &public_key_blob(&key.verifying_key()));
+        put_string(&mut reply, KEY_COMMENT);
+    }
+    reply
+}
+
+/// Signs the data of a sign request with the held key it names. The flags
+/// only select RSA hash algorithms, so they are ignored.
+fn sign_response(keys: &[SigningKey], body: &[u8]) -> Option<Vec<u8>> {
+    let (key_blob, rest) = take_string(body)?;
+    let (data, _flags) = take_string(rest)?;
+    let key = keys
+        .iter()
+        .find(|key| key_blob == public_key_blob(&key.verifying_key()).as_slice())?;
+
+    let mut signature = Vec::new();
+    put_string(&mut signature, b"ssh-ed25519");
+    put_string(&mut signature, &key.sign(data).to_bytes());
+
+    let mut reply = vec![SSH_AGENT_SIGN_RESPONSE];
+    put_string(&mut reply, &signature);
+    Some(reply)
+}
+
+/// Splits an SSH `string` (u32 length and bytes) off the front of `data`.
+fn take_string(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (len, rest) = data.split_first_chunk::<4>()?;
+    let len = u32::from_be_bytes(*len) as usize;
+    (rest.len() >= len).then(|| rest.split_at(len))
+}
+
+fn put_string(out: &mut Vec<u8>, value: &[u8]) {
+    out.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    out.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signature, Verifier};
+
+    fn sign_request(key_blob: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut request = vec![SSH_AGENTC_SIGN_REQUEST];
+        put_string(&mut request, key_blob);
+        put_string(&mut request, data);
+        request.extend_from_slice(&0u32.to_be_bytes());
+        request
+    }
+
+    #[test]
+    fn test_lists_the_key() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let reply = Agent::new(&[7; 32])
+            .unwrap()
+            .handle_message(&[SSH_AGENTC_REQUEST_IDENTITIES]);
+
+        assert_eq!(reply[0], SSH_AGENT_IDENTITIES_ANSWER);
+        assert_eq!(reply[1..5], 1u32.to_be_bytes());
+        let (blob, rest) = take_string(&reply[5..]).unwrap();
+        assert_eq!(blob, public_key_blob(&key.verifying_key()).as_slice());
+        assert_eq!(take_string(rest).unwrap(), (KEY_COMMENT, &[][..]));
+    }
+
+    #[test]
+    fn test_signs_for_the_key() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let blob = public_key_blob(&key.verifying_key());
+        let reply = Agent::new(&[7; 32])
+            .unwrap()
+            .handle_message(&sign_request(&blob, b"session data"));
+
+        assert_eq!(reply[0], SSH_AGENT_SIGN_RESPONSE);
+        let (signature, _) = take_string(&reply[1..]).unwrap();
+        let (algorithm, rest) = take_string(signature).unwrap();
+        let (bytes, _) = take_string(rest).unwrap();
+        assert_eq!(algorithm, b"ssh-ed25519");
+        let signature = Signature::from_slice(bytes).unwrap();
+        assert!(key
+            .verifying_key()
+            .verify(b"session data", &signature)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_holds_several_keys() {
+        let agent = Agent::with_keys(&[&[7; 32], &[8; 32]]).unwrap();
+        let reply = agent.handle_message(&[SSH_AGENTC_REQUEST_IDENTITIES]);
+        assert_eq!(reply[1..5], 2u32.to_be_bytes());
+
+        let second = SigningKey::from_bytes(&[8; 32]);
+        let blob = public_key_blob(&second.verifying_key());
+        let reply = agent.handle_message(&sign_request(&blob, b"data"));
+        assert_eq!(reply[0], SSH_AGENT_SIGN_RESPONSE);
+        let (signature, _) = take_string(&reply[1..]).unwrap();
+        let (_, rest) = take_string(signature).unwrap();
+        let (bytes, _) = take_string(rest).unwrap();
+        let signature = Signature::from_slice(bytes).unwrap();
+        assert!(second.verifying_key().verify(b"data", &signature).is_ok());
+    }
+
+    #[test]
+    fn test_refuses_other_requests() {
+        let agent = Agent::new(&[7; 32]).unwrap();
+        let other = SigningKey::from_bytes(&[8; 32]);
+        let other_blob = public_key_blob(&other.verifying_key());
+
+        for request in [
+            sign_request(&other_blob, b"data"),
+            vec![SSH_AGENTC_SIGN_REQUEST, 0, 0, 1],
+            vec![17], // SSH_AGENTC_ADD_IDENTITY
+            Vec::new(),
+        ] {
+            assert_eq!(agent.handle_message(&request), [SSH_AGENT_FAILURE]);
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! Backups of single SSH identities.
+//!
+//! A backup holds one identity, decrypted, together with the servers
+//! configured to use it, encrypted under a passphrase chosen by the user.
+//! Unlike a transfer bundle it does not depend on the vault it came from:
+//! restoring it on another machine re-encrypts the key under that vault's
+//! data key.
+//!
+//! # Format
+//! ```text
+//! MAGIC (8) || info length (u32 LE) || info (JSON) || nonce (12) || ciphertext
+//! ```
+//! As in `transfer`: the ciphertext is the CBOR-encoded backup under
+//! AES-256-GCM, everything before the nonce is authenticated as associated
+//! data, and the key is derived from the passphrase with Argon2id using the
+//! salt and parameters stored in the info.
+
+use crate::crypto::{self, EncryptedData, KdfParams, KEY_SIZE, NONCE_SIZE};
+use crate::error::VaultError;
+use crate::ttl;
+use crate::vault::{SshServerConfig, Vault};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Magic bytes identifying an identity backup
+pub const MAGIC: &[u8; 8] = b"\0VXSSHK\0";
+
+/// Version of the backup format written by `seal`
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Size of the info length field
+const INFO_LEN_SIZE: usize = 4;
+
+/// The readable description at the start of a backup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    /// Backup format version
+    pub version: u32,
+    /// Name of the identity in the vault it came from
+    pub identity: String,
+    /// Version of VaultX that made the backup
+    pub created_with: String,
+    /// When the backup was made
+    pub created_at: u64,
+    /// Argon2id parameters deriving the backup key from the passphrase
+    pub kdf: KdfParams,
+    #[serde(with = "crate::vault::base64_serde")]
+    pub salt: Vec<u8>,
+}
+
+/// An identity and its servers, as carried by a backup. The private key is
+/// wiped when dropped.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IdentityBackup {
+    pub name: String,
+    pub public_key: String,
+    /// The 32-byte ed25519 seed, unencrypted
+    #[serde(with = "crate::vault::base64_serde")]
+    pub private_key: Vec<u8>,
+    pub created_at: u64,
+    /// Servers configured with the identity, including recorded host keys
+    pub servers: Vec<SshServerConfig>,
+}
+
+impl Drop for IdentityBackup {
+    fn drop(&mut self) {
+        self.private_key.zeroize();
+    }
+}
+
+/// Collects the identity `name` and the servers using it from `vault`,
+/// sorted by server name.
+///
+/// # Errors
+/// `VaultError::IdentityNotFound` if there is no such identity.
+pub fn collect(
+    vault: &Vault,
+    name: &str,
+    encryption_key: &[u8; KEY_SIZE],
+) -> Result<IdentityBackup, VaultError> {
+    let (public_key, private_key) = vault.get_ssh_identity(name, encryption_key)?;
+    let mut servers: Vec<SshServerConfig> = vault
+        .ssh_servers
+        .values()
+        .filter(|server| server.identity_name == name)
+        .cloned()
+        .collect();
+    servers.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(IdentityBackup {
+        name: name.to_string(),
+        public_key,
+        private_key: private_key.expose().to_vec(),
+        created_at: vault.ssh_identities[name].created_at,
+        servers,
+    })
+}
+
+/// Adds `backup` to `vault` as the identity `name`, with its servers
+/// pointed at it. The identity keeps its creation time, so key age audits
+/// still apply. Servers whose names are already configured are left as
+/// they are; their names are returned.
+///
+/// # Errors
+/// `VaultError::IdentityAlreadyExists` if `name` is taken.
+pub fn restore(
+    vault: &mut Vault,
+    backup: &IdentityBackup,
+    name: &str,
+    encryption_key: &[u8; KEY_SIZE],
+) -> Result<Vec<String>, VaultError> {
+    vault.add_ssh_identity(
+        name,
+        backup.public_key.clone(),
+        &backup.private_key,
+        encryption_key,
+    )?;
+    if let Some(identity) = vault.ssh_identities.get_mut(name) {
+        identity.created_at = backup.created_at;
+    }
+
+    let mut skipped = Vec::new();
+    for server in &backup.servers {
+        if vault.has_ssh_server(&server.name) {
+            skipped.push(server.name.clone());
+            continue;
+        }
+        vault.add_ssh_server(
+            &server.name,
+            server.username.clone(),
+            server.ip_address.clone(),
+            name.to_string(),
+        )?;
+        vault.set_ssh_server_multiplex(&server.name, server.multiplex)?;
+        vault.set_ssh_server_host_keys(&server.name, server.host_keys.clone())?;
+    }
+    Ok(skipped)
+}
+
+/// Encrypts `backup` under `passphrase`.
+pub fn seal(backup: &IdentityBackup, passphrase: &[u8]) -> Result<Vec<u8>, VaultError> {
+    let info = BackupInfo {
+        version: BACKUP_VERSION,
+        identity: backup.name.clone(),
+        created_with: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: ttl::current_timestamp(),
+        kdf: KdfParams::STANDARD,
+        salt: crypto::generate_salt().to_vec(),
+    };
+    let info_json =
+        serde_json::to_vec(&info).map_err(|e| VaultError::SerializationError(e.to_string()))?;
+    let info_len = u32::try_from(info_json.len())
+        .map_err(|_| VaultError::SerializationError("backup info too large".to_string()))?;
+
+    let mut data = Vec::new();
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&info_len.to_le_bytes());
+    data.extend_from_slice(&info_json);
+
+    let mut plaintext = Zeroizing::new(Vec::new());
+    ciborium::ser::into_writer(backup, &mut *plaintext)
+        .map_err(|e| VaultError::SerializationError(e.to_string()))?;
+
+    let key = crypto::derive_key_with_params(passphrase, &info.salt, &info.kdf)?;
+    let encrypted = crypto::encrypt_with_aad(&plaintext, &key, &data)?;
+
+    data.extend_from_slice(&encrypted.nonce);
+    data.extend_from_slice(&encrypted.ciphertext);
+    Ok(data)
+}
+
+/// Reads the info of a backup without decrypting it.
+///
+/// # Errors
+/// `VaultError::InvalidFormat` if `data` is not an identity backup.
+pub fn read_info(data: &[u8]) -> Result<BackupInfo, VaultError> {
+    let (info, _) = split_backup(data)?;
+    Ok(info)
+}
+
+/// Decrypts a backup made by `seal`.
+///
+/// # Errors
+/// - `VaultError::InvalidFormat` if `data` is not an identity backup, or
+///   one in a format this version cannot read
+/// - `VaultError::AuthenticationFailed` if the passphrase is wrong or the
+///   backup was modified
+pub fn open(data: &[u8], passphrase: &[u8]) -> Result<IdentityBackup, VaultError> {
+    let (info, body_start) = split_backup(data)?;
+    if info.version > BACKUP_VERSION {
+        return Err(VaultError::InvalidFormat(format!(
+            "the backup was made by VaultX {}; update vx to restore it",
+            info.created_with
+        )));
+    }
+
+    let (aad, body) = data.split_at(body_start);
+    let nonce = body
+        .get(..NONCE_SIZE)
+        .and_then(|nonce| nonce.try_into().ok())
+        .ok_or(VaultError::CorruptedVault)?;
+    let encrypted = EncryptedData {
+        ciphertext: body[NONCE_SIZE..].to_vec(),
+        nonce,
+    };
+
+    let key = crypto::derive_key_with_params(passphrase, &info.salt, &info.kdf)?;
+    let plaintext = crypto::decrypt_with_aad(&encrypted, &key, aad)
+        .map_err(|_| VaultError::AuthenticationFailed)?;
+
+    ciborium::de::from_reader(plaintext.expose()).map_err(|_| VaultError::CorruptedVault)
+}
+
+/// Splits a backup into its info and the offset of the nonce.
+fn split_backup(data: &[u8]) -> Result<(BackupInfo, usize), VaultError> {
+    if !data.starts_with(MAGIC) {
+        return Err(VaultError::InvalidFormat(
+            "not a VaultX SSH identity backup".to_string(),
+        ));
+    }
+
+    let info_start = MAGIC.len() + INFO_LEN_SIZE;
+    let info_len = data
+        .get(MAGIC.len()..info_start)
+        .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+        .ok_or(VaultError::CorruptedVault)?;
+    let info_end = info_start
+        .checked_add(info_len)
+        .ok_or(VaultError::CorruptedVault)?;
+    let info = data
+        .get(info_start..info_end)
+        .ok_or(VaultError::CorruptedVault)?;
+
+    let info = serde_json::from_slice(info).map_err(|_| VaultError::CorruptedVault)?;
+    Ok((info, info_end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; KEY_SIZE] = [3; KEY_SIZE];
+
+    fn vault_with_identity() -> Vault {
+        let mut vault = Vault::new();
+        vault
+            .add_ssh_identity(
+                "deploy",
+                "ssh-ed25519 AAAA deploy".to_string(),
+                &[9; 32],
+                &KEY,
+            )
+            .unwrap();
+        vault
+            .add_ssh_identity(
+                "other",
+                "ssh-ed25519 BBBB other".to_string(),
+                &[8; 32],
+                &KEY,
+            )
+            .unwrap();
+        for (server, identity) in [("web", "deploy"), ("db", "deploy"), ("ci", "other")] {
+            vault
+                .add_ssh_server(
+                    server,
+                    "root".to_string(),
+                    "10.0.0.1".to_string(),
+                    identity.to_string(),
+                )
+                .unwrap();
+        }
+        vault
+            .set_ssh_server_host_keys("web", vec!["ssh-ed25519 HOST".to_string()])
+            .unwrap();
+        vault
+    }
+
+    #[test]
+    fn test_backup_roundtrip() {
+        let backup = collect(&vault_with_identity(), "deploy", &KEY).unwrap();
+        let names: Vec<_> = backup.servers.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["db", "web"]);
+
+        let data = seal(&backup, b"passphrase").unwrap();
+        assert_eq!(read_info(&data).unwrap().identity, "deploy");
+        assert!(!data.windows(32).any(|w| w == [9; 32]));
+
+        let opened = open(&data, b"passphrase").unwrap();
+        assert_eq!(opened.private_key, [9; 32]);
+        assert_eq!(opened.public_key, "ssh-ed25519 AAAA deploy");
+
+        let other_key = [4; KEY_SIZE];
+        let mut vault = Vault::new();
+        vault
+            .add_ssh_identity("x", "ssh-ed25519 CCCC x".to_string(), &[1; 32], &other_key)
+            .unwrap();
+        vault
+            .add_ssh_server(
+                "db",
+                "admin".to_string(),
+                "10.0.0.9".to_string(),
+                "x".to_string(),
+            )
+            .unwrap();
+
+        let skipped = restore(&mut vault, &opened, "deploy-copy", &other_key).unwrap();
+        assert_eq!(skipped, ["db"]);
+        let (_, private_key) = vault.get_ssh_identity("deploy-copy", &other_key).unwrap();
+        assert_eq!(private_key.expose(), [9; 32]);
+        assert_eq!(
+            vault.ssh_identities["deploy-copy"].created_at,
+            backup.created_at
+        );
+        assert_eq!(vault.ssh_servers["db"].identity_name, "x");
+        assert_eq!(vault.ssh_servers["web"].identity_name, "deploy-copy");
+        assert_eq!(vault.ssh_servers["web"].host_keys, ["ssh-ed25519 HOST"]);
+
+        assert!(matches!(
+            restore(&mut vault, &opened, "x", &other_key),
+            Err(VaultError::IdentityAlreadyExists(_))
+        ));
+    }
+
+    #[test]
+    fn test_backup_rejects_wrong_passphrase_and_tampering() {
+        let backup = collect(&vault_with_identity(), "deploy", &KEY).unwrap();
+        let mut data = seal(&backup, b"passphrase").unwrap();
+        assert!(matches!(
+            open(&data, b"wrong"),
+            Err(VaultError::AuthenticationFailed)
+        ));
+
+        // The info is authenticated with the contents
+        let at = data
+            .windows(b"deploy".len())
+            .position(|w| w == b"deploy")
+            .unwrap();
+        data[at] = b'D';
+        assert_eq!(read_info(&data).unwrap().identity, "Deploy");
+        assert!(matches!(
+            open(&data, b"passphrase"),
+            Err(VaultError::AuthenticationFailed)
+        ));
+
+        assert!(matches!(
+            read_info(b"\0VXMOVE\0"),
+            Err(VaultError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            collect(&vault_with_identity(), "missing", &KEY),
+            Err(VaultError::IdentityNotFound(_))
+        ));
+    }
+}
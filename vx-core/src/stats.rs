@@ -0,0 +1,228 @@
+//! Statistics about what a vault holds and how large it is.
+//!
+//! Like the audit, the statistics only read metadata; sizes are those of
+//! the ciphertexts, and no secret value is decrypted.
+
+use crate::audit::SECONDS_PER_DAY;
+use crate::ttl;
+use crate::vault::{SecretRef, Vault};
+use serde::Serialize;
+
+/// Number of largest and oldest secrets listed by default
+pub const DEFAULT_TOP: usize = 5;
+
+/// One secret in a ranking.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SecretStat {
+    pub location: SecretRef,
+    /// Size of the encrypted value in bytes
+    pub size: u64,
+    pub created_at: u64,
+}
+
+/// How many secrets expire within each period.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ExpiryDistribution {
+    pub expired: usize,
+    pub within_day: usize,
+    pub within_week: usize,
+    pub within_month: usize,
+    pub later: usize,
+    pub never: usize,
+}
+
+/// Statistics of a vault.
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultStats {
+    /// Unsealed projects
+    pub projects: usize,
+    /// Named environments in all projects
+    pub environments: usize,
+    /// Secrets in all environments, aliases excluded
+    pub secrets: usize,
+    pub aliases: usize,
+    pub attachments: usize,
+    pub ssh_identities: usize,
+    pub ssh_servers: usize,
+    pub kubeconfigs: usize,
+    pub wireguard_configs: usize,
+    pub trash_items: usize,
+    pub log_entries: usize,
+    /// Size of all encrypted secret values in bytes
+    pub secret_bytes: u64,
+    /// Size of all attachments in bytes
+    pub attachment_bytes: u64,
+    /// Largest secrets, largest first
+    pub largest: Vec<SecretStat>,
+    /// Oldest secrets, oldest first
+    pub oldest: Vec<SecretStat>,
+    pub expiry: ExpiryDistribution,
+}
+
+/// Gathers the statistics of a vault's unsealed projects, listing the `top`
+/// largest and oldest secrets.
+pub fn vault_stats(vault: &Vault, now: u64, top: usize) -> VaultStats {
+    let mut stats = VaultStats {
+        projects: vault.projects.len(),
+        environments: 0,
+        secrets: 0,
+        aliases: 0,
+        attachments: 0,
+        ssh_identities: vault.ssh_identities.len(),
+        ssh_servers: vault.ssh_servers.len(),
+        kubeconfigs: vault.kubeconfigs.len(),
+        wireguard_configs: vault.wireguard_configs.len(),
+        trash_items: vault.trash().len(),
+        log_entries: vault.access_log().len(),
+        secret_bytes: 0,
+        attachment_bytes: 0,
+        largest: Vec::new(),
+        oldest: Vec::new(),
+        expiry: ExpiryDistribution::default(),
+    };
+
+    let mut all = Vec::new();
+    for (name, project) in &vault.projects {
+        stats.environments += project.environments.len();
+        stats.attachments += project.attachments.len();
+        stats.attachment_bytes += project.attachments.values().map(|a| a.size).sum::<u64>();
+
+        for (env, secrets) in project.secrets_by_environment() {
+            for (key, secret) in secrets {
+                if secret.alias.is_some() {
+                    stats.aliases += 1;
+                    continue;
+                }
+                stats.secrets += 1;
+                let size = secret.encrypted_value.len() as u64;
+                stats.secret_bytes += size;
+                count_expiry(&mut stats.expiry, secret.expires_at, now);
+                all.push(SecretStat {
+                    location: SecretRef::new(name, env, key),
+                    size,
+                    created_at: secret.created_at,
+                });
+            }
+        }
+    }
+
+    // Ties are broken by name, so the rankings do not depend on map order
+    let by_name = |a: &SecretStat, b: &SecretStat| {
+        (&a.location.project, &a.location.env, &a.location.key).cmp(&(
+            &b.location.project,
+            &b.location.env,
+            &b.location.key,
+        ))
+    };
+    all.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| by_name(a, b)));
+    stats.largest = all.iter().take(top).cloned().collect();
+    all.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| by_name(a, b)));
+    stats.oldest = all.into_iter().take(top).collect();
+
+    stats
+}
+
+fn count_expiry(expiry: &mut ExpiryDistribution, expires_at: Option<u64>, now: u64) {
+    let Some(expires_at) = expires_at else {
+        expiry.never += 1;
+        return;
+    };
+    if ttl::is_expired(Some(expires_at), now) {
+        expiry.expired += 1;
+        return;
+    }
+    let days = expires_at.saturating_sub(now) / SECONDS_PER_DAY;
+    match days {
+        0 => expiry.within_day += 1,
+        1..=6 => expiry.within_week += 1,
+        7..=29 => expiry.within_month += 1,
+        _ => expiry.later += 1,
+    }
+}
+
+/// Formats a size in bytes with a binary unit, e.g. `12.5 KiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KEY_SIZE;
+
+    #[test]
+    fn test_vault_stats() {
+        let mut vault = Vault::new();
+        let key = [0u8; KEY_SIZE];
+        vault.init_project("app").unwrap();
+        vault.init_project("web").unwrap();
+        vault.add_secret("app", "SMALL", b"x", &key, None).unwrap();
+        vault
+            .add_secret_in("app", Some("prod"), "LARGE", &[0u8; 100], &key, Some(3600))
+            .unwrap();
+        vault
+            .add_secret("web", "WEEK", b"xx", &key, Some(3 * SECONDS_PER_DAY))
+            .unwrap();
+        vault
+            .add_alias("web", None, "LINK", SecretRef::new("app", None, "SMALL"))
+            .unwrap();
+        vault
+            .projects
+            .get_mut("app")
+            .unwrap()
+            .secrets
+            .get_mut("SMALL")
+            .unwrap()
+            .created_at = 1;
+
+        let now = ttl::current_timestamp();
+        let stats = vault_stats(&vault, now, 2);
+        assert_eq!(stats.projects, 2);
+        assert_eq!(stats.environments, 1);
+        assert_eq!(stats.secrets, 3);
+        assert_eq!(stats.aliases, 1);
+        assert_eq!(stats.secret_bytes, 103 + 3 * crate::crypto::TAG_SIZE as u64);
+
+        assert_eq!(stats.largest.len(), 2);
+        assert_eq!(
+            stats.largest[0].location,
+            SecretRef::new("app", Some("prod"), "LARGE")
+        );
+        assert_eq!(
+            stats.largest[1].location,
+            SecretRef::new("web", None, "WEEK")
+        );
+        assert_eq!(
+            stats.oldest[0].location,
+            SecretRef::new("app", None, "SMALL")
+        );
+
+        assert_eq!(
+            stats.expiry,
+            ExpiryDistribution {
+                within_day: 1,
+                within_week: 1,
+                never: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
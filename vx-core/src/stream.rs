@@ -0,0 +1,365 @@
+//! Chunked AES-256-GCM encryption for large blobs.
+//!
+//! Data is split into `CHUNK_SIZE` chunks, each sealed separately, so
+//! neither side has to hold the whole plaintext or ciphertext in memory.
+//! Attachment blobs use this format, and the WASM bindings expose the same
+//! encryptor so a blob written in a browser opens natively and vice versa.
+//!
+//! # Format
+//! ```text
+//! MAGIC (4) || nonce prefix (7) || chunk 0 || chunk 1 || ... || final chunk
+//! ```
+//! Every chunk is `CHUNK_SIZE` bytes of plaintext plus a 16-byte tag, except
+//! the final one, which may be shorter (or empty, for empty input). Chunk
+//! nonces are `prefix || counter (u32 BE) || final flag`, so chunks cannot be
+//! reordered, dropped or truncated without decryption failing. Every chunk
+//! authenticates the same associated data, if any, so a blob only opens in
+//! the context it was written for.
+
+use crate::crypto::{SecretBytes, KEY_SIZE, NONCE_SIZE};
+use crate::error::CryptoError;
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+/// Magic bytes identifying a chunked blob
+pub const MAGIC: &[u8; 4] = b"VXS1";
+
+/// Plaintext bytes per chunk (64 KiB)
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Size of the AES-GCM authentication tag appended to each chunk
+const TAG_SIZE: usize = 16;
+
+/// Size of the random per-blob nonce prefix
+const PREFIX_SIZE: usize = NONCE_SIZE - 5;
+
+/// Size of the blob header
+pub const HEADER_SIZE: usize = MAGIC.len() + PREFIX_SIZE;
+
+/// Incremental encryptor for the chunked format.
+///
+/// Concatenating the output of every `push` call and of `finish` yields the
+/// complete blob, header included.
+pub struct StreamEncryptor {
+    cipher: Aes256Gcm,
+    aad: Vec<u8>,
+    prefix: [u8; PREFIX_SIZE],
+    counter: u32,
+    header_written: bool,
+    buffer: Zeroizing<Vec<u8>>,
+}
+
+impl StreamEncryptor {
+    /// Starts a new blob with a random nonce prefix.
+    pub fn new(key: &[u8; KEY_SIZE]) -> Result<Self, CryptoError> {
+        Self::with_aad(key, &[])
+    }
+
+    /// Starts a new blob that authenticates `aad` with every chunk; it
+    /// opens only with a `StreamDecryptor::with_aad` given the same bytes.
+    pub fn with_aad(key: &[u8; KEY_SIZE], aad: &[u8]) -> Result<Self, CryptoError> {
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| CryptoError::InvalidKeyLength)?;
+
+        let mut prefix = [0u8; PREFIX_SIZE];
+        rand::thread_rng().fill_bytes(&mut prefix);
+
+        Ok(Self {
+            cipher,
+            aad: aad.to_vec(),
+            prefix,
+            counter: 0,
+            header_written: false,
+            buffer: Zeroizing::new(Vec::new()),
+        })
+    }
+
+    /// Encrypts `data`, returning the chunks completed so far.
+    ///
+    /// At least one byte is held back until `finish`, since the last chunk
+    /// is sealed differently and is only known once the input ends.
+    pub fn push(&mut self, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        self.buffer.extend_from_slice(data);
+
+        let mut out = self.take_header();
+        let mut offset = 0;
+        while self.buffer.len() - offset > CHUNK_SIZE {
+            let chunk = &self.buffer[offset..offset + CHUNK_SIZE];
+            out.extend(seal(&self.cipher, &self.aad, &self.prefix, self.counter, false, chunk)?);
+            self.counter = self.counter.checked_add(1).ok_or(CryptoError::EncryptionFailed)?;
+            offset += CHUNK_SIZE;
+        }
+        self.buffer.drain(..offset);
+
+        Ok(out)
+    }
+
+    /// Seals the remaining input as the final chunk.
+    pub fn finish(mut self) -> Result<Vec<u8>, CryptoError> {
+        let mut out = self.take_header();
+        out.extend(seal(&self.cipher, &self.aad, &self.prefix, self.counter, true, &self.buffer)?);
+        Ok(out)
+    }
+
+    fn take_header(&mut self) -> Vec<u8> {
+        if self.header_written {
+            return Vec::new();
+        }
+        self.header_written = true;
+
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&self.prefix);
+        header
+    }
+}
+
+/// Incremental decryptor for the chunked format.
+///
+/// Accepts the blob in pieces of any size. Plaintext is only returned for
+/// chunks that have been authenticated, but a blob is not known to be
+/// complete until `finish` succeeds.
+pub struct StreamDecryptor {
+    cipher: Aes256Gcm,
+    aad: Vec<u8>,
+    prefix: Option<[u8; PREFIX_SIZE]>,
+    counter: u32,
+    buffer: Vec<u8>,
+}
+
+impl StreamDecryptor {
+    pub fn new(key: &[u8; KEY_SIZE]) -> Result<Self, CryptoError> {
+        Self::with_aad(key, &[])
+    }
+
+    /// Starts reading a blob written by `StreamEncryptor::with_aad`.
+    pub fn with_aad(key: &[u8; KEY_SIZE], aad: &[u8]) -> Result<Self, CryptoError> {
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| CryptoError::InvalidKeyLength)?;
+
+        Ok(Self {
+            cipher,
+            aad: aad.to_vec(),
+            prefix: None,
+            counter: 0,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Decrypts `data`, returning the plaintext of the chunks completed so
+    /// far.
+    ///
+    /// # Errors
+    /// `CryptoError::InvalidStreamHeader` if the blob does not start with
+    /// `MAGIC`, `CryptoError::DecryptionFailed` if a chunk fails to
+    /// authenticate.
+    pub fn push(&mut self, data: &[u8]) -> Result<SecretBytes, CryptoError> {
+        self.buffer.extend_from_slice(data);
+
+        let prefix = match self.prefix {
+            Some(prefix) => prefix,
+            None if self.buffer.len() < HEADER_SIZE => return Ok(SecretBytes::new(Vec::new())),
+            None => self.take_header()?,
+        };
+
+        let mut out = Zeroizing::new(Vec::new());
+        let mut offset = 0;
+        while self.buffer.len() - offset > CHUNK_SIZE + TAG_SIZE {
+            let chunk = &self.buffer[offset..offset + CHUNK_SIZE + TAG_SIZE];
+            let opened = open(&self.cipher, &self.aad, &prefix, self.counter, false, chunk)?;
+            out.extend_from_slice(&opened);
+            self.counter = self.counter.checked_add(1).ok_or(CryptoError::DecryptionFailed)?;
+            offset += CHUNK_SIZE + TAG_SIZE;
+        }
+        self.buffer.drain(..offset);
+
+        Ok(SecretBytes::new(std::mem::take(&mut *out)))
+    }
+
+    /// Decrypts the final chunk, failing if the blob was truncated.
+    pub fn finish(mut self) -> Result<SecretBytes, CryptoError> {
+        let prefix = match self.prefix {
+            Some(prefix) => prefix,
+            None => self.take_header()?,
+        };
+
+        open(&self.cipher, &self.aad, &prefix, self.counter, true, &self.buffer)
+    }
+
+    fn take_header(&mut self) -> Result<[u8; PREFIX_SIZE], CryptoError> {
+        if self.buffer.len() < HEADER_SIZE || &self.buffer[..MAGIC.len()] != MAGIC {
+            return Err(CryptoError::InvalidStreamHeader);
+        }
+
+        let prefix: [u8; PREFIX_SIZE] = self.buffer[MAGIC.len()..HEADER_SIZE]
+            .try_into()
+            .expect("header slice has PREFIX_SIZE bytes");
+        self.buffer.drain(..HEADER_SIZE);
+        self.prefix = Some(prefix);
+
+        Ok(prefix)
+    }
+}
+
+/// Encrypts `plaintext` into a complete chunked blob.
+pub fn encrypt(plaintext: &[u8], key: &[u8; KEY_SIZE]) -> Result<Vec<u8>, CryptoError> {
+    encrypt_with_aad(plaintext, key, &[])
+}
+
+/// Encrypts `plaintext` into a complete chunked blob bound to `aad`.
+pub fn encrypt_with_aad(
+    plaintext: &[u8],
+    key: &[u8; KEY_SIZE],
+    aad: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let mut encryptor = StreamEncryptor::with_aad(key, aad)?;
+    let mut blob = encryptor.push(plaintext)?;
+    blob.extend(encryptor.finish()?);
+    Ok(blob)
+}
+
+/// Decrypts a complete chunked blob.
+pub fn decrypt(blob: &[u8], key: &[u8; KEY_SIZE]) -> Result<SecretBytes, CryptoError> {
+    decrypt_with_aad(blob, key, &[])
+}
+
+/// Decrypts a complete chunked blob written by `encrypt_with_aad`.
+pub fn decrypt_with_aad(
+    blob: &[u8],
+    key: &[u8; KEY_SIZE],
+    aad: &[u8],
+) -> Result<SecretBytes, CryptoError> {
+    let mut decryptor = StreamDecryptor::with_aad(key, aad)?;
+    let head = decryptor.push(blob)?;
+    let tail = decryptor.finish()?;
+
+    let mut plaintext = Zeroizing::new(Vec::with_capacity(head.len() + tail.len()));
+    plaintext.extend_from_slice(&head);
+    plaintext.extend_from_slice(&tail);
+    Ok(SecretBytes::new(std::mem::take(&mut *plaintext)))
+}
+
+fn chunk_nonce(prefix: &[u8; PREFIX_SIZE], counter: u32, last: bool) -> [u8; NONCE_SIZE] {
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce[..PREFIX_SIZE].copy_from_slice(prefix);
+    nonce[PREFIX_SIZE..NONCE_SIZE - 1].copy_from_slice(&counter.to_be_bytes());
+    nonce[NONCE_SIZE - 1] = u8::from(last);
+    nonce
+}
+
+fn seal(
+    cipher: &Aes256Gcm,
+    aad: &[u8],
+    prefix: &[u8; PREFIX_SIZE],
+    counter: u32,
+    last: bool,
+    chunk: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let nonce = chunk_nonce(prefix, counter, last);
+    cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: chunk, aad })
+        .map_err(|_| CryptoError::EncryptionFailed)
+}
+
+fn open(
+    cipher: &Aes256Gcm,
+    aad: &[u8],
+    prefix: &[u8; PREFIX_SIZE],
+    counter: u32,
+    last: bool,
+    chunk: &[u8],
+) -> Result<SecretBytes, CryptoError> {
+    let nonce = chunk_nonce(prefix, counter, last);
+    cipher
+        .decrypt(Nonce::from_slice(&nonce), Payload { msg: chunk, aad })
+        .map(SecretBytes::new)
+        .map_err(|_| CryptoError::DecryptionFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; KEY_SIZE] = [7u8; KEY_SIZE];
+
+    #[test]
+    fn test_roundtrip_across_chunk_boundaries() {
+        for len in [0, 1, CHUNK_SIZE - 1, CHUNK_SIZE, CHUNK_SIZE + 1, 3 * CHUNK_SIZE] {
+            let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let blob = encrypt(&plaintext, &KEY).unwrap();
+
+            let chunks = len.div_ceil(CHUNK_SIZE).max(1);
+            assert_eq!(blob.len(), HEADER_SIZE + len + chunks * TAG_SIZE);
+            assert_eq!(decrypt(&blob, &KEY).unwrap().expose(), plaintext.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_incremental_pushes_match_one_shot() {
+        let plaintext: Vec<u8> = (0..2 * CHUNK_SIZE + 100).map(|i| (i % 251) as u8).collect();
+
+        let mut encryptor = StreamEncryptor::new(&KEY).unwrap();
+        let mut blob = Vec::new();
+        for piece in plaintext.chunks(1000) {
+            blob.extend(encryptor.push(piece).unwrap());
+        }
+        blob.extend(encryptor.finish().unwrap());
+
+        let mut decryptor = StreamDecryptor::new(&KEY).unwrap();
+        let mut opened = Vec::new();
+        for piece in blob.chunks(777) {
+            opened.extend_from_slice(&decryptor.push(piece).unwrap());
+        }
+        opened.extend_from_slice(&decryptor.finish().unwrap());
+
+        assert_eq!(opened, plaintext);
+    }
+
+    #[test]
+    fn test_truncation_and_reordering_are_detected() {
+        let plaintext = vec![1u8; 2 * CHUNK_SIZE + 10];
+        let blob = encrypt(&plaintext, &KEY).unwrap();
+        let chunk = CHUNK_SIZE + TAG_SIZE;
+
+        // Dropping the final chunk leaves a full chunk not sealed as final
+        let truncated = &blob[..HEADER_SIZE + 2 * chunk];
+        assert!(matches!(decrypt(truncated, &KEY), Err(CryptoError::DecryptionFailed)));
+
+        let mut swapped = blob[..HEADER_SIZE].to_vec();
+        swapped.extend_from_slice(&blob[HEADER_SIZE + chunk..HEADER_SIZE + 2 * chunk]);
+        swapped.extend_from_slice(&blob[HEADER_SIZE..HEADER_SIZE + chunk]);
+        swapped.extend_from_slice(&blob[HEADER_SIZE + 2 * chunk..]);
+        assert!(matches!(decrypt(&swapped, &KEY), Err(CryptoError::DecryptionFailed)));
+    }
+
+    #[test]
+    fn test_associated_data_must_match() {
+        let plaintext = vec![3u8; CHUNK_SIZE + 10];
+        let blob = encrypt_with_aad(&plaintext, &KEY, b"app/cert.pem").unwrap();
+
+        assert_eq!(
+            decrypt_with_aad(&blob, &KEY, b"app/cert.pem").unwrap().expose(),
+            plaintext.as_slice()
+        );
+        for aad in [&b"other/cert.pem"[..], b""] {
+            assert!(matches!(
+                decrypt_with_aad(&blob, &KEY, aad),
+                Err(CryptoError::DecryptionFailed)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_rejects_wrong_key_and_bad_header() {
+        let blob = encrypt(b"data", &KEY).unwrap();
+
+        let wrong_key = [8u8; KEY_SIZE];
+        assert!(matches!(decrypt(&blob, &wrong_key), Err(CryptoError::DecryptionFailed)));
+        assert!(matches!(
+            decrypt(&blob[1..], &KEY),
+            Err(CryptoError::InvalidStreamHeader)
+        ));
+    }
+}
@@ -0,0 +1,170 @@
+//! Password strength estimation.
+//!
+//! A deliberately simple estimator: the entropy of a password drawn at
+//! random from the character classes it uses, discounting repeated
+//! characters, alphabetic and keyboard sequences, and common passwords.
+//! Human-chosen passwords are less random than that, so the estimate is an
+//! upper bound; callers should set their minimum with that in mind.
+//!
+//! # Security Notes
+//! - The password is only inspected, never stored or logged
+//! - Feedback names weaknesses, not the characters that caused them
+
+/// Minimum estimated entropy, in bits, for a new master password
+pub const DEFAULT_MIN_ENTROPY: u32 = 50;
+
+/// Length below which a password gets a suggestion to make it longer
+const RECOMMENDED_LENGTH: usize = 12;
+
+/// Entropy assigned to a password built around a common one
+const COMMON_PASSWORD_BITS: f64 = 16.0;
+
+/// Keyboard rows; four or more neighbouring keys count as a sequence
+const KEYBOARD_ROWS: [&str; 4] = ["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+/// Frequently used passwords and password stems, lower case
+const COMMON_PASSWORDS: &[&str] = &[
+    "password", "passw0rd", "p@ssw0rd", "qwerty", "letmein", "welcome", "admin", "login",
+    "iloveyou", "monkey", "dragon", "master", "shadow", "sunshine", "princess", "football",
+    "baseball", "superman", "batman", "trustno1", "secret", "freedom", "whatever", "starwars",
+    "hello", "abc123", "changeme", "vaultx",
+];
+
+/// Estimated strength of a password.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Strength {
+    /// Estimated entropy in bits, rounded down
+    pub entropy_bits: u32,
+    /// Suggestions for a stronger password, most important first
+    pub feedback: Vec<&'static str>,
+}
+
+/// Estimates the strength of `password`.
+pub fn estimate(password: &str) -> Strength {
+    let chars: Vec<char> = password.chars().collect();
+    let lower: Vec<char> = chars.iter().map(char::to_ascii_lowercase).collect();
+
+    let mut pool = 0u32;
+    let mut classes = 0;
+    for (present, size) in [
+        (chars.iter().any(|c| c.is_ascii_lowercase()), 26),
+        (chars.iter().any(|c| c.is_ascii_uppercase()), 26),
+        (chars.iter().any(|c| c.is_ascii_digit()), 10),
+        (
+            chars.iter().any(|c| c.is_ascii_punctuation() || *c == ' '),
+            33,
+        ),
+        (chars.iter().any(|c| !c.is_ascii()), 100),
+    ] {
+        if present {
+            pool += size;
+            classes += 1;
+        }
+    }
+
+    // Characters that continue a repeat or a sequence add almost nothing
+    let mut predictable = vec![false; chars.len()];
+    for i in 2..lower.len() {
+        let step = lower[i] as i64 - lower[i - 1] as i64;
+        if step.abs() <= 1 && step == lower[i - 1] as i64 - lower[i - 2] as i64 {
+            predictable[i] = true;
+        }
+    }
+    for start in 0..lower.len().saturating_sub(3) {
+        let window: String = lower[start..start + 4].iter().collect();
+        if KEYBOARD_ROWS.iter().any(|row| row.contains(&window)) {
+            predictable[start + 1..start + 4].fill(true);
+        }
+    }
+    let predictable_count = predictable.iter().filter(|&&p| p).count();
+    let random_count = chars.len() - predictable_count;
+
+    let mut bits = if pool == 0 {
+        0.0
+    } else {
+        random_count as f64 * f64::from(pool).log2() + predictable_count as f64
+    };
+
+    // Compare with and without the digits and symbols people tack on
+    let lower: String = lower.into_iter().collect();
+    let stem = lower.trim_matches(|c: char| !c.is_ascii_alphabetic());
+    let common = COMMON_PASSWORDS.contains(&lower.as_str()) || COMMON_PASSWORDS.contains(&stem);
+    if common {
+        bits = bits.min(COMMON_PASSWORD_BITS);
+    }
+
+    let mut feedback = Vec::new();
+    if common {
+        feedback.push("Avoid common passwords, even with digits or symbols added");
+    }
+    if chars.len() < RECOMMENDED_LENGTH {
+        feedback.push(
+            "Use at least 12 characters; four or more random words are long and easy to remember",
+        );
+    }
+    if predictable_count > 0 {
+        feedback.push("Avoid repeated characters and sequences such as 'aaa', 'abc' or 'qwerty'");
+    }
+    if classes == 1 && chars.len() < 2 * RECOMMENDED_LENGTH {
+        feedback.push("Mix in upper case letters, digits or symbols, or make it longer");
+    }
+
+    Strength {
+        entropy_bits: bits as u32,
+        feedback,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weak_passwords_score_low() {
+        for password in [
+            "",
+            "a",
+            "alice1",
+            "aaaaaaaaaaaa",
+            "abcdefghijkl",
+            "Password123!",
+            "qwerty2024",
+        ] {
+            let strength = estimate(password);
+            assert!(
+                strength.entropy_bits < DEFAULT_MIN_ENTROPY,
+                "{:?} scored {}",
+                password,
+                strength.entropy_bits
+            );
+            assert!(!strength.feedback.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_strong_passwords_score_high() {
+        for password in [
+            "correct horse battery staple",
+            "T7#kq!2vLm9$xR",
+            "ünïcödé-pässwörd",
+        ] {
+            let strength = estimate(password);
+            assert!(
+                strength.entropy_bits >= DEFAULT_MIN_ENTROPY,
+                "{:?} scored {}",
+                password,
+                strength.entropy_bits
+            );
+        }
+    }
+
+    #[test]
+    fn test_sequences_are_discounted() {
+        assert!(estimate("abcdefgh").entropy_bits < estimate("agdbfhce").entropy_bits);
+        assert!(estimate("asdfghjk").entropy_bits < estimate("agdbfhce").entropy_bits);
+        assert_eq!(
+            estimate("1111").entropy_bits,
+            estimate("11").entropy_bits + 2
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Templated secrets.
+//!
+//! A template is a secret value containing `{{NAME}}` placeholders, e.g.
+//! `postgres://{{DB_USER}}:{{DB_PASSWORD}}@{{DB_HOST}}/app`. The template is
+//! stored encrypted like any other value and rendered at read time by
+//! substituting the current values of the named secrets.
+
+use crate::crypto::SecretBytes;
+use crate::error::VaultError;
+use zeroize::Zeroizing;
+
+const OPEN: &[u8] = b"{{";
+const CLOSE: &[u8] = b"}}";
+
+/// Returns the names of the placeholders in a template, in order of
+/// appearance. Surrounding whitespace is trimmed (`{{ DB_USER }}`).
+///
+/// # Errors
+/// `VaultError::InvalidTemplate` if a placeholder is unterminated, empty or
+/// not valid UTF-8.
+///
+/// # Examples
+/// ```
+/// use vx_core::template::placeholders;
+///
+/// let names = placeholders(b"{{USER}}@{{ HOST }}").unwrap();
+/// assert_eq!(names, vec!["USER", "HOST"]);
+/// ```
+pub fn placeholders(template: &[u8]) -> Result<Vec<&str>, VaultError> {
+    let mut names = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = find(rest, OPEN) {
+        let after = &rest[start + OPEN.len()..];
+        let end = find(after, CLOSE).ok_or_else(|| invalid("unterminated '{{'"))?;
+
+        let name = std::str::from_utf8(&after[..end])
+            .map_err(|_| invalid("placeholder is not valid UTF-8"))?
+            .trim();
+        if name.is_empty() {
+            return Err(invalid("empty placeholder"));
+        }
+
+        names.push(name);
+        rest = &after[end + CLOSE.len()..];
+    }
+
+    Ok(names)
+}
+
+/// Renders a template, calling `lookup` for the value of each placeholder.
+///
+/// Errors returned by `lookup` (a missing or expired secret, say) are
+/// passed through.
+pub fn render<F>(template: &[u8], mut lookup: F) -> Result<SecretBytes, VaultError>
+where
+    F: FnMut(&str) -> Result<SecretBytes, VaultError>,
+{
+    let mut rendered = Zeroizing::new(Vec::with_capacity(template.len()));
+    let mut rest = template;
+
+    for name in placeholders(template)? {
+        // placeholders() has validated the structure, so both markers exist
+        let start = find(rest, OPEN).unwrap();
+        let after = &rest[start + OPEN.len()..];
+        let end = find(after, CLOSE).unwrap();
+
+        rendered.extend_from_slice(&rest[..start]);
+        rendered.extend_from_slice(&lookup(name)?);
+        rest = &after[end + CLOSE.len()..];
+    }
+    rendered.extend_from_slice(rest);
+
+    Ok(SecretBytes::new(std::mem::take(&mut *rendered)))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn invalid(reason: &str) -> VaultError {
+    VaultError::InvalidTemplate(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Result<SecretBytes, VaultError> {
+        match name {
+            "DB_USER" => Ok(SecretBytes::new(b"admin".to_vec())),
+            "DB_PASSWORD" => Ok(SecretBytes::new(b"s3cret".to_vec())),
+            _ => Err(VaultError::SecretNotFound(name.to_string())),
+        }
+    }
+
+    #[test]
+    fn test_render_substitutes_placeholders() {
+        let rendered = render(b"postgres://{{DB_USER}}:{{ DB_PASSWORD }}@db/app", lookup).unwrap();
+        assert_eq!(rendered.expose(), b"postgres://admin:s3cret@db/app");
+
+        let plain = render(b"no placeholders", lookup).unwrap();
+        assert_eq!(plain.expose(), b"no placeholders");
+    }
+
+    #[test]
+    fn test_render_passes_lookup_errors() {
+        assert!(matches!(
+            render(b"{{DB_HOST}}", lookup),
+            Err(VaultError::SecretNotFound(name)) if name == "DB_HOST"
+        ));
+    }
+
+    #[test]
+    fn test_malformed_templates() {
+        assert!(matches!(placeholders(b"{{DB_USER"), Err(VaultError::InvalidTemplate(_))));
+        assert!(matches!(placeholders(b"a{{ }}b"), Err(VaultError::InvalidTemplate(_))));
+        assert_eq!(placeholders(b"single { braces }").unwrap(), Vec::<&str>::new());
+    }
+}
@@ -0,0 +1,254 @@
+//! Transfer bundles for moving a vault to another machine.
+//!
+//! A bundle packs the vault file and its attachment blobs into a single
+//! file, encrypted under a one-time passphrase. Both are copied as stored,
+//! so the vault is still unlocked with its own credentials after import;
+//! the passphrase only protects the bundle while it is in transit.
+//!
+//! A readable metadata block describes the bundle (format versions, when it
+//! was made, how many attachments it holds), so that an importer can report
+//! what it is about to install before asking for the passphrase.
+//!
+//! # Format
+//! ```text
+//! MAGIC (8) || info length (u32 LE) || info (JSON) || nonce (12) || ciphertext
+//! ```
+//! The ciphertext is the CBOR-encoded contents under AES-256-GCM, with
+//! everything before the nonce authenticated as associated data. The key is
+//! derived from the passphrase with Argon2id, using the salt and parameters
+//! stored in the info.
+
+use crate::crypto::{self, EncryptedData, KdfParams, NONCE_SIZE};
+use crate::error::VaultError;
+use crate::keyslot;
+use crate::ttl;
+use crate::vault::{self, PayloadFormat};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// Magic bytes identifying a transfer bundle
+pub const MAGIC: &[u8; 8] = b"\0VXMOVE\0";
+
+/// Version of the bundle format written by `seal`
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Size of the info length field
+const INFO_LEN_SIZE: usize = 4;
+
+/// The readable description at the start of a bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleInfo {
+    /// Bundle format version
+    pub version: u32,
+    /// Format version of the bundled vault file
+    pub vault_version: u32,
+    /// Version of VaultX that made the bundle
+    pub created_with: String,
+    /// When the bundle was made
+    pub created_at: u64,
+    /// Number of attachment blobs in the bundle
+    pub attachments: usize,
+    /// Argon2id parameters deriving the bundle key from the passphrase
+    pub kdf: KdfParams,
+    #[serde(with = "crate::vault::base64_serde")]
+    pub salt: Vec<u8>,
+}
+
+/// What a bundle carries.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BundleContents {
+    /// The vault file, as stored on disk
+    #[serde(with = "crate::vault::base64_serde")]
+    pub vault: Vec<u8>,
+    /// Attachment blobs, as stored on disk
+    pub attachments: Vec<BundledBlob>,
+}
+
+/// An attachment blob in a bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundledBlob {
+    /// Blob id (its file name in the attachment directory)
+    pub id: String,
+    #[serde(with = "crate::vault::base64_serde")]
+    pub data: Vec<u8>,
+}
+
+/// Generates a one-time passphrase for a bundle, in the format of a
+/// recovery code.
+pub fn generate_passphrase() -> Zeroizing<String> {
+    keyslot::generate_recovery_code()
+}
+
+/// Encrypts `contents` into a bundle under `passphrase`.
+///
+/// # Errors
+/// `VaultError::CorruptedVault` if `contents.vault` is not a vault file.
+pub fn seal(contents: &BundleContents, passphrase: &[u8]) -> Result<Vec<u8>, VaultError> {
+    let info = BundleInfo {
+        version: BUNDLE_VERSION,
+        vault_version: vault::read_version(&contents.vault)?,
+        created_with: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: ttl::current_timestamp(),
+        attachments: contents.attachments.len(),
+        kdf: KdfParams::STANDARD,
+        salt: crypto::generate_salt().to_vec(),
+    };
+    let info_json =
+        serde_json::to_vec(&info).map_err(|e| VaultError::SerializationError(e.to_string()))?;
+    let info_len = u32::try_from(info_json.len())
+        .map_err(|_| VaultError::SerializationError("bundle info too large".to_string()))?;
+
+    let mut bundle = Vec::new();
+    bundle.extend_from_slice(MAGIC);
+    bundle.extend_from_slice(&info_len.to_le_bytes());
+    bundle.extend_from_slice(&info_json);
+
+    let mut plaintext = Zeroizing::new(Vec::new());
+    ciborium::ser::into_writer(contents, &mut *plaintext)
+        .map_err(|e| VaultError::SerializationError(e.to_string()))?;
+
+    let key = crypto::derive_key_with_params(passphrase, &info.salt, &info.kdf)?;
+    let encrypted = crypto::encrypt_with_aad(&plaintext, &key, &bundle)?;
+
+    bundle.extend_from_slice(&encrypted.nonce);
+    bundle.extend_from_slice(&encrypted.ciphertext);
+    Ok(bundle)
+}
+
+/// Reads the info of a bundle without decrypting it.
+///
+/// # Errors
+/// `VaultError::InvalidFormat` if `data` is not a transfer bundle.
+pub fn read_info(data: &[u8]) -> Result<BundleInfo, VaultError> {
+    let (info, _) = split_bundle(data)?;
+    Ok(info)
+}
+
+/// Decrypts a bundle made by `seal`.
+///
+/// # Errors
+/// - `VaultError::InvalidFormat` if `data` is not a transfer bundle, or
+///   holds a bundle or vault format this version cannot read
+/// - `VaultError::AuthenticationFailed` if the passphrase is wrong or the
+///   bundle was modified
+pub fn open(data: &[u8], passphrase: &[u8]) -> Result<BundleContents, VaultError> {
+    let (info, body_start) = split_bundle(data)?;
+    if info.version > BUNDLE_VERSION || PayloadFormat::for_version(info.vault_version).is_none() {
+        return Err(VaultError::InvalidFormat(format!(
+            "the bundle was made by VaultX {}; update vx to import it",
+            info.created_with
+        )));
+    }
+
+    let (aad, body) = data.split_at(body_start);
+    let nonce = body
+        .get(..NONCE_SIZE)
+        .and_then(|nonce| nonce.try_into().ok())
+        .ok_or(VaultError::CorruptedVault)?;
+    let encrypted = EncryptedData {
+        ciphertext: body[NONCE_SIZE..].to_vec(),
+        nonce,
+    };
+
+    let key = crypto::derive_key_with_params(passphrase, &info.salt, &info.kdf)?;
+    let plaintext = crypto::decrypt_with_aad(&encrypted, &key, aad)
+        .map_err(|_| VaultError::AuthenticationFailed)?;
+
+    let contents: BundleContents =
+        ciborium::de::from_reader(plaintext.expose()).map_err(|_| VaultError::CorruptedVault)?;
+
+    // Blob ids become file names on import
+    let valid_id = |id: &str| !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric());
+    if !contents.attachments.iter().all(|blob| valid_id(&blob.id)) {
+        return Err(VaultError::CorruptedVault);
+    }
+    Ok(contents)
+}
+
+/// Splits a bundle into its info and the offset of the nonce.
+fn split_bundle(data: &[u8]) -> Result<(BundleInfo, usize), VaultError> {
+    if !data.starts_with(MAGIC) {
+        return Err(VaultError::InvalidFormat(
+            "not a VaultX transfer bundle".to_string(),
+        ));
+    }
+
+    let info_start = MAGIC.len() + INFO_LEN_SIZE;
+    let info_len = data
+        .get(MAGIC.len()..info_start)
+        .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+        .ok_or(VaultError::CorruptedVault)?;
+    let info_end = info_start
+        .checked_add(info_len)
+        .ok_or(VaultError::CorruptedVault)?;
+    let info = data
+        .get(info_start..info_end)
+        .ok_or(VaultError::CorruptedVault)?;
+
+    let info = serde_json::from_slice(info).map_err(|_| VaultError::CorruptedVault)?;
+    Ok((info, info_end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents() -> BundleContents {
+        let (mut vault, key) = vault::create_vault(b"master").unwrap();
+        vault.init_project("app").unwrap();
+        vault
+            .add_secret("app", "KEY", b"value", &key, None)
+            .unwrap();
+
+        BundleContents {
+            vault: vault::save_vault_with_key(&vault, &key).unwrap(),
+            attachments: vec![BundledBlob {
+                id: "0123abcd".to_string(),
+                data: vec![7; 10_000],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_bundle_roundtrip() {
+        let contents = contents();
+        let bundle = seal(&contents, b"passphrase").unwrap();
+
+        let info = read_info(&bundle).unwrap();
+        assert_eq!(info.version, BUNDLE_VERSION);
+        assert_eq!(info.attachments, 1);
+
+        let opened = open(&bundle, b"passphrase").unwrap();
+        assert_eq!(opened.vault, contents.vault);
+        assert_eq!(opened.attachments[0].data, contents.attachments[0].data);
+
+        let (vault, _) = vault::unlock_vault(&opened.vault, b"master").unwrap();
+        assert_eq!(vault.project_names(), vec!["app"]);
+    }
+
+    #[test]
+    fn test_bundle_rejects_wrong_passphrase_and_tampering() {
+        let mut bundle = seal(&contents(), b"passphrase").unwrap();
+        assert!(matches!(
+            open(&bundle, b"wrong"),
+            Err(VaultError::AuthenticationFailed)
+        ));
+
+        // The info is authenticated with the contents
+        let at = bundle
+            .windows(b"\"attachments\":1".len())
+            .position(|w| w == b"\"attachments\":1")
+            .unwrap();
+        bundle[at + b"\"attachments\":".len()] = b'2';
+        assert_eq!(read_info(&bundle).unwrap().attachments, 2);
+        assert!(matches!(
+            open(&bundle, b"passphrase"),
+            Err(VaultError::AuthenticationFailed)
+        ));
+
+        assert!(matches!(
+            read_info(b"vault.vx"),
+            Err(VaultError::InvalidFormat(_))
+        ));
+    }
+}
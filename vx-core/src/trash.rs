@@ -0,0 +1,93 @@
+//! The trash: removed secrets, environments and projects, kept for a
+//! retention period so that a mistaken removal can be undone.
+//!
+//! Trash entries live in the encrypted vault payload. Secret values stay
+//! encrypted under the key of the project they came from, and a trashed
+//! project is kept sealed under its subkey, so nothing is decrypted to move
+//! an item to the trash or back. See `Vault::trash_secret`,
+//! `Vault::restore_from_trash` and `Vault::purge_trash`.
+
+use crate::vault::{SealedProject, Secret, SecretRef};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Seconds a trashed item is kept by default (30 days)
+pub const DEFAULT_RETENTION: u64 = 30 * 86400;
+
+/// Something removed from the vault, waiting in the trash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// Identifier used to restore the item, unique within the trash
+    pub id: u32,
+    /// When the item was removed
+    pub deleted_at: u64,
+    pub item: TrashedItem,
+}
+
+/// What a trash entry holds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashedItem {
+    /// A single secret (or alias)
+    Secret {
+        location: SecretRef,
+        secret: Box<Secret>,
+    },
+    /// A named environment with all its secrets
+    Environment {
+        project: String,
+        env: String,
+        secrets: HashMap<String, Secret>,
+    },
+    /// A whole project, sealed under its subkey
+    Project {
+        name: String,
+        sealed: SealedProject,
+        /// Attachment blobs to delete when the project is purged
+        blob_ids: Vec<String>,
+    },
+}
+
+impl TrashedItem {
+    /// Returns the project the item belongs to (or is).
+    pub fn project(&self) -> &str {
+        match self {
+            TrashedItem::Secret { location, .. } => &location.project,
+            TrashedItem::Environment { project, .. } => project,
+            TrashedItem::Project { name, .. } => name,
+        }
+    }
+
+    /// Returns the attachment blobs that are no longer needed once the item
+    /// is purged.
+    pub fn blob_ids(&self) -> &[String] {
+        match self {
+            TrashedItem::Project { blob_ids, .. } => blob_ids,
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for TrashedItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrashedItem::Secret { location, secret } if secret.alias.is_some() => {
+                write!(f, "alias {}", location)
+            }
+            TrashedItem::Secret { location, .. } => write!(f, "secret {}", location),
+            TrashedItem::Environment {
+                project,
+                env,
+                secrets,
+            } => write!(
+                f,
+                "environment {}/{} ({} secrets)",
+                project,
+                env,
+                secrets.len()
+            ),
+            TrashedItem::Project { name, .. } => write!(f, "project {}", name),
+        }
+    }
+}
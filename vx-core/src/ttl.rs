@@ -103,10 +103,170 @@ pub fn current_timestamp() -> u64 {
         .as_secs()
 }
 
+/// Formats a Unix timestamp as a UTC date and time (`YYYY-MM-DD HH:MM:SS`).
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = timestamp / SECONDS_PER_DAY;
+    let secs = timestamp % SECONDS_PER_DAY;
+
+    // Civil-from-days conversion (proleptic Gregorian calendar)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / SECONDS_PER_HOUR,
+        (secs % SECONDS_PER_HOUR) / SECONDS_PER_MINUTE,
+        secs % SECONDS_PER_MINUTE
+    )
+}
+
+/// Converts a UTC date and time to a Unix timestamp, the inverse of
+/// `format_timestamp`. Returns `None` for out-of-range fields or a time
+/// before the epoch.
+pub fn timestamp_from_utc(
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+) -> Option<u64> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    // Days-from-civil conversion (proleptic Gregorian calendar)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = i64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let seconds = days * SECONDS_PER_DAY as i64
+        + i64::from(hour) * SECONDS_PER_HOUR as i64
+        + i64::from(minute) * SECONDS_PER_MINUTE as i64
+        + i64::from(second);
+    u64::try_from(seconds).ok()
+}
+
+/// Formats how long ago `timestamp` was relative to `now` (e.g. `5m ago`).
+pub fn format_relative(timestamp: u64, now: u64) -> String {
+    let elapsed = now.saturating_sub(timestamp);
+
+    if elapsed < SECONDS_PER_MINUTE {
+        "just now".to_string()
+    } else if elapsed < SECONDS_PER_HOUR {
+        format!("{}m ago", elapsed / SECONDS_PER_MINUTE)
+    } else if elapsed < SECONDS_PER_DAY {
+        format!("{}h ago", elapsed / SECONDS_PER_HOUR)
+    } else if elapsed < SECONDS_PER_WEEK {
+        format!("{}d ago", elapsed / SECONDS_PER_DAY)
+    } else {
+        format!("{}w ago", elapsed / SECONDS_PER_WEEK)
+    }
+}
+
+/// Formats how long until `timestamp` relative to `now` (e.g. `in 5m`).
+pub fn format_until(timestamp: u64, now: u64) -> String {
+    let remaining = timestamp.saturating_sub(now);
+
+    if remaining < SECONDS_PER_MINUTE {
+        "now".to_string()
+    } else if remaining < SECONDS_PER_HOUR {
+        format!("in {}m", remaining / SECONDS_PER_MINUTE)
+    } else if remaining < SECONDS_PER_DAY {
+        format!("in {}h", remaining / SECONDS_PER_HOUR)
+    } else if remaining < SECONDS_PER_WEEK {
+        format!("in {}d", remaining / SECONDS_PER_DAY)
+    } else {
+        format!("in {}w", remaining / SECONDS_PER_WEEK)
+    }
+}
+
+/// Formats a duration in seconds in the largest unit that divides it
+/// evenly, the inverse of `parse_ttl` (e.g. `7d`, `90m`). Durations that are
+/// not whole minutes are shown in seconds.
+pub fn format_duration(seconds: u64) -> String {
+    [
+        (SECONDS_PER_WEEK, "w"),
+        (SECONDS_PER_DAY, "d"),
+        (SECONDS_PER_HOUR, "h"),
+        (SECONDS_PER_MINUTE, "m"),
+    ]
+    .iter()
+    .find(|(unit, _)| seconds >= *unit && seconds.is_multiple_of(*unit))
+    .map_or_else(
+        || format!("{}s", seconds),
+        |(unit, suffix)| format!("{}{}", seconds / unit, suffix),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_format_until() {
+        let now = 10_000_000;
+        assert_eq!(format_until(now + 10, now), "now");
+        assert_eq!(format_until(now + 300, now), "in 5m");
+        assert_eq!(format_until(now + 2 * 86400, now), "in 2d");
+        assert_eq!(format_until(now - 60, now), "now");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(parse_ttl("2w").unwrap()), "2w");
+        assert_eq!(format_duration(parse_ttl("10d").unwrap()), "10d");
+        assert_eq!(format_duration(parse_ttl("90m").unwrap()), "90m");
+        assert_eq!(format_duration(45), "45s");
+    }
+
+    #[test]
+    fn test_format_relative() {
+        let now = 10_000_000;
+        assert_eq!(format_relative(now - 10, now), "just now");
+        assert_eq!(format_relative(now - 300, now), "5m ago");
+        assert_eq!(format_relative(now - 7200, now), "2h ago");
+        assert_eq!(format_relative(now - 86400, now), "1d ago");
+        assert_eq!(format_relative(now - 3 * 604800, now), "3w ago");
+        assert_eq!(format_relative(now + 60, now), "just now");
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(format_timestamp(1_702_500_000), "2023-12-13 20:40:00");
+    }
+
+    #[test]
+    fn test_timestamp_from_utc() {
+        assert_eq!(timestamp_from_utc(1970, 1, 1, 0, 0, 0), Some(0));
+        assert_eq!(timestamp_from_utc(2000, 2, 29, 0, 0, 0), Some(951_782_400));
+        assert_eq!(
+            timestamp_from_utc(2023, 12, 13, 20, 40, 0),
+            Some(1_702_500_000)
+        );
+        assert_eq!(timestamp_from_utc(1969, 12, 31, 23, 59, 59), None);
+        assert_eq!(timestamp_from_utc(2024, 13, 1, 0, 0, 0), None);
+    }
+
     #[test]
     fn test_parse_minutes() {
         assert_eq!(parse_ttl("30m").unwrap(), 1800);
@@ -0,0 +1,51 @@
+//! Undoing the most recent add, edit or removal.
+//!
+//! The vault keeps a single `UndoRecord`, replaced by every tracked change,
+//! with what is needed to revert it: the secret as it was before a write,
+//! or the trash entry a removal created. A record is only applied while the
+//! change is still the last thing to have happened to the secret, so an
+//! old record cannot overwrite later edits. See `Vault::track_write` and
+//! `Vault::undo`.
+
+use crate::access_log::Operation;
+use crate::vault::{Secret, SecretRef};
+use serde::{Deserialize, Serialize};
+
+/// The last tracked change to the vault.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoRecord {
+    /// `Add`, `Edit` or `Remove`
+    pub operation: Operation,
+    /// When the change was made
+    pub timestamp: u64,
+    pub change: Change,
+}
+
+/// What a tracked change did.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    /// A secret was written (added, overwritten or edited)
+    Write {
+        location: SecretRef,
+        /// The secret it replaced, if any
+        before: Option<Box<Secret>>,
+        /// The secret as written, to detect later changes
+        after: Box<Secret>,
+    },
+    /// An item was moved to the trash under this id
+    Trash { id: u32 },
+}
+
+/// Returns whether two secrets hold the same stored value and metadata.
+/// Every encryption uses a fresh nonce, so a rewritten value never matches.
+pub(crate) fn same_secret(a: &Secret, b: &Secret) -> bool {
+    a.encrypted_value == b.encrypted_value
+        && a.nonce == b.nonce
+        && a.created_at == b.created_at
+        && a.modified_at == b.modified_at
+        && a.expires_at == b.expires_at
+        && a.alias == b.alias
+        && a.template == b.template
+        && a.kind == b.kind
+}
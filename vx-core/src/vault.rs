@@ -2,22 +2,106 @@
 //!
 //! The vault stores secrets organized by project, plus SSH identities.
 //! All data is encrypted using AES-256-GCM before persistence.
+//!
+//! The payload is encrypted under a random data key, which is wrapped by one
+//! or more key slots (see `keyslot`). Each project is additionally sealed
+//! under its own HKDF subkey of the data key, so a vault can be opened
+//! without decoding every project and individual projects unsealed on demand.
 
-use crate::crypto::{self, EncryptedData, KEY_SIZE, NONCE_SIZE, SALT_SIZE};
+use crate::access_log::{self, ChainStatus, LogEntry, Operation};
+use crate::certificate::{self, CertificateInfo};
+use crate::crypto::{self, EncryptedData, KdfParams, SecretBytes, KEY_SIZE, NONCE_SIZE, SALT_SIZE};
+use crate::database::DbConnection;
+use crate::emergency::EmergencyGrant;
 use crate::error::VaultError;
+use crate::generate::GenerationPolicy;
+use crate::keyslot::{self, KeySlot, SlotKind, MAX_KEY_SLOTS};
+use crate::memory::SecureKey;
+use crate::pattern;
+use crate::rules::SecretRules;
+use crate::stream;
+use crate::structured::JsonDocument;
+use crate::template;
+use crate::trash::{TrashEntry, TrashedItem};
+use crate::undo::{self, Change, UndoRecord};
 use crate::ttl;
+use crate::wireguard;
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
+use zeroize::Zeroizing;
 
 /// Magic bytes identifying a VaultX file
 const VAULT_MAGIC: &[u8; 4] = b"VX01";
 
-/// Current vault format version
-const VAULT_VERSION: u32 = 1;
+/// Current vault format version (key slots, authenticated header, payload
+/// checksum); files are always written in it
+pub const VAULT_VERSION: u32 = 5;
+
+/// Vault format version with key slots and no payload checksum
+const VAULT_VERSION_SLOTS: u32 = 4;
+
+/// Vault format version with a single password salt and sealed projects
+const VAULT_VERSION_SEALED: u32 = 3;
+
+/// Vault format version with a CBOR payload and unsealed projects
+const VAULT_VERSION_CBOR: u32 = 2;
 
-/// Header size in bytes (magic + version + reserved)
+/// Legacy vault format version (JSON payload)
+const VAULT_VERSION_JSON: u32 = 1;
+
+/// Header size in bytes (magic + version + generation + flags)
 const HEADER_SIZE: usize = 16;
 
+/// Offset of the key slot table, after its u32 length (format 4+)
+const SLOT_TABLE_OFFSET: usize = HEADER_SIZE + 4;
+
+/// Size of the payload length and SHA-256 checksum after the slot table
+/// (format 5+)
+const CHECKSUM_BLOCK_SIZE: usize = 8 + 32;
+
+/// Label given to the password slot of a new or migrated vault
+const DEFAULT_SLOT_LABEL: &str = "default";
+
+/// Known plaintext encrypted under a locked project's key to check its
+/// passphrase
+const LOCK_VERIFIER: &[u8] = b"vaultx project lock";
+
+/// Values at least this large are stored once per project however many
+/// secrets hold them; see `Project::share_values`
+const SHARED_VALUE_MIN_SIZE: usize = 256;
+
+/// HKDF label of the key value hashes are computed with
+const VALUE_HASH_CONTEXT: &[u8] = b"vaultx value hash";
+
+/// Maximum number of aliases followed when resolving a secret
+const MAX_ALIAS_DEPTH: usize = 8;
+
+/// Maximum nesting of templates referring to other templates
+const MAX_TEMPLATE_DEPTH: usize = 8;
+
+/// Header flag: the vault is a read-only export and must not be saved
+pub const FLAG_READ_ONLY: u32 = 1;
+
+/// Header flag: the payload is zstd-compressed before encryption; see
+/// `Vault::compressed`
+pub const FLAG_COMPRESSED: u32 = 2;
+
+/// Header flag: projects are stored in shard files outside the vault file;
+/// see `save_vault_sharded`
+pub const FLAG_SHARDED: u32 = 4;
+
+/// File extension of project shards
+pub const SHARD_EXTENSION: &str = "vxp";
+
+/// A project shard written by `save_vault_sharded`: its id and contents
+pub type Shard = (String, Vec<u8>);
+
 /// A secret stored in the vault.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Secret {
@@ -26,16 +110,425 @@ pub struct Secret {
     pub encrypted_value: Vec<u8>,
     #[serde(with = "nonce_serde")]
     pub nonce: [u8; NONCE_SIZE],
+    /// When the value was written; every write resets it
     pub created_at: u64,
+    /// When the secret was last changed without its value being written,
+    /// e.g. given a new TTL; see `last_modified`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<u64>,
     pub expires_at: Option<u64>,
+    /// Set if this secret is an alias of another secret; the alias itself
+    /// holds no value. See `Vault::add_alias`
+    #[serde(default)]
+    pub alias: Option<SecretRef>,
+    /// Set if the value is a template rendered at read time; see
+    /// `Vault::add_template_in`
+    #[serde(default)]
+    pub template: bool,
+    /// What the value holds; see `SecretKind`
+    #[serde(default)]
+    pub kind: SecretKind,
+    /// When the secret was last read successfully; persisted with the next
+    /// save
+    #[serde(default)]
+    pub last_accessed: AccessTime,
+    /// Where the secret was last added or edited; unset for secrets written
+    /// before this was recorded. See `Vault::set_provenance`
+    #[serde(default)]
+    pub provenance: Option<Provenance>,
+    /// Keyed hash of the value, set for values of `SHARED_VALUE_MIN_SIZE`
+    /// bytes or more so that secrets holding the same value can share its
+    /// ciphertext
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_hash: Option<String>,
+}
+
+impl Secret {
+    /// Returns the size of the value in bytes, read from its ciphertext
+    /// without decrypting it, or `None` for an alias, which holds no value.
+    pub fn value_size(&self) -> Option<usize> {
+        if self.alias.is_some() {
+            return None;
+        }
+        Some(self.encrypted_value.len().saturating_sub(crypto::TAG_SIZE))
+    }
+
+    /// Returns when the secret was last written or changed.
+    pub fn last_modified(&self) -> u64 {
+        self.modified_at.map_or(self.created_at, |modified| modified.max(self.created_at))
+    }
+}
+
+/// Where a secret was written from, so entries in a shared vault can be
+/// traced back to a machine and user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Host name of the machine, if it could be read
+    pub hostname: Option<String>,
+    /// Operating system user, if it could be read
+    pub user: Option<String>,
+    /// Version of the program that wrote the secret
+    pub version: String,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let user = self.user.as_deref().unwrap_or("unknown user");
+        let hostname = self.hostname.as_deref().unwrap_or("unknown host");
+        write!(f, "{} on {} (vx {})", user, hostname, self.version)
+    }
+}
+
+/// The type of a secret's value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretKind {
+    /// Arbitrary bytes, usually text
+    #[default]
+    Text,
+    /// A JSON document whose fields can be read with `structured`; see
+    /// `Vault::add_json_in`
+    Json,
+    /// An X.509 certificate that expires with the certificate; see
+    /// `Vault::add_certificate_in`
+    Certificate,
+    /// Database credentials for `vx db connect`; see
+    /// `Vault::add_database_in`
+    Database,
+}
+
+/// When a secret was last read (Unix seconds), or never.
+///
+/// Reads only borrow the vault, so the time is kept in an atomic that can be
+/// updated through a shared reference. It is stored as an optional integer.
+#[derive(Debug, Default)]
+pub struct AccessTime(AtomicU64);
+
+impl AccessTime {
+    /// Returns the time of the last read, if there has been one.
+    pub fn get(&self) -> Option<u64> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => None,
+            timestamp => Some(timestamp),
+        }
+    }
+
+    /// Records a read at `timestamp`.
+    pub fn set(&self, timestamp: u64) {
+        self.0.store(timestamp, Ordering::Relaxed);
+    }
+}
+
+impl Clone for AccessTime {
+    fn clone(&self) -> Self {
+        Self(AtomicU64::new(self.0.load(Ordering::Relaxed)))
+    }
+}
+
+impl Serialize for AccessTime {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AccessTime {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self(AtomicU64::new(
+            Option::<u64>::deserialize(deserializer)?.unwrap_or(0),
+        )))
+    }
+}
+
+/// The location of a secret: project, environment (`None` for the default
+/// environment) and key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretRef {
+    pub project: String,
+    #[serde(default)]
+    pub env: Option<String>,
+    pub key: String,
+}
+
+impl SecretRef {
+    pub fn new(project: &str, env: Option<&str>, key: &str) -> Self {
+        Self {
+            project: project.to_string(),
+            env: env.map(str::to_string),
+            key: key.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.env {
+            Some(env) => write!(f, "{}/{}/{}", self.project, env, self.key),
+            None => write!(f, "{}/{}", self.project, self.key),
+        }
+    }
+}
+
+/// A file attached to a project.
+///
+/// Only metadata lives in the vault; the encrypted contents are stored as a
+/// separate blob identified by `blob_id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    pub blob_id: String,
+    pub size: u64,
+    /// Nonce of a single-shot blob; unused (zero) for chunked blobs, which
+    /// carry their own nonce prefix
+    #[serde(with = "nonce_serde")]
+    pub nonce: [u8; NONCE_SIZE],
+    /// Whether the blob uses the chunked `stream` format. Attachments added
+    /// before that format existed are single AES-GCM ciphertexts.
+    #[serde(default)]
+    pub chunked: bool,
+    /// Whether the blob authenticates its project and attachment name, so
+    /// it cannot be passed off as another attachment; see `attachment_aad`.
+    /// Attachments added before blobs were bound are not.
+    #[serde(default)]
+    pub bound: bool,
+    pub created_at: u64,
 }
 
 /// A project containing secrets.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Project {
     pub name: String,
+    /// Secrets of the default environment
     pub secrets: HashMap<String, Secret>,
+    /// Secrets of named environments (e.g. "staging", "prod"); the same key
+    /// may hold a different value in each
+    #[serde(default)]
+    pub environments: HashMap<String, HashMap<String, Secret>>,
     pub created_at: u64,
+    #[serde(default)]
+    pub attachments: HashMap<String, Attachment>,
+    /// Set if the project requires its own passphrase; see
+    /// `Vault::set_project_passphrase`
+    #[serde(default)]
+    pub lock: Option<ProjectLock>,
+    /// What the project requires of its secrets; see `Vault::set_rules`
+    #[serde(default, skip_serializing_if = "SecretRules::is_empty")]
+    pub rules: SecretRules,
+    /// How `vx generate` creates the project's secrets; see
+    /// `Vault::set_generation_policy`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<GenerationPolicy>,
+    /// Ciphertexts held by several secrets, keyed by value hash; filled
+    /// only while the project is sealed. See `share_values`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    shared_values: HashMap<String, SharedValue>,
+}
+
+/// A ciphertext stored once for every secret with its value hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SharedValue {
+    #[serde(with = "base64_serde")]
+    ciphertext: Vec<u8>,
+}
+
+impl Project {
+    /// Returns the secrets of an environment, or of the default environment
+    /// if `env` is `None`.
+    pub fn secrets_in(&self, env: Option<&str>) -> Result<&HashMap<String, Secret>, VaultError> {
+        match env {
+            None => Ok(&self.secrets),
+            Some(env) => self
+                .environments
+                .get(env)
+                .ok_or_else(|| VaultError::EnvironmentNotFound(env.to_string())),
+        }
+    }
+
+    fn secrets_in_mut(
+        &mut self,
+        env: Option<&str>,
+    ) -> Result<&mut HashMap<String, Secret>, VaultError> {
+        match env {
+            None => Ok(&mut self.secrets),
+            Some(env) => self
+                .environments
+                .get_mut(env)
+                .ok_or_else(|| VaultError::EnvironmentNotFound(env.to_string())),
+        }
+    }
+
+    /// Returns the names of the project's named environments, sorted.
+    pub fn environment_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.environments.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Iterates over every environment's secrets, starting with the default
+    /// environment (`None`).
+    pub fn secrets_by_environment(
+        &self,
+    ) -> impl Iterator<Item = (Option<&str>, &HashMap<String, Secret>)> {
+        std::iter::once((None, &self.secrets)).chain(
+            self.environments
+                .iter()
+                .map(|(env, secrets)| (Some(env.as_str()), secrets)),
+        )
+    }
+
+    /// Iterates over the secrets of every environment.
+    pub fn all_secrets(&self) -> impl Iterator<Item = &Secret> {
+        self.secrets
+            .values()
+            .chain(self.environments.values().flat_map(|secrets| secrets.values()))
+    }
+
+    fn all_secrets_mut(&mut self) -> impl Iterator<Item = &mut Secret> {
+        self.secrets.values_mut().chain(
+            self.environments
+                .values_mut()
+                .flat_map(|secrets| secrets.values_mut()),
+        )
+    }
+
+    /// Moves ciphertexts held by more than one secret into the shared
+    /// value table, leaving those secrets' `encrypted_value` empty, so the
+    /// sealed project stores each of them once.
+    ///
+    /// Secrets are only deduplicated when their ciphertexts and nonces are
+    /// identical, as `Vault::add_secret_in` makes them for equal values.
+    fn share_values(&mut self) {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for secret in self.all_secrets().filter(|s| s.alias.is_none()) {
+            if let Some(hash) = &secret.value_hash {
+                *counts.entry(hash.clone()).or_default() += 1;
+            }
+        }
+
+        let mut shared: HashMap<String, ([u8; NONCE_SIZE], Vec<u8>)> = HashMap::new();
+        for secret in self.all_secrets_mut().filter(|s| s.alias.is_none()) {
+            let Some(hash) = &secret.value_hash else {
+                continue;
+            };
+            if counts[hash] < 2 {
+                continue;
+            }
+            match shared.get(hash) {
+                None => {
+                    let ciphertext = std::mem::take(&mut secret.encrypted_value);
+                    shared.insert(hash.clone(), (secret.nonce, ciphertext));
+                }
+                Some((nonce, ciphertext))
+                    if *nonce == secret.nonce && *ciphertext == secret.encrypted_value =>
+                {
+                    secret.encrypted_value.clear();
+                }
+                Some(_) => {}
+            }
+        }
+
+        self.shared_values = shared
+            .into_iter()
+            .map(|(hash, (_, ciphertext))| (hash, SharedValue { ciphertext }))
+            .collect();
+    }
+
+    /// Reverses `share_values` after the project is unsealed.
+    fn unshare_values(&mut self) -> Result<(), VaultError> {
+        let shared = std::mem::take(&mut self.shared_values);
+        if shared.is_empty() {
+            return Ok(());
+        }
+        for secret in self.all_secrets_mut().filter(|s| s.alias.is_none()) {
+            if !secret.encrypted_value.is_empty() {
+                continue;
+            }
+            let value = secret
+                .value_hash
+                .as_ref()
+                .and_then(|hash| shared.get(hash))
+                .ok_or(VaultError::CorruptedVault)?;
+            secret.encrypted_value = value.ciphertext.clone();
+        }
+        Ok(())
+    }
+
+    /// Returns the ciphertext and nonce of a secret holding the value with
+    /// `value_hash`, if there is one.
+    fn find_value(&self, value_hash: &str) -> Option<(Vec<u8>, [u8; NONCE_SIZE])> {
+        self.all_secrets()
+            .find(|s| s.alias.is_none() && s.value_hash.as_deref() == Some(value_hash))
+            .map(|s| (s.encrypted_value.clone(), s.nonce))
+    }
+}
+
+/// A project passphrase layered on top of the master key.
+///
+/// Secret values in the project are encrypted under a key combining the
+/// master key with the Argon2id hash of the passphrase.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectLock {
+    #[serde(with = "base64_serde")]
+    pub salt: Vec<u8>,
+    /// `LOCK_VERIFIER` encrypted under the project key
+    #[serde(with = "base64_serde")]
+    pub verifier: Vec<u8>,
+    #[serde(with = "nonce_serde")]
+    pub nonce: [u8; NONCE_SIZE],
+}
+
+/// A project encrypted under its own subkey that has not been unsealed yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedProject {
+    #[serde(with = "base64_serde")]
+    pub ciphertext: Vec<u8>,
+    #[serde(with = "nonce_serde")]
+    pub nonce: [u8; NONCE_SIZE],
+}
+
+impl SealedProject {
+    /// Returns the project as a shard file: nonce followed by ciphertext.
+    fn to_shard(&self) -> Vec<u8> {
+        [self.nonce.as_slice(), &self.ciphertext].concat()
+    }
+
+    /// Reverses `to_shard`.
+    fn from_shard(data: &[u8]) -> Result<Self, VaultError> {
+        if data.len() < NONCE_SIZE {
+            return Err(VaultError::CorruptedVault);
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_SIZE);
+        Ok(Self {
+            ciphertext: ciphertext.to_vec(),
+            nonce: nonce.try_into().unwrap(),
+        })
+    }
+}
+
+/// A project stored in a shard file, as recorded in the vault payload.
+///
+/// The digest authenticates the shard: the payload is, so a shard cannot be
+/// swapped for another project's or an older copy of its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ShardRef {
+    /// SHA-256 of the shard file
+    #[serde(with = "base64_serde")]
+    digest: Vec<u8>,
+}
+
+impl ShardRef {
+    fn for_shard(data: &[u8]) -> Self {
+        Self {
+            digest: Sha256::digest(data).to_vec(),
+        }
+    }
+
+    /// Returns the shard's id, its file name without extension. Shards are
+    /// named after their contents, so a changed project is written to a new
+    /// file and the old one stays valid until the vault file no longer
+    /// refers to it.
+    fn id(&self) -> String {
+        self.digest[..16].iter().map(|b| format!("{:02x}", b)).collect()
+    }
 }
 
 /// An SSH identity stored in the vault.
@@ -43,11 +536,26 @@ pub struct Project {
 pub struct SshIdentity {
     pub name: String,
     pub public_key: String,
+    /// The 32-byte ed25519 seed, or for security-key identities (see
+    /// `ssh::is_security_key`) the OpenSSH key handle file
     #[serde(with = "base64_serde")]
     pub encrypted_private_key: Vec<u8>,
     #[serde(with = "nonce_serde")]
     pub nonce: [u8; NONCE_SIZE],
     pub created_at: u64,
+    /// Set for identities whose key stays on a PKCS#11 token, which have no
+    /// private key in the vault; see `add_pkcs11_identity`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pkcs11: Option<Pkcs11Key>,
+}
+
+/// A key on a PKCS#11 token (smartcard or HSM) used as an SSH identity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pkcs11Key {
+    /// Path of the PKCS#11 module ssh loads (`PKCS11Provider`)
+    pub module: String,
+    /// Label of the key on the token
+    pub label: String,
 }
 
 /// An SSH server configuration stored in the vault.
@@ -58,26 +566,186 @@ pub struct SshServerConfig {
     pub ip_address: String,
     pub identity_name: String,
     pub created_at: u64,
+    /// Seconds a shared connection to the server stays open once idle, or
+    /// `None` if every connection authenticates on its own; see
+    /// `set_ssh_server_multiplex`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multiplex: Option<u64>,
+    /// Host keys the user accepted for the server, as `type base64`; empty
+    /// until the first connection; see `set_ssh_server_host_keys`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub host_keys: Vec<String>,
+}
+
+/// A kubeconfig file stored in the vault for `vx kubectl`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Kubeconfig {
+    pub name: String,
+    #[serde(with = "base64_serde")]
+    pub encrypted_config: Vec<u8>,
+    #[serde(with = "nonce_serde")]
+    pub nonce: [u8; NONCE_SIZE],
+    pub created_at: u64,
+}
+
+/// A WireGuard config stored in the vault for `vx wg`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireguardConfig {
+    /// Interface name; see `wireguard::validate_interface_name`
+    pub name: String,
+    #[serde(with = "base64_serde")]
+    pub encrypted_config: Vec<u8>,
+    #[serde(with = "nonce_serde")]
+    pub nonce: [u8; NONCE_SIZE],
+    pub created_at: u64,
 }
 
 /// The main vault structure.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vault {
+    /// Format version of the file the vault was read from; see
+    /// `needs_upgrade`
     pub version: u32,
+    /// Incremented on every save; used to detect concurrent writers
+    #[serde(default)]
+    pub generation: u32,
+    /// Set on exports made by `export_read_only`; such vaults cannot be saved
+    #[serde(default)]
+    pub read_only: bool,
+    /// Whether the payload is compressed with zstd before it is encrypted.
+    /// Recorded in the header (`FLAG_COMPRESSED`), not the payload; read
+    /// from the file and kept on save unless changed
+    #[serde(skip)]
+    pub compressed: bool,
+    /// Whether projects are kept in shard files next to the vault file
+    /// rather than in its payload. Recorded in the header (`FLAG_SHARDED`);
+    /// read from the file and kept on save unless changed. See
+    /// `save_vault_sharded`
+    #[serde(skip)]
+    pub sharded: bool,
+    /// Projects that have been unsealed (or were created in this session)
     pub projects: HashMap<String, Project>,
+    /// Projects still encrypted under their subkey; see `unseal_project`
+    #[serde(default)]
+    pub sealed_projects: HashMap<String, SealedProject>,
+    /// Shards the payload refers to that have not been read yet, by project
+    /// name; see `attach_shards`
+    #[serde(default)]
+    shards: HashMap<String, ShardRef>,
+    /// The sealed form each project unsealed in this session was read
+    /// from, with the digest of its plaintext and subkey (see
+    /// `sealing_digest`), so that a project unchanged and saved under the
+    /// same key is written back as it was; never persisted
+    #[serde(skip)]
+    unsealed_from: HashMap<String, (SealedProject, [u8; 32])>,
     pub ssh_identities: HashMap<String, SshIdentity>,
     #[serde(default)]
     pub ssh_servers: HashMap<String, SshServerConfig>,
+    /// Named lists of servers, keyed by group name; see
+    /// `create_ssh_server_group`
+    #[serde(default)]
+    pub ssh_server_groups: HashMap<String, Vec<String>>,
+    /// Kubeconfig files, keyed by name; see `add_kubeconfig`
+    #[serde(default)]
+    pub kubeconfigs: HashMap<String, Kubeconfig>,
+    /// WireGuard configs, keyed by interface name; see
+    /// `add_wireguard_config`
+    #[serde(default)]
+    pub wireguard_configs: HashMap<String, WireguardConfig>,
+    /// Secrets each browser origin may request through the native messaging
+    /// host, keyed by normalized origin; see `allow_origin`
+    #[serde(default)]
+    pub origin_grants: HashMap<String, Vec<SecretRef>>,
+    /// Append-only; see `record` and `access_log`
+    #[serde(default)]
+    access_log: Vec<LogEntry>,
+    /// Removed items that can still be restored; see `trash_secret`
+    #[serde(default)]
+    trash: Vec<TrashEntry>,
+    /// The last tracked change; see `track_write` and `undo`
+    #[serde(default)]
+    undo: Option<UndoRecord>,
+    /// Credentials wrapping the data key; stored in the file header
+    #[serde(default)]
+    key_slots: Vec<KeySlot>,
+    /// Data key wrapped for emergency access; stored in the file header
+    #[serde(default)]
+    emergency_grants: Vec<EmergencyGrant>,
+    /// Passphrase keys of locked projects unlocked in this session; never
+    /// persisted
+    #[serde(skip)]
+    passphrase_keys: HashMap<String, SecureKey>,
+    /// Access log entries already in the vault file; see `unsaved_log`
+    #[serde(skip)]
+    saved_log_len: usize,
+    /// Recorded on the secrets added or edited in this session; see
+    /// `set_provenance`
+    #[serde(skip)]
+    provenance: Option<Provenance>,
+}
+
+/// Encoding of the decrypted vault payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+    /// JSON with base64-encoded binary fields (format version 1)
+    Json,
+    /// CBOR with raw byte strings (format versions 2 to 5)
+    Cbor,
+}
+
+impl PayloadFormat {
+    /// Returns the payload format used by a given header version.
+    pub fn for_version(version: u32) -> Option<Self> {
+        match version {
+            VAULT_VERSION_JSON => Some(Self::Json),
+            VAULT_VERSION_CBOR..=VAULT_VERSION => Some(Self::Cbor),
+            _ => None,
+        }
+    }
 }
 
-/// Internal vault data (JSON serialized before encryption)
+/// Internal vault data (serialized before encryption)
 #[derive(Debug, Serialize, Deserialize)]
 struct VaultData {
     version: u32,
+    #[serde(default)]
+    generation: u32,
+    #[serde(default)]
+    read_only: bool,
+    #[serde(default)]
     projects: HashMap<String, Project>,
+    #[serde(default)]
+    sealed_projects: HashMap<String, SealedProject>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    shards: HashMap<String, ShardRef>,
     ssh_identities: HashMap<String, SshIdentity>,
     #[serde(default)]
     ssh_servers: HashMap<String, SshServerConfig>,
+    #[serde(default)]
+    ssh_server_groups: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    kubeconfigs: HashMap<String, Kubeconfig>,
+    #[serde(default)]
+    wireguard_configs: HashMap<String, WireguardConfig>,
+    #[serde(default)]
+    origin_grants: HashMap<String, Vec<SecretRef>>,
+    #[serde(default)]
+    access_log: Vec<LogEntry>,
+    #[serde(default)]
+    trash: Vec<TrashEntry>,
+    #[serde(default)]
+    undo: Option<UndoRecord>,
+}
+
+/// Unencrypted part of the header that follows the fixed fields (format 4+).
+///
+/// It is authenticated as associated data of the payload encryption.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct VaultHeader {
+    #[serde(default)]
+    key_slots: Vec<KeySlot>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    emergency_grants: Vec<EmergencyGrant>,
 }
 
 impl Vault {
@@ -85,15 +753,34 @@ impl Vault {
     pub fn new() -> Self {
         Self {
             version: VAULT_VERSION,
+            generation: 0,
+            read_only: false,
+            compressed: false,
+            sharded: false,
             projects: HashMap::new(),
+            sealed_projects: HashMap::new(),
+            shards: HashMap::new(),
+            unsealed_from: HashMap::new(),
             ssh_identities: HashMap::new(),
             ssh_servers: HashMap::new(),
+            ssh_server_groups: HashMap::new(),
+            kubeconfigs: HashMap::new(),
+            wireguard_configs: HashMap::new(),
+            origin_grants: HashMap::new(),
+            access_log: Vec::new(),
+            trash: Vec::new(),
+            undo: None,
+            key_slots: Vec::new(),
+            emergency_grants: Vec::new(),
+            passphrase_keys: HashMap::new(),
+            saved_log_len: 0,
+            provenance: None,
         }
     }
 
     /// Initializes a new project in the vault.
     pub fn init_project(&mut self, name: &str) -> Result<(), VaultError> {
-        if self.projects.contains_key(name) {
+        if self.projects.contains_key(name) || self.sealed_projects.contains_key(name) {
             return Err(VaultError::ProjectAlreadyExists(name.to_string()));
         }
 
@@ -101,6 +788,12 @@ impl Vault {
             name: name.to_string(),
             secrets: HashMap::new(),
             created_at: ttl::current_timestamp(),
+            environments: HashMap::new(),
+            attachments: HashMap::new(),
+            lock: None,
+            rules: SecretRules::default(),
+            generation: None,
+            shared_values: HashMap::new(),
         };
 
         self.projects.insert(name.to_string(), project);
@@ -123,77 +816,728 @@ impl Vault {
         encryption_key: &[u8; KEY_SIZE],
         ttl_seconds: Option<u64>,
     ) -> Result<(), VaultError> {
-        let proj = self
-            .projects
-            .get_mut(project)
-            .ok_or_else(|| VaultError::ProjectNotFound(project.to_string()))?;
+        self.add_secret_in(project, None, key, value, encryption_key, ttl_seconds)
+    }
 
-        let encrypted = crypto::encrypt(value, encryption_key)?;
+    /// Adds a secret to an environment of a project (`None` for the default
+    /// environment). Named environments are created on first use.
+    ///
+    /// # Errors
+    /// - `VaultError::RuleViolation` if the secret breaks one of the
+    ///   project's rules; see `set_rules`
+    /// - `VaultError::TtlTooLong` if the secret would expire past the end of
+    ///   time
+    pub fn add_secret_in(
+        &mut self,
+        project: &str,
+        env: Option<&str>,
+        key: &str,
+        value: &[u8],
+        encryption_key: &[u8; KEY_SIZE],
+        ttl_seconds: Option<u64>,
+    ) -> Result<(), VaultError> {
         let now = ttl::current_timestamp();
+        let expires_at = expiry(key, ttl_seconds, now)?;
+        self.project(project)?.rules.check_expiry(key, expires_at, now)?;
+        self.write_value(project, env, key, value, encryption_key, expires_at)
+    }
+
+    /// Stores a value as a text secret expiring at `expires_at`, after
+    /// checking its name and value against the project's rules.
+    fn write_value(
+        &mut self,
+        project: &str,
+        env: Option<&str>,
+        key: &str,
+        value: &[u8],
+        encryption_key: &[u8; KEY_SIZE],
+        expires_at: Option<u64>,
+    ) -> Result<(), VaultError> {
+        let value_key = self.value_key(project, encryption_key)?;
+        let provenance = self.provenance.clone();
+        let proj = self.project_mut(project)?;
+        proj.rules.check_key(key)?;
+        proj.rules.check_value(key, value)?;
+
+        // A value the project already holds reuses its ciphertext, which
+        // is what encrypting it again with the same nonce would give
+        let value_hash = value_hash(value, &value_key);
+        let (ciphertext, nonce) = match value_hash.as_deref().and_then(|h| proj.find_value(h)) {
+            Some(existing) => existing,
+            None => {
+                let encrypted = crypto::encrypt(value, &value_key)?;
+                (encrypted.ciphertext, encrypted.nonce)
+            }
+        };
 
         let secret = Secret {
             key: key.to_string(),
-            encrypted_value: encrypted.ciphertext,
-            nonce: encrypted.nonce,
-            created_at: now,
-            expires_at: ttl_seconds.and_then(|ttl| ttl::calculate_expiry(ttl, now)),
+            encrypted_value: ciphertext,
+            nonce,
+            created_at: ttl::current_timestamp(),
+            modified_at: None,
+            expires_at,
+            alias: None,
+            template: false,
+            kind: SecretKind::Text,
+            last_accessed: AccessTime::default(),
+            provenance,
+            value_hash,
         };
 
-        proj.secrets.insert(key.to_string(), secret);
+        let secrets = match env {
+            None => &mut proj.secrets,
+            Some(env) => proj.environments.entry(env.to_string()).or_default(),
+        };
+        secrets.insert(key.to_string(), secret);
         Ok(())
     }
 
     /// Retrieves and decrypts a secret from a project.
+    ///
+    /// Projects with a passphrase must be unlocked first with
+    /// `unlock_project`, otherwise `VaultError::ProjectLocked` is returned.
     pub fn get_secret(
         &self,
         project: &str,
         key: &str,
         encryption_key: &[u8; KEY_SIZE],
-    ) -> Result<Vec<u8>, VaultError> {
-        let proj = self
-            .projects
-            .get(project)
-            .ok_or_else(|| VaultError::ProjectNotFound(project.to_string()))?;
+    ) -> Result<SecretBytes, VaultError> {
+        self.get_secret_in(project, None, key, encryption_key)
+    }
 
-        let secret = proj
-            .secrets
-            .get(key)
-            .ok_or_else(|| VaultError::SecretNotFound(key.to_string()))?;
+    /// Retrieves and decrypts a secret from an environment of a project
+    /// (`None` for the default environment).
+    ///
+    /// There is no fallback between environments: a key missing from `env`
+    /// is not found even if the default environment has it. Aliases are
+    /// followed to the secret they refer to, whose project must be unsealed
+    /// and unlocked, and templates are rendered.
+    pub fn get_secret_in(
+        &self,
+        project: &str,
+        env: Option<&str>,
+        key: &str,
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<SecretBytes, VaultError> {
+        self.read_secret(project, env, key, encryption_key, 0)
+    }
+
+    fn read_secret(
+        &self,
+        project: &str,
+        env: Option<&str>,
+        key: &str,
+        encryption_key: &[u8; KEY_SIZE],
+        depth: usize,
+    ) -> Result<SecretBytes, VaultError> {
+        if self.is_locked(project) {
+            return Err(VaultError::ProjectLocked(project.to_string()));
+        }
+
+        let target = self.resolve_alias(project, env, key)?;
+        let value_key = self.value_key(&target.project, encryption_key)?;
+        let secret = self.find_secret(&target)?;
 
         // Check expiration
         let now = ttl::current_timestamp();
         if ttl::is_expired(secret.expires_at, now) {
-            return Err(VaultError::SecretExpired(key.to_string()));
+            return Err(VaultError::SecretExpired(target.key));
         }
 
         let encrypted = EncryptedData {
             ciphertext: secret.encrypted_value.clone(),
             nonce: secret.nonce,
         };
+        let mut value = crypto::decrypt(&encrypted, &value_key)?;
 
-        crypto::decrypt(&encrypted, encryption_key).map_err(VaultError::CryptoError)
-    }
+        if secret.template {
+            if depth >= MAX_TEMPLATE_DEPTH {
+                return Err(VaultError::TemplateCycle(target.to_string()));
+            }
 
-    /// Adds an SSH identity to the vault.
-    pub fn add_ssh_identity(
-        &mut self,
-        name: &str,
-        public_key: String,
-        private_key: &[u8],
-        encryption_key: &[u8; KEY_SIZE],
-    ) -> Result<(), VaultError> {
-        if self.ssh_identities.contains_key(name) {
-            return Err(VaultError::IdentityAlreadyExists(name.to_string()));
+            // Placeholders refer to secrets next to the template
+            value = template::render(&value, |name| {
+                self.read_secret(
+                    &target.project,
+                    target.env.as_deref(),
+                    name,
+                    encryption_key,
+                    depth + 1,
+                )
+            })?;
         }
 
-        let encrypted = crypto::encrypt(private_key, encryption_key)?;
+        // Both an alias and the secret it leads to count as read
+        secret.last_accessed.set(now);
+        if let Ok(requested) = self.find_secret(&SecretRef::new(project, env, key)) {
+            requested.last_accessed.set(now);
+        }
+        Ok(value)
+    }
 
-        let identity = SshIdentity {
+    /// Decrypts every value stored in a project, in all environments and
+    /// including expired secrets, for checks such as `scan`. Aliases and
+    /// templates are skipped: their values come from other secrets.
+    pub fn stored_values(
+        &self,
+        project: &str,
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<Vec<(SecretRef, SecretBytes)>, VaultError> {
+        let value_key = self.value_key(project, encryption_key)?;
+
+        let mut values = Vec::new();
+        for (env, secrets) in self.project(project)?.secrets_by_environment() {
+            for secret in secrets.values() {
+                if secret.alias.is_some() || secret.template {
+                    continue;
+                }
+                let encrypted = EncryptedData {
+                    ciphertext: secret.encrypted_value.clone(),
+                    nonce: secret.nonce,
+                };
+                values.push((
+                    SecretRef::new(project, env, &secret.key),
+                    crypto::decrypt(&encrypted, &value_key)?,
+                ));
+            }
+        }
+        Ok(values)
+    }
+
+    /// Stores a template whose `{{NAME}}` placeholders are replaced with the
+    /// values of the secrets `NAME` in the same project and environment
+    /// whenever it is read; see `template`.
+    ///
+    /// The template itself is encrypted like any other value. Placeholders
+    /// are checked for syntax only, so they may name secrets added later.
+    pub fn add_template_in(
+        &mut self,
+        project: &str,
+        env: Option<&str>,
+        key: &str,
+        template: &[u8],
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<(), VaultError> {
+        template::placeholders(template)?;
+        // Templates need no TTL of their own; see `SecretRules::require_ttl`
+        self.write_value(project, env, key, template, encryption_key, None)?;
+
+        let proj = self.project_mut(project)?;
+        let secrets = match env {
+            None => &mut proj.secrets,
+            Some(env) => proj.environments.get_mut(env).unwrap(),
+        };
+        secrets.get_mut(key).unwrap().template = true;
+        Ok(())
+    }
+
+    /// Stores a JSON document, such as a cloud service-account key, whose
+    /// fields can then be read one at a time with `structured::JsonDocument`.
+    ///
+    /// # Errors
+    /// `VaultError::InvalidJson` if `document` does not parse.
+    pub fn add_json_in(
+        &mut self,
+        project: &str,
+        env: Option<&str>,
+        key: &str,
+        document: &[u8],
+        encryption_key: &[u8; KEY_SIZE],
+        ttl_seconds: Option<u64>,
+    ) -> Result<(), VaultError> {
+        JsonDocument::parse(document)?;
+        self.add_secret_in(project, env, key, document, encryption_key, ttl_seconds)?;
+
+        let proj = self.project_mut(project)?;
+        let secrets = match env {
+            None => &mut proj.secrets,
+            Some(env) => proj.environments.get_mut(env).unwrap(),
+        };
+        secrets.get_mut(key).unwrap().kind = SecretKind::Json;
+        Ok(())
+    }
+
+    /// Stores database credentials, a `database::DbConnection` serialized
+    /// with `to_bytes`.
+    ///
+    /// # Errors
+    /// `VaultError::InvalidDbConnection` if `connection` does not parse.
+    pub fn add_database_in(
+        &mut self,
+        project: &str,
+        env: Option<&str>,
+        key: &str,
+        connection: &[u8],
+        encryption_key: &[u8; KEY_SIZE],
+        ttl_seconds: Option<u64>,
+    ) -> Result<(), VaultError> {
+        DbConnection::parse(connection)?;
+        self.add_secret_in(project, env, key, connection, encryption_key, ttl_seconds)?;
+
+        let proj = self.project_mut(project)?;
+        let secrets = match env {
+            None => &mut proj.secrets,
+            Some(env) => proj.environments.get_mut(env).unwrap(),
+        };
+        secrets.get_mut(key).unwrap().kind = SecretKind::Database;
+        Ok(())
+    }
+
+    /// Stores an X.509 certificate (PEM or DER) that expires at the
+    /// certificate's `notAfter`, so audits and expiry notifications warn
+    /// before it lapses. Returns what was read from the certificate.
+    ///
+    /// # Errors
+    /// - `VaultError::InvalidCertificate` if `value` is not a certificate or
+    ///   has already expired
+    pub fn add_certificate_in(
+        &mut self,
+        project: &str,
+        env: Option<&str>,
+        key: &str,
+        value: &[u8],
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<CertificateInfo, VaultError> {
+        let info = certificate::parse(value)?;
+        if ttl::is_expired(Some(info.not_after), ttl::current_timestamp()) {
+            return Err(VaultError::InvalidCertificate(format!(
+                "expired on {}",
+                ttl::format_timestamp(info.not_after)
+            )));
+        }
+        self.write_value(project, env, key, value, encryption_key, Some(info.not_after))?;
+
+        let proj = self.project_mut(project)?;
+        let secrets = match env {
+            None => &mut proj.secrets,
+            Some(env) => proj.environments.get_mut(env).unwrap(),
+        };
+        secrets.get_mut(key).unwrap().kind = SecretKind::Certificate;
+        Ok(info)
+    }
+
+    /// Sets when a secret expires, `ttl` seconds from now or never if `None`,
+    /// leaving its value as it is. Returns the new expiry.
+    ///
+    /// # Errors
+    /// - `VaultError::FixedExpiry` for certificates, which expire with the
+    ///   certificate, and aliases, which follow the secret they refer to
+    /// - `VaultError::RuleViolation` if the project requires a TTL and `ttl`
+    ///   is `None`, or `ttl` is longer than the project allows
+    /// - `VaultError::TtlTooLong` if the secret would expire past the end of
+    ///   time
+    pub fn set_ttl_in(
+        &mut self,
+        project: &str,
+        env: Option<&str>,
+        key: &str,
+        ttl: Option<u64>,
+    ) -> Result<Option<u64>, VaultError> {
+        let now = ttl::current_timestamp();
+        let expires_at = expiry(key, ttl, now)?;
+        self.project(project)?.rules.check_expiry(key, expires_at, now)?;
+
+        let secret = self
+            .project_mut(project)?
+            .secrets_in_mut(env)?
+            .get_mut(key)
+            .ok_or_else(|| VaultError::SecretNotFound(key.to_string()))?;
+        if secret.alias.is_some() {
+            return Err(VaultError::FixedExpiry(
+                key.to_string(),
+                "it is an alias; change the secret it refers to",
+            ));
+        }
+        if secret.kind == SecretKind::Certificate {
+            return Err(VaultError::FixedExpiry(
+                key.to_string(),
+                "it is a certificate, which expires when the certificate does",
+            ));
+        }
+
+        secret.expires_at = expires_at;
+        secret.modified_at = Some(now);
+        Ok(expires_at)
+    }
+
+    /// Makes a secret an alias of another secret, so a shared credential is
+    /// stored once and read through every project that refers to it.
+    ///
+    /// The target must exist (it may itself be an alias) and every project on
+    /// the chain must be unsealed. An existing secret at the alias location
+    /// is replaced. Returns `VaultError::AliasCycle` if the alias would end
+    /// up referring to itself.
+    pub fn add_alias(
+        &mut self,
+        project: &str,
+        env: Option<&str>,
+        key: &str,
+        target: SecretRef,
+    ) -> Result<(), VaultError> {
+        self.transaction(|tx| {
+            let provenance = tx.provenance.clone();
+            let proj = tx.project_mut(project)?;
+            let secrets = match env {
+                None => &mut proj.secrets,
+                Some(env) => proj.environments.entry(env.to_string()).or_default(),
+            };
+
+            secrets.insert(
+                key.to_string(),
+                Secret {
+                    key: key.to_string(),
+                    encrypted_value: Vec::new(),
+                    nonce: [0u8; NONCE_SIZE],
+                    created_at: ttl::current_timestamp(),
+                    modified_at: None,
+                    expires_at: None,
+                    alias: Some(target),
+                    template: false,
+                    kind: SecretKind::Text,
+                    last_accessed: AccessTime::default(),
+                    provenance,
+                    value_hash: None,
+                },
+            );
+
+            tx.resolve_alias(project, env, key).map(|_| ())
+        })
+    }
+
+    /// Follows a chain of aliases and returns the location of the secret
+    /// that holds the value. A secret that is not an alias resolves to
+    /// itself.
+    ///
+    /// # Errors
+    /// - `VaultError::ProjectSealed` if a project on the chain is sealed
+    /// - `VaultError::DanglingAlias` if a secret on the chain no longer exists
+    /// - `VaultError::AliasCycle` if the chain loops or is nested too deeply
+    pub fn resolve_alias(
+        &self,
+        project: &str,
+        env: Option<&str>,
+        key: &str,
+    ) -> Result<SecretRef, VaultError> {
+        let start = SecretRef::new(project, env, key);
+        let mut current = start.clone();
+
+        for depth in 0..=MAX_ALIAS_DEPTH {
+            let secret = match self.find_secret(&current) {
+                Ok(secret) => secret,
+                Err(e) if depth == 0 || matches!(e, VaultError::ProjectSealed(_)) => {
+                    return Err(e)
+                }
+                Err(_) => return Err(VaultError::DanglingAlias(start.to_string())),
+            };
+
+            match &secret.alias {
+                Some(target) => current = target.clone(),
+                None => return Ok(current),
+            }
+        }
+
+        Err(VaultError::AliasCycle(start.to_string()))
+    }
+
+    /// Returns every alias in the unsealed projects, as pairs of the alias
+    /// location and the secret it refers to.
+    pub fn aliases(&self) -> Vec<(SecretRef, &SecretRef)> {
+        self.projects
+            .iter()
+            .flat_map(|(name, project)| {
+                project.secrets_by_environment().flat_map(move |(env, secrets)| {
+                    secrets.iter().filter_map(move |(key, secret)| {
+                        let target = secret.alias.as_ref()?;
+                        Some((SecretRef::new(name, env, key), target))
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Replaces the validation rules of a project. They apply to secrets
+    /// written from then on; secrets already stored are left as they are,
+    /// and the audit reports those that break the new rules.
+    ///
+    /// # Errors
+    /// `VaultError::InvalidRule` if a pattern is not a valid regular
+    /// expression.
+    pub fn set_rules(&mut self, project: &str, rules: SecretRules) -> Result<(), VaultError> {
+        rules.validate()?;
+        self.project_mut(project)?.rules = rules;
+        Ok(())
+    }
+
+    /// Sets how secrets of a project are generated, or removes its policy
+    /// with `None`. Secrets already stored are left as they are; the audit
+    /// reports those the policy applies to that do not follow it.
+    ///
+    /// # Errors
+    /// `VaultError::InvalidPolicy` if `policy` cannot generate values; see
+    /// `GenerationPolicy::validate`.
+    pub fn set_generation_policy(
+        &mut self,
+        project: &str,
+        policy: Option<GenerationPolicy>,
+    ) -> Result<(), VaultError> {
+        if let Some(policy) = &policy {
+            policy.validate()?;
+        }
+        self.project_mut(project)?.generation = policy;
+        Ok(())
+    }
+
+    /// Returns the policy new secrets of a project are generated with: its
+    /// own, or the default one if it has none.
+    pub fn generation_policy(&self, project: &str) -> Result<GenerationPolicy, VaultError> {
+        Ok(self.project(project)?.generation.clone().unwrap_or_default())
+    }
+
+    /// Protects a project with its own passphrase, or changes it.
+    ///
+    /// Secret values are re-encrypted under a key derived from both the
+    /// master key and `passphrase`. A project that already has a passphrase
+    /// must be unlocked first. Attachments stay under the master key.
+    pub fn set_project_passphrase(
+        &mut self,
+        name: &str,
+        passphrase: &[u8],
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<(), VaultError> {
+        let old_key = self.value_key(name, encryption_key)?;
+
+        let salt = crypto::generate_salt();
+        let passphrase_key = crypto::derive_key(passphrase, &salt)?;
+        let new_key = layered_key(encryption_key, &passphrase_key, name);
+        let verifier = crypto::encrypt(LOCK_VERIFIER, &new_key)?;
+
+        let proj = self.project_mut(name)?;
+        reencrypt_secrets(proj, &old_key, &new_key)?;
+        self.reencrypt_trashed_secrets(name, &old_key, &new_key)?;
+        let proj = self.project_mut(name)?;
+        proj.lock = Some(ProjectLock {
+            salt: salt.to_vec(),
+            verifier: verifier.ciphertext,
+            nonce: verifier.nonce,
+        });
+
+        self.passphrase_keys
+            .insert(name.to_string(), SecureKey::new(&passphrase_key));
+        Ok(())
+    }
+
+    /// Removes a project's passphrase, re-encrypting its secret values under
+    /// the master key. The project must be unlocked.
+    pub fn remove_project_passphrase(
+        &mut self,
+        name: &str,
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<(), VaultError> {
+        let old_key = self.value_key(name, encryption_key)?;
+
+        let proj = self.project_mut(name)?;
+        reencrypt_secrets(proj, &old_key, encryption_key)?;
+        self.reencrypt_trashed_secrets(name, &old_key, encryption_key)?;
+        self.project_mut(name)?.lock = None;
+
+        self.passphrase_keys.remove(name);
+        Ok(())
+    }
+
+    /// Re-encrypts the values of a project's secrets and environments in
+    /// the trash, so they can still be restored after its passphrase
+    /// changes.
+    fn reencrypt_trashed_secrets(
+        &mut self,
+        name: &str,
+        old_key: &[u8; KEY_SIZE],
+        new_key: &[u8; KEY_SIZE],
+    ) -> Result<(), VaultError> {
+        for entry in &mut self.trash {
+            let secrets: Vec<&mut Secret> = match &mut entry.item {
+                TrashedItem::Secret { location, secret } if location.project == name => {
+                    vec![secret.as_mut()]
+                }
+                TrashedItem::Environment {
+                    project, secrets, ..
+                } if project == name => secrets.values_mut().collect(),
+                _ => continue,
+            };
+            reencrypt_all(secrets.into_iter(), old_key, new_key)?;
+        }
+        Ok(())
+    }
+
+    /// Checks a project's passphrase and keeps its key in memory so that
+    /// `get_secret` and `add_secret` can use the project.
+    ///
+    /// Does nothing for projects without a passphrase.
+    pub fn unlock_project(
+        &mut self,
+        name: &str,
+        passphrase: &[u8],
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<(), VaultError> {
+        let lock = match &self.project(name)?.lock {
+            Some(lock) => lock,
+            None => return Ok(()),
+        };
+
+        let salt: [u8; SALT_SIZE] = lock
+            .salt
+            .as_slice()
+            .try_into()
+            .map_err(|_| VaultError::CorruptedVault)?;
+        let passphrase_key = crypto::derive_key(passphrase, &salt)?;
+
+        let verifier = EncryptedData {
+            ciphertext: lock.verifier.clone(),
+            nonce: lock.nonce,
+        };
+        let project_key = layered_key(encryption_key, &passphrase_key, name);
+        if crypto::decrypt(&verifier, &project_key).is_err() {
+            return Err(VaultError::InvalidProjectPassphrase(name.to_string()));
+        }
+
+        self.passphrase_keys
+            .insert(name.to_string(), SecureKey::new(&passphrase_key));
+        Ok(())
+    }
+
+    /// Returns whether a project has a passphrase that has not been unlocked
+    /// in this session.
+    pub fn is_locked(&self, name: &str) -> bool {
+        self.projects
+            .get(name)
+            .is_some_and(|p| p.lock.is_some() && !self.passphrase_keys.contains_key(name))
+    }
+
+    /// Attaches a file to a project.
+    ///
+    /// The contents are encrypted in the chunked `stream` format and
+    /// returned as a blob for the caller to persist under the returned blob
+    /// id. The vault only keeps metadata.
+    pub fn add_attachment(
+        &mut self,
+        project: &str,
+        name: &str,
+        contents: &[u8],
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<(String, Vec<u8>), VaultError> {
+        let proj = self.project_mut(project)?;
+
+        if proj.attachments.contains_key(name) {
+            return Err(VaultError::AttachmentAlreadyExists(name.to_string()));
+        }
+
+        let aad = attachment_aad(project, name);
+        let blob = stream::encrypt_with_aad(contents, encryption_key, &aad)?;
+        let blob_id = generate_blob_id();
+
+        let attachment = Attachment {
+            name: name.to_string(),
+            blob_id: blob_id.clone(),
+            size: contents.len() as u64,
+            nonce: [0u8; NONCE_SIZE],
+            chunked: true,
+            bound: true,
+            created_at: ttl::current_timestamp(),
+        };
+
+        proj.attachments.insert(name.to_string(), attachment);
+        Ok((blob_id, blob))
+    }
+
+    /// Retrieves an attachment's metadata.
+    pub fn get_attachment(&self, project: &str, name: &str) -> Result<&Attachment, VaultError> {
+        self.project(project)?
+            .attachments
+            .get(name)
+            .ok_or_else(|| VaultError::AttachmentNotFound(name.to_string()))
+    }
+
+    /// Decrypts an attachment blob previously returned by `add_attachment`.
+    pub fn open_attachment(
+        &self,
+        project: &str,
+        name: &str,
+        blob: &[u8],
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<SecretBytes, VaultError> {
+        let attachment = self.get_attachment(project, name)?;
+        if attachment.bound {
+            return stream::decrypt_with_aad(blob, encryption_key, &attachment_aad(project, name))
+                .map_err(VaultError::CryptoError);
+        }
+        if attachment.chunked {
+            return stream::decrypt(blob, encryption_key).map_err(VaultError::CryptoError);
+        }
+
+        let encrypted = EncryptedData {
+            ciphertext: blob.to_vec(),
+            nonce: attachment.nonce,
+        };
+
+        crypto::decrypt(&encrypted, encryption_key).map_err(VaultError::CryptoError)
+    }
+
+    /// Removes an attachment from a project, returning its metadata so the
+    /// caller can delete the blob.
+    pub fn remove_attachment(&mut self, project: &str, name: &str) -> Result<Attachment, VaultError> {
+        let proj = self.project_mut(project)?;
+
+        proj.attachments
+            .remove(name)
+            .ok_or_else(|| VaultError::AttachmentNotFound(name.to_string()))
+    }
+
+    /// Adds an SSH identity to the vault.
+    pub fn add_ssh_identity(
+        &mut self,
+        name: &str,
+        public_key: String,
+        private_key: &[u8],
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<(), VaultError> {
+        if self.ssh_identities.contains_key(name) {
+            return Err(VaultError::IdentityAlreadyExists(name.to_string()));
+        }
+
+        let encrypted = crypto::encrypt(private_key, encryption_key)?;
+
+        let identity = SshIdentity {
             name: name.to_string(),
             public_key,
             encrypted_private_key: encrypted.ciphertext,
             nonce: encrypted.nonce,
             created_at: ttl::current_timestamp(),
+            pkcs11: None,
+        };
+
+        self.ssh_identities.insert(name.to_string(), identity);
+        Ok(())
+    }
+
+    /// Adds an SSH identity whose key stays on a PKCS#11 token. Only the
+    /// public key and where to find the key are stored.
+    pub fn add_pkcs11_identity(
+        &mut self,
+        name: &str,
+        public_key: String,
+        key: Pkcs11Key,
+    ) -> Result<(), VaultError> {
+        if self.ssh_identities.contains_key(name) {
+            return Err(VaultError::IdentityAlreadyExists(name.to_string()));
+        }
+
+        let identity = SshIdentity {
+            name: name.to_string(),
+            public_key,
+            encrypted_private_key: Vec::new(),
+            nonce: [0; NONCE_SIZE],
+            created_at: ttl::current_timestamp(),
+            pkcs11: Some(key),
         };
 
         self.ssh_identities.insert(name.to_string(), identity);
@@ -201,339 +1545,4179 @@ impl Vault {
     }
 
     /// Retrieves and decrypts an SSH identity's private key.
+    ///
+    /// # Errors
+    /// `VaultError::IdentityOnToken` for an identity kept on a PKCS#11
+    /// token, which has no private key to return.
     pub fn get_ssh_identity(
         &self,
         name: &str,
         encryption_key: &[u8; KEY_SIZE],
-    ) -> Result<(String, Vec<u8>), VaultError> {
+    ) -> Result<(String, SecretBytes), VaultError> {
         let identity = self
             .ssh_identities
             .get(name)
             .ok_or_else(|| VaultError::IdentityNotFound(name.to_string()))?;
+        if identity.pkcs11.is_some() {
+            return Err(VaultError::IdentityOnToken(name.to_string()));
+        }
+
+        let encrypted = EncryptedData {
+            ciphertext: identity.encrypted_private_key.clone(),
+            nonce: identity.nonce,
+        };
+
+        let private_key = crypto::decrypt(&encrypted, encryption_key)?;
+
+        Ok((identity.public_key.clone(), private_key))
+    }
+
+    /// Adds an SSH server configuration to the vault.
+    pub fn add_ssh_server(
+        &mut self,
+        name: &str,
+        username: String,
+        ip_address: String,
+        identity_name: String,
+    ) -> Result<(), VaultError> {
+        // Validate that the identity exists
+        if !self.ssh_identities.contains_key(&identity_name) {
+            return Err(VaultError::IdentityNotFound(identity_name));
+        }
+
+        let server = SshServerConfig {
+            name: name.to_string(),
+            username,
+            ip_address,
+            identity_name,
+            created_at: ttl::current_timestamp(),
+            multiplex: None,
+            host_keys: Vec::new(),
+        };
+
+        self.ssh_servers.insert(name.to_string(), server);
+        Ok(())
+    }
+
+    /// Records the host keys connections to a server must present. An
+    /// empty list forgets them, so the next connection asks again.
+    pub fn set_ssh_server_host_keys(
+        &mut self,
+        name: &str,
+        host_keys: Vec<String>,
+    ) -> Result<(), VaultError> {
+        let server = self
+            .ssh_servers
+            .get_mut(name)
+            .ok_or_else(|| VaultError::ServerNotFound(name.to_string()))?;
+        server.host_keys = host_keys;
+        Ok(())
+    }
+
+    /// Sets whether connections to a server share one authenticated
+    /// connection (OpenSSH `ControlMaster`), kept open for `idle_seconds`
+    /// after the last one ends. `None` turns sharing off.
+    pub fn set_ssh_server_multiplex(
+        &mut self,
+        name: &str,
+        idle_seconds: Option<u64>,
+    ) -> Result<(), VaultError> {
+        let server = self
+            .ssh_servers
+            .get_mut(name)
+            .ok_or_else(|| VaultError::ServerNotFound(name.to_string()))?;
+        server.multiplex = idle_seconds;
+        Ok(())
+    }
+
+    /// Retrieves an SSH server configuration.
+    pub fn get_ssh_server(&self, name: &str) -> Result<&SshServerConfig, VaultError> {
+        self.ssh_servers
+            .get(name)
+            .ok_or_else(|| VaultError::ServerNotFound(name.to_string()))
+    }
+
+    /// Checks if an SSH server configuration exists.
+    pub fn has_ssh_server(&self, name: &str) -> bool {
+        self.ssh_servers.contains_key(name)
+    }
+
+    /// Removes an SSH server configuration. The identity it uses is kept;
+    /// the server is dropped from its groups, and groups left empty are
+    /// removed.
+    pub fn remove_ssh_server(&mut self, name: &str) -> Result<SshServerConfig, VaultError> {
+        let server = self
+            .ssh_servers
+            .remove(name)
+            .ok_or_else(|| VaultError::ServerNotFound(name.to_string()))?;
+        for members in self.ssh_server_groups.values_mut() {
+            members.retain(|member| member != name);
+        }
+        self.ssh_server_groups.retain(|_, members| !members.is_empty());
+        Ok(server)
+    }
+
+    /// Defines a named group of configured servers, so commands taking
+    /// server names can be given the group instead. Duplicate members are
+    /// kept once, in their first position.
+    ///
+    /// # Errors
+    /// - `VaultError::ServerGroupAlreadyExists` if the group exists
+    /// - `VaultError::ServerAlreadyExists` if a server has the group's name,
+    ///   since server names take precedence when names are expanded
+    /// - `VaultError::ServerNotFound` if a member is not a configured server
+    /// - `VaultError::EmptyServerGroup` if no members are given
+    pub fn create_ssh_server_group(
+        &mut self,
+        name: &str,
+        members: &[String],
+    ) -> Result<(), VaultError> {
+        if self.ssh_server_groups.contains_key(name) {
+            return Err(VaultError::ServerGroupAlreadyExists(name.to_string()));
+        }
+        if self.ssh_servers.contains_key(name) {
+            return Err(VaultError::ServerAlreadyExists(name.to_string()));
+        }
+        if let Some(missing) = members.iter().find(|m| !self.ssh_servers.contains_key(*m)) {
+            return Err(VaultError::ServerNotFound(missing.clone()));
+        }
+
+        let mut unique: Vec<String> = Vec::with_capacity(members.len());
+        for member in members {
+            if !unique.contains(member) {
+                unique.push(member.clone());
+            }
+        }
+        if unique.is_empty() {
+            return Err(VaultError::EmptyServerGroup(name.to_string()));
+        }
+
+        self.ssh_server_groups.insert(name.to_string(), unique);
+        Ok(())
+    }
+
+    /// Removes a server group, returning its members. The servers are kept.
+    pub fn remove_ssh_server_group(&mut self, name: &str) -> Result<Vec<String>, VaultError> {
+        self.ssh_server_groups
+            .remove(name)
+            .ok_or_else(|| VaultError::ServerGroupNotFound(name.to_string()))
+    }
+
+    /// Expands a list of server and group names into server names, in the
+    /// order given and without duplicates. A name is looked up as a server
+    /// first, then as a group.
+    ///
+    /// # Errors
+    /// `VaultError::ServerNotFound` for a name that is neither.
+    pub fn expand_ssh_servers<S: AsRef<str>>(
+        &self,
+        names: &[S],
+    ) -> Result<Vec<String>, VaultError> {
+        let mut servers: Vec<String> = Vec::new();
+        for name in names.iter().map(AsRef::as_ref) {
+            let members = if self.ssh_servers.contains_key(name) {
+                vec![name.to_string()]
+            } else {
+                self.ssh_server_groups
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| VaultError::ServerNotFound(name.to_string()))?
+            };
+            for member in members {
+                if !servers.contains(&member) {
+                    servers.push(member);
+                }
+            }
+        }
+        Ok(servers)
+    }
+
+    /// Stores a kubeconfig file, encrypted under the master key.
+    pub fn add_kubeconfig(
+        &mut self,
+        name: &str,
+        config: &[u8],
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<(), VaultError> {
+        if self.kubeconfigs.contains_key(name) {
+            return Err(VaultError::KubeconfigAlreadyExists(name.to_string()));
+        }
+
+        let encrypted = crypto::encrypt(config, encryption_key)?;
+
+        let kubeconfig = Kubeconfig {
+            name: name.to_string(),
+            encrypted_config: encrypted.ciphertext,
+            nonce: encrypted.nonce,
+            created_at: ttl::current_timestamp(),
+        };
+
+        self.kubeconfigs.insert(name.to_string(), kubeconfig);
+        Ok(())
+    }
+
+    /// Retrieves and decrypts a kubeconfig file.
+    pub fn get_kubeconfig(
+        &self,
+        name: &str,
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<SecretBytes, VaultError> {
+        let kubeconfig = self
+            .kubeconfigs
+            .get(name)
+            .ok_or_else(|| VaultError::KubeconfigNotFound(name.to_string()))?;
+
+        let encrypted = EncryptedData {
+            ciphertext: kubeconfig.encrypted_config.clone(),
+            nonce: kubeconfig.nonce,
+        };
+
+        Ok(crypto::decrypt(&encrypted, encryption_key)?)
+    }
+
+    /// Removes a kubeconfig file.
+    pub fn remove_kubeconfig(&mut self, name: &str) -> Result<Kubeconfig, VaultError> {
+        self.kubeconfigs
+            .remove(name)
+            .ok_or_else(|| VaultError::KubeconfigNotFound(name.to_string()))
+    }
+
+    /// Stores a WireGuard config, encrypted under the master key, for the
+    /// interface `name`.
+    ///
+    /// # Errors
+    /// - `VaultError::InvalidInterfaceName` if `name` cannot name an interface
+    /// - `VaultError::InvalidWireguardConfig` if `config` has no interface
+    ///   private key
+    pub fn add_wireguard_config(
+        &mut self,
+        name: &str,
+        config: &[u8],
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<(), VaultError> {
+        wireguard::validate_interface_name(name)?;
+        wireguard::validate_config(config)?;
+        if self.wireguard_configs.contains_key(name) {
+            return Err(VaultError::WireguardConfigAlreadyExists(name.to_string()));
+        }
+
+        let encrypted = crypto::encrypt(config, encryption_key)?;
+
+        let wireguard = WireguardConfig {
+            name: name.to_string(),
+            encrypted_config: encrypted.ciphertext,
+            nonce: encrypted.nonce,
+            created_at: ttl::current_timestamp(),
+        };
+
+        self.wireguard_configs.insert(name.to_string(), wireguard);
+        Ok(())
+    }
+
+    /// Retrieves and decrypts a WireGuard config.
+    pub fn get_wireguard_config(
+        &self,
+        name: &str,
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<SecretBytes, VaultError> {
+        let wireguard = self
+            .wireguard_configs
+            .get(name)
+            .ok_or_else(|| VaultError::WireguardConfigNotFound(name.to_string()))?;
+
+        let encrypted = EncryptedData {
+            ciphertext: wireguard.encrypted_config.clone(),
+            nonce: wireguard.nonce,
+        };
+
+        Ok(crypto::decrypt(&encrypted, encryption_key)?)
+    }
+
+    /// Removes a WireGuard config.
+    pub fn remove_wireguard_config(&mut self, name: &str) -> Result<WireguardConfig, VaultError> {
+        self.wireguard_configs
+            .remove(name)
+            .ok_or_else(|| VaultError::WireguardConfigNotFound(name.to_string()))
+    }
+
+    /// Allows a browser origin (e.g. `https://github.com`) to request a
+    /// secret through the native messaging host. The origin is normalized
+    /// with `normalize_origin`; granting the same secret twice is a no-op.
+    pub fn allow_origin(&mut self, origin: &str, secret: SecretRef) -> Result<(), VaultError> {
+        let origin = normalize_origin(origin)?;
+        self.find_secret(&secret)?;
+
+        let grants = self.origin_grants.entry(origin).or_default();
+        if !grants.contains(&secret) {
+            grants.push(secret);
+        }
+        Ok(())
+    }
+
+    /// Revokes one secret from an origin, or every secret if `secret` is
+    /// `None`.
+    pub fn revoke_origin(
+        &mut self,
+        origin: &str,
+        secret: Option<&SecretRef>,
+    ) -> Result<(), VaultError> {
+        let origin = normalize_origin(origin)?;
+        let not_allowed = || VaultError::OriginNotAllowed(origin.clone());
+
+        let Some(secret) = secret else {
+            return self.origin_grants.remove(&origin).map(|_| ()).ok_or_else(not_allowed);
+        };
+
+        let grants = self.origin_grants.get_mut(&origin).ok_or_else(not_allowed)?;
+        let before = grants.len();
+        grants.retain(|granted| granted != secret);
+        if grants.len() == before {
+            return Err(not_allowed());
+        }
+        if grants.is_empty() {
+            self.origin_grants.remove(&origin);
+        }
+        Ok(())
+    }
+
+    /// Returns the secrets an origin may request, in the order granted.
+    pub fn origin_allowed_secrets(&self, origin: &str) -> &[SecretRef] {
+        normalize_origin(origin)
+            .ok()
+            .and_then(|origin| self.origin_grants.get(&origin))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns whether an origin may request a secret.
+    pub fn origin_allows(&self, origin: &str, secret: &SecretRef) -> bool {
+        self.origin_allowed_secrets(origin).contains(secret)
+    }
+
+    /// Removes a project and all its secrets.
+    ///
+    /// Sealed projects can be removed without unsealing them first.
+    pub fn remove_project(&mut self, name: &str) -> Result<(), VaultError> {
+        if self.projects.remove(name).is_some() || self.sealed_projects.remove(name).is_some() {
+            Ok(())
+        } else {
+            Err(VaultError::ProjectNotFound(name.to_string()))
+        }
+    }
+
+    /// Returns a copy of a project for keeping outside the vault, with its
+    /// secret values re-encrypted under `export_key`. The copy has no
+    /// passphrase and no attachments, whose blobs live outside the vault.
+    ///
+    /// The project must be unsealed and, if it has a passphrase, unlocked.
+    pub fn export_project(
+        &self,
+        name: &str,
+        encryption_key: &[u8; KEY_SIZE],
+        export_key: &[u8; KEY_SIZE],
+    ) -> Result<Project, VaultError> {
+        let value_key = self.value_key(name, encryption_key)?;
+        let mut project = self.project(name)?.clone();
+        reencrypt_secrets(&mut project, &value_key, export_key)?;
+        project.lock = None;
+        project.attachments.clear();
+        Ok(project)
+    }
+
+    /// Adds a project copied by `export_project` as `name`, re-encrypting
+    /// its secret values from `export_key` under `encryption_key`. Secrets
+    /// keep their timestamps and expiry, and aliases within the project
+    /// follow it to its new name.
+    ///
+    /// # Errors
+    /// `VaultError::ProjectAlreadyExists` if `name` is taken.
+    pub fn import_project(
+        &mut self,
+        name: &str,
+        mut project: Project,
+        export_key: &[u8; KEY_SIZE],
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<(), VaultError> {
+        if self.projects.contains_key(name) || self.sealed_projects.contains_key(name) {
+            return Err(VaultError::ProjectAlreadyExists(name.to_string()));
+        }
+
+        reencrypt_secrets(&mut project, export_key, encryption_key)?;
+        let old_name = std::mem::replace(&mut project.name, name.to_string());
+        for secret in project.all_secrets_mut() {
+            if let Some(target) = secret.alias.as_mut().filter(|t| t.project == old_name) {
+                target.project = name.to_string();
+            }
+        }
+        project.lock = None;
+
+        self.projects.insert(name.to_string(), project);
+        Ok(())
+    }
+
+    /// Removes a secret from a project.
+    pub fn remove_secret(&mut self, project: &str, key: &str) -> Result<(), VaultError> {
+        self.remove_secret_in(project, None, key)
+    }
+
+    /// Removes a secret from an environment of a project (`None` for the
+    /// default environment). A named environment left empty is removed.
+    pub fn remove_secret_in(
+        &mut self,
+        project: &str,
+        env: Option<&str>,
+        key: &str,
+    ) -> Result<(), VaultError> {
+        self.take_secret(project, env, key).map(drop)
+    }
+
+    /// Removes a secret like `remove_secret_in` and returns it.
+    fn take_secret(
+        &mut self,
+        project: &str,
+        env: Option<&str>,
+        key: &str,
+    ) -> Result<Secret, VaultError> {
+        let proj = self.project_mut(project)?;
+
+        let secrets = match env {
+            None => &mut proj.secrets,
+            Some(env) => proj
+                .environments
+                .get_mut(env)
+                .ok_or_else(|| VaultError::EnvironmentNotFound(env.to_string()))?,
+        };
+
+        let secret = secrets
+            .remove(key)
+            .ok_or_else(|| VaultError::SecretNotFound(key.to_string()))?;
+
+        if let Some(env) = env {
+            if proj.environments[env].is_empty() {
+                proj.environments.remove(env);
+            }
+        }
+        Ok(secret)
+    }
+
+    /// Removes the secrets of unsealed projects that expired `grace`
+    /// seconds or more before `now`, and returns them. Named environments
+    /// left empty are removed. Secrets of sealed projects are not looked at.
+    pub fn remove_expired(&mut self, now: u64, grace: u64) -> Vec<SecretRef> {
+        let cutoff = now.saturating_sub(grace);
+        let mut removed = Vec::new();
+
+        for (name, project) in &mut self.projects {
+            let mut emptied = Vec::new();
+            let environments = std::iter::once((None, &mut project.secrets)).chain(
+                project
+                    .environments
+                    .iter_mut()
+                    .map(|(env, secrets)| (Some(env.as_str()), secrets)),
+            );
+            for (env, secrets) in environments {
+                let before = secrets.len();
+                secrets.retain(|key, secret| {
+                    let expired = ttl::is_expired(secret.expires_at, cutoff);
+                    if expired {
+                        removed.push(SecretRef::new(name, env, key));
+                    }
+                    !expired
+                });
+                if let Some(env) = env {
+                    if secrets.is_empty() && before > 0 {
+                        emptied.push(env.to_string());
+                    }
+                }
+            }
+            for env in emptied {
+                project.environments.remove(&env);
+            }
+        }
+
+        removed.sort_by_key(|secret| secret.to_string());
+        removed
+    }
+
+    /// Removes a named environment and all its secrets from a project.
+    pub fn remove_environment(&mut self, project: &str, env: &str) -> Result<(), VaultError> {
+        let proj = self.project_mut(project)?;
+
+        if proj.environments.remove(env).is_some() {
+            Ok(())
+        } else {
+            Err(VaultError::EnvironmentNotFound(env.to_string()))
+        }
+    }
+
+    /// Moves a secret (or alias) to the trash and returns its trash id. A
+    /// named environment left empty is removed.
+    pub fn trash_secret(
+        &mut self,
+        project: &str,
+        env: Option<&str>,
+        key: &str,
+    ) -> Result<u32, VaultError> {
+        let secret = self.take_secret(project, env, key)?;
+        Ok(self.add_to_trash(TrashedItem::Secret {
+            location: SecretRef::new(project, env, key),
+            secret: Box::new(secret),
+        }))
+    }
+
+    /// Returns the keys in an environment of a project (`None` for the
+    /// default environment) that match the glob `pattern`, sorted; see
+    /// `pattern::matches`.
+    pub fn matching_keys(
+        &self,
+        project: &str,
+        env: Option<&str>,
+        pattern: &str,
+    ) -> Result<Vec<String>, VaultError> {
+        let mut keys: Vec<String> = self
+            .project(project)?
+            .secrets_in(env)?
+            .keys()
+            .filter(|key| pattern::matches(pattern, key))
+            .cloned()
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Moves every secret (or alias) in an environment of a project whose
+    /// key matches the glob `pattern` to the trash, each under its own
+    /// trash id. Returns the keys removed with their trash ids, sorted by
+    /// key. A named environment left empty is removed.
+    pub fn remove_matching(
+        &mut self,
+        project: &str,
+        env: Option<&str>,
+        pattern: &str,
+    ) -> Result<Vec<(String, u32)>, VaultError> {
+        let keys = self.matching_keys(project, env, pattern)?;
+        self.transaction(|vault| {
+            keys.into_iter()
+                .map(|key| {
+                    let id = vault.trash_secret(project, env, &key)?;
+                    Ok((key, id))
+                })
+                .collect()
+        })
+    }
+
+    /// Moves a named environment and all its secrets to the trash and
+    /// returns its trash id.
+    pub fn trash_environment(&mut self, project: &str, env: &str) -> Result<u32, VaultError> {
+        let secrets = self
+            .project_mut(project)?
+            .environments
+            .remove(env)
+            .ok_or_else(|| VaultError::EnvironmentNotFound(env.to_string()))?;
+        Ok(self.add_to_trash(TrashedItem::Environment {
+            project: project.to_string(),
+            env: env.to_string(),
+            secrets,
+        }))
+    }
+
+    /// Moves a project to the trash, sealed under its subkey, and returns
+    /// its trash id. Its attachment blobs are kept until it is purged.
+    pub fn trash_project(&mut self, name: &str, key: &[u8; KEY_SIZE]) -> Result<u32, VaultError> {
+        self.unseal_project(name, key)?;
+        let project = &self.projects[name];
+        let sealed = seal_project(name, project, key)?;
+        let blob_ids = project
+            .attachments
+            .values()
+            .map(|attachment| attachment.blob_id.clone())
+            .collect();
+
+        self.projects.remove(name);
+        self.passphrase_keys.remove(name);
+        Ok(self.add_to_trash(TrashedItem::Project {
+            name: name.to_string(),
+            sealed,
+            blob_ids,
+        }))
+    }
+
+    /// Adds an item to the trash, as the change `undo` reverts.
+    fn add_to_trash(&mut self, item: TrashedItem) -> u32 {
+        let now = ttl::current_timestamp();
+        let id = self.trash.iter().map(|entry| entry.id + 1).max().unwrap_or(1);
+        self.trash.push(TrashEntry {
+            id,
+            deleted_at: now,
+            item,
+        });
+        self.undo = Some(UndoRecord {
+            operation: Operation::Remove,
+            timestamp: now,
+            change: Change::Trash { id },
+        });
+        id
+    }
+
+    /// Returns the items in the trash, oldest first.
+    pub fn trash(&self) -> &[TrashEntry] {
+        &self.trash
+    }
+
+    /// Puts a trashed item back where it was removed from and returns it.
+    ///
+    /// The project of a trashed secret or environment must exist and be
+    /// unsealed. Nothing is overwritten: restoring fails if the secret,
+    /// environment or project has been created again since.
+    pub fn restore_from_trash(&mut self, id: u32) -> Result<TrashEntry, VaultError> {
+        let index = self
+            .trash
+            .iter()
+            .position(|entry| entry.id == id)
+            .ok_or(VaultError::TrashEntryNotFound(id))?;
+
+        let entry = self.trash.remove(index);
+        match self.put_back(&entry.item) {
+            Ok(()) => Ok(entry),
+            Err(e) => {
+                self.trash.insert(index, entry);
+                Err(e)
+            }
+        }
+    }
+
+    fn put_back(&mut self, item: &TrashedItem) -> Result<(), VaultError> {
+        match item {
+            TrashedItem::Secret { location, secret } => {
+                let proj = self.project_mut(&location.project)?;
+                let secrets = match &location.env {
+                    None => &mut proj.secrets,
+                    Some(env) => proj.environments.entry(env.clone()).or_default(),
+                };
+                if secrets.contains_key(&location.key) {
+                    return Err(VaultError::SecretAlreadyExists(location.key.clone()));
+                }
+                secrets.insert(location.key.clone(), secret.as_ref().clone());
+            }
+            TrashedItem::Environment {
+                project,
+                env,
+                secrets,
+            } => {
+                let proj = self.project_mut(project)?;
+                if proj.environments.contains_key(env) {
+                    return Err(VaultError::EnvironmentAlreadyExists(env.clone()));
+                }
+                proj.environments.insert(env.clone(), secrets.clone());
+            }
+            TrashedItem::Project { name, sealed, .. } => {
+                if self.projects.contains_key(name) || self.sealed_projects.contains_key(name) {
+                    return Err(VaultError::ProjectAlreadyExists(name.clone()));
+                }
+                self.sealed_projects.insert(name.clone(), sealed.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Permanently deletes the items trashed at or before `cutoff` and
+    /// returns them, so the caller can delete their attachment blobs.
+    pub fn purge_trash(&mut self, cutoff: u64) -> Vec<TrashEntry> {
+        let (purged, kept) = std::mem::take(&mut self.trash)
+            .into_iter()
+            .partition(|entry| entry.deleted_at <= cutoff);
+        self.trash = kept;
+        purged
+    }
+
+    /// Permanently deletes every item in the trash and returns them.
+    pub fn empty_trash(&mut self) -> Vec<TrashEntry> {
+        std::mem::take(&mut self.trash)
+    }
+
+    /// Runs `write`, which adds or changes the secret at `location`, and
+    /// remembers the secret's previous state so that `undo` can revert it.
+    pub fn track_write<T, E, F>(
+        &mut self,
+        operation: Operation,
+        location: SecretRef,
+        write: F,
+    ) -> Result<T, E>
+    where
+        F: FnOnce(&mut Vault) -> Result<T, E>,
+    {
+        let before = self.find_secret(&location).ok().cloned().map(Box::new);
+        let value = write(self)?;
+
+        if let Ok(after) = self.find_secret(&location) {
+            self.undo = Some(UndoRecord {
+                operation,
+                timestamp: ttl::current_timestamp(),
+                change: Change::Write {
+                    after: Box::new(after.clone()),
+                    location,
+                    before,
+                },
+            });
+        }
+        Ok(value)
+    }
+
+    /// Returns the change `undo` would revert, if any.
+    pub fn last_change(&self) -> Option<&UndoRecord> {
+        self.undo.as_ref()
+    }
+
+    /// Reverts the last tracked change and returns it: a written secret
+    /// gets its previous value back (or is removed if it was new), and a
+    /// removed item is restored from the trash.
+    ///
+    /// The project involved must be unsealed.
+    ///
+    /// # Errors
+    /// `VaultError::NothingToUndo` without a tracked change, and
+    /// `VaultError::UndoStale` if the secret has changed since or the item
+    /// is no longer in the trash.
+    pub fn undo(&mut self) -> Result<UndoRecord, VaultError> {
+        let record = self.undo.clone().ok_or(VaultError::NothingToUndo)?;
+
+        match &record.change {
+            Change::Write {
+                location,
+                before,
+                after,
+            } => {
+                let current = self.find_secret(location);
+                if let Err(VaultError::ProjectSealed(name)) = current {
+                    return Err(VaultError::ProjectSealed(name));
+                }
+                if !current.is_ok_and(|current| undo::same_secret(current, after)) {
+                    return Err(VaultError::UndoStale(location.to_string()));
+                }
+
+                match before {
+                    Some(before) => {
+                        let proj = self.project_mut(&location.project)?;
+                        let secrets = match &location.env {
+                            None => &mut proj.secrets,
+                            Some(env) => proj.environments.entry(env.clone()).or_default(),
+                        };
+                        secrets.insert(location.key.clone(), Secret::clone(before));
+                    }
+                    None => {
+                        self.take_secret(&location.project, location.env.as_deref(), &location.key)?;
+                    }
+                }
+            }
+            Change::Trash { id } => {
+                self.restore_from_trash(*id).map_err(|e| match e {
+                    VaultError::TrashEntryNotFound(id) => {
+                        VaultError::UndoStale(format!("trash entry {}", id))
+                    }
+                    e => e,
+                })?;
+            }
+        }
+
+        self.undo = None;
+        Ok(record)
+    }
+
+    /// Replaces the vault's contents with those of `snapshot`, an earlier
+    /// copy of this vault opened with the same data key.
+    ///
+    /// The access log, key slots and generation are kept: the log stays
+    /// append-only, credentials added or removed since still apply, and the
+    /// rolled-back vault saves over the current file. Projects come back
+    /// sealed, and the undo record is cleared.
+    pub fn restore_snapshot(&mut self, snapshot: Vault) {
+        let Vault {
+            projects,
+            sealed_projects,
+            ssh_identities,
+            ssh_servers,
+            ssh_server_groups,
+            kubeconfigs,
+            wireguard_configs,
+            origin_grants,
+            trash,
+            shards,
+            ..
+        } = snapshot;
+
+        self.projects = projects;
+        self.sealed_projects = sealed_projects;
+        self.ssh_identities = ssh_identities;
+        self.ssh_servers = ssh_servers;
+        self.ssh_server_groups = ssh_server_groups;
+        self.kubeconfigs = kubeconfigs;
+        self.wireguard_configs = wireguard_configs;
+        self.origin_grants = origin_grants;
+        self.trash = trash;
+        self.shards = shards;
+        self.unsealed_from.clear();
+        self.undo = None;
+        self.passphrase_keys.clear();
+    }
+
+    /// Applies a batch of mutations atomically.
+    ///
+    /// The closure operates on a staged copy of the vault. If it returns
+    /// `Ok`, the staged copy replaces this vault; if it returns `Err`, every
+    /// change it made is discarded and the vault is left untouched.
+    ///
+    /// ```
+    /// # use vx_core::{Vault, VaultError};
+    /// # let key = [0u8; 32];
+    /// let mut vault = Vault::new();
+    /// vault.init_project("app").unwrap();
+    ///
+    /// let result = vault.transaction(|tx| {
+    ///     tx.add_secret("app", "A", b"1", &key, None)?;
+    ///     tx.remove_secret("app", "MISSING") // fails, so "A" is not added
+    /// });
+    ///
+    /// assert!(result.is_err());
+    /// assert!(vault.projects["app"].secrets.is_empty());
+    /// ```
+    pub fn transaction<T, E, F>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Vault) -> Result<T, E>,
+    {
+        let mut staged = self.clone();
+        let value = f(&mut staged)?;
+        *self = staged;
+        Ok(value)
+    }
+
+    /// Appends an entry to the access log.
+    ///
+    /// Only names are recorded, never secret values. The entry is persisted
+    /// with the next save.
+    pub fn record(&mut self, operation: Operation, project: Option<&str>, name: Option<&str>) {
+        let prev = access_log::head(&self.access_log);
+        self.access_log.push(LogEntry::new(operation, project, name, &prev));
+    }
+
+    /// Verifies the access log's hash chain.
+    pub fn verify_access_log(&self) -> Result<ChainStatus, VaultError> {
+        access_log::verify_chain(&self.access_log)
+    }
+
+    /// Returns the access log, oldest entry first.
+    pub fn access_log(&self) -> &[LogEntry] {
+        &self.access_log
+    }
+
+    /// Returns the access log entries recorded since the vault was read or
+    /// last marked saved with `mark_saved`.
+    pub fn unsaved_log(&self) -> &[LogEntry] {
+        self.access_log.get(self.saved_log_len..).unwrap_or_default()
+    }
+
+    /// Sets where the secrets added or edited from now on are written from.
+    /// The provenance is not persisted; set it again after reading the
+    /// vault.
+    pub fn set_provenance(&mut self, provenance: Provenance) {
+        self.provenance = Some(provenance);
+    }
+
+    /// Marks the whole access log as written to the vault file.
+    pub fn mark_saved(&mut self) {
+        self.saved_log_len = self.access_log.len();
+    }
+
+    /// Returns whether the vault was read from a file in an older format,
+    /// which the next save rewrites in the current one.
+    pub fn needs_upgrade(&self) -> bool {
+        self.version < VAULT_VERSION
+    }
+
+    /// Returns the key slots that can unlock the vault.
+    pub fn key_slots(&self) -> &[KeySlot] {
+        &self.key_slots
+    }
+
+    /// Adds a key slot wrapping `data_key` under `credential` and returns its
+    /// id.
+    ///
+    /// `data_key` must be the key the vault was unlocked with; the slot is
+    /// persisted with the next save. Its key is derived with the costliest
+    /// parameters of any existing slot, or `KdfParams::for_this_platform()`
+    /// if that costs more, so a credential added later does not weaken a
+    /// vault created with `KdfParams::PARANOID`.
+    pub fn add_key_slot(
+        &mut self,
+        kind: SlotKind,
+        label: &str,
+        credential: &[u8],
+        data_key: &[u8; KEY_SIZE],
+    ) -> Result<u32, VaultError> {
+        let kdf = self
+            .key_slots
+            .iter()
+            .map(|slot| slot.kdf)
+            .fold(KdfParams::for_this_platform(), |strongest, kdf| {
+                if kdf.cost() > strongest.cost() {
+                    kdf
+                } else {
+                    strongest
+                }
+            });
+        self.add_key_slot_with_kdf(kind, label, credential, data_key, kdf)
+    }
+
+    /// Adds a key slot like `add_key_slot`, deriving its key with `kdf`.
+    pub fn add_key_slot_with_kdf(
+        &mut self,
+        kind: SlotKind,
+        label: &str,
+        credential: &[u8],
+        data_key: &[u8; KEY_SIZE],
+        kdf: KdfParams,
+    ) -> Result<u32, VaultError> {
+        if self.key_slots.len() >= MAX_KEY_SLOTS {
+            return Err(VaultError::KeySlotsFull(MAX_KEY_SLOTS));
+        }
+
+        let id = self.key_slots.iter().map(|slot| slot.id + 1).max().unwrap_or(0);
+        self.key_slots
+            .push(KeySlot::with_kdf(id, kind, label, credential, data_key, kdf)?);
+        Ok(id)
+    }
+
+    /// Re-wraps a key slot's data key with new Argon2id parameters, e.g.
+    /// ones picked by `crypto::calibrate_kdf`. The slot keeps its id, kind,
+    /// label and credential.
+    ///
+    /// # Errors
+    /// `VaultError::AuthenticationFailed` if `credential` does not unlock
+    /// the slot.
+    pub fn rewrap_key_slot(
+        &mut self,
+        id: u32,
+        credential: &[u8],
+        kdf: KdfParams,
+    ) -> Result<(), VaultError> {
+        let slot = self
+            .key_slots
+            .iter_mut()
+            .find(|slot| slot.id == id)
+            .ok_or(VaultError::KeySlotNotFound(id))?;
+        let data_key = slot
+            .unwrap_key(credential)
+            .ok_or(VaultError::AuthenticationFailed)?;
+
+        let mut rewrapped =
+            KeySlot::with_kdf(id, slot.kind, &slot.label, credential, &data_key, kdf)?;
+        rewrapped.created_at = slot.created_at;
+        *slot = rewrapped;
+        Ok(())
+    }
+
+    /// Removes a key slot. The last remaining slot cannot be removed.
+    pub fn remove_key_slot(&mut self, id: u32) -> Result<KeySlot, VaultError> {
+        let index = self
+            .key_slots
+            .iter()
+            .position(|slot| slot.id == id)
+            .ok_or(VaultError::KeySlotNotFound(id))?;
+
+        if self.key_slots.len() == 1 {
+            return Err(VaultError::LastKeySlot);
+        }
+
+        Ok(self.key_slots.remove(index))
+    }
+
+    /// Returns the emergency access grants.
+    pub fn emergency_grants(&self) -> &[EmergencyGrant] {
+        &self.emergency_grants
+    }
+
+    /// Adds an emergency access grant wrapping `data_key` for `recipient`,
+    /// usable `wait` seconds after the recipient requests it, and returns
+    /// its id. See `emergency`.
+    pub fn add_emergency_grant(
+        &mut self,
+        label: &str,
+        recipient: &str,
+        wait: u64,
+        data_key: &[u8; KEY_SIZE],
+    ) -> Result<u32, VaultError> {
+        let id = self
+            .emergency_grants
+            .iter()
+            .map(|grant| grant.id + 1)
+            .max()
+            .unwrap_or(1);
+        self.emergency_grants
+            .push(EmergencyGrant::new(id, label, recipient, wait, data_key)?);
+        Ok(id)
+    }
+
+    /// Removes an emergency access grant.
+    pub fn remove_emergency_grant(&mut self, id: u32) -> Result<EmergencyGrant, VaultError> {
+        let index = self
+            .emergency_grants
+            .iter()
+            .position(|grant| grant.id == id)
+            .ok_or(VaultError::EmergencyGrantNotFound(id))?;
+        Ok(self.emergency_grants.remove(index))
+    }
+
+    /// Vetoes every request on an emergency access grant made until `now`.
+    /// The grant stays in place for later requests.
+    pub fn veto_emergency_grant(&mut self, id: u32, now: u64) -> Result<(), VaultError> {
+        let grant = self
+            .emergency_grants
+            .iter_mut()
+            .find(|grant| grant.id == id)
+            .ok_or(VaultError::EmergencyGrantNotFound(id))?;
+        grant.vetoed_at = Some(now);
+        Ok(())
+    }
+
+    /// Records on an emergency access grant that its request made at
+    /// `requested_at` has been seen, so the request file cannot later be
+    /// backdated. Returns whether the grant changed; it is kept with the
+    /// next save.
+    pub fn note_emergency_request(&mut self, id: u32, requested_at: u64) -> bool {
+        let Some(grant) = self.emergency_grants.iter_mut().find(|grant| grant.id == id) else {
+            return false;
+        };
+        if grant.requested_at.is_some_and(|seen| seen >= requested_at) {
+            return false;
+        }
+        grant.requested_at = Some(requested_at);
+        true
+    }
+
+    /// Re-encrypts every secret value, SSH private key, kubeconfig and
+    /// WireGuard config from `old_key` to `new_key`.
+    ///
+    /// All projects must be unsealed, and projects with a passphrase
+    /// unlocked. Attachment blobs live outside the vault and are not touched;
+    /// their metadata still refers to `old_key`.
+    pub fn reencrypt(
+        &mut self,
+        old_key: &[u8; KEY_SIZE],
+        new_key: &[u8; KEY_SIZE],
+    ) -> Result<(), VaultError> {
+        if let Some(name) = self.sealed_projects.keys().next() {
+            return Err(VaultError::ProjectSealed(name.clone()));
+        }
+
+        let names: Vec<String> = self.projects.keys().cloned().collect();
+        for name in names {
+            let old_value_key = self.value_key(&name, old_key)?;
+            let new_value_key = self.value_key(&name, new_key)?;
+
+            let project = self.projects.get_mut(&name).unwrap();
+            reencrypt_secrets(project, &old_value_key, &new_value_key)?;
+            if let Some(lock) = &mut project.lock {
+                let (verifier, nonce) =
+                    reencrypt_value(&lock.verifier, lock.nonce, &old_value_key, &new_value_key)?;
+                lock.verifier = verifier;
+                lock.nonce = nonce;
+            }
+        }
+
+        for identity in self.ssh_identities.values_mut() {
+            let (ciphertext, nonce) = reencrypt_value(
+                &identity.encrypted_private_key,
+                identity.nonce,
+                old_key,
+                new_key,
+            )?;
+            identity.encrypted_private_key = ciphertext;
+            identity.nonce = nonce;
+        }
+
+        for kubeconfig in self.kubeconfigs.values_mut() {
+            let (ciphertext, nonce) = reencrypt_value(
+                &kubeconfig.encrypted_config,
+                kubeconfig.nonce,
+                old_key,
+                new_key,
+            )?;
+            kubeconfig.encrypted_config = ciphertext;
+            kubeconfig.nonce = nonce;
+        }
+
+        for wireguard in self.wireguard_configs.values_mut() {
+            let (ciphertext, nonce) = reencrypt_value(
+                &wireguard.encrypted_config,
+                wireguard.nonce,
+                old_key,
+                new_key,
+            )?;
+            wireguard.encrypted_config = ciphertext;
+            wireguard.nonce = nonce;
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether a project exists but has not been unsealed.
+    pub fn is_sealed(&self, name: &str) -> bool {
+        self.sealed_projects.contains_key(name)
+    }
+
+    /// Returns the names of all projects, sealed or not.
+    pub fn project_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .projects
+            .keys()
+            .chain(self.sealed_projects.keys())
+            .chain(self.shards.keys())
+            .map(String::as_str)
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Decrypts a sealed project with its subkey so it can be used.
+    ///
+    /// Does nothing if the project is already unsealed.
+    pub fn unseal_project(&mut self, name: &str, key: &[u8; KEY_SIZE]) -> Result<(), VaultError> {
+        if self.projects.contains_key(name) {
+            return Ok(());
+        }
+        if self.shards.contains_key(name) {
+            return Err(VaultError::MissingShard(name.to_string()));
+        }
+
+        let sealed = self
+            .sealed_projects
+            .get(name)
+            .ok_or_else(|| VaultError::ProjectNotFound(name.to_string()))?;
+
+        let subkey = project_subkey(key, name);
+        let encrypted = EncryptedData {
+            ciphertext: sealed.ciphertext.clone(),
+            nonce: sealed.nonce,
+        };
+        let plaintext =
+            crypto::decrypt(&encrypted, &subkey).map_err(|_| VaultError::CorruptedVault)?;
+
+        let mut project: Project = ciborium::de::from_reader(plaintext.expose())
+            .map_err(|e| VaultError::SerializationError(e.to_string()))?;
+        project.unshare_values()?;
+
+        // Serialized again rather than hashed as read, as map order differs
+        // between the decoded project and the one it was encoded from
+        let digest = sealing_digest(&subkey, &project_plaintext(&project)?);
+        let sealed = self.sealed_projects.remove(name).unwrap();
+        self.unsealed_from.insert(name.to_string(), (sealed, digest));
+        self.projects.insert(name.to_string(), project);
+        tracing::trace!(project = name, "unsealed project");
+        Ok(())
+    }
+
+    /// Unseals every sealed project.
+    pub fn unseal_all(&mut self, key: &[u8; KEY_SIZE]) -> Result<(), VaultError> {
+        let names: Vec<String> = self.sealed_projects.keys().cloned().collect();
+        for name in names {
+            self.unseal_project(&name, key)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the ids of the shards the vault file refers to that have not
+    /// been attached yet; see `attach_shards`.
+    pub fn pending_shards(&self) -> Vec<String> {
+        self.shards.values().map(ShardRef::id).collect()
+    }
+
+    /// Reads the projects of a sharded vault file (see `save_vault_sharded`)
+    /// from their shards, which `read` returns by id. The projects stay
+    /// sealed until unsealed as usual.
+    ///
+    /// # Errors
+    /// - `VaultError::MissingShard` if `read` has no shard for a project
+    /// - `VaultError::CorruptedVault` if a shard is not the one the vault
+    ///   file lists
+    pub fn attach_shards<F>(&mut self, mut read: F) -> Result<(), VaultError>
+    where
+        F: FnMut(&str) -> Option<Vec<u8>>,
+    {
+        let names: Vec<String> = self.shards.keys().cloned().collect();
+        for name in names {
+            let reference = &self.shards[&name];
+            let data = read(&reference.id()).ok_or_else(|| VaultError::MissingShard(name.clone()))?;
+            if ShardRef::for_shard(&data).digest != reference.digest {
+                tracing::debug!(project = %name, "shard does not match its digest");
+                return Err(VaultError::CorruptedVault);
+            }
+
+            self.sealed_projects
+                .insert(name.clone(), SealedProject::from_shard(&data)?);
+            self.shards.remove(&name);
+        }
+        Ok(())
+    }
+
+    /// Returns a copy of the vault with every open project sealed under its
+    /// subkey. Projects that were never unsealed are carried over unchanged.
+    fn sealed(&self, key: &[u8; KEY_SIZE]) -> Result<Vault, VaultError> {
+        let mut sealed_projects = self.sealed_projects.clone();
+
+        for (name, project) in &self.projects {
+            let plaintext = project_plaintext(project)?;
+            let digest = sealing_digest(&project_subkey(key, name), &plaintext);
+            let sealed = match self.unsealed_from.get(name) {
+                Some((sealed, read)) if *read == digest => sealed.clone(),
+                _ => seal_plaintext(name, &plaintext, key)?,
+            };
+            sealed_projects.insert(name.clone(), sealed);
+        }
+
+        Ok(Vault {
+            version: self.version,
+            generation: self.generation,
+            read_only: self.read_only,
+            compressed: self.compressed,
+            sharded: self.sharded,
+            projects: HashMap::new(),
+            sealed_projects,
+            shards: self.shards.clone(),
+            unsealed_from: HashMap::new(),
+            ssh_identities: self.ssh_identities.clone(),
+            ssh_servers: self.ssh_servers.clone(),
+            ssh_server_groups: self.ssh_server_groups.clone(),
+            kubeconfigs: self.kubeconfigs.clone(),
+            wireguard_configs: self.wireguard_configs.clone(),
+            origin_grants: self.origin_grants.clone(),
+            access_log: self.access_log.clone(),
+            trash: self.trash.clone(),
+            undo: self.undo.clone(),
+            key_slots: self.key_slots.clone(),
+            emergency_grants: self.emergency_grants.clone(),
+            passphrase_keys: HashMap::new(),
+            saved_log_len: self.saved_log_len,
+            provenance: self.provenance.clone(),
+        })
+    }
+
+    /// Returns the key secret values in a project are encrypted under: the
+    /// master key, or for projects with a passphrase, the layered key.
+    fn value_key(
+        &self,
+        name: &str,
+        encryption_key: &[u8; KEY_SIZE],
+    ) -> Result<Zeroizing<[u8; KEY_SIZE]>, VaultError> {
+        if self.project(name)?.lock.is_none() {
+            return Ok(Zeroizing::new(*encryption_key));
+        }
+
+        let passphrase_key = self
+            .passphrase_keys
+            .get(name)
+            .ok_or_else(|| VaultError::ProjectLocked(name.to_string()))?;
+        Ok(layered_key(encryption_key, passphrase_key, name))
+    }
+
+    fn find_secret(&self, location: &SecretRef) -> Result<&Secret, VaultError> {
+        self.project(&location.project)?
+            .secrets_in(location.env.as_deref())?
+            .get(&location.key)
+            .ok_or_else(|| VaultError::SecretNotFound(location.key.clone()))
+    }
+
+    fn project(&self, name: &str) -> Result<&Project, VaultError> {
+        self.projects.get(name).ok_or_else(|| self.missing_project(name))
+    }
+
+    fn project_mut(&mut self, name: &str) -> Result<&mut Project, VaultError> {
+        if !self.projects.contains_key(name) {
+            return Err(self.missing_project(name));
+        }
+        Ok(self.projects.get_mut(name).unwrap())
+    }
+
+    fn missing_project(&self, name: &str) -> VaultError {
+        if self.is_sealed(name) {
+            VaultError::ProjectSealed(name.to_string())
+        } else {
+            VaultError::ProjectNotFound(name.to_string())
+        }
+    }
+}
+
+/// Decrypts a value under `old_key` and encrypts it again under `new_key`.
+fn reencrypt_value(
+    ciphertext: &[u8],
+    nonce: [u8; NONCE_SIZE],
+    old_key: &[u8; KEY_SIZE],
+    new_key: &[u8; KEY_SIZE],
+) -> Result<(Vec<u8>, [u8; NONCE_SIZE]), VaultError> {
+    let encrypted = EncryptedData {
+        ciphertext: ciphertext.to_vec(),
+        nonce,
+    };
+    let plaintext = crypto::decrypt(&encrypted, old_key)?;
+    let encrypted = crypto::encrypt(&plaintext, new_key)?;
+    Ok((encrypted.ciphertext, encrypted.nonce))
+}
+
+/// Re-encrypts every secret value in a project (all environments) from
+/// `old_key` to `new_key`. Aliases hold no value and are skipped.
+fn reencrypt_secrets(
+    project: &mut Project,
+    old_key: &[u8; KEY_SIZE],
+    new_key: &[u8; KEY_SIZE],
+) -> Result<(), VaultError> {
+    reencrypt_all(project.all_secrets_mut(), old_key, new_key)
+}
+
+/// Re-encrypts secret values from `old_key` to `new_key`, recomputing
+/// their value hashes. Secrets that shared a ciphertext still share one
+/// afterwards.
+fn reencrypt_all<'a>(
+    secrets: impl Iterator<Item = &'a mut Secret>,
+    old_key: &[u8; KEY_SIZE],
+    new_key: &[u8; KEY_SIZE],
+) -> Result<(), VaultError> {
+    type Reencrypted = (Vec<u8>, [u8; NONCE_SIZE], Option<String>);
+    let mut done: HashMap<([u8; NONCE_SIZE], Vec<u8>), Reencrypted> = HashMap::new();
+
+    for secret in secrets.filter(|s| s.alias.is_none()) {
+        let old = (secret.nonce, secret.encrypted_value.clone());
+        let (ciphertext, nonce, hash) = match done.get(&old) {
+            Some(reencrypted) => reencrypted.clone(),
+            None => {
+                let encrypted = EncryptedData {
+                    ciphertext: old.1.clone(),
+                    nonce: old.0,
+                };
+                let plaintext = crypto::decrypt(&encrypted, old_key)?;
+                let encrypted = crypto::encrypt(&plaintext, new_key)?;
+                let reencrypted = (
+                    encrypted.ciphertext,
+                    encrypted.nonce,
+                    value_hash(&plaintext, new_key),
+                );
+                done.insert(old, reencrypted.clone());
+                reencrypted
+            }
+        };
+        secret.encrypted_value = ciphertext;
+        secret.nonce = nonce;
+        secret.value_hash = hash;
+    }
+    Ok(())
+}
+
+/// Returns when a secret given `ttl` seconds at `now` expires, or never if
+/// `ttl` is `None`.
+fn expiry(key: &str, ttl: Option<u64>, now: u64) -> Result<Option<u64>, VaultError> {
+    ttl.map(|ttl| {
+        ttl::calculate_expiry(ttl, now).ok_or_else(|| VaultError::TtlTooLong(key.to_string()))
+    })
+    .transpose()
+}
+
+/// Returns the keyed hash identifying a value in a project whose values
+/// are encrypted under `key`, or `None` for values too small to share.
+///
+/// The hash is an HMAC under a key derived from `key`, so equal values can
+/// only be recognised by someone who can decrypt them anyway.
+fn value_hash(value: &[u8], key: &[u8; KEY_SIZE]) -> Option<String> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    if value.len() < SHARED_VALUE_MIN_SIZE {
+        return None;
+    }
+    let hash_key = crypto::derive_subkey(key, VALUE_HASH_CONTEXT);
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(hash_key.as_ref())
+        .expect("HMAC accepts keys of any length");
+    mac.update(value);
+    Some(STANDARD.encode(mac.finalize().into_bytes()))
+}
+
+/// Derives the key secret values of a passphrase-protected project are
+/// encrypted under.
+fn layered_key(
+    key: &[u8; KEY_SIZE],
+    passphrase_key: &[u8; KEY_SIZE],
+    name: &str,
+) -> Zeroizing<[u8; KEY_SIZE]> {
+    let mut context = b"vaultx project lock:".to_vec();
+    context.extend_from_slice(name.as_bytes());
+    crypto::combine_keys(key, passphrase_key, &context)
+}
+
+/// Returns the associated data an attachment blob is bound to: its project
+/// and name, so a blob swapped for another attachment's fails to open.
+fn attachment_aad(project: &str, name: &str) -> Vec<u8> {
+    [b"vx-attachment\0", project.as_bytes(), b"\0", name.as_bytes()].concat()
+}
+
+/// Encrypts a project under the subkey of `name`.
+fn seal_project(
+    name: &str,
+    project: &Project,
+    key: &[u8; KEY_SIZE],
+) -> Result<SealedProject, VaultError> {
+    seal_plaintext(name, &project_plaintext(project)?, key)
+}
+
+/// Serializes a project as it is sealed, with shared values stored once.
+fn project_plaintext(project: &Project) -> Result<Zeroizing<Vec<u8>>, VaultError> {
+    let mut project = project.clone();
+    project.share_values();
+
+    let mut plaintext = Zeroizing::new(Vec::new());
+    ciborium::ser::into_writer(&project, &mut *plaintext)
+        .map_err(|e| VaultError::SerializationError(e.to_string()))?;
+    Ok(plaintext)
+}
+
+/// Encrypts a serialized project under the subkey of `name`.
+fn seal_plaintext(
+    name: &str,
+    plaintext: &[u8],
+    key: &[u8; KEY_SIZE],
+) -> Result<SealedProject, VaultError> {
+    let encrypted = crypto::encrypt(plaintext, &project_subkey(key, name))?;
+    Ok(SealedProject {
+        ciphertext: encrypted.ciphertext,
+        nonce: encrypted.nonce,
+    })
+}
+
+/// Digest of a project's plaintext and the subkey it is sealed under, so
+/// that a sealed form is only reused for the same contents under the same
+/// key: after `Vault::reencrypt`, the old ciphertext would not open.
+fn sealing_digest(subkey: &[u8; KEY_SIZE], plaintext: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(subkey);
+    hasher.update(plaintext);
+    hasher.finalize().into()
+}
+
+/// Derives the subkey a project is sealed under.
+fn project_subkey(key: &[u8; KEY_SIZE], name: &str) -> Zeroizing<[u8; KEY_SIZE]> {
+    let mut context = b"vaultx project:".to_vec();
+    context.extend_from_slice(name.as_bytes());
+    crypto::derive_subkey(key, &context)
+}
+
+impl Default for Vault {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Creates an empty vault with a fresh random data key and a single password
+/// slot.
+///
+/// Returns the vault together with its data key.
+pub fn create_vault(password: &[u8]) -> Result<(Vault, Zeroizing<[u8; KEY_SIZE]>), VaultError> {
+    create_vault_with_kdf(password, KdfParams::for_this_platform())
+}
+
+/// Creates a vault like `create_vault`, deriving the password slot's key
+/// with `kdf` (e.g. `KdfParams::BROWSER`).
+pub fn create_vault_with_kdf(
+    password: &[u8],
+    kdf: KdfParams,
+) -> Result<(Vault, Zeroizing<[u8; KEY_SIZE]>), VaultError> {
+    let key = crypto::generate_key();
+    let mut vault = Vault::new();
+    vault.add_key_slot_with_kdf(SlotKind::Password, DEFAULT_SLOT_LABEL, password, &key, kdf)?;
+    Ok((vault, key))
+}
+
+/// Serializes and encrypts a vault for storage under its data key.
+///
+/// # File Format
+/// ```text
+/// +-----------------+-------------------+-------------------------+
+/// | Header (20B)    | Key Slots         | Checksum (40B)  | Encrypted Payload       |
+/// +-----------------+-------------------+-----------------+-------------------------+
+/// | Magic: "VX01"   | CBOR slot table   | Payload len u64 | Nonce (12B)             |
+/// | Version: u32    | (salt + wrapped   | SHA-256 of      | AES-256-GCM encrypted   |
+/// | Generation: u32 |  data key each)   | nonce and       | CBOR vault data (v5)    |
+/// | Flags: u32      |                   | ciphertext      | + Auth Tag (16B)        |
+/// | Slots len: u32  |                   |                 |                         |
+/// +-----------------+-------------------+-----------------+-------------------------+
+/// ```
+/// The header and slot table are authenticated as associated data, so they
+/// cannot be modified without failing decryption.
+///
+/// The checksum is not secret and not authenticated: it lets a truncated or
+/// damaged file be reported before any key derivation, while tampering is
+/// still caught by the payload's authentication tag.
+///
+/// Projects left sealed since loading are written back unchanged, so `key`
+/// must be the data key the vault was opened with.
+///
+/// Read-only vaults are rejected with `VaultError::ReadOnly`.
+pub fn save_vault_with_key(vault: &Vault, key: &[u8; KEY_SIZE]) -> Result<Vec<u8>, VaultError> {
+    if vault.read_only {
+        return Err(VaultError::ReadOnly);
+    }
+
+    write_vault(vault, key)
+}
+
+/// Saves a vault like `save_vault_with_key`, with each project in a shard
+/// of its own instead of in the vault file. Returns the vault file and the
+/// shards, as `(id, shard)` pairs, to be stored as `<id>.vxp` next to it.
+///
+/// A project left unchanged since it was read keeps its shard, byte for
+/// byte, so only the shards of changed projects need to be written. The
+/// vault file authenticates every shard; shards it no longer lists can be
+/// deleted once it is written.
+pub fn save_vault_sharded(
+    vault: &Vault,
+    key: &[u8; KEY_SIZE],
+) -> Result<(Vec<u8>, Vec<Shard>), VaultError> {
+    if vault.read_only {
+        return Err(VaultError::ReadOnly);
+    }
+
+    write_vault_parts(vault, key, true)
+}
+
+/// Convenience function: saves a vault, unlocking its data key with
+/// `password`.
+///
+/// A vault without key slots (e.g. from `Vault::new`) gets a fresh data key
+/// and a single password slot.
+pub fn save_vault(vault: &Vault, password: &[u8]) -> Result<Vec<u8>, VaultError> {
+    if !vault.key_slots.is_empty() {
+        let key = keyslot::unlock(&vault.key_slots, password)?;
+        return save_vault_with_key(vault, &key);
+    }
+
+    // Sealed projects are bound to a key we do not have
+    if let Some(name) = vault.sealed_projects.keys().next() {
+        return Err(VaultError::ProjectSealed(name.clone()));
+    }
+
+    let key = crypto::generate_key();
+    let mut vault = vault.clone();
+    vault.add_key_slot(SlotKind::Password, DEFAULT_SLOT_LABEL, password, &key)?;
+    save_vault_with_key(&vault, &key)
+}
+
+/// Exports a read-only copy of a vault for distribution to consumers such as
+/// CI agents.
+///
+/// The copy is re-encrypted under a fresh data key with a single slot for
+/// `password`, and flagged read-only in both the header and the encrypted
+/// payload. Attachments, the access log, the trash, the undo record and
+/// browser origin grants are not included. All projects must be unsealed.
+pub fn export_read_only(
+    vault: &Vault,
+    key: &[u8; KEY_SIZE],
+    password: &[u8],
+) -> Result<Vec<u8>, VaultError> {
+    let export_key = crypto::generate_key();
+
+    let mut export = vault.clone();
+    export.reencrypt(key, &export_key)?;
+    for project in export.projects.values_mut() {
+        project.attachments.clear();
+    }
+    export.access_log.clear();
+    export.trash.clear();
+    export.undo = None;
+    export.origin_grants.clear();
+    export.key_slots.clear();
+    export.emergency_grants.clear();
+    export.add_key_slot(SlotKind::Password, DEFAULT_SLOT_LABEL, password, &export_key)?;
+    export.generation = 0;
+    export.read_only = true;
+
+    write_vault(&export, &export_key)
+}
+
+/// Seals, serializes and encrypts a vault into a single self-contained
+/// file in the on-disk format.
+fn write_vault(vault: &Vault, key: &[u8; KEY_SIZE]) -> Result<Vec<u8>, VaultError> {
+    let (data, _) = write_vault_parts(vault, key, false)?;
+    Ok(data)
+}
+
+/// Seals, serializes and encrypts a vault into the on-disk format. With
+/// `sharded`, the sealed projects are returned as shards, by id, instead of
+/// being written into the payload.
+fn write_vault_parts(
+    vault: &Vault,
+    key: &[u8; KEY_SIZE],
+    sharded: bool,
+) -> Result<(Vec<u8>, Vec<Shard>), VaultError> {
+    if vault.key_slots.is_empty() {
+        return Err(VaultError::InvalidFormat("Vault has no key slots".to_string()));
+    }
+    // Without its shard the project would be dropped from the file
+    if let Some(name) = vault.shards.keys().next() {
+        return Err(VaultError::MissingShard(name.clone()));
+    }
+
+    let mut flags = if vault.read_only { FLAG_READ_ONLY } else { 0 };
+
+    // Seal each project under its subkey, then serialize the vault payload
+    let mut sealed = vault.sealed(key)?;
+    let mut shards = Vec::new();
+    if sharded {
+        flags |= FLAG_SHARDED;
+        for (name, project) in sealed.sealed_projects.drain() {
+            let shard = project.to_shard();
+            let reference = ShardRef::for_shard(&shard);
+            shards.push((reference.id(), shard));
+            sealed.shards.insert(name, reference);
+        }
+    }
+    let mut payload = encode_payload(&sealed, PayloadFormat::Cbor)?;
+    if vault.compressed {
+        flags |= FLAG_COMPRESSED;
+        payload = compress_payload(&payload);
+    }
+
+    let header = VaultHeader {
+        key_slots: vault.key_slots.clone(),
+        emergency_grants: vault.emergency_grants.clone(),
+    };
+    let mut slot_table = Vec::new();
+    ciborium::ser::into_writer(&header, &mut slot_table)
+        .map_err(|e| VaultError::SerializationError(e.to_string()))?;
+
+    // Header and slot table
+    let mut output = Vec::with_capacity(
+        SLOT_TABLE_OFFSET + slot_table.len() + CHECKSUM_BLOCK_SIZE + payload.len(),
+    );
+    output.extend_from_slice(VAULT_MAGIC);
+    output.extend_from_slice(&VAULT_VERSION.to_le_bytes());
+    output.extend_from_slice(&vault.generation.to_le_bytes());
+    output.extend_from_slice(&flags.to_le_bytes());
+    output.extend_from_slice(&(slot_table.len() as u32).to_le_bytes());
+    output.extend_from_slice(&slot_table);
+
+    // Encrypt the payload, authenticating everything written so far
+    let encrypted = crypto::encrypt_with_aad(&payload, key, &output)?;
+
+    // Checksum of the encrypted payload, then Nonce + Ciphertext
+    let body_len = (NONCE_SIZE + encrypted.ciphertext.len()) as u64;
+    output.extend_from_slice(&body_len.to_le_bytes());
+    output.extend_from_slice(&payload_checksum(&encrypted.nonce, &encrypted.ciphertext));
+    output.extend_from_slice(&encrypted.nonce);
+    output.extend_from_slice(&encrypted.ciphertext);
+
+    tracing::debug!(
+        generation = vault.generation,
+        bytes = output.len(),
+        shards = shards.len(),
+        "encrypted vault"
+    );
+    Ok((output, shards))
+}
+
+/// Recovers a vault's data key from any credential that matches one of its
+/// key slots.
+///
+/// For vaults older than format 4 the key is derived from the password and
+/// the file's salt; it is not verified until the vault is opened.
+pub fn unlock_key(data: &[u8], credential: &[u8]) -> Result<Zeroizing<[u8; KEY_SIZE]>, VaultError> {
+    unlock_key_with_progress(data, credential, |_| {})
+}
+
+/// Recovers the data key like `unlock_key`, calling `progress` with the
+/// fraction of work done (0.0 to 1.0) before the first key derivation and
+/// after each one.
+///
+/// Each derivation runs Argon2 to completion, so progress advances once per
+/// key slot tried. The function is synchronous; in a browser, run it in a
+/// Web Worker and post the progress back to the page.
+pub fn unlock_key_with_progress<F>(
+    data: &[u8],
+    credential: &[u8],
+    mut progress: F,
+) -> Result<Zeroizing<[u8; KEY_SIZE]>, VaultError>
+where
+    F: FnMut(f64),
+{
+    let parts = split_file(data)?;
+    progress(0.0);
+
+    if let Some(salt) = parts.salt {
+        tracing::debug!(version = parts.version, "deriving key from pre-slot vault salt");
+        let key = crypto::derive_key(credential, &salt)?;
+        progress(1.0);
+        return Ok(key);
+    }
+
+    let total = parts.key_slots.len();
+    for (tried, slot) in parts.key_slots.iter().enumerate() {
+        tracing::trace!(slot = slot.id, kind = %slot.kind, "trying key slot");
+        if let Some(key) = slot.unwrap_key(credential) {
+            tracing::debug!(slot = slot.id, kind = %slot.kind, "unlocked key slot");
+            progress(1.0);
+            return Ok(key);
+        }
+        progress((tried + 1) as f64 / total as f64);
+    }
+
+    tracing::debug!(slots = total, "no key slot matched the credential");
+    Err(VaultError::AuthenticationFailed)
+}
+
+/// Unlocks and decrypts a vault with a credential, leaving projects sealed.
+///
+/// Vaults older than format 4 are given a password slot wrapping their
+/// existing key, so they are upgraded on the next save without re-encrypting
+/// anything.
+pub fn unlock_vault(
+    data: &[u8],
+    credential: &[u8],
+) -> Result<(Vault, Zeroizing<[u8; KEY_SIZE]>), VaultError> {
+    unlock_vault_with_progress(data, credential, |_| {})
+}
+
+/// Unlocks a vault like `unlock_vault`, reporting progress as
+/// `unlock_key_with_progress` does.
+pub fn unlock_vault_with_progress<F>(
+    data: &[u8],
+    credential: &[u8],
+    progress: F,
+) -> Result<(Vault, Zeroizing<[u8; KEY_SIZE]>), VaultError>
+where
+    F: FnMut(f64),
+{
+    let key = unlock_key_with_progress(data, credential, progress)?;
+    let vault = read_vault(
+        data,
+        &Upgrade {
+            key: &key,
+            credential: Some(credential),
+        },
+    )?;
+
+    Ok((vault, key))
+}
+
+/// Loads and decrypts a vault from storage, unsealing every project.
+pub fn load_vault(data: &[u8], password: &[u8]) -> Result<Vault, VaultError> {
+    let (mut vault, key) = unlock_vault(data, password)?;
+    vault.unseal_all(&key)?;
+    Ok(vault)
+}
+
+/// Loads a vault with its data key, unsealing every project.
+pub fn load_vault_with_key(data: &[u8], key: &[u8; KEY_SIZE]) -> Result<Vault, VaultError> {
+    let mut vault = open_vault(data, key)?;
+    vault.unseal_all(key)?;
+    Ok(vault)
+}
+
+/// Checks that a vault file is complete and undamaged without decrypting
+/// it, so a bad file can be reported before asking for a password.
+///
+/// Files older than format 5 carry no checksum and are only checked for a
+/// readable header.
+pub fn verify_file(data: &[u8]) -> Result<(), VaultError> {
+    split_file(data).map(|_| ())
+}
+
+/// Reads the generation counter from a vault file header without decrypting.
+///
+/// Compare it with `Vault::generation` before saving to detect a write by
+/// another process since the vault was loaded.
+pub fn read_generation(data: &[u8]) -> Result<u32, VaultError> {
+    if data.len() < HEADER_SIZE || &data[0..4] != VAULT_MAGIC {
+        return Err(VaultError::CorruptedVault);
+    }
+    Ok(u32::from_le_bytes(data[8..12].try_into().unwrap()))
+}
+
+/// Reads the format version from a vault file header without decrypting.
+pub fn read_version(data: &[u8]) -> Result<u32, VaultError> {
+    if data.len() < HEADER_SIZE || &data[0..4] != VAULT_MAGIC {
+        return Err(VaultError::CorruptedVault);
+    }
+    Ok(u32::from_le_bytes(data[4..8].try_into().unwrap()))
+}
+
+/// Reads the header flags (e.g. `FLAG_READ_ONLY`) from a vault file without
+/// decrypting.
+pub fn read_flags(data: &[u8]) -> Result<u32, VaultError> {
+    if data.len() < HEADER_SIZE || &data[0..4] != VAULT_MAGIC {
+        return Err(VaultError::CorruptedVault);
+    }
+    Ok(u32::from_le_bytes(data[12..16].try_into().unwrap()))
+}
+
+/// Reads the emergency access grants from a vault file header without
+/// decrypting, so a recipient can find the grant made for them.
+pub fn read_emergency_grants(data: &[u8]) -> Result<Vec<EmergencyGrant>, VaultError> {
+    Ok(split_file(data)?.emergency_grants)
+}
+
+/// A vault file split into its parts.
+struct FileParts<'a> {
+    version: u32,
+    /// Key slot table (format 4+); empty for older files
+    key_slots: Vec<KeySlot>,
+    /// Emergency access grants (format 4+); empty for older files
+    emergency_grants: Vec<EmergencyGrant>,
+    /// Argon2 salt of files older than format 4
+    salt: Option<[u8; SALT_SIZE]>,
+    /// Bytes authenticated alongside the payload
+    aad: &'a [u8],
+    nonce: [u8; NONCE_SIZE],
+    ciphertext: &'a [u8],
+}
+
+/// Splits a vault file into header fields and encrypted payload.
+fn split_file(data: &[u8]) -> Result<FileParts<'_>, VaultError> {
+    if data.len() < HEADER_SIZE {
+        return Err(VaultError::CorruptedVault);
+    }
+
+    // Verify magic
+    if &data[0..4] != VAULT_MAGIC {
+        return Err(VaultError::InvalidFormat("Invalid magic bytes".to_string()));
+    }
+
+    // Read version
+    let version = u32::from_le_bytes(data[4..8].try_into().unwrap());
+    if version > VAULT_VERSION {
+        return Err(VaultError::UnsupportedVersion(version));
+    }
+    if PayloadFormat::for_version(version).is_none() {
+        return Err(VaultError::InvalidFormat(format!(
+            "Unsupported version: {}",
+            version
+        )));
+    }
+
+    let (header, salt, body_start) = if version > VAULT_VERSION_SEALED {
+        let table_len = data
+            .get(HEADER_SIZE..SLOT_TABLE_OFFSET)
+            .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+            .ok_or(VaultError::CorruptedVault)?;
+        let table_end = SLOT_TABLE_OFFSET
+            .checked_add(table_len)
+            .ok_or(VaultError::CorruptedVault)?;
+        let table = data
+            .get(SLOT_TABLE_OFFSET..table_end)
+            .ok_or(VaultError::CorruptedVault)?;
+
+        let header: VaultHeader =
+            ciborium::de::from_reader(table).map_err(|_| VaultError::CorruptedVault)?;
+        (header, None, table_end)
+    } else {
+        let salt: [u8; SALT_SIZE] = data
+            .get(HEADER_SIZE..HEADER_SIZE + SALT_SIZE)
+            .and_then(|salt| salt.try_into().ok())
+            .ok_or(VaultError::CorruptedVault)?;
+        (VaultHeader::default(), Some(salt), HEADER_SIZE + SALT_SIZE)
+    };
+
+    // Older formats do not authenticate the header
+    let aad = if salt.is_some() { &[][..] } else { &data[..body_start] };
+
+    let body = if version > VAULT_VERSION_SLOTS {
+        verified_body(&data[body_start..])?
+    } else {
+        &data[body_start..]
+    };
+    let nonce: [u8; NONCE_SIZE] = body
+        .get(..NONCE_SIZE)
+        .and_then(|nonce| nonce.try_into().ok())
+        .ok_or(VaultError::CorruptedVault)?;
+
+    Ok(FileParts {
+        version,
+        key_slots: header.key_slots,
+        emergency_grants: header.emergency_grants,
+        salt,
+        aad,
+        nonce,
+        ciphertext: &body[NONCE_SIZE..],
+    })
+}
+
+/// Checks the payload length and checksum that precede the encrypted
+/// payload and returns the payload.
+///
+/// # Errors
+/// `VaultError::TruncatedVault` if the file ends before the payload does,
+/// `VaultError::CorruptedVault` if the checksum does not match.
+fn verified_body(rest: &[u8]) -> Result<&[u8], VaultError> {
+    let (block, body) = rest
+        .split_at_checked(CHECKSUM_BLOCK_SIZE)
+        .ok_or(VaultError::CorruptedVault)?;
+    let expected = u64::from_le_bytes(block[..8].try_into().unwrap());
+
+    let found = body.len() as u64;
+    if found < expected {
+        return Err(VaultError::TruncatedVault { expected, found });
+    }
+    if found != expected || body.len() < NONCE_SIZE {
+        return Err(VaultError::CorruptedVault);
+    }
+
+    let (nonce, ciphertext) = body.split_at(NONCE_SIZE);
+    if payload_checksum(nonce, ciphertext)[..] != block[8..] {
+        return Err(VaultError::CorruptedVault);
+    }
+    Ok(body)
+}
+
+/// SHA-256 of the nonce and ciphertext of the encrypted payload.
+fn payload_checksum(nonce: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(nonce);
+    hasher.update(ciphertext);
+    hasher.finalize().into()
+}
+
+/// Decrypts a vault with its data key, leaving projects sealed.
+///
+/// Use `Vault::unseal_project` to decrypt only the projects that are needed.
+///
+/// Vaults older than format 4 are upgraded in memory, but without a
+/// credential they get no key slot and cannot be saved; open them with
+/// `unlock_vault` instead.
+pub fn open_vault(data: &[u8], key: &[u8; KEY_SIZE]) -> Result<Vault, VaultError> {
+    read_vault(
+        data,
+        &Upgrade {
+            key,
+            credential: None,
+        },
+    )
+}
+
+/// What an upgrade step may need besides the vault itself.
+struct Upgrade<'a> {
+    /// Data key the vault was decrypted with
+    key: &'a [u8; KEY_SIZE],
+    /// Credential the key was unlocked with, if any
+    credential: Option<&'a [u8]>,
+}
+
+/// An upgrade of a decoded vault from one format version to the next.
+struct Migration {
+    /// Format version the step upgrades from
+    from: u32,
+    apply: fn(&mut Vault, &Upgrade) -> Result<(), VaultError>,
+}
+
+/// Upgrade steps in order, one per format version before the current one.
+///
+/// Each format keeps its reader (`split_file` and `PayloadFormat`), so a
+/// file of any supported version decodes into a `Vault`; the steps then
+/// bring what the old format lacked up to date. A new format adds its
+/// reader and a step from the previous version here. Saving always writes
+/// `VAULT_VERSION`.
+const MIGRATIONS: &[Migration] = &[
+    // The payload is re-encoded as CBOR when saved
+    Migration {
+        from: VAULT_VERSION_JSON,
+        apply: |_, _| Ok(()),
+    },
+    // Projects are sealed under their subkeys when saved
+    Migration {
+        from: VAULT_VERSION_CBOR,
+        apply: |_, _| Ok(()),
+    },
+    // The salt-derived key becomes the data key behind a password slot
+    Migration {
+        from: VAULT_VERSION_SEALED,
+        apply: add_password_slot,
+    },
+    // The payload checksum is written when saved
+    Migration {
+        from: VAULT_VERSION_SLOTS,
+        apply: |_, _| Ok(()),
+    },
+];
+
+/// Gives a vault from before key slots a password slot wrapping its
+/// existing key, so nothing has to be re-encrypted.
+fn add_password_slot(vault: &mut Vault, upgrade: &Upgrade) -> Result<(), VaultError> {
+    if let (Some(credential), true) = (upgrade.credential, vault.key_slots.is_empty()) {
+        vault.add_key_slot(SlotKind::Password, DEFAULT_SLOT_LABEL, credential, upgrade.key)?;
+    }
+    Ok(())
+}
+
+/// Runs the upgrade steps from format `version` onwards.
+fn migrate(vault: &mut Vault, version: u32, upgrade: &Upgrade) -> Result<(), VaultError> {
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= version) {
+        tracing::debug!(
+            from = migration.from,
+            to = migration.from + 1,
+            "upgrading vault format"
+        );
+        (migration.apply)(vault, upgrade)?;
+    }
+    Ok(())
+}
+
+/// Decrypts a vault of any supported format and upgrades it in memory.
+fn read_vault(data: &[u8], upgrade: &Upgrade) -> Result<Vault, VaultError> {
+    let key = upgrade.key;
+    let parts = split_file(data)?;
+    let format = PayloadFormat::for_version(parts.version).ok_or(VaultError::CorruptedVault)?;
+
+    let encrypted = EncryptedData {
+        ciphertext: parts.ciphertext.to_vec(),
+        nonce: parts.nonce,
+    };
+
+    // Decrypt
+    let payload = crypto::decrypt_with_aad(&encrypted, key, parts.aad)
+        .map_err(|_| VaultError::AuthenticationFailed)?;
+
+    // Decompress and deserialize
+    let compressed = read_flags(data)? & FLAG_COMPRESSED != 0;
+    let mut vault = if compressed {
+        decode_payload(&decompress_payload(&payload)?, format)?
+    } else {
+        decode_payload(&payload, format)?
+    };
+    vault.compressed = compressed;
+    vault.sharded = read_flags(data)? & FLAG_SHARDED != 0;
+    vault.version = parts.version;
+    vault.key_slots = parts.key_slots;
+    vault.emergency_grants = parts.emergency_grants;
+
+    // The header copies of the generation and read-only flag must agree
+    // with the encrypted payload (older formats do not authenticate them)
+    let header_read_only = read_flags(data)? & FLAG_READ_ONLY != 0;
+    if read_generation(data)? != vault.generation || header_read_only != vault.read_only {
+        tracing::debug!("vault header disagrees with the encrypted payload");
+        return Err(VaultError::CorruptedVault);
+    }
+
+    tracing::debug!(
+        version = vault.version,
+        generation = vault.generation,
+        sealed_projects = vault.sealed_projects.len(),
+        "opened vault"
+    );
+    migrate(&mut vault, parts.version, upgrade)?;
+    Ok(vault)
+}
+
+/// Serializes the vault payload in the given format (before encryption).
+///
+/// The returned buffer is zeroized when dropped.
+pub fn encode_payload(
+    vault: &Vault,
+    format: PayloadFormat,
+) -> Result<Zeroizing<Vec<u8>>, VaultError> {
+    let vault_data = VaultData {
+        version: match format {
+            PayloadFormat::Json => VAULT_VERSION_JSON,
+            PayloadFormat::Cbor => VAULT_VERSION,
+        },
+        generation: vault.generation,
+        read_only: vault.read_only,
+        projects: vault.projects.clone(),
+        sealed_projects: vault.sealed_projects.clone(),
+        shards: vault.shards.clone(),
+        ssh_identities: vault.ssh_identities.clone(),
+        ssh_servers: vault.ssh_servers.clone(),
+        ssh_server_groups: vault.ssh_server_groups.clone(),
+        kubeconfigs: vault.kubeconfigs.clone(),
+        wireguard_configs: vault.wireguard_configs.clone(),
+        origin_grants: vault.origin_grants.clone(),
+        access_log: vault.access_log.clone(),
+        trash: vault.trash.clone(),
+        undo: vault.undo.clone(),
+    };
+
+    let mut payload = Zeroizing::new(Vec::new());
+    match format {
+        PayloadFormat::Json => serde_json::to_writer(&mut *payload, &vault_data)
+            .map_err(|e| VaultError::SerializationError(e.to_string()))?,
+        PayloadFormat::Cbor => ciborium::ser::into_writer(&vault_data, &mut *payload)
+            .map_err(|e| VaultError::SerializationError(e.to_string()))?,
+    }
+
+    Ok(payload)
+}
+
+/// Compresses a serialized payload with zstd.
+///
+/// The payload is compressed as a whole, before encryption, so no
+/// ciphertext length reveals how well a single secret compresses. The
+/// secret values in it are already encrypted and do not shrink; the names,
+/// metadata and access log do.
+fn compress_payload(payload: &[u8]) -> Zeroizing<Vec<u8>> {
+    Zeroizing::new(ruzstd::encoding::compress_to_vec(
+        payload,
+        ruzstd::encoding::CompressionLevel::Fastest,
+    ))
+}
+
+/// Reverses `compress_payload`.
+fn decompress_payload(compressed: &[u8]) -> Result<Zeroizing<Vec<u8>>, VaultError> {
+    let mut payload = Zeroizing::new(Vec::new());
+    ruzstd::decoding::StreamingDecoder::new(compressed)
+        .map_err(|e| VaultError::SerializationError(e.to_string()))?
+        .read_to_end(&mut payload)
+        .map_err(|e| VaultError::SerializationError(e.to_string()))?;
+    Ok(payload)
+}
+
+/// Deserializes a decrypted vault payload in the given format.
+pub fn decode_payload(payload: &[u8], format: PayloadFormat) -> Result<Vault, VaultError> {
+    let vault_data: VaultData = match format {
+        PayloadFormat::Json => serde_json::from_slice(payload)
+            .map_err(|e| VaultError::SerializationError(e.to_string()))?,
+        PayloadFormat::Cbor => ciborium::de::from_reader(payload)
+            .map_err(|e| VaultError::SerializationError(e.to_string()))?,
+    };
+
+    Ok(Vault {
+        version: vault_data.version,
+        generation: vault_data.generation,
+        read_only: vault_data.read_only,
+        compressed: false,
+        sharded: false,
+        projects: vault_data.projects,
+        sealed_projects: vault_data.sealed_projects,
+        shards: vault_data.shards,
+        unsealed_from: HashMap::new(),
+        ssh_identities: vault_data.ssh_identities,
+        ssh_servers: vault_data.ssh_servers,
+        ssh_server_groups: vault_data.ssh_server_groups,
+        kubeconfigs: vault_data.kubeconfigs,
+        wireguard_configs: vault_data.wireguard_configs,
+        origin_grants: vault_data.origin_grants,
+        saved_log_len: vault_data.access_log.len(),
+        provenance: None,
+        access_log: vault_data.access_log,
+        trash: vault_data.trash,
+        undo: vault_data.undo,
+        key_slots: Vec::new(),
+        emergency_grants: Vec::new(),
+        passphrase_keys: HashMap::new(),
+    })
+}
+
+/// Normalizes a web origin to `scheme://host[:port]`, lowercased, without a
+/// trailing slash.
+///
+/// Only `http` and `https` origins are accepted, and paths are rejected, so
+/// a grant cannot be scoped more narrowly than the browser reports.
+pub fn normalize_origin(origin: &str) -> Result<String, VaultError> {
+    let invalid = || VaultError::InvalidOrigin(origin.to_string());
+
+    let origin = origin.trim().trim_end_matches('/').to_ascii_lowercase();
+    let (scheme, host) = origin.split_once("://").ok_or_else(invalid)?;
+    if !matches!(scheme, "http" | "https") {
+        return Err(invalid());
+    }
+    if host.is_empty() || host.contains(['/', '?', '#', '@', ' ']) {
+        return Err(invalid());
+    }
+
+    Ok(origin)
+}
+
+/// Generates a random identifier for an attachment blob.
+fn generate_blob_id() -> String {
+    let mut id = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut id);
+    id.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Custom serde modules for binary data.
+//
+// Human-readable formats (JSON) store bytes as base64 strings; binary formats
+// (CBOR) store them as raw byte strings.
+pub(crate) mod base64_serde {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::de::{self, Visitor};
+    use serde::{Deserializer, Serializer};
+    use std::fmt;
+
+    pub fn serialize<S>(bytes: &Vec<u8>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&STANDARD.encode(bytes))
+        } else {
+            serializer.serialize_bytes(bytes)
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(BytesVisitor)
+        } else {
+            // Not `deserialize_bytes`: ciborium only borrows byte strings
+            // that fit its scratch buffer (4 KiB)
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    /// Accepts either a base64 string or a raw byte string.
+    pub struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a base64 string or byte string")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+            STANDARD.decode(v).map_err(de::Error::custom)
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+            Ok(v)
+        }
+    }
+}
+
+mod nonce_serde {
+    use super::base64_serde::BytesVisitor;
+    use super::NONCE_SIZE;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S>(bytes: &[u8; NONCE_SIZE], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&STANDARD.encode(bytes))
+        } else {
+            serializer.serialize_bytes(bytes)
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<[u8; NONCE_SIZE], D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes = if deserializer.is_human_readable() {
+            deserializer.deserialize_str(BytesVisitor)?
+        } else {
+            deserializer.deserialize_bytes(BytesVisitor)?
+        };
+        bytes
+            .try_into()
+            .map_err(|_| serde::de::Error::custom("Invalid nonce length"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_vault() {
+        let vault = Vault::new();
+        assert_eq!(vault.version, VAULT_VERSION);
+        assert!(vault.projects.is_empty());
+        assert!(vault.ssh_identities.is_empty());
+    }
+
+    #[test]
+    fn test_init_project() {
+        let mut vault = Vault::new();
+        vault.init_project("my-project").unwrap();
+
+        assert!(vault.projects.contains_key("my-project"));
+    }
+
+    #[test]
+    fn test_duplicate_project() {
+        let mut vault = Vault::new();
+        vault.init_project("my-project").unwrap();
+
+        let result = vault.init_project("my-project");
+        assert!(matches!(result, Err(VaultError::ProjectAlreadyExists(_))));
+    }
+
+    #[test]
+    fn test_add_and_get_secret() {
+        let mut vault = Vault::new();
+        vault.init_project("test").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        let secret_value = b"my-secret-value";
+
+        vault
+            .add_secret("test", "DB_PASSWORD", secret_value, &key, None)
+            .unwrap();
+
+        let retrieved = vault.get_secret("test", "DB_PASSWORD", &key).unwrap();
+        assert_eq!(retrieved.expose(), secret_value);
+    }
+
+    #[test]
+    fn test_environments_hold_separate_values() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("app", "DB_URL", b"local", &key, None).unwrap();
+        for env in ["staging", "prod"] {
+            vault
+                .add_secret_in("app", Some(env), "DB_URL", env.as_bytes(), &key, None)
+                .unwrap();
+        }
+
+        assert_eq!(vault.get_secret("app", "DB_URL", &key).unwrap().expose(), b"local");
+        assert_eq!(
+            vault
+                .get_secret_in("app", Some("prod"), "DB_URL", &key)
+                .unwrap()
+                .expose(),
+            b"prod"
+        );
+        assert_eq!(vault.projects["app"].environment_names(), vec!["prod", "staging"]);
+        assert_eq!(vault.projects["app"].all_secrets().count(), 3);
+
+        // No fallback to the default environment
+        vault.add_secret("app", "ONLY_DEFAULT", b"x", &key, None).unwrap();
+        assert!(matches!(
+            vault.get_secret_in("app", Some("prod"), "ONLY_DEFAULT", &key),
+            Err(VaultError::SecretNotFound(_))
+        ));
+        assert!(matches!(
+            vault.get_secret_in("app", Some("qa"), "DB_URL", &key),
+            Err(VaultError::EnvironmentNotFound(_))
+        ));
+
+        vault.remove_secret_in("app", Some("staging"), "DB_URL").unwrap();
+        assert_eq!(vault.projects["app"].environment_names(), vec!["prod"]);
+    }
+
+    #[test]
+    fn test_alias_resolves_to_target_value() {
+        let mut vault = Vault::new();
+        vault.init_project("app1").unwrap();
+        vault.init_project("app2").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("app1", "DB_URL", b"postgres://db", &key, None).unwrap();
+        vault
+            .add_alias("app2", None, "DB_URL", SecretRef::new("app1", None, "DB_URL"))
+            .unwrap();
+        vault
+            .add_alias("app2", Some("prod"), "URL", SecretRef::new("app2", None, "DB_URL"))
+            .unwrap();
+
+        assert_eq!(
+            vault
+                .get_secret_in("app2", Some("prod"), "URL", &key)
+                .unwrap()
+                .expose(),
+            b"postgres://db"
+        );
+        assert_eq!(
+            vault.resolve_alias("app2", Some("prod"), "URL").unwrap(),
+            SecretRef::new("app1", None, "DB_URL")
+        );
+        assert_eq!(vault.aliases().len(), 2);
+
+        // Updating the target is seen through the alias
+        vault.add_secret("app1", "DB_URL", b"postgres://new", &key, None).unwrap();
+        assert_eq!(vault.get_secret("app2", "DB_URL", &key).unwrap().expose(), b"postgres://new");
+
+        vault.remove_secret("app1", "DB_URL").unwrap();
+        assert!(matches!(
+            vault.get_secret("app2", "DB_URL", &key),
+            Err(VaultError::DanglingAlias(_))
+        ));
+    }
+
+    #[test]
+    fn test_alias_cycle_rejected() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("app", "A", b"a", &key, None).unwrap();
+        vault.add_alias("app", None, "B", SecretRef::new("app", None, "A")).unwrap();
+
+        // A -> B -> A
+        assert!(matches!(
+            vault.add_alias("app", None, "A", SecretRef::new("app", None, "B")),
+            Err(VaultError::AliasCycle(_))
+        ));
+        assert_eq!(vault.get_secret("app", "B", &key).unwrap().expose(), b"a");
+
+        assert!(matches!(
+            vault.add_alias("app", None, "C", SecretRef::new("app", None, "MISSING")),
+            Err(VaultError::DanglingAlias(_))
+        ));
+        assert!(!vault.projects["app"].secrets.contains_key("C"));
+    }
+
+    #[test]
+    fn test_template_rendered_on_read() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("app", "DB_USER", b"admin", &key, None).unwrap();
+        vault.add_secret("app", "DB_PASSWORD", b"pw", &key, None).unwrap();
+        vault
+            .add_template_in(
+                "app",
+                None,
+                "DATABASE_URL",
+                b"postgres://{{DB_USER}}:{{DB_PASSWORD}}@{{DB_HOST}}/app",
+                &key,
+            )
+            .unwrap();
+
+        // Placeholders may refer to secrets that do not exist yet
+        assert!(matches!(
+            vault.get_secret("app", "DATABASE_URL", &key),
+            Err(VaultError::SecretNotFound(name)) if name == "DB_HOST"
+        ));
+
+        vault.add_secret("app", "DB_HOST", b"db", &key, None).unwrap();
+        assert_eq!(
+            vault.get_secret("app", "DATABASE_URL", &key).unwrap().expose(),
+            b"postgres://admin:pw@db/app"
+        );
+
+        // The stored value is the template, encrypted
+        let stored = &vault.projects["app"].secrets["DATABASE_URL"];
+        assert!(stored.template);
+        assert!(!stored.encrypted_value.windows(5).any(|w| w == b"admin"));
+    }
+
+    #[test]
+    fn test_template_cycle_detected() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        vault.add_template_in("app", None, "A", b"{{B}}", &key).unwrap();
+        vault.add_template_in("app", None, "B", b"x{{A}}", &key).unwrap();
+
+        assert!(matches!(
+            vault.get_secret("app", "A", &key),
+            Err(VaultError::TemplateCycle(_))
+        ));
+        assert!(matches!(
+            vault.add_template_in("app", None, "C", b"{{A", &key),
+            Err(VaultError::InvalidTemplate(_))
+        ));
+    }
+
+    #[test]
+    fn test_json_secret_kind_and_validation() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        let document = br#"{"client_email": "ci@example.iam", "private_key": "pk"}"#;
+        vault
+            .add_json_in("app", Some("prod"), "SERVICE_ACCOUNT", document, &key, None)
+            .unwrap();
+
+        let value = vault
+            .get_secret_in("app", Some("prod"), "SERVICE_ACCOUNT", &key)
+            .unwrap();
+        assert_eq!(value.expose(), document);
+        assert_eq!(
+            JsonDocument::parse(value.expose())
+                .unwrap()
+                .query(".private_key")
+                .unwrap()
+                .expose(),
+            b"pk"
+        );
+        assert_eq!(
+            vault.projects["app"].environments["prod"]["SERVICE_ACCOUNT"].kind,
+            SecretKind::Json
+        );
+
+        assert!(matches!(
+            vault.add_json_in("app", None, "BROKEN", b"{", &key, None),
+            Err(VaultError::InvalidJson(_))
+        ));
+        assert!(!vault.projects["app"].secrets.contains_key("BROKEN"));
+
+        // A plain value replacing the document is text again
+        vault
+            .add_secret_in("app", Some("prod"), "SERVICE_ACCOUNT", b"x", &key, None)
+            .unwrap();
+        assert_eq!(
+            vault.projects["app"].environments["prod"]["SERVICE_ACCOUNT"].kind,
+            SecretKind::Text
+        );
+    }
+
+    #[test]
+    fn test_certificate_secret_expires_with_certificate() {
+        // CN=db.internal, valid until 2036-10-13 15:24:07
+        const VALID: &str = "-----BEGIN CERTIFICATE-----
+MIIBgjCCASegAwIBAgIUH6V521s14ozctvfej2/Bmp1a25cwCgYIKoZIzj0EAwIw
+FjEUMBIGA1UEAwwLZGIuaW50ZXJuYWwwHhcNMjYxMDE2MTUyNDA3WhcNMzYxMDEz
+MTUyNDA3WjAWMRQwEgYDVQQDDAtkYi5pbnRlcm5hbDBZMBMGByqGSM49AgEGCCqG
+SM49AwEHA0IABHfqYxOtBafvpCART1a8jzkUF9lC7mV7saeBTPuUfO9oiUuxuQdu
+VT3RjWFS+cNlSjMFL0BX/XyOVYIsBMdXsI+jUzBRMB0GA1UdDgQWBBQiMjloDLP8
+i0x8zKMIxinnA+mH4TAfBgNVHSMEGDAWgBQiMjloDLP8i0x8zKMIxinnA+mH4TAP
+BgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0kAMEYCIQC/5SkM5I8iQMG81UlX
+aq1QtWIXxlIOXBd0HlYd8CBinAIhAICtjm5bDB2d9cCDIqI3cdIcyn9CPgF40PTp
+2aFWclWe
+-----END CERTIFICATE-----
+";
+        // CN=old, valid until 2021-01-01 00:00:00
+        const EXPIRED: &str = "-----BEGIN CERTIFICATE-----
+MIIBcDCCARegAwIBAgIUXyBx2kRllReL6If7xZWGqtRgznEwCgYIKoZIzj0EAwIw
+DjEMMAoGA1UEAwwDb2xkMB4XDTIwMDEwMTAwMDAwMFoXDTIxMDEwMTAwMDAwMFow
+DjEMMAoGA1UEAwwDb2xkMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEd+pjE60F
+p++kIBFPVryPORQX2ULuZXuxp4FM+5R872iJS7G5B25VPdGNYVL5w2VKMwUvQFf9
+fI5VgiwEx1ewj6NTMFEwHQYDVR0OBBYEFCIyOWgMs/yLTHzMowjGKecD6YfhMB8G
+A1UdIwQYMBaAFCIyOWgMs/yLTHzMowjGKecD6YfhMA8GA1UdEwEB/wQFMAMBAf8w
+CgYIKoZIzj0EAwIDRwAwRAIgTu2RW6Ml6YYyQ6nX4h7/T5/TC6zDddmn03Xbp0YO
+7OoCIC9Gp1XY8Xccrz9lme72d+SDbbacrjORuSNbBeLh+VFt
+-----END CERTIFICATE-----
+";
+
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        let info = vault
+            .add_certificate_in("app", None, "TLS_CERT", VALID.as_bytes(), &key)
+            .unwrap();
+        assert_eq!(info.common_name.as_deref(), Some("db.internal"));
+
+        let secret = &vault.projects["app"].secrets["TLS_CERT"];
+        assert_eq!(secret.kind, SecretKind::Certificate);
+        assert_eq!(secret.expires_at, Some(2_107_524_247));
+        assert_eq!(
+            vault.get_secret("app", "TLS_CERT", &key).unwrap().expose(),
+            VALID.as_bytes()
+        );
+
+        for value in [EXPIRED.as_bytes(), b"not a certificate"] {
+            assert!(matches!(
+                vault.add_certificate_in("app", None, "OTHER", value, &key),
+                Err(VaultError::InvalidCertificate(_))
+            ));
+        }
+        assert!(!vault.projects["app"].secrets.contains_key("OTHER"));
+    }
+
+    #[test]
+    fn test_remove_ssh_server_keeps_identity() {
+        let mut vault = Vault::new();
+        let key = [0u8; KEY_SIZE];
+        vault
+            .add_ssh_identity("id", "ssh-ed25519 AAAA".to_string(), &[1u8; 32], &key)
+            .unwrap();
+        vault
+            .add_ssh_server("web", "deploy".to_string(), "10.0.0.1".to_string(), "id".to_string())
+            .unwrap();
+
+        assert_eq!(vault.remove_ssh_server("web").unwrap().username, "deploy");
+        assert!(!vault.has_ssh_server("web"));
+        assert!(vault.ssh_identities.contains_key("id"));
+        assert!(matches!(
+            vault.remove_ssh_server("web"),
+            Err(VaultError::ServerNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_ssh_server_multiplex() {
+        let mut vault = Vault::new();
+        let key = [0u8; KEY_SIZE];
+        vault
+            .add_ssh_identity("id", "ssh-ed25519 AAAA".to_string(), &[1u8; 32], &key)
+            .unwrap();
+        vault
+            .add_ssh_server("web", "deploy".to_string(), "10.0.0.1".to_string(), "id".to_string())
+            .unwrap();
+        assert_eq!(vault.ssh_servers["web"].multiplex, None);
+
+        vault.set_ssh_server_multiplex("web", Some(600)).unwrap();
+        let json = serde_json::to_string(&vault.ssh_servers["web"]).unwrap();
+        let server: SshServerConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(server.multiplex, Some(600));
+
+        vault.set_ssh_server_multiplex("web", None).unwrap();
+        assert!(!serde_json::to_string(&vault.ssh_servers["web"])
+            .unwrap()
+            .contains("multiplex"));
+        assert!(matches!(
+            vault.set_ssh_server_multiplex("db", Some(600)),
+            Err(VaultError::ServerNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_pkcs11_identity() {
+        let mut vault = Vault::new();
+        let key = [0u8; KEY_SIZE];
+        let token_key = Pkcs11Key {
+            module: "/usr/lib/opensc-pkcs11.so".to_string(),
+            label: "SSH key".to_string(),
+        };
+        vault
+            .add_pkcs11_identity("card", "ssh-rsa AAAA SSH key".to_string(), token_key.clone())
+            .unwrap();
+
+        assert!(matches!(
+            vault.get_ssh_identity("card", &key),
+            Err(VaultError::IdentityOnToken(_))
+        ));
+        let json = serde_json::to_string(&vault.ssh_identities["card"]).unwrap();
+        let identity: SshIdentity = serde_json::from_str(&json).unwrap();
+        assert_eq!(identity.pkcs11, Some(token_key.clone()));
+        assert!(matches!(
+            vault.add_pkcs11_identity("card", "ssh-rsa BBBB".to_string(), token_key),
+            Err(VaultError::IdentityAlreadyExists(_))
+        ));
+
+        vault
+            .add_ssh_identity("soft", "ssh-ed25519 AAAA".to_string(), &[1u8; 32], &key)
+            .unwrap();
+        assert!(!serde_json::to_string(&vault.ssh_identities["soft"])
+            .unwrap()
+            .contains("pkcs11"));
+    }
+
+    #[test]
+    fn test_ssh_server_groups() {
+        let mut vault = Vault::new();
+        let key = [0u8; KEY_SIZE];
+        vault
+            .add_ssh_identity("id", "ssh-ed25519 AAAA".to_string(), &[1u8; 32], &key)
+            .unwrap();
+        for server in ["web1", "web2", "db"] {
+            vault
+                .add_ssh_server(server, "deploy".to_string(), "10.0.0.1".to_string(), "id".to_string())
+                .unwrap();
+        }
+        let names = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        vault
+            .create_ssh_server_group("web", &names(&["web1", "web2", "web1"]))
+            .unwrap();
+        assert_eq!(vault.ssh_server_groups["web"], names(&["web1", "web2"]));
+        assert_eq!(
+            vault.expand_ssh_servers(&["db", "web", "web2"]).unwrap(),
+            names(&["db", "web1", "web2"])
+        );
+        assert!(matches!(
+            vault.expand_ssh_servers(&["cache"]),
+            Err(VaultError::ServerNotFound(_))
+        ));
+
+        assert!(matches!(
+            vault.create_ssh_server_group("web", &names(&["db"])),
+            Err(VaultError::ServerGroupAlreadyExists(_))
+        ));
+        assert!(matches!(
+            vault.create_ssh_server_group("db", &names(&["web1"])),
+            Err(VaultError::ServerAlreadyExists(_))
+        ));
+        assert!(matches!(
+            vault.create_ssh_server_group("all", &names(&["web1", "cache"])),
+            Err(VaultError::ServerNotFound(_))
+        ));
+        assert!(matches!(
+            vault.create_ssh_server_group("none", &[]),
+            Err(VaultError::EmptyServerGroup(_))
+        ));
+
+        vault.remove_ssh_server("web1").unwrap();
+        assert_eq!(vault.ssh_server_groups["web"], names(&["web2"]));
+        vault.remove_ssh_server("web2").unwrap();
+        assert!(!vault.ssh_server_groups.contains_key("web"));
+
+        vault.create_ssh_server_group("data", &names(&["db"])).unwrap();
+        assert_eq!(vault.remove_ssh_server_group("data").unwrap(), names(&["db"]));
+        assert!(matches!(
+            vault.remove_ssh_server_group("data"),
+            Err(VaultError::ServerGroupNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_database_secret() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        let connection = br#"{"engine":"postgres","host":"db","user":"app","password":"pw"}"#;
+        vault
+            .add_database_in("app", None, "MAIN_DB", connection, &key, None)
+            .unwrap();
+
+        assert_eq!(vault.projects["app"].secrets["MAIN_DB"].kind, SecretKind::Database);
+        let stored = vault.get_secret("app", "MAIN_DB", &key).unwrap();
+        assert_eq!(DbConnection::parse(stored.expose()).unwrap().password, "pw");
+
+        assert!(matches!(
+            vault.add_database_in("app", None, "OTHER", b"postgres://app@db", &key, None),
+            Err(VaultError::InvalidDbConnection(_))
+        ));
+        assert!(!vault.projects["app"].secrets.contains_key("OTHER"));
+    }
+
+    #[test]
+    fn test_kubeconfig_roundtrip() {
+        let mut vault = Vault::new();
+        let key = [0u8; KEY_SIZE];
+        let config = b"apiVersion: v1\nkind: Config\ncurrent-context: prod\n";
+
+        vault.add_kubeconfig("prod", config, &key).unwrap();
+        assert_eq!(vault.get_kubeconfig("prod", &key).unwrap().expose(), config);
+        assert!(matches!(
+            vault.add_kubeconfig("prod", b"other", &key),
+            Err(VaultError::KubeconfigAlreadyExists(_))
+        ));
+
+        // Survives a save and is re-encrypted along with the rest
+        let mut vault = decode_payload(
+            &encode_payload(&vault, PayloadFormat::Cbor).unwrap(),
+            PayloadFormat::Cbor,
+        )
+        .unwrap();
+        let new_key = [1u8; KEY_SIZE];
+        vault.reencrypt(&key, &new_key).unwrap();
+        assert_eq!(vault.get_kubeconfig("prod", &new_key).unwrap().expose(), config);
+
+        assert_eq!(vault.remove_kubeconfig("prod").unwrap().name, "prod");
+        assert!(matches!(
+            vault.get_kubeconfig("prod", &new_key),
+            Err(VaultError::KubeconfigNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_wireguard_config_roundtrip() {
+        let mut vault = Vault::new();
+        let key = [0u8; KEY_SIZE];
+        let config = b"[Interface]\nPrivateKey = yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=\n";
+
+        vault.add_wireguard_config("office", config, &key).unwrap();
+        assert_eq!(vault.get_wireguard_config("office", &key).unwrap().expose(), config);
+        assert!(matches!(
+            vault.add_wireguard_config("office", config, &key),
+            Err(VaultError::WireguardConfigAlreadyExists(_))
+        ));
+        assert!(matches!(
+            vault.add_wireguard_config("not/an/interface", config, &key),
+            Err(VaultError::InvalidInterfaceName(_))
+        ));
+        assert!(matches!(
+            vault.add_wireguard_config("wg1", b"[Peer]\n", &key),
+            Err(VaultError::InvalidWireguardConfig(_))
+        ));
+
+        let new_key = [1u8; KEY_SIZE];
+        vault.reencrypt(&key, &new_key).unwrap();
+        assert_eq!(vault.get_wireguard_config("office", &new_key).unwrap().expose(), config);
+
+        vault.remove_wireguard_config("office").unwrap();
+        assert!(matches!(
+            vault.get_wireguard_config("office", &new_key),
+            Err(VaultError::WireguardConfigNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_secret_not_found() {
+        let vault = Vault::new();
+        let key = [0u8; KEY_SIZE];
+
+        let result = vault.get_secret("nonexistent", "KEY", &key);
+        assert!(matches!(result, Err(VaultError::ProjectNotFound(_))));
+    }
+
+    #[test]
+    fn test_transaction_commits_on_success() {
+        let mut vault = Vault::new();
+        let key = [0u8; KEY_SIZE];
+
+        vault
+            .transaction(|tx| {
+                tx.init_project("app")?;
+                tx.add_secret("app", "A", b"1", &key, None)?;
+                tx.add_secret("app", "B", b"2", &key, None)
+            })
+            .unwrap();
+
+        assert_eq!(vault.projects["app"].secrets.len(), 2);
+    }
+
+    #[test]
+    fn test_transaction_rolls_back_on_error() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("app", "KEEP", b"1", &key, None).unwrap();
+
+        let result = vault.transaction(|tx| {
+            tx.remove_secret("app", "KEEP")?;
+            tx.init_project("other")?;
+            tx.remove_secret("app", "MISSING")
+        });
+
+        assert!(matches!(result, Err(VaultError::SecretNotFound(_))));
+        assert!(vault.projects["app"].secrets.contains_key("KEEP"));
+        assert!(!vault.projects.contains_key("other"));
+    }
+
+    #[test]
+    fn test_access_log_persists() {
+        let mut vault = Vault::new();
+        vault.record(Operation::Get, Some("app"), Some("DB_URL"));
+        vault.record(Operation::SshConnect, None, Some("prod"));
+
+        let saved = save_vault(&vault, b"test-password").unwrap();
+        let loaded = load_vault(&saved, b"test-password").unwrap();
+
+        assert_eq!(loaded.access_log(), vault.access_log());
+        assert_eq!(loaded.access_log()[0].operation, Operation::Get);
+        assert_eq!(loaded.verify_access_log().unwrap().chained, 2);
+    }
+
+    #[test]
+    fn test_unsaved_log() {
+        let mut vault = Vault::new();
+        vault.record(Operation::Add, Some("app"), Some("A"));
+        assert_eq!(vault.unsaved_log().len(), 1);
+
+        let saved = save_vault(&vault, b"test-password").unwrap();
+        let mut loaded = load_vault(&saved, b"test-password").unwrap();
+        assert!(loaded.unsaved_log().is_empty());
+
+        loaded.record(Operation::Remove, Some("app"), Some("A"));
+        assert_eq!(loaded.unsaved_log().len(), 1);
+        assert_eq!(loaded.unsaved_log()[0].operation, Operation::Remove);
+
+        loaded.mark_saved();
+        assert!(loaded.unsaved_log().is_empty());
+    }
+
+    #[test]
+    fn test_project_passphrase_required() {
+        let (mut vault, key) = create_vault(b"test-password").unwrap();
+        vault.init_project("prod").unwrap();
+        vault.add_secret("prod", "KEY", b"value", &key, None).unwrap();
+        vault.set_project_passphrase("prod", b"extra", &key).unwrap();
+
+        // Still usable in the session that set the passphrase
+        assert_eq!(vault.get_secret("prod", "KEY", &key).unwrap().expose(), b"value");
+
+        let saved = save_vault_with_key(&vault, &key).unwrap();
+        let mut loaded = load_vault_with_key(&saved, &key).unwrap();
+        assert!(loaded.is_locked("prod"));
+        assert!(matches!(
+            loaded.get_secret("prod", "KEY", &key),
+            Err(VaultError::ProjectLocked(_))
+        ));
+        assert!(matches!(
+            loaded.add_secret("prod", "NEW", b"new", &key, None),
+            Err(VaultError::ProjectLocked(_))
+        ));
+
+        assert!(matches!(
+            loaded.unlock_project("prod", b"wrong", &key),
+            Err(VaultError::InvalidProjectPassphrase(_))
+        ));
+        loaded.unlock_project("prod", b"extra", &key).unwrap();
+        assert_eq!(loaded.get_secret("prod", "KEY", &key).unwrap().expose(), b"value");
+    }
+
+    #[test]
+    fn test_project_passphrase_layered_on_master_key() {
+        let mut vault = Vault::new();
+        vault.init_project("prod").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("prod", "KEY", b"value", &key, None).unwrap();
+        vault.set_project_passphrase("prod", b"extra", &key).unwrap();
+
+        // The stored value no longer decrypts under the master key alone
+        let secret = &vault.projects["prod"].secrets["KEY"];
+        let encrypted = EncryptedData {
+            ciphertext: secret.encrypted_value.clone(),
+            nonce: secret.nonce,
+        };
+        assert!(crypto::decrypt(&encrypted, &key).is_err());
+
+        vault.remove_project_passphrase("prod", &key).unwrap();
+        assert!(!vault.is_locked("prod"));
+        assert!(vault.projects["prod"].lock.is_none());
+        assert_eq!(vault.get_secret("prod", "KEY", &key).unwrap().expose(), b"value");
+    }
+
+    #[test]
+    fn test_attachment_roundtrip() {
+        let mut vault = Vault::new();
+        vault.init_project("test").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        let contents = b"-----BEGIN CERTIFICATE-----";
+
+        let (blob_id, blob) = vault
+            .add_attachment("test", "cert.pem", contents, &key)
+            .unwrap();
+        assert_eq!(blob_id.len(), 32);
+        assert_ne!(blob.as_slice(), contents.as_slice());
+
+        let opened = vault.open_attachment("test", "cert.pem", &blob, &key).unwrap();
+        assert_eq!(opened.expose(), contents);
+
+        let removed = vault.remove_attachment("test", "cert.pem").unwrap();
+        assert_eq!(removed.blob_id, blob_id);
+        assert!(matches!(
+            vault.get_attachment("test", "cert.pem"),
+            Err(VaultError::AttachmentNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_attachment_blob_bound_to_its_name() {
+        let mut vault = Vault::new();
+        vault.init_project("test").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        let (_, a) = vault.add_attachment("test", "a.pem", b"a", &key).unwrap();
+        vault.add_attachment("test", "b.pem", b"b", &key).unwrap();
+
+        // A blob put in place of another attachment's does not open
+        assert!(matches!(
+            vault.open_attachment("test", "b.pem", &a, &key),
+            Err(VaultError::CryptoError(_))
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_attachment() {
+        let mut vault = Vault::new();
+        vault.init_project("test").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        vault.add_attachment("test", "a.bin", b"1", &key).unwrap();
+
+        let result = vault.add_attachment("test", "a.bin", b"2", &key);
+        assert!(matches!(result, Err(VaultError::AttachmentAlreadyExists(_))));
+    }
+
+    #[test]
+    fn test_open_legacy_attachment() {
+        let mut vault = Vault::new();
+        vault.init_project("test").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        let encrypted = crypto::encrypt(b"old", &key).unwrap();
+        vault.project_mut("test").unwrap().attachments.insert(
+            "old.txt".to_string(),
+            Attachment {
+                name: "old.txt".to_string(),
+                blob_id: generate_blob_id(),
+                size: 3,
+                nonce: encrypted.nonce,
+                chunked: false,
+                bound: false,
+                created_at: 0,
+            },
+        );
+
+        let opened = vault
+            .open_attachment("test", "old.txt", &encrypted.ciphertext, &key)
+            .unwrap();
+        assert_eq!(opened.expose(), b"old");
+    }
+
+    #[test]
+    fn test_vault_save_load_roundtrip() {
+        let mut vault = Vault::new();
+        vault.init_project("test").unwrap();
+
+        let password = b"test-password";
+        let key = crypto::derive_key(password, &crypto::generate_salt()).unwrap();
+
+        vault
+            .add_secret("test", "SECRET", b"value", &key, None)
+            .unwrap();
+
+        let saved = save_vault(&vault, password).unwrap();
+        let loaded = load_vault(&saved, password).unwrap();
+
+        assert_eq!(loaded.version, vault.version);
+        assert!(loaded.projects.contains_key("test"));
+    }
+
+    #[test]
+    fn test_payload_formats_roundtrip() {
+        let mut vault = Vault::new();
+        vault.init_project("test").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        vault
+            .add_secret("test", "SECRET", b"value", &key, None)
+            .unwrap();
+
+        for format in [PayloadFormat::Json, PayloadFormat::Cbor] {
+            let payload = encode_payload(&vault, format).unwrap();
+            let decoded = decode_payload(&payload, format).unwrap();
+
+            let original = &vault.projects["test"].secrets["SECRET"];
+            let restored = &decoded.projects["test"].secrets["SECRET"];
+            assert_eq!(restored.encrypted_value, original.encrypted_value);
+            assert_eq!(restored.nonce, original.nonce);
+        }
+    }
+
+    #[test]
+    fn test_cbor_payload_is_smaller() {
+        let mut vault = Vault::new();
+        vault.init_project("test").unwrap();
+
+        let key = [0u8; KEY_SIZE];
+        for i in 0..20 {
+            vault
+                .add_secret("test", &format!("KEY_{}", i), &[0u8; 256], &key, None)
+                .unwrap();
+        }
+
+        let json = encode_payload(&vault, PayloadFormat::Json).unwrap();
+        let cbor = encode_payload(&vault, PayloadFormat::Cbor).unwrap();
+        assert!(cbor.len() < json.len());
+    }
+
+    #[test]
+    fn test_load_legacy_json_vault() {
+        let mut vault = Vault::new();
+        vault.init_project("legacy").unwrap();
+
+        // Build a version 1 file by hand
+        let password = b"test-password";
+        let salt = crypto::generate_salt();
+        let key = crypto::derive_key(password, &salt).unwrap();
+        let payload = encode_payload(&vault, PayloadFormat::Json).unwrap();
+        let encrypted = crypto::encrypt(&payload, &key).unwrap();
+
+        let mut data = Vec::new();
+        data.extend_from_slice(VAULT_MAGIC);
+        data.extend_from_slice(&VAULT_VERSION_JSON.to_le_bytes());
+        data.extend_from_slice(&[0u8; 8]);
+        data.extend_from_slice(&salt);
+        data.extend_from_slice(&encrypted.nonce);
+        data.extend_from_slice(&encrypted.ciphertext);
+
+        let loaded = load_vault(&data, password).unwrap();
+        assert_eq!(loaded.version, VAULT_VERSION_JSON);
+        assert!(loaded.projects.contains_key("legacy"));
+
+        // The legacy key becomes the data key behind a new password slot
+        assert_eq!(loaded.key_slots().len(), 1);
+        let upgraded = save_vault(&loaded, password).unwrap();
+        assert_eq!(*unlock_key(&upgraded, password).unwrap(), *key);
+        assert_eq!(load_vault(&upgraded, password).unwrap().version, VAULT_VERSION);
+    }
+
+    #[test]
+    fn test_migrations_cover_every_old_version() {
+        let steps: Vec<u32> = MIGRATIONS.iter().map(|m| m.from).collect();
+        let versions: Vec<u32> = (VAULT_VERSION_JSON..VAULT_VERSION).collect();
+        assert_eq!(steps, versions);
+    }
+
+    #[test]
+    fn test_open_vault_without_credential_adds_no_slot() {
+        let mut vault = Vault::new();
+        vault.init_project("legacy").unwrap();
+
+        let password = b"test-password";
+        let salt = crypto::generate_salt();
+        let key = crypto::derive_key(password, &salt).unwrap();
+        let payload = encode_payload(&vault, PayloadFormat::Cbor).unwrap();
+        let encrypted = crypto::encrypt(&payload, &key).unwrap();
+
+        let mut data = Vec::new();
+        data.extend_from_slice(VAULT_MAGIC);
+        data.extend_from_slice(&VAULT_VERSION_CBOR.to_le_bytes());
+        data.extend_from_slice(&[0u8; 8]);
+        data.extend_from_slice(&salt);
+        data.extend_from_slice(&encrypted.nonce);
+        data.extend_from_slice(&encrypted.ciphertext);
+
+        let opened = open_vault(&data, &key).unwrap();
+        assert!(opened.needs_upgrade());
+        assert!(opened.key_slots().is_empty());
+
+        let (unlocked, _) = unlock_vault(&data, password).unwrap();
+        assert_eq!(unlocked.key_slots().len(), 1);
+        let upgraded = save_vault_with_key(&unlocked, &key).unwrap();
+        assert!(!open_vault(&upgraded, &key).unwrap().needs_upgrade());
+    }
+
+    #[test]
+    fn test_truncated_file_detected_before_key_derivation() {
+        let saved = save_vault(&Vault::new(), b"test-password").unwrap();
+
+        // A wrong password would otherwise report AuthenticationFailed
+        let truncated = &saved[..saved.len() - 100];
+        assert!(matches!(
+            unlock_key(truncated, b"wrong-password"),
+            Err(VaultError::TruncatedVault { expected, found }) if found + 100 == expected
+        ));
+
+        let mut damaged = saved.clone();
+        let last = damaged.len() - 1;
+        damaged[last] ^= 1;
+        assert!(matches!(
+            unlock_key(&damaged, b"wrong-password"),
+            Err(VaultError::CorruptedVault)
+        ));
+    }
+
+    #[test]
+    fn test_load_vault_without_checksum() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+        let key = crypto::generate_key();
+        vault
+            .add_key_slot(SlotKind::Password, DEFAULT_SLOT_LABEL, b"pw", &key)
+            .unwrap();
+
+        // Build a version 4 file by hand
+        let payload = encode_payload(&vault.sealed(&key).unwrap(), PayloadFormat::Cbor).unwrap();
+        let header = VaultHeader {
+            key_slots: vault.key_slots.clone(),
+            emergency_grants: Vec::new(),
+        };
+        let mut slot_table = Vec::new();
+        ciborium::ser::into_writer(&header, &mut slot_table).unwrap();
+
+        let mut data = Vec::new();
+        data.extend_from_slice(VAULT_MAGIC);
+        data.extend_from_slice(&VAULT_VERSION_SLOTS.to_le_bytes());
+        data.extend_from_slice(&[0u8; 8]);
+        data.extend_from_slice(&(slot_table.len() as u32).to_le_bytes());
+        data.extend_from_slice(&slot_table);
+        let encrypted = crypto::encrypt_with_aad(&payload, &key, &data).unwrap();
+        data.extend_from_slice(&encrypted.nonce);
+        data.extend_from_slice(&encrypted.ciphertext);
+
+        let loaded = load_vault(&data, b"pw").unwrap();
+        assert_eq!(loaded.version, VAULT_VERSION_SLOTS);
+        assert!(loaded.projects.contains_key("app"));
+    }
+
+    #[test]
+    fn test_newer_format_is_rejected() {
+        let mut data = save_vault(&Vault::new(), b"test-password").unwrap();
+        data[4..8].copy_from_slice(&(VAULT_VERSION + 1).to_le_bytes());
+
+        assert!(matches!(
+            unlock_key(&data, b"test-password"),
+            Err(VaultError::UnsupportedVersion(v)) if v == VAULT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn test_generation_roundtrip() {
+        let mut vault = Vault::new();
+        vault.generation = 41;
+
+        let saved = save_vault(&vault, b"test-password").unwrap();
+        assert_eq!(read_generation(&saved).unwrap(), 41);
+
+        let loaded = load_vault(&saved, b"test-password").unwrap();
+        assert_eq!(loaded.generation, 41);
+    }
+
+    #[test]
+    fn test_tampered_header_generation_detected() {
+        let vault = Vault::new();
+        let mut saved = save_vault(&vault, b"test-password").unwrap();
+        saved[8] ^= 1;
+
+        // The header is authenticated along with the payload
+        assert!(matches!(
+            load_vault(&saved, b"test-password"),
+            Err(VaultError::AuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_read_only_export() {
+        let (saved, key) = two_project_vault(b"test-password");
+        let vault = load_vault_with_key(&saved, &key).unwrap();
+
+        let exported = export_read_only(&vault, &key, b"ci-password").unwrap();
+        assert_eq!(read_flags(&exported).unwrap() & FLAG_READ_ONLY, FLAG_READ_ONLY);
+
+        let export_key = unlock_key(&exported, b"ci-password").unwrap();
+        assert_ne!(*export_key, *key);
+        let consumer = load_vault_with_key(&exported, &export_key).unwrap();
+
+        assert!(consumer.read_only);
+        assert_eq!(
+            consumer.get_secret("a", "KEY", &export_key).unwrap().expose(),
+            b"a"
+        );
+        assert!(matches!(
+            save_vault_with_key(&consumer, &export_key),
+            Err(VaultError::ReadOnly)
+        ));
+    }
+
+    #[test]
+    fn test_read_only_export_of_unchanged_projects() {
+        let (mut vault, key) = create_vault(b"test-password").unwrap();
+        vault.init_project("empty").unwrap();
+        vault.init_project("app").unwrap();
+        vault.add_secret("app", "KEY", b"value", &key, None).unwrap();
+        let saved = save_vault_with_key(&vault, &key).unwrap();
+
+        // Unsealed from disk, so "empty" is unchanged when the export seals it
+        let vault = load_vault_with_key(&saved, &key).unwrap();
+        let exported = export_read_only(&vault, &key, b"ci").unwrap();
+
+        let consumer = load_vault(&exported, b"ci").unwrap();
+        assert_eq!(consumer.project_names(), ["app", "empty"]);
+    }
+
+    #[test]
+    fn test_cleared_read_only_flag_detected() {
+        let vault = Vault::new();
+        let key = [0u8; KEY_SIZE];
+        let mut exported = export_read_only(&vault, &key, b"ci-password").unwrap();
+        exported[12] = 0;
+
+        assert!(matches!(
+            load_vault(&exported, b"ci-password"),
+            Err(VaultError::AuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_compressed_payload() {
+        let (mut vault, key) = create_vault(b"test-password").unwrap();
+        vault.init_project("app").unwrap();
+        vault.add_secret("app", "KEY", b"value", &key, None).unwrap();
+        for _ in 0..200 {
+            vault.record(Operation::Get, Some("app"), Some("KEY"));
+        }
+        let plain = save_vault_with_key(&vault, &key).unwrap();
+        assert_eq!(read_flags(&plain).unwrap() & FLAG_COMPRESSED, 0);
+
+        vault.compressed = true;
+        let compressed = save_vault_with_key(&vault, &key).unwrap();
+        assert_eq!(read_flags(&compressed).unwrap() & FLAG_COMPRESSED, FLAG_COMPRESSED);
+        assert!(compressed.len() < plain.len());
+
+        // The setting is kept when the vault is read and saved again
+        let loaded = load_vault_with_key(&compressed, &key).unwrap();
+        assert!(loaded.compressed);
+        assert_eq!(loaded.access_log().len(), 200);
+        assert_eq!(loaded.get_secret("app", "KEY", &key).unwrap().expose(), b"value");
+        let resaved = save_vault_with_key(&loaded, &key).unwrap();
+        assert_eq!(read_flags(&resaved).unwrap() & FLAG_COMPRESSED, FLAG_COMPRESSED);
+
+        // The flag is authenticated with the payload
+        let mut tampered = compressed.clone();
+        tampered[12] &= !(FLAG_COMPRESSED as u8);
+        assert!(matches!(
+            load_vault_with_key(&tampered, &key),
+            Err(VaultError::AuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_wrong_password_fails() {
+        let vault = Vault::new();
+        let saved = save_vault(&vault, b"correct-password").unwrap();
+
+        let result = load_vault(&saved, b"wrong-password");
+        assert!(matches!(result, Err(VaultError::AuthenticationFailed)));
+    }
+
+    /// Saves a vault with projects "a" and "b", each holding one secret.
+    fn two_project_vault(password: &[u8]) -> (Vec<u8>, Zeroizing<[u8; KEY_SIZE]>) {
+        let (mut vault, key) = create_vault(password).unwrap();
+        for name in ["a", "b"] {
+            vault.init_project(name).unwrap();
+            vault
+                .add_secret(name, "KEY", name.as_bytes(), &key, None)
+                .unwrap();
+        }
+
+        let saved = save_vault_with_key(&vault, &key).unwrap();
+        (saved, key)
+    }
+
+    #[test]
+    fn test_open_vault_leaves_projects_sealed() {
+        let (saved, key) = two_project_vault(b"test-password");
+
+        let mut vault = open_vault(&saved, &key).unwrap();
+        assert!(vault.projects.is_empty());
+        assert_eq!(vault.project_names(), vec!["a", "b"]);
+        assert!(matches!(
+            vault.get_secret("a", "KEY", &key),
+            Err(VaultError::ProjectSealed(_))
+        ));
+
+        vault.unseal_project("a", &key).unwrap();
+        assert_eq!(vault.get_secret("a", "KEY", &key).unwrap().expose(), b"a");
+        assert!(vault.is_sealed("b"));
+    }
+
+    #[test]
+    fn test_save_preserves_sealed_projects() {
+        let password = b"test-password";
+        let (saved, key) = two_project_vault(password);
+
+        let mut vault = open_vault(&saved, &key).unwrap();
+        vault.unseal_project("a", &key).unwrap();
+        vault.add_secret("a", "NEW", b"new", &key, None).unwrap();
+
+        let resaved = save_vault_with_key(&vault, &key).unwrap();
+        let loaded = load_vault(&resaved, password).unwrap();
+
+        assert_eq!(loaded.get_secret("a", "NEW", &key).unwrap().expose(), b"new");
+        assert_eq!(loaded.get_secret("b", "KEY", &key).unwrap().expose(), b"b");
+    }
+
+    #[test]
+    fn test_sharded_vault_round_trip() {
+        let (saved, key) = two_project_vault(b"test-password");
+        let mut vault = open_vault(&saved, &key).unwrap();
+        let (file, shards) = save_vault_sharded(&vault, &key).unwrap();
+        assert_eq!(read_flags(&file).unwrap() & FLAG_SHARDED, FLAG_SHARDED);
+        assert_eq!(shards.len(), 2);
+
+        let read = |id: &str| {
+            shards
+                .iter()
+                .find(|(shard, _)| shard == id)
+                .map(|(_, data)| data.clone())
+        };
+        let mut loaded = open_vault(&file, &key).unwrap();
+        assert!(loaded.sharded);
+        assert_eq!(loaded.project_names(), vec!["a", "b"]);
+        assert!(matches!(
+            loaded.unseal_project("a", &key),
+            Err(VaultError::MissingShard(_))
+        ));
+        loaded.attach_shards(read).unwrap();
+        loaded.unseal_all(&key).unwrap();
+        assert_eq!(loaded.get_secret("b", "KEY", &key).unwrap().expose(), b"b");
+
+        // Only the changed project gets a new shard
+        vault.unseal_all(&key).unwrap();
+        vault.add_secret("a", "NEW", b"new", &key, None).unwrap();
+        let (_, resharded) = save_vault_sharded(&vault, &key).unwrap();
+        let unchanged: Vec<_> = resharded
+            .iter()
+            .filter(|(id, _)| read(id).is_some())
+            .collect();
+        assert_eq!(unchanged.len(), 1);
+    }
+
+    #[test]
+    fn test_sharded_vault_rejects_missing_or_tampered_shard() {
+        let (saved, key) = two_project_vault(b"test-password");
+        let vault = open_vault(&saved, &key).unwrap();
+        let (file, shards) = save_vault_sharded(&vault, &key).unwrap();
+
+        let mut loaded = open_vault(&file, &key).unwrap();
+        assert!(matches!(
+            loaded.attach_shards(|_| None),
+            Err(VaultError::MissingShard(_))
+        ));
+        // A vault with a project missing cannot be saved over its file
+        assert!(matches!(
+            save_vault_with_key(&loaded, &key),
+            Err(VaultError::MissingShard(_))
+        ));
+
+        let mut loaded = open_vault(&file, &key).unwrap();
+        let (_, other) = &shards[0];
+        assert!(matches!(
+            loaded.attach_shards(|_| Some(other.clone())),
+            Err(VaultError::CorruptedVault)
+        ));
+    }
+
+    #[test]
+    fn test_trash_and_restore() {
+        let (saved, key) = two_project_vault(b"test-password");
+        let mut vault = load_vault_with_key(&saved, &key).unwrap();
+        vault
+            .add_secret_in("a", Some("prod"), "DB", b"db", &key, None)
+            .unwrap();
+        vault.add_attachment("b", "cert.pem", b"cert", &key).unwrap();
+
+        let secret = vault.trash_secret("a", None, "KEY").unwrap();
+        let env = vault.trash_environment("a", "prod").unwrap();
+        let project = vault.trash_project("b", &key).unwrap();
+        assert_eq!((secret, env, project), (1, 2, 3));
+        assert!(vault.get_secret("a", "KEY", &key).is_err());
+        assert_eq!(vault.project_names(), ["a"]);
+        assert_eq!(vault.trash()[2].item.blob_ids().len(), 1);
+
+        // The trash survives a save, and restores what was removed
+        let saved = save_vault_with_key(&vault, &key).unwrap();
+        let mut vault = load_vault_with_key(&saved, &key).unwrap();
+        assert_eq!(vault.trash().len(), 3);
+
+        vault.add_secret("a", "KEY", b"again", &key, None).unwrap();
+        assert!(matches!(
+            vault.restore_from_trash(secret),
+            Err(VaultError::SecretAlreadyExists(_))
+        ));
+        vault.remove_secret("a", "KEY").unwrap();
+
+        for id in [secret, env, project] {
+            vault.restore_from_trash(id).unwrap();
+        }
+        vault.unseal_all(&key).unwrap();
+        assert_eq!(vault.get_secret("a", "KEY", &key).unwrap().expose(), b"a");
+        assert_eq!(
+            vault
+                .get_secret_in("a", Some("prod"), "DB", &key)
+                .unwrap()
+                .expose(),
+            b"db"
+        );
+        assert_eq!(vault.get_secret("b", "KEY", &key).unwrap().expose(), b"b");
+        assert!(vault.trash().is_empty());
+        assert!(matches!(
+            vault.restore_from_trash(secret),
+            Err(VaultError::TrashEntryNotFound(1))
+        ));
+    }
+
+    #[test]
+    fn test_value_size() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("app", "KEY", b"12345", &key, None).unwrap();
+        vault
+            .add_alias("app", None, "LINK", SecretRef::new("app", None, "KEY"))
+            .unwrap();
+
+        assert_eq!(vault.projects["app"].secrets["KEY"].value_size(), Some(5));
+        assert_eq!(vault.projects["app"].secrets["LINK"].value_size(), None);
+    }
+
+    #[test]
+    fn test_provenance() {
+        let (mut vault, key) = create_vault(b"test-password").unwrap();
+        vault.init_project("app").unwrap();
+        vault.add_secret("app", "OLD", b"value", &key, None).unwrap();
+
+        let provenance = Provenance {
+            hostname: Some("laptop".to_string()),
+            user: Some("alice".to_string()),
+            version: "1.2.3".to_string(),
+        };
+        vault.set_provenance(provenance.clone());
+        vault.add_secret_in("app", Some("prod"), "NEW", b"value", &key, None).unwrap();
+        vault
+            .add_alias("app", None, "LINK", SecretRef::new("app", None, "OLD"))
+            .unwrap();
+
+        let saved = save_vault_with_key(&vault, &key).unwrap();
+        let loaded = load_vault_with_key(&saved, &key).unwrap();
+        let project = &loaded.projects["app"];
+        assert_eq!(project.secrets["OLD"].provenance, None);
+        assert_eq!(project.environments["prod"]["NEW"].provenance, Some(provenance.clone()));
+        assert_eq!(project.secrets["LINK"].provenance, Some(provenance.clone()));
+        assert_eq!(provenance.to_string(), "alice on laptop (vx 1.2.3)");
+    }
+
+    #[test]
+    fn test_shared_values() {
+        let (mut vault, key) = create_vault(b"test-password").unwrap();
+        vault.init_project("app").unwrap();
+        let bundle = vec![b'x'; 4096];
+        vault.add_secret("app", "CA_BUNDLE", &bundle, &key, None).unwrap();
+        vault
+            .add_secret_in("app", Some("prod"), "CA_BUNDLE", &bundle, &key, None)
+            .unwrap();
+        vault.add_secret("app", "SHORT", b"short", &key, None).unwrap();
+        vault.add_secret("app", "SHORT_TOO", b"short", &key, None).unwrap();
+
+        let project = &vault.projects["app"];
+        let (default, prod) = (
+            &project.secrets["CA_BUNDLE"],
+            &project.environments["prod"]["CA_BUNDLE"],
+        );
+        assert!(default.value_hash.is_some());
+        assert_eq!(default.encrypted_value, prod.encrypted_value);
+        assert_eq!(project.secrets["SHORT"].value_hash, None);
+        assert_ne!(
+            project.secrets["SHORT"].encrypted_value,
+            project.secrets["SHORT_TOO"].encrypted_value
+        );
+
+        // The sealed project holds the bundle once
+        let saved = save_vault_with_key(&vault, &key).unwrap();
+        assert!(saved.len() < 2 * bundle.len());
+        let mut loaded = load_vault_with_key(&saved, &key).unwrap();
+        assert!(loaded.projects["app"].shared_values.is_empty());
+        assert_eq!(loaded.get_secret("app", "CA_BUNDLE", &key).unwrap().expose(), &bundle[..]);
+        assert_eq!(
+            loaded
+                .get_secret_in("app", Some("prod"), "CA_BUNDLE", &key)
+                .unwrap()
+                .expose(),
+            &bundle[..]
+        );
+
+        // Re-encrypting keeps the values shared
+        loaded.set_project_passphrase("app", b"extra", &key).unwrap();
+        let project = &loaded.projects["app"];
+        assert_eq!(
+            project.secrets["CA_BUNDLE"].encrypted_value,
+            project.environments["prod"]["CA_BUNDLE"].encrypted_value
+        );
+        assert_ne!(project.secrets["CA_BUNDLE"].value_hash, default.value_hash);
+        assert_eq!(loaded.get_secret("app", "CA_BUNDLE", &key).unwrap().expose(), &bundle[..]);
+        let saved = save_vault_with_key(&loaded, &key).unwrap();
+        assert!(saved.len() < 2 * bundle.len());
+    }
+
+    #[test]
+    fn test_set_ttl() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("app", "KEY", b"value", &key, Some(60)).unwrap();
+        vault
+            .add_alias("app", None, "LINK", SecretRef::new("app", None, "KEY"))
+            .unwrap();
+
+        let now = ttl::current_timestamp();
+        let expires_at = vault.set_ttl_in("app", None, "KEY", Some(3600)).unwrap();
+        assert!(expires_at.unwrap() >= now + 3600);
+        assert_eq!(vault.projects["app"].secrets["KEY"].expires_at, expires_at);
+        assert_eq!(vault.get_secret("app", "KEY", &key).unwrap().expose(), b"value");
+
+        assert_eq!(vault.set_ttl_in("app", None, "KEY", None).unwrap(), None);
+        assert_eq!(vault.projects["app"].secrets["KEY"].expires_at, None);
+
+        assert!(matches!(
+            vault.set_ttl_in("app", None, "LINK", Some(60)),
+            Err(VaultError::FixedExpiry(..))
+        ));
+        assert!(matches!(
+            vault.set_ttl_in("app", None, "MISSING", Some(60)),
+            Err(VaultError::SecretNotFound(_))
+        ));
+
+        let too_long = ttl::parse_ttl("30500568904943w").unwrap();
+        assert!(matches!(
+            vault.set_ttl_in("app", None, "KEY", Some(too_long)),
+            Err(VaultError::TtlTooLong(_))
+        ));
+        assert!(matches!(
+            vault.add_secret("app", "OTHER", b"value", &key, Some(too_long)),
+            Err(VaultError::TtlTooLong(_))
+        ));
+        assert_eq!(vault.projects["app"].secrets["KEY"].expires_at, None);
+        assert!(!vault.projects["app"].secrets.contains_key("OTHER"));
+    }
+
+    #[test]
+    fn test_project_rules_are_enforced() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("app", "legacy", b"changeme", &key, None).unwrap();
 
-        let encrypted = EncryptedData {
-            ciphertext: identity.encrypted_private_key.clone(),
-            nonce: identity.nonce,
+        let rules = SecretRules {
+            key_pattern: Some("[A-Z_]+".to_string()),
+            require_ttl: true,
+            max_ttl: Some(3600),
+            max_value_size: Some(8),
+            forbidden_patterns: vec!["changeme".to_string()],
         };
+        let invalid = SecretRules {
+            key_pattern: Some("[".to_string()),
+            ..rules.clone()
+        };
+        assert!(matches!(
+            vault.set_rules("app", invalid),
+            Err(VaultError::InvalidRule(..))
+        ));
+        vault.set_rules("app", rules).unwrap();
 
-        let private_key = crypto::decrypt(&encrypted, encryption_key)?;
+        let broken = |result: Result<(), VaultError>| {
+            matches!(result, Err(VaultError::RuleViolation(..)))
+        };
+        assert!(broken(vault.add_secret("app", "lower", b"v", &key, Some(60))));
+        assert!(broken(vault.add_secret("app", "KEY", b"v", &key, None)));
+        assert!(broken(vault.add_secret("app", "KEY", b"v", &key, Some(3660))));
+        assert!(broken(vault.add_secret("app", "KEY", b"too long!", &key, Some(60))));
+        assert!(broken(vault.add_secret("app", "KEY", b"changeme", &key, Some(60))));
+        assert!(broken(vault.set_ttl_in("app", None, "legacy", None).map(|_| ())));
+        assert!(broken(vault.set_ttl_in("app", None, "legacy", Some(7200)).map(|_| ())));
+        assert!(!vault.projects["app"].secrets.contains_key("KEY"));
 
-        Ok((identity.public_key.clone(), private_key))
+        vault.add_secret("app", "KEY", b"v", &key, Some(60)).unwrap();
+        vault.add_template_in("app", None, "URL", b"{{KEY}}", &key).unwrap();
+        // Secrets stored before the rules were set are kept
+        assert_eq!(vault.get_secret("app", "legacy", &key).unwrap().expose(), b"changeme");
     }
 
-    /// Adds an SSH server configuration to the vault.
-    pub fn add_ssh_server(
-        &mut self,
-        name: &str,
-        username: String,
-        ip_address: String,
-        identity_name: String,
-    ) -> Result<(), VaultError> {
-        // Validate that the identity exists
-        if !self.ssh_identities.contains_key(&identity_name) {
-            return Err(VaultError::IdentityNotFound(identity_name));
+    #[test]
+    fn test_remove_matching() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+        let key = [0u8; KEY_SIZE];
+        for name in ["TEMP_A", "TEMP_B", "KEEP"] {
+            vault.add_secret("app", name, b"v", &key, None).unwrap();
         }
+        vault
+            .add_secret_in("app", Some("ci"), "TEMP_C", b"v", &key, None)
+            .unwrap();
 
-        let server = SshServerConfig {
-            name: name.to_string(),
-            username,
-            ip_address,
-            identity_name,
-            created_at: ttl::current_timestamp(),
-        };
+        assert_eq!(
+            vault.matching_keys("app", None, "TEMP_*").unwrap(),
+            ["TEMP_A", "TEMP_B"]
+        );
+        let removed = vault.remove_matching("app", None, "TEMP_*").unwrap();
+        assert_eq!(
+            removed,
+            [("TEMP_A".to_string(), 1), ("TEMP_B".to_string(), 2)]
+        );
+        assert_eq!(vault.matching_keys("app", None, "*").unwrap(), ["KEEP"]);
+        assert_eq!(vault.trash().len(), 2);
 
-        self.ssh_servers.insert(name.to_string(), server);
-        Ok(())
+        // Other environments are left alone, and an emptied one is removed
+        assert!(vault.remove_matching("app", None, "TEMP_*").unwrap().is_empty());
+        vault.remove_matching("app", Some("ci"), "TEMP_*").unwrap();
+        assert!(vault.projects["app"].environments.is_empty());
+        assert!(matches!(
+            vault.remove_matching("missing", None, "*"),
+            Err(VaultError::ProjectNotFound(_))
+        ));
     }
 
-    /// Retrieves an SSH server configuration.
-    pub fn get_ssh_server(&self, name: &str) -> Result<&SshServerConfig, VaultError> {
-        self.ssh_servers
-            .get(name)
-            .ok_or_else(|| VaultError::ServerNotFound(name.to_string()))
-    }
+    #[test]
+    fn test_trashed_secret_follows_passphrase_change() {
+        let mut vault = Vault::new();
+        vault.init_project("prod").unwrap();
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("prod", "KEY", b"value", &key, None).unwrap();
 
-    /// Checks if an SSH server configuration exists.
-    pub fn has_ssh_server(&self, name: &str) -> bool {
-        self.ssh_servers.contains_key(name)
+        let id = vault.trash_secret("prod", None, "KEY").unwrap();
+        vault.set_project_passphrase("prod", b"extra", &key).unwrap();
+        vault.restore_from_trash(id).unwrap();
+        assert_eq!(vault.get_secret("prod", "KEY", &key).unwrap().expose(), b"value");
     }
 
-    /// Removes a project and all its secrets.
-    pub fn remove_project(&mut self, name: &str) -> Result<(), VaultError> {
-        if self.projects.remove(name).is_some() {
-            Ok(())
-        } else {
-            Err(VaultError::ProjectNotFound(name.to_string()))
-        }
-    }
+    #[test]
+    fn test_undo_write() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+        let key = [0u8; KEY_SIZE];
+        assert!(matches!(vault.undo(), Err(VaultError::NothingToUndo)));
 
-    /// Removes a secret from a project.
-    pub fn remove_secret(&mut self, project: &str, key: &str) -> Result<(), VaultError> {
-        let proj = self
-            .projects
-            .get_mut(project)
-            .ok_or_else(|| VaultError::ProjectNotFound(project.to_string()))?;
+        let add = |vault: &mut Vault, value: &[u8]| {
+            let location = SecretRef::new("app", Some("prod"), "KEY");
+            vault.track_write(Operation::Add, location, |vault| {
+                vault.add_secret_in("app", Some("prod"), "KEY", value, &key, None)
+            })
+        };
 
-        if proj.secrets.remove(key).is_some() {
-            Ok(())
-        } else {
-            Err(VaultError::SecretNotFound(key.to_string()))
-        }
-    }
-}
+        // Undoing an overwrite brings the old value back
+        add(&mut vault, b"old").unwrap();
+        add(&mut vault, b"new").unwrap();
+        let record = vault.undo().unwrap();
+        assert_eq!(record.operation, Operation::Add);
+        assert_eq!(
+            vault
+                .get_secret_in("app", Some("prod"), "KEY", &key)
+                .unwrap()
+                .expose(),
+            b"old"
+        );
+        assert!(vault.last_change().is_none());
 
-impl Default for Vault {
-    fn default() -> Self {
-        Self::new()
+        // Undoing an add removes the secret, and its environment with it
+        vault.remove_secret_in("app", Some("prod"), "KEY").unwrap();
+        add(&mut vault, b"new").unwrap();
+        vault.undo().unwrap();
+        assert!(vault.projects["app"].environments.is_empty());
+
+        // A change made since without tracking blocks the undo
+        add(&mut vault, b"new").unwrap();
+        vault
+            .add_secret_in("app", Some("prod"), "KEY", b"untracked", &key, None)
+            .unwrap();
+        assert!(matches!(vault.undo(), Err(VaultError::UndoStale(_))));
     }
-}
 
-/// Serializes and encrypts a vault for storage.
-///
-/// # File Format
-/// ```text
-/// +----------------+------------------+----------------------+
-/// | Header (16B)   | Salt (32B)       | Encrypted Payload    |
-/// +----------------+------------------+----------------------+
-/// | Magic: "VX01"  | Argon2 salt      | AES-256-GCM encrypted|
-/// | Version: u32   |                  | JSON vault data      |
-/// | Reserved: 8B   |                  | + Auth Tag (16B)     |
-/// +----------------+------------------+----------------------+
-/// ```
-/// Saves a vault with optional salt preservation.
-/// If salt is provided, it will be used (for updating existing vaults).
-/// If salt is None, a new salt will be generated (for creating new vaults).
-pub fn save_vault_with_salt(
-    vault: &Vault,
-    password: &[u8],
-    salt: Option<&[u8; SALT_SIZE]>,
-) -> Result<Vec<u8>, VaultError> {
-    // Use provided salt or generate new one
-    let salt = if let Some(s) = salt {
-        s.clone()
-    } else {
-        crypto::generate_salt()
-    };
+    #[test]
+    fn test_undo_remove() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+        let key = [0u8; KEY_SIZE];
+        vault.add_secret("app", "KEY", b"value", &key, None).unwrap();
 
-    // Derive encryption key
-    let key = crypto::derive_key(password, &salt)?;
+        let id = vault.trash_secret("app", None, "KEY").unwrap();
+        let record = vault.undo().unwrap();
+        assert!(matches!(record.change, Change::Trash { id: undone } if undone == id));
+        assert_eq!(vault.get_secret("app", "KEY", &key).unwrap().expose(), b"value");
+        assert!(vault.trash().is_empty());
 
-    // Serialize vault to JSON
-    let vault_data = VaultData {
-        version: vault.version,
-        projects: vault.projects.clone(),
-        ssh_identities: vault.ssh_identities.clone(),
-        ssh_servers: vault.ssh_servers.clone(),
-    };
+        vault.trash_secret("app", None, "KEY").unwrap();
+        vault.empty_trash();
+        assert!(matches!(vault.undo(), Err(VaultError::UndoStale(_))));
+    }
 
-    let json = serde_json::to_vec(&vault_data)
-        .map_err(|e| VaultError::SerializationError(e.to_string()))?;
+    #[test]
+    fn test_export_and_import_project() {
+        let key = [0u8; KEY_SIZE];
+        let export_key = [1u8; KEY_SIZE];
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+        vault.add_secret("app", "KEY", b"value", &key, Some(60)).unwrap();
+        vault
+            .add_alias("app", None, "OTHER", SecretRef::new("app", None, "KEY"))
+            .unwrap();
+
+        let project = vault.export_project("app", &key, &export_key).unwrap();
+        let mut other = Vault::new();
+        other
+            .import_project("app-old", project.clone(), &export_key, &key)
+            .unwrap();
+        let value = other.get_secret("app-old", "OTHER", &key).unwrap();
+        assert_eq!(value.expose(), b"value");
+        assert_eq!(
+            other.projects["app-old"].secrets["KEY"].expires_at,
+            vault.projects["app"].secrets["KEY"].expires_at
+        );
 
-    // Encrypt the JSON
-    let encrypted = crypto::encrypt(&json, &key)?;
+        assert!(matches!(
+            vault.import_project("app", project, &export_key, &key),
+            Err(VaultError::ProjectAlreadyExists(_))
+        ));
+    }
 
-    // Build the file
-    let mut output = Vec::with_capacity(HEADER_SIZE + SALT_SIZE + encrypted.ciphertext.len());
+    #[test]
+    fn test_restore_snapshot() {
+        let (saved, key) = two_project_vault(b"test-password");
+        let snapshot = open_vault(&saved, &key).unwrap();
 
-    // Header
-    output.extend_from_slice(VAULT_MAGIC);
-    output.extend_from_slice(&VAULT_VERSION.to_le_bytes());
-    output.extend_from_slice(&[0u8; 8]); // Reserved
+        let mut vault = open_vault(&saved, &key).unwrap();
+        vault.unseal_all(&key).unwrap();
+        vault.init_project("c").unwrap();
+        vault.add_secret("a", "NEW", b"x", &key, None).unwrap();
+        vault.record(Operation::Add, Some("a"), Some("NEW"));
+        vault.generation = 7;
 
-    // Salt
-    output.extend_from_slice(&salt);
+        vault.restore_snapshot(snapshot);
+        assert_eq!(vault.generation, 7);
+        assert_eq!(vault.access_log().len(), 1);
+        assert_eq!(vault.project_names(), vec!["a", "b"]);
+        assert!(vault.last_change().is_none());
 
-    // Nonce + Ciphertext
-    output.extend_from_slice(&encrypted.nonce);
-    output.extend_from_slice(&encrypted.ciphertext);
+        vault.unseal_all(&key).unwrap();
+        assert!(vault.get_secret("a", "NEW", &key).is_err());
+    }
 
-    Ok(output)
-}
+    #[test]
+    fn test_purge_trash() {
+        let mut vault = Vault::new();
+        vault.init_project("app").unwrap();
+        let key = [0u8; KEY_SIZE];
+        for name in ["OLD", "NEW"] {
+            vault.add_secret("app", name, b"x", &key, None).unwrap();
+            vault.trash_secret("app", None, name).unwrap();
+        }
+        vault.trash[0].deleted_at -= 10 * 86400;
 
-/// Convenience function: saves a new vault with generated salt.
-pub fn save_vault(vault: &Vault, password: &[u8]) -> Result<Vec<u8>, VaultError> {
-    save_vault_with_salt(vault, password, None)
-}
+        let now = ttl::current_timestamp();
+        assert!(vault.purge_trash(now - 30 * 86400).is_empty());
 
-/// Loads and decrypts a vault from storage.
-pub fn load_vault(data: &[u8], password: &[u8]) -> Result<Vault, VaultError> {
-    // Minimum size check
-    let min_size = HEADER_SIZE + SALT_SIZE + NONCE_SIZE;
-    if data.len() < min_size {
-        return Err(VaultError::CorruptedVault);
-    }
+        let purged = vault.purge_trash(now - 7 * 86400);
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].item.to_string(), "secret app/OLD");
+        assert_eq!(vault.trash().len(), 1);
 
-    // Verify magic
-    if &data[0..4] != VAULT_MAGIC {
-        return Err(VaultError::InvalidFormat("Invalid magic bytes".to_string()));
+        assert_eq!(vault.empty_trash().len(), 1);
+        assert!(vault.trash().is_empty());
     }
 
-    // Read version
-    let version = u32::from_le_bytes(data[4..8].try_into().unwrap());
-    if version != VAULT_VERSION {
-        return Err(VaultError::InvalidFormat(format!(
-            "Unsupported version: {}",
-            version
-        )));
-    }
+    #[test]
+    fn test_sealed_project_bound_to_its_name() {
+        let (saved, key) = two_project_vault(b"test-password");
 
-    // Extract salt
-    let salt: [u8; SALT_SIZE] = data[HEADER_SIZE..HEADER_SIZE + SALT_SIZE]
-        .try_into()
-        .map_err(|_| VaultError::CorruptedVault)?;
+        let mut vault = open_vault(&saved, &key).unwrap();
+        let a = vault.sealed_projects["a"].clone();
+        vault.sealed_projects.insert("b".to_string(), a);
 
-    // Derive key
-    let key = crypto::derive_key(password, &salt)?;
+        assert!(matches!(
+            vault.unseal_project("b", &key),
+            Err(VaultError::CorruptedVault)
+        ));
+    }
 
-    // Extract nonce and ciphertext
-    let nonce_start = HEADER_SIZE + SALT_SIZE;
-    let nonce: [u8; NONCE_SIZE] = data[nonce_start..nonce_start + NONCE_SIZE]
-        .try_into()
-        .map_err(|_| VaultError::CorruptedVault)?;
+    #[test]
+    fn test_save_requires_matching_credential() {
+        let (saved, key) = two_project_vault(b"test-password");
+        let vault = open_vault(&saved, &key).unwrap();
 
-    let ciphertext = data[nonce_start + NONCE_SIZE..].to_vec();
+        assert!(matches!(
+            save_vault(&vault, b"other-password"),
+            Err(VaultError::AuthenticationFailed)
+        ));
+    }
 
-    let encrypted = EncryptedData { ciphertext, nonce };
+    #[test]
+    fn test_any_key_slot_unlocks_vault() {
+        let (saved, key) = two_project_vault(b"test-password");
+        let (mut vault, _) = unlock_vault(&saved, b"test-password").unwrap();
 
-    // Decrypt
-    let json = crypto::decrypt(&encrypted, &key).map_err(|_| VaultError::AuthenticationFailed)?;
+        let id = vault
+            .add_key_slot(SlotKind::Keyfile, "ci", b"keyfile-bytes", &key)
+            .unwrap();
+        assert_eq!(id, 1);
+        let saved = save_vault_with_key(&vault, &key).unwrap();
 
-    // Deserialize
-    let vault_data: VaultData =
-        serde_json::from_slice(&json).map_err(|e| VaultError::SerializationError(e.to_string()))?;
+        let loaded = load_vault(&saved, b"keyfile-bytes").unwrap();
+        assert_eq!(loaded.get_secret("b", "KEY", &key).unwrap().expose(), b"b");
+        assert_eq!(*unlock_key(&saved, b"test-password").unwrap(), *key);
+    }
 
-    Ok(Vault {
-        version: vault_data.version,
-        projects: vault_data.projects,
-        ssh_identities: vault_data.ssh_identities,
-        ssh_servers: vault_data.ssh_servers,
-    })
-}
+    #[test]
+    fn test_removed_key_slot_no_longer_unlocks() {
+        let (saved, key) = two_project_vault(b"old-password");
+        let (mut vault, _) = unlock_vault(&saved, b"old-password").unwrap();
 
-// Custom serde modules for binary data
-mod base64_serde {
-    use base64::{engine::general_purpose::STANDARD, Engine};
-    use serde::{Deserialize, Deserializer, Serializer};
+        vault
+            .add_key_slot(SlotKind::Password, "new", b"new-password", &key)
+            .unwrap();
+        vault.remove_key_slot(0).unwrap();
+        let saved = save_vault_with_key(&vault, &key).unwrap();
 
-    pub fn serialize<S>(bytes: &Vec<u8>, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        serializer.serialize_str(&STANDARD.encode(bytes))
+        assert!(matches!(
+            unlock_vault(&saved, b"old-password"),
+            Err(VaultError::AuthenticationFailed)
+        ));
+        assert!(load_vault(&saved, b"new-password").is_ok());
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let s = String::deserialize(deserializer)?;
-        STANDARD.decode(&s).map_err(serde::de::Error::custom)
-    }
-}
+    #[test]
+    fn test_rewrap_key_slot() {
+        let (saved, key) = two_project_vault(b"password");
+        let (mut vault, _) = unlock_vault(&saved, b"password").unwrap();
 
-mod nonce_serde {
-    use super::NONCE_SIZE;
-    use base64::{engine::general_purpose::STANDARD, Engine};
-    use serde::{Deserialize, Deserializer, Serializer};
+        assert!(matches!(
+            vault.rewrap_key_slot(0, b"wrong", KdfParams::BROWSER),
+            Err(VaultError::AuthenticationFailed)
+        ));
+        vault.rewrap_key_slot(0, b"password", KdfParams::BROWSER).unwrap();
+        assert_eq!(vault.key_slots()[0].kdf, KdfParams::BROWSER);
 
-    pub fn serialize<S>(bytes: &[u8; NONCE_SIZE], serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        serializer.serialize_str(&STANDARD.encode(bytes))
+        let saved = save_vault_with_key(&vault, &key).unwrap();
+        let (loaded, unlocked) = unlock_vault(&saved, b"password").unwrap();
+        assert_eq!(*unlocked, *key);
+        assert_eq!(loaded.key_slots()[0].kdf, KdfParams::BROWSER);
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<[u8; NONCE_SIZE], D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let s = String::deserialize(deserializer)?;
-        let bytes = STANDARD.decode(&s).map_err(serde::de::Error::custom)?;
-        bytes
-            .try_into()
-            .map_err(|_| serde::de::Error::custom("Invalid nonce length"))
+    #[test]
+    fn test_new_slots_keep_the_strongest_kdf() {
+        let (mut vault, key) = create_vault_with_kdf(b"first", KdfParams::BROWSER).unwrap();
+        let id = vault
+            .add_key_slot(SlotKind::Password, "", b"second", &key)
+            .unwrap();
+        assert_eq!(
+            vault.key_slots()[id as usize].kdf,
+            KdfParams::for_this_platform()
+        );
+
+        let heavier = KdfParams {
+            iterations: KdfParams::STANDARD.iterations + 1,
+            ..KdfParams::STANDARD
+        };
+        vault.rewrap_key_slot(0, b"first", heavier).unwrap();
+        let id = vault
+            .add_key_slot(SlotKind::RecoveryCode, "", b"third", &key)
+            .unwrap();
+        assert_eq!(vault.key_slots()[id as usize].kdf, heavier);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_key_slot_limits() {
+        let (mut vault, key) = create_vault(b"test-password").unwrap();
+
+        assert!(matches!(vault.remove_key_slot(0), Err(VaultError::LastKeySlot)));
+        assert!(matches!(
+            vault.remove_key_slot(7),
+            Err(VaultError::KeySlotNotFound(7))
+        ));
+
+        for i in 1..MAX_KEY_SLOTS {
+            vault
+                .add_key_slot(SlotKind::RecoveryCode, "", &[i as u8], &key)
+                .unwrap();
+        }
+        assert!(matches!(
+            vault.add_key_slot(SlotKind::RecoveryCode, "", b"one more", &key),
+            Err(VaultError::KeySlotsFull(MAX_KEY_SLOTS))
+        ));
+    }
 
     #[test]
-    fn test_create_vault() {
-        let vault = Vault::new();
-        assert_eq!(vault.version, VAULT_VERSION);
-        assert!(vault.projects.is_empty());
-        assert!(vault.ssh_identities.is_empty());
+    fn test_emergency_grants_stored_in_header() {
+        let (mut vault, key) = create_vault(b"test-password").unwrap();
+        let (secret, public) = crate::emergency::generate_keypair();
+        let id = vault.add_emergency_grant("alice", &public, 0, &key).unwrap();
+        vault.veto_emergency_grant(id, 5).unwrap();
+        assert!(vault.note_emergency_request(id, 7));
+        // A request file rewritten to an earlier date is not recorded
+        assert!(!vault.note_emergency_request(id, 6));
+        let saved = save_vault_with_key(&vault, &key).unwrap();
+
+        // Readable without the key, and authenticated with the payload
+        let grants = read_emergency_grants(&saved).unwrap();
+        assert_eq!(grants.len(), 1);
+        assert!(grants[0].is_for(&secret));
+        assert_eq!(grants[0].vetoed_at, Some(5));
+        assert_eq!(grants[0].requested_at, Some(7));
+        let loaded = load_vault_with_key(&saved, &key).unwrap();
+        assert_eq!(loaded.emergency_grants()[0].id, id);
+
+        let export = export_read_only(&loaded, &key, b"ci-password").unwrap();
+        assert!(read_emergency_grants(&export).unwrap().is_empty());
+
+        vault.remove_emergency_grant(id).unwrap();
+        assert!(matches!(
+            vault.remove_emergency_grant(id),
+            Err(VaultError::EmergencyGrantNotFound(_))
+        ));
     }
 
     #[test]
-    fn test_init_project() {
-        let mut vault = Vault::new();
-        vault.init_project("my-project").unwrap();
+    fn test_tampered_slot_table_detected() {
+        let (mut vault, key) = create_vault(b"test-password").unwrap();
+        vault
+            .add_key_slot(SlotKind::Password, "alice", b"other", &key)
+            .unwrap();
+        let saved = save_vault_with_key(&vault, &key).unwrap();
 
-        assert!(vault.projects.contains_key("my-project"));
+        // Rename the slot label without touching its wrapped key
+        let label = saved.windows(5).position(|w| w == b"alice").unwrap();
+        let mut tampered = saved.clone();
+        tampered[label] = b'A';
+
+        assert!(unlock_key(&tampered, b"test-password").is_ok());
+        assert!(matches!(
+            load_vault(&tampered, b"test-password"),
+            Err(VaultError::AuthenticationFailed)
+        ));
     }
 
     #[test]
-    fn test_duplicate_project() {
-        let mut vault = Vault::new();
-        vault.init_project("my-project").unwrap();
+    fn test_unlock_reports_progress_per_slot() {
+        let (mut vault, key) = create_vault_with_kdf(b"first", KdfParams::BROWSER).unwrap();
+        vault
+            .add_key_slot_with_kdf(SlotKind::Password, "", b"second", &key, KdfParams::BROWSER)
+            .unwrap();
+        let saved = save_vault_with_key(&vault, &key).unwrap();
 
-        let result = vault.init_project("my-project");
-        assert!(matches!(result, Err(VaultError::ProjectAlreadyExists(_))));
+        let mut reported = Vec::new();
+        let (_, unlocked) =
+            unlock_vault_with_progress(&saved, b"second", |p| reported.push(p)).unwrap();
+        assert_eq!(*unlocked, *key);
+        assert_eq!(reported, vec![0.0, 0.5, 1.0]);
+
+        let mut reported = Vec::new();
+        assert!(unlock_key_with_progress(&saved, b"wrong", |p| reported.push(p)).is_err());
+        assert_eq!(reported, vec![0.0, 0.5, 1.0]);
+
+        let (loaded, _) = unlock_vault(&saved, b"first").unwrap();
+        assert!(loaded.key_slots().iter().all(|slot| slot.kdf == KdfParams::BROWSER));
     }
 
     #[test]
-    fn test_add_and_get_secret() {
+    fn test_origin_grants() {
         let mut vault = Vault::new();
-        vault.init_project("test").unwrap();
-
+        vault.init_project("web").unwrap();
         let key = [0u8; KEY_SIZE];
-        let secret_value = b"my-secret-value";
+        vault.add_secret("web", "TOKEN", b"t", &key, None).unwrap();
+        let token = SecretRef::new("web", None, "TOKEN");
 
-        vault
-            .add_secret("test", "DB_PASSWORD", secret_value, &key, None)
-            .unwrap();
+        vault.allow_origin("HTTPS://GitHub.com/", token.clone()).unwrap();
+        vault.allow_origin("https://github.com", token.clone()).unwrap();
+        assert_eq!(
+            vault.origin_allowed_secrets("https://github.com"),
+            std::slice::from_ref(&token)
+        );
+        assert!(vault.origin_allows("https://github.com", &token));
+        assert!(!vault.origin_allows("http://github.com", &token));
+        assert!(!vault.origin_allows("https://github.com.evil.example", &token));
 
-        let retrieved = vault.get_secret("test", "DB_PASSWORD", &key).unwrap();
-        assert_eq!(retrieved, secret_value);
+        assert!(matches!(
+            vault.allow_origin("https://github.com", SecretRef::new("web", None, "MISSING")),
+            Err(VaultError::SecretNotFound(_))
+        ));
+        for invalid in ["github.com", "file:///etc", "https://a.com/path", "https://"] {
+            assert!(matches!(
+                vault.allow_origin(invalid, token.clone()),
+                Err(VaultError::InvalidOrigin(_))
+            ));
+        }
+
+        vault.revoke_origin("https://github.com", Some(&token)).unwrap();
+        assert!(vault.origin_grants.is_empty());
+        assert!(matches!(
+            vault.revoke_origin("https://github.com", None),
+            Err(VaultError::OriginNotAllowed(_))
+        ));
     }
 
     #[test]
-    fn test_secret_not_found() {
-        let vault = Vault::new();
-        let key = [0u8; KEY_SIZE];
+    fn test_origin_grants_round_trip() {
+        let (mut vault, key) = create_vault(b"pw").unwrap();
+        vault.init_project("web").unwrap();
+        vault.add_secret("web", "TOKEN", b"t", &key, None).unwrap();
+        let token = SecretRef::new("web", None, "TOKEN");
+        vault.allow_origin("https://example.com", token.clone()).unwrap();
 
-        let result = vault.get_secret("nonexistent", "KEY", &key);
-        assert!(matches!(result, Err(VaultError::ProjectNotFound(_))));
+        let saved = save_vault_with_key(&vault, &key).unwrap();
+        let (loaded, _) = unlock_vault(&saved, b"pw").unwrap();
+        assert!(loaded.origin_allows("https://example.com", &token));
+
+        let exported = export_read_only(&vault, &key, b"ci").unwrap();
+        let (export, _) = unlock_vault(&exported, b"ci").unwrap();
+        assert!(export.origin_grants.is_empty());
     }
 
     #[test]
-    fn test_vault_save_load_roundtrip() {
+    fn test_remove_expired() {
         let mut vault = Vault::new();
-        vault.init_project("test").unwrap();
+        vault.init_project("app").unwrap();
+        let key = [0u8; KEY_SIZE];
 
-        let password = b"test-password";
-        let key = crypto::derive_key(password, &crypto::generate_salt()).unwrap();
+        let day = 86400;
+        let secrets = [
+            (None, "LIVE", Some(30 * day)),
+            (None, "FOREVER", None),
+            (None, "OLD", Some(3600)),
+            (Some("prod"), "OLD", Some(3600)),
+            (Some("dev"), "RECENT", Some(5 * day)),
+        ];
+        for (env, name, ttl_seconds) in secrets {
+            vault
+                .add_secret_in("app", env, name, b"x", &key, ttl_seconds)
+                .unwrap();
+        }
+
+        // Ten days on, OLD expired about ten days ago and RECENT five
+        let now = ttl::current_timestamp() + 10 * day;
+
+        // Within the grace window nothing old enough has expired
+        assert!(vault.remove_expired(now, 30 * day).is_empty());
+
+        let removed = vault.remove_expired(now, 7 * day);
+        assert_eq!(
+            removed,
+            vec![
+                SecretRef::new("app", None, "OLD"),
+                SecretRef::new("app", Some("prod"), "OLD"),
+            ]
+        );
 
+        let project = vault.project("app").unwrap();
+        let mut keys: Vec<&String> = project.secrets.keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["FOREVER", "LIVE"]);
+        assert_eq!(project.environment_names(), ["dev"]);
+    }
+
+    #[test]
+    fn test_last_accessed() {
+        let (mut vault, key) = create_vault(b"pw").unwrap();
+        vault.init_project("web").unwrap();
+        vault.add_secret("web", "USER", b"admin", &key, None).unwrap();
+        vault.add_secret("web", "UNUSED", b"x", &key, None).unwrap();
         vault
-            .add_secret("test", "SECRET", b"value", &key, None)
+            .add_alias("web", None, "LOGIN", SecretRef::new("web", None, "USER"))
             .unwrap();
 
-        let saved = save_vault(&vault, password).unwrap();
-        let loaded = load_vault(&saved, password).unwrap();
+        let accessed = |vault: &Vault, name: &str| {
+            vault.projects["web"].secrets[name].last_accessed.get()
+        };
+        assert_eq!(accessed(&vault, "USER"), None);
 
-        assert_eq!(loaded.version, vault.version);
-        assert!(loaded.projects.contains_key("test"));
+        vault.get_secret("web", "LOGIN", &key).unwrap();
+        let now = ttl::current_timestamp();
+        assert!(accessed(&vault, "LOGIN").is_some_and(|t| t <= now && t + 5 >= now));
+        assert_eq!(accessed(&vault, "USER"), accessed(&vault, "LOGIN"));
+        assert_eq!(accessed(&vault, "UNUSED"), None);
+
+        // Failed reads leave it alone
+        assert!(vault.get_secret("web", "MISSING", &key).is_err());
+
+        let saved = save_vault_with_key(&vault, &key).unwrap();
+        let (mut loaded, _) = unlock_vault(&saved, b"pw").unwrap();
+        loaded.unseal_all(&key).unwrap();
+        assert_eq!(accessed(&loaded, "USER"), accessed(&vault, "USER"));
+        assert_eq!(accessed(&loaded, "UNUSED"), None);
     }
 
     #[test]
-    fn test_wrong_password_fails() {
-        let vault = Vault::new();
-        let saved = save_vault(&vault, b"correct-password").unwrap();
+    fn test_stored_values() {
+        let (mut vault, key) = create_vault(b"pw").unwrap();
+        vault.init_project("web").unwrap();
+        vault.add_secret("web", "USER", b"admin", &key, None).unwrap();
+        vault
+            .add_secret_in("web", Some("prod"), "TOKEN", b"t0k3n", &key, Some(0))
+            .unwrap();
+        vault
+            .add_template_in("web", None, "URL", b"https://{{USER}}@host", &key)
+            .unwrap();
+        vault
+            .add_alias("web", None, "LOGIN", SecretRef::new("web", None, "USER"))
+            .unwrap();
 
-        let result = load_vault(&saved, b"wrong-password");
-        assert!(matches!(result, Err(VaultError::AuthenticationFailed)));
+        let mut values: Vec<(String, Vec<u8>)> = vault
+            .stored_values("web", &key)
+            .unwrap()
+            .into_iter()
+            .map(|(secret, value)| (secret.to_string(), value.to_vec()))
+            .collect();
+        values.sort();
+
+        // Expired secrets are included; the template and alias are not
+        assert_eq!(
+            values,
+            vec![
+                ("web/USER".to_string(), b"admin".to_vec()),
+                ("web/prod/TOKEN".to_string(), b"t0k3n".to_vec()),
+            ]
+        );
     }
 }
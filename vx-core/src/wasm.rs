@@ -1,13 +1,32 @@
 //! WebAssembly bindings for vx-core.
 //!
 //! These functions are exported via wasm-bindgen for use in other runtimes.
+//!
+//! `WasmVault` keeps an opened vault and its data key inside the module, so
+//! callers derive the key once, use the same vault logic as the CLI for many
+//! operations, and save at the end instead of editing a JSON dump. The
+//! `wasm_vault_*` functions expose the same operations on an opaque handle
+//! for callers that cannot hold a class instance.
+//!
+//! `wasm_encrypt_stream_*` and `wasm_decrypt_stream_*` process large files
+//! piece by piece in the chunked format of `crate::stream`, the same format
+//! the CLI uses for attachment blobs.
 
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use serde::Serialize;
 use wasm_bindgen::prelude::*;
 
-use crate::crypto::{self, EncryptedData, KEY_SIZE, NONCE_SIZE, SALT_SIZE};
+use crate::audit;
+use crate::crypto::{self, EncryptedData, KdfParams, KEY_SIZE, NONCE_SIZE, SALT_SIZE};
+use crate::error::VaultError;
+use crate::memory::SecureKey;
+use crate::share;
 use crate::ssh;
+use crate::stream::{StreamDecryptor, StreamEncryptor};
 use crate::ttl;
-use crate::vault::{self, Vault};
+use crate::vault::{self, SshServerConfig, Vault};
 
 /// Generates a random salt for key derivation.
 #[wasm_bindgen]
@@ -34,6 +53,22 @@ pub fn wasm_derive_key(password: &[u8], salt: &[u8]) -> Result<Vec<u8>, JsValue>
         .map_err(|e| JsValue::from_str(&e.to_string()))
 }
 
+/// Recovers a vault's data key from any credential matching one of its key
+/// slots.
+///
+/// # Arguments
+/// * `data` - Encrypted vault bytes
+/// * `credential` - Password, keyfile contents or recovery code
+///
+/// # Returns
+/// The 32-byte data key secrets are encrypted with
+#[wasm_bindgen]
+pub fn wasm_unlock_key(data: &[u8], credential: &[u8]) -> Result<Vec<u8>, JsValue> {
+    vault::unlock_key(data, credential)
+        .map(|k| k.to_vec())
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
 /// Encrypts plaintext using AES-256-GCM.
 ///
 /// # Arguments
@@ -86,6 +121,7 @@ pub fn wasm_decrypt(encrypted: &[u8], key: &[u8]) -> Result<Vec<u8>, JsValue> {
     let encrypted_data = EncryptedData { ciphertext, nonce };
 
     crypto::decrypt(&encrypted_data, &key_array)
+        .map(|plaintext| plaintext.to_vec())
         .map_err(|e| JsValue::from_str(&e.to_string()))
 }
 
@@ -100,7 +136,7 @@ pub fn wasm_generate_ssh_keypair() -> Result<JsValue, JsValue> {
 
     let result = serde_json::json!({
         "public_key": public_key,
-        "private_key": private_key
+        "private_key": private_key.to_vec()
     });
 
     serde_wasm_bindgen::to_value(&result)
@@ -185,6 +221,36 @@ pub fn wasm_save_vault(vault_json: JsValue, password: &[u8]) -> Result<Vec<u8>,
         .map_err(|e| JsValue::from_str(&e.to_string()))
 }
 
+/// Formats a raw ed25519 private key (as returned by
+/// `WasmVault::get_ssh_identity`) as an OpenSSH private key file.
+///
+/// # Returns
+/// OpenSSH PEM formatted private key
+#[wasm_bindgen]
+pub fn wasm_format_private_key(private_key: &[u8]) -> Result<String, JsValue> {
+    let signing_key = ssh::reconstruct_signing_key(private_key).map_err(to_js)?;
+    let public_key = signing_key.verifying_key();
+
+    ssh::format_private_key(private_key, public_key.as_bytes())
+        .map(|pem| pem.to_string())
+        .map_err(to_js)
+}
+
+/// Decrypts a value shared with `vx get --qr --encrypt` (a `vxs1:` string).
+///
+/// # Arguments
+/// * `text` - The shared value, as scanned
+/// * `passphrase` - Passphrase it was encrypted with
+///
+/// # Returns
+/// The secret value
+#[wasm_bindgen]
+pub fn wasm_open_shared_value(text: &str, passphrase: &[u8]) -> Result<Vec<u8>, JsValue> {
+    share::open(text, passphrase)
+        .map(|value| value.to_vec())
+        .map_err(to_js)
+}
+
 /// Returns the current Unix timestamp in seconds.
 #[wasm_bindgen]
 pub fn wasm_current_timestamp() -> u64 {
@@ -202,3 +268,580 @@ pub fn wasm_current_timestamp() -> u64 {
 pub fn wasm_generate_ssh_setup_commands(public_key: &str) -> String {
     ssh::generate_setup_commands(public_key)
 }
+
+/// An opened vault holding its data key across calls.
+///
+/// ```js
+/// const vault = WasmVault.open(bytes, password);
+/// vault.add_secret("app", "API_KEY", value);
+/// const updated = vault.save();
+/// vault.free();
+/// ```
+#[wasm_bindgen]
+pub struct WasmVault {
+    vault: Vault,
+    key: SecureKey,
+}
+
+/// Secret metadata returned by `WasmVault::list_secrets`; values are never
+/// included.
+#[derive(Serialize)]
+struct SecretInfo<'a> {
+    key: &'a str,
+    created_at: u64,
+    expires_at: Option<u64>,
+}
+
+#[wasm_bindgen]
+impl WasmVault {
+    /// Creates a new empty vault protected by `password`.
+    pub fn create(password: &[u8]) -> Result<WasmVault, JsValue> {
+        let (vault, key) = vault::create_vault(password).map_err(to_js)?;
+        Ok(Self {
+            vault,
+            key: SecureKey::new(&key),
+        })
+    }
+
+    /// Creates a new empty vault, deriving the password slot's key with a
+    /// named Argon2id preset: `"standard"` (64 MB) or `"browser"` (19 MB,
+    /// much faster in single-threaded WASM). The preset is recorded in the
+    /// vault header and used whenever the vault is unlocked.
+    pub fn create_with_preset(password: &[u8], preset: &str) -> Result<WasmVault, JsValue> {
+        let kdf = KdfParams::preset(preset)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown KDF preset '{}'", preset)))?;
+        let (vault, key) = vault::create_vault_with_kdf(password, kdf).map_err(to_js)?;
+        Ok(Self {
+            vault,
+            key: SecureKey::new(&key),
+        })
+    }
+
+    /// Opens a vault from encrypted bytes with any credential matching one
+    /// of its key slots. Every project is unsealed.
+    pub fn open(data: &[u8], credential: &[u8]) -> Result<WasmVault, JsValue> {
+        let (mut vault, key) = vault::unlock_vault(data, credential).map_err(to_js)?;
+        vault.unseal_all(&key).map_err(to_js)?;
+        Ok(Self {
+            vault,
+            key: SecureKey::new(&key),
+        })
+    }
+
+    /// Opens a vault like `open`, calling `on_progress(fraction)` with a
+    /// number from 0 to 1 before the first key derivation and after each
+    /// one.
+    ///
+    /// Argon2 itself cannot be interrupted, so call this from a Web Worker
+    /// and post the progress to the page to keep the UI responsive.
+    pub fn open_with_progress(
+        data: &[u8],
+        credential: &[u8],
+        on_progress: &js_sys::Function,
+    ) -> Result<WasmVault, JsValue> {
+        let (mut vault, key) = vault::unlock_vault_with_progress(data, credential, |fraction| {
+            // A throwing callback must not abort the unlock
+            let _ = on_progress.call1(&JsValue::NULL, &JsValue::from_f64(fraction));
+        })
+        .map_err(to_js)?;
+        vault.unseal_all(&key).map_err(to_js)?;
+        Ok(Self {
+            vault,
+            key: SecureKey::new(&key),
+        })
+    }
+
+    /// Encrypts the vault for writing back to disk.
+    pub fn save(&mut self) -> Result<Vec<u8>, JsValue> {
+        self.vault.generation = self.vault.generation.wrapping_add(1);
+        vault::save_vault_with_key(&self.vault, &self.key).map_err(to_js)
+    }
+
+    /// Creates an empty project.
+    pub fn init_project(&mut self, name: &str) -> Result<(), JsValue> {
+        self.vault.init_project(name).map_err(to_js)
+    }
+
+    /// Adds or replaces a secret in a project.
+    ///
+    /// # Arguments
+    /// * `ttl_seconds` - Optional time-to-live; see `wasm_parse_ttl`
+    pub fn add_secret(
+        &mut self,
+        project: &str,
+        key: &str,
+        value: &[u8],
+        ttl_seconds: Option<u64>,
+    ) -> Result<(), JsValue> {
+        self.vault
+            .add_secret(project, key, value, &self.key, ttl_seconds)
+            .map_err(to_js)
+    }
+
+    /// Retrieves and decrypts a secret.
+    pub fn get_secret(&self, project: &str, key: &str) -> Result<Vec<u8>, JsValue> {
+        self.vault
+            .get_secret(project, key, &self.key)
+            .map(|value| value.to_vec())
+            .map_err(to_js)
+    }
+
+    /// Removes a secret from a project.
+    pub fn remove_secret(&mut self, project: &str, key: &str) -> Result<(), JsValue> {
+        self.vault.remove_secret(project, key).map_err(to_js)
+    }
+
+    /// Lists the names of all projects, sorted.
+    pub fn list_projects(&self) -> Result<JsValue, JsValue> {
+        serde_wasm_bindgen::to_value(&self.vault.project_names()).map_err(to_js)
+    }
+
+    /// Lists the secrets of a project's default environment, sorted by key.
+    ///
+    /// # Returns
+    /// Array of objects with `key`, `created_at` and `expires_at`; values
+    /// are not included
+    pub fn list_secrets(&self, project: &str) -> Result<JsValue, JsValue> {
+        let proj = self
+            .vault
+            .projects
+            .get(project)
+            .ok_or_else(|| to_js(VaultError::ProjectNotFound(project.to_string())))?;
+
+        let mut secrets: Vec<SecretInfo> = proj
+            .secrets
+            .values()
+            .map(|secret| SecretInfo {
+                key: &secret.key,
+                created_at: secret.created_at,
+                expires_at: secret.expires_at,
+            })
+            .collect();
+        secrets.sort_by_key(|info| info.key);
+
+        serde_wasm_bindgen::to_value(&secrets).map_err(to_js)
+    }
+
+    /// Generates a new ed25519 SSH identity and stores it in the vault.
+    ///
+    /// # Returns
+    /// The public key in OpenSSH format
+    pub fn add_ssh_identity(&mut self, name: &str) -> Result<String, JsValue> {
+        let (public_key, private_key) = ssh::generate_keypair().map_err(to_js)?;
+        self.vault
+            .add_ssh_identity(name, public_key.clone(), &private_key, &self.key)
+            .map_err(to_js)?;
+        Ok(public_key)
+    }
+
+    /// Retrieves and decrypts an SSH identity.
+    ///
+    /// # Returns
+    /// Object with `name`, `public_key` and `private_key` (raw ed25519 seed
+    /// bytes; see `wasm_format_private_key`)
+    pub fn get_ssh_identity(&self, name: &str) -> Result<JsValue, JsValue> {
+        let (public_key, private_key) =
+            self.vault.get_ssh_identity(name, &self.key).map_err(to_js)?;
+
+        let result = serde_json::json!({
+            "name": name,
+            "public_key": public_key,
+            "private_key": private_key.to_vec()
+        });
+        serde_wasm_bindgen::to_value(&result).map_err(to_js)
+    }
+
+    /// Stores a server configuration using an existing SSH identity.
+    pub fn add_ssh_server(
+        &mut self,
+        name: &str,
+        username: String,
+        ip_address: String,
+        identity_name: String,
+    ) -> Result<(), JsValue> {
+        self.vault
+            .add_ssh_server(name, username, ip_address, identity_name)
+            .map_err(to_js)
+    }
+
+    /// Retrieves a server configuration.
+    ///
+    /// # Returns
+    /// Object with `name`, `username`, `ip_address`, `identity_name` and
+    /// `created_at`
+    pub fn get_ssh_server(&self, name: &str) -> Result<JsValue, JsValue> {
+        let server = self.vault.get_ssh_server(name).map_err(to_js)?;
+        serde_wasm_bindgen::to_value(server).map_err(to_js)
+    }
+
+    /// Lists all server configurations, sorted by name.
+    pub fn list_ssh_servers(&self) -> Result<JsValue, JsValue> {
+        let mut servers: Vec<&SshServerConfig> = self.vault.ssh_servers.values().collect();
+        servers.sort_by(|a, b| a.name.cmp(&b.name));
+        serde_wasm_bindgen::to_value(&servers).map_err(to_js)
+    }
+
+    /// Removes a server configuration. The identity it uses is kept.
+    pub fn remove_ssh_server(&mut self, name: &str) -> Result<(), JsValue> {
+        self.vault.remove_ssh_server(name).map(|_| ()).map_err(to_js)
+    }
+
+    /// Audits the vault; see `wasm_audit_vault`.
+    pub fn audit(&self) -> Result<JsValue, JsValue> {
+        let report = audit::audit_vault(&self.vault, ttl::current_timestamp());
+        serde_wasm_bindgen::to_value(&report).map_err(to_js)
+    }
+}
+
+fn to_js<E: std::fmt::Display>(e: E) -> JsValue {
+    JsValue::from_str(&e.to_string())
+}
+
+thread_local! {
+    static OPEN_VAULTS: RefCell<HashMap<u32, WasmVault>> = RefCell::new(HashMap::new());
+    static ENCRYPT_STREAMS: RefCell<HashMap<u32, StreamEncryptor>> = RefCell::new(HashMap::new());
+    static DECRYPT_STREAMS: RefCell<HashMap<u32, StreamDecryptor>> = RefCell::new(HashMap::new());
+    static NEXT_HANDLE: Cell<u32> = const { Cell::new(1) };
+}
+
+/// Returns a handle not in use by any open vault or stream. Handles wrap
+/// around after `u32::MAX`, so one still open is skipped rather than
+/// replaced.
+fn next_handle() -> u32 {
+    loop {
+        let handle = NEXT_HANDLE.with(|next| {
+            let handle = next.get();
+            next.set(handle.wrapping_add(1).max(1));
+            handle
+        });
+        let in_use = OPEN_VAULTS.with(|vaults| vaults.borrow().contains_key(&handle))
+            || ENCRYPT_STREAMS.with(|streams| streams.borrow().contains_key(&handle))
+            || DECRYPT_STREAMS.with(|streams| streams.borrow().contains_key(&handle));
+        if !in_use {
+            return handle;
+        }
+    }
+}
+
+fn register(vault: WasmVault) -> u32 {
+    let handle = next_handle();
+    OPEN_VAULTS.with(|vaults| vaults.borrow_mut().insert(handle, vault));
+    handle
+}
+
+/// Runs `f` on the vault behind `handle`, or returns `None` if no vault is
+/// open under it.
+fn try_with_vault<T, F>(handle: u32, f: F) -> Option<T>
+where
+    F: FnOnce(&mut WasmVault) -> T,
+{
+    OPEN_VAULTS.with(|vaults| vaults.borrow_mut().get_mut(&handle).map(f))
+}
+
+/// Runs `f` on the vault behind `handle`.
+fn with_vault<T, F>(handle: u32, f: F) -> Result<T, JsValue>
+where
+    F: FnOnce(&mut WasmVault) -> Result<T, JsValue>,
+{
+    try_with_vault(handle, f).unwrap_or_else(|| Err(JsValue::from_str("Invalid vault handle")))
+}
+
+/// Creates a new empty vault and opens it.
+///
+/// # Returns
+/// A handle for the other `wasm_vault_*` functions
+#[wasm_bindgen]
+pub fn wasm_vault_create(password: &[u8]) -> Result<u32, JsValue> {
+    WasmVault::create(password).map(register)
+}
+
+/// Opens a vault from encrypted bytes with any credential matching one of
+/// its key slots. Every project is unsealed.
+///
+/// # Returns
+/// A handle for the other `wasm_vault_*` functions
+#[wasm_bindgen]
+pub fn wasm_vault_open(data: &[u8], credential: &[u8]) -> Result<u32, JsValue> {
+    WasmVault::open(data, credential).map(register)
+}
+
+/// Encrypts an open vault for writing back to disk.
+///
+/// # Returns
+/// Encrypted vault bytes
+#[wasm_bindgen]
+pub fn wasm_vault_save(handle: u32) -> Result<Vec<u8>, JsValue> {
+    with_vault(handle, |vault| vault.save())
+}
+
+/// Closes an open vault, dropping its data key. Unsaved changes are lost.
+#[wasm_bindgen]
+pub fn wasm_vault_close(handle: u32) {
+    OPEN_VAULTS.with(|vaults| vaults.borrow_mut().remove(&handle));
+}
+
+/// Creates an empty project.
+#[wasm_bindgen]
+pub fn wasm_vault_init_project(handle: u32, name: &str) -> Result<(), JsValue> {
+    with_vault(handle, |vault| vault.init_project(name))
+}
+
+/// Adds or replaces a secret in a project.
+///
+/// # Arguments
+/// * `ttl_seconds` - Optional time-to-live; see `wasm_parse_ttl`
+#[wasm_bindgen]
+pub fn wasm_vault_add_secret(
+    handle: u32,
+    project: &str,
+    key: &str,
+    value: &[u8],
+    ttl_seconds: Option<u64>,
+) -> Result<(), JsValue> {
+    with_vault(handle, |vault| vault.add_secret(project, key, value, ttl_seconds))
+}
+
+/// Retrieves and decrypts a secret.
+#[wasm_bindgen]
+pub fn wasm_vault_get_secret(handle: u32, project: &str, key: &str) -> Result<Vec<u8>, JsValue> {
+    with_vault(handle, |vault| vault.get_secret(project, key))
+}
+
+/// Removes a secret from a project.
+#[wasm_bindgen]
+pub fn wasm_vault_remove_secret(handle: u32, project: &str, key: &str) -> Result<(), JsValue> {
+    with_vault(handle, |vault| vault.remove_secret(project, key))
+}
+
+/// Lists the names of all projects, sorted.
+#[wasm_bindgen]
+pub fn wasm_vault_list_projects(handle: u32) -> Result<JsValue, JsValue> {
+    with_vault(handle, |vault| vault.list_projects())
+}
+
+/// Lists the secrets of a project's default environment, sorted by key.
+///
+/// # Returns
+/// Array of objects with `key`, `created_at` and `expires_at`; values are
+/// not included
+#[wasm_bindgen]
+pub fn wasm_vault_list_secrets(handle: u32, project: &str) -> Result<JsValue, JsValue> {
+    with_vault(handle, |vault| vault.list_secrets(project))
+}
+
+/// Generates a new SSH identity in an open vault.
+///
+/// # Returns
+/// The public key in OpenSSH format
+#[wasm_bindgen]
+pub fn wasm_add_ssh_identity(handle: u32, name: &str) -> Result<String, JsValue> {
+    with_vault(handle, |vault| vault.add_ssh_identity(name))
+}
+
+/// Retrieves and decrypts an SSH identity; see `WasmVault::get_ssh_identity`.
+#[wasm_bindgen]
+pub fn wasm_get_ssh_identity(handle: u32, name: &str) -> Result<JsValue, JsValue> {
+    with_vault(handle, |vault| vault.get_ssh_identity(name))
+}
+
+/// Stores a server configuration using an existing SSH identity.
+#[wasm_bindgen]
+pub fn wasm_add_ssh_server(
+    handle: u32,
+    name: &str,
+    username: String,
+    ip_address: String,
+    identity_name: String,
+) -> Result<(), JsValue> {
+    with_vault(handle, |vault| {
+        vault.add_ssh_server(name, username, ip_address, identity_name)
+    })
+}
+
+/// Retrieves a server configuration.
+#[wasm_bindgen]
+pub fn wasm_get_ssh_server(handle: u32, name: &str) -> Result<JsValue, JsValue> {
+    with_vault(handle, |vault| vault.get_ssh_server(name))
+}
+
+/// Lists all server configurations, sorted by name.
+#[wasm_bindgen]
+pub fn wasm_list_ssh_servers(handle: u32) -> Result<JsValue, JsValue> {
+    with_vault(handle, |vault| vault.list_ssh_servers())
+}
+
+/// Removes a server configuration.
+#[wasm_bindgen]
+pub fn wasm_remove_ssh_server(handle: u32, name: &str) -> Result<(), JsValue> {
+    with_vault(handle, |vault| vault.remove_ssh_server(name))
+}
+
+/// Audits an open vault for expired, long-lived and high-risk secrets.
+///
+/// # Returns
+/// The `AuditReport`: `projects` (each with `name`, `total_secrets` and
+/// `issues`), `ssh_identities`, `long_lived_identities` and `trash`
+#[wasm_bindgen]
+pub fn wasm_audit_vault(handle: u32) -> Result<JsValue, JsValue> {
+    with_vault(handle, |vault| vault.audit())
+}
+
+/// Starts encrypting a blob in the chunked attachment format.
+///
+/// # Arguments
+/// * `key` - 32-byte encryption key
+///
+/// # Returns
+/// A handle for `wasm_encrypt_stream_push` and `wasm_encrypt_stream_finish`
+#[wasm_bindgen]
+pub fn wasm_encrypt_stream_begin(key: &[u8]) -> Result<u32, JsValue> {
+    let key: &[u8; KEY_SIZE] = key
+        .try_into()
+        .map_err(|_| JsValue::from_str("Invalid key length"))?;
+    let encryptor = StreamEncryptor::new(key).map_err(to_js)?;
+
+    let handle = next_handle();
+    ENCRYPT_STREAMS.with(|streams| streams.borrow_mut().insert(handle, encryptor));
+    Ok(handle)
+}
+
+/// Encrypts the next piece of input.
+///
+/// # Returns
+/// Encrypted bytes ready to be written out; may be empty. The blob is the
+/// concatenation of every push result and the finish result.
+#[wasm_bindgen]
+pub fn wasm_encrypt_stream_push(handle: u32, data: &[u8]) -> Result<Vec<u8>, JsValue> {
+    ENCRYPT_STREAMS.with(|streams| {
+        let mut streams = streams.borrow_mut();
+        let encryptor = streams
+            .get_mut(&handle)
+            .ok_or_else(|| JsValue::from_str("Invalid stream handle"))?;
+        encryptor.push(data).map_err(to_js)
+    })
+}
+
+/// Seals the final chunk and releases the handle.
+#[wasm_bindgen]
+pub fn wasm_encrypt_stream_finish(handle: u32) -> Result<Vec<u8>, JsValue> {
+    ENCRYPT_STREAMS
+        .with(|streams| streams.borrow_mut().remove(&handle))
+        .ok_or_else(|| JsValue::from_str("Invalid stream handle"))?
+        .finish()
+        .map_err(to_js)
+}
+
+/// Starts decrypting a blob in the chunked attachment format.
+///
+/// # Returns
+/// A handle for `wasm_decrypt_stream_push` and `wasm_decrypt_stream_finish`
+#[wasm_bindgen]
+pub fn wasm_decrypt_stream_begin(key: &[u8]) -> Result<u32, JsValue> {
+    let key: &[u8; KEY_SIZE] = key
+        .try_into()
+        .map_err(|_| JsValue::from_str("Invalid key length"))?;
+    let decryptor = StreamDecryptor::new(key).map_err(to_js)?;
+
+    let handle = next_handle();
+    DECRYPT_STREAMS.with(|streams| streams.borrow_mut().insert(handle, decryptor));
+    Ok(handle)
+}
+
+/// Decrypts the next piece of a blob.
+///
+/// # Returns
+/// Plaintext of the chunks authenticated so far; may be empty. The blob is
+/// only known to be complete once `wasm_decrypt_stream_finish` succeeds.
+/// On error the handle is released.
+#[wasm_bindgen]
+pub fn wasm_decrypt_stream_push(handle: u32, data: &[u8]) -> Result<Vec<u8>, JsValue> {
+    DECRYPT_STREAMS.with(|streams| {
+        let mut streams = streams.borrow_mut();
+        let decryptor = streams
+            .get_mut(&handle)
+            .ok_or_else(|| JsValue::from_str("Invalid stream handle"))?;
+
+        decryptor.push(data).map(|plaintext| plaintext.to_vec()).map_err(|e| {
+            streams.remove(&handle);
+            to_js(e)
+        })
+    })
+}
+
+/// Decrypts the final chunk and releases the handle.
+///
+/// Fails if the blob was truncated.
+#[wasm_bindgen]
+pub fn wasm_decrypt_stream_finish(handle: u32) -> Result<Vec<u8>, JsValue> {
+    DECRYPT_STREAMS
+        .with(|streams| streams.borrow_mut().remove(&handle))
+        .ok_or_else(|| JsValue::from_str("Invalid stream handle"))?
+        .finish()
+        .map(|plaintext| plaintext.to_vec())
+        .map_err(to_js)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap parameters: these tests are about the bindings, not Argon2
+    const TEST_KDF: KdfParams = KdfParams {
+        memory_kib: 8,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    fn test_vault(password: &[u8]) -> WasmVault {
+        let (vault, key) = vault::create_vault_with_kdf(password, TEST_KDF).unwrap();
+        WasmVault {
+            vault,
+            key: SecureKey::new(&key),
+        }
+    }
+
+    #[test]
+    fn test_vault_round_trip() {
+        let mut vault = test_vault(b"password");
+        vault.init_project("app").unwrap();
+        vault.add_secret("app", "API_KEY", b"s3cret", None).unwrap();
+        assert_eq!(vault.get_secret("app", "API_KEY").unwrap(), b"s3cret");
+
+        let data = vault.save().unwrap();
+        let reopened = WasmVault::open(&data, b"password").unwrap();
+        assert_eq!(reopened.get_secret("app", "API_KEY").unwrap(), b"s3cret");
+        assert_eq!(reopened.vault.generation, vault.vault.generation);
+    }
+
+    #[test]
+    fn test_closed_handle_is_invalid() {
+        let handle = register(test_vault(b"password"));
+        assert_eq!(try_with_vault(handle, |vault| vault.init_project("app").is_ok()), Some(true));
+
+        wasm_vault_close(handle);
+        assert!(try_with_vault(handle, |_| ()).is_none());
+        // Closing twice is harmless
+        wasm_vault_close(handle);
+        assert!(try_with_vault(0, |_| ()).is_none());
+    }
+
+    #[test]
+    fn test_open_handle_is_not_reused() {
+        let handle = register(test_vault(b"password"));
+        NEXT_HANDLE.with(|next| next.set(handle));
+
+        let other = register(test_vault(b"other"));
+        assert_ne!(other, handle);
+        assert_eq!(try_with_vault(handle, |vault| vault.vault.generation), Some(0));
+
+        // Wrapping around skips 0 as well as handles in use
+        NEXT_HANDLE.with(|next| next.set(u32::MAX));
+        assert_eq!(next_handle(), u32::MAX);
+        let wrapped = next_handle();
+        assert_ne!(wrapped, 0);
+        assert!(wrapped != handle && wrapped != other);
+
+        wasm_vault_close(handle);
+        wasm_vault_close(other);
+    }
+}
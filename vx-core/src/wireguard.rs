@@ -0,0 +1,137 @@
+//! WireGuard configs stored for `wg-quick`.
+//!
+//! `wg-quick` names the interface after the config file, so a stored
+//! config's name must be a valid interface name. The config itself is
+//! checked just enough to catch a wrong file: it needs an `[Interface]`
+//! section with a private key.
+//!
+//! # Security Notes
+//! - Validation reads the decrypted config in memory and keeps nothing
+
+use crate::error::VaultError;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use zeroize::Zeroizing;
+
+/// Longest interface name Linux accepts (`IFNAMSIZ` minus the NUL)
+pub const MAX_INTERFACE_NAME: usize = 15;
+
+/// Size of a Curve25519 private key in bytes
+const PRIVATE_KEY_SIZE: usize = 32;
+
+/// Checks that `name` can be used as a WireGuard interface name.
+///
+/// # Errors
+/// `VaultError::InvalidInterfaceName` unless the name is 1 to 15 letters,
+/// digits or `_=+.-`.
+pub fn validate_interface_name(name: &str) -> Result<(), VaultError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_INTERFACE_NAME
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_=+.-".contains(c));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(VaultError::InvalidInterfaceName(name.to_string()))
+    }
+}
+
+/// Checks that `config` looks like a `wg-quick` config.
+///
+/// # Errors
+/// `VaultError::InvalidWireguardConfig` if the config has no `[Interface]`
+/// section or its `PrivateKey` is not a base64 Curve25519 key.
+pub fn validate_config(config: &[u8]) -> Result<(), VaultError> {
+    let text = std::str::from_utf8(config).map_err(|_| invalid("not UTF-8 text"))?;
+
+    let mut in_interface = false;
+    let mut has_interface = false;
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.starts_with('[') {
+            in_interface = line.eq_ignore_ascii_case("[Interface]");
+            has_interface |= in_interface;
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if in_interface && key.trim().eq_ignore_ascii_case("PrivateKey") {
+            let key = STANDARD
+                .decode(value.trim())
+                .map(Zeroizing::new)
+                .map_err(|_| invalid("PrivateKey is not valid base64"))?;
+            if key.len() != PRIVATE_KEY_SIZE {
+                return Err(invalid("PrivateKey is not a 32-byte key"));
+            }
+            return Ok(());
+        }
+    }
+
+    Err(invalid(if has_interface {
+        "[Interface] has no PrivateKey"
+    } else {
+        "no [Interface] section"
+    }))
+}
+
+fn invalid(reason: &str) -> VaultError {
+    VaultError::InvalidWireguardConfig(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+# office VPN
+[Interface]
+Address = 10.0.0.2/32
+PrivateKey = yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=
+DNS = 10.0.0.1
+
+[Peer]
+PublicKey = xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=
+Endpoint = vpn.example.com:51820
+AllowedIPs = 0.0.0.0/0
+";
+
+    #[test]
+    fn test_validate_interface_name() {
+        for name in ["wg0", "office-vpn", "a.b_c=d+e", "fifteen-chars-x"] {
+            assert!(validate_interface_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", "sixteen-chars-xx", "has space", "../etc", "wg/0"] {
+            assert!(matches!(
+                validate_interface_name(name),
+                Err(VaultError::InvalidInterfaceName(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_validate_config() {
+        assert!(validate_config(CONFIG.as_bytes()).is_ok());
+        assert!(validate_config(CONFIG.to_lowercase().as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_validate_config_rejects_other_files() {
+        let peer_key_only = CONFIG.replace("PrivateKey", "# PrivateKey");
+        let short_key = CONFIG.replace("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=", "AAAA");
+
+        for config in [
+            "apiVersion: v1\nkind: Config\n",
+            peer_key_only.as_str(),
+            short_key.as_str(),
+            "[Peer]\nPrivateKey = yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=\n",
+        ] {
+            assert!(matches!(
+                validate_config(config.as_bytes()),
+                Err(VaultError::InvalidWireguardConfig(_))
+            ));
+        }
+    }
+}
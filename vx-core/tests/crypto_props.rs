@@ -3,7 +3,7 @@
 //! These tests verify correctness properties using proptest.
 
 use proptest::prelude::*;
-use vx_core::crypto::{decrypt, derive_key, encrypt, generate_salt, EncryptedData, KEY_SIZE};
+use vx_core::crypto::{decrypt, derive_key, encrypt, generate_salt, KEY_SIZE};
 
 proptest! {
     #![proptest_config(ProptestConfig::with_cases(100))]
@@ -21,7 +21,7 @@ proptest! {
         let encrypted = encrypt(&secret, &key).unwrap();
         let decrypted = decrypt(&encrypted, &key).unwrap();
 
-        prop_assert_eq!(decrypted, secret);
+        prop_assert_eq!(decrypted.expose(), secret.as_slice());
     }
 
     /// **Feature: vaultx-cli, Property 10: Nonce Uniqueness**
@@ -0,0 +1,45 @@
+//! Property-based tests for the leak scanner.
+
+use proptest::prelude::*;
+use vx_core::scan::{LeakScanner, MIN_VALUE_LEN};
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(100))]
+
+    /// A value placed anywhere in a stream is found at its offset and line.
+    #[test]
+    fn prop_inserted_value_is_found(
+        value in proptest::collection::vec(any::<u8>(), MIN_VALUE_LEN..64),
+        before in proptest::collection::vec(any::<u8>(), 0..200_000),
+        after in proptest::collection::vec(any::<u8>(), 0..2_000),
+    ) {
+        let mut scanner = LeakScanner::new();
+        let index = scanner.add(&value).unwrap();
+
+        let mut data = before.clone();
+        data.extend_from_slice(&value);
+        data.extend_from_slice(&after);
+
+        let matches = scanner.scan(&data[..]).unwrap();
+        let expected_line = 1 + before.iter().filter(|&&b| b == b'\n').count() as u64;
+        let found = matches.iter().any(|m| {
+            m.value == index && m.offset == before.len() as u64 && m.line == expected_line
+        });
+        prop_assert!(found);
+    }
+
+    /// Every match reported really is the value.
+    #[test]
+    fn prop_matches_are_exact(
+        value in proptest::collection::vec(0u8..4, MIN_VALUE_LEN..12),
+        data in proptest::collection::vec(0u8..4, 0..5_000),
+    ) {
+        let mut scanner = LeakScanner::new();
+        scanner.add(&value).unwrap();
+
+        for m in scanner.scan(&data[..]).unwrap() {
+            let start = m.offset as usize;
+            prop_assert_eq!(&data[start..start + value.len()], &value[..]);
+        }
+    }
+}
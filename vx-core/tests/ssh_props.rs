@@ -1,7 +1,7 @@
 //! Property-based tests for SSH module.
 
 use proptest::prelude::*;
-use vx_core::crypto::{decrypt, derive_key, encrypt, generate_salt, EncryptedData, KEY_SIZE};
+use vx_core::crypto::{decrypt, derive_key, encrypt, generate_salt, KEY_SIZE};
 use vx_core::ssh::{format_private_key, generate_keypair, generate_setup_commands, reconstruct_signing_key};
 use vx_core::vault::Vault;
 
@@ -90,7 +90,7 @@ proptest! {
 
         // Should still be valid key
         let signing_key = reconstruct_signing_key(&decrypted).unwrap();
-        prop_assert_eq!(signing_key.to_bytes().to_vec(), private_key_bytes);
+        prop_assert_eq!(&signing_key.to_bytes()[..], private_key_bytes.expose());
     }
 
     /// Property: Generated keypairs are unique
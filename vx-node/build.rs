@@ -0,0 +1,5 @@
+//! Build script for vx-node: sets the linker flags Node addons need.
+
+fn main() {
+    napi_build::setup();
+}
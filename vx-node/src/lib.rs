@@ -0,0 +1,250 @@
+//! Native Node.js bindings for VaultX.
+//!
+//! Exposes the same `Vault` surface as `WasmVault` in the WASM module, with
+//! the same method names, arguments and return shapes, so the npm package's
+//! loader can pick either at runtime. Native code runs Argon2 several times
+//! faster than WASM, which matters on servers unlocking vaults often.
+
+use napi::bindgen_prelude::{BigInt, Buffer};
+use napi::{Env, Error, JsFunction, Result};
+use napi_derive::napi;
+use serde::Serialize;
+use serde_json::Value;
+
+use vx_core::audit;
+use vx_core::crypto::KdfParams;
+use vx_core::memory::SecureKey;
+use vx_core::vault::{self, SshServerConfig};
+use vx_core::{ssh, ttl, VaultError};
+
+fn to_napi<E: std::fmt::Display>(e: E) -> Error {
+    Error::from_reason(e.to_string())
+}
+
+fn to_value<T: Serialize>(value: &T) -> Result<Value> {
+    serde_json::to_value(value).map_err(to_napi)
+}
+
+/// Secret metadata returned by `list_secrets`; values are never included.
+#[derive(Serialize)]
+struct SecretInfo<'a> {
+    key: &'a str,
+    created_at: u64,
+    expires_at: Option<u64>,
+}
+
+/// An opened vault holding its data key across calls.
+#[napi(js_name = "Vault")]
+pub struct NodeVault {
+    vault: vault::Vault,
+    key: SecureKey,
+}
+
+#[napi]
+impl NodeVault {
+    /// Creates a new empty vault protected by `password`.
+    #[napi(factory)]
+    pub fn create(password: Buffer) -> Result<Self> {
+        let (vault, key) = vault::create_vault(&password).map_err(to_napi)?;
+        Ok(Self {
+            vault,
+            key: SecureKey::new(&key),
+        })
+    }
+
+    /// Creates a new empty vault with a named Argon2id preset, `"standard"`
+    /// or `"browser"`.
+    #[napi(factory, js_name = "create_with_preset")]
+    pub fn create_with_preset(password: Buffer, preset: String) -> Result<Self> {
+        let kdf = KdfParams::preset(&preset)
+            .ok_or_else(|| Error::from_reason(format!("Unknown KDF preset '{}'", preset)))?;
+        let (vault, key) = vault::create_vault_with_kdf(&password, kdf).map_err(to_napi)?;
+        Ok(Self {
+            vault,
+            key: SecureKey::new(&key),
+        })
+    }
+
+    /// Opens a vault from encrypted bytes with any credential matching one
+    /// of its key slots. Every project is unsealed.
+    #[napi(factory)]
+    pub fn open(data: Buffer, credential: Buffer) -> Result<Self> {
+        let (mut vault, key) = vault::unlock_vault(&data, &credential).map_err(to_napi)?;
+        vault.unseal_all(&key).map_err(to_napi)?;
+        Ok(Self {
+            vault,
+            key: SecureKey::new(&key),
+        })
+    }
+
+    /// Opens a vault like `open`, calling `on_progress(fraction)` before the
+    /// first key derivation and after each one.
+    #[napi(factory, js_name = "open_with_progress")]
+    pub fn open_with_progress(
+        env: Env,
+        data: Buffer,
+        credential: Buffer,
+        on_progress: JsFunction,
+    ) -> Result<Self> {
+        let (mut vault, key) = vault::unlock_vault_with_progress(&data, &credential, |fraction| {
+            // A throwing callback must not abort the unlock
+            if let Ok(fraction) = env.create_double(fraction) {
+                let _ = on_progress.call(None, &[fraction]);
+            }
+        })
+        .map_err(to_napi)?;
+        vault.unseal_all(&key).map_err(to_napi)?;
+        Ok(Self {
+            vault,
+            key: SecureKey::new(&key),
+        })
+    }
+
+    /// Encrypts the vault for writing back to disk.
+    #[napi]
+    pub fn save(&mut self) -> Result<Buffer> {
+        self.vault.generation = self.vault.generation.wrapping_add(1);
+        vault::save_vault_with_key(&self.vault, &self.key)
+            .map(Buffer::from)
+            .map_err(to_napi)
+    }
+
+    /// Creates an empty project.
+    #[napi(js_name = "init_project")]
+    pub fn init_project(&mut self, name: String) -> Result<()> {
+        self.vault.init_project(&name).map_err(to_napi)
+    }
+
+    /// Adds or replaces a secret in a project. `ttl_seconds` is a BigInt,
+    /// as in the WASM module.
+    #[napi(js_name = "add_secret")]
+    pub fn add_secret(
+        &mut self,
+        project: String,
+        key: String,
+        value: Buffer,
+        ttl_seconds: Option<BigInt>,
+    ) -> Result<()> {
+        let ttl_seconds = ttl_seconds
+            .map(|ttl| match ttl.get_u64() {
+                (false, seconds, true) => Ok(seconds),
+                _ => Err(Error::from_reason("ttl_seconds must be a non-negative 64-bit integer")),
+            })
+            .transpose()?;
+
+        self.vault
+            .add_secret(&project, &key, &value, &self.key, ttl_seconds)
+            .map_err(to_napi)
+    }
+
+    /// Retrieves and decrypts a secret.
+    #[napi(js_name = "get_secret")]
+    pub fn get_secret(&self, project: String, key: String) -> Result<Buffer> {
+        self.vault
+            .get_secret(&project, &key, &self.key)
+            .map(|value| Buffer::from(value.to_vec()))
+            .map_err(to_napi)
+    }
+
+    /// Removes a secret from a project.
+    #[napi(js_name = "remove_secret")]
+    pub fn remove_secret(&mut self, project: String, key: String) -> Result<()> {
+        self.vault.remove_secret(&project, &key).map_err(to_napi)
+    }
+
+    /// Lists the names of all projects, sorted.
+    #[napi(js_name = "list_projects")]
+    pub fn list_projects(&self) -> Result<Value> {
+        to_value(&self.vault.project_names())
+    }
+
+    /// Lists the secrets of a project's default environment, sorted by key:
+    /// objects with `key`, `created_at` and `expires_at`.
+    #[napi(js_name = "list_secrets")]
+    pub fn list_secrets(&self, project: String) -> Result<Value> {
+        let proj = self
+            .vault
+            .projects
+            .get(&project)
+            .ok_or_else(|| to_napi(VaultError::ProjectNotFound(project.clone())))?;
+
+        let mut secrets: Vec<SecretInfo> = proj
+            .secrets
+            .values()
+            .map(|secret| SecretInfo {
+                key: &secret.key,
+                created_at: secret.created_at,
+                expires_at: secret.expires_at,
+            })
+            .collect();
+        secrets.sort_by_key(|info| info.key);
+
+        to_value(&secrets)
+    }
+
+    /// Generates a new ed25519 SSH identity, returning its OpenSSH public
+    /// key.
+    #[napi(js_name = "add_ssh_identity")]
+    pub fn add_ssh_identity(&mut self, name: String) -> Result<String> {
+        let (public_key, private_key) = ssh::generate_keypair().map_err(to_napi)?;
+        self.vault
+            .add_ssh_identity(&name, public_key.clone(), &private_key, &self.key)
+            .map_err(to_napi)?;
+        Ok(public_key)
+    }
+
+    /// Retrieves and decrypts an SSH identity: `name`, `public_key` and
+    /// `private_key` (raw ed25519 seed bytes).
+    #[napi(js_name = "get_ssh_identity")]
+    pub fn get_ssh_identity(&self, name: String) -> Result<Value> {
+        let (public_key, private_key) =
+            self.vault.get_ssh_identity(&name, &self.key).map_err(to_napi)?;
+
+        Ok(serde_json::json!({
+            "name": name,
+            "public_key": public_key,
+            "private_key": private_key.to_vec()
+        }))
+    }
+
+    /// Stores a server configuration using an existing SSH identity.
+    #[napi(js_name = "add_ssh_server")]
+    pub fn add_ssh_server(
+        &mut self,
+        name: String,
+        username: String,
+        ip_address: String,
+        identity_name: String,
+    ) -> Result<()> {
+        self.vault
+            .add_ssh_server(&name, username, ip_address, identity_name)
+            .map_err(to_napi)
+    }
+
+    /// Retrieves a server configuration.
+    #[napi(js_name = "get_ssh_server")]
+    pub fn get_ssh_server(&self, name: String) -> Result<Value> {
+        let server = self.vault.get_ssh_server(&name).map_err(to_napi)?;
+        to_value(server)
+    }
+
+    /// Lists all server configurations, sorted by name.
+    #[napi(js_name = "list_ssh_servers")]
+    pub fn list_ssh_servers(&self) -> Result<Value> {
+        let mut servers: Vec<&SshServerConfig> = self.vault.ssh_servers.values().collect();
+        servers.sort_by(|a, b| a.name.cmp(&b.name));
+        to_value(&servers)
+    }
+
+    /// Removes a server configuration. The identity it uses is kept.
+    #[napi(js_name = "remove_ssh_server")]
+    pub fn remove_ssh_server(&mut self, name: String) -> Result<()> {
+        self.vault.remove_ssh_server(&name).map(|_| ()).map_err(to_napi)
+    }
+
+    /// Audits the vault for expired, long-lived and high-risk secrets.
+    #[napi]
+    pub fn audit(&self) -> Result<Value> {
+        to_value(&audit::audit_vault(&self.vault, ttl::current_timestamp()))
+    }
+}
@@ -0,0 +1,249 @@
+//! Python bindings for VaultX.
+//!
+//! Built with maturin into a `vaultx` extension module:
+//!
+//! ```python
+//! import vaultx
+//!
+//! vault = vaultx.open_vault(os.path.expanduser("~/.vaultx/vault.vx"), password)
+//! url = vault.get("my-project", "DATABASE_URL", env="prod").decode()
+//! vault.set("my-project", "API_KEY", "sk-...", ttl="30d")
+//! ```
+//!
+//! Every change is written back to the vault file immediately, and reads are
+//! recorded in the access log, exactly as the corresponding `vx` commands do.
+//! Saving uses the same generation check as the CLI, so a write by another
+//! process in between raises instead of being overwritten.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use vx_core::memory::SecureKey;
+use vx_core::{ttl, vault, Operation};
+
+create_exception!(
+    vaultx,
+    VaultError,
+    PyException,
+    "Raised when a vault operation fails."
+);
+
+fn to_py<E: std::fmt::Display>(e: E) -> PyErr {
+    VaultError::new_err(e.to_string())
+}
+
+/// A password, keyfile or secret value, accepted as `str` or `bytes`.
+#[derive(FromPyObject)]
+enum Bytes {
+    Text(String),
+    Raw(Vec<u8>),
+}
+
+impl Bytes {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Bytes::Text(text) => text.as_bytes(),
+            Bytes::Raw(raw) => raw,
+        }
+    }
+}
+
+impl Drop for Bytes {
+    fn drop(&mut self) {
+        use zeroize::Zeroize;
+        match self {
+            Bytes::Text(text) => text.zeroize(),
+            Bytes::Raw(raw) => raw.zeroize(),
+        }
+    }
+}
+
+/// Formats a secret name for the access log as env/key.
+fn qualified_key(env: Option<&str>, key: &str) -> String {
+    match env {
+        Some(env) => format!("{}/{}", env, key),
+        None => key.to_string(),
+    }
+}
+
+/// An opened vault file.
+///
+/// Created by `open_vault`; holds the data key for as long as it lives.
+#[pyclass(name = "Vault", module = "vaultx")]
+struct PyVault {
+    path: PathBuf,
+    vault: vault::Vault,
+    key: SecureKey,
+}
+
+impl PyVault {
+    /// Writes the vault back to its file, refusing to overwrite a newer
+    /// generation.
+    fn save(&mut self) -> PyResult<()> {
+        let existing = fs::read(&self.path)?;
+        let found = vault::read_generation(&existing).map_err(to_py)?;
+        if found != self.vault.generation {
+            return Err(to_py(vx_core::VaultError::GenerationConflict {
+                expected: self.vault.generation,
+                found,
+            }));
+        }
+
+        self.vault.generation = found.wrapping_add(1);
+        let result = self.write();
+        if result.is_err() {
+            self.vault.generation = found;
+        }
+        result
+    }
+
+    /// Encrypts the vault and atomically replaces the file.
+    fn write(&self) -> PyResult<()> {
+        let data = vault::save_vault_with_key(&self.vault, &self.key).map_err(to_py)?;
+
+        let temp_path = self.path.with_extension("tmp");
+        {
+            let mut file = fs::File::create(&temp_path)?;
+            file.write_all(&data)?;
+            file.sync_all()?;
+        }
+        fs::rename(&temp_path, &self.path)?;
+
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl PyVault {
+    /// Path of the vault file.
+    #[getter]
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    /// Names of all projects, sorted.
+    fn projects(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.vault.projects.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Names of the secrets in a project's environment, sorted.
+    #[pyo3(signature = (project, env=None))]
+    fn secrets(&self, project: &str, env: Option<&str>) -> PyResult<Vec<String>> {
+        let proj = self
+            .vault
+            .projects
+            .get(project)
+            .ok_or_else(|| to_py(vx_core::VaultError::ProjectNotFound(project.to_string())))?;
+
+        let mut keys: Vec<String> = proj.secrets_in(env).map_err(to_py)?.keys().cloned().collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Decrypts a secret, following aliases and rendering templates. The
+    /// read is recorded in the access log.
+    #[pyo3(signature = (project, key, env=None))]
+    fn get<'py>(
+        &mut self,
+        py: Python<'py>,
+        project: &str,
+        key: &str,
+        env: Option<&str>,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let value = self
+            .vault
+            .get_secret_in(project, env, key, &self.key)
+            .map_err(to_py)?;
+
+        if !self.vault.read_only {
+            self.vault
+                .record(Operation::Get, Some(project), Some(&qualified_key(env, key)));
+            self.save()?;
+        }
+
+        Ok(PyBytes::new(py, &value))
+    }
+
+    /// Adds or replaces a secret.
+    ///
+    /// `ttl` is a duration such as `"30m"`, `"24h"` or `"7d"`.
+    #[pyo3(signature = (project, key, value, env=None, ttl=None))]
+    fn set(
+        &mut self,
+        project: &str,
+        key: &str,
+        value: Bytes,
+        env: Option<&str>,
+        ttl: Option<&str>,
+    ) -> PyResult<()> {
+        let ttl_seconds = ttl.map(ttl::parse_ttl).transpose().map_err(to_py)?;
+
+        self.vault
+            .add_secret_in(project, env, key, value.as_bytes(), &self.key, ttl_seconds)
+            .map_err(to_py)?;
+        self.vault
+            .record(Operation::Add, Some(project), Some(&qualified_key(env, key)));
+        self.save()
+    }
+
+    /// Removes a secret, keeping it in the trash (see `vx trash`).
+    #[pyo3(signature = (project, key, env=None))]
+    fn remove(&mut self, project: &str, key: &str, env: Option<&str>) -> PyResult<()> {
+        self.vault
+            .trash_secret(project, env, key)
+            .map_err(to_py)?;
+        self.vault
+            .record(Operation::Remove, Some(project), Some(&qualified_key(env, key)));
+        self.save()
+    }
+
+    /// Creates an empty project.
+    fn init_project(&mut self, name: &str) -> PyResult<()> {
+        self.vault.init_project(name).map_err(to_py)?;
+        self.save()
+    }
+
+    /// Unlocks a project protected by a passphrase for the lifetime of this
+    /// object.
+    fn unlock_project(&mut self, name: &str, passphrase: Bytes) -> PyResult<()> {
+        self.vault
+            .unlock_project(name, passphrase.as_bytes(), &self.key)
+            .map_err(to_py)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<vaultx.Vault {}>", self.path.display())
+    }
+}
+
+/// Opens a vault file with a password, keyfile contents or recovery code.
+/// Every project is unsealed.
+#[pyfunction]
+fn open_vault(path: PathBuf, password: Bytes) -> PyResult<PyVault> {
+    let data = fs::read(&path)?;
+    let (mut vault, key) = vault::unlock_vault(&data, password.as_bytes()).map_err(to_py)?;
+    vault.unseal_all(&key).map_err(to_py)?;
+
+    Ok(PyVault {
+        path,
+        vault,
+        key: SecureKey::new(&key),
+    })
+}
+
+#[pymodule]
+fn vaultx(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    m.add("VaultError", m.py().get_type::<VaultError>())?;
+    m.add_class::<PyVault>()?;
+    m.add_function(wrap_pyfunction!(open_vault, m)?)?;
+    Ok(())
+}
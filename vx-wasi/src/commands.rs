@@ -0,0 +1,70 @@
+//! The commands: the read-only subset of `vx` that needs no terminal.
+
+use crate::error::Error;
+use crate::vault;
+use std::io::{self, Write};
+use vx_core::{audit, dotenv, ttl, VaultError};
+use zeroize::Zeroizing;
+
+/// Prints the value of one secret, followed by a newline.
+pub fn get(project: &str, key: &str, env: Option<&str>, password_stdin: bool) -> Result<(), Error> {
+    let (mut vault, encryption_key) = vault::open(password_stdin)?;
+    let value = vault::read_secret(&mut vault, project, env, key, &encryption_key)?;
+
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(&value)?;
+    stdout.write_all(b"\n")?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Prints `KEY="value"` lines for every secret in the project (or in one of
+/// its environments), as `vx export` does. Expired secrets are skipped.
+pub fn export(project: &str, env: Option<&str>, password_stdin: bool) -> Result<(), Error> {
+    let (mut vault, encryption_key) = vault::open(password_stdin)?;
+    vault.unseal_project(project, &encryption_key)?;
+
+    let mut keys: Vec<String> = vault.projects[project]
+        .secrets_in(env)?
+        .keys()
+        .cloned()
+        .collect();
+    keys.sort();
+
+    let mut out = Zeroizing::new(Vec::new());
+    for key in &keys {
+        let value = match vault::read_secret(&mut vault, project, env, key, &encryption_key) {
+            Ok(value) => value,
+            Err(Error::Vault(VaultError::SecretExpired(_))) => {
+                eprintln!("Skipping expired secret '{}'", key);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        writeln!(
+            out,
+            "{}=\"{}\"",
+            key,
+            *Zeroizing::new(dotenv::escape(&String::from_utf8_lossy(&value)))
+        )?;
+    }
+
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(&out)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Prints the audit of every project as JSON, in the format of
+/// `vx audit --format json` without the file permission checks.
+pub fn audit(password_stdin: bool) -> Result<(), Error> {
+    let (mut vault, encryption_key) = vault::open(password_stdin)?;
+    vault.unseal_all(&encryption_key)?;
+
+    let now = ttl::current_timestamp();
+    let mut report = audit::audit_vault(&vault, now);
+    audit::audit_values(&mut report, &vault, &encryption_key, now)?;
+    let json = serde_json::to_string_pretty(&report).map_err(io::Error::from)?;
+    println!("{}", json);
+    Ok(())
+}
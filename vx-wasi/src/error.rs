@@ -0,0 +1,22 @@
+//! Error types.
+
+use thiserror::Error;
+use vx_core::VaultError;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{0}")]
+    Vault(#[from] VaultError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("No password given: set VX_PASSWORD or pipe it in with --password-stdin")]
+    NoPassword,
+
+    #[error("No vault at {0}; set VX_VAULT_PATH to the directory holding vault.vx")]
+    VaultNotFound(String),
+
+    #[error("Could not determine the vault directory: set VX_VAULT_PATH or HOME")]
+    NoVaultDir,
+}
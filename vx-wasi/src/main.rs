@@ -0,0 +1,83 @@
+//! VaultX for WASI runtimes.
+//!
+//! The non-interactive subset of `vx` (get, export, audit), built on
+//! vx-core alone so that it compiles for `wasm32-wasip1` and runs in
+//! serverless and wasm runtimes that cannot execute native binaries.
+//!
+//! The vault is read from `VX_VAULT_PATH` (or `~/.vaultx`) and unlocked
+//! with the password in `VX_PASSWORD`, or piped in with `--password-stdin`.
+//! Nothing is written back, so reads are not recorded in the access log.
+
+mod commands;
+mod error;
+mod vault;
+
+use clap::{Parser, Subcommand};
+use error::Error;
+
+#[derive(Parser)]
+#[command(name = "vx-wasi")]
+#[command(
+    version,
+    about = "VaultX for WASI runtimes: read secrets without a terminal"
+)]
+#[command(propagate_version = true)]
+struct Cli {
+    /// Read the master password from the first line of stdin instead of
+    /// VX_PASSWORD
+    #[arg(long, global = true)]
+    password_stdin: bool,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the value of a secret
+    Get {
+        /// Project name
+        project: String,
+
+        /// Secret key
+        key: String,
+
+        /// Environment to read from instead of the default one
+        #[arg(long)]
+        env: Option<String>,
+    },
+
+    /// Print a project's secrets as KEY="value" lines
+    Export {
+        /// Project name
+        project: String,
+
+        /// Environment to export instead of the default one
+        #[arg(long)]
+        env: Option<String>,
+    },
+
+    /// Print a security audit of the vault as JSON
+    Audit,
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Get { project, key, env } => {
+            commands::get(&project, &key, env.as_deref(), cli.password_stdin)
+        }
+        Commands::Export { project, env } => {
+            commands::export(&project, env.as_deref(), cli.password_stdin)
+        }
+        Commands::Audit => commands::audit(cli.password_stdin),
+    }
+}
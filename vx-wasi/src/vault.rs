@@ -0,0 +1,78 @@
+//! Finding and unlocking the vault without a terminal.
+
+use crate::error::Error;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::PathBuf;
+use vx_core::{vault, SecretBytes, Vault, VaultError, KEY_SIZE};
+use zeroize::Zeroizing;
+
+/// Vault directory under the home directory, as used by `vx`
+const VAULT_DIR: &str = ".vaultx";
+
+/// Vault file name
+const VAULT_FILE: &str = "vault.vx";
+
+/// Environment variable naming the vault directory
+const VAULT_PATH_ENV: &str = "VX_VAULT_PATH";
+
+/// Environment variable holding the master password
+const PASSWORD_ENV: &str = "VX_PASSWORD";
+
+/// Returns the path of the vault file: `vault.vx` in `VX_VAULT_PATH`, or
+/// in `~/.vaultx`. The runtime must give the module access to the
+/// directory (e.g. `wasmtime --dir`).
+fn vault_path() -> Result<PathBuf, Error> {
+    let dir = match env::var_os(VAULT_PATH_ENV).filter(|path| !path.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(env::var_os("HOME").ok_or(Error::NoVaultDir)?).join(VAULT_DIR),
+    };
+    Ok(dir.join(VAULT_FILE))
+}
+
+/// Reads the master password from the first line of stdin with
+/// `from_stdin`, otherwise from `VX_PASSWORD`.
+fn read_password(from_stdin: bool) -> Result<Zeroizing<String>, Error> {
+    if from_stdin {
+        let mut line = Zeroizing::new(String::new());
+        io::stdin().lock().read_line(&mut line)?;
+        let len = line.trim_end_matches(['\r', '\n']).len();
+        line.truncate(len);
+        return Ok(line);
+    }
+    env::var(PASSWORD_ENV)
+        .map(Zeroizing::new)
+        .map_err(|_| Error::NoPassword)
+}
+
+/// Unlocks the vault, leaving its projects sealed.
+pub fn open(password_stdin: bool) -> Result<(Vault, Zeroizing<[u8; KEY_SIZE]>), Error> {
+    let path = vault_path()?;
+    let data = fs::read(&path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => Error::VaultNotFound(path.display().to_string()),
+        _ => Error::Io(e),
+    })?;
+    vault::verify_file(&data)?;
+
+    let password = read_password(password_stdin)?;
+    Ok(vault::unlock_vault(&data, password.as_bytes())?)
+}
+
+/// Reads a secret, unsealing the projects its aliases and template
+/// placeholders lead to. Projects protected by a passphrase cannot be
+/// read, as there is no one to ask for it.
+pub fn read_secret(
+    vault: &mut Vault,
+    project: &str,
+    env: Option<&str>,
+    name: &str,
+    key: &[u8; KEY_SIZE],
+) -> Result<SecretBytes, Error> {
+    loop {
+        match vault.get_secret_in(project, env, name, key) {
+            Err(VaultError::ProjectSealed(other)) => vault.unseal_project(&other, key)?,
+            result => return Ok(result?),
+        }
+    }
+}